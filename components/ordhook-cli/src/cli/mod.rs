@@ -1,6 +1,15 @@
+use crate::cli::output::{
+    confirm, print_error, print_output, print_output_line, BenchOutput, BlocksDbCheckOutput,
+    BlocksDroppedOutput, ChainStatusOutput, CompletedOutput, ConfigValidationOutput,
+    DeadLettersOutput, FileWrittenOutput, InscriptionOutput, OutputFormat, RedeliveryOutput,
+    RenumberOutput, ReplayOutput, SatTraceOutput, SatTraceStep, ScannedBlock,
+    ScannedBrc20Operation, ScannedInscription, ScannedTransfers,
+};
 use crate::config::file::ConfigFile;
 use crate::config::generator::generate_config;
-use clap::{Parser, Subcommand};
+use crate::config::secrets::resolve_secret;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{generate, Shell};
 use hiro_system_kit;
 use ordhook::chainhook_sdk::chainhooks::types::{
     BitcoinChainhookSpecification, HttpHook, InscriptionFeedData, OrdinalsMetaProtocol,
@@ -9,51 +18,84 @@ use ordhook::chainhook_sdk::chainhooks::types::{
     BitcoinPredicateType, ChainhookFullSpecification, HookAction, OrdinalOperations,
 };
 use ordhook::chainhook_sdk::indexer::bitcoin::{
-    build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
+    download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
 };
 use ordhook::chainhook_sdk::observer::BitcoinConfig;
-use ordhook::chainhook_sdk::types::{BitcoinBlockData, TransactionIdentifier};
+use ordhook::chainhook_sdk::types::{BitcoinBlockData, BitcoinNetwork, TransactionIdentifier};
 use ordhook::chainhook_sdk::utils::BlockHeights;
 use ordhook::chainhook_sdk::utils::Context;
-use ordhook::config::Config;
+use ordhook::config::validation::{validate_config, ValidationStage};
+use ordhook::config::{
+    Config, PredicatesApi, ReplayLogConfig, DEFAULT_CONTROL_PORT, DEFAULT_REPLAY_LOG_MAX_SIZE_MB,
+};
 use ordhook::core::meta_protocols::brc20::db::get_brc20_operations_on_block;
 use ordhook::core::pipeline::bitcoind_download_blocks;
 use ordhook::core::pipeline::processors::block_archiving::start_block_archiving_processor;
 use ordhook::core::pipeline::processors::start_inscription_indexing_processor;
+use ordhook::core::protocol::inscription_content::write_inscription_contents_archive;
 use ordhook::core::protocol::inscription_parsing::parse_inscriptions_and_standardize_block;
 use ordhook::core::protocol::satoshi_numbering::compute_satoshi_number;
 use ordhook::core::{first_inscription_height, new_traversals_lazy_cache};
+use ordhook::db::backup::{backup_all_dbs, get_default_backup_path};
 use ordhook::db::blocks::{
     find_block_bytes_at_block_height, find_last_block_inserted, find_missing_blocks,
     open_blocks_db_with_retry, open_readonly_blocks_db,
 };
+use ordhook::db::chain_status::get_chain_status;
 use ordhook::db::cursor::BlockBytesCursor;
 use ordhook::db::ordinals::{
     find_all_inscriptions_in_block, find_all_transfers_in_block, find_inscription_with_id,
     find_latest_inscription_block_height, get_default_ordinals_db_file_path, open_ordinals_db,
+    open_ordinals_db_rw,
+};
+use ordhook::db::recovery::recover_working_dirs;
+use ordhook::db::renumbering::{
+    complete_pending_renumbering, compute_inscription_number_corrections,
+    find_inscription_numbers_from_block_height, find_inscription_numbers_in_block,
+    find_pending_renumbering, insert_pending_renumbering, PendingRenumbering,
+};
+use ordhook::db::{
+    adopt_dbs_network, check_dbs_network, drop_block_data_from_all_dbs, initialize_sqlite_dbs,
+    open_all_dbs_rw,
 };
-use ordhook::db::{drop_block_data_from_all_dbs, initialize_sqlite_dbs, open_all_dbs_rw};
 use ordhook::download::download_archive_datasets_if_required;
+use ordhook::error::OrdhookError;
 use ordhook::scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate;
+use ordhook::service::amendments::{
+    amendments_from_number_corrections, deliver_amendment_events, AmendmentReason,
+};
+use ordhook::service::dead_letters::{get_dead_letters, redeliver_dead_letters};
 use ordhook::service::observers::initialize_observers_db;
+use ordhook::service::status::ServiceStatus;
 use ordhook::service::{start_observer_forwarding, Service};
-use ordhook::utils::bitcoind::bitcoind_get_block_height;
+use ordhook::utils::bench::{run_bench_workload, BenchWorkload};
+use ordhook::utils::bitcoind::{
+    bitcoind_check_blocks_available, bitcoind_get_block_height, build_bitcoind_http_client,
+};
+use ordhook::utils::logger::{with_runtime_log_levels, LogLevels};
 use ordhook::utils::monitoring::PrometheusMonitoring;
 use ordhook::{hex, try_error, try_info, try_warn};
 use reqwest::Client as HttpClient;
+use serde_json::json;
 use std::collections::HashSet;
 use std::io::{BufReader, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{process, u64};
 
+mod output;
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Opts {
     #[clap(subcommand)]
     command: Command,
+    /// Print results as text, or as JSON
+    #[clap(long = "output", arg_enum, global = true, default_value = "text")]
+    output: OutputFormat,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -70,6 +112,111 @@ enum Command {
     /// Perform maintenance operations on local databases
     #[clap(subcommand)]
     Db(OrdhookDbCommand),
+    /// Inspect ordhook's view of the Bitcoin chain
+    #[clap(subcommand)]
+    Chain(ChainCommand),
+    /// Manage the deliveries of the registered predicates
+    #[clap(subcommand)]
+    Observers(ObserversCommand),
+    /// Run standardized workloads and print scores comparable between releases and machines
+    #[clap(name = "bench", bin_name = "bench")]
+    Bench(BenchCommand),
+    /// Print the completion script of a shell (bash, zsh, fish, elvish or powershell)
+    #[clap(name = "completions", bin_name = "completions")]
+    Completions(CompletionsCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct CompletionsCommand {
+    #[clap(arg_enum)]
+    pub shell: Shell,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct BenchCommand {
+    /// Workloads to run (--workloads parse,sequence,write)
+    #[clap(long = "workloads", default_value = "parse,sequence,write")]
+    pub workloads: String,
+    /// Number of blocks processed by every workload
+    #[clap(long = "blocks", default_value = "100")]
+    pub blocks: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum ChainCommand {
+    /// Compare ordhook's tip and recent blocks with bitcoind's main chain
+    #[clap(name = "status", bin_name = "status")]
+    Status(ChainStatusCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ChainStatusCommand {
+    /// Number of recent blocks compared with bitcoind
+    #[clap(long = "depth", default_value = "100")]
+    pub depth: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum ObserversCommand {
+    /// Inspect and redeliver the occurrences webhooks kept rejecting
+    #[clap(subcommand)]
+    DeadLetter(DeadLetterCommand),
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum DeadLetterCommand {
+    /// List the occurrences in the dead-letter queue
+    #[clap(name = "list", bin_name = "list")]
+    List(DeadLetterSelectionCommand),
+    /// Export the occurrences in the dead-letter queue, payloads included, as JSON lines
+    #[clap(name = "export", bin_name = "export")]
+    Export(ExportDeadLettersCommand),
+    /// Deliver the occurrences in the dead-letter queue again, removing the ones delivered
+    #[clap(name = "redeliver", bin_name = "redeliver")]
+    Redeliver(DeadLetterSelectionCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DeadLetterSelectionCommand {
+    /// Only select the occurrences of a predicate
+    #[clap(long = "predicate")]
+    pub predicate_uuid: Option<String>,
+    /// Only select the occurrences with these ids (--ids 1,2,3)
+    #[clap(long = "ids")]
+    pub ids: Option<String>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+impl DeadLetterSelectionCommand {
+    pub fn get_ids(&self) -> Result<Vec<u64>, String> {
+        let Some(ref ids) = self.ids else {
+            return Ok(vec![]);
+        };
+        ids.split(',')
+            .map(|id| {
+                id.trim()
+                    .parse::<u64>()
+                    .map_err(|e| format!("invalid dead letter id {id}: {e}"))
+            })
+            .collect()
+    }
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportDeadLettersCommand {
+    #[clap(flatten)]
+    pub selection: DeadLetterSelectionCommand,
+    /// Destination file (defaults to stdout)
+    #[clap(long = "output-file")]
+    pub output_file: Option<String>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -129,8 +276,11 @@ struct ScanBlocksCommand {
     #[clap(long = "post-to")]
     pub post_to: Option<String>,
     /// HTTP Auth token
-    #[clap(long = "auth-token")]
+    #[clap(long = "auth-token", conflicts_with = "auth-token-file")]
     pub auth_token: Option<String>,
+    /// Read the HTTP Auth token from a file
+    #[clap(long = "auth-token-file", conflicts_with = "auth-token")]
+    pub auth_token_file: Option<String>,
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -278,10 +428,28 @@ enum ConfigCommand {
     /// Generate new config
     #[clap(name = "new", bin_name = "new", aliases = &["generate"])]
     New(NewConfig),
+    /// Check bitcoind connectivity, ports, disk space, ulimit and snapshot urls
+    #[clap(name = "validate", bin_name = "validate")]
+    Validate(ValidateConfigCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ValidateConfigCommand {
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
 struct NewConfig {
+    /// Target network (mainnet, testnet, testnet4, signet or regtest)
+    #[clap(
+        long = "network",
+        conflicts_with = "regtest",
+        conflicts_with = "testnet",
+        conflicts_with = "mainnet"
+    )]
+    pub network: Option<String>,
     /// Target Regtest network
     #[clap(
         long = "regtest",
@@ -310,6 +478,22 @@ enum ServiceCommand {
     /// Start chainhook-cli
     #[clap(name = "start", bin_name = "start")]
     Start(StartCommand),
+    /// Print the sync height, predicates, jobs and resource usage of a running node
+    #[clap(name = "status", bin_name = "status")]
+    Status(ServiceStatusCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ServiceStatusCommand {
+    /// Base URL of the control API of the node (defaults to the http_api address of the config, or localhost)
+    #[clap(long = "url")]
+    pub url: Option<String>,
+    /// Admin API key, required when the node is shared by tenants
+    #[clap(long = "api-key")]
+    pub api_key: Option<String>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -350,8 +534,11 @@ struct StartCommand {
     #[clap(long = "start-at-block")]
     pub start_at_block: Option<u64>,
     /// HTTP Auth token
-    #[clap(long = "auth-token")]
+    #[clap(long = "auth-token", conflicts_with = "auth-token-file")]
     pub auth_token: Option<String>,
+    /// Read the HTTP Auth token from a file
+    #[clap(long = "auth-token-file", conflicts_with = "auth-token")]
+    pub auth_token_file: Option<String>,
     /// Check blocks integrity
     #[clap(long = "check-blocks-integrity")]
     pub block_integrity_check: bool,
@@ -371,12 +558,27 @@ enum OrdhookDbCommand {
     /// Rebuild inscriptions entries for a given block
     #[clap(name = "drop", bin_name = "drop")]
     Drop(DropOrdhookDbCommand),
+    /// Recompute inscription numbers and classifications from a block height, after a fix of the numbering rules
+    #[clap(name = "renumber", bin_name = "renumber")]
+    Renumber(RenumberOrdhookDbCommand),
     /// Check integrity
     #[clap(name = "check", bin_name = "check")]
     Check(CheckDbCommand),
     /// Db maintenance related commands
     #[clap(subcommand)]
     Repair(RepairCommand),
+    /// Replay the block payloads recorded in the replay log through the pipeline, to reproduce an issue
+    #[clap(name = "replay", bin_name = "replay")]
+    Replay(ReplayOrdhookDbCommand),
+    /// Take a consistent copy of all databases, safe to run while indexing
+    #[clap(name = "backup", bin_name = "backup")]
+    Backup(BackupOrdhookDbCommand),
+    /// Export the contents of a list of inscriptions as a tar archive, with a manifest
+    #[clap(name = "export-contents", bin_name = "export-contents")]
+    ExportContents(ExportContentsCommand),
+    /// Record the network configured as the one of databases indexed before networks were recorded
+    #[clap(name = "adopt-network", bin_name = "adopt-network")]
+    AdoptNetwork(SyncOrdhookDbCommand),
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -491,6 +693,53 @@ struct DropOrdhookDbCommand {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct RenumberOrdhookDbCommand {
+    /// First block re-indexed
+    #[clap(long = "from-height")]
+    pub from_height: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ReplayOrdhookDbCommand {
+    /// First entry of the replay log replayed
+    #[clap(long = "from", default_value = "0")]
+    pub from: u64,
+    /// Last entry of the replay log replayed
+    #[clap(long = "to")]
+    pub to: Option<u64>,
+    /// Replay log directory, e.g. a copy of the log of another instance (defaults to replay_log.path)
+    #[clap(long = "replay-log")]
+    pub replay_log: Option<String>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct BackupOrdhookDbCommand {
+    /// Destination directory (defaults to <working_dir>/backups/<timestamp>)
+    pub destination: Option<String>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportContentsCommand {
+    /// File listing the inscription ids or numbers to export, one per line
+    pub inscriptions_file: String,
+    /// Destination of the tar archive
+    #[clap(long = "output-file", default_value = "contents.tar")]
+    pub output_file: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 #[derive(Parser, PartialEq, Clone, Debug)]
 struct PatchOrdhookDbCommand {
     /// Load config file path
@@ -517,7 +766,9 @@ struct CheckDbCommand {
 }
 
 pub fn main() {
-    let logger = hiro_system_kit::log::setup_logger();
+    // Verbosity can then be adjusted per subsystem with PUT /ordhook/v1/control/log_level.
+    let log_levels = LogLevels::new();
+    let logger = with_runtime_log_levels(hiro_system_kit::log::setup_logger(), &log_levels);
     let _guard = hiro_system_kit::log::setup_global_logger(logger.clone());
     let ctx = Context {
         logger: Some(logger),
@@ -526,20 +777,41 @@ pub fn main() {
 
     let opts: Opts = match Opts::try_parse() {
         Ok(opts) => opts,
+        // Help and version go to stdout, usage errors to stderr.
+        Err(e) if !e.use_stderr() => e.exit(),
         Err(e) => {
-            println!("{}", e);
+            match requested_output_format() {
+                OutputFormat::Json => print_error(&e.to_string(), OutputFormat::Json),
+                OutputFormat::Text => eprintln!("{}", e),
+            }
             process::exit(1);
         }
     };
 
-    if let Err(e) = hiro_system_kit::nestable_block_on(handle_command(opts, &ctx)) {
+    let output = opts.output;
+    if let Err(e) = hiro_system_kit::nestable_block_on(handle_command(opts, log_levels, &ctx)) {
+        print_error(&e, output);
         error!(ctx.expect_logger(), "{e}");
         std::thread::sleep(std::time::Duration::from_millis(500));
         process::exit(1);
     }
 }
 
-async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
+/// `--output` of the command line, read without clap when the command line can't be parsed.
+fn requested_output_format() -> OutputFormat {
+    let args = std::env::args().collect::<Vec<_>>();
+    let json = args.iter().enumerate().any(|(i, arg)| {
+        arg == "--output=json"
+            || (arg == "--output" && args.get(i + 1).map(String::as_str) == Some("json"))
+    });
+    match json {
+        true => OutputFormat::Json,
+        false => OutputFormat::Text,
+    }
+}
+
+async fn handle_command(opts: Opts, log_levels: LogLevels, ctx: &Context) -> Result<(), String> {
+    let output = opts.output;
     match opts.command {
         Command::Scan(ScanCommand::Blocks(cmd)) => {
             let config: Config = ConfigFile::default(
@@ -549,6 +821,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 &cmd.config_path,
                 &cmd.meta_protocols,
             )?;
+            recover_working_dirs(&config, ctx)?;
             // Download dataset if required
             // If console:
             // - Replay based on SQLite queries
@@ -577,7 +850,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     post_to,
                     Some(&block_heights),
                     None,
-                    cmd.auth_token,
+                    resolve_secret("auth_token", cmd.auth_token, cmd.auth_token_file)?,
                     false,
                 )?;
 
@@ -590,6 +863,12 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     ctx,
                 )
                 .await?;
+                print_output(
+                    &CompletedOutput {
+                        command: "scan blocks".into(),
+                    },
+                    output,
+                );
             } else {
                 download_archive_datasets_if_required(&config, ctx).await;
                 let mut total_inscriptions = 0;
@@ -605,59 +884,62 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     let locations =
                         find_all_transfers_in_block(&block_height, &db_connections.ordinals, ctx);
 
-                    let mut total_transfers_in_block = 0;
-
-                    for (_, inscription) in inscriptions.iter() {
-                        println!("Inscription {} revealed at block #{} (inscription_number {}, ordinal_number {})", inscription.get_inscription_id(), block_height, inscription.inscription_number.jubilee, inscription.ordinal_number);
-                        if let Some(transfers) = locations.get(&inscription.ordinal_number) {
-                            for t in transfers.iter().skip(1) {
-                                total_transfers_in_block += 1;
-                                println!(
-                                    "\t→ Transferred in transaction {}",
-                                    t.transaction_identifier_location.hash
-                                );
-                            }
-                        }
-                    }
-                    for (inscription_id, transfers) in locations.iter() {
-                        println!("Inscription {}", inscription_id);
-                        for t in transfers.iter() {
-                            total_transfers_in_block += 1;
-                            println!(
-                                "\t→ Transferred in transaction {}",
-                                t.transaction_identifier_location.hash
-                            );
-                        }
-                    }
-                    match db_connections.brc20 {
-                        Some(ref conn) => {
-                            let activity = get_brc20_operations_on_block(block_height, &conn, ctx);
-                            for (_, row) in activity.iter() {
-                                if row.operation == "transfer_receive" {
-                                    continue;
-                                }
-                                println!(
-                                    "BRC-20 {} {} {}",
-                                    row.operation, row.tick, row.avail_balance
-                                );
-                            }
-                        }
-                        None => todo!(),
-                    }
-                    if total_transfers_in_block > 0 && !inscriptions.is_empty() {
-                        println!(
-                            "Inscriptions revealed: {}, inscriptions transferred: {total_transfers_in_block}",
-                            inscriptions.len()
-                        );
-                        println!("-----");
-                    }
-
-                    total_inscriptions += inscriptions.len();
-                    total_transfers += total_transfers_in_block;
+                    let inscriptions = inscriptions
+                        .values()
+                        .map(|inscription| ScannedInscription {
+                            inscription: InscriptionOutput {
+                                inscription_id: inscription.get_inscription_id(),
+                                block_height,
+                                inscription_number: inscription.inscription_number.jubilee,
+                                ordinal_number: inscription.ordinal_number,
+                            },
+                            transfers: locations
+                                .get(&inscription.ordinal_number)
+                                .map(|transfers| {
+                                    transfers
+                                        .iter()
+                                        .skip(1)
+                                        .map(|t| t.transaction_identifier_location.hash.clone())
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        })
+                        .collect();
+                    let transfers = locations
+                        .iter()
+                        .map(|(ordinal_number, transfers)| ScannedTransfers {
+                            ordinal_number: *ordinal_number,
+                            transactions: transfers
+                                .iter()
+                                .map(|t| t.transaction_identifier_location.hash.clone())
+                                .collect(),
+                        })
+                        .collect();
+                    let brc20_operations = match db_connections.brc20 {
+                        Some(ref conn) => get_brc20_operations_on_block(block_height, &conn, ctx)
+                            .into_values()
+                            .filter(|row| row.operation != "transfer_receive")
+                            .map(|row| ScannedBrc20Operation {
+                                operation: row.operation,
+                                tick: row.tick,
+                                avail_balance: row.avail_balance,
+                            })
+                            .collect(),
+                        None => vec![],
+                    };
+                    let block = ScannedBlock {
+                        block_height,
+                        inscriptions,
+                        transfers,
+                        brc20_operations,
+                    };
+                    total_inscriptions += block.inscriptions.len();
+                    total_transfers += block.transfers_count();
+                    print_output_line(&block, output);
                 }
                 if total_transfers == 0 && total_inscriptions == 0 {
                     let db_file_path =
-                        get_default_ordinals_db_file_path(&config.expected_cache_path());
+                        get_default_ordinals_db_file_path(&config.expected_sqlite_path());
                     try_warn!(ctx, "No data available. Check the validity of the range being scanned and the validity of your local database {}", db_file_path.display());
                 }
             }
@@ -670,10 +952,11 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 &cmd.config_path,
                 &None,
             )?;
+            recover_working_dirs(&config, ctx)?;
 
             let _ = download_archive_datasets_if_required(&config, ctx).await;
 
-            let inscriptions_db_conn = open_ordinals_db(&config.expected_cache_path(), ctx)?;
+            let inscriptions_db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
             let (inscription, block_height) =
                 match find_inscription_with_id(&cmd.inscription_id, &inscriptions_db_conn, ctx)? {
                     Some(entry) => entry,
@@ -684,12 +967,14 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                         ));
                     }
                 };
-            println!(
-                "Inscription {} revealed at block #{} (inscription_number {}, ordinal_number {})",
-                inscription.get_inscription_id(),
-                block_height,
-                inscription.inscription_number.jubilee,
-                inscription.ordinal_number
+            print_output(
+                &InscriptionOutput {
+                    inscription_id: inscription.get_inscription_id(),
+                    block_height,
+                    inscription_number: inscription.inscription_number.jubilee,
+                    ordinal_number: inscription.ordinal_number,
+                },
+                output,
             );
         }
         Command::Scan(ScanCommand::Transaction(cmd)) => {
@@ -700,7 +985,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 &cmd.config_path,
                 &None,
             )?;
-            let http_client = build_http_client();
+            let http_client = build_bitcoind_http_client(&config)?;
             let block = fetch_and_standardize_block(
                 &http_client,
                 cmd.block_height,
@@ -720,10 +1005,23 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 ctx,
             )?;
             back_trace.reverse();
-            for (block_height, tx, index) in back_trace.iter() {
-                println!("{}\t{}:{}", block_height, hex::encode(tx), index);
-            }
-            println!("{:?}", res);
+            print_output(
+                &SatTraceOutput {
+                    ordinal_number: res.ordinal_number,
+                    ordinal_block_height: res.get_ordinal_coinbase_height(),
+                    ordinal_offset: res.get_ordinal_coinbase_offset(),
+                    transfers: res.transfers,
+                    back_trace: back_trace
+                        .iter()
+                        .map(|(block_height, tx, index)| SatTraceStep {
+                            block_height: *block_height,
+                            txid_prefix: hex::encode(tx),
+                            input_index: *index,
+                        })
+                        .collect(),
+                },
+                output,
+            );
         }
         Command::Service(subcmd) => match subcmd {
             ServiceCommand::Start(cmd) => {
@@ -741,6 +1039,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     &cmd.config_path,
                     &None,
                 )?;
+                recover_working_dirs(&config, ctx)?;
                 let db_connections = initialize_sqlite_dbs(&config, ctx);
 
                 let last_known_block =
@@ -764,6 +1063,11 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     },
                 };
 
+                let auth_token = resolve_secret(
+                    "auth_token",
+                    cmd.auth_token.clone(),
+                    cmd.auth_token_file.clone(),
+                )?;
                 let mut predicates = vec![];
                 for post_to in cmd.post_to.iter() {
                     let predicate = build_predicate_from_cli(
@@ -771,50 +1075,146 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                         post_to,
                         None,
                         Some(start_block),
-                        cmd.auth_token.clone(),
+                        auth_token.clone(),
                         true,
                     )?;
                     predicates.push(predicate);
                 }
 
-                let mut service = Service::new(config, ctx.clone());
-                return service
+                let bitcoind_rpc_url = config.network.bitcoind_rpc_url.clone();
+                let mut service = Service::new(config, ctx.clone()).with_log_levels(log_levels);
+                return match service
                     .run(
                         predicates,
                         None,
                         cmd.block_integrity_check,
                         cmd.stream_indexing_to_observers,
                     )
-                    .await;
+                    .await
+                {
+                    Err(OrdhookError::Rpc(e)) => Err(format!(
+                        "{e} (is bitcoind reachable at {bitcoind_rpc_url}?)"
+                    )),
+                    res => res.map_err(|e| e.into()),
+                };
+            }
+            ServiceCommand::Status(cmd) => {
+                let url = match cmd.url {
+                    Some(url) => url,
+                    None => get_control_api_url(&cmd.config_path)?,
+                };
+                let mut request = HttpClient::new()
+                    .get(format!(
+                        "{}/ordhook/v1/control/status",
+                        url.trim_end_matches('/')
+                    ))
+                    .header("Accept", "application/json");
+                if let Some(api_key) = cmd.api_key {
+                    request = request.bearer_auth(api_key);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| format!("unable to reach the control API at {url}: {e}"))?;
+                let status_code = response.status();
+                let body: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| format!("unable to parse the response of {url}: {e}"))?;
+                if !status_code.is_success() {
+                    return Err(format!(
+                        "control API responded with {status_code}: {}",
+                        body["error"].as_str().unwrap_or("unknown error")
+                    ));
+                }
+                let status: ServiceStatus = serde_json::from_value(body["result"].clone())
+                    .map_err(|e| format!("unable to parse the status of {url}: {e}"))?;
+                print_output(&status, output);
             }
         },
         Command::Config(subcmd) => match subcmd {
             ConfigCommand::New(cmd) => {
                 use std::fs::File;
                 use std::io::Write;
-                let config =
-                    ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &None, &None)?;
-                let config_content = generate_config(&config.network.bitcoin_network);
+                let config = match cmd.network.as_deref() {
+                    Some("testnet4") => Config::testnet4_default(),
+                    Some(network) => {
+                        let network = match network {
+                            "mainnet" => BitcoinNetwork::Mainnet,
+                            "testnet" => BitcoinNetwork::Testnet,
+                            "signet" => BitcoinNetwork::Signet,
+                            "regtest" | "devnet" => BitcoinNetwork::Regtest,
+                            _ => return Err(format!("network {network} not supported")),
+                        };
+                        Config::default_for_network(&network)
+                    }
+                    None => {
+                        ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &None, &None)?
+                    }
+                };
+                let config_content = generate_config(&config);
                 let mut file_path = PathBuf::new();
                 file_path.push("Ordhook.toml");
                 let mut file = File::create(&file_path)
                     .map_err(|e| format!("unable to open file {}\n{}", file_path.display(), e))?;
                 file.write_all(config_content.as_bytes())
                     .map_err(|e| format!("unable to write file {}\n{}", file_path.display(), e))?;
-                println!("Created file Ordhook.toml");
+                print_output(
+                    &FileWrittenOutput {
+                        path: file_path.display().to_string(),
+                        entries: None,
+                        description: "Created file Ordhook.toml".into(),
+                    },
+                    output,
+                );
+            }
+            ConfigCommand::Validate(cmd) => {
+                let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+                let validation = ConfigValidationOutput {
+                    diagnostics: validate_config(&config, ValidationStage::Command, ctx).await,
+                };
+                print_output(&validation, output);
+                let errors = validation.errors();
+                if errors > 0 {
+                    return Err(format!("{errors} configuration errors found"));
+                }
             }
         },
         Command::Db(OrdhookDbCommand::New(cmd)) => {
             let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
             // Create DB
             initialize_sqlite_dbs(&config, ctx);
+            check_dbs_network(&config, ctx)?;
             open_blocks_db_with_retry(true, &config, ctx);
+            print_output(
+                &CompletedOutput {
+                    command: "db new".into(),
+                },
+                output,
+            );
+        }
+        Command::Db(OrdhookDbCommand::AdoptNetwork(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            adopt_dbs_network(&config, ctx)?;
+            print_output(
+                &CompletedOutput {
+                    command: "db adopt-network".into(),
+                },
+                output,
+            );
         }
         Command::Db(OrdhookDbCommand::Sync(cmd)) => {
             let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
             initialize_sqlite_dbs(&config, ctx);
+            check_dbs_network(&config, ctx)?;
             let service = Service::new(config, ctx.clone());
             service.catch_up_to_bitcoin_chain_tip(None).await?;
+            print_output(
+                &CompletedOutput {
+                    command: "db sync".into(),
+                },
+                output,
+            );
         }
         Command::Db(OrdhookDbCommand::Repair(subcmd)) => match subcmd {
             RepairCommand::Blocks(cmd) => {
@@ -837,9 +1237,10 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 if let Some(true) = cmd.debug {
                     let blocks_db = open_blocks_db_with_retry(false, &config, ctx);
                     for i in cmd.get_blocks().into_iter() {
-                        let block_bytes =
-                            find_block_bytes_at_block_height(i as u32, 10, &blocks_db, ctx)
-                                .expect("unable to retrieve block {i}");
+                        let block_bytes = find_block_bytes_at_block_height(
+                            i as u32, 10, &blocks_db, &config, ctx,
+                        )
+                        .expect("unable to retrieve block {i}");
                         let block = BlockBytesCursor::new(&block_bytes);
                         info!(ctx.expect_logger(), "--------------------");
                         info!(ctx.expect_logger(), "Block: {i}");
@@ -848,6 +1249,12 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                         }
                     }
                 }
+                print_output(
+                    &CompletedOutput {
+                        command: "db repair blocks".into(),
+                    },
+                    output,
+                );
             }
             RepairCommand::Inscriptions(cmd) => {
                 let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
@@ -856,13 +1263,29 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 }
                 let block_post_processor = match cmd.repair_observers {
                     Some(true) => {
-                        let tx_replayer =
-                            start_observer_forwarding(&config.get_event_observer_config(), ctx);
+                        let tx_replayer = start_observer_forwarding(
+                            &config.get_event_observer_config(),
+                            &config,
+                            ctx,
+                        );
                         Some(tx_replayer)
                     }
                     _ => None,
                 };
                 let blocks = cmd.get_blocks();
+                // Numbers are compared before and after the repair, to amend the ones already delivered.
+                let old_numbers = match config.amendments {
+                    Some(_) => {
+                        let db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
+                        blocks
+                            .iter()
+                            .flat_map(|block_height| {
+                                find_inscription_numbers_in_block(*block_height, &db_conn, ctx)
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                    None => vec![],
+                };
                 let inscription_indexing_processor = start_inscription_indexing_processor(
                     &config,
                     ctx,
@@ -872,20 +1295,45 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
 
                 bitcoind_download_blocks(
                     &config,
-                    blocks,
+                    blocks.clone(),
                     first_inscription_height(&config),
                     &inscription_indexing_processor,
                     10_000,
                     ctx,
                 )
                 .await?;
+                if config.amendments.is_some() {
+                    let db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
+                    let new_numbers = blocks
+                        .iter()
+                        .flat_map(|block_height| {
+                            find_inscription_numbers_in_block(*block_height, &db_conn, ctx)
+                        })
+                        .collect::<Vec<_>>();
+                    let corrections =
+                        compute_inscription_number_corrections(&old_numbers, &new_numbers);
+                    deliver_amendment_events(
+                        &amendments_from_number_corrections(AmendmentReason::Repair, &corrections),
+                        &config,
+                        ctx,
+                    );
+                }
+                print_output(
+                    &CompletedOutput {
+                        command: "db repair inscriptions".into(),
+                    },
+                    output,
+                );
             }
             RepairCommand::Transfers(cmd) => {
                 let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
                 let block_post_processor = match cmd.repair_observers {
                     Some(true) => {
-                        let tx_replayer =
-                            start_observer_forwarding(&config.get_event_observer_config(), ctx);
+                        let tx_replayer = start_observer_forwarding(
+                            &config.get_event_observer_config(),
+                            &config,
+                            ctx,
+                        );
                         Some(tx_replayer)
                     }
                     _ => None,
@@ -902,6 +1350,12 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                         .replay_transfers(vec![block], block_post_processor.clone())
                         .await?;
                 }
+                print_output(
+                    &CompletedOutput {
+                        command: "db repair transfers".into(),
+                    },
+                    output,
+                );
             }
         },
         Command::Db(OrdhookDbCommand::Check(cmd)) => {
@@ -909,21 +1363,155 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             {
                 let blocks_db = open_readonly_blocks_db(&config, ctx)?;
                 let tip = find_last_block_inserted(&blocks_db);
-                println!("Tip: {}", tip);
-                let missing_blocks = find_missing_blocks(&blocks_db, 1, tip, ctx);
-                println!("{:?}", missing_blocks);
+                let missing_blocks = find_missing_blocks(&blocks_db, 1, tip, &config, ctx);
+                print_output(
+                    &BlocksDbCheckOutput {
+                        tip,
+                        missing_blocks,
+                    },
+                    output,
+                );
+            }
+        }
+        Command::Observers(ObserversCommand::DeadLetter(DeadLetterCommand::List(cmd))) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let dead_letters =
+                get_dead_letters(cmd.predicate_uuid.as_deref(), &cmd.get_ids()?, &config, ctx);
+            print_output(
+                &DeadLettersOutput {
+                    dead_letters: dead_letters.iter().map(|d| d.to_json()).collect(),
+                },
+                output,
+            );
+        }
+        Command::Observers(ObserversCommand::DeadLetter(DeadLetterCommand::Export(cmd))) => {
+            let selection = cmd.selection;
+            let config = ConfigFile::default(false, false, false, &selection.config_path, &None)?;
+            let dead_letters = get_dead_letters(
+                selection.predicate_uuid.as_deref(),
+                &selection.get_ids()?,
+                &config,
+                ctx,
+            );
+            let mut lines = String::new();
+            for dead_letter in dead_letters.iter() {
+                let mut entry = dead_letter.to_json();
+                entry["payload"] = dead_letter.payload.clone();
+                lines.push_str(&format!("{entry}\n"));
+            }
+            match cmd.output_file {
+                Some(path) => {
+                    std::fs::write(&path, lines)
+                        .map_err(|e| format!("unable to write {path}: {e}"))?;
+                    print_output(
+                        &FileWrittenOutput {
+                            description: format!(
+                                "{} dead letters written to {path}",
+                                dead_letters.len()
+                            ),
+                            path,
+                            entries: Some(dead_letters.len()),
+                        },
+                        output,
+                    );
+                }
+                // Exported dead letters are already printed as JSON lines.
+                None => print!("{lines}"),
+            }
+        }
+        Command::Observers(ObserversCommand::DeadLetter(DeadLetterCommand::Redeliver(cmd))) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let dead_letters =
+                get_dead_letters(cmd.predicate_uuid.as_deref(), &cmd.get_ids()?, &config, ctx);
+            let (delivered, failed) = redeliver_dead_letters(dead_letters, &config, ctx).await?;
+            print_output(&RedeliveryOutput { delivered, failed }, output);
+        }
+        Command::Chain(ChainCommand::Status(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let report = get_chain_status(&config, cmd.depth, ctx)?;
+            print_output(
+                &ChainStatusOutput {
+                    report,
+                    depth: cmd.depth,
+                },
+                output,
+            );
+        }
+        Command::Bench(cmd) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let workloads = cmd
+                .workloads
+                .split(',')
+                .map(|w| BenchWorkload::parse(w.trim()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut scores = vec![];
+            for workload in workloads.into_iter() {
+                scores.push(
+                    match run_bench_workload(workload, cmd.blocks, &config, ctx) {
+                        Ok(score) => score.to_json(),
+                        Err(e) => json!({ "workload": workload.code(), "error": e }),
+                    },
+                );
             }
+            print_output(
+                &BenchOutput {
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    cpu_cores: num_cpus::get(),
+                    scores,
+                },
+                output,
+            );
+        }
+        Command::Db(OrdhookDbCommand::Backup(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let destination = match cmd.destination {
+                Some(destination) => PathBuf::from(destination),
+                None => get_default_backup_path(&config),
+            };
+            let report = backup_all_dbs(&config, &destination, ctx)?;
+            print_output(&report, output);
+        }
+        Command::Db(OrdhookDbCommand::ExportContents(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let inscriptions = std::fs::read_to_string(&cmd.inscriptions_file)
+                .map_err(|e| format!("unable to read {}: {e}", cmd.inscriptions_file))?
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>();
+            let db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
+            let archive = std::fs::File::create(&cmd.output_file)
+                .map_err(|e| format!("unable to create {}: {e}", cmd.output_file))?;
+            write_inscription_contents_archive(
+                &inscriptions,
+                std::io::BufWriter::new(archive),
+                &db_conn,
+                &config,
+                ctx,
+            )?;
+            print_output(
+                &FileWrittenOutput {
+                    description: format!(
+                        "Contents of {} inscriptions written to {}",
+                        inscriptions.len(),
+                        cmd.output_file
+                    ),
+                    path: cmd.output_file,
+                    entries: Some(inscriptions.len()),
+                },
+                output,
+            );
         }
         Command::Db(OrdhookDbCommand::Drop(cmd)) => {
             let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
 
-            println!(
-                "{} blocks will be deleted. Confirm? [Y/n]",
-                cmd.end_block - cmd.start_block + 1
-            );
-            let mut buffer = String::new();
-            std::io::stdin().read_line(&mut buffer).unwrap();
-            if buffer.starts_with('n') {
+            if !confirm(
+                &format!(
+                    "{} blocks will be deleted.",
+                    cmd.end_block - cmd.start_block + 1
+                ),
+                output,
+            ) {
                 return Err("Deletion aborted".to_string());
             }
 
@@ -934,6 +1522,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 cmd.end_block,
                 &blocks_db_rw,
                 &sqlite_dbs_rw,
+                &config,
                 ctx,
             )?;
             info!(
@@ -941,6 +1530,151 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 "Cleaning ordhook_db: {} blocks dropped",
                 cmd.end_block - cmd.start_block + 1
             );
+            print_output(
+                &BlocksDroppedOutput {
+                    start_block: cmd.start_block,
+                    end_block: cmd.end_block,
+                },
+                output,
+            );
+        }
+        Command::Db(OrdhookDbCommand::Replay(cmd)) => {
+            let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            check_dbs_network(&config, ctx)?;
+            if let Some(ref replay_log) = cmd.replay_log {
+                config.replay_log = Some(ReplayLogConfig {
+                    path: Some(PathBuf::from(replay_log)),
+                    max_size_mb: DEFAULT_REPLAY_LOG_MAX_SIZE_MB,
+                });
+            }
+            if !confirm(
+                &format!(
+                    "Recorded payloads will be applied to {}.",
+                    config.expected_sqlite_path().display()
+                ),
+                output,
+            ) {
+                return Err("Replay aborted".to_string());
+            }
+            let service = Service::new(config, ctx.clone());
+            let replayed =
+                service.replay_received_payloads(cmd.from, cmd.to.unwrap_or(u64::MAX))?;
+            print_output(
+                &ReplayOutput {
+                    entries_replayed: replayed,
+                },
+                output,
+            );
+        }
+        Command::Db(OrdhookDbCommand::Renumber(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            check_dbs_network(&config, ctx)?;
+
+            let (blocks_db_rw, sqlite_dbs_rw) = open_all_dbs_rw(&config, &ctx)?;
+            let last_block_inserted = find_last_block_inserted(&blocks_db_rw) as u64;
+            let tip = find_latest_inscription_block_height(&sqlite_dbs_rw.ordinals, ctx)?
+                .unwrap_or(0)
+                .max(last_block_inserted);
+            // A run interrupted after its blocks were dropped is resumed, with the numbers they had before.
+            let pending = match find_pending_renumbering(&sqlite_dbs_rw.ordinals, ctx) {
+                Some(mut pending) => {
+                    if cmd.from_height < pending.from_block_height {
+                        let mut old_numbers = find_inscription_numbers_from_block_height(
+                            cmd.from_height,
+                            &sqlite_dbs_rw.ordinals,
+                            ctx,
+                        );
+                        old_numbers.retain(|n| n.block_height < pending.from_block_height);
+                        old_numbers.append(&mut pending.old_numbers);
+                        pending.old_numbers = old_numbers;
+                        pending.from_block_height = cmd.from_height;
+                    }
+                    pending
+                }
+                None => PendingRenumbering {
+                    from_block_height: cmd.from_height,
+                    renumbered_at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                    old_numbers: find_inscription_numbers_from_block_height(
+                        cmd.from_height,
+                        &sqlite_dbs_rw.ordinals,
+                        ctx,
+                    ),
+                },
+            };
+            let from_height = pending.from_block_height;
+            if from_height > tip {
+                return Err(format!(
+                    "nothing to renumber: block #{from_height} is past the last block indexed (#{tip})"
+                ));
+            }
+            if !confirm(
+                &format!("Blocks #{from_height} to #{tip} will be indexed again."),
+                output,
+            ) {
+                return Err("Renumbering aborted".to_string());
+            }
+            // Checked before anything gets dropped, since the blocks dropped are downloaded again from bitcoind.
+            bitcoind_check_blocks_available(&config, from_height)?;
+
+            // The numbers are kept before the blocks get dropped: they can not be found again afterwards.
+            insert_pending_renumbering(&pending, &sqlite_dbs_rw.ordinals)?;
+            drop_block_data_from_all_dbs(
+                from_height,
+                tip,
+                &blocks_db_rw,
+                &sqlite_dbs_rw,
+                &config,
+                ctx,
+            )?;
+            // The blocks db can only be opened by one process at a time: release it before indexing.
+            drop(blocks_db_rw);
+            drop(sqlite_dbs_rw);
+
+            let service = Service::new(config.clone(), ctx.clone());
+            service.catch_up_to_bitcoin_chain_tip(None).await?;
+
+            let inscriptions_db_conn_rw = open_ordinals_db_rw(&config.expected_sqlite_path(), ctx)?;
+            let new_numbers = find_inscription_numbers_from_block_height(
+                from_height,
+                &inscriptions_db_conn_rw,
+                ctx,
+            );
+            let corrections =
+                compute_inscription_number_corrections(&pending.old_numbers, &new_numbers);
+            complete_pending_renumbering(
+                &corrections,
+                pending.renumbered_at,
+                &inscriptions_db_conn_rw,
+                ctx,
+            )?;
+            deliver_amendment_events(
+                &amendments_from_number_corrections(AmendmentReason::Renumbering, &corrections),
+                &config,
+                ctx,
+            );
+            let reclassified = corrections
+                .iter()
+                .filter(|c| c.is_reclassification())
+                .count();
+            print_output(
+                &RenumberOutput {
+                    from_block_height: from_height,
+                    renumbered: corrections.len(),
+                    reclassified,
+                },
+                output,
+            );
+        }
+        Command::Completions(cmd) => {
+            generate(
+                cmd.shell,
+                &mut Opts::command(),
+                "ordhook",
+                &mut std::io::stdout(),
+            );
         }
     }
     Ok(())
@@ -976,6 +1710,30 @@ pub async fn fetch_and_standardize_block(
         .map_err(|(e, _)| e)
 }
 
+/// Control API of the node configured by `config_path`, or of a node running with the default settings on this host.
+fn get_control_api_url(config_path: &Option<String>) -> Result<String, String> {
+    let Some(config_path) = config_path else {
+        return Ok(format!("http://127.0.0.1:{DEFAULT_CONTROL_PORT}"));
+    };
+    let config = ConfigFile::default(false, false, false, &Some(config_path.clone()), &None)?;
+    match config.http_api {
+        PredicatesApi::On(ref api_config) if api_config.unix_socket.is_none() => {
+            let address = match api_config.http_address.is_unspecified() {
+                true => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                false => api_config.http_address,
+            };
+            Ok(format!(
+                "http://{}",
+                SocketAddr::new(address, api_config.http_port)
+            ))
+        }
+        PredicatesApi::On(_) => {
+            Err("the control API of this config listens on a unix socket: use --url".to_string())
+        }
+        PredicatesApi::Off => Err("the control API is disabled in this config".to_string()),
+    }
+}
+
 pub fn build_predicate_from_cli(
     config: &Config,
     post_to: &str,
@@ -0,0 +1,449 @@
+use clap::ArgEnum;
+use ordhook::config::validation::{ConfigDiagnostic, DiagnosticSeverity};
+use ordhook::db::backup::BackupReport;
+use ordhook::db::chain_status::ChainStatusReport;
+use ordhook::service::status::{format_service_status, ServiceStatus};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+
+/// How commands print their results: text for operators, or a single JSON document on stdout for automation. Commands
+/// streaming their results, like `scan blocks`, print one JSON document per line instead. In JSON mode, errors are
+/// printed as `{"error": "..."}` on stderr and confirmation prompts go to stderr.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Result of a command.
+pub trait CommandOutput: Serialize {
+    /// Lines printed in text mode. Commands which print nothing return an empty string.
+    fn to_text(&self) -> String;
+}
+
+pub fn print_output<T: CommandOutput>(output: &T, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            let text = output.to_text();
+            if !text.is_empty() {
+                println!("{text}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(output).unwrap()),
+    }
+}
+
+/// Prints one of the results of a command streaming its results, as a single line in JSON mode.
+pub fn print_output_line<T: CommandOutput>(output: &T, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => print_output(output, format),
+        OutputFormat::Json => println!("{}", serde_json::to_string(output).unwrap()),
+    }
+}
+
+pub fn print_error(error: &str, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        eprintln!("{}", json!({ "error": error }));
+    }
+}
+
+/// Asks the operator to confirm a destructive operation. Anything but an answer starting with `n` confirms.
+pub fn confirm(prompt: &str, format: OutputFormat) -> bool {
+    match format {
+        OutputFormat::Text => println!("{prompt} Confirm? [Y/n]"),
+        OutputFormat::Json => eprintln!("{prompt} Confirm? [Y/n]"),
+    }
+    let mut buffer = String::new();
+    std::io::stdin().read_line(&mut buffer).unwrap();
+    !buffer.starts_with('n')
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InscriptionOutput {
+    pub inscription_id: String,
+    pub block_height: u64,
+    pub inscription_number: i64,
+    pub ordinal_number: u64,
+}
+
+impl CommandOutput for InscriptionOutput {
+    fn to_text(&self) -> String {
+        format!(
+            "Inscription {} revealed at block #{} (inscription_number {}, ordinal_number {})",
+            self.inscription_id, self.block_height, self.inscription_number, self.ordinal_number
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedInscription {
+    #[serde(flatten)]
+    pub inscription: InscriptionOutput,
+    /// Transactions the inscription was transferred in, after its reveal.
+    pub transfers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedTransfers {
+    pub ordinal_number: u64,
+    pub transactions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedBrc20Operation {
+    pub operation: String,
+    pub tick: String,
+    pub avail_balance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedBlock {
+    pub block_height: u64,
+    pub inscriptions: Vec<ScannedInscription>,
+    pub transfers: Vec<ScannedTransfers>,
+    pub brc20_operations: Vec<ScannedBrc20Operation>,
+}
+
+impl ScannedBlock {
+    pub fn transfers_count(&self) -> usize {
+        self.inscriptions
+            .iter()
+            .map(|inscription| inscription.transfers.len())
+            .chain(self.transfers.iter().map(|t| t.transactions.len()))
+            .sum()
+    }
+}
+
+/// Blocks are printed as they are scanned, one line of JSON each in JSON mode.
+impl CommandOutput for ScannedBlock {
+    fn to_text(&self) -> String {
+        let mut lines = vec![];
+        for scanned in self.inscriptions.iter() {
+            lines.push(scanned.inscription.to_text());
+            for transaction in scanned.transfers.iter() {
+                lines.push(format!("\t→ Transferred in transaction {transaction}"));
+            }
+        }
+        for transfers in self.transfers.iter() {
+            lines.push(format!("Inscription {}", transfers.ordinal_number));
+            for transaction in transfers.transactions.iter() {
+                lines.push(format!("\t→ Transferred in transaction {transaction}"));
+            }
+        }
+        for operation in self.brc20_operations.iter() {
+            lines.push(format!(
+                "BRC-20 {} {} {}",
+                operation.operation, operation.tick, operation.avail_balance
+            ));
+        }
+        let transfers_count = self.transfers_count();
+        if transfers_count > 0 && !self.inscriptions.is_empty() {
+            lines.push(format!(
+                "Inscriptions revealed: {}, inscriptions transferred: {transfers_count}",
+                self.inscriptions.len()
+            ));
+            lines.push("-----".to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SatTraceStep {
+    pub block_height: u32,
+    /// First 8 bytes of the txid, hex encoded.
+    pub txid_prefix: String,
+    pub input_index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SatTraceOutput {
+    pub ordinal_number: u64,
+    pub ordinal_block_height: u64,
+    pub ordinal_offset: u64,
+    pub transfers: u32,
+    /// Transactions walked back to the coinbase the sat was mined in, oldest first.
+    pub back_trace: Vec<SatTraceStep>,
+}
+
+impl CommandOutput for SatTraceOutput {
+    fn to_text(&self) -> String {
+        let mut lines = self
+            .back_trace
+            .iter()
+            .map(|step| {
+                format!(
+                    "{}\t{}:{}",
+                    step.block_height, step.txid_prefix, step.input_index
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "Satoshi #{} (block #{}, offset {}, {} transfers)",
+            self.ordinal_number, self.ordinal_block_height, self.ordinal_offset, self.transfers
+        ));
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileWrittenOutput {
+    pub path: String,
+    /// Number of entries written, for exports.
+    pub entries: Option<usize>,
+    #[serde(skip)]
+    pub description: String,
+}
+
+impl CommandOutput for FileWrittenOutput {
+    fn to_text(&self) -> String {
+        self.description.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigValidationOutput {
+    pub diagnostics: Vec<ConfigDiagnostic>,
+}
+
+impl ConfigValidationOutput {
+    pub fn errors(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == DiagnosticSeverity::Error)
+            .count()
+    }
+}
+
+impl CommandOutput for ConfigValidationOutput {
+    fn to_text(&self) -> String {
+        if self.diagnostics.is_empty() {
+            return "No problems found".to_string();
+        }
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Commands which complete silently in text mode, like the ones only reporting progress in the logs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompletedOutput {
+    pub command: String,
+}
+
+impl CommandOutput for CompletedOutput {
+    fn to_text(&self) -> String {
+        String::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlocksDbCheckOutput {
+    pub tip: u32,
+    pub missing_blocks: Vec<u32>,
+}
+
+impl CommandOutput for BlocksDbCheckOutput {
+    fn to_text(&self) -> String {
+        format!("Tip: {}\n{:?}", self.tip, self.missing_blocks)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadLettersOutput {
+    /// Summaries of the dead letters, without their payloads.
+    pub dead_letters: Vec<JsonValue>,
+}
+
+impl CommandOutput for DeadLettersOutput {
+    fn to_text(&self) -> String {
+        if self.dead_letters.is_empty() {
+            return "Dead-letter queue is empty".to_string();
+        }
+        self.dead_letters
+            .iter()
+            .map(|dead_letter| {
+                format!(
+                    "#{} predicate {} to {}, failed at {} ({} redelivery attempts): {}",
+                    dead_letter["id"],
+                    dead_letter["predicate_uuid"].as_str().unwrap_or_default(),
+                    dead_letter["url"].as_str().unwrap_or_default(),
+                    dead_letter["failed_at"],
+                    dead_letter["redelivery_attempts"],
+                    dead_letter["error"].as_str().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedeliveryOutput {
+    pub delivered: usize,
+    pub failed: usize,
+}
+
+impl CommandOutput for RedeliveryOutput {
+    fn to_text(&self) -> String {
+        format!(
+            "{} dead letters redelivered, {} still failing",
+            self.delivered, self.failed
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainStatusOutput {
+    #[serde(flatten)]
+    pub report: ChainStatusReport,
+    /// Number of blocks compared with bitcoind.
+    pub depth: u64,
+}
+
+impl CommandOutput for ChainStatusOutput {
+    fn to_text(&self) -> String {
+        let report = &self.report;
+        let mut lines = vec![format!("bitcoind tip: #{}", report.bitcoind_tip)];
+        lines.push(match (report.ordhook_tip, &report.ordhook_tip_hash) {
+            (Some(tip), Some(hash)) => format!("ordhook tip: #{} ({})", tip, hash),
+            (Some(tip), None) => format!("ordhook tip: #{}", tip),
+            _ => "ordhook tip: none".to_string(),
+        });
+        lines.push(format!("hord.rocksdb tip: #{}", report.blocks_db_tip));
+        if report.reorg_events.is_empty() {
+            lines.push("Recent re-orgs: none".to_string());
+        } else {
+            lines.push("Recent re-orgs:".to_string());
+            for event in report.reorg_events.iter() {
+                lines.push(format!(
+                    "  #{} {} rolled back at {}",
+                    event.block_height, event.block_hash, event.rolled_back_at
+                ));
+            }
+        }
+        if !report.skipped_blocks.is_empty() {
+            lines.push("Skipped blocks:".to_string());
+            for block in report.skipped_blocks.iter() {
+                lines.push(format!(
+                    "  #{} {} skipped after {} attempts: {}",
+                    block.block_height, block.block_hash, block.attempts, block.error
+                ));
+            }
+        }
+        if report.divergences.is_empty() {
+            lines.push(format!(
+                "No divergence with bitcoind in the last {} blocks",
+                self.depth
+            ));
+        } else {
+            lines.push("Divergences with bitcoind:".to_string());
+            for divergence in report.divergences.iter() {
+                lines.push(format!(
+                    "  #{} {}: expected {}, found {}",
+                    divergence.block_height,
+                    divergence.store,
+                    divergence.expected,
+                    divergence.found
+                ));
+            }
+        }
+        for remediation in report.remediation.iter() {
+            lines.push(format!("Suggested: {}", remediation));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchOutput {
+    pub version: String,
+    pub cpu_cores: usize,
+    /// Scores of the workloads, or the error they failed with.
+    pub scores: Vec<JsonValue>,
+}
+
+impl CommandOutput for BenchOutput {
+    fn to_text(&self) -> String {
+        self.scores
+            .iter()
+            .map(|score| {
+                let workload = score["workload"].as_str().unwrap_or_default();
+                match score["error"].as_str() {
+                    Some(e) => format!("{:<10} failed: {e}", workload),
+                    None => format!(
+                        "{:<10} {} blocks in {:.2}s: {:.1} blocks/s, {:.0} tx/s, {:.1} MB/s",
+                        workload,
+                        score["blocks"],
+                        score["duration_ms"].as_u64().unwrap_or(0) as f64 / 1000.0,
+                        score["blocks_per_sec"].as_f64().unwrap_or(0.0),
+                        score["transactions_per_sec"].as_f64().unwrap_or(0.0),
+                        score["megabytes_per_sec"].as_f64().unwrap_or(0.0)
+                    ),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl CommandOutput for BackupReport {
+    fn to_text(&self) -> String {
+        let mut lines = vec![format!(
+            "Backup written to {} (last block inserted: #{})",
+            self.destination, self.last_block_inserted
+        )];
+        for file in self.files.iter() {
+            lines.push(format!("  {}", file));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlocksDroppedOutput {
+    pub start_block: u64,
+    pub end_block: u64,
+}
+
+impl CommandOutput for BlocksDroppedOutput {
+    fn to_text(&self) -> String {
+        format!("{} blocks dropped", self.end_block - self.start_block + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayOutput {
+    pub entries_replayed: u64,
+}
+
+impl CommandOutput for ReplayOutput {
+    fn to_text(&self) -> String {
+        format!("Replay log: {} entries replayed", self.entries_replayed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenumberOutput {
+    pub from_block_height: u64,
+    pub renumbered: usize,
+    pub reclassified: usize,
+}
+
+impl CommandOutput for RenumberOutput {
+    fn to_text(&self) -> String {
+        format!(
+            "{} inscriptions renumbered from block #{} ({} reclassified)",
+            self.renumbered, self.from_block_height, self.reclassified
+        )
+    }
+}
+
+impl CommandOutput for ServiceStatus {
+    fn to_text(&self) -> String {
+        format_service_status(self)
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ordhook::chainhook_sdk::bitcoincore_rpc::bitcoin::hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash, HashEngine,
+};
+use reqwest::Client as HttpClient;
+use serde_json::Value;
+
+/// Content type of the requests and responses of the AWS JSON 1.1 protocol (Secrets Manager, KMS).
+const AWS_JSON_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// Credentials and region AWS APIs are called with, read from the standard `AWS_*` environment variables.
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    region: String,
+}
+
+impl AwsCredentials {
+    fn from_env(field: &str) -> Result<AwsCredentials, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .map_err(|_| format!("{field}: {name} must be set to use AWS references"))
+        };
+        Ok(AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            region: std::env::var("AWS_REGION")
+                .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                .map_err(|_| format!("{field}: AWS_REGION must be set to use AWS references"))?,
+        })
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Hmac<sha256::Hash> {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    Hmac::<sha256::Hash>::from_engine(engine)
+}
+
+/// Formats a unix timestamp as the `YYYYMMDDTHHMMSSZ` date requests are signed with.
+fn format_amz_date(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // Civil date of a day count since 1970-01-01, in the proleptic Gregorian calendar.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Headers of a `POST /` request to an AWS JSON API, signed with Signature Version 4.
+fn sign_aws_json_request(
+    credentials: &AwsCredentials,
+    service: &str,
+    host: &str,
+    target: &str,
+    body: &str,
+    amz_date: &str,
+) -> Vec<(String, String)> {
+    let date = &amz_date[..8];
+    let mut headers = vec![
+        (
+            "content-type".to_string(),
+            AWS_JSON_CONTENT_TYPE.to_string(),
+        ),
+        ("host".to_string(), host.to_string()),
+        ("x-amz-date".to_string(), amz_date.to_string()),
+    ];
+    if let Some(ref session_token) = credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), session_token.clone()));
+    }
+    headers.push(("x-amz-target".to_string(), target.to_string()));
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+        sha256::Hash::hash(body.as_bytes())
+    );
+    let scope = format!("{date}/{}/{service}/aws4_request", credentials.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        sha256::Hash::hash(canonical_request.as_bytes())
+    );
+    let signing_key = [credentials.region.as_str(), service, "aws4_request"]
+        .iter()
+        .fold(
+            hmac_sha256(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(key.as_byte_array(), part.as_bytes()),
+        );
+    let signature = hmac_sha256(signing_key.as_byte_array(), string_to_sign.as_bytes());
+    headers.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    ));
+    headers
+}
+
+/// Calls an action (`target`, e.g. `secretsmanager.GetSecretValue`) of an AWS JSON API, with the credentials and
+/// region of the environment.
+pub fn call_aws_json_api(
+    field: &str,
+    service: &str,
+    target: &str,
+    body: &Value,
+) -> Result<Value, String> {
+    let credentials = AwsCredentials::from_env(field)?;
+    let host = format!("{service}.{}.amazonaws.com", credentials.region);
+    let body = body.to_string();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("{field}: invalid system time: {e}"))?
+        .as_secs();
+    let headers = sign_aws_json_request(
+        &credentials,
+        service,
+        &host,
+        target,
+        &body,
+        &format_amz_date(now),
+    );
+    let url = format!("https://{host}/");
+
+    // Config loading is performed from within the async runtime, perform the request on a dedicated thread.
+    let moved_url = url.clone();
+    let service = service.to_string();
+    std::thread::spawn(move || {
+        hiro_system_kit::nestable_block_on(async move {
+            let client = HttpClient::builder()
+                .build()
+                .map_err(|e| format!("unable to build http client: {}", e))?;
+            let mut request = client.post(&moved_url).body(body);
+            for (name, value) in headers.into_iter().filter(|(name, _)| name != "host") {
+                request = request.header(name, value);
+            }
+            let res = request
+                .send()
+                .await
+                .map_err(|e| format!("unable to reach {service}: {}", e))?;
+            let status = res.status();
+            let response = res
+                .json::<Value>()
+                .await
+                .map_err(|e| format!("unable to parse {service} response: {}", e))?;
+            if !status.is_success() {
+                let error = response["__type"]
+                    .as_str()
+                    .or(response["message"].as_str())
+                    .unwrap_or_default();
+                return Err(format!("{service} responded with status {status} {error}"));
+            }
+            Ok(response)
+        })
+    })
+    .join()
+    .map_err(|_| format!("{field}: unable to call {url}"))?
+    .map_err(|e| format!("{field}: {e}"))
+}
@@ -1,25 +1,56 @@
-use ordhook::chainhook_sdk::observer::DEFAULT_INGESTION_PORT;
-use ordhook::chainhook_sdk::types::{
-    BitcoinBlockSignaling, BitcoinNetwork, StacksNetwork, StacksNodeConfig,
-};
+use ordhook::chainhook_sdk::types::{BitcoinBlockSignaling, BitcoinNetwork, StacksNodeConfig};
 use ordhook::config::{
-    Config, IndexerConfig, LogConfig, MetaProtocolsConfig, PredicatesApi, PredicatesApiConfig,
-    ResourcesConfig, SnapshotConfig, SnapshotConfigDownloadUrls, StorageConfig,
-    DEFAULT_BITCOIND_RPC_THREADS, DEFAULT_BITCOIND_RPC_TIMEOUT, DEFAULT_BRC20_LRU_CACHE_SIZE,
-    DEFAULT_CONTROL_PORT, DEFAULT_MEMORY_AVAILABLE, DEFAULT_ULIMIT,
+    namespaced_working_dir, network_name, AlertsConfig, AmendmentsConfig, BlockIngestion,
+    ColdStorageConfig, Config, ContentScanningConfig, DailyWindow, EventTransformConfig,
+    IndexScope, IndexerConfig, IngestionGuardConfig, IngestionTlsConfig, IpRange, LogConfig,
+    MaintenanceConfig, MetaProtocolsConfig, ObserverLivenessConfig, PredicatesApi,
+    PredicatesApiConfig, PreviewsConfig, ReplayLogConfig, ResourcesConfig, RollbacksConfig,
+    SalesAnalyticsConfig, SnapshotConfig, SnapshotConfigDownloadUrls, StorageConfig, TenantConfig,
+    UnixSocketConfig, DEFAULT_ALERTS_MAX_CONSECUTIVE_RPC_FAILURES, DEFAULT_ALERTS_MAX_REORG_DEPTH,
+    DEFAULT_ALERTS_MAX_TIP_LAG, DEFAULT_AMENDMENTS_MIN_REORG_DEPTH,
+    DEFAULT_COLD_STORAGE_OLDER_THAN_BLOCKS, DEFAULT_CONTENT_SCAN_MAX_CONTENT_BYTES,
+    DEFAULT_CONTENT_SCAN_TIMEOUT_SECS, DEFAULT_CONTROL_PORT, DEFAULT_EVENT_TRANSFORM_MAX_FUEL,
+    DEFAULT_EVENT_TRANSFORM_MAX_MEMORY_BYTES, DEFAULT_INGESTION_GUARD_INTERNAL_PORT,
+    DEFAULT_INGESTION_PORT, DEFAULT_LISTENER_ADDRESS, DEFAULT_MAINTENANCE_INTERVAL_SECS,
+    DEFAULT_MAINTENANCE_MAX_API_CALLS_PER_MINUTE, DEFAULT_NATIVE_INGESTION_POLL_INTERVAL_MS,
+    DEFAULT_OBSERVER_LIVENESS_MAX_CONSECUTIVE_FAILURES,
+    DEFAULT_OBSERVER_LIVENESS_PROBE_INTERVAL_SECS, DEFAULT_PREVIEW_MAX_CONTENT_BYTES,
+    DEFAULT_PREVIEW_SIZES, DEFAULT_QUERY_TIMEOUT_MS, DEFAULT_REPLAY_LOG_MAX_SIZE_MB,
+    DEFAULT_SALES_MIN_PRICE_SATS, DEFAULT_UNIX_SOCKET_MODE,
 };
+use ordhook::db::blocks::set_blocks_encryption_key;
+use ordhook::db::ordinals::{check_sqlite_encryption_key, set_sqlite_encryption_key};
+use ordhook::utils::http::parse_network_proxy;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use super::secrets::{resolve_required_secret, resolve_secret};
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigFile {
+    #[serde(default)]
     pub storage: StorageConfigFile,
     pub http_api: Option<PredicatesApiConfigFile>,
+    #[serde(default)]
     pub resources: ResourcesConfigFile,
     pub network: NetworkConfigFile,
     pub logs: Option<LogConfigFile>,
     pub snapshot: Option<SnapshotConfigFile>,
     pub meta_protocols: Option<MetaProtocolsConfigFile>,
+    pub alerts: Option<AlertsConfigFile>,
+    pub observer_liveness: Option<ObserverLivenessConfigFile>,
+    pub event_transforms: Option<Vec<EventTransformConfigFile>>,
+    pub previews: Option<PreviewsConfigFile>,
+    pub content_scanning: Option<ContentScanningConfigFile>,
+    pub sales_analytics: Option<SalesAnalyticsConfigFile>,
+    pub maintenance: Option<MaintenanceConfigFile>,
+    pub amendments: Option<AmendmentsConfigFile>,
+    pub rollbacks: Option<RollbacksConfigFile>,
+    pub replay_log: Option<ReplayLogConfigFile>,
+    pub cold_storage: Option<ColdStorageConfigFile>,
 }
 
 impl ConfigFile {
@@ -32,22 +63,33 @@ impl ConfigFile {
             .read_to_end(&mut file_buffer)
             .map_err(|e| format!("unable to read file {}\n{:?}", file_path, e))?;
 
+        // Unknown keys and type mismatches are reported by toml with the line and column of the offending value.
         let config_file: ConfigFile = match toml::from_slice(&file_buffer) {
             Ok(s) => s,
             Err(e) => {
-                return Err(format!("Config file malformatted {}", e));
+                return Err(format!("{file_path}: config file malformatted: {e}"));
             }
         };
-        ConfigFile::from_config_file(config_file)
+        ConfigFile::from_config_file(config_file).map_err(|e| format!("{file_path}: {e}"))
     }
 
+    /// Settings left out of the file fall back to the defaults of the network selected by `network.mode`.
     pub fn from_config_file(config_file: ConfigFile) -> Result<Config, String> {
-        let (_, bitcoin_network) = match config_file.network.mode.as_str() {
-            "devnet" => (StacksNetwork::Devnet, BitcoinNetwork::Regtest),
-            "testnet" => (StacksNetwork::Testnet, BitcoinNetwork::Testnet),
-            "mainnet" => (StacksNetwork::Mainnet, BitcoinNetwork::Mainnet),
-            "signet" => (StacksNetwork::Testnet, BitcoinNetwork::Signet),
-            _ => return Err("network.mode not supported".to_string()),
+        let (bitcoin_network, testnet4) = match config_file.network.mode.as_str() {
+            "devnet" => (BitcoinNetwork::Regtest, false),
+            "testnet" => (BitcoinNetwork::Testnet, false),
+            "testnet4" => (BitcoinNetwork::Testnet, true),
+            "mainnet" => (BitcoinNetwork::Mainnet, false),
+            "signet" => (BitcoinNetwork::Signet, false),
+            mode => {
+                return Err(format!(
+                    "network.mode: {mode} not supported (expected devnet, testnet, testnet4, signet or mainnet)"
+                ))
+            }
+        };
+        let defaults = match testnet4 {
+            true => Config::testnet4_default(),
+            false => Config::default_for_network(&bitcoin_network),
         };
 
         let snapshot = match config_file.snapshot {
@@ -55,70 +97,322 @@ impl ConfigFile {
                 Some(ref url) => SnapshotConfig::Download(SnapshotConfigDownloadUrls {
                     ordinals: url.to_string(),
                     brc20: bootstrap.brc20_url,
+                    ordinals_mirrors: bootstrap.ordinals_mirror_urls.unwrap_or_default(),
+                    brc20_mirrors: bootstrap.brc20_mirror_urls.unwrap_or_default(),
+                    ordinals_torrent: bootstrap.ordinals_torrent,
+                    brc20_torrent: bootstrap.brc20_torrent,
                 }),
                 None => SnapshotConfig::Build,
             },
             None => SnapshotConfig::Build,
         };
 
+        let encryption_key = match config_file.storage.encryption_key_env {
+            Some(_)
+                if config_file.storage.encryption_key.is_some()
+                    || config_file.storage.encryption_key_file.is_some() =>
+            {
+                return Err("storage.encryption_key_env can not be used along with storage.encryption_key or storage.encryption_key_file".into());
+            }
+            Some(ref env_var) => Some(
+                std::env::var(env_var)
+                    .map_err(|_| format!("storage.encryption_key_env: {} is not set", env_var))?,
+            ),
+            None => resolve_secret(
+                "storage.encryption_key",
+                config_file.storage.encryption_key.clone(),
+                config_file.storage.encryption_key_file.clone(),
+            )?,
+        };
+
+        let bitcoind_rpc_password = resolve_required_secret(
+            "network.bitcoind_rpc_password",
+            config_file.network.bitcoind_rpc_password.clone(),
+            config_file.network.bitcoind_rpc_password_file.clone(),
+        )?;
+
+        let http_api = match config_file.http_api {
+            None => PredicatesApi::Off,
+            Some(http_api) => match http_api.disabled {
+                Some(false) => PredicatesApi::Off,
+                _ => {
+                    let (http_address, http_port) = parse_bind_address(
+                        "http_api",
+                        "http",
+                        &http_api.http_bind_address,
+                        &http_api.http_address,
+                        &http_api.http_port,
+                        DEFAULT_CONTROL_PORT,
+                    )?;
+                    PredicatesApi::On(PredicatesApiConfig {
+                        http_address,
+                        http_port,
+                        unix_socket: parse_unix_socket_config(
+                            "http_api.unix_socket_mode",
+                            &http_api.unix_socket,
+                            &http_api.unix_socket_mode,
+                        )?,
+                        display_logs: http_api.display_logs.unwrap_or(true),
+                        upstream_api_url: http_api.upstream_api_url,
+                        blocklist_path: http_api.blocklist_path,
+                        tenants: parse_tenants_config(http_api.tenants.unwrap_or_default())?,
+                        query_timeout_ms: http_api
+                            .query_timeout_ms
+                            .unwrap_or(DEFAULT_QUERY_TIMEOUT_MS),
+                    })
+                }
+            },
+        };
+
+        let (prometheus_monitoring_address, prometheus_monitoring_port) =
+            match config_file.network.prometheus_monitoring_bind_address {
+                Some(_) => {
+                    let (address, port) = parse_bind_address(
+                        "network",
+                        "prometheus_monitoring",
+                        &config_file.network.prometheus_monitoring_bind_address,
+                        &config_file.network.prometheus_monitoring_address,
+                        &config_file.network.prometheus_monitoring_port,
+                        0,
+                    )?;
+                    (address, Some(port))
+                }
+                None => (
+                    parse_listener_address(
+                        "network.prometheus_monitoring_address",
+                        &config_file.network.prometheus_monitoring_address,
+                    )?,
+                    config_file.network.prometheus_monitoring_port,
+                ),
+            };
+
+        let ingestion_port = config_file
+            .network
+            .ingestion_port
+            .unwrap_or(DEFAULT_INGESTION_PORT);
+
+        let block_ingestion = match config_file.network.block_ingestion.as_deref() {
+            None | Some("observer") => BlockIngestion::Observer,
+            Some("native") => BlockIngestion::Native {
+                poll_interval_ms: config_file
+                    .network
+                    .native_ingestion_poll_interval_ms
+                    .unwrap_or(DEFAULT_NATIVE_INGESTION_POLL_INTERVAL_MS),
+            },
+            Some(_) => {
+                return Err(
+                    "network.block_ingestion not supported (expected observer or native)"
+                        .to_string(),
+                )
+            }
+        };
+
+        // Protocol heights are consensus on public networks, and can only be moved on the ones that can be custom.
+        let custom_network = matches!(
+            bitcoin_network,
+            BitcoinNetwork::Regtest | BitcoinNetwork::Signet
+        );
+        let first_inscription_height = config_file.network.first_inscription_height;
+        let jubilee_height = config_file.network.jubilee_height;
+        if !custom_network && (first_inscription_height.is_some() || jubilee_height.is_some()) {
+            return Err(
+                "network.first_inscription_height and network.jubilee_height are only supported in devnet and signet modes"
+                    .to_string(),
+            );
+        }
+
+        let network_dir_name = network_name(&bitcoin_network, testnet4);
         let config = Config {
             storage: StorageConfig {
-                working_dir: config_file.storage.working_dir.unwrap_or("ordhook".into()),
+                working_dir: namespaced_working_dir(
+                    &config_file.storage.working_dir.unwrap_or("ordhook".into()),
+                    network_dir_name,
+                ),
                 observers_working_dir: config_file
                     .storage
                     .observers_working_dir
                     .unwrap_or("observers".into()),
-            },
-            http_api: match config_file.http_api {
-                None => PredicatesApi::Off,
-                Some(http_api) => match http_api.disabled {
-                    Some(false) => PredicatesApi::Off,
-                    _ => PredicatesApi::On(PredicatesApiConfig {
-                        http_port: http_api.http_port.unwrap_or(DEFAULT_CONTROL_PORT),
-                        display_logs: http_api.display_logs.unwrap_or(true),
-                    }),
+                sqlite_dir: config_file
+                    .storage
+                    .sqlite_dir
+                    .map(|dir| namespaced_working_dir(&dir, network_dir_name)),
+                blocks_dir: config_file
+                    .storage
+                    .blocks_dir
+                    .map(|dir| namespaced_working_dir(&dir, network_dir_name)),
+                content_dir: config_file
+                    .storage
+                    .content_dir
+                    .map(|dir| namespaced_working_dir(&dir, network_dir_name)),
+                encryption_key,
+                store_content: config_file.storage.store_content.unwrap_or(true),
+                content_types_allowed: config_file
+                    .storage
+                    .content_types_allowed
+                    .unwrap_or_default(),
+                content_types_denied: config_file.storage.content_types_denied.unwrap_or_default(),
+                index_commitment_interval: match config_file.storage.index_commitment_interval {
+                    Some(0) => {
+                        return Err(
+                            "storage.index_commitment_interval: must be greater than 0".into()
+                        )
+                    }
+                    interval => interval,
+                },
+                correct_content_types: config_file.storage.correct_content_types.unwrap_or(false),
+                satribute_ranges_path: config_file.storage.satribute_ranges_path,
+                index_scope: match config_file.storage.index_scope {
+                    Some(ref scope) => scope.parse::<IndexScope>()?,
+                    None => IndexScope::InscribedOnly,
                 },
+                content_mmap_threshold_bytes: config_file
+                    .storage
+                    .content_mmap_threshold_bytes
+                    .unwrap_or(defaults.storage.content_mmap_threshold_bytes),
+                content_cache_max_size_bytes: config_file
+                    .storage
+                    .content_cache_max_size_bytes
+                    .unwrap_or(defaults.storage.content_cache_max_size_bytes),
             },
+            http_api,
             snapshot,
             resources: ResourcesConfig {
-                ulimit: config_file.resources.ulimit.unwrap_or(DEFAULT_ULIMIT),
-                cpu_core_available: config_file
-                    .resources
-                    .cpu_core_available
-                    .unwrap_or(num_cpus::get()),
-                memory_available: config_file
+                ulimit: parse_positive(
+                    "resources.ulimit",
+                    config_file.resources.ulimit,
+                    defaults.resources.ulimit,
+                )?,
+                cpu_core_available: parse_positive(
+                    "resources.cpu_core_available",
+                    config_file.resources.cpu_core_available,
+                    defaults.resources.cpu_core_available,
+                )?,
+                memory_available: parse_positive(
+                    "resources.memory_available",
+                    config_file.resources.memory_available,
+                    defaults.resources.memory_available,
+                )?,
+                bitcoind_rpc_threads: parse_positive(
+                    "resources.bitcoind_rpc_threads",
+                    config_file.resources.bitcoind_rpc_threads,
+                    defaults.resources.bitcoind_rpc_threads,
+                )?,
+                bitcoind_rpc_timeout: parse_positive(
+                    "resources.bitcoind_rpc_timeout",
+                    config_file.resources.bitcoind_rpc_timeout,
+                    defaults.resources.bitcoind_rpc_timeout,
+                )?,
+                network_connect_timeout_secs: parse_positive(
+                    "resources.network_connect_timeout_secs",
+                    config_file.resources.network_connect_timeout_secs,
+                    defaults.resources.network_connect_timeout_secs,
+                )?,
+                network_read_timeout_secs: parse_positive(
+                    "resources.network_read_timeout_secs",
+                    config_file.resources.network_read_timeout_secs,
+                    defaults.resources.network_read_timeout_secs,
+                )?,
+                webhook_timeout_secs: parse_positive(
+                    "resources.webhook_timeout_secs",
+                    config_file.resources.webhook_timeout_secs,
+                    defaults.resources.webhook_timeout_secs,
+                )?,
+                network_max_retries: config_file
                     .resources
-                    .memory_available
-                    .unwrap_or(DEFAULT_MEMORY_AVAILABLE),
-                bitcoind_rpc_threads: config_file
+                    .network_max_retries
+                    .unwrap_or(defaults.resources.network_max_retries),
+                network_retry_backoff_ms: config_file
                     .resources
-                    .bitcoind_rpc_threads
-                    .unwrap_or(DEFAULT_BITCOIND_RPC_THREADS),
-                bitcoind_rpc_timeout: config_file
-                    .resources
-                    .bitcoind_rpc_timeout
-                    .unwrap_or(DEFAULT_BITCOIND_RPC_TIMEOUT),
-                expected_observers_count: config_file
+                    .network_retry_backoff_ms
+                    .unwrap_or(defaults.resources.network_retry_backoff_ms),
+                network_proxy: match config_file.resources.network_proxy {
+                    Some(ref proxy_url) => {
+                        parse_network_proxy(proxy_url)
+                            .map_err(|e| format!("resources.network_proxy: {e}"))?;
+                        Some(proxy_url.clone())
+                    }
+                    None => None,
+                },
+                expected_observers_count: parse_positive(
+                    "resources.expected_observers_count",
+                    config_file.resources.expected_observers_count,
+                    defaults.resources.expected_observers_count,
+                )?,
+                brc20_lru_cache_size: parse_positive(
+                    "resources.brc20_lru_cache_size",
+                    config_file.resources.brc20_lru_cache_size,
+                    defaults.resources.brc20_lru_cache_size,
+                )?,
+                max_concurrent_jobs: parse_positive(
+                    "resources.max_concurrent_jobs",
+                    config_file.resources.max_concurrent_jobs,
+                    defaults.resources.max_concurrent_jobs,
+                )?,
+                job_retention_days: config_file
                     .resources
-                    .expected_observers_count
-                    .unwrap_or(1),
-                brc20_lru_cache_size: config_file
+                    .job_retention_days
+                    .unwrap_or(defaults.resources.job_retention_days),
+                block_processing_max_retries: config_file
                     .resources
-                    .brc20_lru_cache_size
-                    .unwrap_or(DEFAULT_BRC20_LRU_CACHE_SIZE),
+                    .block_processing_max_retries
+                    .unwrap_or(defaults.resources.block_processing_max_retries),
+                max_download_rate: config_file.resources.max_download_rate,
+                heavy_network_window: match config_file.resources.heavy_network_window {
+                    Some(ref window) => Some(
+                        DailyWindow::parse(window)
+                            .map_err(|e| format!("resources.heavy_network_window: {e}"))?,
+                    ),
+                    None => None,
+                },
             },
             network: IndexerConfig {
-                bitcoind_rpc_url: config_file.network.bitcoind_rpc_url.to_string(),
-                bitcoind_rpc_username: config_file.network.bitcoind_rpc_username.to_string(),
-                bitcoind_rpc_password: config_file.network.bitcoind_rpc_password.to_string(),
+                bitcoind_rpc_url: config_file
+                    .network
+                    .bitcoind_rpc_url
+                    .unwrap_or(defaults.network.bitcoind_rpc_url),
+                bitcoind_rpc_username: config_file
+                    .network
+                    .bitcoind_rpc_username
+                    .unwrap_or(defaults.network.bitcoind_rpc_username),
+                bitcoind_rpc_password,
                 bitcoin_block_signaling: match config_file.network.bitcoind_zmq_url {
-                    Some(ref zmq_url) => BitcoinBlockSignaling::ZeroMQ(zmq_url.clone()),
-                    None => BitcoinBlockSignaling::Stacks(StacksNodeConfig::default_localhost(
-                        DEFAULT_INGESTION_PORT,
-                    )),
+                    Some(ref zmq_url) => Some(BitcoinBlockSignaling::ZeroMQ(zmq_url.clone())),
+                    None => config_file
+                        .network
+                        .stacks_node_rpc_url
+                        .as_ref()
+                        .map(|rpc_url| {
+                            BitcoinBlockSignaling::Stacks(StacksNodeConfig {
+                                rpc_url: rpc_url.clone(),
+                                ingestion_port,
+                            })
+                        }),
                 },
                 bitcoin_network,
-                prometheus_monitoring_port: config_file.network.prometheus_monitoring_port,
+                testnet4,
+                ingestion_port,
+                ingestion_guard: parse_ingestion_guard_config(
+                    &config_file.network.ingestion_guard,
+                    ingestion_port,
+                )?,
+                prometheus_monitoring_address,
+                prometheus_monitoring_port,
+                prometheus_monitoring_unix_socket: parse_unix_socket_config(
+                    "network.prometheus_monitoring_unix_socket_mode",
+                    &config_file.network.prometheus_monitoring_unix_socket,
+                    &config_file.network.prometheus_monitoring_unix_socket_mode,
+                )?,
+                block_ingestion,
+                bitcoind_pruned: config_file
+                    .network
+                    .bitcoind_pruned
+                    .unwrap_or(defaults.network.bitcoind_pruned),
+                bitcoind_block_filters: config_file
+                    .network
+                    .bitcoind_block_filters
+                    .unwrap_or(defaults.network.bitcoind_block_filters),
+                first_inscription_height,
+                jubilee_height,
             },
             logs: LogConfig {
                 ordinals_internals: config_file
@@ -131,6 +425,14 @@ impl ConfigFile {
                     .as_ref()
                     .and_then(|l| l.chainhook_internals)
                     .unwrap_or(true),
+                slow_block_threshold_ms: config_file
+                    .logs
+                    .as_ref()
+                    .and_then(|l| l.slow_block_threshold_ms),
+                slow_block_profiles_dir: config_file
+                    .logs
+                    .as_ref()
+                    .and_then(|l| l.slow_block_profiles_dir.clone()),
             },
             meta_protocols: MetaProtocolsConfig {
                 brc20: config_file
@@ -138,8 +440,171 @@ impl ConfigFile {
                     .as_ref()
                     .and_then(|l| l.brc20)
                     .unwrap_or(false),
+                sns: config_file
+                    .meta_protocols
+                    .as_ref()
+                    .and_then(|l| l.sns)
+                    .unwrap_or(false),
+                runes: config_file
+                    .meta_protocols
+                    .as_ref()
+                    .and_then(|l| l.runes)
+                    .unwrap_or(false),
+            },
+            alerts: match config_file.alerts {
+                Some(alerts) => {
+                    if alerts.webhook_url.is_none() && alerts.command.is_none() {
+                        return Err("alerts.webhook_url or alerts.command must be provided".into());
+                    }
+                    Some(AlertsConfig {
+                        webhook_url: alerts.webhook_url,
+                        command: alerts.command,
+                        max_tip_lag: alerts.max_tip_lag.unwrap_or(DEFAULT_ALERTS_MAX_TIP_LAG),
+                        max_consecutive_rpc_failures: alerts
+                            .max_consecutive_rpc_failures
+                            .unwrap_or(DEFAULT_ALERTS_MAX_CONSECUTIVE_RPC_FAILURES),
+                        max_reorg_depth: alerts
+                            .max_reorg_depth
+                            .unwrap_or(DEFAULT_ALERTS_MAX_REORG_DEPTH),
+                    })
+                }
+                None => None,
+            },
+            observer_liveness: config_file.observer_liveness.map(|liveness| {
+                ObserverLivenessConfig {
+                    probe_interval_secs: liveness
+                        .probe_interval_secs
+                        .unwrap_or(DEFAULT_OBSERVER_LIVENESS_PROBE_INTERVAL_SECS),
+                    max_consecutive_failures: liveness
+                        .max_consecutive_failures
+                        .unwrap_or(DEFAULT_OBSERVER_LIVENESS_MAX_CONSECUTIVE_FAILURES),
+                    pause_delivery: liveness.pause_delivery.unwrap_or(false),
+                }
+            }),
+            event_transforms: config_file
+                .event_transforms
+                .unwrap_or_default()
+                .into_iter()
+                .map(|transform| EventTransformConfig {
+                    path: transform.path,
+                    max_fuel: transform
+                        .max_fuel
+                        .unwrap_or(DEFAULT_EVENT_TRANSFORM_MAX_FUEL),
+                    max_memory_bytes: transform
+                        .max_memory_mb
+                        .map(|mb| mb * 1024 * 1024)
+                        .unwrap_or(DEFAULT_EVENT_TRANSFORM_MAX_MEMORY_BYTES),
+                })
+                .collect(),
+            previews: match config_file.previews {
+                Some(previews) => {
+                    let sizes = previews.sizes.unwrap_or(DEFAULT_PREVIEW_SIZES.to_vec());
+                    if sizes.is_empty() || sizes.contains(&0) {
+                        return Err("previews.sizes must list positive sizes".into());
+                    }
+                    Some(PreviewsConfig {
+                        sizes,
+                        max_content_bytes: previews
+                            .max_content_bytes
+                            .unwrap_or(DEFAULT_PREVIEW_MAX_CONTENT_BYTES),
+                    })
+                }
+                None => None,
+            },
+            content_scanning: match config_file.content_scanning {
+                Some(content_scanning) => {
+                    if content_scanning.classifier_url.is_none()
+                        && content_scanning.command.is_none()
+                    {
+                        return Err(
+                            "content_scanning requires a classifier_url or a command".into()
+                        );
+                    }
+                    Some(ContentScanningConfig {
+                        classifier_url: content_scanning.classifier_url,
+                        command: content_scanning.command,
+                        content_types: content_scanning.content_types.unwrap_or_default(),
+                        max_content_bytes: content_scanning
+                            .max_content_bytes
+                            .unwrap_or(DEFAULT_CONTENT_SCAN_MAX_CONTENT_BYTES),
+                        timeout_secs: content_scanning
+                            .timeout_secs
+                            .unwrap_or(DEFAULT_CONTENT_SCAN_TIMEOUT_SECS),
+                        block_flagged: content_scanning.block_flagged.unwrap_or(true),
+                        block_unscanned: content_scanning.block_unscanned.unwrap_or(false),
+                    })
+                }
+                None => None,
+            },
+            sales_analytics: config_file.sales_analytics.map(|sales_analytics| {
+                SalesAnalyticsConfig {
+                    webhook_url: sales_analytics.webhook_url,
+                    command: sales_analytics.command,
+                    min_price_sats: sales_analytics
+                        .min_price_sats
+                        .unwrap_or(DEFAULT_SALES_MIN_PRICE_SATS),
+                }
+            }),
+            maintenance: match config_file.maintenance {
+                Some(maintenance) => Some(MaintenanceConfig {
+                    windows: maintenance
+                        .windows
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|window| {
+                            DailyWindow::parse(window)
+                                .map_err(|e| format!("maintenance.windows: {e}"))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    max_api_calls_per_minute: maintenance
+                        .max_api_calls_per_minute
+                        .unwrap_or(DEFAULT_MAINTENANCE_MAX_API_CALLS_PER_MINUTE),
+                    interval_secs: maintenance
+                        .interval_secs
+                        .unwrap_or(DEFAULT_MAINTENANCE_INTERVAL_SECS),
+                }),
+                None => None,
+            },
+            amendments: config_file.amendments.map(|amendments| AmendmentsConfig {
+                webhook_url: amendments.webhook_url,
+                command: amendments.command,
+                min_reorg_depth: amendments
+                    .min_reorg_depth
+                    .unwrap_or(DEFAULT_AMENDMENTS_MIN_REORG_DEPTH),
+            }),
+            rollbacks: match config_file.rollbacks {
+                Some(rollbacks) => {
+                    if rollbacks.webhook_url.is_none() && rollbacks.command.is_none() {
+                        return Err(
+                            "rollbacks.webhook_url or rollbacks.command must be provided".into(),
+                        );
+                    }
+                    Some(RollbacksConfig {
+                        webhook_url: rollbacks.webhook_url,
+                        command: rollbacks.command,
+                    })
+                }
+                None => None,
+            },
+            replay_log: config_file.replay_log.map(|replay_log| ReplayLogConfig {
+                path: replay_log.path.map(PathBuf::from),
+                max_size_mb: replay_log
+                    .max_size_mb
+                    .unwrap_or(DEFAULT_REPLAY_LOG_MAX_SIZE_MB),
+            }),
+            cold_storage: match config_file.cold_storage {
+                Some(cold_storage) => Some(ColdStorageConfig {
+                    path: cold_storage.path.map(PathBuf::from),
+                    older_than_blocks: parse_positive(
+                        "cold_storage.older_than_blocks",
+                        cold_storage.older_than_blocks,
+                        DEFAULT_COLD_STORAGE_OLDER_THAN_BLOCKS,
+                    )?,
+                }),
+                None => None,
             },
         };
+        check_ingestion_guard_ports(&config)?;
         Ok(config)
     }
 
@@ -160,61 +625,422 @@ impl ConfigFile {
         if let Some(meta_protocols) = meta_protocols {
             match meta_protocols.as_str() {
                 "brc20" => config.meta_protocols.brc20 = true,
+                "sns" => config.meta_protocols.sns = true,
+                "runes" => config.meta_protocols.runes = true,
                 _ => Err("Invalid meta protocol".to_string())?,
             }
         }
+        set_sqlite_encryption_key(config.storage.encryption_key.clone())?;
+        set_blocks_encryption_key(config.storage.encryption_key.as_deref())?;
+        check_sqlite_encryption_key(&config.expected_sqlite_path())?;
         Ok(config)
     }
 }
 
+/// Counts, sizes and timeouts: 0 is rejected instead of stalling the component relying on the setting.
+fn parse_positive<T: Copy + Default + PartialEq>(
+    setting: &str,
+    value: Option<T>,
+    default: T,
+) -> Result<T, String> {
+    match value {
+        Some(value) if value == T::default() => Err(format!("{setting}: must be greater than 0")),
+        Some(value) => Ok(value),
+        None => Ok(default),
+    }
+}
+
+fn parse_listener_address(field: &str, address: &Option<String>) -> Result<IpAddr, String> {
+    match address {
+        Some(address) => address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map_err(|e| format!("{field}: invalid address {address} ({e})")),
+        None => Ok(DEFAULT_LISTENER_ADDRESS),
+    }
+}
+
+/// Resolves the address and port of a listener, either from its `bind_address` (`host:port`, `[::1]:port` for IPv6)
+/// or from the `<prefix>_address` / `<prefix>_port` pair.
+fn parse_bind_address(
+    section: &str,
+    prefix: &str,
+    bind_address: &Option<String>,
+    address: &Option<String>,
+    port: &Option<u16>,
+    default_port: u16,
+) -> Result<(IpAddr, u16), String> {
+    match bind_address {
+        Some(bind_address) => {
+            if address.is_some() || port.is_some() {
+                return Err(format!(
+                    "{section}: {prefix}_bind_address can not be combined with {prefix}_address or {prefix}_port"
+                ));
+            }
+            let addr = bind_address
+                .parse::<SocketAddr>()
+                .map_err(|e| format!("{section}: invalid bind address {bind_address} ({e})"))?;
+            Ok((addr.ip(), addr.port()))
+        }
+        None => Ok((
+            parse_listener_address(&format!("{section}.{prefix}_address"), address)?,
+            port.unwrap_or(default_port),
+        )),
+    }
+}
+
+fn parse_unix_socket_config(
+    field: &str,
+    path: &Option<String>,
+    mode: &Option<String>,
+) -> Result<Option<UnixSocketConfig>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let mode = match mode {
+        Some(mode) => u32::from_str_radix(mode, 8)
+            .map_err(|e| format!("{field}: invalid octal mode {mode} ({e})"))?,
+        None => DEFAULT_UNIX_SOCKET_MODE,
+    };
+    Ok(Some(UnixSocketConfig {
+        path: PathBuf::from(path),
+        mode,
+    }))
+}
+
+/// chainhook-sdk's event observer is moved to the internal port of the ingestion guard, which can't be shared with
+/// another listener.
+fn check_ingestion_guard_ports(config: &Config) -> Result<(), String> {
+    let Some(ref guard) = config.network.ingestion_guard else {
+        return Ok(());
+    };
+    if let PredicatesApi::On(ref api) = config.http_api {
+        if api.http_port == guard.internal_port {
+            return Err(format!(
+                "network.ingestion_guard.internal_port: must differ from http_api.http_port ({})",
+                api.http_port
+            ));
+        }
+    }
+    if config.network.prometheus_monitoring_port == Some(guard.internal_port) {
+        return Err(format!(
+            "network.ingestion_guard.internal_port: must differ from network.prometheus_monitoring_port ({})",
+            guard.internal_port
+        ));
+    }
+    Ok(())
+}
+
+fn parse_ingestion_guard_config(
+    guard: &Option<IngestionGuardConfigFile>,
+    ingestion_port: u16,
+) -> Result<Option<IngestionGuardConfig>, String> {
+    let Some(guard) = guard else {
+        return Ok(None);
+    };
+    let mut allowed_ips = vec![];
+    for range in guard.allowed_ips.iter().flatten() {
+        allowed_ips.push(
+            IpRange::parse(range)
+                .map_err(|e| format!("network.ingestion_guard.allowed_ips: {e}"))?,
+        );
+    }
+    let tls =
+        match (&guard.tls_cert_path, &guard.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some(IngestionTlsConfig {
+                cert_path: PathBuf::from(cert_path),
+                key_path: PathBuf::from(key_path),
+                client_ca_path: guard.tls_client_ca_path.as_ref().map(PathBuf::from),
+            }),
+            (None, None) if guard.tls_client_ca_path.is_none() => None,
+            _ => return Err(
+                "network.ingestion_guard: tls_cert_path and tls_key_path are both required for tls"
+                    .into(),
+            ),
+        };
+    let internal_port = guard
+        .internal_port
+        .unwrap_or(DEFAULT_INGESTION_GUARD_INTERNAL_PORT);
+    if internal_port == ingestion_port {
+        return Err(format!(
+            "network.ingestion_guard.internal_port: must differ from network.ingestion_port ({ingestion_port})"
+        ));
+    }
+    let payload_secret = resolve_secret(
+        "network.ingestion_guard.payload_secret",
+        guard.payload_secret.clone(),
+        guard.payload_secret_file.clone(),
+    )?;
+    Ok(Some(IngestionGuardConfig {
+        allowed_ips,
+        tls,
+        internal_port,
+        internal_port_firewalled: guard.internal_port_firewalled.unwrap_or(false),
+        payload_secret,
+    }))
+}
+
+fn parse_tenants_config(tenants: Vec<TenantConfigFile>) -> Result<Vec<TenantConfig>, String> {
+    let mut parsed: Vec<TenantConfig> = vec![];
+    for tenant in tenants.into_iter() {
+        if parsed.iter().any(|t| t.name == tenant.name) {
+            return Err(format!(
+                "http_api.tenants: {} is declared twice",
+                tenant.name
+            ));
+        }
+        if tenant.api_keys.is_empty() {
+            return Err(format!("http_api.tenants: {} has no api_keys", tenant.name));
+        }
+        let mut api_keys = vec![];
+        for api_key in tenant.api_keys.into_iter() {
+            // API keys can be read from Vault like other secrets.
+            let api_key = resolve_secret("http_api.tenants.api_keys", Some(api_key), None)?
+                .unwrap_or_default();
+            if parsed.iter().any(|t| t.api_keys.contains(&api_key)) || api_keys.contains(&api_key) {
+                return Err(format!(
+                    "http_api.tenants: an api key of {} is already in use",
+                    tenant.name
+                ));
+            }
+            api_keys.push(api_key);
+        }
+        parsed.push(TenantConfig {
+            name: tenant.name,
+            api_keys,
+            admin: tenant.admin.unwrap_or(false),
+            max_api_calls_per_day: tenant.max_api_calls_per_day,
+            max_events_per_day: tenant.max_events_per_day,
+        });
+    }
+    Ok(parsed)
+}
+
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LogConfigFile {
     pub ordinals_internals: Option<bool>,
     pub chainhook_internals: Option<bool>,
+    pub slow_block_threshold_ms: Option<u64>,
+    pub slow_block_profiles_dir: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct StorageConfigFile {
     pub working_dir: Option<String>,
     pub observers_working_dir: Option<String>,
+    pub sqlite_dir: Option<String>,
+    pub blocks_dir: Option<String>,
+    pub content_dir: Option<String>,
+    pub encryption_key: Option<String>,
+    pub encryption_key_env: Option<String>,
+    pub encryption_key_file: Option<String>,
+    pub store_content: Option<bool>,
+    pub content_types_allowed: Option<Vec<String>>,
+    pub content_types_denied: Option<Vec<String>>,
+    pub index_commitment_interval: Option<u64>,
+    pub correct_content_types: Option<bool>,
+    pub satribute_ranges_path: Option<String>,
+    pub index_scope: Option<String>,
+    pub content_mmap_threshold_bytes: Option<u64>,
+    pub content_cache_max_size_bytes: Option<u64>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct PredicatesApiConfigFile {
+    pub http_bind_address: Option<String>,
+    pub http_address: Option<String>,
     pub http_port: Option<u16>,
+    pub unix_socket: Option<String>,
+    pub unix_socket_mode: Option<String>,
     pub database_uri: Option<String>,
     pub display_logs: Option<bool>,
     pub disabled: Option<bool>,
+    pub upstream_api_url: Option<String>,
+    pub blocklist_path: Option<String>,
+    pub query_timeout_ms: Option<u64>,
+    pub tenants: Option<Vec<TenantConfigFile>>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfigFile {
+    pub name: String,
+    pub api_keys: Vec<String>,
+    pub admin: Option<bool>,
+    pub max_api_calls_per_day: Option<u64>,
+    pub max_events_per_day: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct SnapshotConfigFile {
     pub ordinals_url: Option<String>,
     pub brc20_url: Option<String>,
+    pub ordinals_mirror_urls: Option<Vec<String>>,
+    pub brc20_mirror_urls: Option<Vec<String>>,
+    pub ordinals_torrent: Option<String>,
+    pub brc20_torrent: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct MetaProtocolsConfigFile {
     pub brc20: Option<bool>,
+    pub sns: Option<bool>,
+    pub runes: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AlertsConfigFile {
+    pub webhook_url: Option<String>,
+    pub command: Option<String>,
+    pub max_tip_lag: Option<u64>,
+    pub max_consecutive_rpc_failures: Option<u32>,
+    pub max_reorg_depth: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ObserverLivenessConfigFile {
+    pub probe_interval_secs: Option<u64>,
+    pub max_consecutive_failures: Option<u32>,
+    pub pause_delivery: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EventTransformConfigFile {
+    pub path: String,
+    pub max_fuel: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PreviewsConfigFile {
+    pub sizes: Option<Vec<u32>>,
+    pub max_content_bytes: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ContentScanningConfigFile {
+    pub classifier_url: Option<String>,
+    pub command: Option<String>,
+    pub content_types: Option<Vec<String>>,
+    pub max_content_bytes: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub block_flagged: Option<bool>,
+    pub block_unscanned: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SalesAnalyticsConfigFile {
+    pub webhook_url: Option<String>,
+    pub command: Option<String>,
+    pub min_price_sats: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceConfigFile {
+    pub windows: Option<Vec<String>>,
+    pub max_api_calls_per_minute: Option<u64>,
+    pub interval_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AmendmentsConfigFile {
+    pub webhook_url: Option<String>,
+    pub command: Option<String>,
+    pub min_reorg_depth: Option<u64>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RollbacksConfigFile {
+    pub webhook_url: Option<String>,
+    pub command: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayLogConfigFile {
+    pub path: Option<String>,
+    pub max_size_mb: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ColdStorageConfigFile {
+    pub path: Option<String>,
+    pub older_than_blocks: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ResourcesConfigFile {
     pub ulimit: Option<usize>,
     pub cpu_core_available: Option<usize>,
     pub memory_available: Option<usize>,
     pub bitcoind_rpc_threads: Option<usize>,
     pub bitcoind_rpc_timeout: Option<u32>,
+    pub network_connect_timeout_secs: Option<u64>,
+    pub network_read_timeout_secs: Option<u64>,
+    pub webhook_timeout_secs: Option<u64>,
+    pub network_max_retries: Option<u32>,
+    pub network_retry_backoff_ms: Option<u64>,
+    pub network_proxy: Option<String>,
     pub expected_observers_count: Option<usize>,
     pub brc20_lru_cache_size: Option<usize>,
+    pub max_concurrent_jobs: Option<usize>,
+    pub job_retention_days: Option<u64>,
+    pub block_processing_max_retries: Option<u32>,
+    pub max_download_rate: Option<u64>,
+    pub heavy_network_window: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IngestionGuardConfigFile {
+    pub allowed_ips: Option<Vec<String>>,
+    pub internal_port: Option<u16>,
+    pub internal_port_firewalled: Option<bool>,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub tls_client_ca_path: Option<String>,
+    pub payload_secret: Option<String>,
+    pub payload_secret_file: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NetworkConfigFile {
     pub mode: String,
-    pub bitcoind_rpc_url: String,
-    pub bitcoind_rpc_username: String,
-    pub bitcoind_rpc_password: String,
+    pub bitcoind_rpc_url: Option<String>,
+    pub bitcoind_rpc_username: Option<String>,
+    pub bitcoind_rpc_password: Option<String>,
+    pub bitcoind_rpc_password_file: Option<String>,
     pub bitcoind_zmq_url: Option<String>,
+    pub stacks_node_rpc_url: Option<String>,
+    pub ingestion_port: Option<u16>,
+    pub ingestion_guard: Option<IngestionGuardConfigFile>,
+    pub prometheus_monitoring_bind_address: Option<String>,
+    pub prometheus_monitoring_address: Option<String>,
     pub prometheus_monitoring_port: Option<u16>,
+    pub prometheus_monitoring_unix_socket: Option<String>,
+    pub prometheus_monitoring_unix_socket_mode: Option<String>,
+    pub block_ingestion: Option<String>,
+    pub native_ingestion_poll_interval_ms: Option<u64>,
+    pub bitcoind_pruned: Option<bool>,
+    pub bitcoind_block_filters: Option<bool>,
+    pub first_inscription_height: Option<u64>,
+    pub jubilee_height: Option<u64>,
 }
@@ -1,10 +1,121 @@
 use ordhook::chainhook_sdk::types::BitcoinNetwork;
+use ordhook::config::{
+    Config, IndexerConfig, SnapshotConfig, DEFAULT_INGESTION_GUARD_INTERNAL_PORT,
+};
 
-pub fn generate_config(network: &BitcoinNetwork) -> String {
-    let network = format!("{:?}", network);
+/// Value of `network.mode` selecting `network`.
+fn network_mode(network: &IndexerConfig) -> &'static str {
+    match network.bitcoin_network {
+        BitcoinNetwork::Mainnet => "mainnet",
+        BitcoinNetwork::Testnet if network.testnet4 => "testnet4",
+        BitcoinNetwork::Testnet => "testnet",
+        BitcoinNetwork::Signet => "signet",
+        BitcoinNetwork::Regtest => "devnet",
+    }
+}
+
+fn mirror_urls_line(setting: &str, mirrors: &[String]) -> String {
+    if mirrors.is_empty() {
+        return format!("# {setting} = []");
+    }
+    let urls: Vec<String> = mirrors.iter().map(|url| format!("\"{url}\"")).collect();
+    format!("{setting} = [{}]", urls.join(", "))
+}
+
+/// Renders a commented config file, which settings are the defaults of `config`.
+pub fn generate_config(config: &Config) -> String {
+    let prometheus_monitoring_port = match config.network.prometheus_monitoring_port {
+        Some(port) => format!("prometheus_monitoring_port = {port}"),
+        None => "# prometheus_monitoring_port = 9153".to_string(),
+    };
+    let snapshot = match &config.snapshot {
+        SnapshotConfig::Download(urls) => format!(
+            r#"# Disable the following section if the state
+# must be built locally
+[snapshot]
+ordinals_url = "{}"
+{}
+# Mirrors serving the same archives. The fastest one is picked
+# when bootstrapping, and the download fails over to the next
+# one if interrupted:
+{}
+{}
+# Archives can also be fetched from the webseeds of a torrent
+# (url or path of the .torrent file), each piece being verified
+# against its hash. The urls above are used as a fallback:
+# ordinals_torrent = ""
+# brc20_torrent = """#,
+            urls.ordinals,
+            match urls.brc20 {
+                Some(ref url) => format!("brc20_url = \"{url}\""),
+                None => "# brc20_url = \"\"".to_string(),
+            },
+            mirror_urls_line("ordinals_mirror_urls", &urls.ordinals_mirrors),
+            mirror_urls_line("brc20_mirror_urls", &urls.brc20_mirrors),
+        ),
+        SnapshotConfig::Build => r#"# No snapshot is published for this network: the state is
+# built locally. Uncomment the following section to bootstrap
+# from archives instead
+# [snapshot]
+# ordinals_url = ""
+# brc20_url = ""
+# ordinals_mirror_urls = []
+# brc20_mirror_urls = []
+# ordinals_torrent = ""
+# brc20_torrent = """#
+            .to_string(),
+    };
     let conf = format!(
         r#"[storage]
+# Databases are stored in a subdirectory named after the
+# network (e.g. "ordhook/{network_name}"), and refuse to be opened
+# with another network
 working_dir = "ordhook"
+# Each store can live on its own volume, in a directory also
+# namespaced by network (defaults to working_dir):
+# sqlite_dir = "/mnt/ssd/ordhook"     # hord.sqlite, brc20.sqlite...
+# blocks_dir = "/mnt/hdd/ordhook"     # hord.rocksdb
+# content_dir = "/mnt/blobs/ordhook"  # cached contents, previews
+# SQLite databases, the blocks of hord.rocksdb, cached contents
+# and previews can be encrypted at rest (requires the `sqlcipher`
+# build feature).
+# The key is read from the following environment variable:
+# encryption_key_env = "ORDHOOK_STORAGE_KEY"
+# or from a file, or a reference to Vault, AWS Secrets Manager,
+# or a ciphertext decrypted with AWS KMS (using the AWS_*
+# environment variables):
+# encryption_key_file = "/run/secrets/ordhook_storage_key"
+# encryption_key = "aws-kms:<base64 ciphertext blob>"
+# Set to false to only index inscription numbers, ids and
+# locations: contents are then replaced by their sha256 hash.
+# store_content = true
+# Contents and metadata can also be stored for a subset of
+# MIME types only (inscriptions are numbered regardless):
+# content_types_allowed = ["text/*", "application/json"]
+# content_types_denied = ["text/html"]
+# Every N blocks, commit to the inscriptions state with a
+# Merkle root, and serve inclusion proofs via the Http Api:
+# index_commitment_interval = 1000
+# Deliver inscriptions with the MIME type detected from their
+# content when it does not match the declared one:
+# correct_content_types = false
+# Sat ranges of the satributes that can't be derived from sat
+# numbers (e.g. pizza sats), as {{"pizza": [[start, end], ..]}}:
+# satribute_ranges_path = "./satributes.json"
+# Sats tracked, beyond inscribed ones:
+# - "inscribed_only": inscribed sats only (cheapest)
+# - "inscribed_plus_rare": rare sats too, to list the rare sats
+#   of an address. Small on disk, but indexes from genesis
+# - "full": every sat range, to list the sats of an address.
+#   Grows with the UTXO set (tens of GB on mainnet) and slows
+#   indexing down
+# index_scope = "inscribed_only"
+# Contents larger than this (in bytes) are kept on disk once read
+# from bitcoind, and served memory-mapped. 0 disables:
+# content_mmap_threshold_bytes = 1048576
+# Size the contents kept on disk are capped at (in bytes), the
+# least recently written being removed first:
+# content_cache_max_size_bytes = 10737418240
 
 # The Http Api allows you to register / deregister
 # dynamically predicates.
@@ -12,39 +123,260 @@ working_dir = "ordhook"
 #
 # [http_api]
 # http_port = 20456
+# IPv4 and IPv6 addresses are supported:
+# http_address = "::"
+# or, as a single setting:
+# http_bind_address = "127.0.0.1:20456"
+# The API can also be exposed on a unix socket:
+# unix_socket = "/run/ordhook/control.sock"
+# unix_socket_mode = "660"
+# Lookups missing locally (e.g. while syncing) can be served from another ordhook API:
+# upstream_api_url = "https://ordhook.example.com"
+# Inscription ids and content hashes (sha256:<hex>) which contents
+# are not served, one per line:
+# blocklist_path = "./blocklist.txt"
+# Database statements of a read request running longer than this are interrupted,
+# and a 503 with a Retry-After header is returned:
+# query_timeout_ms = 10000
+# Teams sharing the API only see their own predicates and jobs, and must
+# send one of their keys (Authorization: Bearer <key>, or X-API-Key):
+# [[http_api.tenants]]
+# name = "indexers"
+# api_keys = ["vault:secret/ordhook#indexers_api_key"]
+# Optional daily quotas (UTC): requests past max_api_calls_per_day get a 429,
+# and events past max_events_per_day are not delivered until the next day.
+# Usage is reported by GET /ordhook/v1/usage.
+# max_api_calls_per_day = 100000
+# max_events_per_day = 1000000
+# [[http_api.tenants]]
+# name = "ops"
+# api_keys = ["ops-api-key"]
+# admin = true
 
 [network]
-mode = "{network}"
-bitcoind_rpc_url = "http://0.0.0.0:8332"
-bitcoind_rpc_username = "devnet"
-bitcoind_rpc_password = "devnet"
+mode = "{mode}"
+bitcoind_rpc_url = "{bitcoind_rpc_url}"
+bitcoind_rpc_username = "{bitcoind_rpc_username}"
+bitcoind_rpc_password = "{bitcoind_rpc_password}"
+# Secrets can also be read from a file, from a Vault KV
+# entry (using VAULT_ADDR and VAULT_TOKEN), or from AWS
+# Secrets Manager (using the AWS_* environment variables):
+# bitcoind_rpc_password_file = "/run/secrets/bitcoind_rpc_password"
+# bitcoind_rpc_password = "vault:secret/data/ordhook#bitcoind_rpc_password"
+# bitcoind_rpc_password = "aws-sm:ordhook/bitcoind#rpc_password"
 # Bitcoin block events can be received by Chainhook
 # either through a Bitcoin node's ZeroMQ interface,
 # or through the Stacks node. Zmq is being
 # used by default:
 bitcoind_zmq_url = "tcp://0.0.0.0:18543"
-# but stacks can also be used:
+# but stacks can also be used. Stacks settings are
+# optional and only needed in that case:
 # stacks_node_rpc_url = "http://0.0.0.0:20443"
+# ingestion_port = {ingestion_port}
+# Prometheus metrics are served on the following port, and
+# can also be served on a unix socket:
+{prometheus_monitoring_port}
+# prometheus_monitoring_address = "0.0.0.0"
+# prometheus_monitoring_unix_socket = "/run/ordhook/metrics.sock"
+# Blocks can also be polled from bitcoind's RPC interface and
+# indexed without going through Chainhook's event observer:
+# block_ingestion = "native"
+# native_ingestion_poll_interval_ms = 1000
+# With a pruned bitcoind, blocks are downloaded one at a time, and
+# the index must be more recent than bitcoind's prune height:
+# bitcoind_pruned = true
+# Rescans of wallet predicates can skip the blocks that can't
+# involve the wallet, using the compact block filters served by
+# bitcoind when started with blockfilterindex=1:
+# bitcoind_block_filters = true
+# In devnet and signet modes, the heights of the first inscription
+# and of the jubilee can be moved to follow custom networks:
+# first_inscription_height = 1
+# jubilee_height = 110
+# Only trusted sources should post to the ingestion port: ordhook
+# then listens on it, and forwards the allowed connections to the
+# event observer, moved to an internal port. The event observer
+# listens on every interface: the internal port must be firewalled,
+# which has to be confirmed for the guard to start.
+# Client certificates are required when a client CA is set:
+# [network.ingestion_guard]
+# allowed_ips = ["10.0.0.0/8", "192.168.1.12"]
+# internal_port = {ingestion_guard_internal_port}
+# internal_port_firewalled = true
+# tls_cert_path = "/etc/ordhook/ingestion.crt"
+# tls_key_path = "/etc/ordhook/ingestion.key"
+# tls_client_ca_path = "/etc/ordhook/stacks-node-ca.crt"
+# payload_secret_file = "/etc/ordhook/stacks-node-payload.secret"
 
 [resources]
-ulimit = 2048
-cpu_core_available = 16
-memory_available = 32
-bitcoind_rpc_threads = 4
-bitcoind_rpc_timeout = 15
-expected_observers_count = 1
-
-# Disable the following section if the state
-# must be built locally
-[snapshot]
-ordinals_url = "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-latest"
-brc20_url = "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-brc20-latest"
+ulimit = {ulimit}
+cpu_core_available = {cpu_core_available}
+memory_available = {memory_available}
+bitcoind_rpc_threads = {bitcoind_rpc_threads}
+bitcoind_rpc_timeout = {bitcoind_rpc_timeout}
+expected_observers_count = {expected_observers_count}
+# Entries of the BRC-20 balances and tokens kept in memory
+# brc20_lru_cache_size = {brc20_lru_cache_size}
+# Rescans and backups running at the same time, others are queued
+# max_concurrent_jobs = {max_concurrent_jobs}
+# Days finished jobs are listed for
+# job_retention_days = {job_retention_days}
+# Retries of a block which processing panics, before it is quarantined and skipped
+# block_processing_max_retries = {block_processing_max_retries}
+# Snapshot downloads can be limited to a number of bytes per
+# second, and only started within a daily window (UTC), so that
+# bootstrapping doesn't saturate shared links:
+# max_download_rate = 10485760
+# heavy_network_window = "01:00-06:00"
+# Timeouts and retries of outbound HTTP requests (bitcoind,
+# snapshot mirrors, webhooks), to raise on high-latency links.
+# bitcoind RPC calls are bounded by bitcoind_rpc_timeout, and
+# network_read_timeout_secs only applies to snapshot downloads:
+# network_connect_timeout_secs = {network_connect_timeout_secs}
+# network_read_timeout_secs = {network_read_timeout_secs}
+# webhook_timeout_secs = {webhook_timeout_secs}
+# network_max_retries = {network_max_retries}
+# network_retry_backoff_ms = {network_retry_backoff_ms}
+# Proxy all outbound connections go through, e.g. a local Tor
+# daemon:
+# network_proxy = "socks5h://127.0.0.1:9050"
+
+{snapshot}
+
+# Meta protocols indexed on top of inscriptions
+# [meta_protocols]
+# brc20 = {brc20}
+# sns = {sns}
+# runes = {runes}
 
 [logs]
-ordinals_internals = true
-chainhook_internals = true
+ordinals_internals = {ordinals_internals}
+chainhook_internals = {chainhook_internals}
+# Log a per-stage breakdown of the blocks taking longer than
+# the following threshold to be processed, and optionally
+# append their profiles to a flamegraph-compatible file
+# slow_block_threshold_ms = 5000
+# slow_block_profiles_dir = "./ordhook/profiles"
+
+# Uncomment the following section to get notified when
+# ordhook falls behind bitcoind, fails to reach bitcoind,
+# or processes a deep reorg
+# [alerts]
+# webhook_url = "http://localhost:3000/alerts"
+# command = "/usr/local/bin/page-oncall"
+# max_tip_lag = 6
+# max_consecutive_rpc_failures = 5
+# max_reorg_depth = 3
+
+# Uncomment the following section to probe the webhooks of the registered
+# predicates, and mark predicates degraded after consecutive failures
+# (requires the http_api)
+# [observer_liveness]
+# probe_interval_secs = 60
+# max_consecutive_failures = 5
+# pause_delivery = false
+
+# Uncomment the following section to run the transactions
+# delivered to predicates through a WebAssembly module
+# (requires the wasm-plugins feature)
+# [[event_transforms]]
+# path = "./plugins/enrich.wasm"
+# max_fuel = 10000000
+# max_memory_mb = 64
+
+# Uncomment the following section to render previews of image
+# inscriptions, served by the HTTP API (requires the previews feature)
+# [previews]
+# sizes = [128, 512]
+# max_content_bytes = 8388608
+
+# Uncomment the following section to scan inscription contents
+# with an external classifier (HTTP endpoint or local command)
+# and refuse to serve flagged contents through the HTTP API
+# [content_scanning]
+# classifier_url = "http://localhost:8080/classify"
+# command = "./scripts/scan.sh"
+# content_types = ["image/*", "text/html"]
+# max_content_bytes = 8388608
+# timeout_secs = 30
+# block_flagged = true
+# block_unscanned = false
+
+# Uncomment the following section to detect likely marketplace
+# sales of inscriptions, served by the HTTP API and delivered
+# as `sale_detected` events to a webhook and / or a command
+# [sales_analytics]
+# webhook_url = "http://localhost:3000/sales"
+# command = "/usr/local/bin/on-sale"
+# min_price_sats = 10000
+
+# Uncomment the following section to vacuum and analyze the SQLite
+# databases and compact the blocks db while the service runs, when
+# the API sees little traffic, and either the indexer is at the chain
+# tip or the time is within one of the windows (UTC)
+# [maintenance]
+# windows = ["02:00-04:00"]
+# max_api_calls_per_minute = 60
+# interval_secs = 86400
+
+# Uncomment the following section to deliver `amendment` events,
+# with the old and new values of the data already delivered that
+# got altered by a repair, a renumbering or a deep re-org
+# [amendments]
+# webhook_url = "http://localhost:3000/amendments"
+# command = "/usr/local/bin/on-amendment"
+# min_reorg_depth = 6
+
+# Uncomment the following section to deliver a `rollback` event
+# for every block rolled back by a re-org, listing the inscriptions
+# it revealed and the transfers it reverted, with their prior
+# locations
+# [rollbacks]
+# webhook_url = "http://localhost:3000/rollbacks"
+# command = "/usr/local/bin/on-rollback"
+
+# Uncomment the following section to record the block payloads
+# received from the Stacks node, compressed, and replay them with
+# `ordhook db replay` when reproducing an issue
+# [replay_log]
+# path = "/var/lib/ordhook/replay_log"
+# max_size_mb = 1024
+
+# Uncomment the following section to move raw blocks older than
+# `older_than_blocks` out of hord.rocksdb, to compressed files read
+# back on demand (defaults to "cold_blocks" in the blocks dir).
+# The path can be an object storage bucket mounted as a filesystem
+# [cold_storage]
+# path = "/mnt/cold/ordhook"
+# older_than_blocks = 52560
 "#,
-        network = network.to_lowercase(),
+        mode = network_mode(&config.network),
+        network_name = config.network.network_name(),
+        bitcoind_rpc_url = config.network.bitcoind_rpc_url,
+        bitcoind_rpc_username = config.network.bitcoind_rpc_username,
+        bitcoind_rpc_password = config.network.bitcoind_rpc_password,
+        ingestion_port = config.network.ingestion_port,
+        ingestion_guard_internal_port = DEFAULT_INGESTION_GUARD_INTERNAL_PORT,
+        ulimit = config.resources.ulimit,
+        cpu_core_available = config.resources.cpu_core_available,
+        memory_available = config.resources.memory_available,
+        bitcoind_rpc_threads = config.resources.bitcoind_rpc_threads,
+        bitcoind_rpc_timeout = config.resources.bitcoind_rpc_timeout,
+        network_connect_timeout_secs = config.resources.network_connect_timeout_secs,
+        network_read_timeout_secs = config.resources.network_read_timeout_secs,
+        webhook_timeout_secs = config.resources.webhook_timeout_secs,
+        network_max_retries = config.resources.network_max_retries,
+        network_retry_backoff_ms = config.resources.network_retry_backoff_ms,
+        expected_observers_count = config.resources.expected_observers_count,
+        brc20_lru_cache_size = config.resources.brc20_lru_cache_size,
+        max_concurrent_jobs = config.resources.max_concurrent_jobs,
+        job_retention_days = config.resources.job_retention_days,
+        block_processing_max_retries = config.resources.block_processing_max_retries,
+        brc20 = config.meta_protocols.brc20,
+        sns = config.meta_protocols.sns,
+        runes = config.meta_protocols.runes,
+        ordinals_internals = config.logs.ordinals_internals,
+        chainhook_internals = config.logs.chainhook_internals,
     );
     conf
 }
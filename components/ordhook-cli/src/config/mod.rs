@@ -1,2 +1,4 @@
+pub mod aws;
 pub mod file;
 pub mod generator;
+pub mod secrets;
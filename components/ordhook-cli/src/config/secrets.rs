@@ -0,0 +1,170 @@
+use std::fs;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Client as HttpClient;
+use serde_json::{json, Value};
+
+use super::aws::call_aws_json_api;
+
+const VAULT_REFERENCE_PREFIX: &str = "vault:";
+const AWS_SECRETS_MANAGER_REFERENCE_PREFIX: &str = "aws-sm:";
+const AWS_KMS_REFERENCE_PREFIX: &str = "aws-kms:";
+
+/// Resolves a secret that can either be provided inline, through a file (`<field>_file`), as a reference to a
+/// HashiCorp Vault KV entry (`vault:<path>#<key>`, using `VAULT_ADDR` and `VAULT_TOKEN` from the environment), or as
+/// a reference to an AWS Secrets Manager secret (`aws-sm:<secret id>`, or `aws-sm:<secret id>#<key>` for a key of a
+/// JSON secret), or as a ciphertext to decrypt with AWS KMS (`aws-kms:<base64 ciphertext blob>`). AWS references use
+/// the `AWS_*` credentials and region from the environment.
+pub fn resolve_secret(
+    field: &str,
+    value: Option<String>,
+    file_path: Option<String>,
+) -> Result<Option<String>, String> {
+    match (value, file_path) {
+        (Some(_), Some(_)) => Err(format!(
+            "{field} and {field}_file can not be used at the same time"
+        )),
+        (None, Some(file_path)) => {
+            let content = fs::read_to_string(&file_path)
+                .map_err(|e| format!("unable to read {field}_file {}: {}", file_path, e))?;
+            Ok(Some(
+                content.trim_end_matches(&['\r', '\n'][..]).to_string(),
+            ))
+        }
+        (Some(value), None) => {
+            if let Some(reference) = value.strip_prefix(VAULT_REFERENCE_PREFIX) {
+                return Ok(Some(fetch_vault_secret(field, reference)?));
+            }
+            if let Some(reference) = value.strip_prefix(AWS_SECRETS_MANAGER_REFERENCE_PREFIX) {
+                return Ok(Some(fetch_aws_secret(field, reference)?));
+            }
+            if let Some(ciphertext) = value.strip_prefix(AWS_KMS_REFERENCE_PREFIX) {
+                return Ok(Some(decrypt_aws_kms_secret(field, ciphertext)?));
+            }
+            Ok(Some(value))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Same as `resolve_secret`, but fails if the secret is not provided.
+pub fn resolve_required_secret(
+    field: &str,
+    value: Option<String>,
+    file_path: Option<String>,
+) -> Result<String, String> {
+    resolve_secret(field, value, file_path)?
+        .ok_or(format!("{field} or {field}_file must be provided"))
+}
+
+fn fetch_vault_secret(field: &str, reference: &str) -> Result<String, String> {
+    let Some((path, key)) = reference.split_once('#') else {
+        return Err(format!(
+            "{field}: vault reference must be formatted as vault:<path>#<key>"
+        ));
+    };
+    let vault_addr = std::env::var("VAULT_ADDR")
+        .map_err(|_| format!("{field}: VAULT_ADDR must be set to use vault references"))?;
+    let vault_token = std::env::var("VAULT_TOKEN")
+        .map_err(|_| format!("{field}: VAULT_TOKEN must be set to use vault references"))?;
+    let url = format!(
+        "{}/v1/{}",
+        vault_addr.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+
+    // Config loading is performed from within the async runtime, perform the request on a dedicated thread.
+    let moved_url = url.clone();
+    let response = std::thread::spawn(move || {
+        hiro_system_kit::nestable_block_on(async move {
+            let client = HttpClient::builder()
+                .build()
+                .map_err(|e| format!("unable to build http client: {}", e))?;
+            let res = client
+                .get(&moved_url)
+                .header("X-Vault-Token", vault_token)
+                .send()
+                .await
+                .map_err(|e| format!("unable to reach vault: {}", e))?;
+            if !res.status().is_success() {
+                return Err(format!("vault responded with status {}", res.status()));
+            }
+            res.json::<Value>()
+                .await
+                .map_err(|e| format!("unable to parse vault response: {}", e))
+        })
+    })
+    .join()
+    .map_err(|_| format!("{field}: unable to retrieve secret from {url}"))?
+    .map_err(|e| format!("{field}: {e}"))?;
+
+    // KV v2 engines nest the secret under data.data, KV v1 engines under data.
+    let data = &response["data"];
+    let secret = match data.get("data").and_then(|d| d.get(key)) {
+        Some(secret) => secret,
+        None => &data[key],
+    };
+    secret.as_str().map(|s| s.to_string()).ok_or(format!(
+        "{field}: key {key} not found in vault secret {path}"
+    ))
+}
+
+fn fetch_aws_secret(field: &str, reference: &str) -> Result<String, String> {
+    let (secret_id, key) = match reference.split_once('#') {
+        Some((secret_id, key)) => (secret_id, Some(key)),
+        None => (reference, None),
+    };
+    if secret_id.is_empty() {
+        return Err(format!(
+            "{field}: aws reference must be formatted as aws-sm:<secret id>[#<key>]"
+        ));
+    }
+    let response = call_aws_json_api(
+        field,
+        "secretsmanager",
+        "secretsmanager.GetSecretValue",
+        &json!({ "SecretId": secret_id }),
+    )?;
+    let secret = response["SecretString"].as_str().ok_or(format!(
+        "{field}: aws secret {secret_id} has no string value"
+    ))?;
+    let Some(key) = key else {
+        return Ok(secret.to_string());
+    };
+    serde_json::from_str::<Value>(secret)
+        .ok()
+        .and_then(|secret| secret[key].as_str().map(|s| s.to_string()))
+        .ok_or(format!(
+            "{field}: key {key} not found in aws secret {secret_id}"
+        ))
+}
+
+/// Binary plaintexts, such as KMS data keys, are returned as a `x'<hex>'` raw key, the form SQLCipher accepts them in.
+fn decrypt_aws_kms_secret(field: &str, ciphertext: &str) -> Result<String, String> {
+    if STANDARD.decode(ciphertext).is_err() {
+        return Err(format!(
+            "{field}: aws kms reference must be formatted as aws-kms:<base64 ciphertext blob>"
+        ));
+    }
+    let response = call_aws_json_api(
+        field,
+        "kms",
+        "TrentService.Decrypt",
+        &json!({ "CiphertextBlob": ciphertext }),
+    )?;
+    let plaintext = response["Plaintext"]
+        .as_str()
+        .and_then(|plaintext| STANDARD.decode(plaintext).ok())
+        .ok_or(format!("{field}: aws kms returned no plaintext"))?;
+    match String::from_utf8(plaintext) {
+        Ok(secret) => Ok(secret),
+        Err(e) => {
+            let hex: String = e
+                .into_bytes()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            Ok(format!("x'{hex}'"))
+        }
+    }
+}
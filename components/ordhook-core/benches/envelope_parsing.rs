@@ -0,0 +1,33 @@
+//! Parsing of inscription envelopes out of witnesses, run for every input of every indexed transaction.
+//!
+//! Run from `components/ordhook-core` with `cargo bench --bench envelope_parsing`.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ordhook::core::protocol::inscription_parsing::parse_inscriptions_from_witness;
+
+mod fixtures;
+
+fn bench_envelope_parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("envelope_parsing");
+    let witnesses = vec![
+        ("key_path", fixtures::key_path_witness()),
+        ("brc20_deploy", fixtures::brc20_deploy_witness()),
+        ("image_4kb", fixtures::image_witness(4 * 1024)),
+        ("image_390kb", fixtures::image_witness(390 * 1024)),
+    ];
+    for (name, witness) in witnesses.into_iter() {
+        let size = witness.iter().map(|element| element.len()).sum::<usize>();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(name, |b| {
+            // The parser takes ownership of the witness: cloning it is left out of the measurements.
+            b.iter_batched(
+                || witness.clone(),
+                |witness| parse_inscriptions_from_witness(0, black_box(witness), fixtures::TXID),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_envelope_parsing);
+criterion_main!(benches);
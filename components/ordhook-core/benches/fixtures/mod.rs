@@ -0,0 +1,80 @@
+//! Fixtures shared by the benchmarks, built deterministically so that runs on different commits compare.
+#![allow(dead_code)]
+
+use ordhook::hex;
+
+pub const TXID: &str = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+
+const INTERNAL_KEY: &str = "9e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746";
+const SIGNATURE: &str = "6c00eb3c4d35fedd257051333b4ca81d1a25a37a9af4891f1fec2869edd56b14180eafbda8851d63138a724c9b15384bc5f0536de658bd294d426a36212e6f08";
+
+const OP_FALSE: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_IF: u8 = 0x63;
+const OP_ENDIF: u8 = 0x68;
+const OP_CHECKSIG: u8 = 0xac;
+const MAX_PUSH_SIZE: usize = 520;
+
+fn push_bytes(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        len if len < OP_PUSHDATA1 as usize => script.push(len as u8),
+        len if len <= u8::MAX as usize => script.extend([OP_PUSHDATA1, len as u8]),
+        len => {
+            script.push(OP_PUSHDATA2);
+            script.extend((len as u16).to_le_bytes());
+        }
+    }
+    script.extend(data);
+}
+
+/// Witness of a script path spend revealing an inscription of `body` with `content_type`, with the body split in
+/// pushes of 520 bytes as `ord` does.
+pub fn inscription_witness(content_type: &str, body: &[u8]) -> Vec<Vec<u8>> {
+    let internal_key = hex::decode(INTERNAL_KEY).unwrap();
+    let mut tapscript = vec![];
+    push_bytes(&mut tapscript, &internal_key);
+    tapscript.extend([OP_CHECKSIG, OP_FALSE, OP_IF]);
+    push_bytes(&mut tapscript, b"ord");
+    push_bytes(&mut tapscript, &[1]);
+    push_bytes(&mut tapscript, content_type.as_bytes());
+    tapscript.push(OP_FALSE);
+    for chunk in body.chunks(MAX_PUSH_SIZE) {
+        push_bytes(&mut tapscript, chunk);
+    }
+    tapscript.push(OP_ENDIF);
+    let mut control_block = vec![0xc1];
+    control_block.extend(&internal_key);
+    vec![hex::decode(SIGNATURE).unwrap(), tapscript, control_block]
+}
+
+/// BRC-20 deploy, the most common kind of reveal.
+pub fn brc20_deploy_witness() -> Vec<Vec<u8>> {
+    let body = br#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#;
+    inscription_witness("text/plain;charset=utf-8", body)
+}
+
+/// Inscription of an image of `size` bytes, spanning many pushes.
+pub fn image_witness(size: usize) -> Vec<Vec<u8>> {
+    let body = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    inscription_witness("image/webp", &body)
+}
+
+/// Key path spend, the most common witness, which reveals nothing.
+pub fn key_path_witness() -> Vec<Vec<u8>> {
+    vec![hex::decode(SIGNATURE).unwrap()]
+}
+
+/// Values of the inputs or outputs of a transaction, spread between dust and a few BTC.
+pub fn values(count: usize, seed: u64) -> Vec<u64> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            // xorshift64, so that the fixtures don't depend on a random number generator.
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            546 + state % 500_000_000
+        })
+        .collect()
+}
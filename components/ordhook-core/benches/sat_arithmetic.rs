@@ -0,0 +1,93 @@
+//! Sat arithmetic run when tracking inscriptions and sat ranges through transactions.
+//!
+//! Run from `components/ordhook-core` with `cargo bench --bench sat_arithmetic`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ordhook::{
+    core::{
+        compute_next_satpoint_data, protocol::satributes::get_satributes, resolve_absolute_pointer,
+    },
+    db::sat_ranges::{distribute_sat_segments, SatSegment},
+};
+
+mod fixtures;
+
+/// Transaction shapes: a simple transfer, a batch payout, and a consolidation.
+const SHAPES: [(&str, usize, usize); 3] = [
+    ("1_in_2_out", 1, 2),
+    ("5_in_500_out", 5, 500),
+    ("500_in_1_out", 500, 1),
+];
+
+fn bench_satpoint_computation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("satpoint_computation");
+    for (name, inputs_count, outputs_count) in SHAPES.iter() {
+        let inputs = fixtures::values(*inputs_count, 1);
+        let total_in: u64 = inputs.iter().sum();
+        // Outputs spend 99% of the inputs, the rest going to fees.
+        let outputs = fixtures::values(*outputs_count, 2)
+            .iter()
+            .map(|v| v % (total_in / 100 * 99 / *outputs_count as u64).max(1))
+            .collect::<Vec<_>>();
+        // Points at a sat of the last input, the worst case for both walks.
+        let pointer = total_in - 1;
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let (input_index, relative_pointer) =
+                    resolve_absolute_pointer(black_box(&inputs), black_box(pointer));
+                compute_next_satpoint_data(input_index, &inputs, &outputs, relative_pointer, None)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_sat_ranges_distribution(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sat_ranges_distribution");
+    for (name, inputs_count, outputs_count) in SHAPES.iter() {
+        let mut start = 1_000_000_000_000;
+        let segments = fixtures::values(*inputs_count, 3)
+            .into_iter()
+            .enumerate()
+            .map(|(i, len)| {
+                start += len * 2;
+                match i % 3 {
+                    2 => SatSegment::Untracked(len),
+                    _ => SatSegment::Range(start, start + len),
+                }
+            })
+            .collect::<Vec<_>>();
+        let total_in: u64 = segments
+            .iter()
+            .map(|s| match s {
+                SatSegment::Range(start, end) => end - start,
+                SatSegment::Untracked(len) => *len,
+            })
+            .sum();
+        let output_value = (total_in / 100 * 99 / *outputs_count as u64).max(1);
+        let outputs = vec![output_value; *outputs_count];
+        group.bench_function(*name, |b| {
+            b.iter(|| distribute_sat_segments(black_box(segments.clone()), black_box(&outputs)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_satributes(c: &mut Criterion) {
+    // First sats of block #9, of the first halving and of the fourth halving.
+    let sats = [45_000_000_000, 1_050_000_000_000_000, 1_968_750_000_000_000];
+    c.bench_function("satributes", |b| {
+        b.iter(|| {
+            for sat in sats.iter() {
+                black_box(get_satributes(black_box(*sat)));
+            }
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_satpoint_computation,
+    bench_sat_ranges_distribution,
+    bench_satributes
+);
+criterion_main!(benches);
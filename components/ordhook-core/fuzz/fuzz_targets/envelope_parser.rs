@@ -0,0 +1,42 @@
+//! Feeds arbitrary witnesses to the inscription envelope parser, and to the parsers run on the inscriptions found.
+//!
+//! Run from `components/ordhook-core` with `cargo +nightly fuzz run envelope_parser`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ordhook::chainhook_sdk::bitcoin::hashes::{sha256, Hash};
+use ordhook::{
+    core::{
+        meta_protocols::brc20::parser::parse_brc20_operation,
+        protocol::{
+            content_sniffing::detect_inscription_content_type,
+            inscription_parsing::parse_inscriptions_from_witness,
+        },
+    },
+    hex,
+};
+
+const TXID: &str = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte tells how many elements the witness is split into, the tapscript being the second to last one.
+    let Some((elements_count, data)) = data.split_first() else {
+        return;
+    };
+    let elements_count = (*elements_count as usize % 4) + 1;
+    let element_size = data.len() / elements_count + 1;
+    let witness_bytes: Vec<Vec<u8>> = data.chunks(element_size).map(|e| e.to_vec()).collect();
+
+    let Some(inscriptions) = parse_inscriptions_from_witness(0, witness_bytes, TXID) else {
+        return;
+    };
+    for (mut reveal, inscription, content_hash) in inscriptions.into_iter() {
+        let content =
+            hex::decode(reveal.content_bytes.trim_start_matches("0x")).unwrap_or_default();
+        // Encoding and hashing share a single pass over the content: both must describe the same bytes.
+        assert_eq!(content.len(), reveal.content_length);
+        assert_eq!(sha256::Hash::hash(&content), content_hash);
+        detect_inscription_content_type(&mut reveal, &content, true);
+        let _ = parse_brc20_operation(&inscription);
+    }
+});
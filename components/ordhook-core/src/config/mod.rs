@@ -1,8 +1,15 @@
+pub mod validation;
+
+use crate::error::OrdhookError;
 use chainhook_sdk::observer::EventObserverConfig;
 use chainhook_sdk::types::{
     BitcoinBlockSignaling, BitcoinNetwork, StacksNetwork, StacksNodeConfig,
 };
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
 const DEFAULT_MAINNET_ORDINALS_SQLITE_ARCHIVE: &str =
     "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-latest";
@@ -10,12 +17,45 @@ const DEFAULT_MAINNET_BRC20_SQLITE_ARCHIVE: &str =
     "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-brc20-latest";
 
 pub const DEFAULT_INGESTION_PORT: u16 = 20455;
+pub const DEFAULT_INGESTION_GUARD_INTERNAL_PORT: u16 = 20457;
 pub const DEFAULT_CONTROL_PORT: u16 = 20456;
 pub const DEFAULT_ULIMIT: usize = 2048;
 pub const DEFAULT_MEMORY_AVAILABLE: usize = 8;
 pub const DEFAULT_BITCOIND_RPC_THREADS: usize = 4;
 pub const DEFAULT_BITCOIND_RPC_TIMEOUT: u32 = 15;
+pub const DEFAULT_NETWORK_CONNECT_TIMEOUT_SECS: u64 = 15;
+pub const DEFAULT_NETWORK_READ_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_WEBHOOK_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_NETWORK_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_NETWORK_RETRY_BACKOFF_MS: u64 = 1_000;
 pub const DEFAULT_BRC20_LRU_CACHE_SIZE: usize = 50_000;
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 2;
+pub const DEFAULT_JOB_RETENTION_DAYS: u64 = 7;
+pub const DEFAULT_BLOCK_PROCESSING_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_UNIX_SOCKET_MODE: u32 = 0o660;
+pub const DEFAULT_LISTENER_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
+pub const DEFAULT_ALERTS_MAX_TIP_LAG: u64 = 6;
+pub const DEFAULT_ALERTS_MAX_CONSECUTIVE_RPC_FAILURES: u32 = 5;
+pub const DEFAULT_ALERTS_MAX_REORG_DEPTH: u64 = 3;
+pub const DEFAULT_OBSERVER_LIVENESS_PROBE_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_OBSERVER_LIVENESS_MAX_CONSECUTIVE_FAILURES: u32 = 5;
+pub const DEFAULT_EVENT_TRANSFORM_MAX_FUEL: u64 = 10_000_000;
+pub const DEFAULT_EVENT_TRANSFORM_MAX_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_PREVIEW_SIZES: [u32; 2] = [128, 512];
+pub const DEFAULT_PREVIEW_MAX_CONTENT_BYTES: u64 = 8 * 1024 * 1024;
+pub const DEFAULT_CONTENT_SCAN_MAX_CONTENT_BYTES: u64 = 8 * 1024 * 1024;
+pub const DEFAULT_CONTENT_SCAN_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_SALES_MIN_PRICE_SATS: u64 = 10_000;
+pub const DEFAULT_NATIVE_INGESTION_POLL_INTERVAL_MS: u64 = 1_000;
+pub const DEFAULT_QUERY_TIMEOUT_MS: u64 = 10_000;
+pub const DEFAULT_MAINTENANCE_INTERVAL_SECS: u64 = 24 * 3600;
+pub const DEFAULT_MAINTENANCE_MAX_API_CALLS_PER_MINUTE: u64 = 60;
+pub const DEFAULT_AMENDMENTS_MIN_REORG_DEPTH: u64 = 6;
+pub const DEFAULT_REPLAY_LOG_MAX_SIZE_MB: u64 = 1024;
+/// About a year of blocks.
+pub const DEFAULT_COLD_STORAGE_OLDER_THAN_BLOCKS: u64 = 52_560;
+pub const DEFAULT_CONTENT_MMAP_THRESHOLD_BYTES: u64 = 1024 * 1024;
+pub const DEFAULT_CONTENT_CACHE_MAX_SIZE_BYTES: u64 = 10 * 1024 * 1024 * 1024;
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -26,23 +66,273 @@ pub struct Config {
     pub snapshot: SnapshotConfig,
     pub meta_protocols: MetaProtocolsConfig,
     pub logs: LogConfig,
+    pub alerts: Option<AlertsConfig>,
+    pub observer_liveness: Option<ObserverLivenessConfig>,
+    pub event_transforms: Vec<EventTransformConfig>,
+    pub previews: Option<PreviewsConfig>,
+    pub content_scanning: Option<ContentScanningConfig>,
+    pub sales_analytics: Option<SalesAnalyticsConfig>,
+    pub maintenance: Option<MaintenanceConfig>,
+    pub amendments: Option<AmendmentsConfig>,
+    pub rollbacks: Option<RollbacksConfig>,
+    pub replay_log: Option<ReplayLogConfig>,
+    pub cold_storage: Option<ColdStorageConfig>,
 }
 
 #[derive(Clone, Debug)]
 pub struct MetaProtocolsConfig {
     pub brc20: bool,
+    /// Index sats names style `<name>.<namespace>` registrations. Names are read from the stored inscription contents,
+    /// so `text/plain` and `application/json` contents must not be filtered out.
+    pub sns: bool,
+    /// Index runes etchings, mints, transfers and burns, from the activation height of runes on. The etching
+    /// commitments are checked against bitcoind. Balances are only complete when blocks are indexed from that height.
+    pub runes: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct LogConfig {
     pub ordinals_internals: bool,
     pub chainhook_internals: bool,
+    /// Blocks taking longer than this to be processed get their per-stage timings logged.
+    pub slow_block_threshold_ms: Option<u64>,
+    /// Directory where the profiles of slow blocks are appended, in a collapsed stack format.
+    pub slow_block_profiles_dir: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AlertsConfig {
+    /// URL that receives a JSON `POST` for every alert.
+    pub webhook_url: Option<String>,
+    /// Shell command executed for every alert, with the JSON alert available in `ORDHOOK_ALERT`.
+    pub command: Option<String>,
+    pub max_tip_lag: u64,
+    pub max_consecutive_rpc_failures: u32,
+    pub max_reorg_depth: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObserverLivenessConfig {
+    pub probe_interval_secs: u64,
+    /// Failed probes in a row after which a predicate is marked `degraded`.
+    pub max_consecutive_failures: u32,
+    /// Stop delivering to degraded predicates until their endpoint recovers. Delivery then resumes from the last block
+    /// delivered.
+    pub pause_delivery: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventTransformConfig {
+    /// Path of the WebAssembly module implementing the event transform ABI.
+    pub path: String,
+    /// Maximum number of wasm instructions executed per transaction transformed.
+    pub max_fuel: u64,
+    pub max_memory_bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PreviewsConfig {
+    /// Bounding boxes (in pixels) previews of image inscriptions get rendered at.
+    pub sizes: Vec<u32>,
+    /// Images larger than this are not previewed.
+    pub max_content_bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContentScanningConfig {
+    /// URL receiving a `POST` of every inscription content scanned, answering `{"flagged": bool, "labels": [..]}`.
+    pub classifier_url: Option<String>,
+    /// Shell command receiving every inscription content scanned on its standard input. Exits with 0 when the content
+    /// is clean, 1 when it is flagged, and can print labels on its standard output, one per line.
+    pub command: Option<String>,
+    /// MIME types (e.g. `image/*`) of the inscriptions scanned. All types are scanned when empty.
+    pub content_types: Vec<String>,
+    /// Contents larger than this are not scanned.
+    pub max_content_bytes: u64,
+    pub timeout_secs: u64,
+    /// Refuse to serve the contents flagged by the scanner.
+    pub block_flagged: bool,
+    /// Refuse to serve the contents that were not successfully scanned yet.
+    pub block_unscanned: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SalesAnalyticsConfig {
+    /// URL that receives a JSON `POST` for every `sale_detected` event.
+    pub webhook_url: Option<String>,
+    /// Shell command executed for every `sale_detected` event, with the JSON event available in `ORDHOOK_EVENT`.
+    pub command: Option<String>,
+    /// Payments below this are not reported as sales, to filter out transfers paying change back.
+    pub min_price_sats: u64,
+}
+
+/// Records the block payloads received from the event observer, so that a problematic sequence can be replayed with
+/// `ordhook db replay`.
+#[derive(Clone, Debug)]
+pub struct ReplayLogConfig {
+    /// Defaults to `replay_log` in the working directory.
+    pub path: Option<PathBuf>,
+    /// Oldest payloads are dropped once the compressed log exceeds this size.
+    pub max_size_mb: u64,
+}
+
+/// Moves the raw blocks lagging the tip of hord.rocksdb to compressed files, read back on demand by sat traversals,
+/// repairs and replays.
+#[derive(Clone, Debug)]
+pub struct ColdStorageConfig {
+    /// Defaults to `cold_blocks` in the blocks dir. Can be an object storage bucket mounted as a filesystem.
+    pub path: Option<PathBuf>,
+    /// Blocks at least this far below the tip of hord.rocksdb are moved to cold storage.
+    pub older_than_blocks: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct AmendmentsConfig {
+    /// URL that receives a JSON `POST` for every `amendment` event.
+    pub webhook_url: Option<String>,
+    /// Shell command executed for every `amendment` event, with the JSON event available in `ORDHOOK_EVENT`.
+    pub command: Option<String>,
+    /// Re-orgs rolling back at least this many blocks get amendments for the data they revert. Shallower re-orgs are
+    /// only reported by the rollbacks of the predicates.
+    pub min_reorg_depth: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RollbacksConfig {
+    /// URL that receives a JSON `POST` for every `rollback` event.
+    pub webhook_url: Option<String>,
+    /// Shell command executed for every `rollback` event, with the JSON event available in `ORDHOOK_EVENT`.
+    pub command: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MaintenanceConfig {
+    /// Daily windows (UTC) in which maintenance can run while the indexer is behind the chain tip. Outside of them,
+    /// maintenance only runs once the indexer has caught up with the tip.
+    pub windows: Vec<DailyWindow>,
+    /// API calls per minute above which traffic is too high for maintenance to run.
+    pub max_api_calls_per_minute: u64,
+    /// Minimum delay between two maintenance runs.
+    pub interval_secs: u64,
 }
 
 #[derive(Clone, Debug)]
 pub struct StorageConfig {
     pub working_dir: String,
     pub observers_working_dir: String,
+    /// Directory of the SQLite databases (hord.sqlite, brc20.sqlite, ...). Defaults to `working_dir`.
+    pub sqlite_dir: Option<String>,
+    /// Directory of the blocks db (hord.rocksdb). Defaults to `working_dir`.
+    pub blocks_dir: Option<String>,
+    /// Directory of the inscription contents and previews kept on disk. Defaults to `working_dir`.
+    pub content_dir: Option<String>,
+    /// Key used to encrypt SQLite databases at rest (requires the `sqlcipher` feature).
+    pub encryption_key: Option<String>,
+    /// When disabled, inscription contents are never kept: revealed inscriptions only carry the sha256 digest of their
+    /// content.
+    pub store_content: bool,
+    /// MIME types (e.g. `text/plain`, `image/*`) of the inscriptions getting their content and metadata stored. All
+    /// types are stored when empty.
+    pub content_types_allowed: Vec<String>,
+    /// MIME types of the inscriptions never getting their content and metadata stored. Takes precedence over
+    /// `content_types_allowed`.
+    pub content_types_denied: Vec<String>,
+    /// When set, a Merkle commitment over the inscriptions state is stored every `index_commitment_interval` blocks.
+    pub index_commitment_interval: Option<u64>,
+    /// When enabled, inscriptions which content does not match their declared MIME type are delivered with the type
+    /// detected from their content instead.
+    pub correct_content_types: bool,
+    /// JSON file of the sat ranges of the satributes that can't be derived from sat numbers, e.g. pizza sats.
+    pub satribute_ranges_path: Option<String>,
+    /// How much sat tracking state is maintained on top of the inscriptions index.
+    pub index_scope: IndexScope,
+    /// Contents read from bitcoind larger than this are kept in `<content_dir>/contents` and served memory-mapped,
+    /// instead of being copied in memory for every request. Disabled when 0.
+    pub content_mmap_threshold_bytes: u64,
+    /// Size the contents kept in `<content_dir>/contents` are capped at, the least recently written being removed first.
+    pub content_cache_max_size_bytes: u64,
+}
+
+/// Sats tracked by the index. Scopes beyond `InscribedOnly` keep sat_ranges.sqlite, indexed from genesis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexScope {
+    /// Only inscribed sats are tracked.
+    InscribedOnly,
+    /// Rare sats are tracked too, one row per rare sat held: storage stays small, but every block since genesis gets
+    /// processed and each input spent costs a lookup.
+    InscribedPlusRare,
+    /// Every sat range is tracked: storage grows with the UTXO set (tens of GB on mainnet) and each output created
+    /// costs a write per range.
+    Full,
+}
+
+impl IndexScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexScope::InscribedOnly => "inscribed_only",
+            IndexScope::InscribedPlusRare => "inscribed_plus_rare",
+            IndexScope::Full => "full",
+        }
+    }
+
+    /// Returns true if sat_ranges.sqlite is maintained.
+    pub fn tracks_sat_ranges(&self) -> bool {
+        *self != IndexScope::InscribedOnly
+    }
+}
+
+impl FromStr for IndexScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<IndexScope, String> {
+        match scope {
+            "inscribed_only" => Ok(IndexScope::InscribedOnly),
+            "inscribed_plus_rare" => Ok(IndexScope::InscribedPlusRare),
+            "full" => Ok(IndexScope::Full),
+            _ => Err(format!("index scope {scope} not supported")),
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Returns true if the content and metadata of an inscription of the given content type should be kept. Inscriptions
+    /// are numbered and tracked regardless.
+    pub fn should_store_content(&self, content_type: &str) -> bool {
+        if !self.store_content {
+            return false;
+        }
+        let matches = |patterns: &Vec<String>| {
+            patterns
+                .iter()
+                .any(|pattern| content_type_matches(pattern, content_type))
+        };
+        if matches(&self.content_types_denied) {
+            return false;
+        }
+        self.content_types_allowed.is_empty() || matches(&self.content_types_allowed)
+    }
+}
+
+/// Matches a content type against a MIME pattern, ignoring parameters (`text/plain;charset=utf-8` matches
+/// `text/plain`). Patterns can use a `*` wildcard as subtype (`image/*`) or to match any type.
+pub fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let pattern = pattern.trim().to_lowercase();
+    if pattern == "*" || pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(media_type) => essence
+            .split_once('/')
+            .map(|(t, _)| t == media_type)
+            .unwrap_or(false),
+        None => essence == pattern,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -53,14 +343,70 @@ pub enum PredicatesApi {
 
 #[derive(Clone, Debug)]
 pub struct PredicatesApiConfig {
+    pub http_address: IpAddr,
     pub http_port: u16,
+    pub unix_socket: Option<UnixSocketConfig>,
     pub display_logs: bool,
+    /// Base URL of an ordhook API queried when a lookup misses locally, e.g. while the index is still syncing.
+    pub upstream_api_url: Option<String>,
+    /// File listing the inscription ids and content hashes (`sha256:<hex>`) which contents are not served, one per
+    /// line. Entries can also be managed through the API.
+    pub blocklist_path: Option<String>,
+    /// Teams sharing the API. When set, predicates and jobs are only visible to the tenant which created them, and
+    /// requests must carry one of the tenant's API keys.
+    pub tenants: Vec<TenantConfig>,
+    /// Time after which the database statements of a read request are interrupted, and a 503 returned.
+    pub query_timeout_ms: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TenantConfig {
+    pub name: String,
+    pub api_keys: Vec<String>,
+    /// Admin tenants see the predicates and jobs of every tenant, and can use the control endpoints.
+    pub admin: bool,
+    /// Requests past this number are rejected with 429 until the next day (UTC).
+    pub max_api_calls_per_day: Option<u64>,
+    /// Transactions past this number are not delivered to the predicates of the tenant until the next day (UTC).
+    pub max_events_per_day: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UnixSocketConfig {
+    pub path: PathBuf,
+    pub mode: u32,
 }
 
 #[derive(Clone, Debug)]
 pub struct SnapshotConfigDownloadUrls {
     pub ordinals: String,
     pub brc20: Option<String>,
+    /// Mirrors serving the same archives as `ordinals`, tried when it is slower or fails.
+    pub ordinals_mirrors: Vec<String>,
+    pub brc20_mirrors: Vec<String>,
+    /// `.torrent` files (url or path) which webseeds serve the archives, verified piece by piece. Preferred over the
+    /// urls above, which remain the fallback.
+    pub ordinals_torrent: Option<String>,
+    pub brc20_torrent: Option<String>,
+}
+
+impl SnapshotConfigDownloadUrls {
+    /// The ordinals snapshot url, followed by its mirrors.
+    pub fn ordinals_urls(&self) -> Vec<String> {
+        let mut urls = vec![self.ordinals.clone()];
+        urls.extend(self.ordinals_mirrors.iter().cloned());
+        urls
+    }
+
+    /// The brc20 snapshot url, followed by its mirrors. Empty if no brc20 snapshot url is configured.
+    pub fn brc20_urls(&self) -> Vec<String> {
+        let Some(ref brc20) = self.brc20 else {
+            return vec![];
+        };
+        let mut urls = vec![brc20.clone()];
+        urls.extend(self.brc20_mirrors.iter().cloned());
+        urls
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -82,11 +428,128 @@ pub struct UrlConfig {
 #[derive(Debug, Clone)]
 pub struct IndexerConfig {
     pub bitcoin_network: BitcoinNetwork,
+    /// Whether the testnet followed is testnet4. chainhook-sdk's `BitcoinNetwork` can't represent testnet4, which is
+    /// indexed as `BitcoinNetwork::Testnet`: both share address formats, but not block heights.
+    pub testnet4: bool,
     pub bitcoind_rpc_url: String,
     pub bitcoind_rpc_username: String,
     pub bitcoind_rpc_password: String,
-    pub bitcoin_block_signaling: BitcoinBlockSignaling,
+    /// Source the event observer receives new blocks from: bitcoind's ZeroMQ interface, or a Stacks node. Not required
+    /// with native block ingestion.
+    pub bitcoin_block_signaling: Option<BitcoinBlockSignaling>,
+    pub ingestion_port: u16,
+    /// Restricts who can post payloads to the ingestion port. Not set, the port is open to anyone on the network.
+    pub ingestion_guard: Option<IngestionGuardConfig>,
+    pub prometheus_monitoring_address: IpAddr,
     pub prometheus_monitoring_port: Option<u16>,
+    pub prometheus_monitoring_unix_socket: Option<UnixSocketConfig>,
+    pub block_ingestion: BlockIngestion,
+    /// Whether bitcoind is pruned. Blocks are then downloaded and processed one at a time, and blocks below bitcoind's
+    /// prune height are never requested.
+    pub bitcoind_pruned: bool,
+    /// Whether bitcoind serves compact block filters (`blockfilterindex=1`). Rescans of wallet predicates then skip the
+    /// blocks which filter can't match any address of the wallet, instead of downloading them.
+    pub bitcoind_block_filters: bool,
+    /// Height of the first block that can hold inscriptions. Only configurable on regtest and signet, to follow custom
+    /// networks: not set, the consensus height of the network is used.
+    pub first_inscription_height: Option<u64>,
+    /// Height from which cursed inscriptions are numbered like blessed ones by the jubilee numbering. Only
+    /// configurable on regtest and signet: not set, the consensus height of the network is used.
+    pub jubilee_height: Option<u64>,
+}
+
+impl IndexerConfig {
+    pub fn network_name(&self) -> &'static str {
+        network_name(&self.bitcoin_network, self.testnet4)
+    }
+}
+
+/// How new blocks reach ordhook once the index has caught up with the chain tip.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockIngestion {
+    /// Blocks are received by chainhook-sdk's event observer, through bitcoind's ZeroMQ interface or the Stacks node.
+    Observer,
+    /// Bitcoind's chain tip is polled over RPC, and new blocks are downloaded and indexed by ordhook's own pipeline.
+    /// Re-orgs are detected by comparing the hashes of the blocks indexed with the ones of bitcoind's best chain.
+    Native { poll_interval_ms: u64 },
+}
+
+/// Protection of the ingestion port. ordhook listens on `ingestion_port` itself, over IPv4 and IPv6, and forwards the
+/// connections it accepts to chainhook-sdk's event observer through the loopback interface. The observer is moved to
+/// `internal_port`, but still listens on every interface since chainhook-sdk doesn't let its address be configured:
+/// the internal port has to be firewalled, which the operator confirms with `internal_port_firewalled`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngestionGuardConfig {
+    /// Ranges of the addresses allowed to connect. Any address is allowed when empty.
+    pub allowed_ips: Vec<IpRange>,
+    pub tls: Option<IngestionTlsConfig>,
+    pub internal_port: u16,
+    /// Set by the operator once `internal_port` is unreachable from the network. The configuration is rejected
+    /// otherwise, since the guard could be bypassed.
+    pub internal_port_firewalled: bool,
+    /// Secret authenticating the payloads of the Stacks node, sent as a bearer token or as the key of an
+    /// `X-Signature` HMAC-SHA256 of the timestamped body. Payloads are not inspected when missing.
+    pub payload_secret: Option<String>,
+}
+
+impl IngestionGuardConfig {
+    pub fn is_allowed(&self, addr: &IpAddr) -> bool {
+        self.allowed_ips.is_empty() || self.allowed_ips.iter().any(|range| range.contains(addr))
+    }
+}
+
+/// TLS termination of the ingestion port. Clients must present a certificate signed by `client_ca_path` when set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngestionTlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>,
+}
+
+/// Range of IP addresses, in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpRange {
+    pub network: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl IpRange {
+    /// Parses a range formatted as `address/prefix_len`, or a single address.
+    pub fn parse(range: &str) -> Result<IpRange, String> {
+        let invalid = || format!("invalid ip range {range}, expected an address or address/prefix");
+        let (address, prefix_len) = match range.trim().split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (range.trim(), None),
+        };
+        let network = IpAddr::from_str(address).map_err(|_| invalid())?;
+        let max_prefix_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+        Ok(IpRange {
+            network,
+            prefix_len,
+        })
+    }
+
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let (network, addr, bits) = match (self.network, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                (u32::from(network) as u128, u32::from(addr) as u128, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => (u128::from(network), u128::from(addr), 128),
+            _ => return false,
+        };
+        let host_bits = bits - self.prefix_len as u32;
+        network.checked_shr(host_bits).unwrap_or(0) == addr.checked_shr(host_bits).unwrap_or(0)
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -95,9 +558,95 @@ pub struct ResourcesConfig {
     pub cpu_core_available: usize,
     pub memory_available: usize,
     pub bitcoind_rpc_threads: usize,
+    /// Seconds a bitcoind RPC call can take, including the download of a block.
     pub bitcoind_rpc_timeout: u32,
+    /// Seconds to establish the connection of an outbound HTTP request (bitcoind, snapshot mirrors, webhooks).
+    pub network_connect_timeout_secs: u64,
+    /// Seconds a snapshot download can go without receiving data before it is resumed.
+    pub network_read_timeout_secs: u64,
+    /// Seconds a webhook delivery can take.
+    pub webhook_timeout_secs: u64,
+    /// Retries of a failed outbound HTTP request: block download, snapshot mirror, webhook delivery.
+    pub network_max_retries: u32,
+    /// Delay before the first retry, doubled on each of the following ones.
+    pub network_retry_backoff_ms: u64,
+    /// Proxy all outbound connections go through (bitcoind, snapshot mirrors, webhooks), e.g.
+    /// `socks5h://127.0.0.1:9050` for Tor.
+    pub network_proxy: Option<String>,
     pub expected_observers_count: usize,
     pub brc20_lru_cache_size: usize,
+    /// Background jobs (rescans, backups) running at the same time. Jobs submitted past this limit are queued.
+    pub max_concurrent_jobs: usize,
+    /// Days finished jobs are kept in observers.sqlite.
+    pub job_retention_days: u64,
+    /// Retries of a block which processing panicked, after which the block is quarantined and skipped.
+    pub block_processing_max_retries: u32,
+    /// Bytes per second snapshot downloads are limited to. Unlimited when not set.
+    pub max_download_rate: Option<u64>,
+    /// Time of the day (UTC) during which snapshot downloads are allowed to start.
+    pub heavy_network_window: Option<DailyWindow>,
+}
+
+/// Daily time window, in minutes since midnight UTC. Windows ending before they start wrap around midnight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DailyWindow {
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+const MAX_NETWORK_RETRY_BACKOFF_MS: u64 = 60_000;
+
+impl DailyWindow {
+    /// Parses a window formatted as `HH:MM-HH:MM`.
+    pub fn parse(window: &str) -> Result<DailyWindow, String> {
+        let parse_time = |time: &str| -> Option<u16> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let hours: u16 = hours.parse().ok()?;
+            let minutes: u16 = minutes.parse().ok()?;
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+        let invalid = || format!("invalid window {window}, expected HH:MM-HH:MM");
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let start_minute = parse_time(start).ok_or_else(invalid)?;
+        let end_minute = parse_time(end).ok_or_else(invalid)?;
+        if start_minute == end_minute {
+            return Err(format!("window {window} is empty"));
+        }
+        Ok(DailyWindow {
+            start_minute,
+            end_minute,
+        })
+    }
+
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        match self.start_minute < self.end_minute {
+            true => minute_of_day >= self.start_minute && minute_of_day < self.end_minute,
+            false => minute_of_day >= self.start_minute || minute_of_day < self.end_minute,
+        }
+    }
+
+    /// Minutes to wait from `minute_of_day` until the window opens, 0 if it is open.
+    pub fn minutes_until_open(&self, minute_of_day: u16) -> u16 {
+        if self.contains(minute_of_day) {
+            return 0;
+        }
+        (self.start_minute + MINUTES_PER_DAY - minute_of_day) % MINUTES_PER_DAY
+    }
+}
+
+impl fmt::Display for DailyWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start_minute / 60,
+            self.start_minute % 60,
+            self.end_minute / 60,
+            self.end_minute % 60
+        )
+    }
 }
 
 impl ResourcesConfig {
@@ -107,6 +656,18 @@ impl ResourcesConfig {
         // handling the "reduce" step.
         self.cpu_core_available.saturating_sub(2).max(1)
     }
+
+    pub fn network_connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.network_connect_timeout_secs)
+    }
+
+    /// Delay before the retry `attempt` (starting at 1) of an outbound HTTP request, capped to a minute.
+    pub fn network_retry_backoff(&self, attempt: u32) -> Duration {
+        let backoff_ms = self
+            .network_retry_backoff_ms
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(16));
+        Duration::from_millis(backoff_ms.min(MAX_NETWORK_RETRY_BACKOFF_MS))
+    }
 }
 
 impl Config {
@@ -117,24 +678,53 @@ impl Config {
         }
     }
 
+    /// Port chainhook-sdk's event observer listens on: moved to the internal port when the ingestion port is guarded.
+    pub fn observer_ingestion_port(&self) -> u16 {
+        match self.network.ingestion_guard {
+            Some(ref guard) => guard.internal_port,
+            None => self.network.ingestion_port,
+        }
+    }
+
     pub fn get_event_observer_config(&self) -> EventObserverConfig {
         EventObserverConfig {
             bitcoin_rpc_proxy_enabled: true,
             chainhook_config: None,
-            ingestion_port: DEFAULT_INGESTION_PORT,
+            ingestion_port: self.observer_ingestion_port(),
             bitcoind_rpc_username: self.network.bitcoind_rpc_username.clone(),
             bitcoind_rpc_password: self.network.bitcoind_rpc_password.clone(),
             bitcoind_rpc_url: self.network.bitcoind_rpc_url.clone(),
-            bitcoin_block_signaling: self.network.bitcoin_block_signaling.clone(),
+            // Only read once blocks are observed, which `expected_bitcoin_block_signaling` is checked for.
+            bitcoin_block_signaling: match self.network.bitcoin_block_signaling.clone() {
+                Some(BitcoinBlockSignaling::Stacks(stacks_node_config)) => {
+                    BitcoinBlockSignaling::Stacks(StacksNodeConfig {
+                        ingestion_port: self.observer_ingestion_port(),
+                        ..stacks_node_config
+                    })
+                }
+                Some(signaling) => signaling,
+                None => BitcoinBlockSignaling::ZeroMQ(String::new()),
+            },
             display_logs: false,
             cache_path: self.storage.working_dir.clone(),
             bitcoin_network: self.network.bitcoin_network.clone(),
-            stacks_network: StacksNetwork::Devnet,
+            stacks_network: match self.network.bitcoin_network {
+                BitcoinNetwork::Mainnet => StacksNetwork::Mainnet,
+                BitcoinNetwork::Testnet | BitcoinNetwork::Signet => StacksNetwork::Testnet,
+                BitcoinNetwork::Regtest => StacksNetwork::Devnet,
+            },
             prometheus_monitoring_port: None,
             data_handler_tx: None,
         }
     }
 
+    pub fn expected_bitcoin_block_signaling(&self) -> Result<BitcoinBlockSignaling, OrdhookError> {
+        self.network.bitcoin_block_signaling.clone().ok_or(OrdhookError::Config(
+            "network.bitcoind_zmq_url or network.stacks_node_rpc_url is required to observe blocks, unless network.block_ingestion is native"
+                .to_string(),
+        ))
+    }
+
     pub fn should_bootstrap_through_download(&self) -> bool {
         match &self.snapshot {
             SnapshotConfig::Build => false,
@@ -149,12 +739,75 @@ impl Config {
         }
     }
 
+    pub fn upstream_api_url(&self) -> Option<&str> {
+        match self.http_api {
+            PredicatesApi::On(ref config) => config.upstream_api_url.as_deref(),
+            PredicatesApi::Off => None,
+        }
+    }
+
+    pub fn blocklist_path(&self) -> Option<&str> {
+        match self.http_api {
+            PredicatesApi::On(ref config) => config.blocklist_path.as_deref(),
+            PredicatesApi::Off => None,
+        }
+    }
+
+    pub fn query_timeout_ms(&self) -> u64 {
+        match self.http_api {
+            PredicatesApi::On(ref config) => config.query_timeout_ms,
+            PredicatesApi::Off => DEFAULT_QUERY_TIMEOUT_MS,
+        }
+    }
+
     pub fn expected_cache_path(&self) -> PathBuf {
         let mut destination_path = PathBuf::new();
         destination_path.push(&self.storage.working_dir);
         destination_path
     }
 
+    /// Directory of the SQLite databases: `storage.sqlite_dir`, or the working dir.
+    pub fn expected_sqlite_path(&self) -> PathBuf {
+        match self.storage.sqlite_dir {
+            Some(ref sqlite_dir) => PathBuf::from(sqlite_dir),
+            None => self.expected_cache_path(),
+        }
+    }
+
+    /// Directory of the blocks db: `storage.blocks_dir`, or the working dir.
+    pub fn expected_blocks_path(&self) -> PathBuf {
+        match self.storage.blocks_dir {
+            Some(ref blocks_dir) => PathBuf::from(blocks_dir),
+            None => self.expected_cache_path(),
+        }
+    }
+
+    /// Directory of the inscription contents and previews: `storage.content_dir`, or the working dir.
+    pub fn expected_content_path(&self) -> PathBuf {
+        match self.storage.content_dir {
+            Some(ref content_dir) => PathBuf::from(content_dir),
+            None => self.expected_cache_path(),
+        }
+    }
+
+    /// Directory of the replay log, `None` when payloads are not recorded.
+    pub fn expected_replay_log_path(&self) -> Option<PathBuf> {
+        let replay_log = self.replay_log.as_ref()?;
+        match replay_log.path {
+            Some(ref path) => Some(path.clone()),
+            None => Some(self.expected_cache_path().join("replay_log")),
+        }
+    }
+
+    /// Directory of the raw blocks moved to cold storage, `None` when blocks are all kept in hord.rocksdb.
+    pub fn expected_cold_storage_path(&self) -> Option<PathBuf> {
+        let cold_storage = self.cold_storage.as_ref()?;
+        match cold_storage.path {
+            Some(ref path) => Some(path.clone()),
+            None => Some(self.expected_blocks_path().join("cold_blocks")),
+        }
+    }
+
     pub fn expected_observers_cache_path(&self) -> PathBuf {
         let mut destination_path = PathBuf::new();
         destination_path.push(&self.storage.observers_working_dir);
@@ -164,8 +817,21 @@ impl Config {
     pub fn devnet_default() -> Config {
         Config {
             storage: StorageConfig {
-                working_dir: default_cache_path(),
+                working_dir: namespaced_working_dir(&default_cache_path(), "regtest"),
                 observers_working_dir: default_observers_cache_path(),
+                sqlite_dir: None,
+                blocks_dir: None,
+                content_dir: None,
+                encryption_key: None,
+                store_content: true,
+                content_types_allowed: vec![],
+                content_types_denied: vec![],
+                index_commitment_interval: None,
+                correct_content_types: false,
+                satribute_ranges_path: None,
+                index_scope: IndexScope::InscribedOnly,
+                content_mmap_threshold_bytes: DEFAULT_CONTENT_MMAP_THRESHOLD_BYTES,
+                content_cache_max_size_bytes: DEFAULT_CONTENT_CACHE_MAX_SIZE_BYTES,
             },
             http_api: PredicatesApi::Off,
             snapshot: SnapshotConfig::Build,
@@ -175,32 +841,83 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                network_connect_timeout_secs: DEFAULT_NETWORK_CONNECT_TIMEOUT_SECS,
+                network_read_timeout_secs: DEFAULT_NETWORK_READ_TIMEOUT_SECS,
+                webhook_timeout_secs: DEFAULT_WEBHOOK_TIMEOUT_SECS,
+                network_max_retries: DEFAULT_NETWORK_MAX_RETRIES,
+                network_retry_backoff_ms: DEFAULT_NETWORK_RETRY_BACKOFF_MS,
+                network_proxy: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                max_concurrent_jobs: DEFAULT_MAX_CONCURRENT_JOBS,
+                job_retention_days: DEFAULT_JOB_RETENTION_DAYS,
+                block_processing_max_retries: DEFAULT_BLOCK_PROCESSING_MAX_RETRIES,
+                max_download_rate: None,
+                heavy_network_window: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18443".into(),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
-                bitcoin_block_signaling: BitcoinBlockSignaling::Stacks(
+                bitcoin_block_signaling: Some(BitcoinBlockSignaling::Stacks(
                     StacksNodeConfig::default_localhost(DEFAULT_INGESTION_PORT),
-                ),
+                )),
                 bitcoin_network: BitcoinNetwork::Regtest,
+                testnet4: false,
+                ingestion_port: DEFAULT_INGESTION_PORT,
+                ingestion_guard: None,
+                prometheus_monitoring_address: DEFAULT_LISTENER_ADDRESS,
                 prometheus_monitoring_port: None,
+                prometheus_monitoring_unix_socket: None,
+                block_ingestion: BlockIngestion::Observer,
+                bitcoind_pruned: false,
+                bitcoind_block_filters: false,
+                first_inscription_height: None,
+                jubilee_height: None,
             },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                slow_block_threshold_ms: None,
+                slow_block_profiles_dir: None,
+            },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                sns: false,
+                runes: false,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            alerts: None,
+            observer_liveness: None,
+            event_transforms: vec![],
+            previews: None,
+            content_scanning: None,
+            sales_analytics: None,
+            maintenance: None,
+            amendments: None,
+            rollbacks: None,
+            replay_log: None,
+            cold_storage: None,
         }
     }
 
     pub fn testnet_default() -> Config {
         Config {
             storage: StorageConfig {
-                working_dir: default_cache_path(),
+                working_dir: namespaced_working_dir(&default_cache_path(), "testnet"),
                 observers_working_dir: default_observers_cache_path(),
+                sqlite_dir: None,
+                blocks_dir: None,
+                content_dir: None,
+                encryption_key: None,
+                store_content: true,
+                content_types_allowed: vec![],
+                content_types_denied: vec![],
+                index_commitment_interval: None,
+                correct_content_types: false,
+                satribute_ranges_path: None,
+                index_scope: IndexScope::InscribedOnly,
+                content_mmap_threshold_bytes: DEFAULT_CONTENT_MMAP_THRESHOLD_BYTES,
+                content_cache_max_size_bytes: DEFAULT_CONTENT_CACHE_MAX_SIZE_BYTES,
             },
             http_api: PredicatesApi::Off,
             snapshot: SnapshotConfig::Build,
@@ -210,37 +927,116 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                network_connect_timeout_secs: DEFAULT_NETWORK_CONNECT_TIMEOUT_SECS,
+                network_read_timeout_secs: DEFAULT_NETWORK_READ_TIMEOUT_SECS,
+                webhook_timeout_secs: DEFAULT_WEBHOOK_TIMEOUT_SECS,
+                network_max_retries: DEFAULT_NETWORK_MAX_RETRIES,
+                network_retry_backoff_ms: DEFAULT_NETWORK_RETRY_BACKOFF_MS,
+                network_proxy: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                max_concurrent_jobs: DEFAULT_MAX_CONCURRENT_JOBS,
+                job_retention_days: DEFAULT_JOB_RETENTION_DAYS,
+                block_processing_max_retries: DEFAULT_BLOCK_PROCESSING_MAX_RETRIES,
+                max_download_rate: None,
+                heavy_network_window: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18332".into(),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
-                bitcoin_block_signaling: BitcoinBlockSignaling::Stacks(
-                    StacksNodeConfig::default_localhost(DEFAULT_INGESTION_PORT),
-                ),
+                bitcoin_block_signaling: None,
                 bitcoin_network: BitcoinNetwork::Testnet,
+                testnet4: false,
+                ingestion_port: DEFAULT_INGESTION_PORT,
+                ingestion_guard: None,
+                prometheus_monitoring_address: DEFAULT_LISTENER_ADDRESS,
                 prometheus_monitoring_port: Some(9153),
+                prometheus_monitoring_unix_socket: None,
+                block_ingestion: BlockIngestion::Observer,
+                bitcoind_pruned: false,
+                bitcoind_block_filters: false,
+                first_inscription_height: None,
+                jubilee_height: None,
             },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                slow_block_threshold_ms: None,
+                slow_block_profiles_dir: None,
+            },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                sns: false,
+                runes: false,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            alerts: None,
+            observer_liveness: None,
+            event_transforms: vec![],
+            previews: None,
+            content_scanning: None,
+            sales_analytics: None,
+            maintenance: None,
+            amendments: None,
+            rollbacks: None,
+            replay_log: None,
+            cold_storage: None,
+        }
+    }
+
+    pub fn signet_default() -> Config {
+        let mut config = Self::testnet_default();
+        config.storage.working_dir = namespaced_working_dir(&default_cache_path(), "signet");
+        config.network.bitcoind_rpc_url = "http://0.0.0.0:38332".into();
+        config.network.bitcoin_network = BitcoinNetwork::Signet;
+        config
+    }
+
+    /// No snapshot is published for testnet4: the index is built from genesis.
+    pub fn testnet4_default() -> Config {
+        let mut config = Self::testnet_default();
+        config.storage.working_dir = namespaced_working_dir(&default_cache_path(), "testnet4");
+        config.network.bitcoind_rpc_url = "http://0.0.0.0:48332".into();
+        config.network.testnet4 = true;
+        config
+    }
+
+    pub fn default_for_network(network: &BitcoinNetwork) -> Config {
+        match network {
+            BitcoinNetwork::Mainnet => Self::mainnet_default(),
+            BitcoinNetwork::Testnet => Self::testnet_default(),
+            BitcoinNetwork::Signet => Self::signet_default(),
+            BitcoinNetwork::Regtest => Self::devnet_default(),
         }
     }
 
     pub fn mainnet_default() -> Config {
         Config {
             storage: StorageConfig {
-                working_dir: default_cache_path(),
+                working_dir: namespaced_working_dir(&default_cache_path(), "mainnet"),
                 observers_working_dir: default_observers_cache_path(),
+                sqlite_dir: None,
+                blocks_dir: None,
+                content_dir: None,
+                encryption_key: None,
+                store_content: true,
+                content_types_allowed: vec![],
+                content_types_denied: vec![],
+                index_commitment_interval: None,
+                correct_content_types: false,
+                satribute_ranges_path: None,
+                index_scope: IndexScope::InscribedOnly,
+                content_mmap_threshold_bytes: DEFAULT_CONTENT_MMAP_THRESHOLD_BYTES,
+                content_cache_max_size_bytes: DEFAULT_CONTENT_CACHE_MAX_SIZE_BYTES,
             },
             http_api: PredicatesApi::Off,
             snapshot: SnapshotConfig::Download(SnapshotConfigDownloadUrls {
                 ordinals: DEFAULT_MAINNET_ORDINALS_SQLITE_ARCHIVE.to_string(),
                 brc20: Some(DEFAULT_MAINNET_BRC20_SQLITE_ARCHIVE.to_string()),
+                ordinals_mirrors: vec![],
+                brc20_mirrors: vec![],
+                ordinals_torrent: None,
+                brc20_torrent: None,
             }),
             resources: ResourcesConfig {
                 cpu_core_available: num_cpus::get(),
@@ -248,24 +1044,60 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                network_connect_timeout_secs: DEFAULT_NETWORK_CONNECT_TIMEOUT_SECS,
+                network_read_timeout_secs: DEFAULT_NETWORK_READ_TIMEOUT_SECS,
+                webhook_timeout_secs: DEFAULT_WEBHOOK_TIMEOUT_SECS,
+                network_max_retries: DEFAULT_NETWORK_MAX_RETRIES,
+                network_retry_backoff_ms: DEFAULT_NETWORK_RETRY_BACKOFF_MS,
+                network_proxy: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                max_concurrent_jobs: DEFAULT_MAX_CONCURRENT_JOBS,
+                job_retention_days: DEFAULT_JOB_RETENTION_DAYS,
+                block_processing_max_retries: DEFAULT_BLOCK_PROCESSING_MAX_RETRIES,
+                max_download_rate: None,
+                heavy_network_window: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:8332".into(),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
-                bitcoin_block_signaling: BitcoinBlockSignaling::Stacks(
-                    StacksNodeConfig::default_localhost(DEFAULT_INGESTION_PORT),
-                ),
+                bitcoin_block_signaling: None,
                 bitcoin_network: BitcoinNetwork::Mainnet,
+                testnet4: false,
+                ingestion_port: DEFAULT_INGESTION_PORT,
+                ingestion_guard: None,
+                prometheus_monitoring_address: DEFAULT_LISTENER_ADDRESS,
                 prometheus_monitoring_port: Some(9153),
+                prometheus_monitoring_unix_socket: None,
+                block_ingestion: BlockIngestion::Observer,
+                bitcoind_pruned: false,
+                bitcoind_block_filters: false,
+                first_inscription_height: None,
+                jubilee_height: None,
             },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                slow_block_threshold_ms: None,
+                slow_block_profiles_dir: None,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                sns: false,
+                runes: false,
+            },
+            alerts: None,
+            observer_liveness: None,
+            event_transforms: vec![],
+            previews: None,
+            content_scanning: None,
+            sales_analytics: None,
+            maintenance: None,
+            amendments: None,
+            rollbacks: None,
+            replay_log: None,
+            cold_storage: None,
         }
     }
 
@@ -279,6 +1111,31 @@ impl Config {
     }
 }
 
+/// Name of `network`, used for its working subdirectory and recorded in the databases.
+pub fn network_name(network: &BitcoinNetwork, testnet4: bool) -> &'static str {
+    match network {
+        BitcoinNetwork::Mainnet => "mainnet",
+        BitcoinNetwork::Testnet if testnet4 => "testnet4",
+        BitcoinNetwork::Testnet => "testnet",
+        BitcoinNetwork::Signet => "signet",
+        BitcoinNetwork::Regtest => "regtest",
+    }
+}
+
+/// Returns the subdirectory of `working_dir` dedicated to the network named `name` (e.g. `ordhook/mainnet`), so that
+/// configs of different networks can't share databases. Working dirs already named after the network, or holding
+/// databases at their root (created before working dirs were namespaced), are returned unchanged.
+pub fn namespaced_working_dir(working_dir: &str, name: &str) -> String {
+    let path = PathBuf::from(working_dir);
+    if path.file_name().map(|f| f == name).unwrap_or(false)
+        || path.join("hord.sqlite").exists()
+        || path.join("hord.rocksdb").exists()
+    {
+        return working_dir.to_string();
+    }
+    format!("{}", path.join(name).display())
+}
+
 pub fn default_cache_path() -> String {
     let mut cache_path = std::env::current_dir().expect("unable to get current dir");
     cache_path.push("ordhook");
@@ -290,3 +1147,128 @@ pub fn default_observers_cache_path() -> String {
     cache_path.push("observers");
     format!("{}", cache_path.display())
 }
+
+#[cfg(test)]
+mod test {
+    use std::{path::PathBuf, time::Duration};
+
+    use test_case::test_case;
+
+    use super::{content_type_matches, namespaced_working_dir, Config, DailyWindow, IpRange};
+
+    #[test_case("text/plain", "text/plain;charset=utf-8" => true; "ignores parameters")]
+    #[test_case("TEXT/PLAIN", "text/plain" => true; "is case insensitive")]
+    #[test_case("image/*", "image/png" => true; "with subtype wildcard")]
+    #[test_case("image/*", "text/plain" => false; "with non matching subtype wildcard")]
+    #[test_case("*", "application/json" => true; "with wildcard")]
+    #[test_case("text/html", "text/plain" => false; "with different type")]
+    fn matches_content_types(pattern: &str, content_type: &str) -> bool {
+        content_type_matches(pattern, content_type)
+    }
+
+    #[test]
+    fn filters_stored_content_types() {
+        let mut storage = Config::test_default().storage;
+        assert!(storage.should_store_content("image/png"));
+        storage.content_types_allowed = vec!["text/*".into(), "application/json".into()];
+        storage.content_types_denied = vec!["text/html".into()];
+        assert!(storage.should_store_content("text/plain;charset=utf-8"));
+        assert!(storage.should_store_content("application/json"));
+        assert!(!storage.should_store_content("text/html"));
+        assert!(!storage.should_store_content("image/png"));
+        storage.store_content = false;
+        assert!(!storage.should_store_content("text/plain"));
+    }
+
+    #[test]
+    fn resolves_store_dirs() {
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/stores".into();
+        assert_eq!(config.expected_sqlite_path(), config.expected_cache_path());
+        assert_eq!(config.expected_blocks_path(), config.expected_cache_path());
+        assert_eq!(config.expected_content_path(), config.expected_cache_path());
+        config.storage.sqlite_dir = Some("tmp/ssd".into());
+        config.storage.blocks_dir = Some("tmp/hdd".into());
+        config.storage.content_dir = Some("tmp/blobs".into());
+        assert_eq!(config.expected_sqlite_path(), PathBuf::from("tmp/ssd"));
+        assert_eq!(config.expected_blocks_path(), PathBuf::from("tmp/hdd"));
+        assert_eq!(config.expected_content_path(), PathBuf::from("tmp/blobs"));
+        assert_eq!(config.expected_cache_path(), PathBuf::from("tmp/stores"));
+    }
+
+    #[test]
+    fn namespaces_working_dirs_by_network() {
+        assert_eq!(
+            namespaced_working_dir("tmp/namespaced", "testnet4"),
+            "tmp/namespaced/testnet4"
+        );
+        assert_eq!(
+            namespaced_working_dir("tmp/namespaced/mainnet", "mainnet"),
+            "tmp/namespaced/mainnet"
+        );
+        std::fs::create_dir_all("tmp/legacy").unwrap();
+        std::fs::write("tmp/legacy/hord.sqlite", []).unwrap();
+        assert_eq!(
+            namespaced_working_dir("tmp/legacy", "mainnet"),
+            "tmp/legacy"
+        );
+        std::fs::remove_dir_all("tmp/legacy").unwrap();
+    }
+
+    #[test]
+    fn parses_daily_windows() {
+        let window = DailyWindow::parse("01:30-06:00").unwrap();
+        assert_eq!(window.to_string(), "01:30-06:00");
+        assert!(window.contains(90));
+        assert!(!window.contains(360));
+        assert_eq!(window.minutes_until_open(60), 30);
+        assert_eq!(window.minutes_until_open(400), 24 * 60 - 400 + 90);
+        assert!(DailyWindow::parse("25:00-06:00").is_err());
+        assert!(DailyWindow::parse("06:00-06:00").is_err());
+        assert!(DailyWindow::parse("06:00").is_err());
+    }
+
+    #[test]
+    fn wraps_daily_windows_around_midnight() {
+        let window = DailyWindow::parse("22:00-02:00").unwrap();
+        assert!(window.contains(23 * 60));
+        assert!(window.contains(60));
+        assert!(!window.contains(12 * 60));
+        assert_eq!(window.minutes_until_open(21 * 60), 60);
+    }
+
+    #[test]
+    fn backs_off_network_retries() {
+        let mut resources = Config::test_default().resources;
+        resources.network_retry_backoff_ms = 500;
+        assert_eq!(
+            resources.network_retry_backoff(1),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            resources.network_retry_backoff(3),
+            Duration::from_millis(2_000)
+        );
+        assert_eq!(resources.network_retry_backoff(30), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn matches_ip_ranges() {
+        let range = IpRange::parse("10.1.0.0/16").unwrap();
+        assert!(range.contains(&"10.1.200.3".parse().unwrap()));
+        assert!(!range.contains(&"10.2.0.1".parse().unwrap()));
+        // Clients reaching a dual-stack listener over IPv4 show up as IPv4-mapped addresses.
+        assert!(range.contains(&"::ffff:10.1.0.1".parse().unwrap()));
+        let single = IpRange::parse("192.168.1.12").unwrap();
+        assert!(single.contains(&"192.168.1.12".parse().unwrap()));
+        assert!(!single.contains(&"192.168.1.13".parse().unwrap()));
+        assert!(IpRange::parse("0.0.0.0/0")
+            .unwrap()
+            .contains(&"8.8.8.8".parse().unwrap()));
+        assert!(IpRange::parse("fd00::/8")
+            .unwrap()
+            .contains(&"fd12::1".parse().unwrap()));
+        assert!(IpRange::parse("10.0.0.0/33").is_err());
+        assert!(IpRange::parse("localhost").is_err());
+    }
+}
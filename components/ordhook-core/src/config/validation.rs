@@ -0,0 +1,687 @@
+use std::{
+    fmt,
+    net::{IpAddr, TcpListener},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chainhook_sdk::{types::BitcoinNetwork, utils::Context};
+
+use crate::{
+    config::{BlockIngestion, Config, PredicatesApi, SnapshotConfig, DEFAULT_LISTENER_ADDRESS},
+    core::find_next_block_height_to_index,
+    db::{
+        blocks::{get_default_blocks_db_path, open_readonly_blocks_db},
+        ordinals::{get_default_ordinals_db_file_path, open_ordinals_db},
+        recovery::WORKING_DIR_SQLITE_DBS,
+    },
+    try_warn,
+    utils::{
+        bitcoind::{bitcoind_get_prune_height, bitcoind_try_get_block_height},
+        http::outbound_http_client_builder,
+    },
+};
+
+const SNAPSHOT_URL_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticSeverity {
+    /// The service can't run with this configuration.
+    Error,
+    /// The service runs, but is likely to fail or slow down later on.
+    Warning,
+}
+
+/// When the configuration gets validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationStage {
+    /// `ordhook config validate`: problems are reported with their severity.
+    Command,
+    /// Start of the service: bitcoind or the snapshot mirrors being unreachable only get warnings, since the service
+    /// retries them and they can come back in the meantime.
+    ServiceStart,
+}
+
+impl ValidationStage {
+    fn reachability_severity(&self, severity: DiagnosticSeverity) -> DiagnosticSeverity {
+        match self {
+            ValidationStage::Command => severity,
+            ValidationStage::ServiceStart => DiagnosticSeverity::Warning,
+        }
+    }
+}
+
+/// A problem found in the configuration, with the setting involved and how to fix it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub setting: String,
+    pub problem: String,
+    pub remediation: String,
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+        };
+        write!(
+            f,
+            "[{severity}] {}: {}\n  -> {}",
+            self.setting, self.problem, self.remediation
+        )
+    }
+}
+
+/// Disk space below which indexing the network from scratch is expected to run out of space.
+fn expected_disk_space_bytes(network: &BitcoinNetwork) -> u64 {
+    match network {
+        BitcoinNetwork::Mainnet => 500 * 1024 * 1024 * 1024,
+        BitcoinNetwork::Testnet | BitcoinNetwork::Signet => 50 * 1024 * 1024 * 1024,
+        BitcoinNetwork::Regtest => 1024 * 1024 * 1024,
+    }
+}
+
+#[cfg(unix)]
+fn available_disk_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Identifies the filesystem of `path`, for the stores sharing a volume to be checked against its space together.
+#[cfg(unix)]
+fn filesystem_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn filesystem_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn open_files_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    Some(limit.rlim_cur as u64)
+}
+
+#[cfg(not(unix))]
+fn open_files_limit() -> Option<u64> {
+    None
+}
+
+fn check_bitcoind_rpc(config: &Config, stage: ValidationStage) -> Option<ConfigDiagnostic> {
+    let e = bitcoind_try_get_block_height(config).err()?;
+    Some(ConfigDiagnostic {
+        severity: stage.reachability_severity(DiagnosticSeverity::Error),
+        setting: "network.bitcoind_rpc_url".into(),
+        problem: format!(
+            "bitcoind is not reachable at {}: {e}",
+            config.network.bitcoind_rpc_url
+        ),
+        remediation: "check that bitcoind is running with `server=1`, and that network.bitcoind_rpc_url, network.bitcoind_rpc_username and network.bitcoind_rpc_password match its rpcbind, rpcuser and rpcpassword settings".into(),
+    })
+}
+
+fn check_pruned_bitcoind(
+    pruned_mode: bool,
+    prune_height: Option<u64>,
+    next_block_height: Option<u64>,
+) -> Option<ConfigDiagnostic> {
+    match (pruned_mode, prune_height) {
+        (false, Some(prune_height)) => Some(ConfigDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            setting: "network.bitcoind_pruned".into(),
+            problem: format!("bitcoind is pruned, its first block available is #{prune_height}"),
+            remediation: "set network.bitcoind_pruned = true, or point network.bitcoind_rpc_url to an archival bitcoind".into(),
+        }),
+        (true, None) => Some(ConfigDiagnostic {
+            severity: DiagnosticSeverity::Warning,
+            setting: "network.bitcoind_pruned".into(),
+            problem: "bitcoind is not pruned, blocks are still downloaded one at a time".into(),
+            remediation: "remove network.bitcoind_pruned to download blocks concurrently".into(),
+        }),
+        (true, Some(prune_height)) => {
+            let next_block_height = next_block_height?;
+            if next_block_height >= prune_height {
+                return None;
+            }
+            Some(ConfigDiagnostic {
+                severity: DiagnosticSeverity::Error,
+                setting: "network.bitcoind_pruned".into(),
+                problem: format!(
+                    "block #{next_block_height} is the next block to index, but bitcoind pruned the blocks below #{prune_height}"
+                ),
+                remediation: format!(
+                    "restore an index more recent than block #{prune_height} (e.g. from a snapshot), or reindex bitcoind with a larger prune setting"
+                ),
+            })
+        }
+        (false, None) => None,
+    }
+}
+
+/// Height of the next block ordhook will request from bitcoind, None when it will come from a snapshot instead. Blocks
+/// missing from hord.rocksdb are requested again, as when the service syncs.
+fn next_block_height_to_index(config: &Config, ctx: &Context) -> Option<u64> {
+    let sqlite_dir = config.expected_sqlite_path();
+    if !get_default_ordinals_db_file_path(&sqlite_dir).exists() {
+        return match config.snapshot {
+            SnapshotConfig::Download(_) => None,
+            SnapshotConfig::Build => Some(0),
+        };
+    }
+    let Ok(blocks_db) = open_readonly_blocks_db(config, ctx) else {
+        return Some(0);
+    };
+    let db_conn = open_ordinals_db(&sqlite_dir, ctx).ok()?;
+    find_next_block_height_to_index(&blocks_db, &db_conn, config, ctx).ok()
+}
+
+fn check_port(setting: &str, address: IpAddr, port: u16) -> Option<ConfigDiagnostic> {
+    let e = TcpListener::bind((address, port)).err()?;
+    Some(ConfigDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        setting: setting.into(),
+        problem: format!("unable to listen on {address}:{port}: {e}"),
+        remediation: format!(
+            "stop the process using port {port}, or configure {setting} with a free port"
+        ),
+    })
+}
+
+fn check_ports(config: &Config) -> Vec<ConfigDiagnostic> {
+    let mut ports = vec![];
+    if config.network.block_ingestion == BlockIngestion::Observer {
+        ports.push((
+            "network.ingestion_port",
+            DEFAULT_LISTENER_ADDRESS,
+            config.network.ingestion_port,
+        ));
+        if let Some(ref guard) = config.network.ingestion_guard {
+            ports.push((
+                "network.ingestion_guard.internal_port",
+                DEFAULT_LISTENER_ADDRESS,
+                guard.internal_port,
+            ));
+        }
+    }
+    if let Some(port) = config.network.prometheus_monitoring_port {
+        ports.push((
+            "network.prometheus_monitoring_port",
+            config.network.prometheus_monitoring_address,
+            port,
+        ));
+    }
+    if let PredicatesApi::On(ref api) = config.http_api {
+        ports.push(("http_api.http_port", api.http_address, api.http_port));
+    }
+    ports
+        .into_iter()
+        .filter_map(|(setting, address, port)| check_port(setting, address, port))
+        .collect()
+}
+
+/// chainhook-sdk's event observer listens on every interface, which can't be changed: behind the ingestion guard, its
+/// port has to be firewalled for the guard not to be bypassed, and the guard is refused until the operator confirms it.
+fn check_ingestion_guard_internal_port(config: &Config) -> Option<ConfigDiagnostic> {
+    if config.network.block_ingestion != BlockIngestion::Observer {
+        return None;
+    }
+    let guard = config.network.ingestion_guard.as_ref()?;
+    if guard.internal_port_firewalled {
+        return None;
+    }
+    Some(ConfigDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        setting: "network.ingestion_guard.internal_port".into(),
+        problem: format!(
+            "the event observer listens on port {} on every interface, where the ingestion guard can be bypassed",
+            guard.internal_port
+        ),
+        remediation: format!(
+            "firewall port {} from the network, only the loopback interface needs to reach it, then set network.ingestion_guard.internal_port_firewalled = true",
+            guard.internal_port
+        ),
+    })
+}
+
+/// Returns the closest existing ancestor of `path`, since the working directory is only created on first start.
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|p| p.exists())
+        .map(|p| p.to_path_buf())
+}
+
+/// A directory the indexer writes to, with its setting and the share of `expected_disk_space_bytes` it takes.
+struct StoreDir {
+    setting: &'static str,
+    path: PathBuf,
+    percent: u64,
+}
+
+fn store_dirs(config: &Config) -> Vec<StoreDir> {
+    let setting = |dir: &Option<String>, setting| match dir {
+        Some(_) => setting,
+        None => "storage.working_dir",
+    };
+    vec![
+        StoreDir {
+            setting: setting(&config.storage.blocks_dir, "storage.blocks_dir"),
+            path: config.expected_blocks_path(),
+            percent: 50,
+        },
+        StoreDir {
+            setting: setting(&config.storage.sqlite_dir, "storage.sqlite_dir"),
+            path: config.expected_sqlite_path(),
+            percent: 40,
+        },
+        StoreDir {
+            setting: setting(&config.storage.content_dir, "storage.content_dir"),
+            path: config.expected_content_path(),
+            percent: 10,
+        },
+    ]
+}
+
+fn check_disk_space(
+    stores: &[&StoreDir],
+    network: &BitcoinNetwork,
+    available: Option<u64>,
+) -> Option<ConfigDiagnostic> {
+    let available = available?;
+    let expected = expected_disk_space_bytes(network) / 100
+        * stores.iter().map(|store| store.percent).sum::<u64>();
+    if available >= expected {
+        return None;
+    }
+    let mut settings = stores.iter().map(|store| store.setting).collect::<Vec<_>>();
+    settings.dedup();
+    let settings = settings.join(", ");
+    let gib = 1024 * 1024 * 1024;
+    Some(ConfigDiagnostic {
+        severity: DiagnosticSeverity::Warning,
+        setting: settings.clone(),
+        problem: format!(
+            "{} GiB available in {}, indexing {network:?} can require {} GiB there",
+            available / gib,
+            stores[0].path.display(),
+            expected / gib
+        ),
+        remediation: format!("free some space, or configure {settings} on a larger volume"),
+    })
+}
+
+/// Checks the space available to the stores, the ones sharing a volume being checked against its space together.
+fn check_stores_disk_space(config: &Config, ctx: &Context) -> Vec<ConfigDiagnostic> {
+    let stores = store_dirs(config);
+    let mut volumes: Vec<(Option<u64>, PathBuf, Vec<&StoreDir>)> = vec![];
+    for store in stores.iter() {
+        let Some(dir) = existing_ancestor(&store.path) else {
+            try_warn!(
+                ctx,
+                "Config validation: unable to check disk space of {}",
+                store.path.display()
+            );
+            continue;
+        };
+        let id = filesystem_id(&dir);
+        match volumes
+            .iter_mut()
+            .find(|(volume_id, volume_dir, _)| match (id, volume_id) {
+                (Some(id), Some(volume_id)) => id == *volume_id,
+                _ => dir == *volume_dir,
+            }) {
+            Some((_, _, volume_stores)) => volume_stores.push(store),
+            None => volumes.push((id, dir, vec![store])),
+        }
+    }
+    volumes
+        .into_iter()
+        .filter_map(|(_, dir, volume_stores)| {
+            check_disk_space(
+                &volume_stores,
+                &config.network.bitcoin_network,
+                available_disk_space(&dir),
+            )
+        })
+        .collect()
+}
+
+/// Databases found in the working dir while their setting points to another dir: started this way, the indexer would
+/// index from scratch in the new dir.
+fn check_stores_moved(config: &Config) -> Vec<ConfigDiagnostic> {
+    let working_dir = config.expected_cache_path();
+    let mut diagnostics = vec![];
+    let mut check = |setting: &str, old_path: PathBuf, new_path: PathBuf| {
+        if old_path == new_path || !old_path.exists() || new_path.exists() {
+            return;
+        }
+        diagnostics.push(ConfigDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            setting: setting.into(),
+            problem: format!(
+                "{} exists, but {setting} points to {}",
+                old_path.display(),
+                new_path.display()
+            ),
+            remediation: format!(
+                "stop ordhook and move {} to {}, or unset {setting}",
+                old_path.display(),
+                new_path.display()
+            ),
+        });
+    };
+    if config.storage.sqlite_dir.is_some() {
+        let sqlite_dir = config.expected_sqlite_path();
+        for db_name in WORKING_DIR_SQLITE_DBS {
+            check(
+                "storage.sqlite_dir",
+                working_dir.join(db_name),
+                sqlite_dir.join(db_name),
+            );
+        }
+    }
+    if config.storage.blocks_dir.is_some() {
+        check(
+            "storage.blocks_dir",
+            get_default_blocks_db_path(&working_dir),
+            get_default_blocks_db_path(&config.expected_blocks_path()),
+        );
+    }
+    diagnostics
+}
+
+fn check_open_files_limit(ulimit: usize, limit: Option<u64>) -> Option<ConfigDiagnostic> {
+    let limit = limit?;
+    if ulimit as u64 <= limit {
+        return None;
+    }
+    Some(ConfigDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        setting: "resources.ulimit".into(),
+        problem: format!(
+            "blocks db is allowed to open {ulimit} files, but the process is limited to {limit}"
+        ),
+        remediation: format!(
+            "raise the limit with `ulimit -n {ulimit}` before starting ordhook, or lower resources.ulimit to {limit}"
+        ),
+    })
+}
+
+async fn check_snapshot_url(client: &reqwest::Client, url: &str) -> Option<String> {
+    let archive_url = format!("{url}.tar.gz");
+    match client.head(&archive_url).send().await {
+        Ok(res) if res.status().is_success() => None,
+        Ok(res) => Some(format!("{archive_url} responded with {}", res.status())),
+        Err(e) => Some(format!("{archive_url} is not reachable: {e}")),
+    }
+}
+
+fn snapshot_urls_diagnostic(
+    setting: &str,
+    urls_count: usize,
+    problems: Vec<String>,
+    bootstrapped: bool,
+    stage: ValidationStage,
+) -> Option<ConfigDiagnostic> {
+    if problems.is_empty() {
+        return None;
+    }
+    // The archive is only downloaded once, when bootstrapping the index, and from any of the mirrors.
+    let severity = match bootstrapped || problems.len() < urls_count {
+        true => DiagnosticSeverity::Warning,
+        false => DiagnosticSeverity::Error,
+    };
+    Some(ConfigDiagnostic {
+        severity: stage.reachability_severity(severity),
+        setting: setting.into(),
+        problem: problems.join(", "),
+        remediation: format!(
+            "check {setting} and its mirrors, or remove the [snapshot] section to index from genesis instead"
+        ),
+    })
+}
+
+async fn check_snapshot_urls(
+    setting: &str,
+    urls: Vec<String>,
+    bootstrapped: bool,
+    stage: ValidationStage,
+    config: &Config,
+) -> Option<ConfigDiagnostic> {
+    let client = outbound_http_client_builder(&config.resources)
+        .ok()?
+        .timeout(Duration::from_secs(SNAPSHOT_URL_TIMEOUT_SECS))
+        .build()
+        .ok()?;
+    let mut problems = vec![];
+    for url in urls.iter() {
+        problems.extend(check_snapshot_url(&client, url).await);
+    }
+    snapshot_urls_diagnostic(setting, urls.len(), problems, bootstrapped, stage)
+}
+
+/// Checks that bitcoind is reachable with the credentials configured, and pruned only if `network.bitcoind_pruned` is set
+/// and the index is past its prune height, that the ports to listen on are free, that the
+/// storage directories have enough space and no database was left behind in the working dir, that the open files limit fits `resources.ulimit`, and that the snapshot
+/// archives can be downloaded. All the problems found are returned, errors first.
+pub async fn validate_config(
+    config: &Config,
+    stage: ValidationStage,
+    ctx: &Context,
+) -> Vec<ConfigDiagnostic> {
+    let mut diagnostics = vec![];
+    match check_bitcoind_rpc(config, stage) {
+        Some(diagnostic) => diagnostics.push(diagnostic),
+        None => match bitcoind_get_prune_height(config) {
+            Ok(prune_height) => diagnostics.extend(check_pruned_bitcoind(
+                config.network.bitcoind_pruned,
+                prune_height,
+                next_block_height_to_index(config, ctx),
+            )),
+            Err(e) => try_warn!(
+                ctx,
+                "Config validation: unable to check if bitcoind is pruned: {e}"
+            ),
+        },
+    }
+    diagnostics.extend(check_ports(config));
+    diagnostics.extend(check_ingestion_guard_internal_port(config));
+    diagnostics.extend(check_stores_disk_space(config, ctx));
+    diagnostics.extend(check_stores_moved(config));
+    diagnostics.extend(check_open_files_limit(
+        config.resources.ulimit,
+        open_files_limit(),
+    ));
+    if let SnapshotConfig::Download(ref urls) = config.snapshot {
+        let bootstrapped =
+            get_default_ordinals_db_file_path(&config.expected_sqlite_path()).exists();
+        diagnostics.extend(
+            check_snapshot_urls(
+                "snapshot.ordinals_url",
+                urls.ordinals_urls(),
+                bootstrapped,
+                stage,
+                config,
+            )
+            .await,
+        );
+        if config.meta_protocols.brc20 && urls.brc20.is_some() {
+            diagnostics.extend(
+                check_snapshot_urls(
+                    "snapshot.brc20_url",
+                    urls.brc20_urls(),
+                    bootstrapped,
+                    stage,
+                    config,
+                )
+                .await,
+            );
+        }
+    }
+    diagnostics.sort_by_key(|d| d.severity != DiagnosticSeverity::Error);
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr, TcpListener};
+
+    use chainhook_sdk::types::BitcoinNetwork;
+
+    use crate::config::{BlockIngestion, Config, IngestionGuardConfig};
+
+    use super::{
+        check_disk_space, check_ingestion_guard_internal_port, check_open_files_limit, check_port,
+        check_pruned_bitcoind, check_stores_moved, snapshot_urls_diagnostic, store_dirs,
+        DiagnosticSeverity, ValidationStage,
+    };
+
+    #[test]
+    fn reports_ports_in_use() {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let listener = TcpListener::bind((localhost, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let diagnostic = check_port("http_api.http_port", localhost, port).unwrap();
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostic.setting, "http_api.http_port");
+        drop(listener);
+        assert!(check_port("http_api.http_port", localhost, port).is_none());
+    }
+
+    #[test]
+    fn rejects_ingestion_guard_until_internal_port_is_firewalled() {
+        let mut config = Config::test_default();
+        config.network.block_ingestion = BlockIngestion::Observer;
+        assert!(check_ingestion_guard_internal_port(&config).is_none());
+        config.network.ingestion_guard = Some(IngestionGuardConfig {
+            allowed_ips: vec![],
+            tls: None,
+            internal_port: 20457,
+            internal_port_firewalled: false,
+            payload_secret: None,
+        });
+        let diagnostic = check_ingestion_guard_internal_port(&config).unwrap();
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Error);
+        if let Some(ref mut guard) = config.network.ingestion_guard {
+            guard.internal_port_firewalled = true;
+        }
+        assert!(check_ingestion_guard_internal_port(&config).is_none());
+    }
+
+    #[test]
+    fn reports_open_files_limit_below_ulimit() {
+        assert!(check_open_files_limit(2048, Some(4096)).is_none());
+        assert!(check_open_files_limit(2048, None).is_none());
+        let diagnostic = check_open_files_limit(2048, Some(1024)).unwrap();
+        assert!(diagnostic.remediation.contains("ulimit -n 2048"));
+    }
+
+    #[test]
+    fn checks_compatibility_with_pruned_bitcoind() {
+        assert!(check_pruned_bitcoind(false, None, Some(0)).is_none());
+        let unexpected = check_pruned_bitcoind(false, Some(800_000), Some(840_000)).unwrap();
+        assert_eq!(unexpected.severity, DiagnosticSeverity::Error);
+        let unpruned = check_pruned_bitcoind(true, None, Some(0)).unwrap();
+        assert_eq!(unpruned.severity, DiagnosticSeverity::Warning);
+        assert!(check_pruned_bitcoind(true, Some(800_000), Some(840_000)).is_none());
+        assert!(check_pruned_bitcoind(true, Some(800_000), Some(800_000)).is_none());
+        assert!(check_pruned_bitcoind(true, Some(800_000), None).is_none());
+        let behind = check_pruned_bitcoind(true, Some(800_000), Some(0)).unwrap();
+        assert_eq!(behind.severity, DiagnosticSeverity::Error);
+        assert!(behind.problem.contains("#800000"));
+    }
+
+    #[test]
+    fn warns_on_low_disk_space() {
+        let mut config = Config::test_default();
+        config.storage.blocks_dir = Some("/tmp/blocks".into());
+        let stores = store_dirs(&config);
+        let stores = stores.iter().collect::<Vec<_>>();
+        assert!(check_disk_space(&stores, &BitcoinNetwork::Regtest, Some(u64::MAX)).is_none());
+        let diagnostic = check_disk_space(&stores, &BitcoinNetwork::Mainnet, Some(1024)).unwrap();
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+        assert_eq!(
+            diagnostic.setting,
+            "storage.blocks_dir, storage.working_dir"
+        );
+        // The blocks db alone takes half of the space needed.
+        let gib = 1024 * 1024 * 1024;
+        assert!(
+            check_disk_space(&stores[..1], &BitcoinNetwork::Mainnet, Some(300 * gib)).is_none()
+        );
+        assert!(check_disk_space(&stores, &BitcoinNetwork::Mainnet, Some(300 * gib)).is_some());
+    }
+
+    #[test]
+    fn reports_dbs_left_in_working_dir() {
+        let mut config = Config::test_default();
+        let dir = std::env::temp_dir().join("ordhook_stores_moved");
+        config.storage.working_dir = dir.to_string_lossy().to_string();
+        let working_dir = config.expected_cache_path();
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        std::fs::write(working_dir.join("hord.sqlite"), []).unwrap();
+        assert!(check_stores_moved(&config).is_empty());
+        config.storage.sqlite_dir = Some(dir.join("sqlite").to_string_lossy().to_string());
+        let diagnostics = check_stores_moved(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[0].setting, "storage.sqlite_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_fails_when_no_snapshot_mirror_is_reachable() {
+        let setting = "snapshot.ordinals_url";
+        assert!(
+            snapshot_urls_diagnostic(setting, 2, vec![], false, ValidationStage::Command).is_none()
+        );
+        let partial = snapshot_urls_diagnostic(
+            setting,
+            2,
+            vec!["down".into()],
+            false,
+            ValidationStage::Command,
+        )
+        .unwrap();
+        assert_eq!(partial.severity, DiagnosticSeverity::Warning);
+        let all = vec!["down".into(), "down".into()];
+        let total =
+            snapshot_urls_diagnostic(setting, 2, all.clone(), false, ValidationStage::Command)
+                .unwrap();
+        assert_eq!(total.severity, DiagnosticSeverity::Error);
+        let at_start = snapshot_urls_diagnostic(
+            setting,
+            2,
+            all.clone(),
+            false,
+            ValidationStage::ServiceStart,
+        )
+        .unwrap();
+        assert_eq!(at_start.severity, DiagnosticSeverity::Warning);
+        let bootstrapped =
+            snapshot_urls_diagnostic(setting, 2, all, true, ValidationStage::Command).unwrap();
+        assert_eq!(bootstrapped.severity, DiagnosticSeverity::Warning);
+    }
+}
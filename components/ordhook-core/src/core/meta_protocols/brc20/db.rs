@@ -1,9 +1,16 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
 
 use crate::{
     config::Config,
-    db::ordinals::{create_or_open_readwrite_db, perform_query_one, perform_query_set},
-    try_error, try_warn,
+    core::meta_protocols::CREATE_PARTIAL_COMMITS_TABLE,
+    db::ordinals::{
+        create_or_open_readwrite_db, open_existing_readonly_db, open_existing_readonly_db_snapshot,
+        perform_query_exists, perform_query_one, perform_query_set,
+    },
+    try_error, try_info, try_warn,
 };
 use chainhook_sdk::{
     types::{
@@ -42,10 +49,32 @@ pub struct Brc20DbLedgerRow {
     pub operation: String,
 }
 
+/// BRC-20 operation inscribed by a transaction and rejected by the validation, with the reason it was rejected for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Brc20DbRejectedOperationRow {
+    pub inscription_id: String,
+    #[serde(skip)]
+    pub block_height: u64,
+    #[serde(skip)]
+    pub tx_id: String,
+    pub operation: String,
+    pub tick: String,
+    pub address: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brc20DbBalanceRow {
+    pub tick: String,
+    pub address: String,
+    pub avail_balance: f64,
+    pub trans_balance: f64,
+}
+
 /// If the given `config` has BRC-20 enabled, returns a read/write DB connection for BRC-20.
 pub fn brc20_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
     if config.meta_protocols.brc20 {
-        match open_readwrite_brc20_db_conn(&config.expected_cache_path(), &ctx) {
+        match open_readwrite_brc20_db_conn(&config.expected_sqlite_path(), &ctx) {
             Ok(db) => Some(db),
             Err(e) => {
                 try_error!(ctx, "Unable to open readwrite brc20 connection: {e}");
@@ -141,10 +170,169 @@ pub fn initialize_brc20_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connect
             try_warn!(ctx, "unable to create brc20.sqlite: {}", e.to_string());
         }
     }
+    // Balance of every address after each block in which it was touched, for balance-at-height queries.
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS balances_history (
+            tick TEXT NOT NULL,
+            address TEXT NOT NULL,
+            block_height INTEGER NOT NULL,
+            avail_balance REAL NOT NULL,
+            trans_balance REAL NOT NULL,
+            PRIMARY KEY (tick, address, block_height)
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table balances_history: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_balances_history_on_address_block_height ON balances_history(address, block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create brc20.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_balances_history_on_block_height ON balances_history(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create brc20.sqlite: {}", e.to_string());
+        }
+        backfill_balances_history(&conn, ctx);
+    }
+    // Current balance of every address, kept along with the history so that holders don't get summed from the ledger.
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS balances (
+            tick TEXT NOT NULL,
+            address TEXT NOT NULL,
+            avail_balance REAL NOT NULL,
+            trans_balance REAL NOT NULL,
+            total_balance REAL NOT NULL,
+            PRIMARY KEY (tick, address)
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table balances: {}", e.to_string());
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_balances_on_tick_total_balance ON balances(tick, total_balance DESC, address);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create brc20.sqlite: {}", e.to_string());
+        }
+        backfill_balances(&conn, ctx);
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS rejected_operations (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            tx_id TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            tick TEXT NOT NULL,
+            address TEXT NOT NULL,
+            reason TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table rejected_operations: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_rejected_operations_on_block_height ON rejected_operations(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create brc20.sqlite: {}", e.to_string());
+        }
+    }
+    if let Err(e) = conn.execute(CREATE_PARTIAL_COMMITS_TABLE, []) {
+        try_warn!(
+            ctx,
+            "Unable to create table partial_commits: {}",
+            e.to_string()
+        );
+    }
 
     conn
 }
 
+/// Databases created before the introduction of `balances_history` get their history rebuilt from the ledger.
+fn backfill_balances_history(conn: &Connection, ctx: &Context) {
+    let history_empty =
+        !perform_query_exists("SELECT 1 FROM balances_history LIMIT 1", &[], conn, ctx);
+    let ledger_empty = !perform_query_exists("SELECT 1 FROM ledger LIMIT 1", &[], conn, ctx);
+    if !history_empty || ledger_empty {
+        return;
+    }
+    try_info!(ctx, "Rebuilding BRC-20 balances history from ledger");
+    if let Err(e) = conn.execute(
+        "INSERT INTO balances_history (tick, address, block_height, avail_balance, trans_balance)
+        SELECT tick, address, block_height,
+            SUM(SUM(avail_balance)) OVER (PARTITION BY tick, address ORDER BY block_height),
+            SUM(SUM(trans_balance)) OVER (PARTITION BY tick, address ORDER BY block_height)
+        FROM ledger
+        GROUP BY tick, address, block_height",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "unable to rebuild brc20.sqlite balances history: {}",
+            e.to_string()
+        );
+    }
+}
+
+/// Databases created before the introduction of `balances` get their balances summed from the ledger once.
+fn backfill_balances(conn: &Connection, ctx: &Context) {
+    let balances_empty = !perform_query_exists("SELECT 1 FROM balances LIMIT 1", &[], conn, ctx);
+    let ledger_empty = !perform_query_exists("SELECT 1 FROM ledger LIMIT 1", &[], conn, ctx);
+    if !balances_empty || ledger_empty {
+        return;
+    }
+    try_info!(ctx, "Rebuilding BRC-20 balances from ledger");
+    if let Err(e) = conn.execute(
+        "INSERT INTO balances (tick, address, avail_balance, trans_balance, total_balance)
+        SELECT tick, address, SUM(avail_balance), SUM(trans_balance), SUM(avail_balance + trans_balance)
+        FROM ledger
+        GROUP BY tick, address",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "unable to rebuild brc20.sqlite balances: {}",
+            e.to_string()
+        );
+    }
+}
+
+/// Opens a read-only connection to an existing brc20.sqlite, used for serving API queries.
+pub fn open_readonly_brc20_db_conn(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    if !config.meta_protocols.brc20 {
+        return Err("BRC-20 indexing is disabled".to_string());
+    }
+    let db_path = get_default_brc20_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(open_existing_readonly_db_snapshot(&db_path, ctx)?)
+}
+
+/// Opens a long-lived read-only connection to an existing brc20.sqlite, seeing what the indexer commits.
+pub fn open_brc20_db(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    if !config.meta_protocols.brc20 {
+        return Err("BRC-20 indexing is disabled".to_string());
+    }
+    let db_path = get_default_brc20_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(open_existing_readonly_db(&db_path, ctx)?)
+}
+
 fn open_readwrite_brc20_db_conn(base_dir: &PathBuf, ctx: &Context) -> Result<Connection, String> {
     let db_path = get_default_brc20_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(Some(&db_path), ctx);
@@ -157,6 +345,14 @@ pub fn delete_activity_in_block_range(
     db_tx: &Connection,
     ctx: &Context,
 ) {
+    let args: &[&dyn ToSql] = &[&start_block.to_sql().unwrap(), &end_block.to_sql().unwrap()];
+    let touched_balances = perform_query_set(
+        "SELECT DISTINCT tick, address FROM balances_history WHERE block_height >= ? AND block_height <= ?",
+        args,
+        db_tx,
+        ctx,
+        |row| (row.get::<_, String>(0).unwrap(), row.get::<_, String>(1).unwrap()),
+    );
     while let Err(e) = db_tx.execute(
         "DELETE FROM ledger WHERE block_height >= ?1 AND block_height <= ?2",
         rusqlite::params![&start_block, &end_block],
@@ -171,6 +367,97 @@ pub fn delete_activity_in_block_range(
         try_warn!(ctx, "unable to query brc20.sqlite: {}", e.to_string());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
+    while let Err(e) = db_tx.execute(
+        "DELETE FROM balances_history WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query brc20.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = db_tx.execute(
+        "DELETE FROM rejected_operations WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query brc20.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    // Balances touched by the range go back to their latest state left in the history.
+    for (tick, address) in touched_balances.iter() {
+        while let Err(e) = db_tx.execute(
+            "DELETE FROM balances WHERE tick = ?1 AND address = ?2",
+            rusqlite::params![tick, address],
+        ) {
+            try_warn!(ctx, "unable to query brc20.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+        while let Err(e) = db_tx.execute(
+            "INSERT INTO balances (tick, address, avail_balance, trans_balance, total_balance)
+            SELECT tick, address, avail_balance, trans_balance, avail_balance + trans_balance
+            FROM balances_history
+            WHERE tick = ?1 AND address = ?2
+            ORDER BY block_height DESC
+            LIMIT 1",
+            rusqlite::params![tick, address],
+        ) {
+            try_warn!(ctx, "unable to query brc20.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+pub fn insert_rejected_operation(
+    row: &Brc20DbRejectedOperationRow,
+    db_tx: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = db_tx.execute(
+        "INSERT OR REPLACE INTO rejected_operations
+        (inscription_id, block_height, tx_id, operation, tick, address, reason)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            &row.inscription_id,
+            &row.block_height,
+            &row.tx_id,
+            &row.operation,
+            &row.tick,
+            &row.address,
+            &row.reason
+        ],
+    ) {
+        try_warn!(ctx, "unable to insert into brc20.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Operations rejected by the BRC-20 validation in a block, by id of the transaction inscribing them.
+pub fn get_rejected_operations_in_block(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> HashMap<String, Vec<Brc20DbRejectedOperationRow>> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "
+        SELECT inscription_id, block_height, tx_id, operation, tick, address, reason
+        FROM rejected_operations
+        WHERE block_height = ?
+        ORDER BY rowid
+    ";
+    let rows = perform_query_set(query, args, db_conn, ctx, |row| {
+        Brc20DbRejectedOperationRow {
+            inscription_id: row.get(0).unwrap(),
+            block_height: row.get(1).unwrap(),
+            tx_id: row.get(2).unwrap(),
+            operation: row.get(3).unwrap(),
+            tick: row.get(4).unwrap(),
+            address: row.get(5).unwrap(),
+            reason: row.get(6).unwrap(),
+        }
+    });
+    let mut map: HashMap<String, Vec<Brc20DbRejectedOperationRow>> = HashMap::new();
+    for row in rows.into_iter() {
+        map.entry(row.tx_id.clone()).or_default().push(row);
+    }
+    map
 }
 
 pub fn get_token(tick: &str, db_tx: &Connection, ctx: &Context) -> Option<Brc20DbTokenRow> {
@@ -194,7 +481,7 @@ pub fn get_token(tick: &str, db_tx: &Connection, ctx: &Context) -> Option<Brc20D
     })
 }
 
-pub fn get_token_minted_supply(tick: &str, db_tx: &Transaction, ctx: &Context) -> Option<f64> {
+pub fn get_token_minted_supply(tick: &str, db_tx: &Connection, ctx: &Context) -> Option<f64> {
     let args: &[&dyn ToSql] = &[&tick.to_sql().unwrap()];
     let query = "
         SELECT COALESCE(SUM(avail_balance + trans_balance), 0.0) AS minted
@@ -291,6 +578,217 @@ pub fn insert_ledger_rows(rows: &Vec<Brc20DbLedgerRow>, db_tx: &Connection, ctx:
         },
         Err(error) => {try_warn!(ctx, "unable to prepare statement for brc20.sqlite: {}", error.to_string());}
     }
+    update_balances_history(rows, db_tx, ctx);
+}
+
+/// Applies the balance changes of the given ledger rows to `balances_history` and `balances`. Rows are expected to be
+/// inserted in ascending block height order.
+fn update_balances_history(rows: &Vec<Brc20DbLedgerRow>, db_tx: &Connection, ctx: &Context) {
+    let mut deltas = BTreeMap::<(u64, &String, &String), (f64, f64)>::new();
+    let mut balance_deltas = BTreeMap::<(&String, &String), (f64, f64)>::new();
+    for row in rows.iter() {
+        let delta = deltas
+            .entry((row.block_height, &row.tick, &row.address))
+            .or_insert((0.0, 0.0));
+        delta.0 += row.avail_balance;
+        delta.1 += row.trans_balance;
+        let delta = balance_deltas
+            .entry((&row.tick, &row.address))
+            .or_insert((0.0, 0.0));
+        delta.0 += row.avail_balance;
+        delta.1 += row.trans_balance;
+    }
+    match db_tx.prepare_cached(
+        "INSERT INTO balances_history (tick, address, block_height, avail_balance, trans_balance)
+        VALUES (?1, ?2, ?3,
+            COALESCE((SELECT avail_balance FROM balances_history WHERE tick = ?1 AND address = ?2 AND block_height < ?3 ORDER BY block_height DESC LIMIT 1), 0.0) + ?4,
+            COALESCE((SELECT trans_balance FROM balances_history WHERE tick = ?1 AND address = ?2 AND block_height < ?3 ORDER BY block_height DESC LIMIT 1), 0.0) + ?5)
+        ON CONFLICT (tick, address, block_height) DO UPDATE SET
+            avail_balance = avail_balance + ?4,
+            trans_balance = trans_balance + ?5",
+    ) {
+        Ok(mut stmt) => {
+            for ((block_height, tick, address), (avail_delta, trans_delta)) in deltas.iter() {
+                while let Err(e) = stmt.execute(rusqlite::params![
+                    tick,
+                    address,
+                    block_height,
+                    avail_delta,
+                    trans_delta
+                ]) {
+                    try_warn!(ctx, "unable to insert into brc20.sqlite: {}", e.to_string());
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+        Err(error) => {
+            try_warn!(
+                ctx,
+                "unable to prepare statement for brc20.sqlite: {}",
+                error.to_string()
+            );
+        }
+    }
+    match db_tx.prepare_cached(
+        "INSERT INTO balances (tick, address, avail_balance, trans_balance, total_balance)
+        VALUES (?1, ?2, ?3, ?4, ?3 + ?4)
+        ON CONFLICT (tick, address) DO UPDATE SET
+            avail_balance = avail_balance + ?3,
+            trans_balance = trans_balance + ?4,
+            total_balance = total_balance + ?3 + ?4",
+    ) {
+        Ok(mut stmt) => {
+            for ((tick, address), (avail_delta, trans_delta)) in balance_deltas.iter() {
+                while let Err(e) =
+                    stmt.execute(rusqlite::params![tick, address, avail_delta, trans_delta])
+                {
+                    try_warn!(ctx, "unable to insert into brc20.sqlite: {}", e.to_string());
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        }
+        Err(error) => {
+            try_warn!(
+                ctx,
+                "unable to prepare statement for brc20.sqlite: {}",
+                error.to_string()
+            );
+        }
+    }
+}
+
+/// Returns the addresses holding a positive balance of `tick`, largest balances first.
+/// Holders of a token ordered by overall balance, as of `block_height` (latest when `None`).
+/// Holders of a token as of `block_height`, by descending balance then address, starting after the holder with the
+/// balance and address `after` if given.
+/// Whether balances at `block_height` are the current ones, served from `balances` rather than from the history.
+fn is_latest_block_height(block_height: i64, db_conn: &Connection, ctx: &Context) -> bool {
+    get_latest_ledger_block_height(db_conn, ctx)
+        .map_or(true, |latest| block_height >= latest as i64)
+}
+
+pub fn get_token_holders(
+    tick: &str,
+    block_height: Option<u64>,
+    after: Option<&(f64, String)>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<Brc20DbBalanceRow> {
+    let block_height = block_height.map(|h| h as i64).unwrap_or(i64::MAX);
+    let after_balance = after.map(|a| a.0);
+    let after_address = after.map(|a| a.1.as_str());
+    let args: &[&dyn ToSql] = &[
+        &tick.to_sql().unwrap(),
+        &block_height.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+        &after_balance.to_sql().unwrap(),
+        &after_address.to_sql().unwrap(),
+    ];
+    let query = if is_latest_block_height(block_height, db_conn, ctx) {
+        "
+        SELECT tick, address, avail_balance, trans_balance
+        FROM balances
+        WHERE tick = ?1 AND total_balance > 0 AND (
+            ?5 IS NULL OR total_balance < ?5 OR (total_balance = ?5 AND address > ?6)
+        )
+        ORDER BY total_balance DESC, address ASC
+        LIMIT ?3 OFFSET ?4
+        "
+    } else {
+        "
+        SELECT h.tick, h.address, h.avail_balance, h.trans_balance
+        FROM balances_history AS h
+        WHERE h.tick = ?1
+            AND h.block_height = (
+                SELECT MAX(block_height) FROM balances_history
+                WHERE tick = h.tick AND address = h.address AND block_height <= ?2
+            )
+            AND h.avail_balance + h.trans_balance > 0 AND (
+                ?5 IS NULL OR h.avail_balance + h.trans_balance < ?5
+                OR (h.avail_balance + h.trans_balance = ?5 AND h.address > ?6)
+            )
+        ORDER BY h.avail_balance + h.trans_balance DESC, h.address ASC
+        LIMIT ?3 OFFSET ?4
+        "
+    };
+    perform_query_set(query, args, db_conn, ctx, |row| Brc20DbBalanceRow {
+        tick: row.get(0).unwrap(),
+        address: row.get(1).unwrap(),
+        avail_balance: row.get(2).unwrap(),
+        trans_balance: row.get(3).unwrap(),
+    })
+}
+
+/// Height of the latest block with BRC-20 activity.
+pub fn get_latest_ledger_block_height(db_conn: &Connection, ctx: &Context) -> Option<u64> {
+    let query = "SELECT MAX(block_height) FROM ledger";
+    perform_query_one(query, &[], db_conn, ctx, |row| row.get(0).unwrap()).unwrap_or(None)
+}
+
+pub fn get_token_holders_count(
+    tick: &str,
+    block_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> u64 {
+    let block_height = block_height.map(|h| h as i64).unwrap_or(i64::MAX);
+    if is_latest_block_height(block_height, db_conn, ctx) {
+        let args: &[&dyn ToSql] = &[&tick.to_sql().unwrap()];
+        let query = "SELECT COUNT(*) FROM balances WHERE tick = ? AND total_balance > 0";
+        return perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+            .unwrap_or(0);
+    }
+    let args: &[&dyn ToSql] = &[&tick.to_sql().unwrap(), &block_height.to_sql().unwrap()];
+    let query = "
+        SELECT COUNT(*)
+        FROM balances_history AS h
+        WHERE h.tick = ?1
+            AND h.block_height = (
+                SELECT MAX(block_height) FROM balances_history
+                WHERE tick = h.tick AND address = h.address AND block_height <= ?2
+            )
+            AND h.avail_balance + h.trans_balance > 0
+    ";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap()).unwrap_or(0)
+}
+
+/// Returns the balances of `address` as they were right after `block_height` was processed (latest balances when
+/// `block_height` is `None`), optionally restricted to a single token.
+pub fn get_address_balances_at_block_height(
+    address: &str,
+    tick: Option<&str>,
+    block_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<Brc20DbBalanceRow> {
+    let block_height = block_height.map(|h| h as i64).unwrap_or(i64::MAX);
+    let tick = tick.map(|t| t.to_lowercase());
+    let args: &[&dyn ToSql] = &[
+        &address.to_sql().unwrap(),
+        &block_height.to_sql().unwrap(),
+        &tick.to_sql().unwrap(),
+        &tick.to_sql().unwrap(),
+    ];
+    let query = "
+        SELECT h.tick, h.address, h.avail_balance, h.trans_balance
+        FROM balances_history AS h
+        WHERE h.address = ?1
+            AND h.block_height = (
+                SELECT MAX(block_height) FROM balances_history
+                WHERE tick = h.tick AND address = h.address AND block_height <= ?2
+            )
+            AND (?3 IS NULL OR h.tick = ?4)
+        ORDER BY h.tick ASC
+    ";
+    perform_query_set(query, args, db_conn, ctx, |row| Brc20DbBalanceRow {
+        tick: row.get(0).unwrap(),
+        address: row.get(1).unwrap(),
+        avail_balance: row.get(2).unwrap(),
+        trans_balance: row.get(3).unwrap(),
+    })
 }
 
 pub fn insert_token_rows(rows: &Vec<Brc20DbTokenRow>, db_tx: &Connection, ctx: &Context) {
@@ -633,7 +1131,32 @@ mod test {
         db::{drop_all_dbs, initialize_sqlite_dbs},
     };
 
-    use super::{get_token, write_augmented_block_to_brc20_db};
+    use super::{
+        delete_activity_in_block_range, get_address_balances_at_block_height,
+        get_latest_ledger_block_height, get_token, get_token_holders, get_token_holders_count,
+        insert_ledger_rows, write_augmented_block_to_brc20_db, Brc20DbLedgerRow,
+    };
+
+    fn ledger_row(
+        block_height: u64,
+        address: &str,
+        avail_balance: f64,
+        trans_balance: f64,
+        operation: &str,
+    ) -> Brc20DbLedgerRow {
+        Brc20DbLedgerRow {
+            inscription_id: format!("{block_height}{address}{operation}i0"),
+            inscription_number: block_height,
+            ordinal_number: block_height,
+            block_height,
+            tx_index: 0,
+            tick: "ordi".to_string(),
+            address: address.to_string(),
+            avail_balance,
+            trans_balance,
+            operation: operation.to_string(),
+        }
+    }
 
     #[test]
     fn writes_augmented_block_to_db() {
@@ -700,4 +1223,75 @@ mod test {
         assert_eq!(deploy.inscription_number, 0);
         assert_eq!(deploy.address, "3K9KZZPB8NRwZVP5wNKX4VYhnswrJxpgZ4");
     }
+
+    #[test]
+    fn tracks_balances_history_and_holders() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.meta_protocols.brc20 = true;
+        drop_all_dbs(&config);
+        let sqlite_dbs = initialize_sqlite_dbs(&config, &ctx);
+        let db_conn = sqlite_dbs.brc20.as_ref().unwrap();
+
+        insert_ledger_rows(
+            &vec![
+                ledger_row(850000, "alice", 1000.0, 0.0, "mint"),
+                ledger_row(850000, "bob", 500.0, 0.0, "mint"),
+            ],
+            db_conn,
+            &ctx,
+        );
+        insert_ledger_rows(
+            &vec![
+                ledger_row(850001, "alice", -300.0, 300.0, "transfer"),
+                ledger_row(850002, "alice", 0.0, -300.0, "transfer_send"),
+                ledger_row(850002, "carol", 300.0, 0.0, "transfer_receive"),
+            ],
+            db_conn,
+            &ctx,
+        );
+
+        let balances =
+            get_address_balances_at_block_height("alice", None, Some(850001), db_conn, &ctx);
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].avail_balance, 700.0);
+        assert_eq!(balances[0].trans_balance, 300.0);
+        let balances =
+            get_address_balances_at_block_height("alice", Some("ORDI"), None, db_conn, &ctx);
+        assert_eq!(balances[0].avail_balance, 700.0);
+        assert_eq!(balances[0].trans_balance, 0.0);
+        assert!(
+            get_address_balances_at_block_height("carol", None, Some(850001), db_conn, &ctx)
+                .is_empty()
+        );
+
+        assert_eq!(get_token_holders_count("ordi", None, db_conn, &ctx), 3);
+        assert_eq!(
+            get_token_holders_count("ordi", Some(850001), db_conn, &ctx),
+            2
+        );
+        let holders = get_token_holders("ordi", Some(850001), None, 0, 2, db_conn, &ctx);
+        assert_eq!(holders[0].avail_balance + holders[0].trans_balance, 1000.0);
+        let holders = get_token_holders("ordi", None, None, 0, 2, db_conn, &ctx);
+        assert_eq!(holders.len(), 2);
+        assert_eq!(holders[0].address, "alice");
+        assert_eq!(holders[1].address, "bob");
+        let holders = get_token_holders("ordi", None, None, 2, 2, db_conn, &ctx);
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].address, "carol");
+        let after_bob = (500.0, "bob".to_string());
+        let holders = get_token_holders("ordi", None, Some(&after_bob), 0, 2, db_conn, &ctx);
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].address, "carol");
+        assert_eq!(get_latest_ledger_block_height(db_conn, &ctx), Some(850002));
+
+        // Rolled back balances go back to their state at the previous block.
+        delete_activity_in_block_range(850002, 850002, db_conn, &ctx);
+        assert_eq!(get_token_holders_count("ordi", None, db_conn, &ctx), 2);
+        let holders = get_token_holders("ordi", None, None, 0, 10, db_conn, &ctx);
+        assert_eq!(holders.len(), 2);
+        assert_eq!(holders[0].address, "alice");
+        assert_eq!(holders[0].avail_balance, 700.0);
+        assert_eq!(holders[0].trans_balance, 300.0);
+    }
 }
@@ -3,6 +3,7 @@ use chainhook_sdk::types::BitcoinNetwork;
 pub mod cache;
 pub mod db;
 pub mod parser;
+pub mod predicate;
 pub mod test_utils;
 pub mod verifier;
 
@@ -34,6 +34,23 @@ pub enum ParsedBrc20Operation {
     Transfer(ParsedBrc20BalanceData),
 }
 
+impl ParsedBrc20Operation {
+    pub fn operation_name(&self) -> &'static str {
+        match self {
+            ParsedBrc20Operation::Deploy(_) => "deploy",
+            ParsedBrc20Operation::Mint(_) => "mint",
+            ParsedBrc20Operation::Transfer(_) => "transfer",
+        }
+    }
+
+    pub fn tick(&self) -> &str {
+        match self {
+            ParsedBrc20Operation::Deploy(data) => &data.tick,
+            ParsedBrc20Operation::Mint(data) | ParsedBrc20Operation::Transfer(data) => &data.tick,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct Brc20DeployJson {
     p: String,
@@ -0,0 +1,313 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use chainhook_sdk::{
+    chainhooks::{
+        bitcoin::BitcoinTriggerChainhook,
+        types::{
+            BitcoinPredicateType, ChainhookFullSpecification, InscriptionFeedData,
+            OrdinalOperations, OrdinalsMetaProtocol,
+        },
+    },
+    types::{BitcoinTransactionData, Brc20Operation},
+    utils::Context,
+};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{config::Config, service::enrichment::EnrichmentDbConnections, try_warn};
+
+use super::db::{get_rejected_operations_in_block, Brc20DbRejectedOperationRow};
+
+const BRC20_PREDICATE_SCOPE: &str = "brc20";
+const BRC20_OPERATIONS: [&str; 4] = ["deploy", "mint", "transfer", "transfer_send"];
+
+lazy_static! {
+    /// BRC-20 filters of the registered predicates, by predicate uuid.
+    static ref BRC20_PREDICATE_FILTERS: RwLock<HashMap<String, Brc20PredicateFilter>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Restricts the transactions delivered to a predicate to the BRC-20 operations matching every non-empty criteria.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Brc20PredicateFilter {
+    #[serde(default)]
+    pub operations: Vec<String>,
+    #[serde(default)]
+    pub tickers: Vec<String>,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Also deliver the transactions inscribing matching operations rejected by the BRC-20 validation, along with the
+    /// reason they were rejected for.
+    #[serde(default)]
+    pub include_rejected: bool,
+}
+
+impl Brc20PredicateFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        for operation in self.operations.iter() {
+            if !BRC20_OPERATIONS.contains(&operation.as_str()) {
+                return Err(format!(
+                    "invalid brc20 operation {operation}, expected one of {}",
+                    BRC20_OPERATIONS.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches_parts(&self, name: &str, tick: &str, addresses: &[&String]) -> bool {
+        (self.operations.is_empty() || self.operations.iter().any(|o| o == name))
+            && (self.tickers.is_empty()
+                || self.tickers.iter().any(|t| t.eq_ignore_ascii_case(tick)))
+            && (self.addresses.is_empty() || addresses.iter().any(|a| self.addresses.contains(a)))
+    }
+
+    pub fn matches(&self, operation: &Brc20Operation) -> bool {
+        let (name, tick, addresses) = match operation {
+            Brc20Operation::Deploy(data) => ("deploy", &data.tick, vec![&data.address]),
+            Brc20Operation::Mint(data) => ("mint", &data.tick, vec![&data.address]),
+            Brc20Operation::Transfer(data) => ("transfer", &data.tick, vec![&data.address]),
+            Brc20Operation::TransferSend(data) => (
+                "transfer_send",
+                &data.tick,
+                vec![&data.sender_address, &data.receiver_address],
+            ),
+        };
+        self.matches_parts(name, tick, &addresses)
+    }
+
+    pub fn matches_rejected_operation(&self, operation: &Brc20DbRejectedOperationRow) -> bool {
+        self.include_rejected
+            && self.matches_parts(&operation.operation, &operation.tick, &[&operation.address])
+    }
+
+    pub fn matches_transaction(&self, tx: &BitcoinTransactionData) -> bool {
+        tx.metadata
+            .brc20_operation
+            .as_ref()
+            .map(|op| self.matches(op))
+            .unwrap_or(false)
+    }
+}
+
+/// Predicates can use `{"scope": "brc20", "operations": [..], "tickers": [..], "addresses": [..]}` as `if_this`, with
+/// `"include_rejected": true` to also get the operations rejected by the validation. Since this scope is not known to
+/// Chainhook, it gets replaced by a BRC-20 enabled inscription feed, and the filter is returned so that deliveries can be
+/// narrowed down to the matching operations.
+pub fn extract_brc20_predicate_filter(
+    predicate: &mut JsonValue,
+) -> Result<Option<Brc20PredicateFilter>, String> {
+    let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    else {
+        return Ok(None);
+    };
+    let mut filter = None;
+    for (_, network) in networks.iter_mut() {
+        let Some(if_this) = network.get_mut("if_this") else {
+            continue;
+        };
+        if if_this.get("scope").and_then(|s| s.as_str()) != Some(BRC20_PREDICATE_SCOPE) {
+            continue;
+        }
+        let network_filter: Brc20PredicateFilter = serde_json::from_value(if_this.clone())
+            .map_err(|e| format!("invalid brc20 predicate: {e}"))?;
+        network_filter.validate()?;
+        filter = Some(network_filter);
+        *if_this = json!({
+            "scope": "ordinals_protocol",
+            "operation": "inscription_feed",
+        });
+    }
+    Ok(filter)
+}
+
+/// Enables BRC-20 data in the inscription feeds of a predicate rewritten by `extract_brc20_predicate_filter`.
+pub fn enable_brc20_meta_protocol(predicate: &mut ChainhookFullSpecification) {
+    let ChainhookFullSpecification::Bitcoin(spec) = predicate else {
+        return;
+    };
+    for (_, network) in spec.networks.iter_mut() {
+        if let BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(
+            InscriptionFeedData { meta_protocols },
+        )) = &mut network.predicate
+        {
+            *meta_protocols = Some([OrdinalsMetaProtocol::All].into_iter().collect());
+        }
+    }
+}
+
+pub fn set_brc20_predicate_filter(uuid: &str, filter: Option<Brc20PredicateFilter>) {
+    let Ok(mut filters) = BRC20_PREDICATE_FILTERS.write() else {
+        return;
+    };
+    match filter {
+        Some(filter) => filters.insert(uuid.to_string(), filter),
+        None => filters.remove(uuid),
+    };
+}
+
+pub fn get_brc20_predicate_filter(uuid: &str) -> Option<Brc20PredicateFilter> {
+    BRC20_PREDICATE_FILTERS
+        .read()
+        .ok()
+        .and_then(|filters| filters.get(uuid).cloned())
+}
+
+/// Drops the transactions not matching the BRC-20 filter of the triggered predicate, if any, keeping the ones with a
+/// matching rejected operation when the filter includes them. Returns false when nothing is left to deliver.
+pub fn apply_brc20_predicate_filter(
+    trigger: &mut BitcoinTriggerChainhook,
+    db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) -> bool {
+    let Some(filter) = get_brc20_predicate_filter(&trigger.chainhook.uuid) else {
+        return true;
+    };
+    let db_conn = match filter.include_rejected {
+        true => match db_conns.get_brc20_db_conn(config, ctx) {
+            Ok(db_conn) => Some(db_conn),
+            Err(e) => {
+                try_warn!(
+                    ctx,
+                    "Unable to read rejected BRC-20 operations for predicate {}: {e}",
+                    trigger.chainhook.uuid
+                );
+                None
+            }
+        },
+        false => None,
+    };
+    let retain = |transactions: &mut Vec<&BitcoinTransactionData>, block_height: u64| {
+        let rejected_operations = db_conn
+            .map(|db_conn| get_rejected_operations_in_block(block_height, db_conn, ctx))
+            .unwrap_or_default();
+        transactions.retain(|tx| {
+            filter.matches_transaction(tx)
+                || rejected_operations
+                    .get(tx.transaction_identifier.get_hash_bytes_str())
+                    .is_some_and(|operations| {
+                        operations
+                            .iter()
+                            .any(|operation| filter.matches_rejected_operation(operation))
+                    })
+        });
+    };
+    for (transactions, block) in trigger.apply.iter_mut() {
+        retain(transactions, block.block_identifier.index);
+    }
+    trigger
+        .apply
+        .retain(|(transactions, _)| !transactions.is_empty());
+    for (transactions, block) in trigger.rollback.iter_mut() {
+        retain(transactions, block.block_identifier.index);
+    }
+    trigger
+        .rollback
+        .retain(|(transactions, _)| !transactions.is_empty());
+    !trigger.apply.is_empty() || !trigger.rollback.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::{Brc20BalanceData, Brc20Operation, Brc20TransferData};
+    use serde_json::json;
+
+    use crate::core::meta_protocols::brc20::db::Brc20DbRejectedOperationRow;
+
+    use super::{extract_brc20_predicate_filter, Brc20PredicateFilter};
+
+    #[test]
+    fn extracts_brc20_filter_from_predicate() {
+        let mut predicate = json!({
+            "uuid": "1",
+            "name": "brc20",
+            "version": 1,
+            "chain": "bitcoin",
+            "networks": {
+                "mainnet": {
+                    "if_this": { "scope": "brc20", "operations": ["mint"], "tickers": ["ORDI"] },
+                    "then_that": "noop"
+                }
+            }
+        });
+        let filter = extract_brc20_predicate_filter(&mut predicate)
+            .unwrap()
+            .unwrap();
+        assert_eq!(filter.operations, vec!["mint".to_string()]);
+        assert_eq!(filter.tickers, vec!["ORDI".to_string()]);
+        assert_eq!(
+            predicate["networks"]["mainnet"]["if_this"],
+            json!({ "scope": "ordinals_protocol", "operation": "inscription_feed" })
+        );
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "brc20", "operations": ["burn"] } } }
+        });
+        assert!(extract_brc20_predicate_filter(&mut predicate).is_err());
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol" } } }
+        });
+        assert_eq!(extract_brc20_predicate_filter(&mut predicate), Ok(None));
+    }
+
+    #[test]
+    fn matches_brc20_operations() {
+        let mint = Brc20Operation::Mint(Brc20BalanceData {
+            tick: "ordi".to_string(),
+            amt: "1000".to_string(),
+            address: "alice".to_string(),
+            inscription_id: "i0".to_string(),
+        });
+        let transfer_send = Brc20Operation::TransferSend(Brc20TransferData {
+            tick: "pepe".to_string(),
+            amt: "10".to_string(),
+            sender_address: "alice".to_string(),
+            receiver_address: "bob".to_string(),
+            inscription_id: "i1".to_string(),
+        });
+        assert!(Brc20PredicateFilter::default().matches(&mint));
+        let filter = Brc20PredicateFilter {
+            operations: vec![],
+            tickers: vec!["ORDI".to_string()],
+            addresses: vec![],
+            include_rejected: false,
+        };
+        assert!(filter.matches(&mint));
+        assert!(!filter.matches(&transfer_send));
+        let filter = Brc20PredicateFilter {
+            operations: vec!["transfer_send".to_string()],
+            tickers: vec![],
+            addresses: vec!["bob".to_string()],
+            include_rejected: false,
+        };
+        assert!(!filter.matches(&mint));
+        assert!(filter.matches(&transfer_send));
+    }
+
+    #[test]
+    fn matches_rejected_brc20_operations() {
+        let rejected_mint = Brc20DbRejectedOperationRow {
+            inscription_id: "i0".to_string(),
+            block_height: 800000,
+            tx_id: "a".repeat(64),
+            operation: "mint".to_string(),
+            tick: "ordi".to_string(),
+            address: "alice".to_string(),
+            reason: "Token ordi does not exist on mint attempt".to_string(),
+        };
+        let mut filter = Brc20PredicateFilter {
+            operations: vec!["mint".to_string()],
+            tickers: vec!["ORDI".to_string()],
+            addresses: vec![],
+            include_rejected: false,
+        };
+        assert!(!filter.matches_rejected_operation(&rejected_mint));
+        filter.include_rejected = true;
+        assert!(filter.matches_rejected_operation(&rejected_mint));
+        filter.addresses = vec!["bob".to_string()];
+        assert!(!filter.matches_rejected_operation(&rejected_mint));
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use chainhook_sdk::{
+    types::{
+        BitcoinBlockData, BitcoinTransactionData, BlockIdentifier, OrdinalInscriptionRevealData,
+        OrdinalInscriptionTransferData, OrdinalOperation,
+    },
+    utils::Context,
+};
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    config::Config,
+    db::ordinals::{create_or_open_readwrite_db, perform_query_one},
+    try_error, try_warn,
+};
+
+lazy_static! {
+    static ref METAPROTOCOL_INDEXERS: RwLock<Vec<Arc<dyn MetaprotocolIndexer>>> =
+        RwLock::new(vec![]);
+}
+
+/// Indexer for a protocol built on top of inscriptions. Implementations are registered with
+/// `register_metaprotocol_indexer` before starting ordhook, and get called for every block indexed, once its
+/// inscriptions have been numbered and persisted.
+pub trait MetaprotocolIndexer: Send + Sync {
+    /// Unique name of the protocol, used for scoping the indexer's store.
+    fn name(&self) -> &str;
+
+    fn on_inscription_revealed(
+        &self,
+        _reveal: &OrdinalInscriptionRevealData,
+        _tx: &BitcoinTransactionData,
+        _block_identifier: &BlockIdentifier,
+        _store: &MetaprotocolStore,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn on_inscription_transferred(
+        &self,
+        _transfer: &OrdinalInscriptionTransferData,
+        _tx: &BitcoinTransactionData,
+        _block_identifier: &BlockIdentifier,
+        _store: &MetaprotocolStore,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called before the store entries written at `block_height` are discarded, for indexers keeping state outside
+    /// of their store.
+    fn on_block_rolled_back(
+        &self,
+        _block_height: u64,
+        _store: &MetaprotocolStore,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub fn register_metaprotocol_indexer(indexer: Arc<dyn MetaprotocolIndexer>) -> Result<(), String> {
+    let mut indexers = METAPROTOCOL_INDEXERS
+        .write()
+        .map_err(|e| format!("unable to register metaprotocol indexer: {e}"))?;
+    if indexers.iter().any(|i| i.name() == indexer.name()) {
+        return Err(format!(
+            "metaprotocol indexer {} already registered",
+            indexer.name()
+        ));
+    }
+    indexers.push(indexer);
+    Ok(())
+}
+
+fn get_metaprotocol_indexers() -> Vec<Arc<dyn MetaprotocolIndexer>> {
+    METAPROTOCOL_INDEXERS
+        .read()
+        .map(|indexers| indexers.clone())
+        .unwrap_or_default()
+}
+
+/// Key-value store scoped to a single metaprotocol. Entries are versioned by block height, so that rolling back a
+/// block restores the values that preceded it.
+pub struct MetaprotocolStore<'a> {
+    protocol: &'a str,
+    block_height: u64,
+    db_conn: &'a Connection,
+    ctx: &'a Context,
+}
+
+impl<'a> MetaprotocolStore<'a> {
+    pub fn new(
+        protocol: &'a str,
+        block_height: u64,
+        db_conn: &'a Connection,
+        ctx: &'a Context,
+    ) -> Self {
+        MetaprotocolStore {
+            protocol,
+            block_height,
+            db_conn,
+            ctx,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let args: &[&dyn ToSql] = &[&self.protocol.to_sql().unwrap(), &key.to_sql().unwrap()];
+        let query = "
+            SELECT value
+            FROM entries
+            WHERE protocol = ? AND key = ?
+            ORDER BY block_height DESC
+            LIMIT 1
+        ";
+        perform_query_one(query, args, self.db_conn, self.ctx, |row| {
+            row.get::<_, Option<Vec<u8>>>(0).unwrap()
+        })
+        .flatten()
+    }
+
+    pub fn set(&self, key: &str, value: &[u8]) {
+        self.write(key, Some(value));
+    }
+
+    pub fn delete(&self, key: &str) {
+        self.write(key, None);
+    }
+
+    fn write(&self, key: &str, value: Option<&[u8]>) {
+        while let Err(e) = self.db_conn.execute(
+            "INSERT OR REPLACE INTO entries (protocol, key, block_height, value) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![&self.protocol, &key, &self.block_height, &value],
+        ) {
+            try_warn!(self.ctx, "unable to query metaprotocols.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+pub fn get_default_metaprotocols_db_file_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("metaprotocols.sqlite");
+    destination_path
+}
+
+pub fn initialize_metaprotocols_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let db_path = base_dir.map(|dir| get_default_metaprotocols_db_file_path(dir));
+    let conn = create_or_open_readwrite_db(db_path.as_ref(), ctx);
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS entries (
+            protocol TEXT NOT NULL,
+            key TEXT NOT NULL,
+            block_height INTEGER NOT NULL,
+            value BLOB,
+            PRIMARY KEY (protocol, key, block_height)
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table entries: {}", e.to_string());
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_entries_on_block_height ON entries(block_height);",
+            [],
+        ) {
+            try_warn!(
+                ctx,
+                "unable to create metaprotocols.sqlite: {}",
+                e.to_string()
+            );
+        }
+    }
+    conn
+}
+
+/// If metaprotocol indexers were registered, returns a read/write DB connection for their stores.
+pub fn metaprotocols_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
+    if get_metaprotocol_indexers().is_empty() {
+        return None;
+    }
+    Some(initialize_metaprotocols_db(
+        Some(&config.expected_sqlite_path()),
+        ctx,
+    ))
+}
+
+/// Feeds the inscription operations of an indexed block to every registered metaprotocol indexer.
+pub fn index_block_with_metaprotocol_indexers(
+    block: &BitcoinBlockData,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    for indexer in get_metaprotocol_indexers().iter() {
+        let store =
+            MetaprotocolStore::new(indexer.name(), block.block_identifier.index, db_conn, ctx);
+        for tx in block.transactions.iter() {
+            for operation in tx.metadata.ordinal_operations.iter() {
+                let result = match operation {
+                    OrdinalOperation::InscriptionRevealed(reveal) => {
+                        indexer.on_inscription_revealed(reveal, tx, &block.block_identifier, &store)
+                    }
+                    OrdinalOperation::InscriptionTransferred(transfer) => indexer
+                        .on_inscription_transferred(transfer, tx, &block.block_identifier, &store),
+                };
+                if let Err(e) = result {
+                    try_error!(
+                        ctx,
+                        "Metaprotocol indexer {} failed on block #{}: {e}",
+                        indexer.name(),
+                        block.block_identifier.index
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Notifies the registered metaprotocol indexers of the blocks rolled back, from the highest, and discards the store
+/// entries written in these blocks.
+pub fn rollback_metaprotocol_indexers_in_block_range(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    for indexer in get_metaprotocol_indexers().iter() {
+        for block_height in (start_block..=end_block).rev() {
+            let store = MetaprotocolStore::new(indexer.name(), block_height, db_conn, ctx);
+            if let Err(e) = indexer.on_block_rolled_back(block_height, &store) {
+                try_error!(
+                    ctx,
+                    "Metaprotocol indexer {} failed to roll back block #{block_height}: {e}",
+                    indexer.name()
+                );
+            }
+        }
+    }
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM entries WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(
+            ctx,
+            "unable to query metaprotocols.sqlite: {}",
+            e.to_string()
+        );
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chainhook_sdk::types::{
+        BitcoinTransactionData, BlockIdentifier, OrdinalInscriptionRevealData, OrdinalOperation,
+    };
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::{get_test_ctx, Brc20RevealBuilder},
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        index_block_with_metaprotocol_indexers, initialize_metaprotocols_db,
+        register_metaprotocol_indexer, rollback_metaprotocol_indexers_in_block_range,
+        MetaprotocolIndexer, MetaprotocolStore,
+    };
+
+    struct RevealCounter;
+
+    impl MetaprotocolIndexer for RevealCounter {
+        fn name(&self) -> &str {
+            "reveal-counter"
+        }
+
+        fn on_inscription_revealed(
+            &self,
+            _reveal: &OrdinalInscriptionRevealData,
+            _tx: &BitcoinTransactionData,
+            _block_identifier: &BlockIdentifier,
+            store: &MetaprotocolStore,
+        ) -> Result<(), String> {
+            let count = store.get("count").map(|v| v[0]).unwrap_or(0);
+            store.set("count", &[count + 1]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn store_entries_are_scoped_and_rolled_back() {
+        let ctx = get_test_ctx();
+        let conn = initialize_metaprotocols_db(None, &ctx);
+        register_metaprotocol_indexer(Arc::new(RevealCounter)).unwrap();
+        assert!(register_metaprotocol_indexer(Arc::new(RevealCounter)).is_err());
+
+        for height in [800000, 800001] {
+            let block = TestBlockBuilder::new()
+                .height(height)
+                .add_transaction(
+                    TestTransactionBuilder::new()
+                        .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                            Brc20RevealBuilder::new().build(),
+                        ))
+                        .build(),
+                )
+                .build();
+            index_block_with_metaprotocol_indexers(&block, &conn, &ctx);
+        }
+        let store = MetaprotocolStore::new("reveal-counter", 800002, &conn, &ctx);
+        assert_eq!(store.get("count"), Some(vec![2]));
+        assert_eq!(
+            MetaprotocolStore::new("other", 800002, &conn, &ctx).get("count"),
+            None
+        );
+
+        rollback_metaprotocol_indexers_in_block_range(800001, 800001, &conn, &ctx);
+        assert_eq!(store.get("count"), Some(vec![1]));
+        store.delete("count");
+        assert_eq!(store.get("count"), None);
+    }
+}
@@ -1 +1,47 @@
 pub mod brc20;
+pub mod indexer;
+pub mod runes;
+pub mod sns;
+
+use chainhook_sdk::utils::Context;
+use rusqlite::{Connection, ToSql};
+
+use crate::{db::ordinals::perform_query_exists, try_warn};
+
+/// Blocks which meta protocols changes were committed while their inscriptions changes may not have been. Written in
+/// the transaction of the meta protocol changes, and cleared once the inscriptions changes are committed.
+pub const CREATE_PARTIAL_COMMITS_TABLE: &str =
+    "CREATE TABLE IF NOT EXISTS partial_commits (block_height INTEGER NOT NULL PRIMARY KEY)";
+
+pub fn mark_partial_commit(block_height: u32, db_conn: &Connection) -> Result<(), String> {
+    db_conn
+        .execute(
+            "INSERT OR REPLACE INTO partial_commits (block_height) VALUES (?)",
+            rusqlite::params![&block_height],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("unable to mark partial commit of block #{block_height}: {e}"))
+}
+
+pub fn is_partial_commit_marked(block_height: u32, db_conn: &Connection, ctx: &Context) -> bool {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    perform_query_exists(
+        "SELECT 1 FROM partial_commits WHERE block_height = ?",
+        args,
+        db_conn,
+        ctx,
+    )
+}
+
+pub fn clear_partial_commit(block_height: u32, db_conn: &Connection, ctx: &Context) {
+    if let Err(e) = db_conn.execute(
+        "DELETE FROM partial_commits WHERE block_height = ?",
+        rusqlite::params![&block_height],
+    ) {
+        try_warn!(
+            ctx,
+            "unable to clear partial commit of block #{block_height}: {}",
+            e.to_string()
+        );
+    }
+}
@@ -0,0 +1,1134 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chainhook_sdk::{
+    bitcoin::{blockdata::script::Instruction::PushBytes, Network, Witness},
+    types::{BitcoinBlockData, BitcoinNetwork, BitcoinTransactionData},
+    utils::Context,
+};
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::CREATE_PARTIAL_COMMITS_TABLE,
+        protocol::{
+            addresses::script_hex_address, inscription_parsing::decode_witness,
+            inscription_sequencing::get_bitcoin_network,
+        },
+    },
+    db::ordinals::{
+        create_or_open_readwrite_db, open_existing_readonly_db, open_existing_readonly_db_snapshot,
+        perform_query_one, perform_query_set,
+    },
+    try_info, try_warn,
+    utils::{bitcoind::bitcoind_get_transaction_in_block, format_outpoint_to_watch},
+};
+
+use super::{
+    decipher_runestone, get_first_rune_height, is_op_return_output, Artifact, BurnReason, Rune,
+    RuneId, SpacedRune, Terms, COMMIT_CONFIRMATIONS,
+};
+
+/// Rune etched by a transaction, or by the runes protocol itself for the genesis rune.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunesDbEntryRow {
+    pub id: RuneId,
+    pub number: u64,
+    pub spaced_rune: SpacedRune,
+    pub block_height: u64,
+    pub tx_index: u32,
+    pub tx_id: String,
+    pub divisibility: u8,
+    pub symbol: Option<char>,
+    pub premine: u128,
+    pub terms: Option<Terms>,
+    pub turbo: bool,
+    /// Etched by a cenotaph: the rune can't be minted and has no premine.
+    pub cenotaph: bool,
+    pub timestamp: u32,
+}
+
+impl RunesDbEntryRow {
+    /// First height the rune can be minted at, the latest of its absolute and relative start heights.
+    pub fn mint_start(&self) -> Option<u64> {
+        let terms = self.terms?;
+        let relative = terms.offset.0.map(|o| self.block_height.saturating_add(o));
+        let absolute = terms.height.0;
+        relative
+            .zip(absolute)
+            .map(|(relative, absolute)| relative.max(absolute))
+            .or(relative)
+            .or(absolute)
+    }
+
+    /// Height the mints of the rune end at, the earliest of its absolute and relative end heights.
+    pub fn mint_end(&self) -> Option<u64> {
+        let terms = self.terms?;
+        let relative = terms.offset.1.map(|o| self.block_height.saturating_add(o));
+        let absolute = terms.height.1;
+        relative
+            .zip(absolute)
+            .map(|(relative, absolute)| relative.min(absolute))
+            .or(relative)
+            .or(absolute)
+    }
+
+    /// Amount of a mint of the rune at `height`, when it can still be minted.
+    pub fn mintable_amount(&self, height: u64, mints: u64) -> Option<u128> {
+        let terms = self.terms?;
+        if self.mint_start().is_some_and(|start| height < start) {
+            return None;
+        }
+        if self.mint_end().is_some_and(|end| height >= end) {
+            return None;
+        }
+        if u128::from(mints) >= terms.cap.unwrap_or_default() {
+            return None;
+        }
+        Some(terms.amount.unwrap_or_default())
+    }
+}
+
+/// Balance of a rune held by an unspent output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunesDbOutpointRow {
+    pub outpoint: String,
+    pub rune_id: RuneId,
+    pub amount: u128,
+    pub address: Option<String>,
+    pub value: u64,
+    pub block_height: u64,
+}
+
+/// Etching, mint or burn of a rune by a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunesDbActivityRow {
+    pub rune_id: RuneId,
+    pub operation: String,
+    pub amount: u128,
+    /// Why the runes got burned, for burns.
+    pub reason: Option<String>,
+}
+
+/// Rune every mainnet index starts with, mintable by anyone from block 840,000 to block 1,050,000.
+fn get_genesis_rune_entry() -> RunesDbEntryRow {
+    RunesDbEntryRow {
+        id: RuneId { block: 1, tx: 0 },
+        number: 0,
+        spaced_rune: SpacedRune {
+            rune: Rune(2055900680524219742),
+            spacers: 128,
+        },
+        block_height: 1,
+        tx_index: 0,
+        tx_id: "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+        divisibility: 0,
+        symbol: Some('\u{29C9}'),
+        premine: 0,
+        terms: Some(Terms {
+            amount: Some(1),
+            cap: Some(u128::MAX),
+            height: (Some(840000), Some(1050000)),
+            offset: (None, None),
+        }),
+        turbo: true,
+        cenotaph: false,
+        timestamp: 0,
+    }
+}
+
+/// If the given `config` has runes enabled, returns a read/write DB connection for runes.
+pub fn runes_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
+    if config.meta_protocols.runes {
+        Some(initialize_runes_db(
+            Some(&config.expected_sqlite_path()),
+            ctx,
+        ))
+    } else {
+        None
+    }
+}
+
+pub fn get_default_runes_db_file_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("runes.sqlite");
+    destination_path
+}
+
+pub fn initialize_runes_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let db_path = base_dir.map(|dir| get_default_runes_db_file_path(dir));
+    let conn = create_or_open_readwrite_db(db_path.as_ref(), ctx);
+    // Amounts are `u128` and stored as decimal text.
+    for statement in [
+        "CREATE TABLE IF NOT EXISTS runes (
+            id TEXT NOT NULL PRIMARY KEY,
+            number INTEGER NOT NULL,
+            name TEXT NOT NULL UNIQUE,
+            spacers INTEGER NOT NULL,
+            block_height INTEGER NOT NULL,
+            tx_index INTEGER NOT NULL,
+            tx_id TEXT NOT NULL,
+            divisibility INTEGER NOT NULL,
+            symbol TEXT,
+            premine TEXT NOT NULL,
+            has_terms INTEGER NOT NULL,
+            terms_amount TEXT,
+            terms_cap TEXT,
+            terms_height_start INTEGER,
+            terms_height_end INTEGER,
+            terms_offset_start INTEGER,
+            terms_offset_end INTEGER,
+            turbo INTEGER NOT NULL,
+            cenotaph INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL
+        )",
+        "CREATE INDEX IF NOT EXISTS index_runes_on_block_height ON runes(block_height);",
+        "CREATE TABLE IF NOT EXISTS rune_activity (
+            rune_id TEXT NOT NULL,
+            block_height INTEGER NOT NULL,
+            tx_index INTEGER NOT NULL,
+            tx_id TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            amount TEXT NOT NULL,
+            reason TEXT,
+            timestamp INTEGER NOT NULL
+        )",
+        "CREATE INDEX IF NOT EXISTS index_rune_activity_on_rune_id_and_operation ON rune_activity(rune_id, operation);",
+        "CREATE INDEX IF NOT EXISTS index_rune_activity_on_block_height ON rune_activity(block_height);",
+        "CREATE INDEX IF NOT EXISTS index_rune_activity_on_tx_id ON rune_activity(tx_id);",
+        "CREATE TABLE IF NOT EXISTS cenotaphs (
+            tx_id TEXT NOT NULL,
+            block_height INTEGER NOT NULL,
+            tx_index INTEGER NOT NULL,
+            flaw TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            PRIMARY KEY (tx_id, block_height)
+        )",
+        "CREATE INDEX IF NOT EXISTS index_cenotaphs_on_block_height ON cenotaphs(block_height);",
+        "CREATE TABLE IF NOT EXISTS rune_outpoints (
+            outpoint TEXT NOT NULL,
+            rune_id TEXT NOT NULL,
+            amount TEXT NOT NULL,
+            address TEXT,
+            value INTEGER NOT NULL,
+            block_height INTEGER NOT NULL,
+            spent_block_height INTEGER,
+            PRIMARY KEY (outpoint, rune_id)
+        )",
+        "CREATE INDEX IF NOT EXISTS index_rune_outpoints_on_address ON rune_outpoints(address, spent_block_height);",
+        "CREATE INDEX IF NOT EXISTS index_rune_outpoints_on_block_height ON rune_outpoints(block_height);",
+        "CREATE INDEX IF NOT EXISTS index_rune_outpoints_on_spent_block_height ON rune_outpoints(spent_block_height);",
+        CREATE_PARTIAL_COMMITS_TABLE,
+    ] {
+        if let Err(e) = conn.execute(statement, []) {
+            try_warn!(ctx, "unable to create runes.sqlite: {}", e.to_string());
+        }
+    }
+    conn
+}
+
+fn get_existing_runes_db_file_path(config: &Config) -> Result<PathBuf, String> {
+    if !config.meta_protocols.runes {
+        return Err("Runes indexing is disabled".to_string());
+    }
+    let db_path = get_default_runes_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(db_path)
+}
+
+/// Opens a read-only connection to an existing runes.sqlite, used for serving API queries.
+pub fn open_readonly_runes_db_conn(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    let db_path = get_existing_runes_db_file_path(config)?;
+    Ok(open_existing_readonly_db_snapshot(&db_path, ctx)?)
+}
+
+/// Opens a long-lived read-only connection to an existing runes.sqlite, seeing what the indexer commits.
+pub fn open_runes_db(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    let db_path = get_existing_runes_db_file_path(config)?;
+    Ok(open_existing_readonly_db(&db_path, ctx)?)
+}
+
+fn insert_rune_entry(entry: &RunesDbEntryRow, db_conn: &Connection) -> Result<(), String> {
+    let terms = entry.terms.unwrap_or_default();
+    db_conn
+        .execute(
+            "INSERT OR IGNORE INTO runes (id, number, name, spacers, block_height, tx_index, tx_id, divisibility,
+            symbol, premine, has_terms, terms_amount, terms_cap, terms_height_start, terms_height_end,
+            terms_offset_start, terms_offset_end, turbo, cenotaph, timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+            rusqlite::params![
+                &entry.id.to_string(),
+                &entry.number,
+                &entry.spaced_rune.rune.to_string(),
+                &entry.spaced_rune.spacers,
+                &entry.block_height,
+                &entry.tx_index,
+                &entry.tx_id,
+                &entry.divisibility,
+                &entry.symbol.map(|symbol| symbol.to_string()),
+                &entry.premine.to_string(),
+                &entry.terms.is_some(),
+                &terms.amount.map(|amount| amount.to_string()),
+                &terms.cap.map(|cap| cap.to_string()),
+                &terms.height.0,
+                &terms.height.1,
+                &terms.offset.0,
+                &terms.offset.1,
+                &entry.turbo,
+                &entry.cenotaph,
+                &entry.timestamp,
+            ],
+        )
+        .map_err(|e| format!("unable to insert into runes.sqlite: {e}"))?;
+    Ok(())
+}
+
+fn insert_rune_activity(
+    rune_id: &RuneId,
+    operation: &str,
+    amount: u128,
+    reason: Option<BurnReason>,
+    tx_index: u32,
+    tx: &BitcoinTransactionData,
+    block: &BitcoinBlockData,
+    db_conn: &Connection,
+) -> Result<(), String> {
+    db_conn
+        .execute(
+            "INSERT INTO rune_activity (rune_id, block_height, tx_index, tx_id, operation, amount, reason, timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                &rune_id.to_string(),
+                &block.block_identifier.index,
+                &tx_index,
+                &tx.transaction_identifier.get_hash_bytes_str(),
+                operation,
+                &amount.to_string(),
+                &reason.map(|reason| reason.as_str()),
+                &block.timestamp,
+            ],
+        )
+        .map_err(|e| format!("unable to insert into runes.sqlite: {e}"))?;
+    Ok(())
+}
+
+/// Marks the rune balances of an outpoint as spent at `block_height`, returning them.
+fn spend_rune_outpoint(
+    outpoint: &str,
+    block_height: u64,
+    db_conn: &Connection,
+) -> Result<Vec<(RuneId, u128)>, String> {
+    let mut stmt = db_conn
+        .prepare_cached(
+            "SELECT rune_id, amount FROM rune_outpoints WHERE outpoint = ? AND spent_block_height IS NULL",
+        )
+        .map_err(|e| format!("unable to query runes.sqlite: {e}"))?;
+    let balances = stmt
+        .query_map([outpoint], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("unable to query runes.sqlite: {e}"))?;
+    if balances.is_empty() {
+        return Ok(vec![]);
+    }
+    db_conn
+        .execute(
+            "UPDATE rune_outpoints SET spent_block_height = ?1 WHERE outpoint = ?2 AND spent_block_height IS NULL",
+            rusqlite::params![&block_height, outpoint],
+        )
+        .map_err(|e| format!("unable to update runes.sqlite: {e}"))?;
+    balances
+        .into_iter()
+        .map(|(rune_id, amount)| {
+            Ok((
+                rune_id.parse()?,
+                amount
+                    .parse()
+                    .map_err(|e| format!("invalid rune amount {amount}: {e}"))?,
+            ))
+        })
+        .collect()
+}
+
+fn map_rune_entry_row(row: &rusqlite::Row<'_>) -> RunesDbEntryRow {
+    let id: String = row.get(0).unwrap();
+    let name: String = row.get(2).unwrap();
+    let symbol: Option<String> = row.get(8).unwrap();
+    let premine: String = row.get(9).unwrap();
+    let has_terms: bool = row.get(10).unwrap();
+    let terms_amount: Option<String> = row.get(11).unwrap();
+    let terms_cap: Option<String> = row.get(12).unwrap();
+    RunesDbEntryRow {
+        id: id.parse().unwrap(),
+        number: row.get(1).unwrap(),
+        spaced_rune: SpacedRune {
+            rune: name.parse().unwrap(),
+            spacers: row.get(3).unwrap(),
+        },
+        block_height: row.get(4).unwrap(),
+        tx_index: row.get(5).unwrap(),
+        tx_id: row.get(6).unwrap(),
+        divisibility: row.get(7).unwrap(),
+        symbol: symbol.and_then(|symbol| symbol.chars().next()),
+        premine: premine.parse().unwrap(),
+        terms: has_terms.then(|| Terms {
+            amount: terms_amount.map(|amount| amount.parse().unwrap()),
+            cap: terms_cap.map(|cap| cap.parse().unwrap()),
+            height: (row.get(13).unwrap(), row.get(14).unwrap()),
+            offset: (row.get(15).unwrap(), row.get(16).unwrap()),
+        }),
+        turbo: row.get(17).unwrap(),
+        cenotaph: row.get(18).unwrap(),
+        timestamp: row.get(19).unwrap(),
+    }
+}
+
+const RUNE_ENTRY_COLUMNS: &str = "id, number, name, spacers, block_height, tx_index, tx_id, divisibility, symbol,
+    premine, has_terms, terms_amount, terms_cap, terms_height_start, terms_height_end, terms_offset_start,
+    terms_offset_end, turbo, cenotaph, timestamp";
+
+pub fn get_rune_entry(
+    rune_id: &RuneId,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<RunesDbEntryRow> {
+    let args: &[&dyn ToSql] = &[&rune_id.to_string().to_sql().unwrap()];
+    let query = format!("SELECT {RUNE_ENTRY_COLUMNS} FROM runes WHERE id = ?");
+    perform_query_one(&query, args, db_conn, ctx, map_rune_entry_row)
+}
+
+pub fn get_rune_entry_by_name(
+    rune: &Rune,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<RunesDbEntryRow> {
+    let args: &[&dyn ToSql] = &[&rune.to_string().to_sql().unwrap()];
+    let query = format!("SELECT {RUNE_ENTRY_COLUMNS} FROM runes WHERE name = ?");
+    perform_query_one(&query, args, db_conn, ctx, map_rune_entry_row)
+}
+
+fn count_runes(db_conn: &Connection, ctx: &Context) -> u64 {
+    perform_query_one("SELECT COUNT(*) FROM runes", &[], db_conn, ctx, |row| {
+        row.get(0).unwrap()
+    })
+    .unwrap_or(0)
+}
+
+pub fn count_rune_mints(rune_id: &RuneId, db_conn: &Connection, ctx: &Context) -> u64 {
+    let args: &[&dyn ToSql] = &[&rune_id.to_string().to_sql().unwrap()];
+    let query = "SELECT COUNT(*) FROM rune_activity WHERE rune_id = ? AND operation = 'mint'";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap()).unwrap_or(0)
+}
+
+/// Amount of a rune burned so far, summed in Rust as amounts don't fit SQLite integers.
+pub fn get_rune_burned_amount(rune_id: &RuneId, db_conn: &Connection, ctx: &Context) -> u128 {
+    let args: &[&dyn ToSql] = &[&rune_id.to_string().to_sql().unwrap()];
+    let query = "SELECT amount FROM rune_activity WHERE rune_id = ? AND operation = 'burn'";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        row.get::<_, String>(0).unwrap().parse::<u128>().unwrap()
+    })
+    .into_iter()
+    .fold(0u128, |total, amount| total.saturating_add(amount))
+}
+
+/// Etchings, mints and burns of a transaction, in the order they were indexed.
+pub fn find_rune_activity_of_transaction(
+    tx_id: &str,
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<RunesDbActivityRow> {
+    let args: &[&dyn ToSql] = &[&tx_id.to_sql().unwrap(), &block_height.to_sql().unwrap()];
+    let query = "
+        SELECT rune_id, operation, amount, reason
+        FROM rune_activity
+        WHERE tx_id = ? AND block_height = ?
+        ORDER BY rowid
+    ";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        let rune_id: String = row.get(0).unwrap();
+        let amount: String = row.get(2).unwrap();
+        RunesDbActivityRow {
+            rune_id: rune_id.parse().unwrap(),
+            operation: row.get(1).unwrap(),
+            amount: amount.parse().unwrap(),
+            reason: row.get(3).unwrap(),
+        }
+    })
+}
+
+/// Flaw of the cenotaph of a transaction, if its runestone was malformed.
+pub fn find_cenotaph_flaw_of_transaction(
+    tx_id: &str,
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<String> {
+    let args: &[&dyn ToSql] = &[&tx_id.to_sql().unwrap(), &block_height.to_sql().unwrap()];
+    let query = "SELECT flaw FROM cenotaphs WHERE tx_id = ? AND block_height = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+fn map_rune_outpoint_row(row: &rusqlite::Row<'_>) -> RunesDbOutpointRow {
+    let rune_id: String = row.get(1).unwrap();
+    let amount: String = row.get(2).unwrap();
+    RunesDbOutpointRow {
+        outpoint: row.get(0).unwrap(),
+        rune_id: rune_id.parse().unwrap(),
+        amount: amount.parse().unwrap(),
+        address: row.get(3).unwrap(),
+        value: row.get(4).unwrap(),
+        block_height: row.get(5).unwrap(),
+    }
+}
+
+/// Rune balances of the unspent outputs of an address, one row per output and rune.
+pub fn find_unspent_rune_outpoints_of_address(
+    address: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<RunesDbOutpointRow> {
+    let args: &[&dyn ToSql] = &[&address.to_sql().unwrap()];
+    let query = "
+        SELECT outpoint, rune_id, amount, address, value, block_height
+        FROM rune_outpoints
+        WHERE address = ? AND spent_block_height IS NULL
+        ORDER BY block_height, outpoint, rune_id
+    ";
+    perform_query_set(query, args, db_conn, ctx, map_rune_outpoint_row)
+}
+
+/// Rune balances transferred to the outputs of a transaction, spent since or not.
+pub fn find_rune_outpoints_of_transaction(
+    tx_id: &str,
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<RunesDbOutpointRow> {
+    let args: &[&dyn ToSql] = &[
+        &block_height.to_sql().unwrap(),
+        &format!("{tx_id}:%").to_sql().unwrap(),
+    ];
+    let query = "
+        SELECT outpoint, rune_id, amount, address, value, block_height
+        FROM rune_outpoints
+        WHERE block_height = ? AND outpoint LIKE ?
+        ORDER BY outpoint, rune_id
+    ";
+    perform_query_set(query, args, db_conn, ctx, map_rune_outpoint_row)
+}
+
+/// Rune balances of an unspent output.
+pub fn find_unspent_rune_balances_at_outpoint(
+    outpoint: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<RunesDbOutpointRow> {
+    let args: &[&dyn ToSql] = &[&outpoint.to_sql().unwrap()];
+    let query = "
+        SELECT outpoint, rune_id, amount, address, value, block_height
+        FROM rune_outpoints
+        WHERE outpoint = ? AND spent_block_height IS NULL
+    ";
+    perform_query_set(query, args, db_conn, ctx, map_rune_outpoint_row)
+}
+
+/// Whether an input of the transaction commits to `rune` in its tapscript, spending a taproot output confirmed at
+/// least `COMMIT_CONFIRMATIONS` blocks before. The committed output is fetched from bitcoind.
+fn transaction_commits_to_rune(
+    tx: &BitcoinTransactionData,
+    rune: Rune,
+    block_height: u64,
+    config: &Config,
+) -> Result<bool, String> {
+    let commitment = rune.commitment();
+    for input in tx.metadata.inputs.iter() {
+        let Some(witness) = decode_witness(&input.witness) else {
+            continue;
+        };
+        let witness = Witness::from_slice(&witness);
+        let Some(tapscript) = witness.tapscript() else {
+            continue;
+        };
+        for instruction in tapscript.instructions() {
+            let Ok(instruction) = instruction else {
+                break;
+            };
+            let PushBytes(push) = instruction else {
+                continue;
+            };
+            if push.as_bytes() != commitment.as_slice() {
+                continue;
+            }
+            let commit_height = input.previous_output.block_height;
+            if block_height.saturating_sub(commit_height) + 1 < COMMIT_CONFIRMATIONS {
+                continue;
+            }
+            let commit_tx = bitcoind_get_transaction_in_block(
+                &input.previous_output.txid.get_hash_bytes_str(),
+                commit_height,
+                config,
+            )
+            .map_err(|e| e.to_string())?;
+            if commit_tx
+                .output
+                .get(input.previous_output.vout as usize)
+                .is_some_and(|output| output.script_pubkey.is_v1_p2tr())
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Rune etched by the artifact of a transaction, if any. Explicit names must be unlocked at the height of the block,
+/// not reserved, available, and committed to by the transaction.
+fn get_etched_rune(
+    artifact: &Artifact,
+    tx: &BitcoinTransactionData,
+    tx_index: u32,
+    block_height: u64,
+    minimum: Rune,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Option<(RuneId, Rune)>, String> {
+    let rune = match artifact {
+        Artifact::Runestone(runestone) => match runestone.etching {
+            Some(etching) => etching.rune,
+            None => return Ok(None),
+        },
+        Artifact::Cenotaph(cenotaph) => match cenotaph.etching {
+            Some(rune) => Some(rune),
+            None => return Ok(None),
+        },
+    };
+    let rune = match rune {
+        Some(rune) => {
+            if rune < minimum
+                || rune.is_reserved()
+                || get_rune_entry_by_name(&rune, db_conn, ctx).is_some()
+                || !transaction_commits_to_rune(tx, rune, block_height, config)?
+            {
+                return Ok(None);
+            }
+            rune
+        }
+        None => Rune::reserved(block_height, tx_index),
+    };
+    Ok(Some((
+        RuneId {
+            block: block_height,
+            tx: tx_index,
+        },
+        rune,
+    )))
+}
+
+fn allocate(balance: &mut u128, balances: &mut BTreeMap<RuneId, u128>, id: RuneId, amount: u128) {
+    if amount > 0 {
+        *balance -= amount;
+        *balances.entry(id).or_default() += amount;
+    }
+}
+
+/// Moves the runes of a transaction following the ord runes specification: runes of the spent outputs and runes
+/// minted, etched or premined are allocated to the outputs by the edicts of its runestone, then the remaining ones to
+/// its pointer or first non-OP_RETURN output. Runes sent to OP_RETURN outputs, left without an output to receive
+/// them, or spent by cenotaphs are burned, each burn recording its reason.
+fn index_runes_in_transaction(
+    tx: &BitcoinTransactionData,
+    tx_index: u32,
+    block: &BitcoinBlockData,
+    minimum: Rune,
+    network: &Network,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let block_height = block.block_identifier.index;
+    let artifact = decipher_runestone(tx);
+
+    let mut unallocated: BTreeMap<RuneId, u128> = BTreeMap::new();
+    for input in tx.metadata.inputs.iter() {
+        let outpoint = format_outpoint_to_watch(
+            &input.previous_output.txid,
+            input.previous_output.vout as usize,
+        );
+        for (rune_id, amount) in spend_rune_outpoint(&outpoint, block_height, db_conn)? {
+            *unallocated.entry(rune_id).or_default() += amount;
+        }
+    }
+    if artifact.is_none() && unallocated.is_empty() {
+        return Ok(());
+    }
+
+    let outputs = &tx.metadata.outputs;
+    let op_returns: Vec<bool> = outputs.iter().map(is_op_return_output).collect();
+    let mut allocated: Vec<BTreeMap<RuneId, u128>> = vec![BTreeMap::new(); outputs.len()];
+    let mut burned: BTreeMap<(RuneId, BurnReason), u128> = BTreeMap::new();
+
+    if let Some(artifact) = &artifact {
+        if let Some(rune_id) = artifact.mint() {
+            if let Some(entry) = get_rune_entry(&rune_id, db_conn, ctx) {
+                let mints = count_rune_mints(&rune_id, db_conn, ctx);
+                if let Some(amount) = entry.mintable_amount(block_height, mints) {
+                    *unallocated.entry(rune_id).or_default() += amount;
+                    insert_rune_activity(
+                        &rune_id, "mint", amount, None, tx_index, tx, block, db_conn,
+                    )?;
+                }
+            }
+        }
+
+        let etched = get_etched_rune(
+            artifact,
+            tx,
+            tx_index,
+            block_height,
+            minimum,
+            db_conn,
+            config,
+            ctx,
+        )?;
+
+        if let Artifact::Runestone(runestone) = artifact {
+            if let Some((rune_id, _)) = etched {
+                *unallocated.entry(rune_id).or_default() += runestone
+                    .etching
+                    .and_then(|etching| etching.premine)
+                    .unwrap_or_default();
+            }
+            for edict in runestone.edicts.iter() {
+                // The rune id `0:0` refers to the rune etched by the transaction.
+                let rune_id = if edict.id == RuneId::default() {
+                    match etched {
+                        Some((rune_id, _)) => rune_id,
+                        None => continue,
+                    }
+                } else {
+                    edict.id
+                };
+                let Some(balance) = unallocated.get_mut(&rune_id) else {
+                    continue;
+                };
+                let output = edict.output as usize;
+                if output == outputs.len() {
+                    // Edicts to the output count split the runes between the non-OP_RETURN outputs.
+                    let destinations: Vec<usize> = op_returns
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(vout, op_return)| (!op_return).then_some(vout))
+                        .collect();
+                    if destinations.is_empty() {
+                        continue;
+                    }
+                    if edict.amount == 0 {
+                        let amount = *balance / destinations.len() as u128;
+                        let remainder = (*balance % destinations.len() as u128) as usize;
+                        for (i, vout) in destinations.iter().enumerate() {
+                            let amount = if i < remainder { amount + 1 } else { amount };
+                            allocate(balance, &mut allocated[*vout], rune_id, amount);
+                        }
+                    } else {
+                        for vout in destinations.iter() {
+                            let amount = edict.amount.min(*balance);
+                            allocate(balance, &mut allocated[*vout], rune_id, amount);
+                        }
+                    }
+                } else {
+                    let amount = if edict.amount == 0 {
+                        *balance
+                    } else {
+                        edict.amount.min(*balance)
+                    };
+                    allocate(balance, &mut allocated[output], rune_id, amount);
+                }
+            }
+        }
+
+        if let Some((rune_id, rune)) = etched {
+            let number = count_runes(db_conn, ctx);
+            let entry = match artifact {
+                Artifact::Runestone(runestone) => {
+                    let etching = runestone.etching.unwrap_or_default();
+                    RunesDbEntryRow {
+                        id: rune_id,
+                        number,
+                        spaced_rune: SpacedRune {
+                            rune,
+                            spacers: etching.spacers.unwrap_or_default(),
+                        },
+                        block_height,
+                        tx_index,
+                        tx_id: tx.transaction_identifier.get_hash_bytes_str().to_string(),
+                        divisibility: etching.divisibility.unwrap_or_default(),
+                        symbol: etching.symbol,
+                        premine: etching.premine.unwrap_or_default(),
+                        terms: etching.terms,
+                        turbo: etching.turbo,
+                        cenotaph: false,
+                        timestamp: block.timestamp,
+                    }
+                }
+                Artifact::Cenotaph(_) => RunesDbEntryRow {
+                    id: rune_id,
+                    number,
+                    spaced_rune: SpacedRune { rune, spacers: 0 },
+                    block_height,
+                    tx_index,
+                    tx_id: tx.transaction_identifier.get_hash_bytes_str().to_string(),
+                    divisibility: 0,
+                    symbol: None,
+                    premine: 0,
+                    terms: None,
+                    turbo: false,
+                    cenotaph: true,
+                    timestamp: block.timestamp,
+                },
+            };
+            insert_rune_entry(&entry, db_conn)?;
+            insert_rune_activity(
+                &rune_id,
+                "etching",
+                entry.premine,
+                None,
+                tx_index,
+                tx,
+                block,
+                db_conn,
+            )?;
+            try_info!(
+                ctx,
+                "Rune {} ({rune_id}) etched at block #{block_height}",
+                entry.spaced_rune
+            );
+        }
+    }
+
+    if let Some(Artifact::Cenotaph(cenotaph)) = &artifact {
+        db_conn
+            .execute(
+                "INSERT OR IGNORE INTO cenotaphs (tx_id, block_height, tx_index, flaw, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    &tx.transaction_identifier.get_hash_bytes_str(),
+                    &block_height,
+                    &tx_index,
+                    cenotaph.flaw.as_str(),
+                    &block.timestamp,
+                ],
+            )
+            .map_err(|e| format!("unable to insert into runes.sqlite: {e}"))?;
+        try_info!(
+            ctx,
+            "Cenotaph ({}) in transaction {} at block #{block_height}",
+            cenotaph.flaw.as_str(),
+            tx.transaction_identifier.hash
+        );
+        for (rune_id, balance) in unallocated {
+            *burned.entry((rune_id, BurnReason::Cenotaph)).or_default() += balance;
+        }
+    } else {
+        let pointer = match &artifact {
+            Some(Artifact::Runestone(runestone)) => runestone.pointer,
+            _ => None,
+        };
+        match pointer
+            .map(|pointer| pointer as usize)
+            .or_else(|| op_returns.iter().position(|op_return| !op_return))
+        {
+            Some(vout) => {
+                for (rune_id, balance) in unallocated {
+                    if balance > 0 {
+                        *allocated[vout].entry(rune_id).or_default() += balance;
+                    }
+                }
+            }
+            None => {
+                for (rune_id, balance) in unallocated {
+                    *burned.entry((rune_id, BurnReason::NoOutput)).or_default() += balance;
+                }
+            }
+        }
+    }
+
+    for (vout, balances) in allocated.into_iter().enumerate() {
+        if op_returns[vout] {
+            for (rune_id, amount) in balances {
+                *burned.entry((rune_id, BurnReason::OpReturn)).or_default() += amount;
+            }
+            continue;
+        }
+        let outpoint = format_outpoint_to_watch(&tx.transaction_identifier, vout);
+        let address = script_hex_address(&outputs[vout].script_pubkey, network);
+        for (rune_id, amount) in balances {
+            db_conn
+                .execute(
+                    "INSERT INTO rune_outpoints (outpoint, rune_id, amount, address, value, block_height)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    rusqlite::params![
+                        &outpoint,
+                        &rune_id.to_string(),
+                        &amount.to_string(),
+                        &address,
+                        &outputs[vout].value,
+                        &block_height,
+                    ],
+                )
+                .map_err(|e| format!("unable to insert into runes.sqlite: {e}"))?;
+        }
+    }
+    for ((rune_id, reason), amount) in burned {
+        if amount > 0 {
+            insert_rune_activity(
+                &rune_id,
+                "burn",
+                amount,
+                Some(reason),
+                tx_index,
+                tx,
+                block,
+                db_conn,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Indexes the runes etched, minted, transferred and burned in a block, from the activation height of runes on.
+pub fn index_runes_in_block(
+    block: &BitcoinBlockData,
+    config: &Config,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<(), String> {
+    let block_height = block.block_identifier.index;
+    let first_rune_height = get_first_rune_height(config);
+    if block_height < first_rune_height {
+        return Ok(());
+    }
+    if matches!(config.network.bitcoin_network, BitcoinNetwork::Mainnet) {
+        insert_rune_entry(&get_genesis_rune_entry(), db_conn)?;
+    }
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    let minimum = Rune::minimum_at_height(first_rune_height, block_height);
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        index_runes_in_transaction(
+            tx,
+            tx_index as u32,
+            block,
+            minimum,
+            &network,
+            db_conn,
+            config,
+            ctx,
+        )?;
+    }
+    Ok(())
+}
+
+/// Undoes the runes changes of a block range: its etchings, activity and outputs are deleted, and the outputs it spent
+/// are unspent.
+pub fn delete_runes_in_block_range(
+    start_block: u32,
+    end_block: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    for statement in [
+        "DELETE FROM runes WHERE block_height >= ?1 AND block_height <= ?2",
+        "DELETE FROM rune_activity WHERE block_height >= ?1 AND block_height <= ?2",
+        "DELETE FROM cenotaphs WHERE block_height >= ?1 AND block_height <= ?2",
+        "DELETE FROM rune_outpoints WHERE block_height >= ?1 AND block_height <= ?2",
+        "UPDATE rune_outpoints SET spent_block_height = NULL WHERE spent_block_height >= ?1 AND spent_block_height <= ?2",
+    ] {
+        while let Err(e) = db_conn.execute(statement, rusqlite::params![&start_block, &end_block]) {
+            try_warn!(ctx, "unable to query runes.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::{
+        bitcoin::{OutPoint, TxIn},
+        TransactionIdentifier,
+    };
+
+    use crate::{
+        config::Config,
+        core::{
+            meta_protocols::{
+                brc20::test_utils::get_test_ctx,
+                runes::{test_utils::runestone_tx, Rune, RuneId},
+            },
+            test_builders::{TestBlockBuilder, TestTxInBuilder},
+        },
+    };
+
+    use super::{
+        count_rune_mints, delete_runes_in_block_range, find_cenotaph_flaw_of_transaction,
+        find_rune_activity_of_transaction, find_rune_outpoints_of_transaction,
+        find_unspent_rune_outpoints_of_address, get_rune_burned_amount, get_rune_entry,
+        index_runes_in_block, initialize_runes_db,
+    };
+
+    const ADDRESS: &str = "bc1qd2j97e4h8k4jh7lq9usx9feyjgzy08u9me5yda";
+
+    fn spending_input(txid: &str, vout: u32, block_height: u64) -> TxIn {
+        let mut input = TestTxInBuilder::new().build();
+        input.previous_output = OutPoint {
+            txid: TransactionIdentifier {
+                hash: format!("0x{txid}"),
+            },
+            vout,
+            value: 5000,
+            block_height,
+        };
+        input
+    }
+
+    #[test]
+    fn etches_mints_and_transfers_runes() {
+        let ctx = get_test_ctx();
+        let mut config = Config::mainnet_default();
+        config.meta_protocols.runes = true;
+        let conn = initialize_runes_db(None, &ctx);
+
+        // Etching of a reserved name, premine of 1000 to output 1 and open mints of 100.
+        let mut etching = runestone_tx(&[2, 0b11, 6, 1000, 10, 100, 8, 10], 1);
+        etching.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "aa".repeat(32)),
+        };
+        let block = TestBlockBuilder::new()
+            .height(850000)
+            .add_transaction(runestone_tx(&[], 1))
+            .add_transaction(etching)
+            .build();
+        index_runes_in_block(&block, &config, &conn, &ctx).unwrap();
+        let rune_id = RuneId {
+            block: 850000,
+            tx: 1,
+        };
+        let entry = get_rune_entry(&rune_id, &conn, &ctx).unwrap();
+        assert_eq!(entry.spaced_rune.rune, Rune::reserved(850000, 1));
+        assert_eq!(entry.number, 1);
+        let outpoints = find_unspent_rune_outpoints_of_address(ADDRESS, &conn, &ctx);
+        assert_eq!(outpoints.len(), 1);
+        assert_eq!(outpoints[0].amount, 1000);
+        assert_eq!(outpoints[0].outpoint, format!("{}:1", "aa".repeat(32)));
+
+        // Mint sent to the first output, along with the premine spent by the transaction.
+        let mut mint = runestone_tx(&[20, 850000, 20, 1], 1);
+        mint.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "bb".repeat(32)),
+        };
+        mint.metadata
+            .inputs
+            .push(spending_input(&"aa".repeat(32), 1, 850000));
+        let block = TestBlockBuilder::new()
+            .height(850001)
+            .add_transaction(mint)
+            .build();
+        index_runes_in_block(&block, &config, &conn, &ctx).unwrap();
+        assert_eq!(count_rune_mints(&rune_id, &conn, &ctx), 1);
+        let outpoints = find_unspent_rune_outpoints_of_address(ADDRESS, &conn, &ctx);
+        assert_eq!(outpoints.len(), 1);
+        assert_eq!(outpoints[0].amount, 1100);
+        assert_eq!(outpoints[0].outpoint, format!("{}:1", "bb".repeat(32)));
+
+        delete_runes_in_block_range(850001, 850001, &conn, &ctx);
+        let outpoints = find_unspent_rune_outpoints_of_address(ADDRESS, &conn, &ctx);
+        assert_eq!(outpoints.len(), 1);
+        assert_eq!(outpoints[0].amount, 1000);
+        assert_eq!(count_rune_mints(&rune_id, &conn, &ctx), 0);
+    }
+
+    #[test]
+    fn records_burn_reasons() {
+        let ctx = get_test_ctx();
+        let mut config = Config::mainnet_default();
+        config.meta_protocols.runes = true;
+        let conn = initialize_runes_db(None, &ctx);
+
+        let mut etching = runestone_tx(&[2, 0b1, 6, 1000], 1);
+        etching.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "aa".repeat(32)),
+        };
+        let block = TestBlockBuilder::new()
+            .height(850000)
+            .add_transaction(runestone_tx(&[], 1))
+            .add_transaction(etching)
+            .build();
+        index_runes_in_block(&block, &config, &conn, &ctx).unwrap();
+        let rune_id = RuneId {
+            block: 850000,
+            tx: 1,
+        };
+
+        // 400 sent to the OP_RETURN output, the rest going to output 1.
+        let mut op_return_burn = runestone_tx(&[0, 850000, 1, 400, 0], 1);
+        op_return_burn.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "bb".repeat(32)),
+        };
+        op_return_burn
+            .metadata
+            .inputs
+            .push(spending_input(&"aa".repeat(32), 1, 850000));
+        // Unrecognized even tag: the remaining 600 get burned by the cenotaph.
+        let mut cenotaph = runestone_tx(&[24, 1], 1);
+        cenotaph.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "cc".repeat(32)),
+        };
+        cenotaph
+            .metadata
+            .inputs
+            .push(spending_input(&"bb".repeat(32), 1, 850001));
+        let block = TestBlockBuilder::new()
+            .height(850001)
+            .add_transaction(op_return_burn)
+            .add_transaction(cenotaph)
+            .build();
+        index_runes_in_block(&block, &config, &conn, &ctx).unwrap();
+
+        let activity = find_rune_activity_of_transaction(&"bb".repeat(32), 850001, &conn, &ctx);
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].operation, "burn");
+        assert_eq!(activity[0].amount, 400);
+        assert_eq!(activity[0].reason.as_deref(), Some("op_return"));
+        let transfers = find_rune_outpoints_of_transaction(&"bb".repeat(32), 850001, &conn, &ctx);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].amount, 600);
+        assert_eq!(
+            find_cenotaph_flaw_of_transaction(&"bb".repeat(32), 850001, &conn, &ctx),
+            None
+        );
+
+        let activity = find_rune_activity_of_transaction(&"cc".repeat(32), 850001, &conn, &ctx);
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].amount, 600);
+        assert_eq!(activity[0].reason.as_deref(), Some("cenotaph"));
+        assert_eq!(
+            find_cenotaph_flaw_of_transaction(&"cc".repeat(32), 850001, &conn, &ctx),
+            Some("unrecognized_even_tag".to_string())
+        );
+        assert!(
+            find_rune_outpoints_of_transaction(&"cc".repeat(32), 850001, &conn, &ctx).is_empty()
+        );
+        assert_eq!(get_rune_burned_amount(&rune_id, &conn, &ctx), 1000);
+        assert!(find_unspent_rune_outpoints_of_address(ADDRESS, &conn, &ctx).is_empty());
+
+        delete_runes_in_block_range(850001, 850001, &conn, &ctx);
+        assert_eq!(
+            find_cenotaph_flaw_of_transaction(&"cc".repeat(32), 850001, &conn, &ctx),
+            None
+        );
+        assert_eq!(get_rune_burned_amount(&rune_id, &conn, &ctx), 0);
+    }
+}
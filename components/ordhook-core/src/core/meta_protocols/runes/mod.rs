@@ -0,0 +1,779 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    str::FromStr,
+};
+
+use chainhook_sdk::{
+    bitcoin::blockdata::{
+        opcodes,
+        script::Instruction::{Op, PushBytes},
+    },
+    types::{bitcoin::TxOut, BitcoinNetwork, BitcoinTransactionData},
+};
+
+use crate::{config::Config, core::protocol::addresses::parse_script_hex};
+
+pub mod db;
+
+const TAG_BODY: u128 = 0;
+const TAG_DIVISIBILITY: u128 = 1;
+const TAG_FLAGS: u128 = 2;
+const TAG_SPACERS: u128 = 3;
+const TAG_RUNE: u128 = 4;
+const TAG_SYMBOL: u128 = 5;
+const TAG_PREMINE: u128 = 6;
+const TAG_CAP: u128 = 8;
+const TAG_AMOUNT: u128 = 10;
+const TAG_HEIGHT_START: u128 = 12;
+const TAG_HEIGHT_END: u128 = 14;
+const TAG_OFFSET_START: u128 = 16;
+const TAG_OFFSET_END: u128 = 18;
+const TAG_MINT: u128 = 20;
+const TAG_POINTER: u128 = 22;
+
+const FLAG_ETCHING: u32 = 0;
+const FLAG_TERMS: u32 = 1;
+const FLAG_TURBO: u32 = 2;
+
+const MAX_DIVISIBILITY: u8 = 38;
+const MAX_SPACERS: u32 = 0b00000111_11111111_11111111_11111111;
+const SUBSIDY_HALVING_INTERVAL: u64 = 210_000;
+/// Confirmations the output committing to a rune name must have when the rune gets etched.
+pub const COMMIT_CONFIRMATIONS: u64 = 6;
+
+/// `STEPS[n]` is the smallest rune name made of `n + 1` letters.
+const STEPS: [u128; 28] = [
+    0,
+    26,
+    702,
+    18278,
+    475254,
+    12356630,
+    321272406,
+    8353082582,
+    217180147158,
+    5646683826134,
+    146813779479510,
+    3817158266467286,
+    99246114928149462,
+    2580398988131886038,
+    67090373691429037014,
+    1744349715977154962390,
+    45353092615406029022166,
+    1179180408000556754576342,
+    30658690608014475618984918,
+    797125955808376366093607894,
+    20725274851017785518433805270,
+    538857146126462423479278937046,
+    14010285799288023010461252363222,
+    364267430781488598271992561443798,
+    9470953200318703555071806597538774,
+    246244783208286292431866971536008150,
+    6402364363415443603228541259936211926,
+    166461473448801533683942072758341510102,
+];
+
+/// Names from `AAAAAAAAAAAAAAAAAAAAAAAAAAA` on are reserved for the runes etched without an explicit name.
+const RESERVED: u128 = STEPS[26];
+
+/// Height runes get activated at, following ord: the fourth halving on mainnet.
+pub fn get_first_rune_height(config: &Config) -> u64 {
+    match config.network.bitcoin_network {
+        BitcoinNetwork::Mainnet => SUBSIDY_HALVING_INTERVAL * 4,
+        BitcoinNetwork::Testnet if !config.network.testnet4 => SUBSIDY_HALVING_INTERVAL * 12,
+        _ => 0,
+    }
+}
+
+/// Identifier of a rune, the height and index of its etching transaction, rendered as `<block>:<tx>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuneId {
+    pub block: u64,
+    pub tx: u32,
+}
+
+impl RuneId {
+    pub fn new(block: u64, tx: u32) -> Option<RuneId> {
+        if block == 0 && tx > 0 {
+            return None;
+        }
+        Some(RuneId { block, tx })
+    }
+
+    /// Edicts identify their rune by its delta with the rune of the previous edict.
+    fn next(self, block: u128, tx: u128) -> Option<RuneId> {
+        RuneId::new(
+            self.block.checked_add(block.try_into().ok()?)?,
+            if block == 0 {
+                self.tx.checked_add(tx.try_into().ok()?)?
+            } else {
+                tx.try_into().ok()?
+            },
+        )
+    }
+}
+
+impl fmt::Display for RuneId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.block, self.tx)
+    }
+}
+
+impl FromStr for RuneId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (block, tx) = s.split_once(':').ok_or(format!("invalid rune id {s}"))?;
+        RuneId::new(
+            block.parse().map_err(|_| format!("invalid rune id {s}"))?,
+            tx.parse().map_err(|_| format!("invalid rune id {s}"))?,
+        )
+        .ok_or(format!("invalid rune id {s}"))
+    }
+}
+
+/// Name of a rune, a base-26 number rendered with the letters `A` to `Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Rune(pub u128);
+
+impl Rune {
+    /// Smallest name that can be etched at `height`: names of 13 letters get unlocked first, then one letter less
+    /// every 17,500 blocks, until every name is unlocked after 210,000 blocks.
+    pub fn minimum_at_height(first_rune_height: u64, height: u64) -> Rune {
+        let offset = height.saturating_add(1);
+        const INTERVAL: u64 = SUBSIDY_HALVING_INTERVAL / 12;
+        let start = first_rune_height;
+        let end = start + SUBSIDY_HALVING_INTERVAL;
+        if offset < start {
+            return Rune(STEPS[12]);
+        }
+        if offset >= end {
+            return Rune(0);
+        }
+        let progress = offset.saturating_sub(start);
+        let length = 12u64.saturating_sub(progress / INTERVAL) as usize;
+        let end = STEPS[length - 1];
+        let start = STEPS[length];
+        let remainder = u128::from(progress % INTERVAL);
+        Rune(start - ((start - end) * remainder / u128::from(INTERVAL)))
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.0 >= RESERVED
+    }
+
+    /// Name given to the rune etched without an explicit name by the transaction `tx` of block `block`.
+    pub fn reserved(block: u64, tx: u32) -> Rune {
+        Rune(RESERVED + ((u128::from(block) << 32) | u128::from(tx)))
+    }
+
+    /// Data an input of the etching transaction must push in its tapscript to commit to the name.
+    pub fn commitment(&self) -> Vec<u8> {
+        let bytes = self.0.to_le_bytes();
+        let mut end = bytes.len();
+        while end > 0 && bytes[end - 1] == 0 {
+            end -= 1;
+        }
+        bytes[..end].to_vec()
+    }
+}
+
+impl fmt::Display for Rune {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut n = self.0;
+        if n == u128::MAX {
+            return write!(f, "BCGDENLQRQWDSLRUGSNLBTMFIJAV");
+        }
+        n += 1;
+        let mut symbol = String::new();
+        while n > 0 {
+            symbol.push(char::from(b'A' + ((n - 1) % 26) as u8));
+            n = (n - 1) / 26;
+        }
+        write!(f, "{}", symbol.chars().rev().collect::<String>())
+    }
+}
+
+impl FromStr for Rune {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut x = 0u128;
+        for (i, c) in s.chars().enumerate() {
+            if i > 0 {
+                x = x
+                    .checked_add(1)
+                    .ok_or(format!("rune name {s} out of range"))?;
+            }
+            x = x
+                .checked_mul(26)
+                .ok_or(format!("rune name {s} out of range"))?;
+            match c {
+                'A'..='Z' => {
+                    x = x
+                        .checked_add(c as u128 - 'A' as u128)
+                        .ok_or(format!("rune name {s} out of range"))?;
+                }
+                _ => return Err(format!("invalid character {c} in rune name {s}")),
+            }
+        }
+        if s.is_empty() {
+            return Err("empty rune name".to_string());
+        }
+        Ok(Rune(x))
+    }
+}
+
+/// Name of a rune along with its spacers: bit `i` of `spacers` adds a `•` after its letter `i`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpacedRune {
+    pub rune: Rune,
+    pub spacers: u32,
+}
+
+impl fmt::Display for SpacedRune {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rune = self.rune.to_string();
+        for (i, c) in rune.chars().enumerate() {
+            write!(f, "{c}")?;
+            if i < rune.len() - 1 && self.spacers & (1 << i) != 0 {
+                write!(f, "•")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SpacedRune {
+    type Err = String;
+
+    /// Spacers can be written as `•` or `.`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rune = String::new();
+        let mut spacers = 0u32;
+        for c in s.chars() {
+            match c {
+                'A'..='Z' => rune.push(c),
+                '.' | '•' => {
+                    if rune.is_empty() || rune.len() > 32 {
+                        return Err(format!("invalid spacer in rune name {s}"));
+                    }
+                    let flag = 1 << (rune.len() - 1);
+                    if spacers & flag != 0 {
+                        return Err(format!("double spacer in rune name {s}"));
+                    }
+                    spacers |= flag;
+                }
+                _ => return Err(format!("invalid character {c} in rune name {s}")),
+            }
+        }
+        if 32 - spacers.leading_zeros() >= rune.len() as u32 {
+            return Err(format!("trailing spacer in rune name {s}"));
+        }
+        Ok(SpacedRune {
+            rune: rune.parse()?,
+            spacers,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edict {
+    pub id: RuneId,
+    pub amount: u128,
+    pub output: u32,
+}
+
+/// Conditions of the open mints of a rune. Heights are absolute, offsets relative to the etching block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Terms {
+    pub amount: Option<u128>,
+    pub cap: Option<u128>,
+    pub height: (Option<u64>, Option<u64>),
+    pub offset: (Option<u64>, Option<u64>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Etching {
+    pub divisibility: Option<u8>,
+    pub premine: Option<u128>,
+    pub rune: Option<Rune>,
+    pub spacers: Option<u32>,
+    pub symbol: Option<char>,
+    pub terms: Option<Terms>,
+    pub turbo: bool,
+}
+
+impl Etching {
+    /// Maximum supply of the rune, if it fits in a `u128`.
+    pub fn supply(&self) -> Option<u128> {
+        let premine = self.premine.unwrap_or_default();
+        let cap = self.terms.and_then(|t| t.cap).unwrap_or_default();
+        let amount = self.terms.and_then(|t| t.amount).unwrap_or_default();
+        premine.checked_add(cap.checked_mul(amount)?)
+    }
+}
+
+/// Reasons a runestone is malformed, which makes it a cenotaph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flaw {
+    EdictOutput,
+    EdictRuneId,
+    InvalidScript,
+    Opcode,
+    SupplyOverflow,
+    TrailingIntegers,
+    TruncatedField,
+    UnrecognizedEvenTag,
+    UnrecognizedFlag,
+    Varint,
+}
+
+impl Flaw {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Flaw::EdictOutput => "edict_output",
+            Flaw::EdictRuneId => "edict_rune_id",
+            Flaw::InvalidScript => "invalid_script",
+            Flaw::Opcode => "opcode",
+            Flaw::SupplyOverflow => "supply_overflow",
+            Flaw::TrailingIntegers => "trailing_integers",
+            Flaw::TruncatedField => "truncated_field",
+            Flaw::UnrecognizedEvenTag => "unrecognized_even_tag",
+            Flaw::UnrecognizedFlag => "unrecognized_flag",
+            Flaw::Varint => "varint",
+        }
+    }
+}
+
+/// Why runes got burned by a transaction, as opposed to transferred to one of its outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BurnReason {
+    /// Allocated to an OP_RETURN output.
+    OpReturn,
+    /// Left unallocated by a transaction without any output able to receive them.
+    NoOutput,
+    /// Spent, minted or premined by a transaction with a cenotaph.
+    Cenotaph,
+}
+
+impl BurnReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BurnReason::OpReturn => "op_return",
+            BurnReason::NoOutput => "no_output",
+            BurnReason::Cenotaph => "cenotaph",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Runestone {
+    pub edicts: Vec<Edict>,
+    pub etching: Option<Etching>,
+    pub mint: Option<RuneId>,
+    pub pointer: Option<u32>,
+}
+
+/// Malformed runestone: the runes sent to its transaction get burned, its etching creates an unmintable rune without
+/// supply and its mint still counts against the cap of the rune.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cenotaph {
+    pub flaw: Flaw,
+    pub etching: Option<Rune>,
+    pub mint: Option<RuneId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Artifact {
+    Runestone(Runestone),
+    Cenotaph(Cenotaph),
+}
+
+impl Artifact {
+    pub fn mint(&self) -> Option<RuneId> {
+        match self {
+            Artifact::Runestone(runestone) => runestone.mint,
+            Artifact::Cenotaph(cenotaph) => cenotaph.mint,
+        }
+    }
+}
+
+/// Whether runes sent to an output get burned.
+pub fn is_op_return_output(output: &TxOut) -> bool {
+    parse_script_hex(&output.script_pubkey)
+        .map(|script| script.is_op_return())
+        .unwrap_or(false)
+}
+
+/// Decodes a LEB128 integer, returning it along with the number of bytes read.
+fn decode_varint(buffer: &[u8]) -> Option<(u128, usize)> {
+    let mut n = 0u128;
+    for (i, &byte) in buffer.iter().enumerate() {
+        if i > 18 {
+            return None;
+        }
+        let value = u128::from(byte) & 0b0111_1111;
+        if i == 18 && value & 0b0111_1100 != 0 {
+            return None;
+        }
+        n |= value << (7 * i);
+        if byte & 0b1000_0000 == 0 {
+            return Some((n, i + 1));
+        }
+    }
+    None
+}
+
+/// Payload of the first output whose script is `OP_RETURN OP_13` followed by data pushes.
+fn runestone_payload(tx: &BitcoinTransactionData) -> Option<Result<Vec<u8>, Flaw>> {
+    for output in tx.metadata.outputs.iter() {
+        let Some(script) = parse_script_hex(&output.script_pubkey) else {
+            continue;
+        };
+        let mut instructions = script.instructions();
+        if instructions.next() != Some(Ok(Op(opcodes::all::OP_RETURN))) {
+            continue;
+        }
+        if instructions.next() != Some(Ok(Op(opcodes::all::OP_PUSHNUM_13))) {
+            continue;
+        }
+        let mut payload = Vec::new();
+        for instruction in instructions {
+            match instruction {
+                Ok(PushBytes(push)) => payload.extend_from_slice(push.as_bytes()),
+                Ok(Op(_)) => return Some(Err(Flaw::Opcode)),
+                Err(_) => return Some(Err(Flaw::InvalidScript)),
+            }
+        }
+        return Some(Ok(payload));
+    }
+    None
+}
+
+/// Removes the `N` first values of a field when `with` accepts them. Rejected values are left in place, which makes
+/// an even tag an unrecognized one.
+fn take_field<const N: usize, T>(
+    fields: &mut HashMap<u128, VecDeque<u128>>,
+    tag: u128,
+    with: impl Fn([u128; N]) -> Option<T>,
+) -> Option<T> {
+    let field = fields.get_mut(&tag)?;
+    let mut values: [u128; N] = [0; N];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = *field.get(i)?;
+    }
+    let value = with(values)?;
+    field.drain(0..N);
+    if field.is_empty() {
+        fields.remove(&tag);
+    }
+    Some(value)
+}
+
+fn take_flag(flags: &mut u128, flag: u32) -> bool {
+    let mask = 1u128 << flag;
+    let set = *flags & mask != 0;
+    *flags &= !mask;
+    set
+}
+
+/// Deciphers the runestone of a transaction following the ord runes specification. Returns `None` for transactions
+/// without runestone, and a cenotaph for malformed ones.
+pub fn decipher_runestone(tx: &BitcoinTransactionData) -> Option<Artifact> {
+    let cenotaph = |flaw| {
+        Some(Artifact::Cenotaph(Cenotaph {
+            flaw,
+            etching: None,
+            mint: None,
+        }))
+    };
+    let payload = match runestone_payload(tx)? {
+        Ok(payload) => payload,
+        Err(flaw) => return cenotaph(flaw),
+    };
+    let mut integers = Vec::new();
+    let mut i = 0;
+    while i < payload.len() {
+        let Some((integer, length)) = decode_varint(&payload[i..]) else {
+            return cenotaph(Flaw::Varint);
+        };
+        integers.push(integer);
+        i += length;
+    }
+
+    let outputs_count = tx.metadata.outputs.len();
+    let mut flaw = None;
+    let mut edicts = Vec::new();
+    let mut fields: HashMap<u128, VecDeque<u128>> = HashMap::new();
+    for i in (0..integers.len()).step_by(2) {
+        let tag = integers[i];
+        if tag == TAG_BODY {
+            let mut id = RuneId::default();
+            for chunk in integers[i + 1..].chunks(4) {
+                if chunk.len() != 4 {
+                    flaw.get_or_insert(Flaw::TrailingIntegers);
+                    break;
+                }
+                let Some(next) = id.next(chunk[0], chunk[1]) else {
+                    flaw.get_or_insert(Flaw::EdictRuneId);
+                    break;
+                };
+                let Some(output) = u32::try_from(chunk[3])
+                    .ok()
+                    .filter(|output| *output as usize <= outputs_count)
+                else {
+                    flaw.get_or_insert(Flaw::EdictOutput);
+                    break;
+                };
+                id = next;
+                edicts.push(Edict {
+                    id,
+                    amount: chunk[2],
+                    output,
+                });
+            }
+            break;
+        }
+        let Some(&value) = integers.get(i + 1) else {
+            flaw.get_or_insert(Flaw::TruncatedField);
+            break;
+        };
+        fields.entry(tag).or_default().push_back(value);
+    }
+
+    let mut flags = take_field(&mut fields, TAG_FLAGS, |[flags]| Some(flags)).unwrap_or_default();
+    let etching = take_flag(&mut flags, FLAG_ETCHING).then(|| Etching {
+        divisibility: take_field(&mut fields, TAG_DIVISIBILITY, |[divisibility]| {
+            u8::try_from(divisibility)
+                .ok()
+                .filter(|divisibility| *divisibility <= MAX_DIVISIBILITY)
+        }),
+        premine: take_field(&mut fields, TAG_PREMINE, |[premine]| Some(premine)),
+        rune: take_field(&mut fields, TAG_RUNE, |[rune]| Some(Rune(rune))),
+        spacers: take_field(&mut fields, TAG_SPACERS, |[spacers]| {
+            u32::try_from(spacers)
+                .ok()
+                .filter(|spacers| *spacers <= MAX_SPACERS)
+        }),
+        symbol: take_field(&mut fields, TAG_SYMBOL, |[symbol]| {
+            char::from_u32(u32::try_from(symbol).ok()?)
+        }),
+        terms: take_flag(&mut flags, FLAG_TERMS).then(|| Terms {
+            cap: take_field(&mut fields, TAG_CAP, |[cap]| Some(cap)),
+            height: (
+                take_field(&mut fields, TAG_HEIGHT_START, |[height]| {
+                    u64::try_from(height).ok()
+                }),
+                take_field(&mut fields, TAG_HEIGHT_END, |[height]| {
+                    u64::try_from(height).ok()
+                }),
+            ),
+            amount: take_field(&mut fields, TAG_AMOUNT, |[amount]| Some(amount)),
+            offset: (
+                take_field(&mut fields, TAG_OFFSET_START, |[offset]| {
+                    u64::try_from(offset).ok()
+                }),
+                take_field(&mut fields, TAG_OFFSET_END, |[offset]| {
+                    u64::try_from(offset).ok()
+                }),
+            ),
+        }),
+        turbo: take_flag(&mut flags, FLAG_TURBO),
+    });
+    let mint = take_field(&mut fields, TAG_MINT, |[block, tx]| {
+        RuneId::new(block.try_into().ok()?, tx.try_into().ok()?)
+    });
+    let pointer = take_field(&mut fields, TAG_POINTER, |[pointer]| {
+        u32::try_from(pointer)
+            .ok()
+            .filter(|pointer| (*pointer as usize) < outputs_count)
+    });
+
+    if etching.map(|e| e.supply().is_none()).unwrap_or_default() {
+        flaw.get_or_insert(Flaw::SupplyOverflow);
+    }
+    if flags != 0 {
+        flaw.get_or_insert(Flaw::UnrecognizedFlag);
+    }
+    if fields.keys().any(|tag| tag % 2 == 0) {
+        flaw.get_or_insert(Flaw::UnrecognizedEvenTag);
+    }
+    if let Some(flaw) = flaw {
+        return Some(Artifact::Cenotaph(Cenotaph {
+            flaw,
+            etching: etching.and_then(|etching| etching.rune),
+            mint,
+        }));
+    }
+    Some(Artifact::Runestone(Runestone {
+        edicts,
+        etching,
+        mint,
+        pointer,
+    }))
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use chainhook_sdk::types::BitcoinTransactionData;
+
+    use crate::core::test_builders::{TestTransactionBuilder, TestTxOutBuilder};
+
+    fn encode_varint(mut n: u128, buffer: &mut Vec<u8>) {
+        while n >> 7 > 0 {
+            buffer.push(n.to_le_bytes()[0] | 0b1000_0000);
+            n >>= 7;
+        }
+        buffer.push(n.to_le_bytes()[0]);
+    }
+
+    /// `OP_RETURN OP_13` output script pushing the given integers.
+    pub fn runestone_script(integers: &[u128]) -> String {
+        let mut payload = Vec::new();
+        for integer in integers {
+            encode_varint(*integer, &mut payload);
+        }
+        assert!(payload.len() < 76);
+        format!("0x6a5d{:02x}{}", payload.len(), hex::encode(payload))
+    }
+
+    /// Transaction with a runestone made of `integers` as first output, followed by `outputs_count` P2WPKH outputs.
+    pub fn runestone_tx(integers: &[u128], outputs_count: usize) -> BitcoinTransactionData {
+        let mut builder = TestTransactionBuilder::new().add_output(
+            TestTxOutBuilder::new()
+                .value(0)
+                .script_pubkey(runestone_script(integers))
+                .build(),
+        );
+        for _ in 0..outputs_count {
+            builder = builder.add_output(TestTxOutBuilder::new().build());
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{
+        decipher_runestone, test_utils::runestone_tx, Artifact, Edict, Etching, Flaw, Rune, RuneId,
+        SpacedRune, Terms,
+    };
+    use crate::core::test_builders::{TestTransactionBuilder, TestTxOutBuilder};
+
+    #[test_case(0 => "A".to_string(); "first name")]
+    #[test_case(25 => "Z".to_string(); "last one letter name")]
+    #[test_case(26 => "AA".to_string(); "first two letters name")]
+    #[test_case(2055900680524219742 => "UNCOMMONGOODS".to_string(); "genesis rune")]
+    #[test_case(u128::MAX => "BCGDENLQRQWDSLRUGSNLBTMFIJAV".to_string(); "last name")]
+    fn renders_rune_names(rune: u128) -> String {
+        let name = Rune(rune).to_string();
+        assert_eq!(name.parse::<Rune>(), Ok(Rune(rune)));
+        name
+    }
+
+    #[test]
+    fn parses_spaced_rune_names() {
+        let spaced: SpacedRune = "UNCOMMON•GOODS".parse().unwrap();
+        assert_eq!(spaced.rune, Rune(2055900680524219742));
+        assert_eq!(spaced.spacers, 128);
+        assert_eq!(spaced.to_string(), "UNCOMMON•GOODS");
+        assert_eq!("UNCOMMON.GOODS".parse::<SpacedRune>(), Ok(spaced));
+        assert!("UNCOMMON•".parse::<SpacedRune>().is_err());
+        assert!("•GOODS".parse::<SpacedRune>().is_err());
+    }
+
+    #[test_case(840000, 839999 => Rune(99246114928149462); "before activation")]
+    #[test_case(840000, 840000 => Rune(99246114928149462 - (99246114928149462 - 3817158266467286) / 17500); "at activation")]
+    #[test_case(840000, 1049999 => Rune(0); "fully unlocked")]
+    fn unlocks_rune_names(first_rune_height: u64, height: u64) -> Rune {
+        Rune::minimum_at_height(first_rune_height, height)
+    }
+
+    #[test]
+    fn deciphers_runestones() {
+        // Etching with terms, premine sent to output 1 by an edict of the etched rune.
+        let tx = runestone_tx(
+            &[
+                2, 0b11, 4, 1000, 1, 2, 6, 500, 10, 100, 8, 21, 0, 0, 0, 500, 1,
+            ],
+            2,
+        );
+        assert_eq!(
+            decipher_runestone(&tx),
+            Some(Artifact::Runestone(super::Runestone {
+                edicts: vec![Edict {
+                    id: RuneId { block: 0, tx: 0 },
+                    amount: 500,
+                    output: 1,
+                }],
+                etching: Some(Etching {
+                    divisibility: Some(2),
+                    premine: Some(500),
+                    rune: Some(Rune(1000)),
+                    spacers: None,
+                    symbol: None,
+                    terms: Some(Terms {
+                        amount: Some(100),
+                        cap: Some(21),
+                        height: (None, None),
+                        offset: (None, None),
+                    }),
+                    turbo: false,
+                }),
+                mint: None,
+                pointer: None,
+            }))
+        );
+
+        let tx = TestTransactionBuilder::new()
+            .add_output(TestTxOutBuilder::new().build())
+            .build();
+        assert_eq!(decipher_runestone(&tx), None);
+    }
+
+    #[test_case(&[0, 1, 0, 0, 5] => Some(Flaw::EdictOutput); "edict to missing output")]
+    #[test_case(&[0, 0, 1, 0, 0] => Some(Flaw::EdictRuneId); "edict of invalid rune id")]
+    #[test_case(&[0, 1, 0, 0] => Some(Flaw::TrailingIntegers); "incomplete edict")]
+    #[test_case(&[20, 840000, 20] => Some(Flaw::TruncatedField); "truncated field")]
+    #[test_case(&[2, 0b1000, 20, 1, 20, 0] => Some(Flaw::UnrecognizedFlag); "unrecognized flag")]
+    #[test_case(&[24, 1] => Some(Flaw::UnrecognizedEvenTag); "unrecognized even tag")]
+    #[test_case(&[25, 1] => None; "unrecognized odd tag")]
+    #[test_case(&[2, 0b11, 6, u128::MAX, 8, 2, 10, 1] => Some(Flaw::SupplyOverflow); "supply overflow")]
+    fn detects_cenotaphs(integers: &[u128]) -> Option<Flaw> {
+        match decipher_runestone(&runestone_tx(integers, 1)) {
+            Some(Artifact::Cenotaph(cenotaph)) => Some(cenotaph.flaw),
+            Some(Artifact::Runestone(_)) => None,
+            None => panic!("runestone not found"),
+        }
+    }
+
+    #[test]
+    fn detects_malformed_payloads() {
+        let tx = TestTransactionBuilder::new()
+            .add_output(
+                TestTxOutBuilder::new()
+                    .script_pubkey("0x6a5d51".to_string())
+                    .build(),
+            )
+            .build();
+        assert!(matches!(
+            decipher_runestone(&tx),
+            Some(Artifact::Cenotaph(cenotaph)) if cenotaph.flaw == Flaw::Opcode
+        ));
+        let tx = TestTransactionBuilder::new()
+            .add_output(
+                TestTxOutBuilder::new()
+                    .script_pubkey("0x6a5d0180".to_string())
+                    .build(),
+            )
+            .build();
+        assert!(matches!(
+            decipher_runestone(&tx),
+            Some(Artifact::Cenotaph(cenotaph)) if cenotaph.flaw == Flaw::Varint
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::CREATE_PARTIAL_COMMITS_TABLE,
+        protocol::inscription_parsing::get_inscriptions_revealed_in_block,
+    },
+    db::ordinals::{
+        create_or_open_readwrite_db, open_existing_readonly_db_snapshot, perform_query_one,
+    },
+    try_info, try_warn,
+};
+
+use super::parse_sns_name_from_reveal;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnsDbNameRow {
+    pub name: String,
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    pub block_height: u64,
+    pub address: Option<String>,
+}
+
+/// If the given `config` has SNS enabled, returns a read/write DB connection for SNS.
+pub fn sns_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
+    if config.meta_protocols.sns {
+        Some(initialize_sns_db(Some(&config.expected_sqlite_path()), ctx))
+    } else {
+        None
+    }
+}
+
+pub fn get_default_sns_db_file_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("sns.sqlite");
+    destination_path
+}
+
+pub fn initialize_sns_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let db_path = base_dir.map(|dir| get_default_sns_db_file_path(dir));
+    let conn = create_or_open_readwrite_db(db_path.as_ref(), ctx);
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS names (
+            name TEXT NOT NULL PRIMARY KEY,
+            inscription_id TEXT NOT NULL,
+            inscription_number INTEGER NOT NULL,
+            block_height INTEGER NOT NULL,
+            address TEXT
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table names: {}", e.to_string());
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_names_on_block_height ON names(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sns.sqlite: {}", e.to_string());
+        }
+    }
+    if let Err(e) = conn.execute(CREATE_PARTIAL_COMMITS_TABLE, []) {
+        try_warn!(
+            ctx,
+            "Unable to create table partial_commits: {}",
+            e.to_string()
+        );
+    }
+    conn
+}
+
+/// Opens a read-only connection to an existing sns.sqlite, used for serving API queries.
+pub fn open_readonly_sns_db_conn(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    if !config.meta_protocols.sns {
+        return Err("SNS indexing is disabled".to_string());
+    }
+    let db_path = get_default_sns_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(open_existing_readonly_db_snapshot(&db_path, ctx)?)
+}
+
+/// Registers the names inscribed in a block. Only the first inscription of a name is valid, later ones are ignored.
+pub fn index_sns_names_in_block(
+    block: &BitcoinBlockData,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<(), String> {
+    for reveal in get_inscriptions_revealed_in_block(block) {
+        let Some(name) = parse_sns_name_from_reveal(reveal) else {
+            continue;
+        };
+        match db_conn.execute(
+            "INSERT OR IGNORE INTO names (name, inscription_id, inscription_number, block_height, address)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                &name,
+                &reveal.inscription_id,
+                &reveal.inscription_number.classic,
+                &block.block_identifier.index,
+                &reveal.inscriber_address
+            ],
+        ) {
+            Ok(1) => {
+                try_info!(
+                    ctx,
+                    "SNS name {name} registered by inscription {} at block #{}",
+                    reveal.inscription_id,
+                    block.block_identifier.index
+                );
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("unable to insert into sns.sqlite: {e}")),
+        }
+    }
+    Ok(())
+}
+
+pub fn delete_names_in_block_range(
+    start_block: u32,
+    end_block: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM names WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query sns.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn get_sns_name(name: &str, db_conn: &Connection, ctx: &Context) -> Option<SnsDbNameRow> {
+    let args: &[&dyn ToSql] = &[&name.to_sql().unwrap()];
+    let query = "
+        SELECT name, inscription_id, inscription_number, block_height, address
+        FROM names
+        WHERE name = ?
+    ";
+    perform_query_one(query, args, db_conn, ctx, |row| SnsDbNameRow {
+        name: row.get(0).unwrap(),
+        inscription_id: row.get(1).unwrap(),
+        inscription_number: row.get(2).unwrap(),
+        block_height: row.get(3).unwrap(),
+        address: row.get(4).unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::{OrdinalInscriptionRevealData, OrdinalOperation};
+
+    use crate::core::{
+        meta_protocols::{
+            brc20::test_utils::{get_test_ctx, Brc20RevealBuilder},
+            clear_partial_commit, is_partial_commit_marked, mark_partial_commit,
+        },
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        delete_names_in_block_range, get_sns_name, index_sns_names_in_block, initialize_sns_db,
+    };
+
+    fn name_reveal(
+        content: &str,
+        inscription_number: i64,
+        inscription_id: &str,
+    ) -> OrdinalInscriptionRevealData {
+        let mut reveal = Brc20RevealBuilder::new()
+            .inscription_number(inscription_number)
+            .inscription_id(inscription_id)
+            .build();
+        reveal.content_bytes = format!("0x{}", hex::encode(content));
+        reveal
+    }
+
+    #[test]
+    fn first_inscription_of_a_name_is_valid() {
+        let ctx = get_test_ctx();
+        let conn = initialize_sns_db(None, &ctx);
+        let block = TestBlockBuilder::new()
+            .height(800000)
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(name_reveal(
+                        "satoshi.sats",
+                        10,
+                        "a0",
+                    )))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(name_reveal(
+                        "Satoshi.sats",
+                        11,
+                        "b0",
+                    )))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(name_reveal(
+                        "cursed.sats",
+                        -1,
+                        "c0",
+                    )))
+                    .build(),
+            )
+            .build();
+        index_sns_names_in_block(&block, &conn, &ctx).unwrap();
+
+        let row = get_sns_name("satoshi.sats", &conn, &ctx).unwrap();
+        assert_eq!(row.inscription_id, "a0");
+        assert_eq!(row.inscription_number, 10);
+        assert_eq!(row.block_height, 800000);
+        assert_eq!(get_sns_name("cursed.sats", &conn, &ctx), None);
+
+        delete_names_in_block_range(800000, 800000, &conn, &ctx);
+        assert_eq!(get_sns_name("satoshi.sats", &conn, &ctx), None);
+    }
+
+    #[test]
+    fn marks_and_clears_partial_commits() {
+        let ctx = get_test_ctx();
+        let conn = initialize_sns_db(None, &ctx);
+        assert!(!is_partial_commit_marked(800000, &conn, &ctx));
+        mark_partial_commit(800000, &conn).unwrap();
+        assert!(is_partial_commit_marked(800000, &conn, &ctx));
+        assert!(!is_partial_commit_marked(800001, &conn, &ctx));
+        clear_partial_commit(800000, &conn, &ctx);
+        assert!(!is_partial_commit_marked(800000, &conn, &ctx));
+    }
+}
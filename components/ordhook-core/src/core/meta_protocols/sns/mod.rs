@@ -0,0 +1,75 @@
+use chainhook_sdk::types::OrdinalInscriptionRevealData;
+
+use crate::config::content_type_matches;
+
+pub mod db;
+
+/// Content types an SNS registration can be inscribed with.
+const SNS_CONTENT_TYPES: [&str; 2] = ["text/plain", "application/json"];
+
+#[derive(Deserialize)]
+struct SnsRegistrationJson {
+    p: String,
+    op: String,
+    name: String,
+}
+
+/// Normalizes a name following the sats names rules: only the text preceding the first whitespace is considered, it is
+/// lowercased and must be made of a non-empty name and namespace separated by a single period (`satoshi.sats`).
+pub fn normalize_sns_name(text: &str) -> Option<String> {
+    let name = text.split_whitespace().next()?.to_lowercase();
+    let (label, namespace) = name.split_once('.')?;
+    if label.is_empty() || namespace.is_empty() || namespace.contains('.') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Extracts the name registered by an inscription, either inscribed as plain text (`satoshi.sats`) or as a JSON
+/// document (`{"p": "sns", "op": "reg", "name": "satoshi.sats"}`).
+pub fn parse_sns_name(content_type: &str, content: &[u8]) -> Option<String> {
+    if !SNS_CONTENT_TYPES
+        .iter()
+        .any(|pattern| content_type_matches(pattern, content_type))
+    {
+        return None;
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    if text.trim_start().starts_with('{') {
+        let json = serde_json::from_str::<SnsRegistrationJson>(text).ok()?;
+        if json.p != "sns" || json.op != "reg" {
+            return None;
+        }
+        return normalize_sns_name(&json.name);
+    }
+    normalize_sns_name(text)
+}
+
+/// Cursed inscriptions can not register names. Registrations always get their content stored (see
+/// `parse_inscriptions_from_standardized_tx`), other inscriptions whose content was not stored are ignored.
+pub fn parse_sns_name_from_reveal(reveal: &OrdinalInscriptionRevealData) -> Option<String> {
+    if reveal.inscription_number.classic < 0 {
+        return None;
+    }
+    let content = hex::decode(reveal.content_bytes.strip_prefix("0x")?).ok()?;
+    parse_sns_name(&reveal.content_type, &content)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::parse_sns_name;
+
+    #[test_case("text/plain;charset=utf-8", "satoshi.sats" => Some("satoshi.sats".to_string()); "plain text")]
+    #[test_case("text/plain", "  Satoshi.SATS\nsome notes" => Some("satoshi.sats".to_string()); "first word lowercased")]
+    #[test_case("text/plain", "satoshi" => None; "missing namespace")]
+    #[test_case("text/plain", "sat.oshi.sats" => None; "multiple periods")]
+    #[test_case("text/plain", ".sats" => None; "empty name")]
+    #[test_case("image/png", "satoshi.sats" => None; "unsupported content type")]
+    #[test_case("application/json", r#"{"p":"sns","op":"reg","name":"Satoshi.sats"}"# => Some("satoshi.sats".to_string()); "json registration")]
+    #[test_case("text/plain", r#"{"p":"brc-20","op":"reg","name":"satoshi.sats"}"# => None; "json other protocol")]
+    fn parses_sns_names(content_type: &str, content: &str) -> Option<String> {
+        parse_sns_name(content_type, content.as_bytes())
+    }
+}
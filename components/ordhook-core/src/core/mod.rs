@@ -10,6 +10,8 @@ use std::hash::BuildHasherDefault;
 use std::ops::Div;
 
 use chainhook_sdk::{types::BitcoinNetwork, utils::Context};
+use rocksdb::DB;
+use rusqlite::Connection;
 
 use crate::{
     config::Config,
@@ -22,13 +24,18 @@ use crate::{
         initialize_sqlite_dbs,
         ordinals::{find_latest_inscription_block_height, open_ordinals_db},
     },
+    error::OrdhookError,
     utils::bitcoind::bitcoind_get_block_height,
 };
 
 pub fn first_inscription_height(config: &Config) -> u64 {
+    if let Some(first_inscription_height) = config.network.first_inscription_height {
+        return first_inscription_height;
+    }
     match config.network.bitcoin_network {
         BitcoinNetwork::Mainnet => 767430,
         BitcoinNetwork::Regtest => 1,
+        BitcoinNetwork::Testnet if config.network.testnet4 => 1,
         BitcoinNetwork::Testnet => 2413343,
         BitcoinNetwork::Signet => 112402,
     }
@@ -116,9 +123,12 @@ pub fn compute_next_satpoint_data(
     SatPosition::Output((selected_output_index, relative_offset_in_selected_output))
 }
 
-pub fn should_sync_rocks_db(config: &Config, ctx: &Context) -> Result<Option<(u64, u64)>, String> {
+pub fn should_sync_rocks_db(
+    config: &Config,
+    ctx: &Context,
+) -> Result<Option<(u64, u64)>, OrdhookError> {
     let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
-    let inscriptions_db_conn = open_ordinals_db(&config.expected_cache_path(), &ctx)?;
+    let inscriptions_db_conn = open_ordinals_db(&config.expected_sqlite_path(), &ctx)?;
     let last_compressed_block = find_last_block_inserted(&blocks_db) as u64;
     let last_indexed_block = match find_latest_inscription_block_height(&inscriptions_db_conn, ctx)?
     {
@@ -134,33 +144,42 @@ pub fn should_sync_rocks_db(config: &Config, ctx: &Context) -> Result<Option<(u6
     Ok(res)
 }
 
-pub fn should_sync_ordhook_db(
+/// Height of the next block to index, right after the last block indexed in the ordinals db. Blocks missing from
+/// hord.rocksdb are downloaded again.
+pub fn find_next_block_height_to_index(
+    blocks_db: &DB,
+    inscriptions_db_conn: &Connection,
     config: &Config,
     ctx: &Context,
-) -> Result<Option<(u64, u64, usize)>, String> {
-    let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
-    let mut start_block = find_last_block_inserted(&blocks_db) as u64;
-
-    if start_block == 0 {
-        let _ = initialize_sqlite_dbs(config, ctx);
-    }
-
-    let inscriptions_db_conn = open_ordinals_db(&config.expected_cache_path(), &ctx)?;
-
-    match find_latest_inscription_block_height(&inscriptions_db_conn, ctx)? {
+) -> Result<u64, OrdhookError> {
+    let last_block_inserted = find_last_block_inserted(blocks_db) as u64;
+    let next_block_height = match find_latest_inscription_block_height(inscriptions_db_conn, ctx)? {
         Some(height) => {
-            if find_pinned_block_bytes_at_block_height(height as u32, 3, &blocks_db, &ctx).is_none()
+            if find_pinned_block_bytes_at_block_height(height as u32, 3, blocks_db, config, ctx)
+                .is_none()
             {
-                start_block = start_block.min(height);
+                last_block_inserted.min(height) + 1
             } else {
-                start_block = height;
+                height + 1
             }
-            start_block += 1;
-        }
-        None => {
-            start_block = start_block.min(first_inscription_height(config));
         }
+        None => last_block_inserted.min(first_inscription_height(config)),
     };
+    Ok(next_block_height)
+}
+
+pub fn should_sync_ordhook_db(
+    config: &Config,
+    ctx: &Context,
+) -> Result<Option<(u64, u64, usize)>, OrdhookError> {
+    let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
+    if find_last_block_inserted(&blocks_db) == 0 {
+        let _ = initialize_sqlite_dbs(config, ctx);
+    }
+
+    let inscriptions_db_conn = open_ordinals_db(&config.expected_sqlite_path(), &ctx)?;
+    let start_block =
+        find_next_block_height_to_index(&blocks_db, &inscriptions_db_conn, config, ctx)?;
 
     // TODO: Gracefully handle Regtest, Testnet and Signet
     let end_block = bitcoind_get_block_height(config, ctx);
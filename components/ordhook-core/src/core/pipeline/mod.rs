@@ -6,15 +6,19 @@ use chainhook_sdk::utils::Context;
 use crossbeam_channel::bounded;
 use std::collections::{HashMap, VecDeque};
 use std::thread::{sleep, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 
-use crate::config::Config;
+use crate::config::{Config, ResourcesConfig};
 use crate::db::cursor::BlockBytesCursor;
-use crate::{try_debug, try_info};
+use crate::error::OrdhookError;
+use crate::utils::bitcoind::{bitcoind_check_blocks_available, build_bitcoind_http_client};
+use crate::utils::monitoring::PIPELINE_METRICS;
+use crate::utils::profiler::{record_block_fetch_duration, PendingFetchDurationsGuard};
+use crate::{try_debug, try_info, try_warn};
 
 use chainhook_sdk::indexer::bitcoin::{
-    build_http_client, parse_downloaded_block, try_download_block_bytes_with_retry,
+    parse_downloaded_block, try_download_block_bytes_with_retry,
 };
 
 use super::protocol::inscription_parsing::parse_inscriptions_and_standardize_block;
@@ -35,6 +39,49 @@ pub struct PostProcessorController {
     pub thread_handle: JoinHandle<()>,
 }
 
+/// Measures the time taken by a block download, for profiling purposes.
+async fn timed_download<F: std::future::Future>(
+    block_height: u64,
+    download: F,
+) -> (u64, Duration, F::Output) {
+    let started_at = Instant::now();
+    let result = download.await;
+    (block_height, started_at.elapsed(), result)
+}
+
+/// Downloads a block, retried `network_max_retries` times with backoff once chainhook-sdk gave up on it.
+async fn download_block_bytes_with_backoff(
+    http_client: reqwest::Client,
+    block_height: u64,
+    bitcoin_config: BitcoinConfig,
+    resources: ResourcesConfig,
+    ctx: Context,
+) -> Result<Vec<u8>, String> {
+    let mut attempt = 0;
+    loop {
+        match try_download_block_bytes_with_retry(
+            http_client.clone(),
+            block_height,
+            bitcoin_config.clone(),
+            ctx.clone(),
+        )
+        .await
+        {
+            Err(e) if attempt < resources.network_max_retries => {
+                attempt += 1;
+                let backoff = resources.network_retry_backoff(attempt);
+                try_warn!(
+                    ctx,
+                    "Unable to download block #{block_height}, retrying in {:?}: {e}",
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            res => return res,
+        }
+    }
+}
+
 /// Downloads blocks from bitcoind's RPC interface and pushes them to a `PostProcessorController` so they can be indexed or
 /// ingested as needed.
 pub async fn bitcoind_download_blocks(
@@ -44,7 +91,7 @@ pub async fn bitcoind_download_blocks(
     blocks_post_processor: &PostProcessorController,
     speed: usize,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     let bitcoin_config = BitcoinConfig {
         username: config.network.bitcoind_rpc_username.clone(),
         password: config.network.bitcoind_rpc_password.clone(),
@@ -55,17 +102,30 @@ pub async fn bitcoind_download_blocks(
 
     let number_of_blocks_to_process = blocks.len() as u64;
 
+    // A pruned bitcoind only keeps the most recent blocks: blocks are fetched and processed one at a time, and the ones
+    // it no longer has are never requested.
+    let (rpc_threads, speed) = match config.network.bitcoind_pruned {
+        true => {
+            bitcoind_check_blocks_available(config, blocks.iter().min().copied().unwrap_or(0))?;
+            (1, 1)
+        }
+        false => (config.resources.bitcoind_rpc_threads, speed),
+    };
+
     let (block_compressed_tx, block_compressed_rx) = crossbeam_channel::bounded(speed);
-    let http_client = build_http_client();
+    let http_client = build_bitcoind_http_client(config)?;
 
     let moved_config = bitcoin_config.clone();
     let moved_ctx = ctx.clone();
     let moved_http_client = http_client.clone();
+    let resources = config.resources.clone();
 
     let mut set = JoinSet::new();
 
     let start_block = *blocks.first().expect("no blocks to pipeline");
     let end_block = *blocks.last().expect("no blocks to pipeline");
+    // Fetch durations of blocks that never reach a processor are dropped however the pipeline exits.
+    let _pending_fetch_durations = PendingFetchDurationsGuard::new(start_block, end_block);
     let mut block_heights = VecDeque::from(blocks);
 
     // All the requests are being processed on the same thread.
@@ -83,18 +143,22 @@ pub async fn bitcoind_download_blocks(
     // Start blocking networking when each worker has a backlog of 8 blocks seems reasonable.
     let worker_queue_size = 2;
 
-    for _ in 0..config.resources.bitcoind_rpc_threads {
+    for _ in 0..rpc_threads {
         if let Some(block_height) = block_heights.pop_front() {
             let config = moved_config.clone();
             let ctx = moved_ctx.clone();
             let http_client = moved_http_client.clone();
             // We interleave the initial requests to avoid DDOSing bitcoind from the get go.
             sleep(Duration::from_millis(500));
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
+            set.spawn(timed_download(
                 block_height,
-                config,
-                ctx,
+                download_block_bytes_with_backoff(
+                    http_client,
+                    block_height,
+                    config,
+                    resources.clone(),
+                    ctx,
+                ),
             ));
         }
     }
@@ -136,6 +200,7 @@ pub async fn bitcoind_download_blocks(
                     } else {
                         None
                     };
+                    PIPELINE_METRICS.metrics_block_parsed();
                     let _ = block_compressed_tx_moved.send(Some((
                         block_height,
                         block_data,
@@ -207,6 +272,7 @@ pub async fn bitcoind_download_blocks(
                 // Early "continue"
                 if !ooo_compacted_blocks.is_empty() {
                     blocks_processed += ooo_compacted_blocks.len() as u64;
+                    PIPELINE_METRICS.metrics_blocks_sequenced(ooo_compacted_blocks.len() as u64);
                     let _ = blocks_post_processor_commands_tx.send(
                         PostProcessorCommand::ProcessBlocks(ooo_compacted_blocks, vec![]),
                     );
@@ -228,6 +294,7 @@ pub async fn bitcoind_download_blocks(
                 blocks_processed += blocks.len() as u64;
 
                 if !blocks.is_empty() {
+                    PIPELINE_METRICS.metrics_blocks_sequenced(blocks.len() as u64);
                     let _ = blocks_post_processor_commands_tx.send(
                         PostProcessorCommand::ProcessBlocks(compacted_blocks, blocks),
                     );
@@ -243,9 +310,12 @@ pub async fn bitcoind_download_blocks(
 
     let mut round_robin_worker_thread_index = 0;
     while let Some(res) = set.join_next().await {
-        let block = res
-            .expect("unable to retrieve block")
-            .expect("unable to deserialize block");
+        let (block_height, fetch_duration, res) = res.expect("unable to retrieve block");
+        let block = res.expect("unable to deserialize block");
+        if block_height >= start_sequencing_blocks_at_height {
+            record_block_fetch_duration(block_height, fetch_duration, config);
+        }
+        PIPELINE_METRICS.metrics_block_downloaded();
 
         loop {
             let res = tx_thread_pool[round_robin_worker_thread_index].send(Some(block.clone()));
@@ -261,11 +331,15 @@ pub async fn bitcoind_download_blocks(
             let config = moved_config.clone();
             let ctx = ctx.clone();
             let http_client = moved_http_client.clone();
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
+            set.spawn(timed_download(
                 block_height,
-                config,
-                ctx,
+                download_block_bytes_with_backoff(
+                    http_client,
+                    block_height,
+                    config,
+                    resources.clone(),
+                    ctx,
+                ),
             ));
         }
     }
@@ -9,8 +9,12 @@ use std::{
 use crate::{
     config::Config,
     core::pipeline::{PostProcessorCommand, PostProcessorController, PostProcessorEvent},
-    db::blocks::{insert_entry_in_blocks, open_blocks_db_with_retry},
+    db::{
+        blocks::{insert_entry_in_blocks, open_blocks_db_with_retry},
+        cold_blocks::{tier_cold_blocks, COLD_BLOCKS_BATCH_SIZE},
+    },
     try_error, try_info,
+    utils::monitoring::PIPELINE_METRICS,
 };
 
 pub fn start_block_archiving_processor(
@@ -49,7 +53,12 @@ pub fn start_block_archiving_processor(
                     },
                 };
                 processed_blocks += compacted_blocks.len();
+                let compacted_blocks_count = compacted_blocks.len() as u64;
                 store_compacted_blocks(compacted_blocks, update_tip, &blocks_db_rw, &ctx);
+                PIPELINE_METRICS.metrics_blocks_written(compacted_blocks_count);
+                if update_tip {
+                    tier_cold_blocks(COLD_BLOCKS_BATCH_SIZE, &blocks_db_rw, &config, &ctx);
+                }
 
                 if processed_blocks % 10_000 == 0 {
                     let _ = blocks_db_rw.flush_wal(true);
@@ -152,7 +161,7 @@ mod test {
 
         // Check that blocks exist in rocksdb
         let blocks_db = open_blocks_db_with_retry(false, &config, &ctx);
-        let result = find_block_bytes_at_block_height(849999, 3, &blocks_db, &ctx);
+        let result = find_block_bytes_at_block_height(849999, 3, &blocks_db, &config, &ctx);
         assert!(result.is_some());
     }
 }
@@ -1,5 +1,7 @@
 use std::{
+    any::Any,
     collections::{BTreeMap, HashMap},
+    panic::{catch_unwind, AssertUnwindSafe},
     sync::Arc,
     thread::{sleep, JoinHandle},
     time::Duration,
@@ -19,20 +21,28 @@ use std::hash::BuildHasherDefault;
 
 use crate::{
     core::{
-        meta_protocols::brc20::{
-            cache::{brc20_new_cache, Brc20MemoryCache},
-            db::brc20_new_rw_db_conn,
+        meta_protocols::{
+            brc20::{
+                cache::{brc20_new_cache, Brc20MemoryCache},
+                db::{brc20_new_rw_db_conn, delete_activity_in_block_range},
+            },
+            clear_partial_commit,
+            indexer::{index_block_with_metaprotocol_indexers, metaprotocols_new_rw_db_conn},
+            is_partial_commit_marked, mark_partial_commit,
+            runes::db::{delete_runes_in_block_range, index_runes_in_block, runes_new_rw_db_conn},
+            sns::db::{delete_names_in_block_range, index_sns_names_in_block, sns_new_rw_db_conn},
         },
         pipeline::processors::block_archiving::store_compacted_blocks,
         protocol::{
+            index_commitment::update_index_commitment_with_block,
             inscription_parsing::{
                 get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
                 parse_inscriptions_in_standardized_block,
             },
             inscription_sequencing::{
                 augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx,
-                get_bitcoin_network, get_jubilee_block_height,
-                parallelize_inscription_data_computations, SequenceCursor,
+                get_jubilee_block_height, parallelize_inscription_data_computations,
+                SequenceCursor,
             },
             satoshi_numbering::TraversalResult,
             satoshi_tracking::augment_block_with_ordinals_transfer_data,
@@ -40,15 +50,28 @@ use crate::{
     },
     db::{
         blocks::open_blocks_db_with_retry,
+        cold_blocks::{tier_cold_blocks, COLD_BLOCKS_BATCH_SIZE},
         cursor::TransactionBytesCursor,
         ordinals::{
             get_any_entry_in_ordinal_activities, get_latest_indexed_inscription_number,
-            open_ordinals_db, open_ordinals_db_rw,
+            insert_block_events_hash, insert_quarantined_block, open_ordinals_db,
+            open_ordinals_db_rw, QuarantinedBlock,
         },
+        sales::{index_sales_in_block, sales_new_rw_db_conn},
+        sat_ranges::{index_sat_ranges_in_compacted_blocks, sat_ranges_new_rw_db_conn},
+    },
+    service::{
+        alerts::{send_alert, Alert},
+        query_cache::invalidate_cached_responses_of_block,
+        write_brc20_block_operations,
     },
-    service::write_brc20_block_operations,
     try_error, try_info,
-    utils::monitoring::PrometheusMonitoring,
+    utils::{
+        content_scanning::enqueue_block_content_scans,
+        monitoring::{PrometheusMonitoring, PIPELINE_METRICS},
+        previews::enqueue_block_previews,
+        profiler::BlockProfiler,
+    },
 };
 
 use crate::{
@@ -78,15 +101,21 @@ pub fn start_inscription_indexing_processor(
             let mut garbage_collect_nth_block = 0;
 
             let mut inscriptions_db_conn_rw =
-                open_ordinals_db_rw(&config.expected_cache_path(), &ctx).unwrap();
+                open_ordinals_db_rw(&config.expected_sqlite_path(), &ctx).unwrap();
             let mut empty_cycles = 0;
 
             let inscriptions_db_conn =
-                open_ordinals_db(&config.expected_cache_path(), &ctx).unwrap();
-            let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_conn);
+                open_ordinals_db(&config.expected_sqlite_path(), &ctx).unwrap();
+            let mut sequence_cursor =
+                SequenceCursor::new(&inscriptions_db_conn, get_jubilee_block_height(&config));
 
             let mut brc20_cache = brc20_new_cache(&config);
             let mut brc20_db_conn_rw = brc20_new_rw_db_conn(&config, &ctx);
+            let mut sns_db_conn_rw = sns_new_rw_db_conn(&config, &ctx);
+            let mut runes_db_conn_rw = runes_new_rw_db_conn(&config, &ctx);
+            let metaprotocols_db_conn_rw = metaprotocols_new_rw_db_conn(&config, &ctx);
+            let sat_ranges_db_conn_rw = sat_ranges_new_rw_db_conn(&config, &ctx);
+            let sales_db_conn_rw = sales_new_rw_db_conn(&config, &ctx);
 
             loop {
                 let (compacted_blocks, mut blocks) = match commands_rx.try_recv() {
@@ -115,6 +144,10 @@ pub fn start_inscription_indexing_processor(
                     },
                 };
 
+                let compacted_blocks_count = compacted_blocks.len() as u64;
+                let sat_ranges_compacted_blocks = sat_ranges_db_conn_rw
+                    .as_ref()
+                    .map(|_| compacted_blocks.clone());
                 {
                     let blocks_db_rw = open_blocks_db_with_retry(true, &config, &ctx);
                     store_compacted_blocks(
@@ -123,10 +156,24 @@ pub fn start_inscription_indexing_processor(
                         &blocks_db_rw,
                         &Context::empty(),
                     );
+                    tier_cold_blocks(COLD_BLOCKS_BATCH_SIZE, &blocks_db_rw, &config, &ctx);
+                }
+
+                if let (Some(db_conn), Some(sat_ranges_compacted_blocks)) =
+                    (&sat_ranges_db_conn_rw, sat_ranges_compacted_blocks)
+                {
+                    index_sat_ranges_in_compacted_blocks(
+                        sat_ranges_compacted_blocks,
+                        &blocks,
+                        config.storage.index_scope,
+                        db_conn,
+                        &ctx,
+                    );
                 }
 
                 // Early return
                 if blocks.is_empty() {
+                    PIPELINE_METRICS.metrics_blocks_written(compacted_blocks_count);
                     continue;
                 }
 
@@ -138,11 +185,16 @@ pub fn start_inscription_indexing_processor(
                     &mut inscriptions_db_conn_rw,
                     &mut brc20_cache,
                     &mut brc20_db_conn_rw,
+                    &mut sns_db_conn_rw,
+                    &mut runes_db_conn_rw,
+                    &metaprotocols_db_conn_rw,
+                    &sales_db_conn_rw,
                     &post_processor,
                     &prometheus,
                     &config,
                     &ctx,
                 );
+                PIPELINE_METRICS.metrics_blocks_written(compacted_blocks_count);
 
                 garbage_collect_nth_block += blocks.len();
                 if garbage_collect_nth_block > garbage_collect_every_n_blocks {
@@ -154,7 +206,7 @@ pub fn start_inscription_indexing_processor(
 
                     // Recreate sqlite db connection on a regular basis
                     inscriptions_db_conn_rw =
-                        open_ordinals_db_rw(&config.expected_cache_path(), &ctx).unwrap();
+                        open_ordinals_db_rw(&config.expected_sqlite_path(), &ctx).unwrap();
                     inscriptions_db_conn_rw.flush_prepared_statement_cache();
                     garbage_collect_nth_block = 0;
                 }
@@ -176,6 +228,10 @@ pub fn process_blocks(
     inscriptions_db_conn_rw: &mut Connection,
     brc20_cache: &mut Option<Brc20MemoryCache>,
     brc20_db_conn_rw: &mut Option<Connection>,
+    sns_db_conn_rw: &mut Option<Connection>,
+    runes_db_conn_rw: &mut Option<Connection>,
+    metaprotocols_db_conn_rw: &Option<Connection>,
+    sales_db_conn_rw: &Option<Connection>,
     post_processor: &Option<Sender<BitcoinBlockData>>,
     prometheus: &PrometheusMonitoring,
     config: &Config,
@@ -185,93 +241,343 @@ pub fn process_blocks(
     let mut updated_blocks = vec![];
 
     for _cursor in 0..next_blocks.len() {
-        let inscriptions_db_tx = inscriptions_db_conn_rw.transaction().unwrap();
-        let brc20_db_tx = brc20_db_conn_rw.as_mut().map(|c| c.transaction().unwrap());
+        let original_block = next_blocks.remove(0);
+        let mut profiler = BlockProfiler::new(original_block.block_identifier.index, config);
+
+        // A block failing to be processed or committed can't crash-loop the service: the block is quarantined and
+        // retried, then skipped once `block_processing_max_retries` is exhausted.
+        let mut attempts = 0;
+        let block = loop {
+            attempts += 1;
+            let mut block = original_block.clone();
+            let Err(error) = process_and_persist_block(
+                &mut block,
+                &next_blocks,
+                sequence_cursor,
+                &mut cache_l1,
+                cache_l2,
+                inscriptions_db_conn_rw,
+                brc20_cache,
+                brc20_db_conn_rw,
+                sns_db_conn_rw,
+                runes_db_conn_rw,
+                metaprotocols_db_conn_rw,
+                sales_db_conn_rw,
+                &mut profiler,
+                prometheus,
+                config,
+                ctx,
+            ) else {
+                break Some(block);
+            };
+            let skipped = attempts > config.resources.block_processing_max_retries;
+            try_error!(
+                ctx,
+                "Processing of block #{} failed (attempt {attempts}): {error}",
+                original_block.block_identifier.index
+            );
+            // State carried across blocks may have been left half updated by the failed attempt.
+            sequence_cursor.reset();
+            cache_l1.clear();
+            *brc20_cache = brc20_new_cache(config);
+            insert_quarantined_block(
+                &QuarantinedBlock {
+                    block_height: original_block.block_identifier.index,
+                    block_hash: original_block.block_identifier.hash.clone(),
+                    error: error.clone(),
+                    attempts,
+                    skipped,
+                    last_failed_at: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                },
+                inscriptions_db_conn_rw,
+                ctx,
+            );
+            if skipped {
+                try_error!(
+                    ctx,
+                    "Skipping block #{} after {attempts} failed attempts, its inscriptions and transfers are missing from the index",
+                    original_block.block_identifier.index
+                );
+                prometheus.metrics_block_quarantined();
+                if let Some(ref alerts_config) = config.alerts {
+                    let alert = Alert::BlockQuarantined {
+                        block_height: original_block.block_identifier.index,
+                        attempts,
+                        error,
+                    };
+                    send_alert(&alert, alerts_config, &config.resources, ctx);
+                }
+                break None;
+            }
+        };
+        let Some(block) = block else {
+            continue;
+        };
 
-        let mut block = next_blocks.remove(0);
+        if let Some(post_processor_tx) = post_processor {
+            PIPELINE_METRICS.queue_block_for_delivery(block.clone(), post_processor_tx);
+        }
+        profiler.mark("deliver");
+        profiler.report(config, ctx);
+        updated_blocks.push(block);
+    }
+    updated_blocks
+}
 
-        // We check before hand if some data were pre-existing, before processing
-        // Always discard if we have some existing content at this block height (inscription or transfers)
-        let any_existing_activity = get_any_entry_in_ordinal_activities(
-            &block.block_identifier.index,
-            &inscriptions_db_tx,
-            ctx,
-        );
+/// Message of a panic payload, as passed to `panic!`.
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
 
-        // Invalidate and recompute cursor when crossing the jubilee height
-        let jubilee_height =
-            get_jubilee_block_height(&get_bitcoin_network(&block.metadata.network));
-        if block.block_identifier.index == jubilee_height {
-            sequence_cursor.reset();
+/// Processes a block and commits its changes, unless activities were already recorded at its height. Nothing is
+/// committed when processing panics, and the BRC-20 changes are undone when the inscriptions ones can't be committed,
+/// so that the block can be processed again from scratch.
+fn process_and_persist_block(
+    block: &mut BitcoinBlockData,
+    next_blocks: &Vec<BitcoinBlockData>,
+    sequence_cursor: &mut SequenceCursor,
+    cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
+    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    inscriptions_db_conn_rw: &mut Connection,
+    brc20_cache: &mut Option<Brc20MemoryCache>,
+    brc20_db_conn_rw: &mut Option<Connection>,
+    sns_db_conn_rw: &mut Option<Connection>,
+    runes_db_conn_rw: &mut Option<Connection>,
+    metaprotocols_db_conn_rw: &Option<Connection>,
+    sales_db_conn_rw: &Option<Connection>,
+    profiler: &mut BlockProfiler,
+    prometheus: &PrometheusMonitoring,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let block_height = block.block_identifier.index;
+    let inscriptions_db_tx = inscriptions_db_conn_rw
+        .transaction()
+        .map_err(|e| format!("unable to open ordinals transaction: {e}"))?;
+    let brc20_db_tx = match brc20_db_conn_rw.as_mut() {
+        Some(conn) => Some(
+            conn.transaction()
+                .map_err(|e| format!("unable to open brc20 transaction: {e}"))?,
+        ),
+        None => None,
+    };
+    let sns_db_tx = match sns_db_conn_rw.as_mut() {
+        Some(conn) => Some(
+            conn.transaction()
+                .map_err(|e| format!("unable to open sns transaction: {e}"))?,
+        ),
+        None => None,
+    };
+    let runes_db_tx = match runes_db_conn_rw.as_mut() {
+        Some(conn) => Some(
+            conn.transaction()
+                .map_err(|e| format!("unable to open runes transaction: {e}"))?,
+        ),
+        None => None,
+    };
+
+    // We check before hand if some data were pre-existing, before processing
+    // Always discard if we have some existing content at this block height (inscription or transfers)
+    let any_existing_activity = get_any_entry_in_ordinal_activities(
+        &block.block_identifier.index,
+        &inscriptions_db_tx,
+        ctx,
+    );
+
+    // Invalidate and recompute cursor when crossing the jubilee height
+    let jubilee_height = get_jubilee_block_height(config);
+    if block.block_identifier.index == jubilee_height {
+        sequence_cursor.reset();
+    }
+
+    // Meta protocols changes committed by an attempt whose inscriptions changes were not, and that could not be undone.
+    if !any_existing_activity {
+        if let Some(ref brc20_db_tx) = brc20_db_tx {
+            undo_partial_commit(
+                block_height as u32,
+                brc20_db_tx,
+                delete_activity_in_block_range,
+                ctx,
+            );
+        }
+        if let Some(ref sns_db_tx) = sns_db_tx {
+            undo_partial_commit(
+                block_height as u32,
+                sns_db_tx,
+                delete_names_in_block_range,
+                ctx,
+            );
+        }
+        if let Some(ref runes_db_tx) = runes_db_tx {
+            undo_partial_commit(
+                block_height as u32,
+                runes_db_tx,
+                delete_runes_in_block_range,
+                ctx,
+            );
         }
+    }
 
+    catch_unwind(AssertUnwindSafe(|| {
         let _ = process_block(
-            &mut block,
-            &next_blocks,
+            block,
+            next_blocks,
             sequence_cursor,
-            &mut cache_l1,
+            cache_l1,
             cache_l2,
             &inscriptions_db_tx,
             brc20_db_tx.as_ref(),
             brc20_cache.as_mut(),
+            profiler,
             prometheus,
             config,
             ctx,
         );
+    }))
+    .map_err(|panic| format!("panicked: {}", panic_message(&panic)))?;
 
-        let inscriptions_revealed = get_inscriptions_revealed_in_block(&block)
-            .iter()
-            .map(|d| d.get_inscription_number().to_string())
-            .collect::<Vec<String>>();
+    let inscriptions_revealed = get_inscriptions_revealed_in_block(&block)
+        .iter()
+        .map(|d| d.get_inscription_number().to_string())
+        .collect::<Vec<String>>();
 
-        let inscriptions_transferred = get_inscriptions_transferred_in_block(&block).len();
+    let inscriptions_transferred = get_inscriptions_transferred_in_block(&block).len();
 
-        try_info!(
+    try_info!(
+        ctx,
+        "Block #{} processed, revealed {} inscriptions [{}] and {inscriptions_transferred} transfers",
+        block.block_identifier.index,
+        inscriptions_revealed.len(),
+        inscriptions_revealed.join(", ")
+    );
+
+    if any_existing_activity {
+        try_error!(
             ctx,
-            "Block #{} processed, revealed {} inscriptions [{}] and {inscriptions_transferred} transfers",
+            "Dropping updates for block #{}, activities present in database",
             block.block_identifier.index,
-            inscriptions_revealed.len(),
-            inscriptions_revealed.join(", ")
         );
-
-        if any_existing_activity {
-            try_error!(
-                ctx,
-                "Dropping updates for block #{}, activities present in database",
-                block.block_identifier.index,
-            );
-            let _ = inscriptions_db_tx.rollback();
-            let _ = brc20_db_tx.map(|t| t.rollback());
-        } else {
-            match inscriptions_db_tx.commit() {
-                Ok(_) => {
-                    if let Some(brc20_db_tx) = brc20_db_tx {
-                        match brc20_db_tx.commit() {
-                            Ok(_) => {}
-                            Err(_) => {
-                                // TODO: Synchronize rollbacks and commits between BRC-20 and inscription DBs.
-                                todo!()
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    try_error!(
-                        ctx,
-                        "Unable to update changes in block #{}: {}",
-                        block.block_identifier.index,
-                        e.to_string()
-                    );
-                }
+        let _ = inscriptions_db_tx.rollback();
+        let _ = brc20_db_tx.map(|t| t.rollback());
+        let _ = sns_db_tx.map(|t| t.rollback());
+        let _ = runes_db_tx.map(|t| t.rollback());
+        profiler.mark("persist");
+        return Ok(());
+    }
+    if let Some(ref sns_db_tx) = sns_db_tx {
+        index_sns_names_in_block(&block, sns_db_tx, ctx)?;
+    }
+    if let Some(ref runes_db_tx) = runes_db_tx {
+        index_runes_in_block(&block, config, runes_db_tx, ctx)?;
+    }
+    // SQLite can't commit the databases atomically: meta protocols changes are committed first, along with a partial
+    // commit marker, and undone when a later commit fails, the inscriptions activities being what marks the block as
+    // indexed. Markers left behind by a failed undo or a crash get the changes undone on the next attempt.
+    for db_tx in [
+        brc20_db_tx.as_ref(),
+        sns_db_tx.as_ref(),
+        runes_db_tx.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        mark_partial_commit(block_height as u32, db_tx)?;
+    }
+    let undo_meta_protocols_changes =
+        |brc20_db_conn_rw: &Option<Connection>,
+         sns_db_conn_rw: &Option<Connection>,
+         runes_db_conn_rw: &Option<Connection>| {
+            if let Some(brc20_db_conn_rw) = brc20_db_conn_rw.as_ref() {
+                delete_activity_in_block_range(
+                    block_height as u32,
+                    block_height as u32,
+                    brc20_db_conn_rw,
+                    ctx,
+                );
+            }
+            if let Some(sns_db_conn_rw) = sns_db_conn_rw.as_ref() {
+                delete_names_in_block_range(
+                    block_height as u32,
+                    block_height as u32,
+                    sns_db_conn_rw,
+                    ctx,
+                );
+            }
+            if let Some(runes_db_conn_rw) = runes_db_conn_rw.as_ref() {
+                delete_runes_in_block_range(
+                    block_height as u32,
+                    block_height as u32,
+                    runes_db_conn_rw,
+                    ctx,
+                );
             }
+        };
+    if let Some(brc20_db_tx) = brc20_db_tx {
+        brc20_db_tx
+            .commit()
+            .map_err(|e| format!("unable to commit brc20 changes: {e}"))?;
+    }
+    if let Some(sns_db_tx) = sns_db_tx {
+        if let Err(e) = sns_db_tx.commit() {
+            undo_meta_protocols_changes(brc20_db_conn_rw, sns_db_conn_rw, &None);
+            return Err(format!("unable to commit sns changes: {e}"));
         }
-
-        if let Some(post_processor_tx) = post_processor {
-            let _ = post_processor_tx.send(block.clone());
+    }
+    if let Some(runes_db_tx) = runes_db_tx {
+        if let Err(e) = runes_db_tx.commit() {
+            undo_meta_protocols_changes(brc20_db_conn_rw, sns_db_conn_rw, &None);
+            return Err(format!("unable to commit runes changes: {e}"));
         }
-        updated_blocks.push(block);
     }
-    updated_blocks
+    if let Err(e) = inscriptions_db_tx.commit() {
+        undo_meta_protocols_changes(brc20_db_conn_rw, sns_db_conn_rw, runes_db_conn_rw);
+        return Err(format!("unable to commit ordinals changes: {e}"));
+    }
+    for db_conn in [
+        brc20_db_conn_rw.as_ref(),
+        sns_db_conn_rw.as_ref(),
+        runes_db_conn_rw.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        clear_partial_commit(block_height as u32, db_conn, ctx);
+    }
+    if let Some(metaprotocols_db_conn_rw) = metaprotocols_db_conn_rw {
+        index_block_with_metaprotocol_indexers(&block, metaprotocols_db_conn_rw, ctx);
+    }
+    if let Some(sales_db_conn_rw) = sales_db_conn_rw {
+        index_sales_in_block(&block, config, sales_db_conn_rw, ctx);
+    }
+    invalidate_cached_responses_of_block(&block);
+    // Only enqueued once committed, for retried and dropped attempts not to enqueue them again.
+    enqueue_block_previews(&block);
+    enqueue_block_content_scans(&block);
+    profiler.mark("persist");
+    Ok(())
+}
+
+/// Undoes the meta protocol changes of a block committed by an attempt whose inscriptions changes were not, when a
+/// partial commit marker was left behind.
+fn undo_partial_commit(
+    block_height: u32,
+    db_conn: &Connection,
+    delete_in_block_range: fn(u32, u32, &Connection, &Context),
+    ctx: &Context,
+) {
+    if is_partial_commit_marked(block_height, db_conn, ctx) {
+        delete_in_block_range(block_height, block_height, db_conn, ctx);
+        clear_partial_commit(block_height, db_conn, ctx);
+    }
 }
 
 pub fn process_block(
@@ -283,6 +589,7 @@ pub fn process_block(
     inscriptions_db_tx: &Transaction,
     brc20_db_tx: Option<&Transaction>,
     brc20_cache: Option<&mut Brc20MemoryCache>,
+    profiler: &mut BlockProfiler,
     prometheus: &PrometheusMonitoring,
     config: &Config,
     ctx: &Context,
@@ -290,6 +597,7 @@ pub fn process_block(
     // Parsed BRC20 ops will be deposited here for this block.
     let mut brc20_operation_map = HashMap::new();
     parse_inscriptions_in_standardized_block(block, &mut brc20_operation_map, config, &ctx);
+    profiler.mark("parse");
 
     let any_processable_transactions = parallelize_inscription_data_computations(
         &block,
@@ -309,16 +617,18 @@ pub fn process_block(
 
     // Inscriptions
     if any_processable_transactions {
-        let _ = augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
+        augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
             block,
             sequence_cursor,
             cache_l1,
             &inscriptions_db_tx,
             &inner_ctx,
-        );
+        )?;
     }
+    profiler.mark("number");
     // Transfers
     let _ = augment_block_with_ordinals_transfer_data(block, inscriptions_db_tx, true, &inner_ctx);
+    profiler.mark("track");
     // BRC-20
     match (brc20_db_tx, brc20_cache) {
         (Some(brc20_db_tx), Some(brc20_cache)) => write_brc20_block_operations(
@@ -330,6 +640,9 @@ pub fn process_block(
         ),
         _ => {}
     }
+    profiler.mark("brc20");
+    insert_block_events_hash(block, inscriptions_db_tx, &inner_ctx);
+    update_index_commitment_with_block(block, inscriptions_db_tx, config, ctx);
 
     // Monitoring
     prometheus.metrics_block_indexed(block.block_identifier.index);
@@ -366,7 +679,7 @@ mod test {
         utils::monitoring::PrometheusMonitoring,
     };
 
-    use super::start_inscription_indexing_processor;
+    use super::{panic_message, start_inscription_indexing_processor};
 
     #[test]
     fn process_inscription_reveal_and_transfer_via_processor() {
@@ -546,4 +859,15 @@ mod test {
         // Close channel.
         let _ = controller.commands_tx.send(PostProcessorCommand::Terminate);
     }
+
+    #[test]
+    fn extracts_panic_messages() {
+        let panic = std::panic::catch_unwind(|| panic!("static message")).unwrap_err();
+        assert_eq!(panic_message(&panic), "static message");
+        let height = 800_000;
+        let panic = std::panic::catch_unwind(|| panic!("block #{height}")).unwrap_err();
+        assert_eq!(panic_message(&panic), "block #800000");
+        let panic = std::panic::catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(&panic), "unknown panic");
+    }
 }
@@ -16,10 +16,11 @@ use crate::{
         },
     },
     db::ordinals::{
-        insert_entries_from_block_in_inscriptions, open_ordinals_db_rw,
-        remove_entries_from_locations_at_block_height,
+        insert_entries_from_block_in_inscriptions, insert_watched_outputs_from_block,
+        open_ordinals_db_rw, remove_entries_from_locations_at_block_height,
     },
     try_info, try_warn,
+    utils::monitoring::PIPELINE_METRICS,
 };
 
 pub fn start_transfers_recomputing_processor(
@@ -35,14 +36,14 @@ pub fn start_transfers_recomputing_processor(
     let handle: JoinHandle<()> = hiro_system_kit::thread_named("Inscription indexing runloop")
         .spawn(move || {
             let mut inscriptions_db_conn_rw =
-                open_ordinals_db_rw(&config.expected_cache_path(), &ctx).unwrap();
+                open_ordinals_db_rw(&config.expected_sqlite_path(), &ctx).unwrap();
             let mut empty_cycles = 0;
 
             loop {
-                let mut blocks = match commands_rx.try_recv() {
-                    Ok(PostProcessorCommand::ProcessBlocks(_, blocks)) => {
+                let (compacted_blocks_count, mut blocks) = match commands_rx.try_recv() {
+                    Ok(PostProcessorCommand::ProcessBlocks(compacted_blocks, blocks)) => {
                         empty_cycles = 0;
-                        blocks
+                        (compacted_blocks.len() as u64, blocks)
                     }
                     Ok(PostProcessorCommand::Terminate) => {
                         let _ = events_tx.send(PostProcessorEvent::Terminated);
@@ -92,11 +93,14 @@ pub fn start_transfers_recomputing_processor(
                         &ctx,
                     );
 
+                    insert_watched_outputs_from_block(block, &inscriptions_db_tx, &ctx);
+
                     if let Some(ref post_processor) = post_processor {
-                        let _ = post_processor.send(block.clone());
+                        PIPELINE_METRICS.queue_block_for_delivery(block.clone(), post_processor);
                     }
                 }
                 let _ = inscriptions_db_tx.commit();
+                PIPELINE_METRICS.metrics_blocks_written(compacted_blocks_count);
             }
         })
         .expect("unable to spawn thread");
@@ -0,0 +1,259 @@
+use std::str::FromStr;
+
+use chainhook_sdk::{
+    bitcoincore_rpc_json::bitcoin::{
+        address::NetworkUnchecked, Address, Network, Script, ScriptBuf,
+    },
+    types::OrdinalInscriptionTransferDestination,
+};
+
+/// Standard output script templates, as classified by Bitcoin Core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    P2tr,
+    P2wpkh,
+    P2wsh,
+    P2sh,
+    P2pkh,
+    /// Pay to a bare public key, without address.
+    P2pk,
+    /// Witness program of a future segwit version.
+    WitnessUnknown,
+    /// Unspendable output, without address.
+    OpReturn,
+    NonStandard,
+}
+
+impl ScriptType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptType::P2tr => "p2tr",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2wsh => "p2wsh",
+            ScriptType::P2sh => "p2sh",
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2pk => "p2pk",
+            ScriptType::WitnessUnknown => "witness_unknown",
+            ScriptType::OpReturn => "op_return",
+            ScriptType::NonStandard => "non_standard",
+        }
+    }
+}
+
+/// Output script rendered for storage and delivery: its address when it has one, and always its type and hex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputScript {
+    pub script_type: ScriptType,
+    pub address: Option<String>,
+    /// Hex encoded, lowercase and without `0x` prefix.
+    pub script_pubkey: String,
+}
+
+pub fn classify_script(script: &Script) -> ScriptType {
+    if script.is_v1_p2tr() {
+        ScriptType::P2tr
+    } else if script.is_v0_p2wpkh() {
+        ScriptType::P2wpkh
+    } else if script.is_v0_p2wsh() {
+        ScriptType::P2wsh
+    } else if script.is_p2sh() {
+        ScriptType::P2sh
+    } else if script.is_p2pkh() {
+        ScriptType::P2pkh
+    } else if script.is_p2pk() {
+        ScriptType::P2pk
+    } else if script.is_witness_program() {
+        ScriptType::WitnessUnknown
+    } else if script.is_op_return() {
+        ScriptType::OpReturn
+    } else {
+        ScriptType::NonStandard
+    }
+}
+
+/// Address of an output script, the way every address stored or delivered by ordhook is rendered: bech32 and bech32m
+/// addresses in lowercase, base58 ones as encoded.
+pub fn script_address(script: &Script, network: &Network) -> Option<String> {
+    Address::from_script(script, *network)
+        .ok()
+        .map(|address| address.to_string())
+}
+
+/// Parses an output script encoded in hex, with or without `0x` prefix.
+pub fn parse_script_hex(script_pubkey_hex: &str) -> Option<ScriptBuf> {
+    ScriptBuf::from_hex(script_pubkey_hex.trim_start_matches("0x")).ok()
+}
+
+/// Address of an output script encoded in hex, with or without `0x` prefix.
+pub fn script_hex_address(script_pubkey_hex: &str, network: &Network) -> Option<String> {
+    script_address(&parse_script_hex(script_pubkey_hex)?, network)
+}
+
+pub fn describe_script(script: &Script, network: &Network) -> OutputScript {
+    OutputScript {
+        script_type: classify_script(script),
+        address: script_address(script, network),
+        script_pubkey: script.to_hex_string(),
+    }
+}
+
+impl OutputScript {
+    /// Rendering of the script in the destination of reveals and transfers: its address, or `<type>:<hex>` for the
+    /// scripts without address, like `p2pk:2102..ac`.
+    pub fn to_destination_value(&self) -> String {
+        match self.address {
+            Some(ref address) => address.clone(),
+            None => format!("{}:{}", self.script_type.as_str(), self.script_pubkey),
+        }
+    }
+}
+
+/// Destination of sats sent to an output script encoded in hex. Only OP_RETURN outputs burn sats: scripts without
+/// address, like P2PK, are still spendable. Scripts that can't be decoded are rendered as non-standard.
+pub fn get_output_destination(
+    script_pubkey_hex: &str,
+    network: &Network,
+) -> OrdinalInscriptionTransferDestination {
+    let output_script = match parse_script_hex(script_pubkey_hex) {
+        Some(script) => describe_script(&script, network),
+        None => OutputScript {
+            script_type: ScriptType::NonStandard,
+            address: None,
+            script_pubkey: script_pubkey_hex.trim_start_matches("0x").to_lowercase(),
+        },
+    };
+    match output_script.script_type {
+        ScriptType::OpReturn => {
+            OrdinalInscriptionTransferDestination::Burnt(output_script.to_destination_value())
+        }
+        _ => {
+            OrdinalInscriptionTransferDestination::Transferred(output_script.to_destination_value())
+        }
+    }
+}
+
+/// Validates an address of `network` given by a user, and renders it like the addresses of the index so that lookups
+/// match them.
+pub fn normalize_address(address: &str, network: &Network) -> Result<String, String> {
+    Address::<NetworkUnchecked>::from_str(address.trim())
+        .map_err(|e| format!("invalid address {address}: {e}"))?
+        .require_network(*network)
+        .map(|address| address.to_string())
+        .map_err(|e| format!("invalid address {address}: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::{
+        bitcoincore_rpc_json::bitcoin::Network, types::OrdinalInscriptionTransferDestination,
+    };
+
+    use super::{
+        describe_script, get_output_destination, normalize_address, parse_script_hex,
+        script_hex_address, ScriptType,
+    };
+
+    #[test]
+    fn describes_output_scripts() {
+        let describe =
+            |hex: &str| describe_script(&parse_script_hex(hex).unwrap(), &Network::Bitcoin);
+        let p2tr =
+            describe("0x512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        assert_eq!(p2tr.script_type, ScriptType::P2tr);
+        assert_eq!(
+            p2tr.address.as_deref(),
+            Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0")
+        );
+        assert_eq!(
+            p2tr.script_pubkey,
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        let p2wpkh = describe("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(p2wpkh.script_type, ScriptType::P2wpkh);
+        assert_eq!(
+            p2wpkh.address.as_deref(),
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        let p2wsh =
+            describe("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+        assert_eq!(p2wsh.script_type, ScriptType::P2wsh);
+        assert_eq!(
+            p2wsh.address.as_deref(),
+            Some("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3")
+        );
+        let p2pkh = describe("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+        assert_eq!(p2pkh.script_type, ScriptType::P2pkh);
+        assert_eq!(
+            p2pkh.address.as_deref(),
+            Some("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH")
+        );
+        let p2sh = describe("a914751e76e8199196d454941c45d1b3a323f1433bd687");
+        assert_eq!(p2sh.script_type, ScriptType::P2sh);
+        assert!(p2sh.address.unwrap().starts_with('3'));
+
+        let p2pk =
+            describe("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac");
+        assert_eq!(p2pk.script_type, ScriptType::P2pk);
+        assert_eq!(p2pk.address, None);
+        let op_return = describe("6a0568656c6c6f");
+        assert_eq!(op_return.script_type, ScriptType::OpReturn);
+        assert_eq!(op_return.address, None);
+        assert_eq!(op_return.script_pubkey, "6a0568656c6c6f");
+        assert_eq!(describe("51").script_type, ScriptType::NonStandard);
+
+        assert_eq!(script_hex_address("not hex", &Network::Bitcoin), None);
+    }
+
+    #[test]
+    fn only_burns_sats_sent_to_op_return() {
+        assert_eq!(
+            get_output_destination(
+                "0x0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                &Network::Bitcoin
+            ),
+            OrdinalInscriptionTransferDestination::Transferred(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()
+            )
+        );
+        assert_eq!(
+            get_output_destination(
+                "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+                &Network::Bitcoin
+            ),
+            OrdinalInscriptionTransferDestination::Transferred(
+                "p2pk:210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            get_output_destination("51", &Network::Bitcoin),
+            OrdinalInscriptionTransferDestination::Transferred("non_standard:51".to_string())
+        );
+        assert_eq!(
+            get_output_destination("6a0568656c6c6f", &Network::Bitcoin),
+            OrdinalInscriptionTransferDestination::Burnt("op_return:6a0568656c6c6f".to_string())
+        );
+    }
+
+    #[test]
+    fn normalizes_addresses() {
+        assert_eq!(
+            normalize_address(
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                &Network::Bitcoin
+            ),
+            Ok("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string())
+        );
+        assert_eq!(
+            normalize_address(" 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", &Network::Bitcoin),
+            Ok("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".to_string())
+        );
+        assert!(normalize_address(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            &Network::Testnet
+        )
+        .is_err());
+        assert!(normalize_address("not an address", &Network::Bitcoin).is_err());
+    }
+}
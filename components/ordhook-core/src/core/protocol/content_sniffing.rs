@@ -0,0 +1,148 @@
+use chainhook_sdk::types::OrdinalInscriptionRevealData;
+
+/// Signatures of binary formats, matched against the first bytes of the content.
+const CONTENT_SIGNATURES: [(&[u8], &str); 13] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"OggS", "audio/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x00asm", "application/wasm"),
+    (b"glTF", "model/gltf-binary"),
+];
+
+/// Content types interchangeable with the detected type, in addition to the detected type itself.
+const CONTENT_TYPE_ALIASES: [(&str, &[&str]); 7] = [
+    ("image/jpeg", &["image/jpg"]),
+    ("audio/mpeg", &["audio/mp3"]),
+    ("video/mp4", &["audio/mp4", "video/quicktime"]),
+    ("application/gzip", &["application/x-gzip"]),
+    ("application/zip", &["application/x-zip-compressed"]),
+    ("text/html", &["application/xhtml+xml"]),
+    ("image/svg+xml", &["text/xml", "application/xml"]),
+];
+
+/// Detects the MIME type of a content from its bytes. Returns None for unrecognized binary contents.
+pub fn sniff_content_type(content: &[u8]) -> Option<&'static str> {
+    for (signature, content_type) in CONTENT_SIGNATURES.iter() {
+        if content.starts_with(signature) {
+            return Some(content_type);
+        }
+    }
+    if content.len() >= 12 && &content[0..4] == b"RIFF" {
+        match &content[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            _ => {}
+        }
+    }
+    if content.len() >= 12 && &content[4..8] == b"ftyp" {
+        return match &content[8..12] {
+            b"avif" | b"avis" => Some("image/avif"),
+            b"heic" | b"heix" => Some("image/heic"),
+            _ => Some("video/mp4"),
+        };
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let prefix = text
+        .trim_start()
+        .chars()
+        .take(256)
+        .collect::<String>()
+        .to_lowercase();
+    if prefix.starts_with("<!doctype html") || prefix.starts_with("<html") {
+        return Some("text/html");
+    }
+    if prefix.starts_with("<svg") || (prefix.starts_with("<?xml") && prefix.contains("<svg")) {
+        return Some("image/svg+xml");
+    }
+    if (prefix.starts_with('{') || prefix.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+    {
+        return Some("application/json");
+    }
+    Some("text/plain")
+}
+
+fn content_type_essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+fn is_textual_content_type(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.ends_with("+json")
+        || content_type.ends_with("+xml")
+        || [
+            "application/json",
+            "application/javascript",
+            "application/x-javascript",
+            "application/xml",
+        ]
+        .contains(&content_type)
+}
+
+/// Returns true if the declared content type does not describe the detected one. Plain text and JSON contents are
+/// compatible with any textual type, since those can't be told apart reliably.
+pub fn is_content_type_mismatch(declared: &str, detected: &str) -> bool {
+    let declared = content_type_essence(declared);
+    if declared == detected {
+        return false;
+    }
+    if detected == "text/plain" || detected == "application/json" {
+        return !is_textual_content_type(&declared);
+    }
+    !CONTENT_TYPE_ALIASES.iter().any(|(content_type, aliases)| {
+        *content_type == detected && aliases.contains(&declared.as_str())
+    })
+}
+
+/// Replaces the declared content type of a revealed inscription by the one detected from its content, when they do
+/// not match. The declared type stays in the envelope, see `get_inscription_content_types_in_block`.
+pub fn correct_inscription_content_type(reveal: &mut OrdinalInscriptionRevealData, content: &[u8]) {
+    let Some(detected) = sniff_content_type(content) else {
+        return;
+    };
+    if is_content_type_mismatch(&reveal.content_type, detected) {
+        reveal.content_type = detected.to_string();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{is_content_type_mismatch, sniff_content_type};
+
+    #[test_case(b"\x89PNG\r\n\x1a\n\x00\x00" => Some("image/png"); "png")]
+    #[test_case(b"RIFF\x00\x00\x00\x00WEBPVP8 " => Some("image/webp"); "webp")]
+    #[test_case(b"\x00\x00\x00\x1cftypavif\x00\x00" => Some("image/avif"); "avif")]
+    #[test_case(b"  <!DOCTYPE html><html></html>" => Some("text/html"); "html")]
+    #[test_case(b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>" => Some("image/svg+xml"); "svg")]
+    #[test_case(br#"{"p":"brc-20","op":"mint"}"# => Some("application/json"); "json")]
+    #[test_case(b"hello.sats" => Some("text/plain"); "text")]
+    #[test_case(b"\xfe\xed\xfa\xce" => None; "unknown binary")]
+    fn sniffs_content_types(content: &[u8]) -> Option<&'static str> {
+        sniff_content_type(content)
+    }
+
+    #[test_case("image/png", "image/png" => false; "same type")]
+    #[test_case("image/jpg", "image/jpeg" => false; "alias")]
+    #[test_case("text/plain;charset=utf-8", "application/json" => false; "json as text")]
+    #[test_case("image/png", "text/plain" => true; "text as image")]
+    #[test_case("text/plain", "text/html" => true; "html as text")]
+    #[test_case("image/png", "image/webp" => true; "webp as png")]
+    fn detects_mismatches(declared: &str, detected: &str) -> bool {
+        is_content_type_mismatch(declared, detected)
+    }
+}
@@ -0,0 +1,151 @@
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash},
+    types::{BitcoinBlockData, OrdinalOperation},
+};
+use serde_json::{json, Value as JsonValue};
+
+/// Serializes a JSON value with its object keys sorted, so that the output does not depend on field ordering.
+fn write_canonical_json(value: &JsonValue, output: &mut String) {
+    match value {
+        JsonValue::Array(values) => {
+            output.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_canonical_json(value, output);
+            }
+            output.push(']');
+        }
+        JsonValue::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            output.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                output.push_str(&JsonValue::String(key.clone()).to_string());
+                output.push(':');
+                write_canonical_json(&map[key], output);
+            }
+            output.push('}');
+        }
+        value => output.push_str(&value.to_string()),
+    }
+}
+
+/// Canonical representation of the ordinal events of a block, one line per event. Inscription contents are left out,
+/// since nodes can be configured to strip them.
+pub fn get_canonical_block_events(block: &BitcoinBlockData) -> Vec<String> {
+    let mut events = vec![];
+    for tx in block.transactions.iter() {
+        let txid = &tx.transaction_identifier.hash;
+        for operation in tx.metadata.ordinal_operations.iter() {
+            let event = match operation {
+                OrdinalOperation::InscriptionRevealed(reveal) => json!([
+                    "inscription_revealed",
+                    txid,
+                    reveal.inscription_id,
+                    reveal.inscription_number.classic,
+                    reveal.inscription_number.jubilee,
+                    reveal.ordinal_number,
+                    reveal.content_type,
+                    reveal.content_length,
+                    reveal.inscriber_address,
+                    reveal.satpoint_post_inscription,
+                    reveal.curse_type,
+                ]),
+                OrdinalOperation::InscriptionTransferred(transfer) => json!([
+                    "inscription_transferred",
+                    txid,
+                    transfer.ordinal_number,
+                    transfer.destination,
+                    transfer.satpoint_pre_transfer,
+                    transfer.satpoint_post_transfer,
+                    transfer.post_transfer_output_value,
+                ]),
+            };
+            let mut line = String::new();
+            write_canonical_json(&event, &mut line);
+            events.push(line);
+        }
+        if let Some(ref brc20_operation) = tx.metadata.brc20_operation {
+            let mut line = String::new();
+            write_canonical_json(
+                &json!(["brc20_operation", txid, brc20_operation]),
+                &mut line,
+            );
+            events.push(line);
+        }
+    }
+    events
+}
+
+/// Deterministic hash of the ordinal events of a block, along with the number of events. Independent nodes indexing
+/// the same block are expected to compute the same hash.
+pub fn compute_block_events_hash(block: &BitcoinBlockData) -> (String, u64) {
+    let events = get_canonical_block_events(block);
+    let mut payload = String::new();
+    for event in events.iter() {
+        payload.push_str(event);
+        payload.push('\n');
+    }
+    (
+        sha256::Hash::hash(payload.as_bytes()).to_string(),
+        events.len() as u64,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::{OrdinalInscriptionTransferDestination, OrdinalOperation};
+    use serde_json::json;
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::{Brc20RevealBuilder, Brc20TransferBuilder},
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{compute_block_events_hash, write_canonical_json};
+
+    #[test]
+    fn canonical_json_sorts_keys() {
+        let mut output = String::new();
+        write_canonical_json(
+            &json!({"b": [1, {"d": 2, "c": null}], "a": "x"}),
+            &mut output,
+        );
+        assert_eq!(output, r#"{"a":"x","b":[1,{"c":null,"d":2}]}"#);
+    }
+
+    #[test]
+    fn events_hash_ignores_contents() {
+        let build_block = |content_bytes: &str| {
+            let mut reveal = Brc20RevealBuilder::new().build();
+            reveal.content_bytes = content_bytes.to_string();
+            TestBlockBuilder::new()
+                .add_transaction(
+                    TestTransactionBuilder::new()
+                        .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(reveal))
+                        .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                            Brc20TransferBuilder::new()
+                                .destination(OrdinalInscriptionTransferDestination::SpentInFees)
+                                .build(),
+                        ))
+                        .build(),
+                )
+                .build()
+        };
+        let (hash, count) = compute_block_events_hash(&build_block("0x7b7d"));
+        assert_eq!(count, 2);
+        assert_eq!(
+            compute_block_events_hash(&build_block("sha256:1234")),
+            (hash.clone(), 2)
+        );
+        assert_ne!(
+            compute_block_events_hash(&TestBlockBuilder::new().build()).0,
+            hash
+        );
+    }
+}
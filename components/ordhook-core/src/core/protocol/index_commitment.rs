@@ -0,0 +1,358 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash},
+    types::BitcoinBlockData,
+    utils::Context,
+};
+use rusqlite::Connection;
+
+use crate::{
+    config::Config,
+    core::protocol::inscription_parsing::{
+        get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
+    },
+    db::ordinals::{
+        delete_index_commitment_leaves, find_index_commitment, find_index_commitment_leaves,
+        find_index_commitment_leaves_block_height, find_index_commitment_leaves_changed_in_block,
+        for_each_index_commitment_leaf_hash, insert_index_commitment,
+        insert_index_commitment_leaves, insert_index_commitment_leaves_block_height,
+        IndexCommitmentLeaf, IndexCommitmentRow,
+    },
+    try_info, try_warn,
+};
+
+lazy_static! {
+    /// Tree of the latest commitment a proof was requested for, since rebuilding it requires a full scan of the index.
+    static ref LAST_COMMITMENT_TREE: Mutex<Option<(u64, Arc<IndexCommitmentTree>)>> = Mutex::new(None);
+}
+
+/// Merkle tree over the inscriptions indexed up to a given block, one leaf per inscription ordered by inscription
+/// number. Each leaf commits to the inscription number, id, and current location (`<outpoint>:<offset>`), covering both
+/// the number → id and id → owner mappings.
+pub struct IndexCommitmentTree {
+    pub leaves: Vec<IndexCommitmentLeaf>,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+/// Sibling hash needed to climb one level of the tree, with its side relative to the current node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InclusionProofStep {
+    pub position: String,
+    pub hash: String,
+}
+
+pub fn hash_commitment_leaf(leaf: &IndexCommitmentLeaf) -> [u8; 32] {
+    let mut payload = vec![0u8];
+    payload.extend(
+        format!(
+            "{}:{}:{}",
+            leaf.inscription_number, leaf.inscription_id, leaf.location
+        )
+        .as_bytes(),
+    );
+    sha256::Hash::hash(&payload).to_byte_array()
+}
+
+fn hash_commitment_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut payload = vec![1u8];
+    payload.extend(left);
+    payload.extend(right);
+    sha256::Hash::hash(&payload).to_byte_array()
+}
+
+impl IndexCommitmentTree {
+    pub fn new(leaves: Vec<IndexCommitmentLeaf>) -> IndexCommitmentTree {
+        let mut levels = vec![leaves.iter().map(hash_commitment_leaf).collect::<Vec<_>>()];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = &levels[levels.len() - 1];
+            // Odd nodes are promoted as is rather than duplicated, so that two different leaf sets can't share a root.
+            let next_level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_commitment_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next_level);
+        }
+        IndexCommitmentTree { leaves, levels }
+    }
+
+    pub fn root(&self) -> String {
+        match self.levels[self.levels.len() - 1].first() {
+            Some(root) => hex::encode(root),
+            None => hex::encode([0u8; 32]),
+        }
+    }
+
+    pub fn find_leaf_index(&self, inscription_id: &str) -> Option<usize> {
+        self.leaves
+            .iter()
+            .position(|leaf| leaf.inscription_id == inscription_id)
+    }
+
+    pub fn find_leaf_index_by_number(&self, inscription_number: i64) -> Option<usize> {
+        self.leaves
+            .binary_search_by_key(&inscription_number, |leaf| leaf.inscription_number)
+            .ok()
+    }
+
+    pub fn proof(&self, leaf_index: usize) -> Vec<InclusionProofStep> {
+        let mut steps = vec![];
+        let mut index = leaf_index;
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            let sibling_index = index ^ 1;
+            if let Some(sibling) = level.get(sibling_index) {
+                steps.push(InclusionProofStep {
+                    position: if sibling_index < index {
+                        "left"
+                    } else {
+                        "right"
+                    }
+                    .to_string(),
+                    hash: hex::encode(sibling),
+                });
+            }
+            index /= 2;
+        }
+        steps
+    }
+}
+
+/// Computes the root of an `IndexCommitmentTree` from its leaf hashes pushed in order, keeping a single pending node per
+/// level instead of the whole tree.
+#[derive(Default)]
+pub struct CommitmentRootBuilder {
+    pending: Vec<Option<[u8; 32]>>,
+    pub leaves_count: u64,
+}
+
+impl CommitmentRootBuilder {
+    pub fn push(&mut self, leaf_hash: [u8; 32]) {
+        self.leaves_count += 1;
+        let mut node = leaf_hash;
+        for pending in self.pending.iter_mut() {
+            match pending.take() {
+                Some(left) => node = hash_commitment_node(&left, &node),
+                None => {
+                    *pending = Some(node);
+                    return;
+                }
+            }
+        }
+        self.pending.push(Some(node));
+    }
+
+    pub fn root(&self) -> String {
+        // Incomplete levels are folded from the bottom, odd nodes being promoted as is like in `IndexCommitmentTree`.
+        let mut carry: Option<[u8; 32]> = None;
+        for pending in self.pending.iter() {
+            carry = match (pending, carry) {
+                (Some(left), Some(right)) => Some(hash_commitment_node(left, &right)),
+                (Some(node), None) => Some(*node),
+                (None, carry) => carry,
+            };
+        }
+        hex::encode(carry.unwrap_or([0u8; 32]))
+    }
+}
+
+/// Recomputes the root from a leaf and its proof, as a client checking a proof against the served root would.
+pub fn verify_inclusion_proof(
+    leaf: &IndexCommitmentLeaf,
+    proof: &Vec<InclusionProofStep>,
+    root: &str,
+) -> bool {
+    let mut node = hash_commitment_leaf(leaf);
+    for step in proof.iter() {
+        let Ok(sibling) = hex::decode(&step.hash) else {
+            return false;
+        };
+        let Ok(sibling) = <[u8; 32]>::try_from(sibling) else {
+            return false;
+        };
+        node = match step.position.as_str() {
+            "left" => hash_commitment_node(&sibling, &node),
+            "right" => hash_commitment_node(&node, &sibling),
+            _ => return false,
+        };
+    }
+    hex::encode(node) == root
+}
+
+/// Brings the stored leaf hashes up to date with the block: only the leaves of the inscriptions revealed or moved by the
+/// block get recomputed when the leaves are at the previous block, the whole index is read otherwise.
+fn update_index_commitment_leaves_with_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let block_height = block.block_identifier.index;
+    let leaves = match find_index_commitment_leaves_block_height(inscriptions_db_conn_rw, ctx) {
+        Some(height) if height == block_height => return,
+        Some(height) if height + 1 == block_height => {
+            let mut ordinal_numbers = BTreeSet::new();
+            for reveal in get_inscriptions_revealed_in_block(block) {
+                ordinal_numbers.insert(reveal.ordinal_number);
+            }
+            for transfer in get_inscriptions_transferred_in_block(block) {
+                ordinal_numbers.insert(transfer.ordinal_number);
+            }
+            find_index_commitment_leaves_changed_in_block(
+                block_height,
+                &ordinal_numbers,
+                inscriptions_db_conn_rw,
+                ctx,
+            )
+        }
+        _ => {
+            try_info!(
+                ctx,
+                "Rebuilding index commitment leaves at block #{block_height}"
+            );
+            delete_index_commitment_leaves(inscriptions_db_conn_rw, ctx);
+            find_index_commitment_leaves(block_height, inscriptions_db_conn_rw, ctx)
+        }
+    };
+    let leaves = leaves
+        .into_iter()
+        .map(|leaf| {
+            let leaf_hash = hash_commitment_leaf(&leaf);
+            (leaf, leaf_hash)
+        })
+        .collect::<Vec<_>>();
+    insert_index_commitment_leaves(&leaves, inscriptions_db_conn_rw, ctx);
+    insert_index_commitment_leaves_block_height(block_height, inscriptions_db_conn_rw, ctx);
+}
+
+/// Keeps the commitment leaves up to date, and stores the commitment of the index if the block falls on the configured
+/// commitment interval.
+pub fn update_index_commitment_with_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    config: &Config,
+    ctx: &Context,
+) {
+    let Some(interval) = config.storage.index_commitment_interval else {
+        return;
+    };
+    if interval == 0 {
+        return;
+    }
+    update_index_commitment_leaves_with_block(block, inscriptions_db_conn_rw, ctx);
+    let block_height = block.block_identifier.index;
+    if block_height % interval != 0 {
+        return;
+    }
+    let mut builder = CommitmentRootBuilder::default();
+    if let Err(e) = for_each_index_commitment_leaf_hash(inscriptions_db_conn_rw, |leaf_hash| {
+        builder.push(leaf_hash)
+    }) {
+        try_warn!(
+            ctx,
+            "Unable to compute index commitment at block #{block_height}: {e}"
+        );
+        return;
+    }
+    let root = builder.root();
+    insert_index_commitment(
+        &IndexCommitmentRow {
+            block_height,
+            block_hash: block.block_identifier.hash.clone(),
+            root: root.clone(),
+            leaves_count: builder.leaves_count,
+        },
+        inscriptions_db_conn_rw,
+        ctx,
+    );
+    try_info!(
+        ctx,
+        "Index commitment {root} computed at block #{block_height}"
+    );
+}
+
+/// Returns the stored commitment at the given height along with its tree, rebuilt from the index state at that height.
+pub fn get_index_commitment_tree(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<(IndexCommitmentRow, Arc<IndexCommitmentTree>)> {
+    let commitment = find_index_commitment(Some(block_height), db_conn, ctx)?;
+    if let Ok(last_tree) = LAST_COMMITMENT_TREE.lock() {
+        if let Some((height, tree)) = last_tree.as_ref() {
+            if *height == block_height {
+                return Some((commitment, tree.clone()));
+            }
+        }
+    }
+    let tree = Arc::new(IndexCommitmentTree::new(find_index_commitment_leaves(
+        block_height,
+        db_conn,
+        ctx,
+    )));
+    if tree.root() != commitment.root {
+        return None;
+    }
+    if let Ok(mut last_tree) = LAST_COMMITMENT_TREE.lock() {
+        *last_tree = Some((block_height, tree.clone()));
+    }
+    Some((commitment, tree))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::ordinals::IndexCommitmentLeaf;
+
+    use super::{
+        hash_commitment_leaf, verify_inclusion_proof, CommitmentRootBuilder, IndexCommitmentTree,
+    };
+
+    fn leaf(inscription_number: i64) -> IndexCommitmentLeaf {
+        IndexCommitmentLeaf {
+            inscription_number,
+            inscription_id: format!("{:064x}i0", inscription_number),
+            location: format!("{:064x}:0:0", inscription_number + 1000),
+        }
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for count in [1, 2, 5, 8] {
+            let tree = IndexCommitmentTree::new((0..count).map(leaf).collect());
+            let root = tree.root();
+            for i in 0..count as usize {
+                let proof = tree.proof(i);
+                assert!(verify_inclusion_proof(&tree.leaves[i], &proof, &root));
+                let mut moved = tree.leaves[i].clone();
+                moved.location = "00:1:0".to_string();
+                assert!(!verify_inclusion_proof(&moved, &proof, &root));
+            }
+            assert_eq!(
+                tree.find_leaf_index_by_number(count - 1),
+                Some(count as usize - 1)
+            );
+        }
+        assert_ne!(
+            IndexCommitmentTree::new((0..3).map(leaf).collect()).root(),
+            IndexCommitmentTree::new((0..4).map(leaf).collect()).root()
+        );
+    }
+
+    #[test]
+    fn root_builder_matches_tree_root() {
+        for count in 0..20 {
+            let leaves = (0..count).map(leaf).collect::<Vec<_>>();
+            let mut builder = CommitmentRootBuilder::default();
+            for leaf in leaves.iter() {
+                builder.push(hash_commitment_leaf(leaf));
+            }
+            assert_eq!(builder.leaves_count, count as u64);
+            assert_eq!(builder.root(), IndexCommitmentTree::new(leaves).root());
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    fs::File,
+    io::Write,
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash},
+    utils::Context,
+};
+use memmap2::Mmap;
+use rusqlite::Connection;
+
+use crate::{
+    config::Config,
+    core::protocol::inscription_parsing::parse_inscriptions_from_witness,
+    db::{
+        blocks::{decrypt_stored_file_bytes, encrypt_stored_file_bytes, is_encrypted_bytes},
+        ordinals::find_inscription_location,
+    },
+    service::blocklist::check_blocklist,
+    try_warn,
+    utils::{
+        bitcoind::bitcoind_get_transaction_in_block, content_scanning::check_content_scan,
+        get_unique_tmp_path,
+    },
+};
+
+/// Temporary files of the content cache older than this were left behind by a writer that died mid-write.
+const STALE_CONTENT_CACHE_TMP_SECS: u64 = 3600;
+
+/// Maximum number of inscriptions exported in a single contents archive.
+pub const INSCRIPTION_CONTENTS_ARCHIVE_MAX_IDS: usize = 10_000;
+
+/// Body of an inscription. Large bodies are mapped from the content cache, so that concurrent readers share the page
+/// cache instead of holding a copy each, unless the cache is encrypted.
+pub enum ContentBody {
+    Heap(Vec<u8>),
+    /// Cached file, which body starts at `offset`.
+    Mapped {
+        map: Mmap,
+        offset: usize,
+    },
+}
+
+impl Deref for ContentBody {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ContentBody::Heap(body) => body,
+            ContentBody::Mapped { map, offset } => &map[*offset..],
+        }
+    }
+}
+
+impl AsRef<[u8]> for ContentBody {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+pub struct InscriptionContent {
+    pub inscription_id: String,
+    pub content_type: String,
+    pub body: ContentBody,
+}
+
+/// Cached contents are stored as the length of their content type (2 bytes, big endian), their content type, then
+/// their body, encrypted like blocks when a storage encryption key is set. Inscriptions never change once revealed:
+/// entries are written once and never updated.
+pub fn get_content_cache_path(config: &Config, inscription_id: &str) -> PathBuf {
+    let mut path = config.expected_content_path();
+    path.push("contents");
+    path.push(inscription_id);
+    path
+}
+
+fn read_cached_content(path: &Path, inscription_id: &str) -> Option<InscriptionContent> {
+    let file = File::open(path).ok()?;
+    // Safety: entries are written to a temporary file renamed once complete, and never modified afterwards.
+    let map = unsafe { Mmap::map(&file) }.ok()?;
+    if is_encrypted_bytes(&map) {
+        // Read as a cache miss when the storage encryption key changed, for the entry to be written again.
+        let mut entry = decrypt_stored_file_bytes(map.to_vec()).ok()?;
+        let (content_type, offset) = parse_cached_content_type(&entry)?;
+        entry.drain(..offset);
+        return Some(InscriptionContent {
+            inscription_id: inscription_id.to_string(),
+            content_type,
+            body: ContentBody::Heap(entry),
+        });
+    }
+    let (content_type, offset) = parse_cached_content_type(&map)?;
+    Some(InscriptionContent {
+        inscription_id: inscription_id.to_string(),
+        content_type,
+        body: ContentBody::Mapped { map, offset },
+    })
+}
+
+/// Returns the content type of a cache entry, and the offset of its body.
+fn parse_cached_content_type(entry: &[u8]) -> Option<(String, usize)> {
+    let content_type_len = u16::from_be_bytes([*entry.first()?, *entry.get(1)?]) as usize;
+    let offset = 2 + content_type_len;
+    let content_type = std::str::from_utf8(entry.get(2..offset)?).ok()?.to_string();
+    Some((content_type, offset))
+}
+
+fn write_cached_content(path: &Path, content_type: &str, body: &[u8]) -> Result<(), String> {
+    let parent = path
+        .parent()
+        .ok_or(format!("invalid path {}", path.display()))?;
+    std::fs::create_dir_all(parent)
+        .map_err(|e| format!("unable to create {}: {e}", parent.display()))?;
+    let content_type_len = u16::try_from(content_type.len())
+        .map_err(|_| format!("content type of {} bytes", content_type.len()))?;
+    let mut entry = Vec::with_capacity(2 + content_type.len() + body.len());
+    entry.extend_from_slice(&content_type_len.to_be_bytes());
+    entry.extend_from_slice(content_type.as_bytes());
+    entry.extend_from_slice(body);
+    let entry = encrypt_stored_file_bytes(entry)?;
+    let tmp_path = get_unique_tmp_path(path);
+    let mut file = File::create(&tmp_path)
+        .map_err(|e| format!("unable to create {}: {e}", tmp_path.display()))?;
+    file.write_all(&entry)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("unable to write {}: {e}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("unable to write {}: {e}", path.display()))
+}
+
+/// Removes the least recently written entries of the content cache until it fits in `max_size_bytes`. `kept_path`,
+/// the entry just written, is never removed. Temporary files are left to their writers, unless stale.
+fn evict_cached_contents(
+    cache_dir: &Path,
+    kept_path: &Path,
+    max_size_bytes: u64,
+) -> Result<(), String> {
+    let dir = std::fs::read_dir(cache_dir)
+        .map_err(|e| format!("unable to read {}: {e}", cache_dir.display()))?;
+    let now = SystemTime::now();
+    let mut entries = vec![];
+    let mut total_size = 0;
+    for entry in dir.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let modified = metadata.modified().unwrap_or(now);
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age >= Duration::from_secs(STALE_CONTENT_CACHE_TMP_SECS) {
+                let _ = std::fs::remove_file(&path);
+            }
+            continue;
+        }
+        total_size += metadata.len();
+        if path != kept_path {
+            entries.push((modified, path, metadata.len()));
+        }
+    }
+    entries.sort();
+    for (_, path, size) in entries.into_iter() {
+        if total_size <= max_size_bytes {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => total_size -= size,
+            // Evicted by a concurrent writer.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => total_size -= size,
+            Err(e) => return Err(format!("unable to remove {}: {e}", path.display())),
+        }
+    }
+    Ok(())
+}
+
+/// Bodies above `content_mmap_threshold_bytes` are moved to the content cache and mapped. Failing that, they are
+/// served from memory.
+fn cache_large_content(
+    content: InscriptionContent,
+    config: &Config,
+    ctx: &Context,
+) -> InscriptionContent {
+    let threshold = config.storage.content_mmap_threshold_bytes;
+    if threshold == 0 || content.body.len() as u64 <= threshold {
+        return content;
+    }
+    let path = get_content_cache_path(config, &content.inscription_id);
+    if let Err(e) = write_cached_content(&path, &content.content_type, &content.body) {
+        try_warn!(ctx, "Content cache: {e}");
+        return content;
+    }
+    if let Some(cache_dir) = path.parent() {
+        if let Err(e) = evict_cached_contents(
+            cache_dir,
+            &path,
+            config.storage.content_cache_max_size_bytes,
+        ) {
+            try_warn!(ctx, "Content cache: {e}");
+        }
+    }
+    read_cached_content(&path, &content.inscription_id).unwrap_or(content)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionContentsManifestEntry {
+    pub inscription: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inscription_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reads the content of an inscription, by id or number, from its reveal transaction. Contents are not kept in our
+/// databases: the transaction is fetched from bitcoind, using the reveal block indexed to avoid requiring `txindex`.
+pub fn fetch_inscription_content(
+    inscription: &str,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<InscriptionContent, String> {
+    let row = find_inscription_location(inscription, None, db_conn, ctx)
+        .ok_or(format!("inscription {inscription} not found"))?;
+    if config.storage.content_mmap_threshold_bytes > 0 {
+        let path = get_content_cache_path(config, &row.inscription_id);
+        if let Some(content) = read_cached_content(&path, &row.inscription_id) {
+            return Ok(content);
+        }
+    }
+    let (txid, _) = row
+        .inscription_id
+        .rsplit_once('i')
+        .ok_or(format!("invalid inscription id {}", row.inscription_id))?;
+    let tx = bitcoind_get_transaction_in_block(txid, row.block_height, config)?;
+    for (input_index, input) in tx.input.iter().enumerate() {
+        let Some(inscriptions) =
+            parse_inscriptions_from_witness(input_index, input.witness.to_vec(), txid)
+        else {
+            continue;
+        };
+        for (reveal, parsed_inscription, _) in inscriptions.into_iter() {
+            if reveal.inscription_id == row.inscription_id {
+                let content = InscriptionContent {
+                    inscription_id: row.inscription_id,
+                    content_type: reveal.content_type,
+                    body: ContentBody::Heap(parsed_inscription.into_body().unwrap_or_default()),
+                };
+                return Ok(cache_large_content(content, config, ctx));
+            }
+        }
+    }
+    Err(format!(
+        "inscription {} not found in its reveal transaction",
+        row.inscription_id
+    ))
+}
+
+/// Writes a tar archive holding the content of each inscription as `contents/<inscription_id>`, followed by a
+/// `manifest.json` describing every entry. Inscriptions that could not be exported are listed in the manifest with an
+/// `error`. The archive is written entry by entry, so that it can be streamed as it gets built.
+pub fn write_inscription_contents_archive<W: Write>(
+    inscriptions: &[String],
+    writer: W,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mut archive = tar::Builder::new(writer);
+    let mut manifest = vec![];
+    for inscription in inscriptions.iter() {
+        let content = match fetch_inscription_content(inscription, db_conn, config, ctx) {
+            Ok(content) => content,
+            Err(e) => {
+                manifest.push(InscriptionContentsManifestEntry {
+                    inscription: inscription.clone(),
+                    inscription_id: None,
+                    path: None,
+                    content_type: None,
+                    content_length: None,
+                    sha256: None,
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
+        if let Err(e) = check_blocklist(
+            &content.inscription_id,
+            Some(&content.body[..]),
+            db_conn,
+            config,
+            ctx,
+        )
+        .and_then(|_| {
+            check_content_scan(
+                &content.inscription_id,
+                Some(&content.content_type),
+                db_conn,
+                config,
+                ctx,
+            )
+        }) {
+            manifest.push(InscriptionContentsManifestEntry {
+                inscription: inscription.clone(),
+                inscription_id: Some(content.inscription_id),
+                path: None,
+                content_type: Some(content.content_type),
+                content_length: None,
+                sha256: None,
+                error: Some(e),
+            });
+            continue;
+        }
+        let path = format!("contents/{}", content.inscription_id);
+        append_archive_entry(&mut archive, &path, &content.body)?;
+        manifest.push(InscriptionContentsManifestEntry {
+            inscription: inscription.clone(),
+            inscription_id: Some(content.inscription_id),
+            path: Some(path),
+            content_type: Some(content.content_type),
+            content_length: Some(content.body.len()),
+            sha256: Some(sha256::Hash::hash(&content.body).to_string()),
+            error: None,
+        });
+    }
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("unable to serialize manifest: {e}"))?;
+    append_archive_entry(&mut archive, "manifest.json", &manifest)?;
+    archive
+        .into_inner()
+        .and_then(|mut writer| writer.flush())
+        .map_err(|e| format!("unable to write archive: {e}"))
+}
+
+fn append_archive_entry<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    archive
+        .append_data(&mut header, path, data)
+        .map_err(|e| format!("unable to write {path} to archive: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evict_cached_contents, read_cached_content, write_cached_content, ContentBody};
+
+    #[test]
+    fn maps_cached_contents() {
+        let path = std::env::temp_dir()
+            .join("ordhook_test_content_cache")
+            .join("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0");
+        let _ = std::fs::remove_file(&path);
+        let body = (0..4096).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        write_cached_content(&path, "image/webp", &body).unwrap();
+
+        let content = read_cached_content(
+            &path,
+            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0",
+        )
+        .unwrap();
+        assert_eq!(content.content_type, "image/webp");
+        assert!(matches!(content.body, ContentBody::Mapped { .. }));
+        assert_eq!(&content.body[..], &body[..]);
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn evicts_least_recently_written_contents() {
+        let cache_dir = std::env::temp_dir().join("ordhook_test_content_cache_eviction");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let body = vec![0u8; 1000];
+        let paths = (0..3)
+            .map(|i| cache_dir.join(format!("{i}")))
+            .collect::<Vec<_>>();
+        for path in paths.iter() {
+            write_cached_content(path, "image/webp", &body).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let entry_size = std::fs::metadata(&paths[0]).unwrap().len();
+
+        evict_cached_contents(&cache_dir, &paths[2], 2 * entry_size).unwrap();
+        assert!(!paths[0].exists());
+        assert!(paths[1].exists());
+        assert!(paths[2].exists());
+
+        // The entry just written is kept, even when it doesn't fit on its own.
+        evict_cached_contents(&cache_dir, &paths[2], 0).unwrap();
+        assert!(!paths[1].exists());
+        assert!(paths[2].exists());
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+}
@@ -11,26 +11,114 @@ use serde_json::json;
 use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
-use crate::config::Config;
+use crate::config::{Config, StorageConfig};
 use crate::core::meta_protocols::brc20::brc20_activation_height;
 use crate::core::meta_protocols::brc20::parser::{parse_brc20_operation, ParsedBrc20Operation};
+use crate::core::meta_protocols::sns::parse_sns_name;
+use crate::core::protocol::content_sniffing::{
+    correct_inscription_content_type, sniff_content_type,
+};
 use crate::ord::envelope::{Envelope, ParsedEnvelope, RawEnvelope};
 use crate::ord::inscription::Inscription;
 use crate::ord::inscription_id::InscriptionId;
 use crate::try_warn;
+use chainhook_sdk::bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine};
 use {chainhook_sdk::bitcoincore_rpc::bitcoin::Witness, std::str};
 
+/// Size of the slices contents are hashed and hex encoded by, small enough for the encoding buffer to stay in cache.
+const CONTENT_ENCODING_CHUNK_SIZE: usize = 4096;
+/// Prefix of the `content_bytes` of inscriptions which content was not stored, followed by the hex sha256 digest.
+const STRIPPED_CONTENT_PREFIX: &str = "sha256:";
+
+/// Digest sent in place of the inscription content when its storage is disabled.
+pub fn get_inscription_content_hash(content: &[u8]) -> String {
+    format_inscription_content_hash(&sha256::Hash::hash(content))
+}
+
+fn format_inscription_content_hash(content_hash: &sha256::Hash) -> String {
+    format!("{STRIPPED_CONTENT_PREFIX}{content_hash}")
+}
+
+/// Returns the hex sha256 digest of the content of an inscription if its content was not stored, from its
+/// `content_bytes`.
+pub fn get_stripped_content_hash(content_bytes: &str) -> Option<&str> {
+    content_bytes.strip_prefix(STRIPPED_CONTENT_PREFIX)
+}
+
+/// Hex encodes a content as `content_bytes` and hashes it in a single pass, writing straight into a string sized
+/// upfront instead of going through intermediate buffers. Nothing gets written to disk while envelopes are extracted:
+/// contents only reach the content cache when served by the API (see `inscription_content::cache_large_content`).
+fn encode_inscription_content(content: &[u8]) -> (String, sha256::Hash) {
+    let mut engine = sha256::Hash::engine();
+    let mut content_bytes = String::with_capacity(2 + 2 * content.len());
+    content_bytes.push_str("0x");
+    let mut buffer = [0u8; 2 * CONTENT_ENCODING_CHUNK_SIZE];
+    for chunk in content.chunks(CONTENT_ENCODING_CHUNK_SIZE) {
+        engine.input(chunk);
+        let hex_chunk = &mut buffer[..2 * chunk.len()];
+        hex::encode_to_slice(chunk, hex_chunk).expect("buffer sized for the chunk");
+        content_bytes.push_str(str::from_utf8(hex_chunk).expect("hex digits are ascii"));
+    }
+    (content_bytes, sha256::Hash::from_engine(engine))
+}
+
+/// Replaces the content of an inscription by its hash and drops its metadata.
+fn strip_inscription_content(
+    reveal: &mut OrdinalInscriptionRevealData,
+    content_hash: &sha256::Hash,
+) {
+    reveal.content_bytes = format_inscription_content_hash(content_hash);
+    reveal.metadata = None;
+}
+
+/// Strips the content of the inscriptions revealed in a block that should not be stored, according to
+/// `StorageConfig::should_store_content`. Declared content types are corrected first if
+/// `StorageConfig::correct_content_types` is set. Contents are decoded from the block for their type detection, so
+/// their hash is computed from that copy rather than during the envelope extraction.
+pub fn strip_inscription_contents_in_block(block: &mut BitcoinBlockData, storage: &StorageConfig) {
+    for tx in block.transactions.iter_mut() {
+        for op in tx.metadata.ordinal_operations.iter_mut() {
+            if let OrdinalOperation::InscriptionRevealed(reveal) = op {
+                let Some(hex_content) = reveal.content_bytes.strip_prefix("0x") else {
+                    continue;
+                };
+                let content = hex::decode(hex_content).unwrap_or_default();
+                if storage.correct_content_types {
+                    correct_inscription_content_type(reveal, &content);
+                }
+                if storage.should_store_content(&reveal.content_type) {
+                    continue;
+                }
+                strip_inscription_content(reveal, &sha256::Hash::hash(&content));
+            }
+        }
+    }
+}
+
+/// Decodes the hex encoded elements of a witness, with or without their `0x` prefix. Returns `None` if any element is
+/// malformed.
+pub fn decode_witness(witness: &[String]) -> Option<Vec<Vec<u8>>> {
+    witness
+        .iter()
+        .map(|w| hex::decode(w.strip_prefix("0x").unwrap_or(w)).ok())
+        .collect()
+}
+
+/// Parses the inscriptions revealed in the witness of an input, along with the sha256 of their content, exactly like
+/// ord does. A panic of the envelope parser is not caught here: it fails the whole block, which gets retried then
+/// quarantined, rather than silently shifting the numbers of the inscriptions that follow.
 pub fn parse_inscriptions_from_witness(
     input_index: usize,
     witness_bytes: Vec<Vec<u8>>,
     txid: &str,
-) -> Option<Vec<(OrdinalInscriptionRevealData, Inscription)>> {
+) -> Option<Vec<(OrdinalInscriptionRevealData, Inscription, sha256::Hash)>> {
+    let txid = Txid::from_str(txid).ok()?;
     let witness = Witness::from_slice(&witness_bytes);
     let tapscript = witness.tapscript()?;
     let envelopes: Vec<Envelope<Inscription>> = RawEnvelope::from_tapscript(tapscript, input_index)
         .ok()?
         .into_iter()
-        .map(|e| ParsedEnvelope::from(e))
+        .map(ParsedEnvelope::from)
         .collect();
     let mut inscriptions = vec![];
     for envelope in envelopes.into_iter() {
@@ -55,14 +143,12 @@ pub fn parse_inscriptions_from_witness(
         };
 
         let inscription_id = InscriptionId {
-            txid: Txid::from_str(txid).unwrap(),
+            txid,
             index: input_index as u32,
         };
 
-        let no_content_bytes = vec![];
-        let inscription_content_bytes = envelope.payload.body().take().unwrap_or(&no_content_bytes);
-        let mut content_bytes = "0x".to_string();
-        content_bytes.push_str(&hex::encode(&inscription_content_bytes));
+        let inscription_content_bytes = envelope.payload.body().unwrap_or_default();
+        let (content_bytes, content_hash) = encode_inscription_content(inscription_content_bytes);
 
         let parent = envelope.payload.parent().and_then(|i| Some(i.to_string()));
         let delegate = envelope
@@ -102,7 +188,7 @@ pub fn parse_inscriptions_from_witness(
             satpoint_post_inscription: format!(""),
             curse_type,
         };
-        inscriptions.push((reveal_data, envelope.payload));
+        inscriptions.push((reveal_data, envelope.payload, content_hash));
     }
     Some(inscriptions)
 }
@@ -117,18 +203,38 @@ pub fn parse_inscriptions_from_standardized_tx(
 ) -> Vec<OrdinalOperation> {
     let mut operations = vec![];
     for (input_index, input) in tx.metadata.inputs.iter().enumerate() {
-        let witness_bytes: Vec<Vec<u8>> = input
-            .witness
-            .iter()
-            .map(|w| hex::decode(&w[2..]).unwrap())
-            .collect();
+        let Some(witness_bytes) = decode_witness(&input.witness) else {
+            try_warn!(
+                ctx,
+                "Ignoring malformed witness of input #{input_index} in tx {}",
+                tx.transaction_identifier.hash
+            );
+            continue;
+        };
 
         if let Some(inscriptions) = parse_inscriptions_from_witness(
             input_index,
             witness_bytes,
             tx.transaction_identifier.get_hash_bytes_str(),
         ) {
-            for (reveal, inscription) in inscriptions.into_iter() {
+            for (mut reveal, inscription, content_hash) in inscriptions.into_iter() {
+                if config.storage.correct_content_types {
+                    correct_inscription_content_type(
+                        &mut reveal,
+                        inscription.body().unwrap_or_default(),
+                    );
+                }
+                // SNS names are indexed from the contents delivered, registrations keep theirs.
+                if !config.storage.should_store_content(&reveal.content_type)
+                    && !(config.meta_protocols.sns
+                        && parse_sns_name(
+                            &reveal.content_type,
+                            inscription.body().unwrap_or_default(),
+                        )
+                        .is_some())
+                {
+                    strip_inscription_content(&mut reveal, &content_hash);
+                }
                 if config.meta_protocols.brc20
                     && block_identifier.index >= brc20_activation_height(&network)
                 {
@@ -156,15 +262,14 @@ pub fn parse_inscriptions_in_raw_tx(
     let mut operations = vec![];
     for (input_index, input) in tx.vin.iter().enumerate() {
         if let Some(ref witness_data) = input.txinwitness {
-            let witness_bytes: Vec<Vec<u8>> = witness_data
-                .iter()
-                .map(|w| hex::decode(w).unwrap())
-                .collect();
+            let Some(witness_bytes) = decode_witness(witness_data) else {
+                continue;
+            };
 
             if let Some(inscriptions) =
                 parse_inscriptions_from_witness(input_index, witness_bytes, &tx.txid)
             {
-                for (reveal, _inscription) in inscriptions.into_iter() {
+                for (reveal, _, _) in inscriptions.into_iter() {
                     operations.push(OrdinalOperation::InscriptionRevealed(reveal));
                 }
             }
@@ -228,6 +333,54 @@ pub fn get_inscriptions_revealed_in_block(
     ops
 }
 
+/// Returns the `(declared, detected)` content types of the inscriptions revealed in a block, by inscription id. Contents
+/// are read back from the witnesses of the block, since the reveals can have had their content stripped and their
+/// content type corrected since they were parsed.
+pub fn get_inscription_content_types_in_block(
+    block: &BitcoinBlockData,
+) -> HashMap<String, (String, &'static str)> {
+    let mut content_types = HashMap::new();
+    for tx in block.transactions.iter() {
+        let mut reveals_by_input: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+        for op in tx.metadata.ordinal_operations.iter() {
+            if let OrdinalOperation::InscriptionRevealed(reveal) = op {
+                reveals_by_input
+                    .entry(reveal.inscription_input_index)
+                    .or_default()
+                    .push(&reveal.inscription_id);
+            }
+        }
+        for (input_index, inscription_ids) in reveals_by_input.into_iter() {
+            let Some(witness_bytes) = tx
+                .metadata
+                .inputs
+                .get(input_index)
+                .and_then(|input| decode_witness(&input.witness))
+            else {
+                continue;
+            };
+            let Some(inscriptions) = parse_inscriptions_from_witness(
+                input_index,
+                witness_bytes,
+                tx.transaction_identifier.get_hash_bytes_str(),
+            ) else {
+                continue;
+            };
+            for (reveal, inscription, _) in inscriptions.into_iter() {
+                if !inscription_ids.contains(&reveal.inscription_id.as_str()) {
+                    continue;
+                }
+                let Some(detected) = sniff_content_type(inscription.body().unwrap_or_default())
+                else {
+                    continue;
+                };
+                content_types.insert(reveal.inscription_id, (reveal.content_type, detected));
+            }
+        }
+    }
+    content_types
+}
+
 pub fn get_inscriptions_transferred_in_block(
     block: &BitcoinBlockData,
 ) -> Vec<&OrdinalInscriptionTransferData> {
@@ -269,8 +422,11 @@ mod test {
     };
 
     use super::{
-        get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
-        parse_inscriptions_and_standardize_block, parse_inscriptions_in_standardized_block,
+        decode_witness, encode_inscription_content, get_inscription_content_hash,
+        get_inscription_content_types_in_block, get_inscriptions_revealed_in_block,
+        get_inscriptions_transferred_in_block, get_stripped_content_hash,
+        parse_inscriptions_and_standardize_block, parse_inscriptions_from_witness,
+        parse_inscriptions_in_standardized_block, strip_inscription_contents_in_block,
     };
 
     pub fn new_test_transfer_tx_with_operation() -> BitcoinTransactionData {
@@ -400,4 +556,95 @@ mod test {
         assert_eq!(reveal.content_bytes, "0x7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d".to_string());
         assert_eq!(reveal.content_length, 94);
     }
+
+    #[test_case(0; "empty content")]
+    #[test_case(94; "single chunk")]
+    #[test_case(4096; "exactly one chunk")]
+    #[test_case(10_000; "multiple chunks")]
+    fn encodes_and_hashes_content_in_one_pass(size: usize) {
+        let content = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let (content_bytes, content_hash) = encode_inscription_content(&content);
+        assert_eq!(content_bytes, format!("0x{}", hex::encode(&content)));
+        assert_eq!(content_bytes.capacity(), 2 + 2 * size);
+        assert_eq!(
+            format!("sha256:{content_hash}"),
+            get_inscription_content_hash(&content)
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_witnesses() {
+        assert_eq!(
+            decode_witness(&vec!["0x".into(), "6a".into()]),
+            Some(vec![vec![], vec![0x6a]])
+        );
+        assert_eq!(decode_witness(&vec!["0".into()]), None);
+        assert_eq!(decode_witness(&vec!["0xzz".into()]), None);
+
+        let txid = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+        let truncated_envelope = vec![
+            vec![0x00, 0x63, 0x03, b'o', b'r', b'd', 0x4d, 0xff],
+            vec![0xc1],
+        ];
+        assert!(
+            parse_inscriptions_from_witness(0, truncated_envelope.clone(), txid)
+                .unwrap_or_default()
+                .is_empty()
+        );
+        assert!(parse_inscriptions_from_witness(0, truncated_envelope, "not a txid").is_none());
+    }
+
+    #[test]
+    fn replaces_content_by_hash_when_content_is_not_stored() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.store_content = false;
+        let mut block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_input(
+                        TestTxInBuilder::new()
+                            .witness(vec![
+                                "0x6c00eb3c4d35fedd257051333b4ca81d1a25a37a9af4891f1fec2869edd56b14180eafbda8851d63138a724c9b15384bc5f0536de658bd294d426a36212e6f08".to_string(),
+                                "0x209e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746ac0063036f7264010118746578742f706c61696e3b636861727365743d7574662d38004c5e7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d68".to_string(),
+                                "0xc19e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746".to_string(),
+                            ])
+                            .build()
+                    )
+                    .build(),
+            )
+            .build();
+        parse_inscriptions_in_standardized_block(&mut block, &mut HashMap::new(), &config, &ctx);
+        let OrdinalOperation::InscriptionRevealed(reveal) =
+            &block.transactions[0].metadata.ordinal_operations[0]
+        else {
+            panic!();
+        };
+        let expected_hash = get_inscription_content_hash(&hex::decode("7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d").unwrap());
+        assert!(expected_hash.starts_with("sha256:"));
+        assert_eq!(reveal.content_bytes, expected_hash);
+        assert_eq!(
+            get_stripped_content_hash(&reveal.content_bytes),
+            expected_hash.strip_prefix("sha256:")
+        );
+        assert_eq!(reveal.content_length, 94);
+        assert_eq!(reveal.metadata, None);
+
+        let raw_block = new_test_raw_block(vec![new_test_reveal_raw_tx()]);
+        let mut block =
+            parse_inscriptions_and_standardize_block(raw_block, &BitcoinNetwork::Mainnet, &ctx)
+                .unwrap();
+        strip_inscription_contents_in_block(&mut block, &config.storage);
+        let OrdinalOperation::InscriptionRevealed(reveal) =
+            &block.transactions[0].metadata.ordinal_operations[0]
+        else {
+            panic!();
+        };
+        assert_eq!(reveal.content_bytes, expected_hash);
+        // Still detected from the witness once the content is stripped.
+        assert_eq!(
+            get_inscription_content_types_in_block(&block).remove(&reveal.inscription_id),
+            Some(("text/plain;charset=utf-8".to_string(), "application/json"))
+        );
+    }
 }
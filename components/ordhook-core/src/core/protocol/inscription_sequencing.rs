@@ -32,9 +32,12 @@ use crate::{
             find_all_inscriptions_in_block, find_blessed_inscription_with_ordinal_number,
             find_nth_classic_neg_number_at_block_height,
             find_nth_classic_pos_number_at_block_height, find_nth_jubilee_number_at_block_height,
-            update_ordinals_db_with_block, update_sequence_metadata_with_block,
+            find_nth_unbound_sequence_at_block_height, find_unbound_inscriptions_in_block,
+            format_unbound_satpoint, update_ordinals_db_with_block,
+            update_sequence_metadata_with_block,
         },
     },
+    error::OrdhookError,
     ord::height::Height,
     try_error, try_info, try_warn,
     utils::format_inscription_id,
@@ -81,7 +84,7 @@ pub fn parallelize_inscription_data_computations(
     inscriptions_db_tx: &Transaction,
     config: &Config,
     ctx: &Context,
-) -> Result<bool, String> {
+) -> Result<bool, OrdhookError> {
     let inner_ctx = if config.logs.ordinals_internals {
         ctx.clone()
     } else {
@@ -405,18 +408,24 @@ pub struct SequenceCursor<'a> {
     pos_cursor: Option<i64>,
     neg_cursor: Option<i64>,
     jubilee_cursor: Option<i64>,
+    unbound_cursor: Option<u64>,
     inscriptions_db_conn: &'a Connection,
     current_block_height: u64,
+    jubilee_height: u64,
 }
 
 impl<'a> SequenceCursor<'a> {
-    pub fn new(inscriptions_db_conn: &'a Connection) -> SequenceCursor<'a> {
+    /// `jubilee_height` is the height from which cursed inscriptions get positive numbers, see
+    /// `get_jubilee_block_height`.
+    pub fn new(inscriptions_db_conn: &'a Connection, jubilee_height: u64) -> SequenceCursor<'a> {
         SequenceCursor {
             jubilee_cursor: None,
             pos_cursor: None,
             neg_cursor: None,
+            unbound_cursor: None,
             inscriptions_db_conn,
             current_block_height: 0,
+            jubilee_height,
         }
     }
 
@@ -424,6 +433,7 @@ impl<'a> SequenceCursor<'a> {
         self.pos_cursor = None;
         self.neg_cursor = None;
         self.jubilee_cursor = None;
+        self.unbound_cursor = None;
         self.current_block_height = 0;
     }
 
@@ -431,7 +441,6 @@ impl<'a> SequenceCursor<'a> {
         &mut self,
         cursed: bool,
         block_height: u64,
-        network: &Network,
         ctx: &Context,
     ) -> OrdinalInscriptionNumber {
         if block_height < self.current_block_height {
@@ -444,7 +453,7 @@ impl<'a> SequenceCursor<'a> {
             false => self.pick_next_pos_classic(ctx),
         };
 
-        let jubilee = if block_height >= get_jubilee_block_height(&network) {
+        let jubilee = if block_height >= self.jubilee_height {
             self.pick_next_jubilee_number(ctx)
         } else {
             classic
@@ -529,15 +538,43 @@ impl<'a> SequenceCursor<'a> {
     fn increment_jubilee_number(&mut self, ctx: &Context) {
         self.jubilee_cursor = Some(self.pick_next_jubilee_number(ctx))
     }
+
+    /// Unbound sequence of the next unbound inscription of the block last picked an inscription number for.
+    pub fn pick_next_unbound(&mut self, ctx: &Context) -> u64 {
+        match self.unbound_cursor {
+            None => match find_nth_unbound_sequence_at_block_height(
+                self.current_block_height,
+                &self.inscriptions_db_conn,
+                &ctx,
+            ) {
+                Some(unbound_sequence) => {
+                    self.unbound_cursor = Some(unbound_sequence);
+                    unbound_sequence + 1
+                }
+                _ => 0,
+            },
+            Some(value) => value + 1,
+        }
+    }
+
+    pub fn increment_unbound(&mut self, ctx: &Context) {
+        self.unbound_cursor = Some(self.pick_next_unbound(ctx));
+    }
 }
 
-pub fn get_jubilee_block_height(network: &Network) -> u64 {
-    match network {
-        Network::Bitcoin => 824544,
-        Network::Regtest => 110,
-        Network::Signet => 175392,
-        Network::Testnet => 2544192,
-        _ => unreachable!(),
+/// Height from which cursed inscriptions get positive numbers on the network followed: `network.jubilee_height` on
+/// regtest and signet when set, ord's height otherwise.
+pub fn get_jubilee_block_height(config: &Config) -> u64 {
+    match (
+        &config.network.bitcoin_network,
+        config.network.jubilee_height,
+    ) {
+        (BitcoinNetwork::Regtest | BitcoinNetwork::Signet, Some(jubilee_height)) => jubilee_height,
+        (BitcoinNetwork::Mainnet, _) => 824544,
+        (BitcoinNetwork::Regtest, _) => 110,
+        (BitcoinNetwork::Signet, _) => 175392,
+        (BitcoinNetwork::Testnet, _) if config.network.testnet4 => 0,
+        (BitcoinNetwork::Testnet, _) => 2544192,
     }
 }
 
@@ -562,7 +599,7 @@ pub fn augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     inscriptions_db_tx: &Transaction,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, String> {
     // Handle re-inscriptions
     let mut reinscriptions_data = HashMap::new();
     for (_, inscription_data) in inscriptions_data.iter() {
@@ -584,12 +621,12 @@ pub fn augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
         inscriptions_data,
         &mut reinscriptions_data,
         &ctx,
-    );
+    )?;
 
     // Store inscriptions
     update_ordinals_db_with_block(block, inscriptions_db_tx, ctx);
     update_sequence_metadata_with_block(block, inscriptions_db_tx, ctx);
-    any_events
+    Ok(any_events)
 }
 
 /// Given a `BitcoinBlockData` that have been augmented with the functions `parse_inscriptions_in_raw_tx`, `parse_inscriptions_in_standardized_tx`
@@ -606,7 +643,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     reinscriptions_data: &mut HashMap<u64, String>,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, String> {
     // Handle sat oveflows
     let mut sats_overflows = VecDeque::new();
     let mut any_event = false;
@@ -630,7 +667,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
             &mut sats_overflows,
             reinscriptions_data,
             ctx,
-        );
+        )?;
     }
 
     // Handle sats overflow
@@ -642,13 +679,13 @@ pub fn augment_block_with_ordinals_inscriptions_data(
         };
         let is_cursed = inscription_data.curse_type.is_some();
         let inscription_number =
-            sequence_cursor.pick_next(is_cursed, block.block_identifier.index, &network, &ctx);
+            sequence_cursor.pick_next(is_cursed, block.block_identifier.index, &ctx);
         inscription_data.inscription_number = inscription_number;
 
         sequence_cursor.increment(is_cursed, ctx);
         try_info!(
             ctx,
-            "Unbound inscription {} (#{}) detected on Satoshi {} (block #{}, {} transfers)",
+            "Inscription {} (#{}) spent in fees detected on Satoshi {} (block #{}, {} transfers)",
             inscription_data.inscription_id,
             inscription_data.get_inscription_number(),
             inscription_data.ordinal_number,
@@ -656,7 +693,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
             inscription_data.transfers_pre_inscription,
         );
     }
-    any_event
+    Ok(any_event)
 }
 
 /// Given a `BitcoinTransactionData` that have been augmented with the functions `parse_inscriptions_in_raw_tx` or
@@ -678,7 +715,7 @@ fn augment_transaction_with_ordinals_inscriptions_data(
     sats_overflows: &mut VecDeque<(usize, usize)>,
     reinscriptions_data: &mut HashMap<u64, String>,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, String> {
     let inputs = tx
         .metadata
         .inputs
@@ -718,9 +755,24 @@ fn augment_transaction_with_ordinals_inscriptions_data(
                 }
             };
 
+        let Some(input_value) = inputs.get(input_index) else {
+            return Err(format!(
+                "inscription {inscription_id} is revealed on input #{input_index} of {}, which has {} inputs",
+                tx.transaction_identifier.hash,
+                inputs.len()
+            ));
+        };
+        // Like in ord, inscriptions revealed on a zero-value input, or carrying an unrecognized even field, are not
+        // bound to any sat: they get an inscription number, but no sat and no owner.
+        let unbound = *input_value == 0
+            || matches!(
+                inscription.curse_type,
+                Some(OrdinalInscriptionCurseType::UnrecognizedEvenField)
+            );
+
         // Do we need to curse the inscription?
         let mut inscription_number =
-            sequence_cursor.pick_next(is_cursed, block_identifier.index, network, ctx);
+            sequence_cursor.pick_next(is_cursed, block_identifier.index, ctx);
         let mut curse_type_override = None;
         if !is_cursed {
             // Is this inscription re-inscribing an existing blessed inscription?
@@ -737,7 +789,7 @@ fn augment_transaction_with_ordinals_inscriptions_data(
 
                 is_cursed = true;
                 inscription_number =
-                    sequence_cursor.pick_next(is_cursed, block_identifier.index, network, ctx);
+                    sequence_cursor.pick_next(is_cursed, block_identifier.index, ctx);
                 curse_type_override = Some(OrdinalInscriptionCurseType::Reinscription)
             }
         };
@@ -755,6 +807,30 @@ fn augment_transaction_with_ordinals_inscriptions_data(
             None => inscription.curse_type.take(),
         };
 
+        if unbound {
+            let unbound_sequence = sequence_cursor.pick_next_unbound(ctx);
+            inscription.ordinal_offset = 0;
+            inscription.ordinal_block_height = 0;
+            inscription.ordinal_number = 0;
+            inscription.inscription_output_value = 0;
+            inscription.inscriber_address = None;
+            inscription.satpoint_post_inscription = format_unbound_satpoint(unbound_sequence);
+            inscription_subindex += 1;
+
+            try_info!(
+                ctx,
+                "Unbound inscription {} (#{}) detected (block #{}, unbound sequence {})",
+                inscription.inscription_id,
+                inscription.get_inscription_number(),
+                block_identifier.index,
+                unbound_sequence,
+            );
+
+            sequence_cursor.increment_unbound(ctx);
+            sequence_cursor.increment(is_cursed, ctx);
+            continue;
+        }
+
         let (destination, satpoint_post_transfer, output_value) = compute_satpoint_post_transfer(
             &&*tx,
             input_index,
@@ -810,7 +886,7 @@ fn augment_transaction_with_ordinals_inscriptions_data(
         .ordinal_operations
         .append(&mut mutated_operations);
 
-    any_event
+    Ok(any_event)
 }
 
 /// Best effort to re-augment a `BitcoinTransactionData` with data coming from `inscriptions` and `locations` tables.
@@ -823,6 +899,7 @@ fn consolidate_transaction_with_pre_computed_inscription_data(
     cumulated_fees: &mut u64,
     network: &Network,
     inscriptions_data: &mut BTreeMap<String, TraversalResult>,
+    unbound_inscriptions: &HashMap<String, u64>,
     ctx: &Context,
 ) {
     let mut subindex = 0;
@@ -858,6 +935,13 @@ fn consolidate_transaction_with_pre_computed_inscription_data(
         inscription.inscription_fee = tx.metadata.fee;
         inscription.tx_index = tx_index;
 
+        if let Some(unbound_sequence) = unbound_inscriptions.get(&inscription_id) {
+            inscription.satpoint_post_inscription = format_unbound_satpoint(*unbound_sequence);
+            inscription.inscription_output_value = 0;
+            inscription.inscriber_address = None;
+            continue;
+        }
+
         let (input_index, relative_offset) = match inscription.inscription_pointer {
             Some(pointer) => resolve_absolute_pointer(&inputs, pointer),
             None => (traversal.inscription_input_index, 0),
@@ -922,6 +1006,8 @@ pub fn consolidate_block_with_pre_computed_ordinals_data(
         }
         break results;
     };
+    let unbound_inscriptions =
+        find_unbound_inscriptions_in_block(block.block_identifier.index, inscriptions_db_tx, ctx);
     let mut brc20_token_map = HashMap::new();
     let mut brc20_block_ledger_map = match brc20_db_conn {
         Some(conn) => get_brc20_operations_on_block(block.block_identifier.index, &conn, &ctx),
@@ -937,6 +1023,7 @@ pub fn consolidate_block_with_pre_computed_ordinals_data(
             &mut cumulated_fees,
             &network,
             &mut inscriptions_data,
+            &unbound_inscriptions,
             ctx,
         );
 
@@ -983,7 +1070,21 @@ mod test {
         db::{drop_all_dbs, initialize_sqlite_dbs, ordinals::insert_entry_in_inscriptions},
     };
 
-    use super::consolidate_block_with_pre_computed_ordinals_data;
+    use super::{consolidate_block_with_pre_computed_ordinals_data, get_jubilee_block_height};
+
+    #[test]
+    fn reads_jubilee_height_of_testnets_from_config() {
+        assert_eq!(
+            get_jubilee_block_height(&Config::testnet_default()),
+            2544192
+        );
+        assert_eq!(get_jubilee_block_height(&Config::testnet4_default()), 0);
+        // Building a testnet4 config has no effect on the others.
+        assert_eq!(
+            get_jubilee_block_height(&Config::testnet_default()),
+            2544192
+        );
+    }
 
     #[test]
     fn consolidates_block_with_pre_computed_data() {
@@ -1071,13 +1172,13 @@ mod test {
     }
 
     mod cursor {
-        use chainhook_sdk::{bitcoin::Network, utils::Context};
+        use chainhook_sdk::{types::BitcoinNetwork, utils::Context};
 
         use test_case::test_case;
 
         use crate::{
             config::Config,
-            core::protocol::inscription_sequencing::SequenceCursor,
+            core::protocol::inscription_sequencing::{get_jubilee_block_height, SequenceCursor},
             core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
             db::{
                 drop_all_dbs, initialize_sqlite_dbs, ordinals::update_sequence_metadata_with_block,
@@ -1100,27 +1201,28 @@ mod test {
 
             // Pick next twice so we can test all cases.
             update_sequence_metadata_with_block(&block, &db_conns.ordinals, &ctx);
-            let mut cursor = SequenceCursor::new(&db_conns.ordinals);
-            let _ = cursor.pick_next(
-                cursed,
-                block.block_identifier.index + 1,
-                &Network::Bitcoin,
-                &ctx,
-            );
+            let mut cursor = SequenceCursor::new(&db_conns.ordinals, 824544);
+            let _ = cursor.pick_next(cursed, block.block_identifier.index + 1, &ctx);
             cursor.increment(cursed, &ctx);
 
             block.block_identifier.index = block.block_identifier.index + 1;
             update_sequence_metadata_with_block(&block, &db_conns.ordinals, &ctx);
-            let next = cursor.pick_next(
-                cursed,
-                block.block_identifier.index + 1,
-                &Network::Bitcoin,
-                &ctx,
-            );
+            let next = cursor.pick_next(cursed, block.block_identifier.index + 1, &ctx);
 
             (next.classic, next.jubilee)
         }
 
+        #[test]
+        fn follows_configured_jubilee_height() {
+            let mut config = Config::test_default();
+            config.network.bitcoin_network = BitcoinNetwork::Regtest;
+            assert_eq!(get_jubilee_block_height(&config), 110);
+            config.network.jubilee_height = Some(3);
+            assert_eq!(get_jubilee_block_height(&config), 3);
+            config.network.bitcoin_network = BitcoinNetwork::Mainnet;
+            assert_eq!(get_jubilee_block_height(&config), 824544);
+        }
+
         #[test]
         fn resets_on_previous_block() {
             let ctx = Context::empty();
@@ -1131,22 +1233,12 @@ mod test {
                 .transactions(vec![TestTransactionBuilder::new_with_operation().build()])
                 .build();
             update_sequence_metadata_with_block(&block, &db_conns.ordinals, &ctx);
-            let mut cursor = SequenceCursor::new(&db_conns.ordinals);
-            let _ = cursor.pick_next(
-                false,
-                block.block_identifier.index + 1,
-                &Network::Bitcoin,
-                &ctx,
-            );
+            let mut cursor = SequenceCursor::new(&db_conns.ordinals, 824544);
+            let _ = cursor.pick_next(false, block.block_identifier.index + 1, &ctx);
             cursor.increment(false, &ctx);
 
             cursor.reset();
-            let next = cursor.pick_next(
-                false,
-                block.block_identifier.index - 10,
-                &Network::Bitcoin,
-                &ctx,
-            );
+            let next = cursor.pick_next(false, block.block_identifier.index - 10, &ctx);
             assert_eq!(next.classic, 0);
             assert_eq!(next.jubilee, 0);
         }
@@ -1,4 +1,11 @@
+pub mod addresses;
+pub mod content_sniffing;
+pub mod event_hash;
+pub mod index_commitment;
+pub mod inscription_content;
 pub mod inscription_parsing;
 pub mod inscription_sequencing;
+pub mod sale_detection;
 pub mod satoshi_numbering;
 pub mod satoshi_tracking;
+pub mod satributes;
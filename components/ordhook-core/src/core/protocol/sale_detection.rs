@@ -0,0 +1,221 @@
+use chainhook_sdk::{
+    bitcoincore_rpc_json::bitcoin::Network,
+    types::{BitcoinTransactionData, OrdinalInscriptionTransferDestination, OrdinalOperation},
+};
+
+use crate::{core::protocol::addresses::script_hex_address, utils::format_outpoint_to_watch};
+
+/// `SIGHASH_SINGLE | SIGHASH_ANYONECANPAY`, the sighash type sellers sign their listings with: the signature only
+/// commits to the inscription input and to the payment output at the same index.
+const SIGHASH_SINGLE_ANYONECANPAY: u8 = 0x83;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaleConfidence {
+    /// The inscription input was signed `SIGHASH_SINGLE | SIGHASH_ANYONECANPAY`, as marketplace listings are.
+    High,
+    /// The transaction has the layout of a marketplace sale, but its inscription input signature does not tell.
+    Low,
+}
+
+impl SaleConfidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaleConfidence::High => "high",
+            SaleConfidence::Low => "low",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<SaleConfidence> {
+        match value {
+            "high" => Some(SaleConfidence::High),
+            "low" => Some(SaleConfidence::Low),
+            _ => None,
+        }
+    }
+}
+
+/// Likely sale of an inscribed sat.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectedSale {
+    pub ordinal_number: u64,
+    pub tx_id: String,
+    pub tx_index: usize,
+    /// Index of the input spending the inscription, paired with the payment output.
+    pub input_index: usize,
+    /// Value of the payment output, received by the seller. Marketplace and royalty fees paid on other outputs are not
+    /// included.
+    pub price_sats: u64,
+    /// Address the payment was sent to.
+    pub seller_address: Option<String>,
+    pub buyer_address: String,
+    pub confidence: SaleConfidence,
+}
+
+/// Sighash type of a key path or segwit v0 signature, read from the last byte of the first witness element.
+fn input_sighash_type(witness: &Vec<String>) -> Option<u8> {
+    let signature = witness.first()?.trim_start_matches("0x");
+    // Taproot signatures committing to the default sighash type omit it.
+    if signature.len() < 2 || signature.len() == 128 {
+        return None;
+    }
+    u8::from_str_radix(&signature[signature.len() - 2..], 16).ok()
+}
+
+/// Detects the sales of the inscriptions transferred by a transaction, following the layout of the PSBTs used by
+/// marketplaces: the seller signs the input holding the inscription along with an output of the same index paying
+/// them, and the buyer completes the transaction with the inputs funding it and the output receiving the inscription.
+/// A transfer is reported as a sale when the output paired with its input is not the one receiving the inscription,
+/// does not pay the buyer, and carries at least `min_price_sats`. The inscriptions sharing an input are reported at
+/// the same price.
+pub fn detect_sales_in_transaction(
+    tx: &BitcoinTransactionData,
+    network: &Network,
+    min_price_sats: u64,
+) -> Vec<DetectedSale> {
+    let mut sales = vec![];
+    if tx.metadata.inputs.len() < 2 {
+        return sales;
+    }
+    for op in tx.metadata.ordinal_operations.iter() {
+        let OrdinalOperation::InscriptionTransferred(transfer) = op else {
+            continue;
+        };
+        let OrdinalInscriptionTransferDestination::Transferred(ref buyer_address) =
+            transfer.destination
+        else {
+            continue;
+        };
+        let Some(input_index) = tx.metadata.inputs.iter().position(|input| {
+            let outpoint = format_outpoint_to_watch(
+                &input.previous_output.txid,
+                input.previous_output.vout as usize,
+            );
+            transfer
+                .satpoint_pre_transfer
+                .starts_with(&format!("{outpoint}:"))
+        }) else {
+            continue;
+        };
+        let Some(payment_output) = tx.metadata.outputs.get(input_index) else {
+            continue;
+        };
+        let inscription_vout = transfer
+            .satpoint_post_transfer
+            .split(':')
+            .nth(1)
+            .and_then(|vout| vout.parse::<usize>().ok());
+        if inscription_vout == Some(input_index) || payment_output.value < min_price_sats {
+            continue;
+        }
+        let seller_address = script_hex_address(&payment_output.get_script_pubkey_hex(), network);
+        if seller_address.as_ref() == Some(buyer_address) {
+            continue;
+        }
+        let confidence = match input_sighash_type(&tx.metadata.inputs[input_index].witness) {
+            Some(SIGHASH_SINGLE_ANYONECANPAY) => SaleConfidence::High,
+            _ => SaleConfidence::Low,
+        };
+        sales.push(DetectedSale {
+            ordinal_number: transfer.ordinal_number,
+            tx_id: tx.transaction_identifier.get_hash_bytes_str().to_string(),
+            tx_index: transfer.tx_index,
+            input_index,
+            price_sats: payment_output.value,
+            seller_address,
+            buyer_address: buyer_address.clone(),
+            confidence,
+        });
+    }
+    sales
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::{
+        bitcoincore_rpc_json::bitcoin::Network,
+        types::{
+            BitcoinTransactionData, OrdinalInscriptionTransferData,
+            OrdinalInscriptionTransferDestination, OrdinalOperation,
+        },
+    };
+
+    use super::{detect_sales_in_transaction, SaleConfidence};
+    use crate::core::test_builders::{TestTransactionBuilder, TestTxInBuilder, TestTxOutBuilder};
+
+    const BUYER_ADDRESS: &str = "bc1qd2j97e4h8k4jh7lq9usx9feyjgzy08u9me5yda";
+    const SELLER_SCRIPT_PUBKEY: &str = "0x0014ccb8e0d87a7a1ec4b2fd1e1bb2b0e0e8b0a0d1e0";
+    const SELLER_SIGNATURE: &str = "0x3044022000000000000000000000000000000000000000000000000000000000000000010220000000000000000000000000000000000000000000000000000000000000000183";
+
+    /// Buyer dummy input, seller inscription input, buyer funding input.
+    fn sale_tx(
+        seller_witness: Vec<String>,
+        price: u64,
+        inscription_vout: usize,
+    ) -> BitcoinTransactionData {
+        TestTransactionBuilder::new()
+            .add_input(TestTxInBuilder::new().value(600).build())
+            .add_input(
+                TestTxInBuilder::new()
+                    .prev_out_tx_hash(
+                        "0x9f1dd53a4c5ff9e4a2b5d1e2c3b4a5968778695a4b3c2d1e0f1a2b3c4d5e6f70"
+                            .to_string(),
+                    )
+                    .value(546)
+                    .witness(seller_witness)
+                    .build(),
+            )
+            .add_input(TestTxInBuilder::new().value(1_000_000).build())
+            .add_output(TestTxOutBuilder::new().value(1_146).build())
+            .add_output(
+                TestTxOutBuilder::new()
+                    .value(price)
+                    .script_pubkey(SELLER_SCRIPT_PUBKEY.to_string())
+                    .build(),
+            )
+            .add_output(TestTxOutBuilder::new().value(546).build())
+            .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                OrdinalInscriptionTransferData {
+                    ordinal_number: 1_964_125_000_000_123,
+                    destination: OrdinalInscriptionTransferDestination::Transferred(
+                        BUYER_ADDRESS.to_string(),
+                    ),
+                    satpoint_pre_transfer:
+                        "9f1dd53a4c5ff9e4a2b5d1e2c3b4a5968778695a4b3c2d1e0f1a2b3c4d5e6f70:0:0"
+                            .to_string(),
+                    satpoint_post_transfer: format!(
+                        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:{inscription_vout}:0"
+                    ),
+                    post_transfer_output_value: Some(546),
+                    tx_index: 1,
+                },
+            ))
+            .build()
+    }
+
+    #[test]
+    fn detects_listing_sales() {
+        let tx = sale_tx(vec![SELLER_SIGNATURE.to_string()], 250_000, 2);
+        let sales = detect_sales_in_transaction(&tx, &Network::Bitcoin, 10_000);
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].input_index, 1);
+        assert_eq!(sales[0].price_sats, 250_000);
+        assert_eq!(sales[0].buyer_address, BUYER_ADDRESS);
+        assert_eq!(sales[0].confidence, SaleConfidence::High);
+    }
+
+    #[test]
+    fn reports_unsigned_listing_layouts_with_low_confidence() {
+        let tx = sale_tx(vec![], 250_000, 2);
+        let sales = detect_sales_in_transaction(&tx, &Network::Bitcoin, 10_000);
+        assert_eq!(sales[0].confidence, SaleConfidence::Low);
+    }
+
+    #[test]
+    fn ignores_plain_transfers() {
+        let tx = sale_tx(vec![SELLER_SIGNATURE.to_string()], 250_000, 1);
+        assert!(detect_sales_in_transaction(&tx, &Network::Bitcoin, 10_000).is_empty());
+        let tx = sale_tx(vec![SELLER_SIGNATURE.to_string()], 5_000, 2);
+        assert!(detect_sales_in_transaction(&tx, &Network::Bitcoin, 10_000).is_empty());
+    }
+}
@@ -73,8 +73,13 @@ pub fn compute_satoshi_number(
             )
         }
         None => loop {
-            match find_pinned_block_bytes_at_block_height(ordinal_block_number, 3, &blocks_db, &ctx)
-            {
+            match find_pinned_block_bytes_at_block_height(
+                ordinal_block_number,
+                3,
+                &blocks_db,
+                config,
+                &ctx,
+            ) {
                 None => {
                     return Err(format!("block #{ordinal_block_number} not in database"));
                 }
@@ -164,6 +169,7 @@ pub fn compute_satoshi_number(
                     ordinal_block_number,
                     3,
                     &blocks_db,
+                    config,
                     &ctx,
                 ) {
                     Some(block) => break block,
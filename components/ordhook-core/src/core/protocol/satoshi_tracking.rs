@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use chainhook_sdk::{
-    bitcoincore_rpc_json::bitcoin::{Address, Network, ScriptBuf},
+    bitcoincore_rpc_json::bitcoin::Network,
     types::{
         BitcoinBlockData, BitcoinTransactionData, OrdinalInscriptionTransferData,
         OrdinalInscriptionTransferDestination, OrdinalOperation,
@@ -10,7 +10,7 @@ use chainhook_sdk::{
 };
 
 use crate::{
-    core::{compute_next_satpoint_data, SatPosition},
+    core::{compute_next_satpoint_data, protocol::addresses::get_output_destination, SatPosition},
     db::ordinals::{
         find_inscribed_ordinals_at_wached_outpoint, insert_ordinal_transfer_in_locations_tx,
         OrdinalLocation,
@@ -103,29 +103,7 @@ pub fn compute_satpoint_post_transfer(
             SatPosition::Output((output_index, offset)) => {
                 let outpoint = format_outpoint_to_watch(&tx.transaction_identifier, output_index);
                 let script_pub_key_hex = tx.metadata.outputs[output_index].get_script_pubkey_hex();
-                let updated_address = match ScriptBuf::from_hex(&script_pub_key_hex) {
-                    Ok(script) => match Address::from_script(&script, network.clone()) {
-                        Ok(address) => {
-                            OrdinalInscriptionTransferDestination::Transferred(address.to_string())
-                        }
-                        Err(e) => {
-                            try_info!(
-                                ctx,
-                                "unable to retrieve address from {script_pub_key_hex}: {}",
-                                e.to_string()
-                            );
-                            OrdinalInscriptionTransferDestination::Burnt(script.to_string())
-                        }
-                    },
-                    Err(e) => {
-                        try_info!(
-                            ctx,
-                            "unable to retrieve address from {script_pub_key_hex}: {}",
-                            e.to_string()
-                        );
-                        OrdinalInscriptionTransferDestination::Burnt(script_pub_key_hex.to_string())
-                    }
-                };
+                let updated_address = get_output_destination(&script_pub_key_hex, network);
 
                 (
                     outpoint,
@@ -329,7 +307,7 @@ mod test {
 
         assert_eq!(
             destination,
-            OrdinalInscriptionTransferDestination::Burnt("OP_RETURN OP_PUSHBYTES_36 aa21a9edd3ce297baa3ee8fd96ecd7613f2743552e2f91ed4864540cf059835ff5b35cff".to_string())
+            OrdinalInscriptionTransferDestination::Burnt("op_return:6a24aa21a9edd3ce297baa3ee8fd96ecd7613f2743552e2f91ed4864540cf059835ff5b35cff".to_string())
         );
         assert_eq!(
             satpoint,
@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, sync::RwLock};
+
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::Config,
+    ord::{rarity::Rarity, sat::Sat, COIN_VALUE},
+    try_info,
+};
+
+/// Satributes derived from the sat number itself, including the rarities above common.
+pub const BUILTIN_SATRIBUTES: [&str; 13] = [
+    "uncommon",
+    "rare",
+    "epic",
+    "legendary",
+    "mythic",
+    "vintage",
+    "block_9",
+    "block_78",
+    "palindrome",
+    "alpha",
+    "omega",
+    "black",
+    "first_transaction",
+];
+/// Sats mined in the first 1000 blocks.
+const VINTAGE_MAX_HEIGHT: u64 = 1_000;
+/// Sats sent to Hal Finney in the first bitcoin transaction (block 170), the first 10 BTC of block 9's coinbase.
+const FIRST_TRANSACTION_SATS: (u64, u64) = (450 * COIN_VALUE, 460 * COIN_VALUE);
+
+lazy_static! {
+    /// Satributes defined by sat ranges rather than by sat numbers (e.g. pizza sats), by name. Ranges are end exclusive
+    /// and sorted.
+    static ref SATRIBUTE_RANGES: RwLock<BTreeMap<String, Vec<(u64, u64)>>> =
+        RwLock::new(BTreeMap::new());
+}
+
+fn is_palindrome(ordinal_number: u64) -> bool {
+    let digits = ordinal_number.to_string();
+    digits.bytes().eq(digits.bytes().rev())
+}
+
+/// Reads the `{"<satribute>": [[start, end], ..]}` ranges file configured in `storage.satribute_ranges_path`, for the
+/// satributes that can't be derived from sat numbers. Ranges are end exclusive.
+pub fn parse_satribute_ranges(content: &str) -> Result<BTreeMap<String, Vec<(u64, u64)>>, String> {
+    let mut ranges: BTreeMap<String, Vec<(u64, u64)>> =
+        serde_json::from_str(content).map_err(|e| format!("invalid satribute ranges: {e}"))?;
+    for (satribute, satribute_ranges) in ranges.iter_mut() {
+        if satribute.is_empty()
+            || !satribute
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(format!(
+                "invalid satribute name {satribute}, expected lowercase letters, digits and underscores"
+            ));
+        }
+        if BUILTIN_SATRIBUTES.contains(&satribute.as_str()) {
+            return Err(format!("satribute {satribute} is built-in"));
+        }
+        if let Some((start, end)) = satribute_ranges.iter().find(|(start, end)| start >= end) {
+            return Err(format!("{satribute}: invalid range [{start}, {end})"));
+        }
+        satribute_ranges.sort();
+    }
+    Ok(ranges)
+}
+
+/// Loads the satribute ranges configured. Only performed once per process.
+pub fn load_satribute_ranges(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Some(ref path) = config.storage.satribute_ranges_path else {
+        return Ok(());
+    };
+    let mut satribute_ranges = SATRIBUTE_RANGES
+        .write()
+        .map_err(|e| format!("unable to load satribute ranges: {e}"))?;
+    if !satribute_ranges.is_empty() {
+        return Ok(());
+    }
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("unable to read {path}: {e}"))?;
+    *satribute_ranges = parse_satribute_ranges(&content)?;
+    try_info!(
+        ctx,
+        "Satribute ranges loaded from {path} ({})",
+        satribute_ranges
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(())
+}
+
+/// Names of the satributes of a sat: its rarity when above common, the satributes derived from its number, and the
+/// satributes which ranges were loaded covering it.
+pub fn get_satributes(ordinal_number: u64) -> Vec<String> {
+    let sat = Sat(ordinal_number);
+    let mut satributes = vec![];
+    let rarity = Rarity::from(sat);
+    if rarity != Rarity::Common {
+        satributes.push(rarity.name().to_string());
+    }
+    let height = sat.height().n();
+    if height < VINTAGE_MAX_HEIGHT {
+        satributes.push("vintage".to_string());
+    }
+    if height == 9 {
+        satributes.push("block_9".to_string());
+    }
+    if height == 78 {
+        satributes.push("block_78".to_string());
+    }
+    if is_palindrome(ordinal_number) {
+        satributes.push("palindrome".to_string());
+    }
+    if ordinal_number % COIN_VALUE == 0 {
+        satributes.push("alpha".to_string());
+    }
+    if ordinal_number % COIN_VALUE == COIN_VALUE - 1 {
+        satributes.push("omega".to_string());
+    }
+    if (sat + 1).third() == 0 {
+        satributes.push("black".to_string());
+    }
+    if ordinal_number >= FIRST_TRANSACTION_SATS.0 && ordinal_number < FIRST_TRANSACTION_SATS.1 {
+        satributes.push("first_transaction".to_string());
+    }
+    if let Ok(satribute_ranges) = SATRIBUTE_RANGES.read() {
+        for (satribute, ranges) in satribute_ranges.iter() {
+            let i = ranges.partition_point(|(start, _)| *start <= ordinal_number);
+            if i > 0 && ordinal_number < ranges[i - 1].1 {
+                satributes.push(satribute.clone());
+            }
+        }
+    }
+    satributes
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{get_satributes, parse_satribute_ranges};
+
+    #[test_case(0 => vec!["mythic", "vintage", "palindrome", "alpha"]; "first sat")]
+    #[test_case(1 => vec!["vintage", "palindrome"]; "second sat")]
+    #[test_case(4_999_999_999 => vec!["vintage", "omega", "black"]; "last sat of the genesis block")]
+    #[test_case(45_000_000_000 => vec!["uncommon", "vintage", "block_9", "alpha", "first_transaction"]; "first sat of block 9")]
+    #[test_case(1_964_125_000_000_000 => vec!["uncommon", "alpha"]; "first sat of a block")]
+    #[test_case(1_964_125_000_000_123 => Vec::<String>::new(); "common sat")]
+    fn computes_satributes(ordinal_number: u64) -> Vec<String> {
+        get_satributes(ordinal_number)
+    }
+
+    #[test]
+    fn parses_satribute_ranges() {
+        let ranges = parse_satribute_ranges(r#"{"pizza": [[20, 30], [0, 10]]}"#).unwrap();
+        assert_eq!(ranges.get("pizza"), Some(&vec![(0, 10), (20, 30)]));
+        assert!(parse_satribute_ranges(r#"{"pizza": [[30, 20]]}"#).is_err());
+        assert!(parse_satribute_ranges(r#"{"Pizza": []}"#).is_err());
+        assert!(parse_satribute_ranges(r#"{"vintage": []}"#).is_err());
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_sdk::utils::Context;
+use rocksdb::{checkpoint::Checkpoint, WriteBatch, DB};
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection,
+};
+
+use crate::{
+    config::Config,
+    core::meta_protocols::{
+        brc20::db::get_default_brc20_db_file_path, indexer::get_default_metaprotocols_db_file_path,
+        runes::db::get_default_runes_db_file_path, sns::db::get_default_sns_db_file_path,
+    },
+    db::{
+        blocks::{
+            find_last_block_inserted, get_default_blocks_db_path, open_readonly_blocks_db,
+            open_readwrite_blocks_db, rocks_db_default_options,
+        },
+        ordinals::{
+            apply_sqlite_encryption_key, get_default_ordinals_db_file_path,
+            open_existing_readonly_db_snapshot,
+        },
+        sales::get_default_sales_db_file_path,
+        sat_ranges::get_default_sat_ranges_db_file_path,
+    },
+    service::observers::get_default_observers_db_file_path,
+    try_info, try_warn,
+};
+
+/// Guards against running two backups concurrently from the same process.
+static BACKUP_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Number of rocksdb entries written per batch when copying blocks from a read-only instance.
+const BLOCKS_COPY_BATCH_SIZE: usize = 1_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupReport {
+    pub destination: String,
+    pub files: Vec<String>,
+    pub last_block_inserted: u32,
+    pub duration_ms: u128,
+}
+
+/// Returns a timestamped directory inside the working dir to use when no explicit backup destination is given.
+pub fn get_default_backup_path(config: &Config) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut destination_path = config.expected_cache_path();
+    destination_path.push("backups");
+    destination_path.push(format!("{}", timestamp));
+    destination_path
+}
+
+/// Takes a consistent point-in-time copy of every Ordhook database into `destination`.
+///
+/// A read transaction is opened on every SQLite database first, and the blocks rocksdb is captured while they are held:
+/// blocks are stored before being indexed, so the copy of hord.rocksdb holds every block the copies of the SQLite
+/// databases refer to. Since all of our databases run in WAL mode, writers are not blocked and indexing can continue
+/// while the backup is running.
+///
+/// SQLite databases are then copied from their read transactions with SQLite's online backup API. The blocks rocksdb is
+/// copied with a native checkpoint when we are able to acquire a read/write handle (i.e. no other process is indexing).
+/// Otherwise, a read-only instance is opened, which gives us a stable view of the db, and its content is copied into a
+/// fresh rocksdb at the destination.
+pub fn backup_all_dbs(
+    config: &Config,
+    destination: &PathBuf,
+    ctx: &Context,
+) -> Result<BackupReport, String> {
+    if BACKUP_IN_PROGRESS
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err("a backup is already in progress".to_string());
+    }
+    let result = perform_backup(config, destination, ctx);
+    BACKUP_IN_PROGRESS.store(false, Ordering::SeqCst);
+    result
+}
+
+pub fn is_backup_in_progress() -> bool {
+    BACKUP_IN_PROGRESS.load(Ordering::SeqCst)
+}
+
+fn perform_backup(
+    config: &Config,
+    destination: &PathBuf,
+    ctx: &Context,
+) -> Result<BackupReport, String> {
+    let start = SystemTime::now();
+    if destination.exists() {
+        let mut entries = std::fs::read_dir(destination)
+            .map_err(|e| format!("unable to read {}: {}", destination.display(), e))?;
+        if entries.next().is_some() {
+            return Err(format!(
+                "backup destination {} is not empty",
+                destination.display()
+            ));
+        }
+    }
+    std::fs::create_dir_all(destination)
+        .map_err(|e| format!("unable to create {}: {}", destination.display(), e))?;
+    try_info!(ctx, "Starting backup to {}", destination.display());
+
+    let base_dir = config.expected_sqlite_path();
+    let mut sqlite_db_paths = vec![get_default_ordinals_db_file_path(&base_dir)];
+    if config.meta_protocols.brc20 {
+        sqlite_db_paths.push(get_default_brc20_db_file_path(&base_dir));
+    }
+    if config.meta_protocols.sns {
+        sqlite_db_paths.push(get_default_sns_db_file_path(&base_dir));
+    }
+    if config.meta_protocols.runes {
+        sqlite_db_paths.push(get_default_runes_db_file_path(&base_dir));
+    }
+    if config.storage.index_scope.tracks_sat_ranges() {
+        sqlite_db_paths.push(get_default_sat_ranges_db_file_path(&base_dir));
+    }
+    if config.sales_analytics.is_some() {
+        sqlite_db_paths.push(get_default_sales_db_file_path(&base_dir));
+    }
+    let metaprotocols_db_path = get_default_metaprotocols_db_file_path(&base_dir);
+    if metaprotocols_db_path.exists() {
+        sqlite_db_paths.push(metaprotocols_db_path);
+    }
+    let observers_db_path = get_default_observers_db_file_path(config);
+    if observers_db_path.exists() {
+        sqlite_db_paths.push(observers_db_path);
+    }
+
+    let mut sqlite_snapshots = vec![];
+    for db_path in sqlite_db_paths.into_iter() {
+        let snapshot = open_sqlite_snapshot(&db_path, ctx)?;
+        sqlite_snapshots.push((db_path, snapshot));
+    }
+
+    // The rocksdb tip is recorded while the SQLite read transactions are held.
+    let (blocks_db_destination, last_block_inserted) = backup_blocks_db(config, destination, ctx)?;
+
+    let mut files = vec![];
+    for (db_path, snapshot) in sqlite_snapshots.iter() {
+        files.push(backup_sqlite_db(db_path, snapshot, destination, ctx)?);
+    }
+    drop(sqlite_snapshots);
+    files.push(blocks_db_destination);
+
+    let duration_ms = start.elapsed().map(|d| d.as_millis()).unwrap_or(0);
+    try_info!(
+        ctx,
+        "Backup to {} completed in {}ms (tip: #{})",
+        destination.display(),
+        duration_ms,
+        last_block_inserted
+    );
+    Ok(BackupReport {
+        destination: destination.display().to_string(),
+        files,
+        last_block_inserted,
+        duration_ms,
+    })
+}
+
+/// Opens a read-only connection to a SQLite database, and starts its read transaction: every later read of the
+/// connection, including the backup, sees the database as it was at this point.
+fn open_sqlite_snapshot(db_path: &PathBuf, ctx: &Context) -> Result<Connection, String> {
+    let conn = open_existing_readonly_db_snapshot(db_path, ctx)?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|e| format!("unable to read {}: {}", db_path.display(), e))?;
+    Ok(conn)
+}
+
+fn backup_sqlite_db(
+    db_path: &PathBuf,
+    src_conn: &Connection,
+    destination: &PathBuf,
+    ctx: &Context,
+) -> Result<String, String> {
+    let Some(file_name) = db_path.file_name() else {
+        return Err(format!("invalid db path {}", db_path.display()));
+    };
+    let mut destination_path = destination.clone();
+    destination_path.push(file_name);
+
+    try_info!(
+        ctx,
+        "Backing up {} to {}",
+        db_path.display(),
+        destination_path.display()
+    );
+    let mut dst_conn = Connection::open(&destination_path)
+        .map_err(|e| format!("unable to create {}: {}", destination_path.display(), e))?;
+    apply_sqlite_encryption_key(&dst_conn)?;
+    {
+        let backup = Backup::new(src_conn, &mut dst_conn)
+            .map_err(|e| format!("unable to start backup of {}: {}", db_path.display(), e))?;
+        // The source connection holds its read transaction for the whole copy, which therefore can't be restarted by
+        // the indexer writing to the source db.
+        loop {
+            match backup.step(-1) {
+                Ok(StepResult::Done) => break,
+                Ok(StepResult::More) => continue,
+                Ok(_) => {
+                    try_warn!(ctx, "{} is busy, retrying backup in 1s", db_path.display());
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+                Err(e) => return Err(format!("unable to backup {}: {}", db_path.display(), e)),
+            }
+        }
+    }
+    Ok(destination_path.display().to_string())
+}
+
+fn backup_blocks_db(
+    config: &Config,
+    destination: &PathBuf,
+    ctx: &Context,
+) -> Result<(String, u32), String> {
+    let destination_path = get_default_blocks_db_path(destination);
+    match open_readwrite_blocks_db(config, ctx) {
+        Ok(blocks_db_rw) => {
+            try_info!(
+                ctx,
+                "Creating hord.rocksdb checkpoint in {}",
+                destination_path.display()
+            );
+            let checkpoint = Checkpoint::new(&blocks_db_rw)
+                .map_err(|e| format!("unable to create hord.rocksdb checkpoint: {}", e))?;
+            checkpoint
+                .create_checkpoint(&destination_path)
+                .map_err(|e| format!("unable to create hord.rocksdb checkpoint: {}", e))?;
+            let last_block_inserted = find_last_block_inserted(&blocks_db_rw);
+            Ok((destination_path.display().to_string(), last_block_inserted))
+        }
+        Err(e) => {
+            try_warn!(
+                ctx,
+                "hord.rocksdb is in use ({}), copying from a read-only instance",
+                e
+            );
+            let blocks_db = open_readonly_blocks_db(config, ctx)?;
+            let last_block_inserted = copy_blocks_db(&blocks_db, config, &destination_path, ctx)?;
+            Ok((destination_path.display().to_string(), last_block_inserted))
+        }
+    }
+}
+
+fn copy_blocks_db(
+    blocks_db: &DB,
+    config: &Config,
+    destination_path: &PathBuf,
+    ctx: &Context,
+) -> Result<u32, String> {
+    let opts = rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
+    let blocks_db_copy = DB::open(&opts, destination_path)
+        .map_err(|e| format!("unable to create {}: {}", destination_path.display(), e))?;
+
+    let snapshot = blocks_db.snapshot();
+    let mut batch = WriteBatch::default();
+    let mut copied = 0;
+    for entry in snapshot.iterator(rocksdb::IteratorMode::Start) {
+        let (key, value) = entry.map_err(|e| format!("unable to read hord.rocksdb: {}", e))?;
+        batch.put(key, value);
+        copied += 1;
+        if batch.len() >= BLOCKS_COPY_BATCH_SIZE {
+            blocks_db_copy
+                .write(batch)
+                .map_err(|e| format!("unable to write {}: {}", destination_path.display(), e))?;
+            batch = WriteBatch::default();
+            try_info!(ctx, "Backup: {} hord.rocksdb entries copied", copied);
+        }
+    }
+    blocks_db_copy
+        .write(batch)
+        .map_err(|e| format!("unable to write {}: {}", destination_path.display(), e))?;
+    blocks_db_copy
+        .flush()
+        .map_err(|e| format!("unable to flush {}: {}", destination_path.display(), e))?;
+    Ok(find_last_block_inserted(&blocks_db_copy))
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::Config,
+        db::{
+            blocks::{find_last_block_inserted, insert_entry_in_blocks, open_blocks_db_with_retry},
+            drop_all_dbs, initialize_sqlite_dbs,
+            ordinals::{get_default_ordinals_db_file_path, open_existing_readonly_db},
+        },
+    };
+
+    use super::backup_all_dbs;
+
+    #[test]
+    fn backs_up_all_dbs() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/backup".to_string();
+        drop_all_dbs(&config);
+        let _ = initialize_sqlite_dbs(&config, &ctx);
+        {
+            let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
+            insert_entry_in_blocks(800000, &[1, 2, 3], true, &blocks_db, &ctx);
+        }
+
+        let mut destination = config.expected_cache_path();
+        destination.push("snapshot");
+        let report = backup_all_dbs(&config, &destination, &ctx).unwrap();
+        assert_eq!(report.last_block_inserted, 800000);
+        assert!(get_default_ordinals_db_file_path(&destination).exists());
+        assert!(
+            open_existing_readonly_db(&get_default_ordinals_db_file_path(&destination), &ctx)
+                .is_ok()
+        );
+
+        let mut backup_config = config.clone();
+        backup_config.storage.working_dir = destination.display().to_string();
+        let blocks_db = open_blocks_db_with_retry(false, &backup_config, &ctx);
+        assert_eq!(find_last_block_inserted(&blocks_db), 800000);
+
+        assert!(backup_all_dbs(&config, &destination, &ctx).is_err());
+        drop_all_dbs(&config);
+    }
+}
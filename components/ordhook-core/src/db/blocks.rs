@@ -1,18 +1,154 @@
-use std::{path::PathBuf, thread::sleep, time::Duration};
+use std::{ops::Deref, path::PathBuf, sync::RwLock, thread::sleep, time::Duration};
 
-use chainhook_sdk::utils::Context;
-use rand::{thread_rng, Rng};
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash},
+    utils::Context,
+};
+use rand::{thread_rng, Rng, RngCore};
 use rocksdb::{DBPinnableSlice, Options, DB};
 
-use crate::{config::Config, try_error, try_warn};
+use crate::{
+    config::Config,
+    db::cold_blocks::{find_cold_block_bytes, remove_cold_block, rewind_cold_storage_cursor},
+    error::OrdhookError,
+    try_error, try_warn,
+};
 
-fn get_default_blocks_db_path(base_dir: &PathBuf) -> PathBuf {
+/// Prefix of the blocks stored encrypted. Blocks stored in clear start with their transaction count followed by the
+/// zero input count of their coinbase, which this prefix can't match: blocks written before encryption got enabled
+/// stay readable.
+const ENCRYPTED_BLOCK_PREFIX: &[u8; 4] = b"OHE1";
+const ENCRYPTED_BLOCK_NONCE_SIZE: usize = 12;
+
+lazy_static! {
+    /// Cipher encrypting the blocks stored in hord.rocksdb and in cold storage, derived from the storage encryption key.
+    static ref BLOCKS_CIPHER: RwLock<Option<Aes256Gcm>> = RwLock::new(None);
+}
+
+/// Bytes of a block, read in place from hord.rocksdb, decrypted, or read back from cold storage.
+pub enum BlockBytes<'a> {
+    Pinned(DBPinnableSlice<'a>),
+    Decrypted(Vec<u8>),
+    Cold(Vec<u8>),
+}
+
+impl<'a> Deref for BlockBytes<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            BlockBytes::Pinned(bytes) => bytes.as_ref(),
+            BlockBytes::Decrypted(bytes) | BlockBytes::Cold(bytes) => bytes.as_slice(),
+        }
+    }
+}
+
+impl<'a> AsRef<[u8]> for BlockBytes<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+fn derive_blocks_cipher(key: &str) -> Aes256Gcm {
+    let key = sha256::Hash::hash(format!("ordhook:blocks:{key}").as_bytes());
+    Aes256Gcm::new(key.as_byte_array().into())
+}
+
+/// Sets the key the blocks of hord.rocksdb and cold storage get encrypted with (AES-256-GCM, keyed with the sha256 of
+/// the storage encryption key), for every blocks db opened by this process.
+pub fn set_blocks_encryption_key(key: Option<&str>) -> Result<(), OrdhookError> {
+    let mut current_cipher = BLOCKS_CIPHER
+        .write()
+        .map_err(|e| OrdhookError::Db(format!("unable to set encryption key: {}", e)))?;
+    *current_cipher = key.map(derive_blocks_cipher);
+    Ok(())
+}
+
+/// Encrypts the bytes of a block, prefixed with `ENCRYPTED_BLOCK_PREFIX` and the random nonce used.
+fn encrypt_block_bytes_with(cipher: &Aes256Gcm, block_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; ENCRYPTED_BLOCK_NONCE_SIZE];
+    thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), block_bytes)
+        .map_err(|e| format!("unable to encrypt block: {e}"))?;
+    let mut encrypted =
+        Vec::with_capacity(ENCRYPTED_BLOCK_PREFIX.len() + nonce.len() + ciphertext.len());
+    encrypted.extend_from_slice(ENCRYPTED_BLOCK_PREFIX);
+    encrypted.extend_from_slice(&nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+/// Decrypts the bytes of a block if they were stored encrypted. Blocks stored in clear are returned as is.
+fn decrypt_block_bytes_with<'a>(
+    cipher: Option<&Aes256Gcm>,
+    block_bytes: BlockBytes<'a>,
+) -> Result<BlockBytes<'a>, String> {
+    let Some(encrypted) = block_bytes.strip_prefix(ENCRYPTED_BLOCK_PREFIX) else {
+        return Ok(block_bytes);
+    };
+    let Some(cipher) = cipher else {
+        return Err("block is encrypted, and no storage encryption key is set".into());
+    };
+    if encrypted.len() < ENCRYPTED_BLOCK_NONCE_SIZE {
+        return Err("truncated encrypted block".into());
+    }
+    let (nonce, ciphertext) = encrypted.split_at(ENCRYPTED_BLOCK_NONCE_SIZE);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map(BlockBytes::Decrypted)
+        .map_err(|_| "unable to decrypt block, the storage encryption key does not match".into())
+}
+
+/// Encrypts the bytes of a block with the storage encryption key, if set.
+fn encrypt_block_bytes(block_bytes: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let cipher = BLOCKS_CIPHER
+        .read()
+        .map_err(|e| format!("unable to read encryption key: {e}"))?;
+    cipher
+        .as_ref()
+        .map(|cipher| encrypt_block_bytes_with(cipher, block_bytes))
+        .transpose()
+}
+
+/// Decrypts the bytes of a block read from hord.rocksdb or cold storage with the storage encryption key.
+pub fn decrypt_block_bytes<'a>(block_bytes: BlockBytes<'a>) -> Result<BlockBytes<'a>, String> {
+    let cipher = BLOCKS_CIPHER
+        .read()
+        .map_err(|e| format!("unable to read encryption key: {e}"))?;
+    decrypt_block_bytes_with(cipher.as_ref(), block_bytes)
+}
+
+/// Whether bytes read from storage were encrypted with the storage encryption key.
+pub fn is_encrypted_bytes(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENCRYPTED_BLOCK_PREFIX)
+}
+
+/// Encrypts the files ordhook stores next to its databases (content cache entries, previews) like blocks, when a
+/// storage encryption key is set. They are returned as is otherwise.
+pub fn encrypt_stored_file_bytes(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    Ok(encrypt_block_bytes(&bytes)?.unwrap_or(bytes))
+}
+
+/// Decrypts the files encrypted with `encrypt_stored_file_bytes`. Files written in clear are returned as is.
+pub fn decrypt_stored_file_bytes(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    match decrypt_block_bytes(BlockBytes::Cold(bytes))? {
+        BlockBytes::Decrypted(bytes) | BlockBytes::Cold(bytes) => Ok(bytes),
+        BlockBytes::Pinned(bytes) => Ok(bytes.to_vec()),
+    }
+}
+
+pub fn get_default_blocks_db_path(base_dir: &PathBuf) -> PathBuf {
     let mut destination_path = base_dir.clone();
     destination_path.push("hord.rocksdb");
     destination_path
 }
 
-fn rocks_db_default_options(ulimit: usize, _memory_available: usize) -> Options {
+pub fn rocks_db_default_options(ulimit: usize, _memory_available: usize) -> Options {
     let mut opts = Options::default();
     // Per rocksdb's documentation:
     // If cache_index_and_filter_blocks is false (which is default),
@@ -70,22 +206,30 @@ pub fn open_blocks_db_with_retry(readwrite: bool, config: &Config, ctx: &Context
     blocks_db
 }
 
-pub fn open_readonly_blocks_db(config: &Config, _ctx: &Context) -> Result<DB, String> {
-    let path = get_default_blocks_db_path(&config.expected_cache_path());
+pub fn open_readonly_blocks_db(config: &Config, _ctx: &Context) -> Result<DB, OrdhookError> {
+    let path = get_default_blocks_db_path(&config.expected_blocks_path());
     let mut opts =
         rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
     opts.set_disable_auto_compactions(true);
     opts.set_max_background_jobs(0);
     let db = DB::open_for_read_only(&opts, path, false)
-        .map_err(|e| format!("unable to read hord.rocksdb: {}", e.to_string()))?;
+        .map_err(|e| OrdhookError::Db(format!("unable to read hord.rocksdb: {}", e.to_string())))?;
     Ok(db)
 }
 
-fn open_readwrite_blocks_db(config: &Config, _ctx: &Context) -> Result<DB, String> {
-    let path = get_default_blocks_db_path(&config.expected_cache_path());
+pub fn open_readwrite_blocks_db(config: &Config, _ctx: &Context) -> Result<DB, OrdhookError> {
+    let blocks_dir = config.expected_blocks_path();
+    // Unlike the working dir, a separate blocks dir isn't created along with the SQLite databases.
+    std::fs::create_dir_all(&blocks_dir)
+        .map_err(|e| OrdhookError::Db(format!("unable to create {}: {e}", blocks_dir.display())))?;
+    let path = get_default_blocks_db_path(&blocks_dir);
     let opts = rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
-    let db = DB::open(&opts, path)
-        .map_err(|e| format!("unable to read-write hord.rocksdb: {}", e.to_string()))?;
+    let db = DB::open(&opts, path).map_err(|e| {
+        OrdhookError::Db(format!(
+            "unable to read-write hord.rocksdb: {}",
+            e.to_string()
+        ))
+    })?;
     Ok(db)
 }
 
@@ -97,6 +241,15 @@ pub fn insert_entry_in_blocks(
     ctx: &Context,
 ) {
     let block_height_bytes = block_height.to_be_bytes();
+    let encrypted_block_bytes = match encrypt_block_bytes(block_bytes) {
+        Ok(encrypted_block_bytes) => encrypted_block_bytes,
+        Err(e) => {
+            // Blocks are never written in clear once encryption is enabled.
+            try_error!(ctx, "Unable to insert block {block_height}: {e}");
+            return;
+        }
+    };
+    let block_bytes = encrypted_block_bytes.as_deref().unwrap_or(block_bytes);
     let mut retries = 0;
     loop {
         let res = blocks_db_rw.put(&block_height_bytes, block_bytes);
@@ -134,8 +287,9 @@ pub fn find_pinned_block_bytes_at_block_height<'a>(
     block_height: u32,
     retry: u8,
     blocks_db: &'a DB,
+    config: &Config,
     ctx: &Context,
-) -> Option<DBPinnableSlice<'a>> {
+) -> Option<BlockBytes<'a>> {
     let mut attempt = 1;
     // let mut read_options = rocksdb::ReadOptions::default();
     // read_options.fill_cache(true);
@@ -143,9 +297,17 @@ pub fn find_pinned_block_bytes_at_block_height<'a>(
     let mut backoff: f64 = 1.0;
     let mut rng = thread_rng();
     loop {
-        match blocks_db.get_pinned(block_height.to_be_bytes()) {
-            Ok(Some(res)) => return Some(res),
-            _ => {
+        let block_bytes = match blocks_db.get_pinned(block_height.to_be_bytes()) {
+            Ok(Some(res)) => Some(BlockBytes::Pinned(res)),
+            _ => find_cold_block_bytes(block_height, config, ctx).map(BlockBytes::Cold),
+        };
+        match block_bytes.map(decrypt_block_bytes) {
+            Some(Ok(block_bytes)) => return Some(block_bytes),
+            Some(Err(e)) => {
+                try_error!(ctx, "Unable to read block #{block_height}: {e}");
+                return None;
+            }
+            None => {
                 attempt += 1;
                 backoff = 2.0 * backoff + (backoff * rng.gen_range(0.0..1.0));
                 let duration = std::time::Duration::from_millis((backoff * 1_000.0) as u64);
@@ -168,6 +330,7 @@ pub fn find_block_bytes_at_block_height<'a>(
     block_height: u32,
     retry: u8,
     blocks_db: &DB,
+    config: &Config,
     ctx: &Context,
 ) -> Option<Vec<u8>> {
     let mut attempt = 1;
@@ -178,9 +341,20 @@ pub fn find_block_bytes_at_block_height<'a>(
     let mut rng = thread_rng();
 
     loop {
-        match blocks_db.get(block_height.to_be_bytes()) {
-            Ok(Some(res)) => return Some(res),
-            _ => {
+        let block_bytes = match blocks_db.get(block_height.to_be_bytes()) {
+            Ok(Some(res)) => Some(res),
+            _ => find_cold_block_bytes(block_height, config, ctx),
+        };
+        match block_bytes.map(|block_bytes| decrypt_block_bytes(BlockBytes::Cold(block_bytes))) {
+            Some(Ok(BlockBytes::Decrypted(block_bytes) | BlockBytes::Cold(block_bytes))) => {
+                return Some(block_bytes)
+            }
+            Some(Ok(BlockBytes::Pinned(block_bytes))) => return Some(block_bytes.to_vec()),
+            Some(Err(e)) => {
+                try_error!(ctx, "Unable to read block #{block_height}: {e}");
+                return None;
+            }
+            None => {
                 attempt += 1;
                 backoff = 2.0 * backoff + (backoff * rng.gen_range(0.0..1.0));
                 let duration = std::time::Duration::from_millis((backoff * 1_000.0) as u64);
@@ -204,31 +378,45 @@ pub fn run_compaction(blocks_db_rw: &DB, lim: u32) {
     let _ = blocks_db_rw.compact_range(Some(&gen), Some(&lim.to_be_bytes()));
 }
 
-pub fn find_missing_blocks(blocks_db: &DB, start: u32, end: u32, ctx: &Context) -> Vec<u32> {
+pub fn find_missing_blocks(
+    blocks_db: &DB,
+    start: u32,
+    end: u32,
+    config: &Config,
+    ctx: &Context,
+) -> Vec<u32> {
     let mut missing_blocks = vec![];
     for i in start..=end {
-        if find_pinned_block_bytes_at_block_height(i as u32, 0, &blocks_db, ctx).is_none() {
+        if find_pinned_block_bytes_at_block_height(i as u32, 0, &blocks_db, config, ctx).is_none() {
             missing_blocks.push(i);
         }
     }
     missing_blocks
 }
 
-pub fn remove_entry_from_blocks(block_height: u32, blocks_db_rw: &DB, ctx: &Context) {
+pub fn remove_entry_from_blocks(
+    block_height: u32,
+    blocks_db_rw: &DB,
+    config: &Config,
+    ctx: &Context,
+) {
     if let Err(e) = blocks_db_rw.delete(block_height.to_be_bytes()) {
         try_error!(ctx, "{}", e.to_string());
     }
+    remove_cold_block(block_height, config, ctx);
 }
 
 pub fn delete_blocks_in_block_range(
     start_block: u32,
     end_block: u32,
     blocks_db_rw: &DB,
+    config: &Config,
     ctx: &Context,
 ) {
     for block_height in start_block..=end_block {
-        remove_entry_from_blocks(block_height, blocks_db_rw, ctx);
+        remove_entry_from_blocks(block_height, blocks_db_rw, config, ctx);
     }
+    rewind_cold_storage_cursor(start_block, blocks_db_rw, ctx);
     let start_block_bytes = (start_block - 1).to_be_bytes();
     blocks_db_rw
         .put(b"metadata::last_insert", start_block_bytes)
@@ -264,3 +452,34 @@ pub fn insert_standardized_block(
         try_error!(ctx, "{}", e.to_string());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decrypt_block_bytes_with, derive_blocks_cipher, encrypt_block_bytes_with, BlockBytes,
+    };
+
+    #[test]
+    fn encrypts_block_bytes() {
+        let cipher = derive_blocks_cipher("storage key");
+        let block_bytes = vec![0, 2, 0, 0, 0, 1, 0, 3];
+        let encrypted = encrypt_block_bytes_with(&cipher, &block_bytes).unwrap();
+        assert!(encrypted.starts_with(b"OHE1"));
+        assert_ne!(&encrypted[16..], &block_bytes[..]);
+        let decrypted =
+            decrypt_block_bytes_with(Some(&cipher), BlockBytes::Cold(encrypted.clone())).unwrap();
+        assert_eq!(&decrypted[..], &block_bytes[..]);
+
+        // Blocks stored before encryption got enabled are read as is.
+        let clear =
+            decrypt_block_bytes_with(Some(&cipher), BlockBytes::Cold(block_bytes.clone())).unwrap();
+        assert_eq!(&clear[..], &block_bytes[..]);
+
+        let other_cipher = derive_blocks_cipher("other key");
+        assert!(
+            decrypt_block_bytes_with(Some(&other_cipher), BlockBytes::Cold(encrypted.clone()))
+                .is_err()
+        );
+        assert!(decrypt_block_bytes_with(None, BlockBytes::Cold(encrypted)).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::Config,
+    db::{
+        blocks::{
+            find_block_bytes_at_block_height, find_last_block_inserted, open_readonly_blocks_db,
+        },
+        cursor::{BlockBytesCursor, TXID_LEN},
+        ordinals::{
+            find_block_events_hash, find_last_block_events_hash,
+            find_latest_inscription_block_height, find_latest_reorg_events,
+            find_quarantined_blocks, open_ordinals_db, QuarantinedBlock, ReorgEvent,
+        },
+    },
+    utils::bitcoind::{bitcoind_get_block_summary, bitcoind_try_get_block_height},
+};
+
+/// Number of recent re-org events included in the report.
+const CHAIN_STATUS_REORG_EVENTS: usize = 10;
+
+/// Block of a local store that does not match the block found at the same height on bitcoind's main chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockDivergence {
+    pub block_height: u64,
+    /// `hord.rocksdb` (compared on coinbase txid prefixes) or `hord.sqlite` (compared on block hashes).
+    pub store: String,
+    pub expected: String,
+    pub found: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStatusReport {
+    pub bitcoind_tip: u64,
+    pub blocks_db_tip: u64,
+    pub ordhook_tip: Option<u64>,
+    pub ordhook_tip_hash: Option<String>,
+    pub reorg_events: Vec<ReorgEvent>,
+    /// Blocks skipped after their processing kept panicking, and not indexed since.
+    pub skipped_blocks: Vec<QuarantinedBlock>,
+    pub divergences: Vec<BlockDivergence>,
+    pub remediation: Vec<String>,
+}
+
+/// Compares the last `depth` blocks of the local stores against bitcoind's main chain. Meant to be run from the
+/// CLI, the databases are opened read-only and can be in use by a running service.
+pub fn get_chain_status(
+    config: &Config,
+    depth: u64,
+    ctx: &Context,
+) -> Result<ChainStatusReport, String> {
+    let bitcoind_tip = bitcoind_try_get_block_height(config)?;
+    let blocks_db = open_readonly_blocks_db(config, ctx)?;
+    let blocks_db_tip = find_last_block_inserted(&blocks_db) as u64;
+    let ordinals_db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
+    let (ordhook_tip, ordhook_tip_hash) = match find_last_block_events_hash(&ordinals_db_conn, ctx)
+    {
+        Some(row) => (Some(row.block_height), Some(row.block_hash)),
+        None => (
+            find_latest_inscription_block_height(&ordinals_db_conn, ctx)?,
+            None,
+        ),
+    };
+    let reorg_events = find_latest_reorg_events(CHAIN_STATUS_REORG_EVENTS, &ordinals_db_conn, ctx);
+    let skipped_blocks = find_quarantined_blocks(&ordinals_db_conn, ctx)
+        .into_iter()
+        .filter(|block| {
+            block.skipped
+                && find_block_events_hash(block.block_height, &ordinals_db_conn, ctx).is_none()
+        })
+        .collect();
+
+    let mut divergences = vec![];
+    let top = blocks_db_tip
+        .max(ordhook_tip.unwrap_or(0))
+        .min(bitcoind_tip);
+    for block_height in (top + 1).saturating_sub(depth)..=top {
+        let (block_hash, coinbase_txid) = bitcoind_get_block_summary(block_height, config)?;
+        if block_height <= blocks_db_tip {
+            if let Some(block_bytes) =
+                find_block_bytes_at_block_height(block_height as u32, 3, &blocks_db, config, ctx)
+            {
+                let found = hex::encode(BlockBytesCursor::new(&block_bytes).get_coinbase_txid());
+                let expected = coinbase_txid.chars().take(TXID_LEN * 2).collect::<String>();
+                if found != expected {
+                    divergences.push(BlockDivergence {
+                        block_height,
+                        store: "hord.rocksdb".into(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+        if let Some(row) = find_block_events_hash(block_height, &ordinals_db_conn, ctx) {
+            let found = row.block_hash.trim_start_matches("0x").to_string();
+            if found != block_hash {
+                divergences.push(BlockDivergence {
+                    block_height,
+                    store: "hord.sqlite".into(),
+                    expected: block_hash,
+                    found,
+                });
+            }
+        }
+    }
+
+    let mut report = ChainStatusReport {
+        bitcoind_tip,
+        blocks_db_tip,
+        ordhook_tip,
+        ordhook_tip_hash,
+        reorg_events,
+        skipped_blocks,
+        divergences,
+        remediation: vec![],
+    };
+    report.remediation = suggest_chain_remediation(&report);
+    Ok(report)
+}
+
+/// Suggested operator actions for the state described by the report. Empty when ordhook agrees with bitcoind.
+pub fn suggest_chain_remediation(report: &ChainStatusReport) -> Vec<String> {
+    let mut remediation = vec![];
+    let local_tip = report.blocks_db_tip.max(report.ordhook_tip.unwrap_or(0));
+    if let Some(first_divergence) = report.divergences.iter().map(|d| d.block_height).min() {
+        remediation.push(format!(
+            "Blocks #{first_divergence} to #{local_tip} are not on bitcoind's main chain: stop the service, run `ordhook db drop {first_divergence} {local_tip}` then `ordhook db sync`"
+        ));
+    }
+    if local_tip > report.bitcoind_tip {
+        remediation.push(format!(
+            "ordhook is ahead of bitcoind (#{local_tip} vs #{}): bitcoind is probably reindexing or was switched to another data directory, wait for it to catch up before resuming indexing",
+            report.bitcoind_tip
+        ));
+    }
+    if let Some(first_skipped) = report.skipped_blocks.iter().map(|b| b.block_height).min() {
+        remediation.push(format!(
+            "{} block(s) were skipped after their processing kept panicking, starting at #{first_skipped}: once the cause is fixed, stop the service, run `ordhook db drop {first_skipped} {local_tip}` then `ordhook db sync`",
+            report.skipped_blocks.len()
+        ));
+    }
+    if let Some(ordhook_tip) = report.ordhook_tip {
+        if report.blocks_db_tip < ordhook_tip {
+            remediation.push(format!(
+                "hord.rocksdb is behind hord.sqlite: run `ordhook db repair blocks --interval {}:{ordhook_tip}`",
+                report.blocks_db_tip + 1
+            ));
+        }
+    }
+    remediation
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::ordinals::QuarantinedBlock;
+
+    use super::{suggest_chain_remediation, BlockDivergence, ChainStatusReport};
+
+    fn report(bitcoind_tip: u64, blocks_db_tip: u64, ordhook_tip: u64) -> ChainStatusReport {
+        ChainStatusReport {
+            bitcoind_tip,
+            blocks_db_tip,
+            ordhook_tip: Some(ordhook_tip),
+            ordhook_tip_hash: None,
+            reorg_events: vec![],
+            skipped_blocks: vec![],
+            divergences: vec![],
+            remediation: vec![],
+        }
+    }
+
+    #[test]
+    fn suggests_remediation() {
+        assert!(suggest_chain_remediation(&report(800_002, 800_000, 800_000)).is_empty());
+
+        let mut diverging = report(800_002, 800_000, 800_000);
+        diverging.divergences.push(BlockDivergence {
+            block_height: 799_999,
+            store: "hord.sqlite".into(),
+            expected: "aa".into(),
+            found: "bb".into(),
+        });
+        let remediation = suggest_chain_remediation(&diverging);
+        assert_eq!(remediation.len(), 1);
+        assert!(remediation[0].contains("`ordhook db drop 799999 800000`"));
+
+        let remediation = suggest_chain_remediation(&report(799_990, 799_995, 800_000));
+        assert_eq!(remediation.len(), 2);
+        assert!(remediation[1].contains("--interval 799996:800000"));
+
+        let mut skipping = report(800_002, 800_000, 800_000);
+        skipping.skipped_blocks.push(QuarantinedBlock {
+            block_height: 799_998,
+            block_hash: "0xaa".into(),
+            error: "index out of bounds".into(),
+            attempts: 4,
+            skipped: true,
+            last_failed_at: 0,
+        });
+        let remediation = suggest_chain_remediation(&skipping);
+        assert_eq!(remediation.len(), 1);
+        assert!(remediation[0].contains("`ordhook db drop 799998 800000`"));
+    }
+}
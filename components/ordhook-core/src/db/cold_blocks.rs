@@ -0,0 +1,298 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chainhook_sdk::utils::Context;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rocksdb::{Direction, IteratorMode, DB};
+
+use crate::{
+    config::Config,
+    db::blocks::find_last_block_inserted,
+    try_info, try_warn,
+    utils::{get_unique_tmp_path, sync_parent_dir},
+};
+
+const COLD_BLOCK_EXTENSION: &str = "blk.gz";
+
+/// Cold blocks are grouped by directories of this many heights, to keep listings short.
+const COLD_BLOCKS_PER_DIR: u32 = 1_000;
+
+/// Height of the first block of hord.rocksdb not moved to cold storage yet, for the moves to skip the deleted entries.
+const COLD_STORAGE_CURSOR_KEY: &[u8] = b"metadata::cold_until";
+
+/// Blocks moved after each write to hord.rocksdb, so that indexing is only held back briefly while a backlog of old
+/// blocks gets moved.
+pub const COLD_BLOCKS_BATCH_SIZE: usize = 100;
+
+/// Blocks moved per batch by the maintenance, which moves batches until the backlog is cleared.
+pub const COLD_BLOCKS_MAINTENANCE_BATCH_SIZE: usize = 10_000;
+
+/// Serializes the moves of the indexer and of the maintenance, which would otherwise both move the blocks from the
+/// same cursor.
+static COLD_STORAGE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn get_cold_block_path(cold_storage_path: &Path, block_height: u32) -> PathBuf {
+    cold_storage_path
+        .join(format!("{:06}", block_height / COLD_BLOCKS_PER_DIR))
+        .join(format!("{block_height}.{COLD_BLOCK_EXTENSION}"))
+}
+
+fn write_cold_block(
+    cold_storage_path: &Path,
+    block_height: u32,
+    block_bytes: &[u8],
+) -> Result<(), String> {
+    let path = get_cold_block_path(cold_storage_path, block_height);
+    if let Some(dir) = path.parent() {
+        if !dir.exists() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("unable to create {}: {e}", dir.display()))?;
+            sync_parent_dir(dir)?;
+        }
+    }
+    // Written to a temporary file renamed once synced, since the block is deleted from hord.rocksdb right after.
+    let tmp_path = get_unique_tmp_path(&path);
+    let file = File::create(&tmp_path)
+        .map_err(|e| format!("unable to create {}: {e}", tmp_path.display()))?;
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    encoder
+        .write_all(block_bytes)
+        .map_err(|e| format!("unable to write {}: {e}", tmp_path.display()))?;
+    let file = encoder
+        .finish()
+        .map_err(|e| format!("unable to write {}: {e}", tmp_path.display()))?
+        .into_inner()
+        .map_err(|e| format!("unable to write {}: {e}", tmp_path.display()))?;
+    file.sync_all()
+        .map_err(|e| format!("unable to sync {}: {e}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("unable to rename {}: {e}", path.display()))?;
+    sync_parent_dir(&path)?;
+    // Read back before the block gets deleted from hord.rocksdb, the cold file being its only copy afterwards.
+    match read_cold_block(cold_storage_path, block_height)? {
+        Some(written_bytes) if written_bytes == block_bytes => Ok(()),
+        _ => Err(format!(
+            "{} does not match block #{block_height}",
+            path.display()
+        )),
+    }
+}
+
+pub fn read_cold_block(
+    cold_storage_path: &Path,
+    block_height: u32,
+) -> Result<Option<Vec<u8>>, String> {
+    let path = get_cold_block_path(cold_storage_path, block_height);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("unable to open {}: {e}", path.display())),
+    };
+    let mut block_bytes = vec![];
+    GzDecoder::new(file)
+        .read_to_end(&mut block_bytes)
+        .map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+    Ok(Some(block_bytes))
+}
+
+/// Reads a block moved out of hord.rocksdb, when cold storage is enabled.
+pub fn find_cold_block_bytes(block_height: u32, config: &Config, ctx: &Context) -> Option<Vec<u8>> {
+    let cold_storage_path = config.expected_cold_storage_path()?;
+    match read_cold_block(&cold_storage_path, block_height) {
+        Ok(block_bytes) => block_bytes,
+        Err(e) => {
+            try_warn!(ctx, "Cold storage: {e}");
+            None
+        }
+    }
+}
+
+pub fn remove_cold_block(block_height: u32, config: &Config, ctx: &Context) {
+    let Some(cold_storage_path) = config.expected_cold_storage_path() else {
+        return;
+    };
+    let path = get_cold_block_path(&cold_storage_path, block_height);
+    match fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => try_warn!(
+            ctx,
+            "Cold storage: unable to remove {}: {e}",
+            path.display()
+        ),
+    }
+}
+
+fn find_cold_storage_cursor(blocks_db: &DB) -> u32 {
+    match blocks_db.get(COLD_STORAGE_CURSOR_KEY) {
+        Ok(Some(bytes)) if bytes.len() == 4 => {
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+        _ => 0,
+    }
+}
+
+/// Makes the next moves start from `block_height` again, once the blocks from there were dropped to be indexed again.
+pub fn rewind_cold_storage_cursor(block_height: u32, blocks_db_rw: &DB, ctx: &Context) {
+    if find_cold_storage_cursor(blocks_db_rw) <= block_height {
+        return;
+    }
+    if let Err(e) = blocks_db_rw.put(COLD_STORAGE_CURSOR_KEY, block_height.to_be_bytes()) {
+        try_warn!(ctx, "Cold storage: unable to rewind cursor: {e}");
+    }
+}
+
+/// Moves up to `max_blocks` of the oldest blocks lagging the tip of hord.rocksdb by `older_than_blocks` or more to
+/// `cold_storage_path`. Returns the number of blocks moved.
+pub fn move_blocks_to_cold_storage(
+    cold_storage_path: &Path,
+    older_than_blocks: u64,
+    max_blocks: usize,
+    blocks_db_rw: &DB,
+) -> Result<usize, String> {
+    let _lock = COLD_STORAGE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let tip = find_last_block_inserted(blocks_db_rw) as u64;
+    let Some(last_cold_block) = tip.checked_sub(older_than_blocks) else {
+        return Ok(0);
+    };
+    let cursor = find_cold_storage_cursor(blocks_db_rw).to_be_bytes();
+    let mut moved = vec![];
+    // Blocks are keyed by their big endian height: the iteration goes from the oldest block to the most recent one.
+    for entry in blocks_db_rw.iterator(IteratorMode::From(&cursor, Direction::Forward)) {
+        if moved.len() >= max_blocks {
+            break;
+        }
+        let (key, block_bytes) = entry.map_err(|e| format!("unable to read hord.rocksdb: {e}"))?;
+        let Ok(block_height_bytes) = <[u8; 4]>::try_from(key.as_ref()) else {
+            continue;
+        };
+        let block_height = u32::from_be_bytes(block_height_bytes);
+        if block_height as u64 > last_cold_block {
+            break;
+        }
+        write_cold_block(cold_storage_path, block_height, &block_bytes)?;
+        moved.push(block_height);
+    }
+    let Some(last_moved) = moved.last() else {
+        return Ok(0);
+    };
+    for block_height in moved.iter() {
+        blocks_db_rw
+            .delete(block_height.to_be_bytes())
+            .map_err(|e| format!("unable to delete block #{block_height}: {e}"))?;
+    }
+    blocks_db_rw
+        .put(COLD_STORAGE_CURSOR_KEY, (last_moved + 1).to_be_bytes())
+        .map_err(|e| format!("unable to update cursor: {e}"))?;
+    Ok(moved.len())
+}
+
+/// Moves up to `max_blocks` blocks lagging the tip of hord.rocksdb to cold storage, when enabled. Failures are only
+/// logged: blocks stay in hord.rocksdb until the next attempt.
+pub fn tier_cold_blocks(
+    max_blocks: usize,
+    blocks_db_rw: &DB,
+    config: &Config,
+    ctx: &Context,
+) -> usize {
+    let (Some(cold_storage), Some(cold_storage_path)) =
+        (&config.cold_storage, config.expected_cold_storage_path())
+    else {
+        return 0;
+    };
+    match move_blocks_to_cold_storage(
+        &cold_storage_path,
+        cold_storage.older_than_blocks,
+        max_blocks,
+        blocks_db_rw,
+    ) {
+        Ok(0) => 0,
+        Ok(moved) => {
+            try_info!(
+                ctx,
+                "Cold storage: {moved} blocks moved to {}",
+                cold_storage_path.display()
+            );
+            moved
+        }
+        Err(e) => {
+            try_warn!(ctx, "Cold storage: {e}");
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::Config,
+        db::{
+            blocks::{find_last_block_inserted, insert_entry_in_blocks, open_blocks_db_with_retry},
+            drop_all_dbs,
+        },
+    };
+
+    use super::{move_blocks_to_cold_storage, read_cold_block, rewind_cold_storage_cursor};
+
+    #[test]
+    fn moves_old_blocks_to_cold_storage() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/cold_blocks".to_string();
+        drop_all_dbs(&config);
+        let cold_storage_path = config.expected_cache_path().join("cold_blocks");
+        let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
+        for block_height in 1..=10u32 {
+            insert_entry_in_blocks(
+                block_height,
+                &[block_height as u8; 64],
+                true,
+                &blocks_db,
+                &ctx,
+            );
+        }
+
+        assert_eq!(
+            move_blocks_to_cold_storage(&cold_storage_path, 3, 5, &blocks_db),
+            Ok(5)
+        );
+        assert_eq!(
+            move_blocks_to_cold_storage(&cold_storage_path, 3, 5, &blocks_db),
+            Ok(2)
+        );
+        assert_eq!(
+            move_blocks_to_cold_storage(&cold_storage_path, 3, 5, &blocks_db),
+            Ok(0)
+        );
+        for block_height in 1..=7u32 {
+            assert!(blocks_db.get(block_height.to_be_bytes()).unwrap().is_none());
+            assert_eq!(
+                read_cold_block(&cold_storage_path, block_height),
+                Ok(Some(vec![block_height as u8; 64]))
+            );
+        }
+        assert!(blocks_db.get(8u32.to_be_bytes()).unwrap().is_some());
+        assert_eq!(read_cold_block(&cold_storage_path, 8), Ok(None));
+        assert_eq!(find_last_block_inserted(&blocks_db), 10);
+
+        // Blocks indexed again after a drop get moved again.
+        rewind_cold_storage_cursor(5, &blocks_db, &ctx);
+        insert_entry_in_blocks(5, &[0; 64], false, &blocks_db, &ctx);
+        assert_eq!(
+            move_blocks_to_cold_storage(&cold_storage_path, 3, 5, &blocks_db),
+            Ok(1)
+        );
+        assert_eq!(
+            read_cold_block(&cold_storage_path, 5),
+            Ok(Some(vec![0; 64]))
+        );
+        drop(blocks_db);
+        drop_all_dbs(&config);
+    }
+}
@@ -57,7 +57,7 @@ impl TransactionInputBytesCursor {
     }
 }
 
-const TXID_LEN: usize = 8;
+pub const TXID_LEN: usize = 8;
 const SATS_LEN: usize = 8;
 const INPUT_SIZE: usize = TXID_LEN + 4 + 2 + SATS_LEN;
 const OUTPUT_SIZE: usize = 8;
@@ -1,40 +1,167 @@
+pub mod backup;
 pub mod blocks;
+pub mod chain_status;
+pub mod cold_blocks;
 pub mod cursor;
 pub mod ordinals;
+pub mod query_timeout;
+pub mod recovery;
+pub mod renumbering;
+pub mod sales;
+pub mod sat_ranges;
 
-use blocks::{delete_blocks_in_block_range, open_blocks_db_with_retry};
+use blocks::{
+    delete_blocks_in_block_range, find_last_block_inserted, open_blocks_db_with_retry,
+    open_readonly_blocks_db,
+};
 
-use ordinals::{delete_inscriptions_in_block_range, initialize_ordinals_db, open_ordinals_db_rw};
+use ordinals::{
+    delete_inscriptions_in_block_range, find_db_jubilee_height, find_db_network,
+    find_latest_inscription_block_height, initialize_ordinals_db, insert_db_jubilee_height,
+    insert_db_network, open_ordinals_db_rw,
+};
 use rocksdb::DB;
 use rusqlite::Connection;
+use sales::{delete_sales_in_block_range, sales_new_rw_db_conn};
+use sat_ranges::{rollback_sat_ranges_in_block_range, sat_ranges_new_rw_db_conn};
 
 use chainhook_sdk::utils::Context;
 
 use crate::{
     config::Config,
-    core::meta_protocols::brc20::db::{
-        brc20_new_rw_db_conn, delete_activity_in_block_range, initialize_brc20_db,
+    core::{
+        meta_protocols::{
+            brc20::db::{
+                brc20_new_rw_db_conn, delete_activity_in_block_range, initialize_brc20_db,
+            },
+            indexer::{
+                metaprotocols_new_rw_db_conn, rollback_metaprotocol_indexers_in_block_range,
+            },
+            runes::db::{delete_runes_in_block_range, initialize_runes_db, runes_new_rw_db_conn},
+            sns::db::{delete_names_in_block_range, initialize_sns_db, sns_new_rw_db_conn},
+        },
+        protocol::inscription_sequencing::get_jubilee_block_height,
     },
+    error::OrdhookError,
     try_info,
 };
 
 pub struct SqliteDbConnections {
     pub ordinals: Connection,
     pub brc20: Option<Connection>,
+    pub sns: Option<Connection>,
+    pub runes: Option<Connection>,
+    pub metaprotocols: Option<Connection>,
+    pub sat_ranges: Option<Connection>,
+    pub sales: Option<Connection>,
 }
 
 /// Opens and initializes all SQLite databases required for Ordhook operation, depending if they are requested by the current
 /// `Config`. Returns a struct with all the open connections.
 pub fn initialize_sqlite_dbs(config: &Config, ctx: &Context) -> SqliteDbConnections {
     SqliteDbConnections {
-        ordinals: initialize_ordinals_db(&config.expected_cache_path(), ctx),
+        ordinals: initialize_ordinals_db(&config.expected_sqlite_path(), ctx),
         brc20: match config.meta_protocols.brc20 {
             true => Some(initialize_brc20_db(
-                Some(&config.expected_cache_path()),
+                Some(&config.expected_sqlite_path()),
                 ctx,
             )),
             false => None,
         },
+        sns: match config.meta_protocols.sns {
+            true => Some(initialize_sns_db(Some(&config.expected_sqlite_path()), ctx)),
+            false => None,
+        },
+        runes: match config.meta_protocols.runes {
+            true => Some(initialize_runes_db(
+                Some(&config.expected_sqlite_path()),
+                ctx,
+            )),
+            false => None,
+        },
+        metaprotocols: metaprotocols_new_rw_db_conn(config, ctx),
+        sat_ranges: sat_ranges_new_rw_db_conn(config, ctx),
+        sales: sales_new_rw_db_conn(config, ctx),
+    }
+}
+
+/// Returns true if the databases hold indexed inscriptions or blocks.
+fn has_indexed_data(
+    conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<bool, OrdhookError> {
+    if find_latest_inscription_block_height(conn, ctx)?.is_some() {
+        return Ok(true);
+    }
+    Ok(open_readonly_blocks_db(config, ctx)
+        .map(|blocks_db| find_last_block_inserted(&blocks_db) > 0)
+        .unwrap_or(false))
+}
+
+/// Refuses databases created for another network than the one configured, which would otherwise be corrupted by
+/// blocks of the wrong chain. The network is recorded in new databases. Databases holding data but no network, created
+/// before the network was recorded, are refused until the operator adopts them with `ordhook db adopt-network`.
+pub fn check_dbs_network(config: &Config, ctx: &Context) -> Result<(), OrdhookError> {
+    let conn = initialize_ordinals_db(&config.expected_sqlite_path(), ctx);
+    let network = config.network.network_name();
+    match find_db_network(&conn, ctx) {
+        Some(db_network) if db_network != network => Err(OrdhookError::Config(format!(
+            "databases in {} were created for {db_network}, but network.mode targets {network}: check storage.working_dir and storage.sqlite_dir",
+            config.expected_sqlite_path().display()
+        ))),
+        Some(_) => Ok(()),
+        None if has_indexed_data(&conn, config, ctx)? => Err(OrdhookError::Config(format!(
+            "databases in {} don't record their network: once checked that they were indexed on {network}, run `ordhook db adopt-network`",
+            config.expected_sqlite_path().display()
+        ))),
+        None => {
+            try_info!(ctx, "Recording {network} as the network of the databases");
+            insert_db_network(network, &conn, ctx);
+            Ok(())
+        }
+    }?;
+    check_dbs_jubilee_height(&conn, config, ctx)
+}
+
+/// Records the network configured as the one of databases which don't record theirs.
+pub fn adopt_dbs_network(config: &Config, ctx: &Context) -> Result<(), OrdhookError> {
+    let conn = initialize_ordinals_db(&config.expected_sqlite_path(), ctx);
+    let network = config.network.network_name();
+    match find_db_network(&conn, ctx) {
+        Some(db_network) if db_network != network => Err(OrdhookError::Config(format!(
+            "databases in {} were created for {db_network}, but network.mode targets {network}",
+            config.expected_sqlite_path().display()
+        ))),
+        Some(_) => Ok(()),
+        None => {
+            try_info!(ctx, "Recording {network} as the network of the databases");
+            insert_db_network(network, &conn, ctx);
+            Ok(())
+        }
+    }
+}
+
+/// Refuses databases numbered with another jubilee height than the one configured: inscriptions numbered past either
+/// height would get numbers inconsistent with the ones already indexed.
+fn check_dbs_jubilee_height(
+    conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let jubilee_height = get_jubilee_block_height(config);
+    match find_db_jubilee_height(conn, ctx) {
+        Some(db_jubilee_height) if db_jubilee_height != jubilee_height => {
+            Err(OrdhookError::Config(format!(
+                "databases in {} were numbered with a jubilee height of {db_jubilee_height}, but the jubilee height configured is {jubilee_height}: restore network.jubilee_height, or drop the databases to index again",
+                config.expected_sqlite_path().display()
+            )))
+        }
+        Some(_) => Ok(()),
+        None => {
+            insert_db_jubilee_height(jubilee_height, conn, ctx);
+            Ok(())
+        }
     }
 }
 
@@ -42,15 +169,25 @@ pub fn initialize_sqlite_dbs(config: &Config, ctx: &Context) -> SqliteDbConnecti
 pub fn open_all_dbs_rw(
     config: &Config,
     ctx: &Context,
-) -> Result<(DB, SqliteDbConnections), String> {
+) -> Result<(DB, SqliteDbConnections), OrdhookError> {
     let blocks_db = open_blocks_db_with_retry(true, &config, ctx);
-    let inscriptions_db = open_ordinals_db_rw(&config.expected_cache_path(), ctx)?;
+    let inscriptions_db = open_ordinals_db_rw(&config.expected_sqlite_path(), ctx)?;
     let brc20_db = brc20_new_rw_db_conn(config, ctx);
+    let sns_db = sns_new_rw_db_conn(config, ctx);
+    let runes_db = runes_new_rw_db_conn(config, ctx);
+    let metaprotocols_db = metaprotocols_new_rw_db_conn(config, ctx);
+    let sat_ranges_db = sat_ranges_new_rw_db_conn(config, ctx);
+    let sales_db = sales_new_rw_db_conn(config, ctx);
     Ok((
         blocks_db,
         SqliteDbConnections {
             ordinals: inscriptions_db,
             brc20: brc20_db,
+            sns: sns_db,
+            runes: runes_db,
+            metaprotocols: metaprotocols_db,
+            sat_ranges: sat_ranges_db,
+            sales: sales_db,
         },
     ))
 }
@@ -61,13 +198,20 @@ pub fn drop_block_data_from_all_dbs(
     end_block: u64,
     blocks_db_rw: &DB,
     sqlite_dbs_rw: &SqliteDbConnections,
+    config: &Config,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     try_info!(
         ctx,
         "Deleting entries from block #{start_block} to block #{end_block}"
     );
-    delete_blocks_in_block_range(start_block as u32, end_block as u32, &blocks_db_rw, &ctx);
+    delete_blocks_in_block_range(
+        start_block as u32,
+        end_block as u32,
+        &blocks_db_rw,
+        config,
+        &ctx,
+    );
     try_info!(
         ctx,
         "Deleting inscriptions and locations from block #{start_block} to block #{end_block}"
@@ -85,14 +229,54 @@ pub fn drop_block_data_from_all_dbs(
             "Deleting BRC-20 activity from block #{start_block} to block #{end_block}"
         );
     }
+    if let Some(conn) = &sqlite_dbs_rw.sns {
+        delete_names_in_block_range(start_block as u32, end_block as u32, &conn, &ctx);
+        try_info!(
+            ctx,
+            "Deleting SNS names from block #{start_block} to block #{end_block}"
+        );
+    }
+    if let Some(conn) = &sqlite_dbs_rw.runes {
+        delete_runes_in_block_range(start_block as u32, end_block as u32, &conn, &ctx);
+        try_info!(
+            ctx,
+            "Deleting runes activity from block #{start_block} to block #{end_block}"
+        );
+    }
+    if let Some(conn) = &sqlite_dbs_rw.metaprotocols {
+        rollback_metaprotocol_indexers_in_block_range(start_block, end_block, &conn, &ctx);
+        try_info!(
+            ctx,
+            "Rolling back metaprotocol indexers from block #{start_block} to block #{end_block}"
+        );
+    }
+    if let Some(conn) = &sqlite_dbs_rw.sat_ranges {
+        rollback_sat_ranges_in_block_range(start_block, end_block, &conn, &ctx);
+        try_info!(
+            ctx,
+            "Rolling back sat ranges from block #{start_block} to block #{end_block}"
+        );
+    }
+    if let Some(conn) = &sqlite_dbs_rw.sales {
+        delete_sales_in_block_range(start_block as u32, end_block as u32, &conn, &ctx);
+        try_info!(
+            ctx,
+            "Deleting sales from block #{start_block} to block #{end_block}"
+        );
+    }
     Ok(())
 }
 
 /// Drops DB files in a test environment.
 #[cfg(test)]
 pub fn drop_all_dbs(config: &Config) {
-    let dir_path = &config.expected_cache_path();
-    if dir_path.exists() {
-        std::fs::remove_dir_all(dir_path).unwrap();
+    for dir_path in [
+        config.expected_cache_path(),
+        config.expected_sqlite_path(),
+        config.expected_blocks_path(),
+    ] {
+        if dir_path.exists() {
+            std::fs::remove_dir_all(dir_path).unwrap();
+        }
     }
 }
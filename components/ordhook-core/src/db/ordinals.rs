@@ -1,6 +1,7 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     path::PathBuf,
+    sync::RwLock,
 };
 
 use rusqlite::{Connection, OpenFlags, ToSql, Transaction};
@@ -8,59 +9,182 @@ use rusqlite::{Connection, OpenFlags, ToSql, Transaction};
 use chainhook_sdk::{
     types::{
         BitcoinBlockData, BlockIdentifier, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
-        TransactionIdentifier,
+        OrdinalInscriptionTransferDestination, TransactionIdentifier,
     },
     utils::Context,
 };
 
 use crate::{
     core::protocol::{
+        addresses::script_hex_address,
+        content_sniffing::is_content_type_mismatch,
+        event_hash::compute_block_events_hash,
         inscription_parsing::{
-            get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
+            get_inscription_content_types_in_block, get_inscriptions_revealed_in_block,
+            get_inscriptions_transferred_in_block,
         },
+        inscription_sequencing::get_bitcoin_network,
         satoshi_numbering::TraversalResult,
+        satributes::get_satributes,
     },
-    try_error, try_warn,
+    error::OrdhookError,
+    try_error, try_info, try_warn,
     utils::{
         format_outpoint_to_watch, parse_inscription_id, parse_outpoint_to_watch,
         parse_satpoint_to_watch,
     },
 };
 
+/// Unbound inscriptions are located at `<UNBOUND_INSCRIPTIONS_TXID>:0:<unbound sequence>`, like ord does. They are not
+/// bound to any sat, and never move.
+pub const UNBOUND_INSCRIPTIONS_TXID: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Blocks a spent watched output is kept for once spent, for the re-orgs of the spending block. Outputs spent by a block
+/// rolled back after they were deleted are looked up on bitcoind again.
+const SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS: u64 = 6;
+
+pub fn format_unbound_satpoint(unbound_sequence: u64) -> String {
+    format!("{UNBOUND_INSCRIPTIONS_TXID}:0:{unbound_sequence}")
+}
+
+/// Unbound sequence of an unbound inscription satpoint.
+pub fn parse_unbound_satpoint(satpoint: &str) -> Option<u64> {
+    satpoint
+        .trim_start_matches("0x")
+        .strip_prefix(UNBOUND_INSCRIPTIONS_TXID)?
+        .strip_prefix(":0:")?
+        .parse()
+        .ok()
+}
+
 pub fn get_default_ordinals_db_file_path(base_dir: &PathBuf) -> PathBuf {
     let mut destination_path = base_dir.clone();
     destination_path.push("hord.sqlite");
     destination_path
 }
 
-pub fn open_ordinals_db(base_dir: &PathBuf, ctx: &Context) -> Result<Connection, String> {
+pub fn open_ordinals_db(base_dir: &PathBuf, ctx: &Context) -> Result<Connection, OrdhookError> {
     let path = get_default_ordinals_db_file_path(&base_dir);
-    let conn = open_existing_readonly_db(&path, ctx);
-    Ok(conn)
+    open_existing_readonly_db(&path, ctx)
+}
+
+/// Same as `open_ordinals_db`, with every query of the connection served from the same snapshot of the database.
+pub fn open_ordinals_db_snapshot(
+    base_dir: &PathBuf,
+    ctx: &Context,
+) -> Result<Connection, OrdhookError> {
+    let path = get_default_ordinals_db_file_path(&base_dir);
+    open_existing_readonly_db_snapshot(&path, ctx)
 }
 
-pub fn open_ordinals_db_rw(base_dir: &PathBuf, ctx: &Context) -> Result<Connection, String> {
+pub fn open_ordinals_db_rw(base_dir: &PathBuf, ctx: &Context) -> Result<Connection, OrdhookError> {
     let db_path = get_default_ordinals_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(Some(&db_path), ctx);
     Ok(conn)
 }
 
+/// Columns of the `inscriptions` table. `ordinal_number` is NULL for unbound inscriptions, which are not inscribed on
+/// any sat.
+const INSCRIPTIONS_TABLE_COLUMNS: &str = "
+    inscription_id TEXT NOT NULL PRIMARY KEY,
+    input_index INTEGER NOT NULL,
+    block_height INTEGER NOT NULL,
+    ordinal_number INTEGER,
+    jubilee_inscription_number INTEGER NOT NULL,
+    classic_inscription_number INTEGER NOT NULL,
+    CONSTRAINT inscription_id_uniqueness UNIQUE (inscription_id),
+    CONSTRAINT jubilee_inscription_number_uniqueness UNIQUE (inscription_id),
+    CONSTRAINT classic_inscription_number_uniqueness UNIQUE (inscription_id)
+";
+
+/// Databases created by earlier releases stored unbound inscriptions on sat 0, in a NOT NULL `ordinal_number` column:
+/// the table is rebuilt with a nullable column, and the unbound inscriptions already recorded get their sat cleared.
+/// Unbound inscriptions indexed before `unbound_inscriptions` existed can't be told apart, they need a reindex.
+fn migrate_inscriptions_ordinal_number_to_nullable(conn: &Connection, ctx: &Context) {
+    let not_null = conn
+        .query_row(
+            "SELECT \"notnull\" FROM pragma_table_info('inscriptions') WHERE name = 'ordinal_number'",
+            [],
+            |row| row.get::<_, bool>(0),
+        )
+        .unwrap_or(false);
+    if !not_null {
+        return;
+    }
+    let has_unbound_inscriptions = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'unbound_inscriptions'",
+            [],
+            |_| Ok(()),
+        )
+        .is_ok();
+    let ordinal_number = if has_unbound_inscriptions {
+        "CASE WHEN inscription_id IN (SELECT inscription_id FROM unbound_inscriptions) THEN NULL ELSE ordinal_number END"
+    } else {
+        "ordinal_number"
+    };
+    try_info!(
+        ctx,
+        "Migrating hord.sqlite: unbound inscriptions are stored without a sat"
+    );
+    // Indexes are dropped with the former table, and created again right after.
+    if let Err(e) = conn.execute_batch(&format!(
+        "BEGIN;
+        CREATE TABLE inscriptions_migrated ({INSCRIPTIONS_TABLE_COLUMNS});
+        INSERT INTO inscriptions_migrated (inscription_id, input_index, block_height, ordinal_number, jubilee_inscription_number, classic_inscription_number)
+            SELECT inscription_id, input_index, block_height, {ordinal_number}, jubilee_inscription_number, classic_inscription_number FROM inscriptions;
+        DROP TABLE inscriptions;
+        ALTER TABLE inscriptions_migrated RENAME TO inscriptions;
+        COMMIT;"
+    )) {
+        let _ = conn.execute_batch("ROLLBACK");
+        try_error!(ctx, "Unable to migrate table inscriptions: {}", e.to_string());
+    }
+}
+
+fn migrate_watched_outputs_spent_block_height(conn: &Connection, ctx: &Context) {
+    let has_table = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'watched_outputs'",
+            [],
+            |_| Ok(()),
+        )
+        .is_ok();
+    let has_column = conn
+        .query_row(
+            "SELECT 1 FROM pragma_table_info('watched_outputs') WHERE name = 'spent_block_height'",
+            [],
+            |_| Ok(()),
+        )
+        .is_ok();
+    if !has_table || has_column {
+        return;
+    }
+    try_info!(
+        ctx,
+        "Migrating hord.sqlite: spent watched outputs are pruned"
+    );
+    if let Err(e) = conn.execute(
+        "ALTER TABLE watched_outputs ADD COLUMN spent_block_height INTEGER",
+        [],
+    ) {
+        try_error!(
+            ctx,
+            "Unable to migrate table watched_outputs: {}",
+            e.to_string()
+        );
+    }
+}
+
 pub fn initialize_ordinals_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
     let db_path = get_default_ordinals_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(Some(&db_path), ctx);
+    migrate_inscriptions_ordinal_number_to_nullable(&conn, ctx);
+    migrate_watched_outputs_spent_block_height(&conn, ctx);
     // TODO: introduce initial output
     if let Err(e) = conn.execute(
-        "CREATE TABLE IF NOT EXISTS inscriptions (
-            inscription_id TEXT NOT NULL PRIMARY KEY,
-            input_index INTEGER NOT NULL,
-            block_height INTEGER NOT NULL,
-            ordinal_number INTEGER NOT NULL,
-            jubilee_inscription_number INTEGER NOT NULL,
-            classic_inscription_number INTEGER NOT NULL,
-            CONSTRAINT inscription_id_uniqueness UNIQUE (inscription_id),
-            CONSTRAINT jubilee_inscription_number_uniqueness UNIQUE (inscription_id),
-            CONSTRAINT classic_inscription_number_uniqueness UNIQUE (inscription_id)
-        )",
+        &format!("CREATE TABLE IF NOT EXISTS inscriptions ({INSCRIPTIONS_TABLE_COLUMNS})"),
         [],
     ) {
         try_warn!(
@@ -131,6 +255,13 @@ pub fn initialize_ordinals_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
         ) {
             try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
         }
+        // Locations of a sat as of a given height, read by index commitments.
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS locations_indexed_on_ordinal_number_and_block_height ON locations(ordinal_number, block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
     }
 
     if let Err(e) = conn.execute(
@@ -156,217 +287,1756 @@ pub fn initialize_ordinals_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
         }
     }
 
-    conn
-}
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS block_events_hashes (
+            block_height INTEGER NOT NULL PRIMARY KEY,
+            block_hash TEXT NOT NULL,
+            events_hash TEXT NOT NULL,
+            events_count INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table block_events_hashes: {}",
+            e.to_string()
+        );
+    }
 
-pub fn create_or_open_readwrite_db(db_path: Option<&PathBuf>, ctx: &Context) -> Connection {
-    let open_flags = if let Some(db_path) = db_path {
-        match std::fs::metadata(&db_path) {
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    // need to create
-                    if let Some(dirp) = PathBuf::from(&db_path).parent() {
-                        std::fs::create_dir_all(dirp).unwrap_or_else(|e| {
-                            try_error!(ctx, "{}", e.to_string());
-                        });
-                    }
-                    OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
-                } else {
-                    panic!("FATAL: could not stat {}", db_path.display());
-                }
-            }
-            Ok(_md) => {
-                // can just open
-                OpenFlags::SQLITE_OPEN_READ_WRITE
-            }
-        }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS block_timestamps (
+            block_height INTEGER NOT NULL PRIMARY KEY,
+            timestamp INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table block_timestamps: {}",
+            e.to_string()
+        );
     } else {
-        OpenFlags::SQLITE_OPEN_READ_WRITE
-    };
-
-    let path = match db_path {
-        Some(path) => path.to_str().unwrap(),
-        None => ":memory:",
-    };
-    let conn = loop {
-        match Connection::open_with_flags(&path, open_flags) {
-            Ok(conn) => break conn,
-            Err(e) => {
-                try_error!(ctx, "{}", e.to_string());
-            }
-        };
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    };
-    connection_with_defaults_pragma(conn)
-}
-
-pub fn open_existing_readonly_db(db_path: &PathBuf, ctx: &Context) -> Connection {
-    let open_flags = match std::fs::metadata(db_path) {
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                panic!("FATAL: could not find {}", db_path.display());
-            } else {
-                panic!("FATAL: could not stat {}", db_path.display());
-            }
-        }
-        Ok(_md) => {
-            // can just open
-            OpenFlags::SQLITE_OPEN_READ_ONLY
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_block_timestamps_on_timestamp ON block_timestamps(timestamp);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
         }
-    };
-
-    let conn = loop {
-        match Connection::open_with_flags(db_path, open_flags) {
-            Ok(conn) => break conn,
-            Err(e) => {
-                try_warn!(ctx, "unable to open hord.rocksdb: {}", e.to_string());
-            }
-        };
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    };
-    connection_with_defaults_pragma(conn)
-}
-
-fn connection_with_defaults_pragma(conn: Connection) -> Connection {
-    conn.busy_timeout(std::time::Duration::from_secs(300))
-        .expect("unable to set db timeout");
-    conn.pragma_update(None, "mmap_size", 512 * 1024 * 1024)
-        .expect("unable to enable mmap_size");
-    conn.pragma_update(None, "cache_size", 512 * 1024 * 1024)
-        .expect("unable to enable cache_size");
-    conn.pragma_update(None, "journal_mode", &"WAL")
-        .expect("unable to enable wal");
-    conn
-}
+    }
 
-pub fn insert_entry_in_inscriptions(
-    inscription_data: &OrdinalInscriptionRevealData,
-    block_identifier: &BlockIdentifier,
-    inscriptions_db_conn_rw: &Connection,
-    ctx: &Context,
-) {
-    while let Err(e) = inscriptions_db_conn_rw.execute(
-        "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        rusqlite::params![&inscription_data.inscription_id, &inscription_data.ordinal_number, &inscription_data.inscription_number.jubilee, &inscription_data.inscription_number.classic, &block_identifier.index, &inscription_data.inscription_input_index],
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS reorg_events (
+            block_height INTEGER NOT NULL,
+            block_hash TEXT NOT NULL,
+            rolled_back_at INTEGER NOT NULL
+        )",
+        [],
     ) {
-        try_warn!(ctx, "unable to insert inscription in hord.sqlite: {} - {:?}", e.to_string(), inscription_data);
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        try_warn!(
+            ctx,
+            "Unable to create table reorg_events: {}",
+            e.to_string()
+        );
     }
-}
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct OrdinalLocation {
-    pub offset: u64,
-    pub block_height: u64,
-    pub tx_index: usize,
-}
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS inscription_content_types (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            declared_content_type TEXT NOT NULL,
+            detected_content_type TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table inscription_content_types: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_content_types_on_block_height ON inscription_content_types(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
 
-pub fn insert_entries_from_block_in_inscriptions(
-    block: &BitcoinBlockData,
-    inscriptions_db_conn_rw: &Connection,
-    ctx: &Context,
-) {
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
-        insert_entry_in_inscriptions(
-            inscription_data,
-            &block.block_identifier,
-            inscriptions_db_conn_rw,
-            &ctx,
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS inscription_content_scans (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            verdict TEXT NOT NULL,
+            labels TEXT NOT NULL,
+            scanned_at INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table inscription_content_scans: {}",
+            e.to_string()
         );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_content_scans_on_block_height ON inscription_content_scans(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
     }
-}
 
-pub fn update_ordinals_db_with_block(
-    block: &BitcoinBlockData,
-    inscriptions_db_conn_rw: &Connection,
-    ctx: &Context,
-) {
-    let mut locations_to_insert = HashMap::new();
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS inscription_satributes (
+            inscription_id TEXT NOT NULL,
+            ordinal_number INTEGER NOT NULL,
+            block_height INTEGER NOT NULL,
+            satribute TEXT NOT NULL,
+            PRIMARY KEY (inscription_id, satribute)
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table inscription_satributes: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_satributes_on_satribute ON inscription_satributes(satribute);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_satributes_on_block_height ON inscription_satributes(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
 
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
-        insert_entry_in_inscriptions(
-            inscription_data,
-            &block.block_identifier,
-            inscriptions_db_conn_rw,
-            &ctx,
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS inscription_genesis (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            content_type TEXT NOT NULL,
+            parent TEXT,
+            metaprotocol TEXT
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table inscription_genesis: {}",
+            e.to_string()
         );
-        let (tx, output_index, offset) =
-            parse_satpoint_to_watch(&inscription_data.satpoint_post_inscription);
-        let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
-        let _ = locations_to_insert.insert(
-            (inscription_data.ordinal_number, outpoint_to_watch),
-            OrdinalLocation {
-                offset,
-                block_height: block.block_identifier.index,
-                tx_index: inscription_data.tx_index,
-            },
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_genesis_on_block_height ON inscription_genesis(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS unbound_inscriptions (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            unbound_sequence INTEGER NOT NULL UNIQUE
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table unbound_inscriptions: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_unbound_inscriptions_on_block_height ON unbound_inscriptions(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS miner_payouts (
+            satpoint TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            address TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table miner_payouts: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_miner_payouts_on_block_height ON miner_payouts(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS watched_outputs (
+            outpoint TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            value INTEGER NOT NULL,
+            script_pubkey TEXT NOT NULL,
+            spent_block_height INTEGER
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table watched_outputs: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_watched_outputs_on_block_height ON watched_outputs(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_watched_outputs_on_spent_block_height ON watched_outputs(spent_block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS index_commitments (
+            block_height INTEGER NOT NULL PRIMARY KEY,
+            block_hash TEXT NOT NULL,
+            root TEXT NOT NULL,
+            leaves_count INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table index_commitments: {}",
+            e.to_string()
+        );
+    }
+
+    // Leaves of the index commitment at the last block indexed, kept up to date block by block.
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS index_commitment_leaves (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            inscription_number INTEGER NOT NULL,
+            leaf_hash BLOB NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table index_commitment_leaves: {}",
+            e.to_string()
+        );
+    } else if let Err(e) = conn.execute(
+        "CREATE INDEX IF NOT EXISTS index_commitment_leaves_on_inscription_number ON index_commitment_leaves(inscription_number);",
+        [],
+    ) {
+        try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS quarantined_blocks (
+            block_height INTEGER NOT NULL PRIMARY KEY,
+            block_hash TEXT NOT NULL,
+            error TEXT NOT NULL,
+            attempts INTEGER NOT NULL,
+            skipped INTEGER NOT NULL,
+            last_failed_at INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table quarantined_blocks: {}",
+            e.to_string()
+        );
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS db_metadata (
+            key TEXT NOT NULL PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table db_metadata: {}", e.to_string());
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS inscription_number_corrections (
+            inscription_id TEXT NOT NULL,
+            block_height INTEGER NOT NULL,
+            old_jubilee_inscription_number INTEGER,
+            old_classic_inscription_number INTEGER,
+            new_jubilee_inscription_number INTEGER,
+            new_classic_inscription_number INTEGER,
+            renumbered_at INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table inscription_number_corrections: {}",
+            e.to_string()
+        );
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_inscription_number_corrections_on_block_height ON inscription_number_corrections(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create hord.sqlite: {}", e.to_string());
+        }
+    }
+
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_renumbering_numbers (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            jubilee_inscription_number INTEGER NOT NULL,
+            classic_inscription_number INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table pending_renumbering_numbers: {}",
+            e.to_string()
         );
     }
 
-    for transfer_data in get_inscriptions_transferred_in_block(&block).iter() {
-        let (tx, output_index, offset) =
-            parse_satpoint_to_watch(&transfer_data.satpoint_post_transfer);
-        let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
-        let _ = locations_to_insert.insert(
-            (transfer_data.ordinal_number, outpoint_to_watch),
-            OrdinalLocation {
-                offset,
-                block_height: block.block_identifier.index,
-                tx_index: transfer_data.tx_index,
-            },
-        );
+    conn
+}
+
+pub fn create_or_open_readwrite_db(db_path: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let open_flags = if let Some(db_path) = db_path {
+        match std::fs::metadata(&db_path) {
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    // need to create
+                    if let Some(dirp) = PathBuf::from(&db_path).parent() {
+                        std::fs::create_dir_all(dirp).unwrap_or_else(|e| {
+                            try_error!(ctx, "{}", e.to_string());
+                        });
+                    }
+                    OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+                } else {
+                    panic!("FATAL: could not stat {}", db_path.display());
+                }
+            }
+            Ok(_md) => {
+                // can just open
+                OpenFlags::SQLITE_OPEN_READ_WRITE
+            }
+        }
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    };
+
+    let path = match db_path {
+        Some(path) => path.to_str().unwrap(),
+        None => ":memory:",
+    };
+    let conn = loop {
+        match Connection::open_with_flags(&path, open_flags) {
+            Ok(conn) => match apply_sqlite_encryption_key(&conn) {
+                Ok(()) => break conn,
+                Err(e) => try_error!(ctx, "{path}: {}", e.to_string()),
+            },
+            Err(e) => {
+                try_error!(ctx, "{}", e.to_string());
+            }
+        };
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    };
+    connection_with_defaults_pragma(conn)
+}
+
+/// Attempts made to open a read-only connection on transient errors, such as the database being busy, 1s apart.
+const OPEN_READONLY_DB_MAX_RETRIES: u32 = 5;
+
+pub fn open_existing_readonly_db(
+    db_path: &PathBuf,
+    ctx: &Context,
+) -> Result<Connection, OrdhookError> {
+    let open_flags = match std::fs::metadata(db_path) {
+        Err(e) => {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                return Err(OrdhookError::Db(format!(
+                    "could not find {}",
+                    db_path.display()
+                )));
+            } else {
+                return Err(OrdhookError::Db(format!(
+                    "could not stat {}: {}",
+                    db_path.display(),
+                    e
+                )));
+            }
+        }
+        Ok(_md) => {
+            // can just open
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        }
+    };
+
+    let mut retries = 0;
+    let conn = loop {
+        let error = match Connection::open_with_flags(db_path, open_flags) {
+            Ok(conn) => match apply_sqlite_encryption_key(&conn) {
+                Ok(()) => break conn,
+                // A key which doesn't unlock the database won't unlock it on retry.
+                Err(OrdhookError::Config(e)) => {
+                    return Err(OrdhookError::Config(format!(
+                        "{}: {}",
+                        db_path.display(),
+                        e
+                    )))
+                }
+                Err(e) => e.to_string(),
+            },
+            Err(e) => e.to_string(),
+        };
+        retries += 1;
+        if retries > OPEN_READONLY_DB_MAX_RETRIES {
+            return Err(OrdhookError::Db(format!(
+                "unable to open {}: {}",
+                db_path.display(),
+                error
+            )));
+        }
+        try_warn!(ctx, "unable to open {}: {}", db_path.display(), error);
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    };
+    Ok(connection_with_reader_pragma(conn))
+}
+
+/// Opens a read-only connection which queries all see the WAL snapshot taken by its first query, whatever the
+/// writer commits meanwhile. Readers of a snapshot never wait on the writer, so API requests get consistent results
+/// across their queries without contending with the indexer. The snapshot is released when the connection is dropped,
+/// which must happen quickly: the WAL can't be checkpointed past the oldest snapshot still open.
+pub fn open_existing_readonly_db_snapshot(
+    db_path: &PathBuf,
+    ctx: &Context,
+) -> Result<Connection, OrdhookError> {
+    let conn = open_existing_readonly_db(db_path, ctx)?;
+    conn.execute_batch("BEGIN DEFERRED").map_err(|e| {
+        OrdhookError::Db(format!(
+            "unable to open snapshot of {}: {}",
+            db_path.display(),
+            e
+        ))
+    })?;
+    Ok(conn)
+}
+
+lazy_static! {
+    static ref SQLITE_ENCRYPTION_KEY: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// Sets the key used to unlock encrypted databases, for every SQLite connection opened by this process.
+pub fn set_sqlite_encryption_key(key: Option<String>) -> Result<(), OrdhookError> {
+    if key.is_some() && !cfg!(feature = "sqlcipher") {
+        return Err(OrdhookError::Config(
+            "storage encryption requires ordhook to be built with the `sqlcipher` feature"
+                .to_string(),
+        ));
+    }
+    let mut current_key = SQLITE_ENCRYPTION_KEY
+        .write()
+        .map_err(|e| OrdhookError::Db(format!("unable to set encryption key: {}", e)))?;
+    *current_key = key;
+    Ok(())
+}
+
+/// Checks that the encryption key set unlocks hord.sqlite, when it exists, for a wrong key to be reported on start
+/// rather than by every connection opened.
+pub fn check_sqlite_encryption_key(base_dir: &PathBuf) -> Result<(), OrdhookError> {
+    let db_path = get_default_ordinals_db_file_path(base_dir);
+    if !db_path.exists() {
+        return Ok(());
+    }
+    let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| OrdhookError::Db(format!("unable to open {}: {}", db_path.display(), e)))?;
+    apply_sqlite_encryption_key(&conn)
+        .map_err(|e| OrdhookError::Config(format!("{}: {}", db_path.display(), e)))
+}
+
+/// Unlocks the given connection with SQLCipher. Must be performed before any other statement is executed. Fails with
+/// `OrdhookError::Config` if the key does not decrypt the database, and with `OrdhookError::Db` if the database was
+/// busy and can be retried.
+pub fn apply_sqlite_encryption_key(conn: &Connection) -> Result<(), OrdhookError> {
+    let key = SQLITE_ENCRYPTION_KEY
+        .read()
+        .map_err(|e| OrdhookError::Db(format!("unable to read encryption key: {}", e)))?;
+    let Some(ref key) = *key else {
+        return Ok(());
+    };
+    conn.pragma_update(None, "key", key)
+        .map_err(|e| OrdhookError::Config(format!("unable to set encryption key: {}", e)))?;
+    // SQLCipher only checks the key once the database gets read.
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|e| match is_busy(&e) {
+            true => OrdhookError::Db(format!("unable to unlock database: {}", e)),
+            false => OrdhookError::Config(format!("unable to unlock database: {}", e)),
+        })
+}
+
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked,
+                ..
+            },
+            _
+        )
+    )
+}
+
+fn connection_with_defaults_pragma(conn: Connection) -> Connection {
+    conn.busy_timeout(std::time::Duration::from_secs(300))
+        .expect("unable to set db timeout");
+    conn.pragma_update(None, "mmap_size", 512 * 1024 * 1024)
+        .expect("unable to enable mmap_size");
+    conn.pragma_update(None, "cache_size", 512 * 1024 * 1024)
+        .expect("unable to enable cache_size");
+    // Only effective on databases created without tables yet: older ones keep reclaiming free pages on VACUUM.
+    conn.pragma_update(None, "auto_vacuum", &"INCREMENTAL")
+        .expect("unable to enable auto_vacuum");
+    conn.pragma_update(None, "journal_mode", &"WAL")
+        .expect("unable to enable wal");
+    conn
+}
+
+/// Pragmas of read-only connections. The journal mode is persisted in the database by its writer: switching it from
+/// a reader requires a lock on the database, and was failing with `database is locked` while the indexer was writing.
+fn connection_with_reader_pragma(conn: Connection) -> Connection {
+    conn.busy_timeout(std::time::Duration::from_secs(300))
+        .expect("unable to set db timeout");
+    conn.pragma_update(None, "mmap_size", 512 * 1024 * 1024)
+        .expect("unable to enable mmap_size");
+    conn.pragma_update(None, "cache_size", 512 * 1024 * 1024)
+        .expect("unable to enable cache_size");
+    conn.pragma_update(None, "query_only", true)
+        .expect("unable to enable query_only");
+    conn
+}
+
+pub fn insert_entry_in_inscriptions(
+    inscription_data: &OrdinalInscriptionRevealData,
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    // Unbound inscriptions are not inscribed on any sat.
+    let ordinal_number = parse_unbound_satpoint(&inscription_data.satpoint_post_inscription)
+        .is_none()
+        .then_some(inscription_data.ordinal_number);
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![&inscription_data.inscription_id, &ordinal_number, &inscription_data.inscription_number.jubilee, &inscription_data.inscription_number.classic, &block_identifier.index, &inscription_data.inscription_input_index],
+    ) {
+        try_warn!(ctx, "unable to insert inscription in hord.sqlite: {} - {:?}", e.to_string(), inscription_data);
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrdinalLocation {
+    pub offset: u64,
+    pub block_height: u64,
+    pub tx_index: usize,
+}
+
+pub fn insert_entries_from_block_in_inscriptions(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+        insert_entry_in_inscriptions(
+            inscription_data,
+            &block.block_identifier,
+            inscriptions_db_conn_rw,
+            &ctx,
+        );
+    }
+}
+
+pub fn update_ordinals_db_with_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let mut locations_to_insert = HashMap::new();
+
+    insert_block_timestamp(block, inscriptions_db_conn_rw, ctx);
+
+    let mut content_types = get_inscription_content_types_in_block(block);
+    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+        insert_entry_in_inscriptions(
+            inscription_data,
+            &block.block_identifier,
+            inscriptions_db_conn_rw,
+            &ctx,
+        );
+        if let Some((declared_content_type, detected_content_type)) =
+            content_types.remove(&inscription_data.inscription_id)
+        {
+            insert_inscription_content_type(
+                &inscription_data.inscription_id,
+                &declared_content_type,
+                detected_content_type,
+                &block.block_identifier,
+                inscriptions_db_conn_rw,
+                &ctx,
+            );
+        }
+        insert_inscription_genesis(
+            inscription_data,
+            &block.block_identifier,
+            inscriptions_db_conn_rw,
+            &ctx,
+        );
+        if let Some(unbound_sequence) =
+            parse_unbound_satpoint(&inscription_data.satpoint_post_inscription)
+        {
+            insert_unbound_inscription(
+                &inscription_data.inscription_id,
+                unbound_sequence,
+                block.block_identifier.index,
+                inscriptions_db_conn_rw,
+                ctx,
+            );
+            continue;
+        }
+        insert_inscription_satributes(
+            inscription_data,
+            &block.block_identifier,
+            inscriptions_db_conn_rw,
+            &ctx,
+        );
+        let (tx, output_index, offset) =
+            parse_satpoint_to_watch(&inscription_data.satpoint_post_inscription);
+        let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
+        let _ = locations_to_insert.insert(
+            (inscription_data.ordinal_number, outpoint_to_watch),
+            OrdinalLocation {
+                offset,
+                block_height: block.block_identifier.index,
+                tx_index: inscription_data.tx_index,
+            },
+        );
+    }
+
+    for transfer_data in get_inscriptions_transferred_in_block(&block).iter() {
+        let (tx, output_index, offset) =
+            parse_satpoint_to_watch(&transfer_data.satpoint_post_transfer);
+        let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
+        let _ = locations_to_insert.insert(
+            (transfer_data.ordinal_number, outpoint_to_watch),
+            OrdinalLocation {
+                offset,
+                block_height: block.block_identifier.index,
+                tx_index: transfer_data.tx_index,
+            },
+        );
+    }
+
+    for ((ordinal_number, outpoint_to_watch), location_data) in locations_to_insert {
+        insert_ordinal_transfer_in_locations_tx(
+            ordinal_number,
+            &outpoint_to_watch,
+            location_data,
+            &inscriptions_db_conn_rw,
+            ctx,
+        );
+    }
+
+    insert_watched_outputs_from_block(block, inscriptions_db_conn_rw, ctx);
+    prune_spent_watched_outputs_from_block(block, inscriptions_db_conn_rw, ctx);
+    insert_miner_payouts_from_block(block, inscriptions_db_conn_rw, ctx);
+}
+
+/// Stores the address of the coinbase output each inscribed sat spent in fees in a block landed in, so that transfers
+/// to the miner carry a recipient. Sats landing past the coinbase outputs are lost, and sats paid to a script without
+/// address have no address to store.
+pub fn insert_miner_payouts_from_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let Some(coinbase_tx) = block.transactions.first() else {
+        return;
+    };
+    let network = get_bitcoin_network(&block.metadata.network);
+    for transfer in get_inscriptions_transferred_in_block(&block).into_iter() {
+        if !matches!(
+            transfer.destination,
+            OrdinalInscriptionTransferDestination::SpentInFees
+        ) {
+            continue;
+        }
+        let (tx_identifier, output_index, offset) =
+            parse_satpoint_to_watch(&transfer.satpoint_post_transfer);
+        if tx_identifier.get_hash_bytes_str()
+            != coinbase_tx.transaction_identifier.get_hash_bytes_str()
+        {
+            continue;
+        }
+        let Some(output) = coinbase_tx.metadata.outputs.get(output_index) else {
+            continue;
+        };
+        if offset >= output.value {
+            continue;
+        }
+        let Some(address) = script_hex_address(&output.get_script_pubkey_hex(), &network) else {
+            continue;
+        };
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "INSERT OR REPLACE INTO miner_payouts (satpoint, block_height, address) VALUES (?1, ?2, ?3)",
+            rusqlite::params![&transfer.satpoint_post_transfer, &block.block_identifier.index, &address],
+        ) {
+            try_warn!(ctx, "unable to update miner_payouts: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+/// Address of the miner payout a sat spent in fees landed in, stored when its block was indexed.
+pub fn find_miner_payout_address(
+    satpoint: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<String> {
+    let args: &[&dyn ToSql] = &[&satpoint.to_sql().unwrap()];
+    let query = "SELECT address FROM miner_payouts WHERE satpoint = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+/// Value and script of an output an inscription was revealed or moved to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedOutput {
+    pub value: u64,
+    pub script_pubkey: String,
+}
+
+/// Stores the value and script of the outputs inscriptions were revealed or moved to in a block, so that the inputs
+/// spending them can be resolved without asking bitcoind.
+pub fn insert_watched_outputs_from_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let satpoints = get_inscriptions_revealed_in_block(&block)
+        .into_iter()
+        .map(|reveal| &reveal.satpoint_post_inscription)
+        .chain(
+            get_inscriptions_transferred_in_block(&block)
+                .into_iter()
+                .map(|transfer| &transfer.satpoint_post_transfer),
+        )
+        .collect::<Vec<_>>();
+    if satpoints.is_empty() {
+        return;
+    }
+    let transactions = block
+        .transactions
+        .iter()
+        .map(|tx| (tx.transaction_identifier.get_hash_bytes_str(), tx))
+        .collect::<HashMap<_, _>>();
+    for satpoint in satpoints.into_iter() {
+        let (tx_identifier, output_index, _) = parse_satpoint_to_watch(satpoint);
+        let Some(output) = transactions
+            .get(tx_identifier.get_hash_bytes_str())
+            .and_then(|tx| tx.metadata.outputs.get(output_index))
+        else {
+            continue;
+        };
+        let outpoint = format_outpoint_to_watch(&tx_identifier, output_index);
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "INSERT OR REPLACE INTO watched_outputs (outpoint, block_height, value, script_pubkey) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![&outpoint, &block.block_identifier.index, &output.value, &output.script_pubkey],
+        ) {
+            try_warn!(ctx, "unable to update watched_outputs: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+/// Marks the watched outputs spent by the transfers of a block, and deletes the ones spent more than
+/// `SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS` blocks ago. Outputs spent recently are kept, for a re-org to make them
+/// unspent again.
+pub fn prune_spent_watched_outputs_from_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    for transfer in get_inscriptions_transferred_in_block(&block).into_iter() {
+        let (tx_identifier, output_index, _) =
+            parse_satpoint_to_watch(&transfer.satpoint_pre_transfer);
+        let outpoint = format_outpoint_to_watch(&tx_identifier, output_index);
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "UPDATE watched_outputs SET spent_block_height = ?1 WHERE outpoint = ?2 AND spent_block_height IS NULL",
+            rusqlite::params![&block.block_identifier.index, &outpoint],
+        ) {
+            try_warn!(ctx, "unable to update watched_outputs: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+    let Some(last_pruned_block_height) = block
+        .block_identifier
+        .index
+        .checked_sub(SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS)
+    else {
+        return;
+    };
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM watched_outputs WHERE spent_block_height <= ?1",
+        rusqlite::params![&last_pruned_block_height],
+    ) {
+        try_warn!(ctx, "unable to update watched_outputs: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Outputs of `outpoints` found in the watched outputs, by outpoint.
+pub fn find_watched_outputs(
+    outpoints: &[String],
+    db_conn: &Connection,
+    ctx: &Context,
+) -> HashMap<String, WatchedOutput> {
+    let mut watched_outputs = HashMap::new();
+    for outpoint in outpoints.iter() {
+        let args: &[&dyn ToSql] = &[&outpoint.to_sql().unwrap()];
+        let query = "SELECT value, script_pubkey FROM watched_outputs WHERE outpoint = ?";
+        if let Some(output) = perform_query_one(query, args, db_conn, ctx, |row| WatchedOutput {
+            value: row.get(0).unwrap(),
+            script_pubkey: row.get(1).unwrap(),
+        }) {
+            watched_outputs.insert(outpoint.clone(), output);
+        }
+    }
+    watched_outputs
+}
+
+/// Stores the content type detected from the content of an inscription next to the one it declared.
+pub fn insert_inscription_content_type(
+    inscription_id: &str,
+    declared_content_type: &str,
+    detected_content_type: &str,
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO inscription_content_types (inscription_id, block_height, declared_content_type, detected_content_type) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![&inscription_id, &block_identifier.index, &declared_content_type, &detected_content_type],
+    ) {
+        try_warn!(ctx, "unable to update inscription_content_types: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionContentTypes {
+    pub declared_content_type: String,
+    pub detected_content_type: String,
+    pub content_type_mismatch: bool,
+}
+
+pub fn find_inscription_content_types(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<InscriptionContentTypes> {
+    let args: &[&dyn ToSql] = &[&inscription_id.to_sql().unwrap()];
+    let query = "SELECT declared_content_type, detected_content_type FROM inscription_content_types WHERE inscription_id = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        let declared_content_type: String = row.get(0).unwrap();
+        let detected_content_type: String = row.get(1).unwrap();
+        InscriptionContentTypes {
+            content_type_mismatch: is_content_type_mismatch(
+                &declared_content_type,
+                &detected_content_type,
+            ),
+            declared_content_type,
+            detected_content_type,
+        }
+    })
+}
+
+/// Stores the reveal data that does not change once inscribed, so that events about the inscription can carry it.
+pub fn insert_inscription_genesis(
+    inscription_data: &OrdinalInscriptionRevealData,
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO inscription_genesis (inscription_id, block_height, content_type, parent, metaprotocol) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![&inscription_data.inscription_id, &block_identifier.index, &inscription_data.content_type, &inscription_data.parent, &inscription_data.metaprotocol],
+    ) {
+        try_warn!(ctx, "unable to update inscription_genesis: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionGenesis {
+    pub inscription_id: String,
+    pub jubilee_inscription_number: i64,
+    pub classic_inscription_number: i64,
+    pub block_height: u64,
+    /// Missing for inscriptions indexed before genesis data was stored.
+    pub content_type: Option<String>,
+    pub parent: Option<String>,
+    pub metaprotocol: Option<String>,
+}
+
+/// Genesis data of the inscriptions of a sat, in inscription number order.
+pub fn find_inscriptions_genesis_with_ordinal_number(
+    ordinal_number: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionGenesis> {
+    let args: &[&dyn ToSql] = &[&ordinal_number.to_sql().unwrap()];
+    let query = "SELECT i.inscription_id, i.jubilee_inscription_number, i.classic_inscription_number, i.block_height, g.content_type, g.parent, g.metaprotocol FROM inscriptions AS i LEFT JOIN inscription_genesis AS g ON g.inscription_id = i.inscription_id WHERE i.ordinal_number = ? ORDER BY i.jubilee_inscription_number";
+    perform_query_set(query, args, db_conn, ctx, |row| InscriptionGenesis {
+        inscription_id: row.get(0).unwrap(),
+        jubilee_inscription_number: row.get(1).unwrap(),
+        classic_inscription_number: row.get(2).unwrap(),
+        block_height: row.get(3).unwrap(),
+        content_type: row.get(4).unwrap(),
+        parent: row.get(5).unwrap(),
+        metaprotocol: row.get(6).unwrap(),
+    })
+}
+
+/// Content type an inscription declared when revealed. None for inscriptions indexed before genesis data was stored.
+pub fn find_inscription_genesis_content_type(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<String> {
+    let args: &[&dyn ToSql] = &[&inscription_id.to_sql().unwrap()];
+    let query = "SELECT content_type FROM inscription_genesis WHERE inscription_id = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+/// Stores the satributes of the sat an inscription was revealed on, so that inscriptions can be looked up by satribute.
+pub fn insert_inscription_satributes(
+    inscription_data: &OrdinalInscriptionRevealData,
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    for satribute in get_satributes(inscription_data.ordinal_number).iter() {
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "INSERT OR REPLACE INTO inscription_satributes (inscription_id, ordinal_number, block_height, satribute) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![&inscription_data.inscription_id, &inscription_data.ordinal_number, &block_identifier.index, &satribute],
+        ) {
+            try_warn!(ctx, "unable to update inscription_satributes: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionSatributeRow {
+    pub inscription_id: String,
+    pub ordinal_number: u64,
+    pub block_height: u64,
+}
+
+/// Inscriptions with a satribute, ordered by block height and inscription id, starting after `after` if given.
+pub fn find_inscriptions_with_satribute(
+    satribute: &str,
+    after: Option<&(u64, String)>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionSatributeRow> {
+    let after_block_height = after.map(|a| a.0);
+    let after_inscription_id = after.map(|a| a.1.as_str());
+    let args: &[&dyn ToSql] = &[
+        &satribute.to_sql().unwrap(),
+        &after_block_height.to_sql().unwrap(),
+        &after_inscription_id.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, ordinal_number, block_height FROM inscription_satributes
+        WHERE satribute = ?1 AND (?2 IS NULL OR (block_height, inscription_id) > (?2, ?3))
+        ORDER BY block_height, inscription_id LIMIT ?4 OFFSET ?5";
+    perform_query_set(query, args, db_conn, ctx, |row| InscriptionSatributeRow {
+        inscription_id: row.get(0).unwrap(),
+        ordinal_number: row.get(1).unwrap(),
+        block_height: row.get(2).unwrap(),
+    })
+}
+
+pub fn count_inscriptions_with_satribute(
+    satribute: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> u64 {
+    let args: &[&dyn ToSql] = &[&satribute.to_sql().unwrap()];
+    let query = "SELECT COUNT(*) FROM inscription_satributes WHERE satribute = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap()).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionContentScan {
+    /// `clean`, `flagged` or `error`.
+    pub verdict: String,
+    pub labels: Vec<String>,
+    pub scanned_at: u64,
+}
+
+/// Stores the verdict of the content scanner for an inscription, replacing any previous one.
+pub fn insert_inscription_content_scan(
+    inscription_id: &str,
+    block_height: u64,
+    scan: &InscriptionContentScan,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let labels = serde_json::to_string(&scan.labels).unwrap_or("[]".into());
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO inscription_content_scans (inscription_id, block_height, verdict, labels, scanned_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![&inscription_id, &block_height, &scan.verdict, &labels, &scan.scanned_at],
+    ) {
+        try_warn!(ctx, "unable to update inscription_content_scans: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_inscription_content_scan(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<InscriptionContentScan> {
+    let args: &[&dyn ToSql] = &[&inscription_id.to_sql().unwrap()];
+    let query = "SELECT verdict, labels, scanned_at FROM inscription_content_scans WHERE inscription_id = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        let labels: String = row.get(1).unwrap();
+        InscriptionContentScan {
+            verdict: row.get(0).unwrap(),
+            labels: serde_json::from_str(&labels).unwrap_or_default(),
+            scanned_at: row.get(2).unwrap(),
+        }
+    })
+}
+
+pub fn update_sequence_metadata_with_block(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let mut nth_classic_pos_number = find_nth_classic_pos_number_at_block_height(
+        &block.block_identifier.index,
+        inscriptions_db_conn_rw,
+        ctx,
+    )
+    .unwrap_or(0);
+    let mut nth_classic_neg_number = find_nth_classic_neg_number_at_block_height(
+        &block.block_identifier.index,
+        inscriptions_db_conn_rw,
+        ctx,
+    )
+    .unwrap_or(0);
+    let mut nth_jubilee_number = find_nth_jubilee_number_at_block_height(
+        &block.block_identifier.index,
+        inscriptions_db_conn_rw,
+        ctx,
+    )
+    .unwrap_or(0);
+    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+        nth_classic_pos_number =
+            nth_classic_pos_number.max(inscription_data.inscription_number.classic);
+        nth_classic_neg_number =
+            nth_classic_neg_number.min(inscription_data.inscription_number.classic);
+        nth_jubilee_number = nth_jubilee_number.max(inscription_data.inscription_number.jubilee);
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT INTO sequence_metadata (block_height, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![&block.block_identifier.index, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number],
+    ) {
+        try_warn!(ctx, "unable to update sequence_metadata: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Stores the hash of the ordinal events of a block, computed once the block is fully augmented.
+pub fn insert_block_events_hash(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let (events_hash, events_count) = compute_block_events_hash(block);
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO block_events_hashes (block_height, block_hash, events_hash, events_count) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![&block.block_identifier.index, &block.block_identifier.hash, &events_hash, &events_count],
+    ) {
+        try_warn!(ctx, "unable to update block_events_hashes: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Records an inscription revealed unbound. Unbound inscriptions have no sat, and get no location.
+pub fn insert_unbound_inscription(
+    inscription_id: &str,
+    unbound_sequence: u64,
+    block_height: u64,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO unbound_inscriptions (inscription_id, block_height, unbound_sequence) VALUES (?1, ?2, ?3)",
+        rusqlite::params![&inscription_id, &block_height, &unbound_sequence],
+    ) {
+        try_warn!(ctx, "unable to update unbound_inscriptions: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Last unbound sequence assigned before `block_height`.
+pub fn find_nth_unbound_sequence_at_block_height(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT MAX(unbound_sequence) FROM unbound_inscriptions WHERE block_height < ?";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        row.get::<_, Option<u64>>(0).unwrap()
+    })
+    .flatten()
+}
+
+/// Unbound sequences of the inscriptions revealed unbound in a block, by inscription id.
+pub fn find_unbound_inscriptions_in_block(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> HashMap<String, u64> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query =
+        "SELECT inscription_id, unbound_sequence FROM unbound_inscriptions WHERE block_height = ?";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        let inscription_id: String = row.get(0).unwrap();
+        let unbound_sequence: u64 = row.get(1).unwrap();
+        (inscription_id, unbound_sequence)
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Stores the timestamp of a block, so that date ranges can be resolved to block heights.
+pub fn insert_block_timestamp(
+    block: &BitcoinBlockData,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO block_timestamps (block_height, timestamp) VALUES (?1, ?2)",
+        rusqlite::params![&block.block_identifier.index, &block.timestamp],
+    ) {
+        try_warn!(ctx, "unable to update block_timestamps: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Block heights covering the blocks mined between two timestamps, both inclusive: the first block mined at or after
+/// `from_timestamp` and the last block mined at or before `to_timestamp`. Returns `None` when no block was indexed in
+/// the range, and an error when the range starts before the first block with a timestamp while earlier blocks were
+/// indexed by a previous release, which did not store timestamps.
+pub fn find_block_height_range_for_timestamps(
+    from_timestamp: Option<u64>,
+    to_timestamp: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<(u64, u64)>, String> {
+    let first_inscription_block_height = perform_query_one(
+        "SELECT MIN(block_height) FROM inscriptions",
+        &[],
+        db_conn,
+        ctx,
+        |row| row.get::<_, Option<u64>>(0).unwrap(),
+    )
+    .flatten();
+    let first_block_timestamp = perform_query_one(
+        "SELECT block_height, timestamp FROM block_timestamps ORDER BY block_height ASC LIMIT 1",
+        &[],
+        db_conn,
+        ctx,
+        |row| (row.get::<_, u64>(0).unwrap(), row.get::<_, u64>(1).unwrap()),
+    );
+    if let Some(first_inscription_block_height) = first_inscription_block_height {
+        let covered = match first_block_timestamp {
+            Some((block_height, _)) if block_height <= first_inscription_block_height => true,
+            Some((_, timestamp)) => matches!(from_timestamp, Some(from) if from >= timestamp),
+            None => false,
+        };
+        if !covered {
+            return Err(match first_block_timestamp {
+                Some((block_height, _)) => format!(
+                    "block timestamps are only known from block #{block_height}, blocks indexed earlier can't be filtered by date"
+                ),
+                None => "block timestamps are unknown, blocks indexed by a previous release can't be filtered by date".to_string(),
+            });
+        }
+    }
+    let args: &[&dyn ToSql] = &[
+        &from_timestamp.to_sql().unwrap(),
+        &to_timestamp.to_sql().unwrap(),
+    ];
+    let query = "SELECT MIN(block_height), MAX(block_height) FROM block_timestamps
+        WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2)";
+    Ok(perform_query_one(query, args, db_conn, ctx, |row| {
+        let from: Option<u64> = row.get(0).unwrap();
+        let to: Option<u64> = row.get(1).unwrap();
+        from.zip(to)
+    })
+    .flatten())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEventsHash {
+    pub block_height: u64,
+    pub block_hash: String,
+    pub events_hash: String,
+    pub events_count: u64,
+}
+
+pub fn find_block_events_hash(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<BlockEventsHash> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT block_height, block_hash, events_hash, events_count FROM block_events_hashes WHERE block_height = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| BlockEventsHash {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        events_hash: row.get(2).unwrap(),
+        events_count: row.get(3).unwrap(),
+    })
+}
+
+/// Latest block hash recorded for the ordinal events, i.e. the tip of the chain as seen by ordhook.
+pub fn find_last_block_events_hash(db_conn: &Connection, ctx: &Context) -> Option<BlockEventsHash> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT block_height, block_hash, events_hash, events_count FROM block_events_hashes ORDER BY block_height DESC LIMIT 1";
+    perform_query_one(query, args, db_conn, ctx, |row| BlockEventsHash {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        events_hash: row.get(2).unwrap(),
+        events_count: row.get(3).unwrap(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReorgEvent {
+    pub block_height: u64,
+    pub block_hash: String,
+    pub rolled_back_at: u64,
+}
+
+/// Records a block rolled back during a re-org. Entries are kept across rollbacks, for diagnostics.
+pub fn insert_reorg_event(
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    let rolled_back_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT INTO reorg_events (block_height, block_hash, rolled_back_at) VALUES (?1, ?2, ?3)",
+        rusqlite::params![
+            &block_identifier.index,
+            &block_identifier.hash,
+            &rolled_back_at
+        ],
+    ) {
+        try_warn!(ctx, "unable to update reorg_events: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_latest_reorg_events(
+    limit: usize,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<ReorgEvent> {
+    let args: &[&dyn ToSql] = &[&limit.to_sql().unwrap()];
+    let query = "SELECT block_height, block_hash, rolled_back_at FROM reorg_events ORDER BY rowid DESC LIMIT ?";
+    perform_query_set(query, args, db_conn, ctx, |row| ReorgEvent {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        rolled_back_at: row.get(2).unwrap(),
+    })
+}
+
+/// Block which processing panicked. Entries are kept once the block gets processed, for diagnostics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuarantinedBlock {
+    pub block_height: u64,
+    pub block_hash: String,
+    pub error: String,
+    pub attempts: u32,
+    /// The block was given up on: its inscriptions and transfers are missing from the index.
+    pub skipped: bool,
+    pub last_failed_at: u64,
+}
+
+pub fn insert_quarantined_block(
+    quarantined_block: &QuarantinedBlock,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO quarantined_blocks (block_height, block_hash, error, attempts, skipped, last_failed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            &quarantined_block.block_height,
+            &quarantined_block.block_hash,
+            &quarantined_block.error,
+            &quarantined_block.attempts,
+            &quarantined_block.skipped,
+            &quarantined_block.last_failed_at
+        ],
+    ) {
+        try_warn!(ctx, "unable to update quarantined_blocks: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_quarantined_blocks(db_conn: &Connection, ctx: &Context) -> Vec<QuarantinedBlock> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT block_height, block_hash, error, attempts, skipped, last_failed_at FROM quarantined_blocks ORDER BY block_height ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| QuarantinedBlock {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        error: row.get(2).unwrap(),
+        attempts: row.get(3).unwrap(),
+        skipped: row.get(4).unwrap(),
+        last_failed_at: row.get(5).unwrap(),
+    })
+}
+
+/// Network the databases were created for, recorded on first start.
+pub fn find_db_network(db_conn: &Connection, ctx: &Context) -> Option<String> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT value FROM db_metadata WHERE key = 'network'";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+pub fn insert_db_network(network: &str, inscriptions_db_conn_rw: &Connection, ctx: &Context) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO db_metadata (key, value) VALUES ('network', ?1)",
+        rusqlite::params![&network],
+    ) {
+        try_warn!(ctx, "unable to update db_metadata: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_db_jubilee_height(db_conn: &Connection, ctx: &Context) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT value FROM db_metadata WHERE key = 'jubilee_height'";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        row.get::<_, String>(0).unwrap().parse::<u64>().unwrap_or(0)
+    })
+}
+
+pub fn insert_db_jubilee_height(
+    jubilee_height: u64,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO db_metadata (key, value) VALUES ('jubilee_height', ?1)",
+        rusqlite::params![jubilee_height.to_string()],
+    ) {
+        try_warn!(ctx, "unable to update db_metadata: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexCommitmentLeaf {
+    pub inscription_number: i64,
+    pub inscription_id: String,
+    pub location: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexCommitmentRow {
+    pub block_height: u64,
+    pub block_hash: String,
+    pub root: String,
+    pub leaves_count: u64,
+}
+
+/// Query of the index commitment leaves of the inscriptions revealed up to `?1` and matching `filter`, with their
+/// location at that height.
+fn get_index_commitment_leaves_query(filter: &str) -> String {
+    format!(
+        "
+        SELECT i.jubilee_inscription_number, i.inscription_id, COALESCE((
+            SELECT '{UNBOUND_INSCRIPTIONS_TXID}:0:' || u.unbound_sequence
+            FROM unbound_inscriptions AS u
+            WHERE u.inscription_id = i.inscription_id
+        ), (
+            SELECT l.outpoint_to_watch || ':' || l.offset
+            FROM locations AS l
+            WHERE l.ordinal_number = i.ordinal_number AND l.block_height <= ?1
+            ORDER BY l.block_height DESC, l.tx_index DESC
+            LIMIT 1
+        ), '')
+        FROM inscriptions AS i
+        WHERE i.block_height <= ?1 {filter}
+        ORDER BY i.jubilee_inscription_number ASC
+    "
+    )
+}
+
+fn map_index_commitment_leaf(row: &rusqlite::Row<'_>) -> IndexCommitmentLeaf {
+    IndexCommitmentLeaf {
+        inscription_number: row.get(0).unwrap(),
+        inscription_id: row.get(1).unwrap(),
+        location: row.get(2).unwrap(),
+    }
+}
+
+/// Inscriptions revealed up to `block_height`, ordered by inscription number, with their location at that height.
+pub fn find_index_commitment_leaves(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<IndexCommitmentLeaf> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = get_index_commitment_leaves_query("");
+    perform_query_set(&query, args, db_conn, ctx, map_index_commitment_leaf)
+}
+
+/// Leaves changed by a block: the inscriptions it revealed, and the ones on the sats it transferred.
+pub fn find_index_commitment_leaves_changed_in_block(
+    block_height: u64,
+    transferred_ordinal_numbers: &BTreeSet<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<IndexCommitmentLeaf> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = get_index_commitment_leaves_query("AND i.block_height = ?1");
+    let mut leaves = perform_query_set(&query, args, db_conn, ctx, map_index_commitment_leaf);
+    let query = get_index_commitment_leaves_query("AND i.ordinal_number = ?2");
+    for ordinal_number in transferred_ordinal_numbers.iter() {
+        let args: &[&dyn ToSql] = &[
+            &block_height.to_sql().unwrap(),
+            &ordinal_number.to_sql().unwrap(),
+        ];
+        leaves.extend(perform_query_set(
+            &query,
+            args,
+            db_conn,
+            ctx,
+            map_index_commitment_leaf,
+        ));
+    }
+    leaves
+}
+
+/// Stores the hashes of leaves of the index commitment at the last block indexed, replacing their previous version.
+pub fn insert_index_commitment_leaves(
+    leaves: &[(IndexCommitmentLeaf, [u8; 32])],
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    for (leaf, leaf_hash) in leaves.iter() {
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "INSERT OR REPLACE INTO index_commitment_leaves (inscription_id, inscription_number, leaf_hash) VALUES (?1, ?2, ?3)",
+            rusqlite::params![&leaf.inscription_id, &leaf.inscription_number, &leaf_hash[..]],
+        ) {
+            try_warn!(ctx, "unable to update index_commitment_leaves: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+/// Drops the stored leaves, for them to be rebuilt from the whole index.
+pub fn delete_index_commitment_leaves(inscriptions_db_conn_rw: &Connection, ctx: &Context) {
+    while let Err(e) = inscriptions_db_conn_rw.execute_batch(
+        "DELETE FROM index_commitment_leaves; DELETE FROM db_metadata WHERE key = 'index_commitment_leaves_block_height';",
+    ) {
+        try_warn!(ctx, "unable to update index_commitment_leaves: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Block the stored leaves are up to date with. None when they have to be rebuilt.
+pub fn find_index_commitment_leaves_block_height(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT value FROM db_metadata WHERE key = 'index_commitment_leaves_block_height'";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        row.get::<_, String>(0).unwrap().parse::<u64>().ok()
+    })
+    .flatten()
+}
+
+pub fn insert_index_commitment_leaves_block_height(
+    block_height: u64,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "INSERT OR REPLACE INTO db_metadata (key, value) VALUES ('index_commitment_leaves_block_height', ?1)",
+        rusqlite::params![block_height.to_string()],
+    ) {
+        try_warn!(ctx, "unable to update db_metadata: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Streams the stored leaf hashes, ordered by inscription number, without loading them all in memory.
+pub fn for_each_index_commitment_leaf_hash<F>(
+    db_conn: &Connection,
+    mut f: F,
+) -> Result<(), OrdhookError>
+where
+    F: FnMut([u8; 32]),
+{
+    let mut stmt = db_conn
+        .prepare("SELECT leaf_hash FROM index_commitment_leaves ORDER BY inscription_number ASC")
+        .map_err(|e| OrdhookError::Db(e.to_string()))?;
+    let mut rows = stmt
+        .query([])
+        .map_err(|e| OrdhookError::Db(e.to_string()))?;
+    while let Some(row) = rows.next().map_err(|e| OrdhookError::Db(e.to_string()))? {
+        let leaf_hash: Vec<u8> = row.get(0).map_err(|e| OrdhookError::Db(e.to_string()))?;
+        let leaf_hash = <[u8; 32]>::try_from(leaf_hash)
+            .map_err(|_| OrdhookError::Db("invalid index commitment leaf hash".into()))?;
+        f(leaf_hash);
     }
+    Ok(())
+}
 
-    for ((ordinal_number, outpoint_to_watch), location_data) in locations_to_insert {
-        insert_ordinal_transfer_in_locations_tx(
-            ordinal_number,
-            &outpoint_to_watch,
-            location_data,
-            &inscriptions_db_conn_rw,
-            ctx,
-        );
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct InscriptionLocationRow {
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    /// None for unbound inscriptions.
+    pub ordinal_number: Option<u64>,
+    pub block_height: u64,
+    pub location: String,
 }
 
-pub fn update_sequence_metadata_with_block(
-    block: &BitcoinBlockData,
+/// Location (`<outpoint>:<offset>`) of an inscription, looked up by id or by jubilee number, as of `at_block_height`
+/// (defaults to the latest block indexed). Returns None if the inscription was revealed after that block.
+pub fn find_inscription_location(
+    inscription: &str,
+    at_block_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<InscriptionLocationRow> {
+    let inscription_number = inscription.parse::<i64>().ok();
+    let (filter, arg) = match inscription_number {
+        Some(ref number) => (
+            "i.jubilee_inscription_number = ?1",
+            number.to_sql().unwrap(),
+        ),
+        None => ("i.inscription_id = ?1", inscription.to_sql().unwrap()),
+    };
+    let at_block_height = at_block_height
+        .map(|height| height as i64)
+        .unwrap_or(i64::MAX);
+    let args: &[&dyn ToSql] = &[&arg, &at_block_height.to_sql().unwrap()];
+    let query = format!(
+        "
+        SELECT i.inscription_id, i.jubilee_inscription_number, i.ordinal_number, i.block_height, COALESCE((
+            SELECT '{UNBOUND_INSCRIPTIONS_TXID}:0:' || u.unbound_sequence
+            FROM unbound_inscriptions AS u
+            WHERE u.inscription_id = i.inscription_id
+        ), (
+            SELECT l.outpoint_to_watch || ':' || l.offset
+            FROM locations AS l
+            WHERE l.ordinal_number = i.ordinal_number AND l.block_height <= ?2
+            ORDER BY l.block_height DESC, l.tx_index DESC
+            LIMIT 1
+        ), '')
+        FROM inscriptions AS i
+        WHERE {filter} AND i.block_height <= ?2
+    "
+    );
+    perform_query_one(&query, args, db_conn, ctx, |row| InscriptionLocationRow {
+        inscription_id: row.get(0).unwrap(),
+        inscription_number: row.get(1).unwrap(),
+        ordinal_number: row.get(2).unwrap(),
+        block_height: row.get(3).unwrap(),
+        location: row.get(4).unwrap(),
+    })
+}
+
+/// Inscriptions held by an unspent output, with their location in it. Sats of an unspent output can't have moved, every
+/// inscription ever located at it is still there.
+pub fn find_inscriptions_at_outpoint(
+    outpoint: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionLocationRow> {
+    let args: &[&dyn ToSql] = &[&outpoint.to_sql().unwrap()];
+    let query = "
+        SELECT i.inscription_id, i.jubilee_inscription_number, i.ordinal_number, i.block_height, l.outpoint_to_watch || ':' || l.offset
+        FROM locations AS l
+        INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+        WHERE l.outpoint_to_watch = ?
+        ORDER BY l.offset, i.jubilee_inscription_number
+    ";
+    perform_query_set(query, args, db_conn, ctx, |row| InscriptionLocationRow {
+        inscription_id: row.get(0).unwrap(),
+        inscription_number: row.get(1).unwrap(),
+        ordinal_number: row.get(2).unwrap(),
+        block_height: row.get(3).unwrap(),
+        location: row.get(4).unwrap(),
+    })
+}
+
+/// Inscriptions revealed or moved in `(from_block_height, to_block_height]`, with their location at
+/// `to_block_height`. Inscriptions moved several times in the range are only returned once.
+pub fn find_inscription_changes_in_block_range(
+    from_block_height: u64,
+    to_block_height: u64,
+    after_inscription_number: Option<i64>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionLocationRow> {
+    let args: &[&dyn ToSql] = &[
+        &from_block_height.to_sql().unwrap(),
+        &to_block_height.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+        &after_inscription_number.to_sql().unwrap(),
+    ];
+    let query = format!(
+        "
+        SELECT i.inscription_id, i.jubilee_inscription_number, i.ordinal_number, i.block_height, COALESCE((
+            SELECT '{UNBOUND_INSCRIPTIONS_TXID}:0:' || u.unbound_sequence
+            FROM unbound_inscriptions AS u
+            WHERE u.inscription_id = i.inscription_id
+        ), (
+            SELECT l.outpoint_to_watch || ':' || l.offset
+            FROM locations AS l
+            WHERE l.ordinal_number = i.ordinal_number AND l.block_height <= ?2
+            ORDER BY l.block_height DESC, l.tx_index DESC
+            LIMIT 1
+        ), '')
+        FROM inscriptions AS i
+        WHERE i.block_height <= ?2 AND (?5 IS NULL OR i.jubilee_inscription_number > ?5) AND (
+            i.block_height > ?1 OR EXISTS (
+                SELECT 1 FROM locations AS l
+                WHERE l.ordinal_number = i.ordinal_number AND l.block_height > ?1 AND l.block_height <= ?2
+            )
+        )
+        ORDER BY i.jubilee_inscription_number ASC
+        LIMIT ?3 OFFSET ?4
+    "
+    );
+    perform_query_set(&query, args, db_conn, ctx, |row| InscriptionLocationRow {
+        inscription_id: row.get(0).unwrap(),
+        inscription_number: row.get(1).unwrap(),
+        ordinal_number: row.get(2).unwrap(),
+        block_height: row.get(3).unwrap(),
+        location: row.get(4).unwrap(),
+    })
+}
+
+pub fn insert_index_commitment(
+    commitment: &IndexCommitmentRow,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
 ) {
-    let mut nth_classic_pos_number = find_nth_classic_pos_number_at_block_height(
-        &block.block_identifier.index,
-        inscriptions_db_conn_rw,
-        ctx,
-    )
-    .unwrap_or(0);
-    let mut nth_classic_neg_number = find_nth_classic_neg_number_at_block_height(
-        &block.block_identifier.index,
-        inscriptions_db_conn_rw,
-        ctx,
-    )
-    .unwrap_or(0);
-    let mut nth_jubilee_number = find_nth_jubilee_number_at_block_height(
-        &block.block_identifier.index,
-        inscriptions_db_conn_rw,
-        ctx,
-    )
-    .unwrap_or(0);
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
-        nth_classic_pos_number =
-            nth_classic_pos_number.max(inscription_data.inscription_number.classic);
-        nth_classic_neg_number =
-            nth_classic_neg_number.min(inscription_data.inscription_number.classic);
-        nth_jubilee_number = nth_jubilee_number.max(inscription_data.inscription_number.jubilee);
-    }
     while let Err(e) = inscriptions_db_conn_rw.execute(
-        "INSERT INTO sequence_metadata (block_height, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number) VALUES (?1, ?2, ?3, ?4)",
-        rusqlite::params![&block.block_identifier.index, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number],
+        "INSERT OR REPLACE INTO index_commitments (block_height, block_hash, root, leaves_count) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![&commitment.block_height, &commitment.block_hash, &commitment.root, &commitment.leaves_count],
     ) {
-        try_warn!(ctx, "unable to update sequence_metadata: {}", e.to_string());
+        try_warn!(ctx, "unable to update index_commitments: {}", e.to_string());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
 }
 
+/// Commitment stored at `block_height`, or the latest one if none is given.
+pub fn find_index_commitment(
+    block_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<IndexCommitmentRow> {
+    let mapping = |row: &rusqlite::Row<'_>| IndexCommitmentRow {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        root: row.get(2).unwrap(),
+        leaves_count: row.get(3).unwrap(),
+    };
+    match block_height {
+        Some(block_height) => {
+            let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+            let query = "SELECT block_height, block_hash, root, leaves_count FROM index_commitments WHERE block_height = ?";
+            perform_query_one(query, args, db_conn, ctx, mapping)
+        }
+        None => {
+            let args: &[&dyn ToSql] = &[];
+            let query = "SELECT block_height, block_hash, root, leaves_count FROM index_commitments ORDER BY block_height DESC LIMIT 1";
+            perform_query_one(query, args, db_conn, ctx, mapping)
+        }
+    }
+}
+
+/// Latest commitment stored at or below `block_height`, or the latest one if none is given.
+pub fn find_index_commitment_at_height(
+    block_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<IndexCommitmentRow> {
+    let Some(block_height) = block_height else {
+        return find_index_commitment(None, db_conn, ctx);
+    };
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT block_height, block_hash, root, leaves_count FROM index_commitments WHERE block_height <= ? ORDER BY block_height DESC LIMIT 1";
+    perform_query_one(query, args, db_conn, ctx, |row| IndexCommitmentRow {
+        block_height: row.get(0).unwrap(),
+        block_hash: row.get(1).unwrap(),
+        root: row.get(2).unwrap(),
+        leaves_count: row.get(3).unwrap(),
+    })
+}
+
 pub fn insert_ordinal_transfer_in_locations_tx(
     ordinal_number: u64,
     outpoint_to_watch: &str,
@@ -444,6 +2114,7 @@ where
     loop {
         let mut stmt = match db_conn.prepare(query) {
             Ok(stmt) => stmt,
+            Err(e) if is_interrupted(&e) => break,
             Err(e) => {
                 try_warn!(ctx, "unable to prepare query {query}: {}", e.to_string());
                 std::thread::sleep(std::time::Duration::from_secs(5));
@@ -462,6 +2133,7 @@ where
                         }
                     }
                     Ok(None) => break,
+                    Err(e) if is_interrupted(&e) => break,
                     Err(e) => {
                         try_warn!(
                             ctx,
@@ -473,6 +2145,7 @@ where
                     }
                 }
             },
+            Err(e) if is_interrupted(&e) => {}
             Err(e) => {
                 try_warn!(ctx, "unable to execute query {query}: {}", e.to_string());
                 std::thread::sleep(std::time::Duration::from_secs(5));
@@ -484,6 +2157,21 @@ where
     results
 }
 
+/// Statements interrupted by a `QueryDeadline` are not retried: their partial results are returned, and discarded
+/// by the caller.
+fn is_interrupted(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::OperationInterrupted,
+                ..
+            },
+            _
+        )
+    )
+}
+
 pub fn get_any_entry_in_ordinal_activities(
     block_height: &u64,
     db_conn: &Connection,
@@ -502,7 +2190,7 @@ pub fn get_any_entry_in_ordinal_activities(
 pub fn find_latest_inscription_block_height(
     db_conn: &Connection,
     ctx: &Context,
-) -> Result<Option<u64>, String> {
+) -> Result<Option<u64>, OrdhookError> {
     let args: &[&dyn ToSql] = &[];
     let query = "SELECT block_height FROM sequence_metadata ORDER BY block_height DESC LIMIT 1";
     let entry = perform_query_one(query, args, db_conn, ctx, |row| {
@@ -516,7 +2204,7 @@ pub fn find_initial_inscription_transfer_data(
     ordinal_number: &u64,
     db_conn: &Connection,
     ctx: &Context,
-) -> Result<Option<TransferData>, String> {
+) -> Result<Option<TransferData>, OrdhookError> {
     let args: &[&dyn ToSql] = &[&ordinal_number.to_sql().unwrap()];
     let query = "SELECT outpoint_to_watch, offset, tx_index FROM locations WHERE ordinal_number = ? ORDER BY block_height ASC, tx_index ASC LIMIT 1";
     let entry = perform_query_one(query, args, db_conn, ctx, |row| {
@@ -539,7 +2227,7 @@ pub fn find_latest_inscription_transfer_data(
     ordinal_number: &u64,
     db_conn: &Connection,
     ctx: &Context,
-) -> Result<Option<TransferData>, String> {
+) -> Result<Option<TransferData>, OrdhookError> {
     let args: &[&dyn ToSql] = &[&ordinal_number.to_sql().unwrap()];
     let query = "SELECT outpoint_to_watch, offset, tx_index FROM locations WHERE ordinal_number = ? ORDER BY block_height DESC, tx_index DESC LIMIT 1";
     let entry = perform_query_one(query, args, db_conn, ctx, |row| {
@@ -558,6 +2246,31 @@ pub fn find_latest_inscription_transfer_data(
     Ok(entry)
 }
 
+/// Location of an ordinal before `block_height`, i.e. where a re-org rolling back `block_height` moves it back to.
+pub fn find_inscription_transfer_data_before_block_height(
+    ordinal_number: u64,
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<TransferData> {
+    let args: &[&dyn ToSql] = &[
+        &ordinal_number.to_sql().unwrap(),
+        &block_height.to_sql().unwrap(),
+    ];
+    let query = "SELECT outpoint_to_watch, offset, tx_index FROM locations WHERE ordinal_number = ? AND block_height < ? ORDER BY block_height DESC, tx_index DESC LIMIT 1";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        let outpoint_to_watch: String = row.get(0).unwrap();
+        let (transaction_identifier_location, output_index) =
+            parse_outpoint_to_watch(&outpoint_to_watch);
+        TransferData {
+            transaction_identifier_location,
+            output_index,
+            inscription_offset_intra_output: row.get(1).unwrap(),
+            tx_index: row.get(2).unwrap(),
+        }
+    })
+}
+
 pub fn find_latest_transfers_block_height(db_conn: &Connection, ctx: &Context) -> Option<u64> {
     let args: &[&dyn ToSql] = &[];
     let query = "SELECT block_height FROM locations ORDER BY block_height DESC LIMIT 1";
@@ -771,11 +2484,46 @@ pub fn find_blessed_inscription_with_ordinal_number(
     })
 }
 
+/// Ids of all the inscriptions of a sat, blessed and cursed, in inscription order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevealedInscription {
+    pub inscription_id: String,
+    /// None for unbound inscriptions.
+    pub ordinal_number: Option<u64>,
+    pub jubilee_inscription_number: i64,
+    pub classic_inscription_number: i64,
+}
+
+pub fn find_inscriptions_revealed_in_block(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<RevealedInscription> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number FROM inscriptions WHERE block_height = ? ORDER BY jubilee_inscription_number ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| RevealedInscription {
+        inscription_id: row.get(0).unwrap(),
+        ordinal_number: row.get(1).unwrap(),
+        jubilee_inscription_number: row.get(2).unwrap(),
+        classic_inscription_number: row.get(3).unwrap(),
+    })
+}
+
+pub fn find_inscription_ids_with_ordinal_number(
+    ordinal_number: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<String> {
+    let args: &[&dyn ToSql] = &[&ordinal_number.to_sql().unwrap()];
+    let query = "SELECT inscription_id FROM inscriptions WHERE ordinal_number = ? ORDER BY jubilee_inscription_number";
+    perform_query_set(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
 pub fn find_inscription_with_id(
     inscription_id: &str,
     db_conn: &Connection,
     ctx: &Context,
-) -> Result<Option<(TraversalResult, u64)>, String> {
+) -> Result<Option<(TraversalResult, u64)>, OrdhookError> {
     let args: &[&dyn ToSql] = &[&inscription_id.to_sql().unwrap()];
     let query = "SELECT classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index FROM inscriptions WHERE inscription_id = ?";
     let entry = perform_query_one(query, args, db_conn, ctx, move |row| {
@@ -783,7 +2531,8 @@ pub fn find_inscription_with_id(
             classic: row.get(0).unwrap(),
             jubilee: row.get(1).unwrap(),
         };
-        let ordinal_number: u64 = row.get(2).unwrap();
+        // Traversals of unbound inscriptions point to sat 0, like the inscriptions revealed in the block being indexed.
+        let ordinal_number: u64 = row.get::<_, Option<u64>>(2).unwrap().unwrap_or(0);
         let block_height: u64 = row.get(3).unwrap();
         let inscription_input_index: usize = row.get(4).unwrap();
         let (transaction_identifier_inscription, _) = parse_inscription_id(inscription_id);
@@ -804,9 +2553,9 @@ pub fn find_inscription_with_id(
         block_height,
     )) = entry
     else {
-        return Err(format!(
+        return Err(OrdhookError::Db(format!(
             "unable to retrieve inscription for {inscription_id}"
-        ));
+        )));
     };
 
     Ok(Some((
@@ -856,7 +2605,9 @@ pub fn find_all_inscriptions_in_block(
                     classic: row.get(0).unwrap(),
                     jubilee: row.get(1).unwrap(),
                 };
-                let ordinal_number: u64 = row.get(2).unwrap();
+                // Traversals of unbound inscriptions point to sat 0, like the inscriptions revealed in the block being
+                // indexed.
+                let ordinal_number: u64 = row.get::<_, Option<u64>>(2).unwrap().unwrap_or(0);
                 let inscription_id: String = row.get(3).unwrap();
                 let inscription_input_index: usize = row.get(4).unwrap();
                 let (transaction_identifier_inscription, _) =
@@ -930,6 +2681,78 @@ pub fn delete_inscriptions_in_block_range(
         try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM index_commitments WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    // The leaves of the next commitment get rebuilt from the remaining blocks.
+    delete_index_commitment_leaves(inscriptions_db_conn_rw, ctx);
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM block_events_hashes WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM inscription_content_types WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM inscription_satributes WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM inscription_genesis WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM block_timestamps WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM inscription_content_scans WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM watched_outputs WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "UPDATE watched_outputs SET spent_block_height = NULL WHERE spent_block_height >= ?1 AND spent_block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_conn_rw.execute(
+        "DELETE FROM miner_payouts WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
 }
 
 pub fn remove_entry_from_inscriptions(
@@ -965,4 +2788,285 @@ pub fn remove_entries_from_locations_at_block_height(
         try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
+    delete_index_commitment_leaves(inscriptions_db_rw_conn, ctx);
+    while let Err(e) = inscriptions_db_rw_conn.execute(
+        "DELETE FROM watched_outputs WHERE block_height = ?1",
+        rusqlite::params![&block_height],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_rw_conn.execute(
+        "UPDATE watched_outputs SET spent_block_height = NULL WHERE spent_block_height = ?1",
+        rusqlite::params![&block_height],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = inscriptions_db_rw_conn.execute(
+        "DELETE FROM miner_payouts WHERE block_height = ?1",
+        rusqlite::params![&block_height],
+    ) {
+        try_warn!(ctx, "unable to query hord.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use chainhook_sdk::{
+        types::{
+            OrdinalInscriptionTransferData, OrdinalInscriptionTransferDestination, OrdinalOperation,
+        },
+        utils::Context,
+    };
+    use rusqlite::Connection;
+
+    use crate::core::test_builders::{TestBlockBuilder, TestTransactionBuilder, TestTxOutBuilder};
+
+    use super::{
+        create_or_open_readwrite_db, delete_inscriptions_in_block_range,
+        find_block_height_range_for_timestamps, find_nth_unbound_sequence_at_block_height,
+        find_unbound_inscriptions_in_block, find_watched_outputs, format_unbound_satpoint,
+        initialize_ordinals_db, insert_block_timestamp, insert_unbound_inscription,
+        insert_watched_outputs_from_block, open_existing_readonly_db_snapshot,
+        parse_unbound_satpoint, perform_query_one, prune_spent_watched_outputs_from_block,
+        WatchedOutput, SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS,
+    };
+
+    #[test]
+    fn serves_reads_from_snapshots() {
+        let ctx = Context::empty();
+        let db_path = PathBuf::from("tmp/snapshots/hord.sqlite");
+        let _ = std::fs::remove_dir_all("tmp/snapshots");
+        assert!(open_existing_readonly_db_snapshot(&db_path, &ctx).is_err());
+        let writer = create_or_open_readwrite_db(Some(&db_path), &ctx);
+        writer
+            .execute_batch("CREATE TABLE items (id INTEGER); INSERT INTO items VALUES (1);")
+            .unwrap();
+        let count = |conn: &Connection| {
+            perform_query_one("SELECT COUNT(*) FROM items", &[], conn, &ctx, |row| {
+                row.get::<_, u64>(0).unwrap()
+            })
+        };
+
+        let snapshot = open_existing_readonly_db_snapshot(&db_path, &ctx).unwrap();
+        assert_eq!(count(&snapshot), Some(1));
+        writer.execute("INSERT INTO items VALUES (2)", []).unwrap();
+        assert_eq!(count(&snapshot), Some(1));
+        assert!(snapshot
+            .execute("INSERT INTO items VALUES (3)", [])
+            .is_err());
+        drop(snapshot);
+
+        let snapshot = open_existing_readonly_db_snapshot(&db_path, &ctx).unwrap();
+        assert_eq!(count(&snapshot), Some(2));
+    }
+
+    #[test]
+    fn watches_outputs_holding_inscriptions() {
+        let ctx = Context::empty();
+        let base_dir = PathBuf::from("tmp/watched_outputs");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        std::fs::create_dir_all(&base_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&base_dir, &ctx);
+
+        let txid = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+        let block = TestBlockBuilder::new()
+            .height(840_000)
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_output(TestTxOutBuilder::new().value(10_000).build())
+                    .add_output(TestTxOutBuilder::new().value(546).build())
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 300144140535834,
+                            destination: OrdinalInscriptionTransferDestination::Transferred(
+                                "bc1pcwway0ne322s0lrc5e905f3chuclvnyy3z6wn86azkgmgcprf3tqvyy7ws"
+                                    .to_string(),
+                            ),
+                            satpoint_pre_transfer:
+                                "ab2683db34e335c89a5c1d634e6c5bd8d8bca8ded281be84f71f921c9e8783b2:0:0"
+                                    .to_string(),
+                            satpoint_post_transfer: format!("{txid}:1:0"),
+                            post_transfer_output_value: Some(546),
+                            tx_index: 0,
+                        },
+                    ))
+                    .build(),
+            )
+            .build();
+        insert_watched_outputs_from_block(&block, &db_conn, &ctx);
+
+        let outpoints = vec![format!("{txid}:0"), format!("{txid}:1")];
+        let watched_outputs = find_watched_outputs(&outpoints, &db_conn, &ctx);
+        assert_eq!(watched_outputs.len(), 1);
+        assert_eq!(
+            watched_outputs.get(&outpoints[1]),
+            Some(&WatchedOutput {
+                value: 546,
+                script_pubkey: TestTxOutBuilder::new().build().script_pubkey,
+            })
+        );
+
+        delete_inscriptions_in_block_range(840_000, 840_000, &db_conn, &ctx);
+        assert!(find_watched_outputs(&outpoints, &db_conn, &ctx).is_empty());
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn prunes_spent_watched_outputs() {
+        let ctx = Context::empty();
+        let base_dir = std::env::temp_dir().join("ordhook_test_spent_watched_outputs");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        std::fs::create_dir_all(&base_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&base_dir, &ctx);
+
+        let funding_txid = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+        let outpoints = vec![format!("{funding_txid}:0")];
+        db_conn
+            .execute(
+                "INSERT INTO watched_outputs (outpoint, block_height, value, script_pubkey) VALUES (?1, 840000, 546, '0x00')",
+                rusqlite::params![&outpoints[0]],
+            )
+            .unwrap();
+        let spending_block = TestBlockBuilder::new()
+            .height(840_001)
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 300144140535834,
+                            destination: OrdinalInscriptionTransferDestination::SpentInFees,
+                            satpoint_pre_transfer: format!("{funding_txid}:0:0"),
+                            satpoint_post_transfer: format!("{funding_txid}:0:0"),
+                            post_transfer_output_value: None,
+                            tx_index: 0,
+                        },
+                    ))
+                    .build(),
+            )
+            .build();
+        prune_spent_watched_outputs_from_block(&spending_block, &db_conn, &ctx);
+        assert_eq!(find_watched_outputs(&outpoints, &db_conn, &ctx).len(), 1);
+
+        // Rolling the spending block back makes the output unspent again.
+        delete_inscriptions_in_block_range(840_001, 840_001, &db_conn, &ctx);
+        let later_block = TestBlockBuilder::new()
+            .height(840_001 + SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS)
+            .build();
+        prune_spent_watched_outputs_from_block(&later_block, &db_conn, &ctx);
+        assert_eq!(find_watched_outputs(&outpoints, &db_conn, &ctx).len(), 1);
+
+        prune_spent_watched_outputs_from_block(&spending_block, &db_conn, &ctx);
+        prune_spent_watched_outputs_from_block(&later_block, &db_conn, &ctx);
+        assert!(find_watched_outputs(&outpoints, &db_conn, &ctx).is_empty());
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn resolves_timestamps_to_block_heights() {
+        let ctx = Context::empty();
+        let base_dir = std::env::temp_dir().join("ordhook_test_block_timestamps");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        std::fs::create_dir_all(&base_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&base_dir, &ctx);
+
+        for (height, timestamp) in [
+            (840_000, 1713571767),
+            (840_001, 1713572288),
+            (840_002, 1713573122),
+        ] {
+            let mut block = TestBlockBuilder::new().height(height).build();
+            block.timestamp = timestamp;
+            insert_block_timestamp(&block, &db_conn, &ctx);
+        }
+        assert_eq!(
+            find_block_height_range_for_timestamps(Some(1713572000), None, &db_conn, &ctx),
+            Ok(Some((840_001, 840_002)))
+        );
+        assert_eq!(
+            find_block_height_range_for_timestamps(None, Some(1713572288), &db_conn, &ctx),
+            Ok(Some((840_000, 840_001)))
+        );
+        assert_eq!(
+            find_block_height_range_for_timestamps(Some(1713573200), None, &db_conn, &ctx),
+            Ok(None)
+        );
+
+        delete_inscriptions_in_block_range(840_002, 840_002, &db_conn, &ctx);
+        assert_eq!(
+            find_block_height_range_for_timestamps(None, None, &db_conn, &ctx),
+            Ok(Some((840_000, 840_001)))
+        );
+
+        // Blocks indexed before timestamps were stored can't be resolved.
+        db_conn
+            .execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES ('a', 1, 1, 1, 839990, 0)",
+                [],
+            )
+            .unwrap();
+        assert!(find_block_height_range_for_timestamps(None, None, &db_conn, &ctx).is_err());
+        assert!(
+            find_block_height_range_for_timestamps(Some(1713571000), None, &db_conn, &ctx).is_err()
+        );
+        assert_eq!(
+            find_block_height_range_for_timestamps(Some(1713572000), None, &db_conn, &ctx),
+            Ok(Some((840_001, 840_001)))
+        );
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn sequences_unbound_inscriptions() {
+        let ctx = Context::empty();
+        let base_dir = PathBuf::from("tmp/unbound_inscriptions");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        std::fs::create_dir_all(&base_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&base_dir, &ctx);
+
+        let satpoint = format_unbound_satpoint(2);
+        assert_eq!(
+            satpoint,
+            "0000000000000000000000000000000000000000000000000000000000000000:0:2"
+        );
+        assert_eq!(parse_unbound_satpoint(&satpoint), Some(2));
+        assert_eq!(
+            parse_unbound_satpoint(
+                "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:0:0"
+            ),
+            None
+        );
+
+        assert_eq!(
+            find_nth_unbound_sequence_at_block_height(767_430, &db_conn, &ctx),
+            None
+        );
+        insert_unbound_inscription(&("a".repeat(64) + "i0"), 0, 767_430, &db_conn, &ctx);
+        insert_unbound_inscription(&("b".repeat(64) + "i0"), 1, 767_430, &db_conn, &ctx);
+        insert_unbound_inscription(&("c".repeat(64) + "i0"), 2, 767_431, &db_conn, &ctx);
+        assert_eq!(
+            find_nth_unbound_sequence_at_block_height(767_430, &db_conn, &ctx),
+            None
+        );
+        assert_eq!(
+            find_nth_unbound_sequence_at_block_height(767_431, &db_conn, &ctx),
+            Some(1)
+        );
+        assert_eq!(
+            find_unbound_inscriptions_in_block(767_431, &db_conn, &ctx),
+            [("c".repeat(64) + "i0", 2)].into_iter().collect()
+        );
+
+        delete_inscriptions_in_block_range(767_431, 767_431, &db_conn, &ctx);
+        assert!(find_unbound_inscriptions_in_block(767_431, &db_conn, &ctx).is_empty());
+        assert_eq!(
+            find_nth_unbound_sequence_at_block_height(767_432, &db_conn, &ctx),
+            Some(1)
+        );
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
 }
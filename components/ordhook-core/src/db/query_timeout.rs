@@ -0,0 +1,105 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+/// Delay between two interruptions of the statements of a connection past its deadline.
+const INTERRUPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Execution deadline of the database statements of a read request.
+///
+/// Statements of the connections watched are interrupted once the deadline passes, so a pathological query can't
+/// hold its read locks against the writer. Interrupted statements end early: callers must check `timed_out` before
+/// trusting their results.
+pub struct QueryDeadline {
+    deadline: Instant,
+    timed_out: Arc<AtomicBool>,
+    // Dropping the senders stops the watchdogs.
+    watchdogs: Mutex<Vec<Sender<()>>>,
+}
+
+impl QueryDeadline {
+    pub fn new(timeout: Duration) -> QueryDeadline {
+        QueryDeadline::with_flag(timeout, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Same as `new`, with the flag raised on timeout shared with the caller.
+    pub fn with_flag(timeout: Duration, timed_out: Arc<AtomicBool>) -> QueryDeadline {
+        QueryDeadline {
+            deadline: Instant::now() + timeout,
+            timed_out,
+            watchdogs: Mutex::new(vec![]),
+        }
+    }
+
+    /// Interrupts the statements of `db_conn` from the deadline until this `QueryDeadline` is dropped.
+    pub fn watch(&self, db_conn: &Connection) {
+        let interrupt_handle = db_conn.get_interrupt_handle();
+        let timed_out = self.timed_out.clone();
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        let (tx, rx) = channel::<()>();
+        let _ = hiro_system_kit::thread_named("query_deadline").spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(remaining) {
+                timed_out.store(true, Ordering::SeqCst);
+                // Interrupting a connection with no statement running is a no-op: statements started after the
+                // deadline are interrupted by the next round.
+                while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(INTERRUPT_INTERVAL) {
+                    interrupt_handle.interrupt();
+                }
+            }
+        });
+        if let Ok(mut watchdogs) = self.watchdogs.lock() {
+            watchdogs.push(tx);
+        }
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use chainhook_sdk::utils::Context;
+    use rusqlite::Connection;
+
+    use crate::db::ordinals::perform_query_one;
+
+    use super::QueryDeadline;
+
+    #[test]
+    fn interrupts_statements_past_deadline() {
+        let ctx = Context::empty();
+        let db_conn = Connection::open_in_memory().unwrap();
+        let deadline = QueryDeadline::new(Duration::from_millis(50));
+        deadline.watch(&db_conn);
+        let started_at = Instant::now();
+        let count = perform_query_one(
+            "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT COUNT(*) FROM c",
+            &[],
+            &db_conn,
+            &ctx,
+            |row| row.get::<_, u64>(0).unwrap(),
+        );
+        assert_eq!(count, None);
+        assert!(deadline.timed_out());
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+        drop(deadline);
+
+        let deadline = QueryDeadline::new(Duration::from_secs(5));
+        deadline.watch(&db_conn);
+        let one = perform_query_one("SELECT 1", &[], &db_conn, &ctx, |row| {
+            row.get::<_, u64>(0).unwrap()
+        });
+        assert_eq!(one, Some(1));
+        assert!(!deadline.timed_out());
+    }
+}
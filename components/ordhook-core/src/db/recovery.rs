@@ -0,0 +1,218 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::Config, db::ordinals::create_or_open_readwrite_db, error::OrdhookError, try_info,
+    try_warn,
+};
+
+/// SQLite databases stored in the SQLite directory (the working directory unless `storage.sqlite_dir` is set).
+pub const WORKING_DIR_SQLITE_DBS: [&str; 7] = [
+    "hord.sqlite",
+    "brc20.sqlite",
+    "sns.sqlite",
+    "runes.sqlite",
+    "metaprotocols.sqlite",
+    "sat_ranges.sqlite",
+    "sales.sqlite",
+];
+
+/// Snapshot archives restored in the SQLite directory.
+const SNAPSHOT_ARCHIVES: [&str; 2] = ["hord", "brc20"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecoveryAction {
+    /// Write-ahead log left by an unclean shutdown, checkpointed into its database.
+    CheckpointedWal(PathBuf),
+    /// WAL or SHM file which database doesn't exist anymore.
+    RemovedOrphanedFile(PathBuf),
+    /// File of a snapshot restore interrupted midway. The snapshot is restored again on the next download.
+    RemovedPartialSnapshot(PathBuf),
+}
+
+impl fmt::Display for RecoveryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryAction::CheckpointedWal(path) => {
+                write!(f, "checkpointed leftover WAL into {}", path.display())
+            }
+            RecoveryAction::RemovedOrphanedFile(path) => {
+                write!(f, "removed orphaned {}", path.display())
+            }
+            RecoveryAction::RemovedPartialSnapshot(path) => write!(
+                f,
+                "removed {} of an interrupted snapshot restore",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Marker created in the SQLite directory while the snapshot `archive` is being restored.
+pub fn get_snapshot_restore_marker_path(sqlite_dir: &Path, archive: &str) -> PathBuf {
+    sqlite_dir.join(format!("{archive}.restoring"))
+}
+
+fn sqlite_sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn remove_file(path: &Path) -> Result<(), OrdhookError> {
+    std::fs::remove_file(path)
+        .map_err(|e| OrdhookError::Db(format!("unable to remove {}: {e}", path.display())))
+}
+
+/// Removes the files of the restore of the snapshot `archive` if it was interrupted midway, since a partially
+/// extracted database can't be told apart from a complete one.
+pub fn remove_partial_snapshot(
+    sqlite_dir: &Path,
+    archive: &str,
+) -> Result<Vec<RecoveryAction>, OrdhookError> {
+    let marker = get_snapshot_restore_marker_path(sqlite_dir, archive);
+    if !marker.exists() {
+        return Ok(vec![]);
+    }
+    let mut actions = vec![];
+    let db_path = sqlite_dir.join(format!("{archive}.sqlite"));
+    let leftovers = [
+        sqlite_sidecar_path(&db_path, "-wal"),
+        sqlite_sidecar_path(&db_path, "-shm"),
+        sqlite_sidecar_path(&db_path, ".sha256"),
+        db_path,
+        // Archives are downloaded in the current directory before being extracted.
+        PathBuf::from(format!("{archive}.tar.gz")),
+    ];
+    for path in leftovers {
+        if path.exists() {
+            remove_file(&path)?;
+            actions.push(RecoveryAction::RemovedPartialSnapshot(path));
+        }
+    }
+    remove_file(&marker)?;
+    Ok(actions)
+}
+
+/// Removes the files of the snapshot restores interrupted midway.
+pub fn recover_partial_snapshots(sqlite_dir: &Path) -> Result<Vec<RecoveryAction>, OrdhookError> {
+    let mut actions = vec![];
+    for archive in SNAPSHOT_ARCHIVES {
+        actions.extend(remove_partial_snapshot(sqlite_dir, archive)?);
+    }
+    Ok(actions)
+}
+
+/// Checkpoints the WAL left next to `db_path`, or removes it if the database is gone.
+fn recover_sqlite_wal(db_path: &Path, ctx: &Context) -> Result<Vec<RecoveryAction>, OrdhookError> {
+    let wal_path = sqlite_sidecar_path(db_path, "-wal");
+    let shm_path = sqlite_sidecar_path(db_path, "-shm");
+    if !db_path.exists() {
+        let mut actions = vec![];
+        for path in [wal_path, shm_path] {
+            if path.exists() {
+                remove_file(&path)?;
+                actions.push(RecoveryAction::RemovedOrphanedFile(path));
+            }
+        }
+        return Ok(actions);
+    }
+    let has_wal = std::fs::metadata(&wal_path)
+        .map(|m| m.len() > 0)
+        .unwrap_or(false);
+    if !has_wal {
+        return Ok(vec![]);
+    }
+    let conn = create_or_open_readwrite_db(Some(&db_path.to_path_buf()), ctx);
+    let busy: i64 = conn
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        .map_err(|e| {
+            OrdhookError::Db(format!("unable to checkpoint {}: {e}", wal_path.display()))
+        })?;
+    if busy != 0 {
+        // Another process is using the database, the WAL is not leftover.
+        try_warn!(
+            ctx,
+            "Recovery: {} is in use, WAL left untouched",
+            db_path.display()
+        );
+        return Ok(vec![]);
+    }
+    Ok(vec![RecoveryAction::CheckpointedWal(db_path.to_path_buf())])
+}
+
+/// Cleans up after an unclean shutdown: removes the files of interrupted snapshot restores, and checkpoints leftover
+/// SQLite WALs and removes the ones without database. The LOCK file of the blocks db is left to rocksdb, which only
+/// refuses to open a db locked by a live process. Must run before any database is opened by this process.
+pub fn recover_working_dirs(
+    config: &Config,
+    ctx: &Context,
+) -> Result<Vec<RecoveryAction>, OrdhookError> {
+    let sqlite_dir = config.expected_sqlite_path();
+    let observers_dir = config.expected_observers_cache_path();
+    let mut actions = recover_partial_snapshots(&sqlite_dir)?;
+    let mut db_paths: Vec<PathBuf> = WORKING_DIR_SQLITE_DBS
+        .iter()
+        .map(|db| sqlite_dir.join(db))
+        .collect();
+    db_paths.push(observers_dir.join("observers.sqlite"));
+    for db_path in db_paths.iter() {
+        actions.extend(recover_sqlite_wal(db_path, ctx)?);
+    }
+    for action in actions.iter() {
+        try_info!(ctx, "Recovery: {action}");
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use super::{
+        get_snapshot_restore_marker_path, recover_partial_snapshots, recover_sqlite_wal,
+        RecoveryAction,
+    };
+    use chainhook_sdk::utils::Context;
+
+    fn working_dir(name: &str) -> PathBuf {
+        let dir = PathBuf::from(format!("tmp/recovery/{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn removes_partially_restored_snapshots() {
+        let dir = working_dir("snapshots");
+        fs::write(dir.join("hord.sqlite"), []).unwrap();
+        fs::write(dir.join("brc20.sqlite"), []).unwrap();
+        fs::write(get_snapshot_restore_marker_path(&dir, "hord"), []).unwrap();
+        let actions = recover_partial_snapshots(&dir).unwrap();
+        assert_eq!(
+            actions,
+            vec![RecoveryAction::RemovedPartialSnapshot(
+                dir.join("hord.sqlite")
+            )]
+        );
+        assert!(!get_snapshot_restore_marker_path(&dir, "hord").exists());
+        assert!(dir.join("brc20.sqlite").exists());
+    }
+
+    #[test]
+    fn removes_orphaned_wal_files() {
+        let dir = working_dir("orphaned");
+        fs::write(dir.join("sales.sqlite-wal"), [1]).unwrap();
+        let actions = recover_sqlite_wal(&dir.join("sales.sqlite"), &Context::empty()).unwrap();
+        assert_eq!(
+            actions,
+            vec![RecoveryAction::RemovedOrphanedFile(
+                dir.join("sales.sqlite-wal")
+            )]
+        );
+    }
+}
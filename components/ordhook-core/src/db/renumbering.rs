@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use chainhook_sdk::utils::Context;
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    db::ordinals::{perform_query_one, perform_query_set},
+    try_warn,
+};
+
+/// Numbers assigned to an inscription by the index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InscriptionNumbers {
+    pub inscription_id: String,
+    pub block_height: u64,
+    pub jubilee_inscription_number: i64,
+    pub classic_inscription_number: i64,
+}
+
+/// Change of the numbers of an inscription, after its blocks were indexed again. Numbers are missing on the side the
+/// inscription is not indexed on: reveals no longer recognized by the rules, or newly recognized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionNumberCorrection {
+    pub inscription_id: String,
+    pub block_height: u64,
+    pub old_jubilee_inscription_number: Option<i64>,
+    pub old_classic_inscription_number: Option<i64>,
+    pub new_jubilee_inscription_number: Option<i64>,
+    pub new_classic_inscription_number: Option<i64>,
+}
+
+impl InscriptionNumberCorrection {
+    /// Cursed inscriptions are the ones with a negative classic number.
+    pub fn is_reclassification(&self) -> bool {
+        match (
+            self.old_classic_inscription_number,
+            self.new_classic_inscription_number,
+        ) {
+            (Some(old), Some(new)) => (old < 0) != (new < 0),
+            _ => false,
+        }
+    }
+}
+
+pub fn find_inscription_numbers_from_block_height(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionNumbers> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT inscription_id, block_height, jubilee_inscription_number, classic_inscription_number FROM inscriptions WHERE block_height >= ? ORDER BY block_height ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| InscriptionNumbers {
+        inscription_id: row.get(0).unwrap(),
+        block_height: row.get(1).unwrap(),
+        jubilee_inscription_number: row.get(2).unwrap(),
+        classic_inscription_number: row.get(3).unwrap(),
+    })
+}
+
+pub fn find_inscription_numbers_in_block(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionNumbers> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT inscription_id, block_height, jubilee_inscription_number, classic_inscription_number FROM inscriptions WHERE block_height = ? ORDER BY jubilee_inscription_number ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| InscriptionNumbers {
+        inscription_id: row.get(0).unwrap(),
+        block_height: row.get(1).unwrap(),
+        jubilee_inscription_number: row.get(2).unwrap(),
+        classic_inscription_number: row.get(3).unwrap(),
+    })
+}
+
+/// Diffs the numbers of the inscriptions of a block range, taken before and after the range was indexed again.
+pub fn compute_inscription_number_corrections(
+    old_numbers: &Vec<InscriptionNumbers>,
+    new_numbers: &Vec<InscriptionNumbers>,
+) -> Vec<InscriptionNumberCorrection> {
+    let mut new_numbers_by_id: HashMap<&str, &InscriptionNumbers> = new_numbers
+        .iter()
+        .map(|entry| (entry.inscription_id.as_str(), entry))
+        .collect();
+    let mut corrections = vec![];
+    for old in old_numbers.iter() {
+        match new_numbers_by_id.remove(old.inscription_id.as_str()) {
+            Some(new)
+                if new.jubilee_inscription_number == old.jubilee_inscription_number
+                    && new.classic_inscription_number == old.classic_inscription_number => {}
+            new => corrections.push(InscriptionNumberCorrection {
+                inscription_id: old.inscription_id.clone(),
+                block_height: old.block_height,
+                old_jubilee_inscription_number: Some(old.jubilee_inscription_number),
+                old_classic_inscription_number: Some(old.classic_inscription_number),
+                new_jubilee_inscription_number: new.map(|n| n.jubilee_inscription_number),
+                new_classic_inscription_number: new.map(|n| n.classic_inscription_number),
+            }),
+        }
+    }
+    for new in new_numbers_by_id.into_values() {
+        corrections.push(InscriptionNumberCorrection {
+            inscription_id: new.inscription_id.clone(),
+            block_height: new.block_height,
+            old_jubilee_inscription_number: None,
+            old_classic_inscription_number: None,
+            new_jubilee_inscription_number: Some(new.jubilee_inscription_number),
+            new_classic_inscription_number: Some(new.classic_inscription_number),
+        });
+    }
+    corrections.sort_by(|a, b| {
+        (a.block_height, &a.inscription_id).cmp(&(b.block_height, &b.inscription_id))
+    });
+    corrections
+}
+
+pub fn insert_inscription_number_corrections(
+    corrections: &Vec<InscriptionNumberCorrection>,
+    renumbered_at: u64,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) {
+    for correction in corrections.iter() {
+        while let Err(e) = inscriptions_db_conn_rw.execute(
+            "INSERT INTO inscription_number_corrections (inscription_id, block_height, old_jubilee_inscription_number, old_classic_inscription_number, new_jubilee_inscription_number, new_classic_inscription_number, renumbered_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                &correction.inscription_id,
+                &correction.block_height,
+                &correction.old_jubilee_inscription_number,
+                &correction.old_classic_inscription_number,
+                &correction.new_jubilee_inscription_number,
+                &correction.new_classic_inscription_number,
+                &renumbered_at
+            ],
+        ) {
+            try_warn!(
+                ctx,
+                "unable to update inscription_number_corrections: {}",
+                e.to_string()
+            );
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+}
+
+/// Renumbering run whose blocks were dropped, and whose corrections were not recorded yet. The numbers the blocks had
+/// before being dropped are kept until then, for a run interrupted while indexing to be resumed without losing them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingRenumbering {
+    pub from_block_height: u64,
+    pub renumbered_at: u64,
+    pub old_numbers: Vec<InscriptionNumbers>,
+}
+
+pub fn find_pending_renumbering(db_conn: &Connection, ctx: &Context) -> Option<PendingRenumbering> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT value FROM db_metadata WHERE key = 'pending_renumbering'";
+    let value: String = perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())?;
+    let (from_block_height, renumbered_at) = value.split_once(':')?;
+    let query = "SELECT inscription_id, block_height, jubilee_inscription_number, classic_inscription_number FROM pending_renumbering_numbers ORDER BY block_height ASC, jubilee_inscription_number ASC";
+    let old_numbers = perform_query_set(query, args, db_conn, ctx, |row| InscriptionNumbers {
+        inscription_id: row.get(0).unwrap(),
+        block_height: row.get(1).unwrap(),
+        jubilee_inscription_number: row.get(2).unwrap(),
+        classic_inscription_number: row.get(3).unwrap(),
+    });
+    Some(PendingRenumbering {
+        from_block_height: from_block_height.parse().ok()?,
+        renumbered_at: renumbered_at.parse().ok()?,
+        old_numbers,
+    })
+}
+
+/// Records the numbers of the blocks about to be dropped by a renumbering run, replacing the ones of a previous run.
+/// Must be committed before the blocks get dropped.
+pub fn insert_pending_renumbering(
+    pending: &PendingRenumbering,
+    inscriptions_db_conn_rw: &Connection,
+) -> Result<(), String> {
+    let db_tx = inscriptions_db_conn_rw
+        .unchecked_transaction()
+        .map_err(|e| format!("unable to record renumbering: {e}"))?;
+    db_tx
+        .execute("DELETE FROM pending_renumbering_numbers", [])
+        .map_err(|e| format!("unable to record renumbering: {e}"))?;
+    for numbers in pending.old_numbers.iter() {
+        db_tx
+            .execute(
+                "INSERT INTO pending_renumbering_numbers (inscription_id, block_height, jubilee_inscription_number, classic_inscription_number)
+                VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    &numbers.inscription_id,
+                    &numbers.block_height,
+                    &numbers.jubilee_inscription_number,
+                    &numbers.classic_inscription_number
+                ],
+            )
+            .map_err(|e| format!("unable to record renumbering: {e}"))?;
+    }
+    db_tx
+        .execute(
+            "INSERT OR REPLACE INTO db_metadata (key, value) VALUES ('pending_renumbering', ?1)",
+            rusqlite::params![format!(
+                "{}:{}",
+                pending.from_block_height, pending.renumbered_at
+            )],
+        )
+        .map_err(|e| format!("unable to record renumbering: {e}"))?;
+    db_tx
+        .commit()
+        .map_err(|e| format!("unable to record renumbering: {e}"))
+}
+
+/// Records the corrections of a renumbering run, and forgets the numbers kept for it, in a single transaction.
+pub fn complete_pending_renumbering(
+    corrections: &Vec<InscriptionNumberCorrection>,
+    renumbered_at: u64,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), String> {
+    let db_tx = inscriptions_db_conn_rw
+        .unchecked_transaction()
+        .map_err(|e| format!("unable to record corrections: {e}"))?;
+    insert_inscription_number_corrections(corrections, renumbered_at, &db_tx, ctx);
+    db_tx
+        .execute("DELETE FROM pending_renumbering_numbers", [])
+        .map_err(|e| format!("unable to record corrections: {e}"))?;
+    db_tx
+        .execute(
+            "DELETE FROM db_metadata WHERE key = 'pending_renumbering'",
+            [],
+        )
+        .map_err(|e| format!("unable to record corrections: {e}"))?;
+    db_tx
+        .commit()
+        .map_err(|e| format!("unable to record corrections: {e}"))
+}
+
+/// Corrections recorded by the renumbering runs that started after `renumbered_at`.
+pub fn find_inscription_number_corrections(
+    renumbered_at: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<InscriptionNumberCorrection> {
+    let args: &[&dyn ToSql] = &[&renumbered_at.to_sql().unwrap()];
+    let query = "SELECT inscription_id, block_height, old_jubilee_inscription_number, old_classic_inscription_number, new_jubilee_inscription_number, new_classic_inscription_number FROM inscription_number_corrections WHERE renumbered_at > ? ORDER BY renumbered_at ASC, block_height ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        InscriptionNumberCorrection {
+            inscription_id: row.get(0).unwrap(),
+            block_height: row.get(1).unwrap(),
+            old_jubilee_inscription_number: row.get(2).unwrap(),
+            old_classic_inscription_number: row.get(3).unwrap(),
+            new_jubilee_inscription_number: row.get(4).unwrap(),
+            new_classic_inscription_number: row.get(5).unwrap(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+    use rusqlite::Connection;
+
+    use crate::db::ordinals::initialize_ordinals_db;
+
+    use super::{
+        complete_pending_renumbering, compute_inscription_number_corrections,
+        find_inscription_number_corrections, find_pending_renumbering,
+        insert_inscription_number_corrections, insert_pending_renumbering, InscriptionNumbers,
+        PendingRenumbering,
+    };
+
+    fn numbers(inscription_id: &str, jubilee: i64, classic: i64) -> InscriptionNumbers {
+        InscriptionNumbers {
+            inscription_id: inscription_id.to_string(),
+            block_height: 800_000,
+            jubilee_inscription_number: jubilee,
+            classic_inscription_number: classic,
+        }
+    }
+
+    #[test]
+    fn computes_inscription_number_corrections() {
+        let old_numbers = vec![
+            numbers("a", 10, 10),
+            numbers("b", 11, -1),
+            numbers("c", 12, 11),
+            numbers("d", 13, 12),
+        ];
+        let new_numbers = vec![
+            numbers("a", 10, 10),
+            numbers("b", 11, 11),
+            numbers("d", 12, 12),
+            numbers("e", 13, 13),
+        ];
+        let corrections = compute_inscription_number_corrections(&old_numbers, &new_numbers);
+        let ids = corrections
+            .iter()
+            .map(|c| c.inscription_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["b", "c", "d", "e"]);
+        assert!(corrections[0].is_reclassification());
+        assert_eq!(corrections[1].new_jubilee_inscription_number, None);
+        assert!(!corrections[2].is_reclassification());
+        assert_eq!(corrections[3].old_classic_inscription_number, None);
+
+        let ctx = Context::empty();
+        let working_dir = std::env::temp_dir().join("ordhook_test_renumbering");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let db_conn: Connection = initialize_ordinals_db(&working_dir, &ctx);
+        insert_inscription_number_corrections(&corrections, 1_700_000_000, &db_conn, &ctx);
+        assert_eq!(
+            find_inscription_number_corrections(0, &db_conn, &ctx),
+            corrections
+        );
+        assert!(find_inscription_number_corrections(1_700_000_000, &db_conn, &ctx).is_empty());
+
+        let pending = PendingRenumbering {
+            from_block_height: 800_000,
+            renumbered_at: 1_700_000_100,
+            old_numbers: old_numbers.clone(),
+        };
+        insert_pending_renumbering(&pending, &db_conn).unwrap();
+        assert_eq!(find_pending_renumbering(&db_conn, &ctx), Some(pending));
+        complete_pending_renumbering(&corrections, 1_700_000_100, &db_conn, &ctx).unwrap();
+        assert_eq!(find_pending_renumbering(&db_conn, &ctx), None);
+        assert_eq!(
+            find_inscription_number_corrections(1_700_000_000, &db_conn, &ctx),
+            corrections
+        );
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+
+use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    config::Config,
+    core::protocol::{
+        inscription_sequencing::get_bitcoin_network,
+        sale_detection::{detect_sales_in_transaction, DetectedSale, SaleConfidence},
+    },
+    db::ordinals::{
+        create_or_open_readwrite_db, open_existing_readonly_db_snapshot, perform_query_set,
+    },
+    try_error, try_info, try_warn,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SaleRow {
+    pub ordinal_number: u64,
+    pub block_height: u64,
+    pub tx_id: String,
+    pub tx_index: u64,
+    pub input_index: u64,
+    pub price_sats: u64,
+    pub seller_address: Option<String>,
+    pub buyer_address: String,
+    pub confidence: SaleConfidence,
+}
+
+/// If the given `config` has sales analytics enabled, returns a read/write DB connection for sales.
+pub fn sales_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
+    if config.sales_analytics.is_some() {
+        Some(initialize_sales_db(
+            Some(&config.expected_sqlite_path()),
+            ctx,
+        ))
+    } else {
+        None
+    }
+}
+
+pub fn get_default_sales_db_file_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("sales.sqlite");
+    destination_path
+}
+
+pub fn initialize_sales_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let db_path = base_dir.map(|dir| get_default_sales_db_file_path(dir));
+    let conn = create_or_open_readwrite_db(db_path.as_ref(), ctx);
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS sales (
+            ordinal_number INTEGER NOT NULL,
+            block_height INTEGER NOT NULL,
+            tx_id TEXT NOT NULL,
+            tx_index INTEGER NOT NULL,
+            input_index INTEGER NOT NULL,
+            price_sats INTEGER NOT NULL,
+            seller_address TEXT,
+            buyer_address TEXT NOT NULL,
+            confidence TEXT NOT NULL,
+            PRIMARY KEY (tx_id, ordinal_number)
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table sales: {}", e.to_string());
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sales_on_ordinal_number ON sales(ordinal_number, block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sales.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sales_on_block_height ON sales(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sales.sqlite: {}", e.to_string());
+        }
+    }
+    conn
+}
+
+/// Opens a read-only connection to an existing sales.sqlite, used for serving API queries.
+pub fn open_readonly_sales_db_conn(config: &Config, ctx: &Context) -> Result<Connection, String> {
+    if config.sales_analytics.is_none() {
+        return Err("Sales analytics are disabled".to_string());
+    }
+    let db_path = get_default_sales_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(open_existing_readonly_db_snapshot(&db_path, ctx)?)
+}
+
+/// Records the likely sales of the inscriptions transferred in a block, and returns them.
+pub fn index_sales_in_block(
+    block: &BitcoinBlockData,
+    config: &Config,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<DetectedSale> {
+    let Some(ref sales_config) = config.sales_analytics else {
+        return vec![];
+    };
+    let network = get_bitcoin_network(&block.metadata.network);
+    let mut sales = vec![];
+    for tx in block.transactions.iter().skip(1) {
+        for sale in detect_sales_in_transaction(tx, &network, sales_config.min_price_sats) {
+            match db_conn.execute(
+                "INSERT OR IGNORE INTO sales (ordinal_number, block_height, tx_id, tx_index, input_index, price_sats, seller_address, buyer_address, confidence)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                rusqlite::params![
+                    &sale.ordinal_number,
+                    &block.block_identifier.index,
+                    &sale.tx_id,
+                    &sale.tx_index,
+                    &sale.input_index,
+                    &sale.price_sats,
+                    &sale.seller_address,
+                    &sale.buyer_address,
+                    sale.confidence.as_str(),
+                ],
+            ) {
+                Ok(_) => sales.push(sale),
+                Err(e) => {
+                    try_error!(ctx, "unable to insert into sales.sqlite: {}", e.to_string());
+                }
+            }
+        }
+    }
+    if !sales.is_empty() {
+        try_info!(
+            ctx,
+            "{} likely sales detected in block #{}",
+            sales.len(),
+            block.block_identifier.index
+        );
+    }
+    sales
+}
+
+pub fn delete_sales_in_block_range(
+    start_block: u32,
+    end_block: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM sales WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query sales.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+fn sale_row_from(row: &rusqlite::Row<'_>) -> SaleRow {
+    let confidence: String = row.get(8).unwrap();
+    SaleRow {
+        ordinal_number: row.get(0).unwrap(),
+        block_height: row.get(1).unwrap(),
+        tx_id: row.get(2).unwrap(),
+        tx_index: row.get(3).unwrap(),
+        input_index: row.get(4).unwrap(),
+        price_sats: row.get(5).unwrap(),
+        seller_address: row.get(6).unwrap(),
+        buyer_address: row.get(7).unwrap(),
+        confidence: SaleConfidence::from_str(&confidence).unwrap_or(SaleConfidence::Low),
+    }
+}
+
+/// Sales of a sat, most recent first.
+/// Sort keys of a sale in the lists of sales, most recent first: block height, tx index, input index and ordinal number.
+pub type SaleSortKeys = (u64, u64, u64, u64);
+
+pub fn get_sale_sort_keys(sale: &SaleRow) -> SaleSortKeys {
+    (
+        sale.block_height,
+        sale.tx_index,
+        sale.input_index,
+        sale.ordinal_number,
+    )
+}
+
+fn sale_sort_keys_args(after: Option<&SaleSortKeys>) -> [Option<u64>; 4] {
+    [
+        after.map(|a| a.0),
+        after.map(|a| a.1),
+        after.map(|a| a.2),
+        after.map(|a| a.3),
+    ]
+}
+
+/// Sales of a sat, most recent first, starting after the sale with the sort keys `after` if given.
+pub fn find_sales_of_sat(
+    ordinal_number: u64,
+    after: Option<&SaleSortKeys>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<SaleRow> {
+    let [a0, a1, a2, a3] = sale_sort_keys_args(after);
+    let args: &[&dyn ToSql] = &[
+        &ordinal_number.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+        &a0.to_sql().unwrap(),
+        &a1.to_sql().unwrap(),
+        &a2.to_sql().unwrap(),
+        &a3.to_sql().unwrap(),
+    ];
+    let query = "SELECT ordinal_number, block_height, tx_id, tx_index, input_index, price_sats, seller_address, buyer_address, confidence FROM sales
+        WHERE ordinal_number = ?1 AND (?4 IS NULL OR (block_height, tx_index, input_index, ordinal_number) < (?4, ?5, ?6, ?7))
+        ORDER BY block_height DESC, tx_index DESC, input_index DESC, ordinal_number DESC LIMIT ?2 OFFSET ?3";
+    perform_query_set(query, args, db_conn, ctx, sale_row_from)
+}
+
+/// Latest sales, most recent first, optionally limited to the sales detected with high confidence and to the sales of a
+/// block range, starting after the sale with the sort keys `after` if given.
+pub fn find_latest_sales(
+    high_confidence_only: bool,
+    block_range: Option<(u64, u64)>,
+    after: Option<&SaleSortKeys>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<SaleRow> {
+    let [a0, a1, a2, a3] = sale_sort_keys_args(after);
+    let from_block_height = block_range.map(|(from, _)| from);
+    let to_block_height = block_range.map(|(_, to)| to);
+    let args: &[&dyn ToSql] = &[
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+        &a0.to_sql().unwrap(),
+        &a1.to_sql().unwrap(),
+        &a2.to_sql().unwrap(),
+        &a3.to_sql().unwrap(),
+        &from_block_height.to_sql().unwrap(),
+        &to_block_height.to_sql().unwrap(),
+    ];
+    let query = match high_confidence_only {
+        true => "SELECT ordinal_number, block_height, tx_id, tx_index, input_index, price_sats, seller_address, buyer_address, confidence FROM sales
+            WHERE confidence = 'high' AND (?3 IS NULL OR (block_height, tx_index, input_index, ordinal_number) < (?3, ?4, ?5, ?6))
+            AND (?7 IS NULL OR block_height BETWEEN ?7 AND ?8)
+            ORDER BY block_height DESC, tx_index DESC, input_index DESC, ordinal_number DESC LIMIT ?1 OFFSET ?2",
+        false => "SELECT ordinal_number, block_height, tx_id, tx_index, input_index, price_sats, seller_address, buyer_address, confidence FROM sales
+            WHERE (?3 IS NULL OR (block_height, tx_index, input_index, ordinal_number) < (?3, ?4, ?5, ?6))
+            AND (?7 IS NULL OR block_height BETWEEN ?7 AND ?8)
+            ORDER BY block_height DESC, tx_index DESC, input_index DESC, ordinal_number DESC LIMIT ?1 OFFSET ?2",
+    };
+    perform_query_set(query, args, db_conn, ctx, sale_row_from)
+}
@@ -0,0 +1,705 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use chainhook_sdk::{
+    indexer::bitcoin::{
+        download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
+        standardize_bitcoin_block,
+    },
+    types::{BitcoinBlockData, TransactionIdentifier},
+    utils::Context,
+};
+use rusqlite::{Connection, ToSql};
+
+use crate::{
+    config::{Config, IndexScope},
+    core::protocol::{addresses::script_hex_address, inscription_sequencing::get_bitcoin_network},
+    db::{
+        blocks::{find_pinned_block_bytes_at_block_height, open_blocks_db_with_retry},
+        cursor::BlockBytesCursor,
+        ordinals::{
+            create_or_open_readwrite_db, open_existing_readonly_db_snapshot, perform_query_one,
+            perform_query_set,
+        },
+    },
+    ord::{height::Height, rarity::Rarity, sat::Sat},
+    try_info, try_warn,
+    utils::bitcoind::build_bitcoind_http_client,
+};
+
+/// Number of blocks spent sat ranges are kept for, to be restored by re-orgs. Deeper rollbacks reset the index.
+const SAT_RANGES_SPENT_RETENTION_BLOCKS: u64 = 100;
+
+/// Full outpoint and address of every output of a block, by transaction.
+type BlockOutputs = Vec<Vec<(String, Option<String>)>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SatRangeRow {
+    pub outpoint: String,
+    /// Offset of the first sat of the range in its output.
+    pub offset: u64,
+    pub start: u64,
+    pub end: u64,
+    pub rarity: Option<String>,
+    pub block_height: u64,
+}
+
+/// If the given `config` has sat range indexing enabled, returns a read/write DB connection for sat ranges.
+pub fn sat_ranges_new_rw_db_conn(config: &Config, ctx: &Context) -> Option<Connection> {
+    if config.storage.index_scope.tracks_sat_ranges() {
+        Some(initialize_sat_ranges_db(
+            Some(&config.expected_sqlite_path()),
+            ctx,
+        ))
+    } else {
+        None
+    }
+}
+
+pub fn get_default_sat_ranges_db_file_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("sat_ranges.sqlite");
+    destination_path
+}
+
+pub fn initialize_sat_ranges_db(base_dir: Option<&PathBuf>, ctx: &Context) -> Connection {
+    let db_path = base_dir.map(|dir| get_default_sat_ranges_db_file_path(dir));
+    let conn = create_or_open_readwrite_db(db_path.as_ref(), ctx);
+    // Outputs are keyed by the first 8 bytes of their txid, like in the blocks DB. `outpoint` is only known for the
+    // outputs indexed from full blocks.
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS sat_ranges (
+            output_key TEXT NOT NULL,
+            range_index INTEGER NOT NULL,
+            outpoint TEXT,
+            output_offset INTEGER NOT NULL,
+            start INTEGER NOT NULL,
+            end INTEGER NOT NULL,
+            rarity TEXT,
+            address TEXT,
+            block_height INTEGER NOT NULL,
+            spent_block_height INTEGER,
+            PRIMARY KEY (output_key, range_index)
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table sat_ranges: {}", e.to_string());
+    } else {
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sat_ranges_on_address ON sat_ranges(address, start);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sat_ranges.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sat_ranges_on_block_height ON sat_ranges(block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sat_ranges.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sat_ranges_on_spent_block_height ON sat_ranges(spent_block_height);",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sat_ranges.sqlite: {}", e.to_string());
+        }
+        if let Err(e) = conn.execute(
+            "CREATE INDEX IF NOT EXISTS index_sat_ranges_on_unresolved_block_height ON sat_ranges(block_height) WHERE outpoint IS NULL AND spent_block_height IS NULL;",
+            [],
+        ) {
+            try_warn!(ctx, "unable to create sat_ranges.sqlite: {}", e.to_string());
+        }
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS sat_ranges_progress (
+            id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+            block_height INTEGER NOT NULL,
+            index_scope TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table sat_ranges_progress: {}",
+            e.to_string()
+        );
+    }
+    conn
+}
+
+/// Opens a read-only connection to an existing sat_ranges.sqlite, used for serving API queries.
+pub fn open_readonly_sat_ranges_db_conn(
+    config: &Config,
+    ctx: &Context,
+) -> Result<Connection, String> {
+    if !config.storage.index_scope.tracks_sat_ranges() {
+        return Err("Sat tracking is limited to inscribed sats".to_string());
+    }
+    let db_path = get_default_sat_ranges_db_file_path(&config.expected_sqlite_path());
+    if !db_path.exists() {
+        return Err(format!("unable to find {}", db_path.display()));
+    }
+    Ok(open_existing_readonly_db_snapshot(&db_path, ctx)?)
+}
+
+/// Sats spent by a transaction, in order: a tracked sat range, or a run of untracked sats only taking room.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SatSegment {
+    Range(u64, u64),
+    Untracked(u64),
+}
+
+impl SatSegment {
+    fn len(&self) -> u64 {
+        match self {
+            SatSegment::Range(start, end) => end - start,
+            SatSegment::Untracked(len) => *len,
+        }
+    }
+
+    fn split_at(&self, n: u64) -> (SatSegment, SatSegment) {
+        match self {
+            SatSegment::Range(start, end) => (
+                SatSegment::Range(*start, start + n),
+                SatSegment::Range(start + n, *end),
+            ),
+            SatSegment::Untracked(len) => {
+                (SatSegment::Untracked(n), SatSegment::Untracked(len - n))
+            }
+        }
+    }
+}
+
+/// Splits the sats spent by a transaction across its outputs, first in first out. Returns the tracked ranges of each
+/// output with their offset in the output, and the segments left, paid as fees.
+pub fn distribute_sat_segments(
+    input_segments: Vec<SatSegment>,
+    output_values: &[u64],
+) -> (Vec<Vec<(u64, u64, u64)>>, Vec<SatSegment>) {
+    let mut remaining = VecDeque::from(input_segments);
+    let mut outputs = Vec::with_capacity(output_values.len());
+    for value in output_values.iter() {
+        let mut ranges = vec![];
+        let mut offset = 0;
+        while offset < *value {
+            let Some(mut segment) = remaining.pop_front() else {
+                break;
+            };
+            if segment.len() > value - offset {
+                let (head, tail) = segment.split_at(value - offset);
+                remaining.push_front(tail);
+                segment = head;
+            }
+            if let SatSegment::Range(start, end) = segment {
+                ranges.push((offset, start, end));
+            }
+            offset += segment.len();
+        }
+        outputs.push(ranges);
+    }
+    (outputs, remaining.into_iter().collect())
+}
+
+fn format_output_key(txid: &[u8], vout: usize) -> String {
+    format!("{}:{}", hex::encode(txid), vout)
+}
+
+fn get_block_outputs(block: &BitcoinBlockData) -> BlockOutputs {
+    let network = get_bitcoin_network(&block.metadata.network);
+    block
+        .transactions
+        .iter()
+        .map(|tx| {
+            tx.metadata
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(vout, output)| {
+                    let address = script_hex_address(&output.get_script_pubkey_hex(), &network);
+                    (
+                        format!(
+                            "{}:{}",
+                            tx.transaction_identifier.get_hash_bytes_str(),
+                            vout
+                        ),
+                        address,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn find_sat_ranges_tip(db_conn: &Connection, ctx: &Context) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT block_height FROM sat_ranges_progress WHERE id = 0";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+fn insert_outputs_sat_ranges(
+    block_height: u64,
+    txid: &[u8],
+    outputs_ranges: Vec<Vec<(u64, u64, u64)>>,
+    tx_outputs: Option<&Vec<(String, Option<String>)>>,
+    db_conn: &Connection,
+) -> Result<(), String> {
+    for (vout, ranges) in outputs_ranges.into_iter().enumerate() {
+        let output_key = format_output_key(txid, vout);
+        let (outpoint, address) = match tx_outputs.and_then(|tx_outputs| tx_outputs.get(vout)) {
+            Some((outpoint, address)) => (Some(outpoint.clone()), address.clone()),
+            None => (None, None),
+        };
+        for (range_index, (output_offset, start, end)) in ranges.into_iter().enumerate() {
+            let rarity = match Rarity::from(Sat(start)) {
+                Rarity::Common => None,
+                rarity => Some(rarity.name()),
+            };
+            db_conn
+                .execute(
+                    "INSERT OR REPLACE INTO sat_ranges (output_key, range_index, outpoint, output_offset, start, end, rarity, address, block_height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    rusqlite::params![&output_key, &range_index, &outpoint, &output_offset, &start, &end, &rarity, &address, &block_height],
+                )
+                .map_err(|e| format!("unable to index sat ranges: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Indexes the sat ranges moved by a block, from its compacted form. `block` provides the addresses of the outputs when
+/// available. Blocks already indexed are skipped, and so are blocks not following the last block indexed: these gaps
+/// get filled from the blocks DB by `catch_up_sat_ranges_db`. With `InscribedPlusRare`, only rare sats are tracked, as
+/// 1 sat ranges.
+pub fn index_sat_ranges_in_block(
+    block_height: u64,
+    block_bytes: &[u8],
+    block: Option<&BitcoinBlockData>,
+    index_scope: IndexScope,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<(), String> {
+    let next_block_height = find_sat_ranges_tip(db_conn, ctx).map_or(0, |tip| tip + 1);
+    if block_height < next_block_height {
+        return Ok(());
+    }
+    if block_height > next_block_height {
+        try_warn!(
+            ctx,
+            "Sat ranges: skipping block #{block_height}, expecting block #{next_block_height}"
+        );
+        return Ok(());
+    }
+    let block_outputs = block.map(get_block_outputs);
+    let db_tx = db_conn
+        .unchecked_transaction()
+        .map_err(|e| format!("unable to index sat ranges: {e}"))?;
+    let cursor = BlockBytesCursor::new(block_bytes);
+    let transactions = cursor.iter_tx().collect::<Vec<_>>();
+    let mut fee_segments = vec![];
+    for (tx_index, tx) in transactions.iter().enumerate().skip(1) {
+        let mut input_segments = vec![];
+        for input in tx.inputs.iter() {
+            let output_key = format_output_key(&input.txin, input.vout as usize);
+            let args: &[&dyn ToSql] = &[&output_key.to_sql().unwrap()];
+            let query = "SELECT output_offset, start, end FROM sat_ranges WHERE output_key = ? AND spent_block_height IS NULL ORDER BY range_index";
+            let ranges = perform_query_set(query, args, &db_tx, ctx, |row| {
+                (
+                    row.get::<_, u64>(0).unwrap(),
+                    row.get::<_, u64>(1).unwrap(),
+                    row.get::<_, u64>(2).unwrap(),
+                )
+            });
+            let mut tracked = 0;
+            for (output_offset, start, end) in ranges.into_iter() {
+                if output_offset > tracked {
+                    input_segments.push(SatSegment::Untracked(output_offset - tracked));
+                }
+                input_segments.push(SatSegment::Range(start, end));
+                tracked = output_offset + end - start;
+            }
+            if tracked < input.txin_value {
+                if index_scope == IndexScope::Full {
+                    try_warn!(
+                        ctx,
+                        "Sat ranges: ranges of {output_key} spent in block #{block_height} don't match its value"
+                    );
+                }
+                input_segments.push(SatSegment::Untracked(input.txin_value - tracked));
+            }
+            db_tx
+                .execute(
+                    "UPDATE sat_ranges SET spent_block_height = ?1 WHERE output_key = ?2 AND spent_block_height IS NULL",
+                    rusqlite::params![&block_height, &output_key],
+                )
+                .map_err(|e| format!("unable to index sat ranges: {e}"))?;
+        }
+        let (outputs_ranges, fees) = distribute_sat_segments(input_segments, &tx.outputs);
+        // Inserted right away, outputs can be spent later in the same block.
+        insert_outputs_sat_ranges(
+            block_height,
+            &tx.txid,
+            outputs_ranges,
+            block_outputs
+                .as_ref()
+                .and_then(|outputs| outputs.get(tx_index)),
+            &db_tx,
+        )?;
+        fee_segments.extend(fees);
+    }
+    // Coinbase outputs get the subsidy, then the fees. Whatever they don't claim is lost.
+    if let Some(coinbase) = transactions.first() {
+        let subsidy_start = Height(block_height).starting_sat().n();
+        let subsidy = Height(block_height).subsidy();
+        let mut coinbase_segments = vec![];
+        if subsidy > 0 && index_scope == IndexScope::Full {
+            coinbase_segments.push(SatSegment::Range(subsidy_start, subsidy_start + subsidy));
+        } else if subsidy > 0 {
+            // The first sat of a block is its only rare sat.
+            coinbase_segments.push(SatSegment::Range(subsidy_start, subsidy_start + 1));
+            coinbase_segments.push(SatSegment::Untracked(subsidy - 1));
+        }
+        coinbase_segments.extend(fee_segments);
+        let (outputs_ranges, _) = distribute_sat_segments(coinbase_segments, &coinbase.outputs);
+        insert_outputs_sat_ranges(
+            block_height,
+            &coinbase.txid,
+            outputs_ranges,
+            block_outputs.as_ref().and_then(|outputs| outputs.first()),
+            &db_tx,
+        )?;
+    }
+    if block_height > SAT_RANGES_SPENT_RETENTION_BLOCKS {
+        db_tx
+            .execute(
+                "DELETE FROM sat_ranges WHERE spent_block_height < ?1",
+                rusqlite::params![&(block_height - SAT_RANGES_SPENT_RETENTION_BLOCKS)],
+            )
+            .map_err(|e| format!("unable to index sat ranges: {e}"))?;
+    }
+    db_tx
+        .execute(
+            "INSERT OR REPLACE INTO sat_ranges_progress (id, block_height, index_scope) VALUES (0, ?1, ?2)",
+            rusqlite::params![&block_height, &index_scope.as_str()],
+        )
+        .map_err(|e| format!("unable to index sat ranges: {e}"))?;
+    db_tx
+        .commit()
+        .map_err(|e| format!("unable to index sat ranges: {e}"))
+}
+
+/// Indexes the sat ranges of the blocks received by a block processor, with the addresses of the blocks standardized.
+pub fn index_sat_ranges_in_compacted_blocks(
+    mut compacted_blocks: Vec<(u64, Vec<u8>)>,
+    blocks: &Vec<BitcoinBlockData>,
+    index_scope: IndexScope,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    compacted_blocks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (block_height, block_bytes) in compacted_blocks.iter() {
+        let block = blocks
+            .iter()
+            .find(|block| block.block_identifier.index == *block_height);
+        if let Err(e) =
+            index_sat_ranges_in_block(*block_height, block_bytes, block, index_scope, db_conn, ctx)
+        {
+            try_warn!(ctx, "Sat ranges: block #{block_height}: {e}");
+            return;
+        }
+    }
+}
+
+/// Adapts sat_ranges.sqlite when the index scope changed since it was built. Going from `Full` to `InscribedPlusRare`
+/// only drops the common sats; going the other way requires sats that were never tracked, the index is reset and
+/// rebuilt from genesis.
+fn enforce_sat_ranges_index_scope(
+    index_scope: IndexScope,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<(), String> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT index_scope FROM sat_ranges_progress WHERE id = 0";
+    let Some(built_scope) = perform_query_one(query, args, db_conn, ctx, |row| {
+        row.get::<_, String>(0).unwrap()
+    }) else {
+        return Ok(());
+    };
+    let built_scope = built_scope.parse::<IndexScope>()?;
+    let statement = match (built_scope, index_scope) {
+        (built_scope, index_scope) if built_scope == index_scope => return Ok(()),
+        (IndexScope::Full, IndexScope::InscribedPlusRare) => {
+            try_info!(ctx, "Sat ranges: narrowing index scope to rare sats");
+            "DELETE FROM sat_ranges WHERE rarity IS NULL; UPDATE sat_ranges SET end = start + 1;"
+        }
+        _ => {
+            try_warn!(
+                ctx,
+                "Sat ranges: index built with scope {}, rebuilding it from genesis with scope {}",
+                built_scope.as_str(),
+                index_scope.as_str()
+            );
+            "DELETE FROM sat_ranges; DELETE FROM sat_ranges_progress;"
+        }
+    };
+    db_conn
+        .execute_batch(statement)
+        .map_err(|e| format!("unable to change sat ranges index scope: {e}"))?;
+    db_conn
+        .execute(
+            "UPDATE sat_ranges_progress SET index_scope = ?1 WHERE id = 0",
+            rusqlite::params![&index_scope.as_str()],
+        )
+        .map_err(|e| format!("unable to change sat ranges index scope: {e}"))?;
+    Ok(())
+}
+
+/// Indexes the sat ranges of the blocks stored in the blocks DB, from the last block indexed up to `end_block`. Blocks
+/// DB entries don't keep output scripts: outputs indexed this way have no address until `resolve_sat_ranges_addresses`
+/// runs.
+pub fn catch_up_sat_ranges_db(
+    end_block: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let Some(db_conn) = sat_ranges_new_rw_db_conn(config, ctx) else {
+        return Ok(());
+    };
+    enforce_sat_ranges_index_scope(config.storage.index_scope, &db_conn, ctx)?;
+    let start_block = find_sat_ranges_tip(&db_conn, ctx).map_or(0, |tip| tip + 1);
+    if start_block > end_block {
+        return Ok(());
+    }
+    try_info!(
+        ctx,
+        "Sat ranges: indexing blocks #{start_block} to #{end_block} from blocks DB"
+    );
+    let blocks_db = open_blocks_db_with_retry(false, config, ctx);
+    for block_height in start_block..=end_block {
+        let Some(block_bytes) = find_pinned_block_bytes_at_block_height(
+            block_height as u32,
+            3,
+            &blocks_db,
+            config,
+            ctx,
+        ) else {
+            return Err(format!("block #{block_height} not in blocks DB"));
+        };
+        index_sat_ranges_in_block(
+            block_height,
+            &block_bytes,
+            None,
+            config.storage.index_scope,
+            &db_conn,
+            ctx,
+        )?;
+        if block_height % 10_000 == 0 {
+            try_info!(ctx, "Sat ranges: indexed block #{block_height}");
+        }
+    }
+    Ok(())
+}
+
+/// Heights of the blocks holding unspent outputs indexed from the blocks DB, without outpoint nor address.
+fn find_unresolved_outputs_block_heights(db_conn: &Connection, ctx: &Context) -> Vec<u64> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT DISTINCT block_height FROM sat_ranges WHERE outpoint IS NULL AND spent_block_height IS NULL ORDER BY block_height";
+    perform_query_set(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+/// Resolves the outpoint and address of the unspent outputs indexed by `catch_up_sat_ranges_db`, from the blocks which
+/// created them, downloaded from bitcoind. Only blocks still holding such outputs are downloaded, and each block is
+/// committed on its own: an interrupted resolution resumes at the next start.
+pub async fn resolve_sat_ranges_addresses(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Some(db_conn) = sat_ranges_new_rw_db_conn(config, ctx) else {
+        return Ok(());
+    };
+    let block_heights = find_unresolved_outputs_block_heights(&db_conn, ctx);
+    if block_heights.is_empty() {
+        return Ok(());
+    }
+    try_info!(
+        ctx,
+        "Sat ranges: resolving the addresses of unspent outputs in {} blocks",
+        block_heights.len()
+    );
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    let http_client = build_bitcoind_http_client(config)?;
+    for (i, block_height) in block_heights.iter().enumerate() {
+        let block_hash =
+            retrieve_block_hash_with_retry(&http_client, block_height, &bitcoin_config, ctx)
+                .await?;
+        let block_breakdown =
+            download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
+                .await?;
+        let block =
+            standardize_bitcoin_block(block_breakdown, &config.network.bitcoin_network, ctx)
+                .map_err(|(e, _)| format!("unable to resolve block #{block_height}: {e}"))?;
+        let db_tx = db_conn
+            .unchecked_transaction()
+            .map_err(|e| format!("unable to resolve sat ranges addresses: {e}"))?;
+        for (tx, tx_outputs) in block.transactions.iter().zip(get_block_outputs(&block)) {
+            let txid = tx.transaction_identifier.get_8_hash_bytes();
+            for (vout, (outpoint, address)) in tx_outputs.into_iter().enumerate() {
+                db_tx
+                    .execute(
+                        "UPDATE sat_ranges SET outpoint = ?1, address = ?2 WHERE output_key = ?3 AND outpoint IS NULL",
+                        rusqlite::params![&outpoint, &address, &format_output_key(&txid, vout)],
+                    )
+                    .map_err(|e| format!("unable to resolve sat ranges addresses: {e}"))?;
+            }
+        }
+        db_tx
+            .commit()
+            .map_err(|e| format!("unable to resolve sat ranges addresses: {e}"))?;
+        if (i + 1) % 10_000 == 0 {
+            try_info!(
+                ctx,
+                "Sat ranges: resolved addresses in {} blocks out of {}",
+                i + 1,
+                block_heights.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Reverts the sat ranges moved within a block range. Rollbacks deeper than the spent ranges kept reset the index,
+/// rebuilt from the blocks DB at the next start.
+pub fn rollback_sat_ranges_in_block_range(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    let Some(tip) = find_sat_ranges_tip(db_conn, ctx) else {
+        return;
+    };
+    if start_block > tip {
+        return;
+    }
+    if tip - start_block >= SAT_RANGES_SPENT_RETENTION_BLOCKS {
+        try_warn!(
+            ctx,
+            "Sat ranges: rolling back to block #{start_block} exceeds the {SAT_RANGES_SPENT_RETENTION_BLOCKS} blocks kept, resetting the index"
+        );
+        while let Err(e) =
+            db_conn.execute_batch("DELETE FROM sat_ranges; DELETE FROM sat_ranges_progress;")
+        {
+            try_warn!(ctx, "unable to query sat_ranges.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+        return;
+    }
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM sat_ranges WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query sat_ranges.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = db_conn.execute(
+        "UPDATE sat_ranges SET spent_block_height = NULL WHERE spent_block_height >= ?1 AND spent_block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+    ) {
+        try_warn!(ctx, "unable to query sat_ranges.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = match start_block.checked_sub(1) {
+        Some(block_height) => db_conn.execute(
+            "UPDATE sat_ranges_progress SET block_height = ?1 WHERE id = 0",
+            rusqlite::params![&block_height],
+        ),
+        None => db_conn.execute("DELETE FROM sat_ranges_progress", []),
+    } {
+        try_warn!(ctx, "unable to query sat_ranges.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Unspent sat ranges held by an address, ordered by first sat. With `rare_only`, only the ranges starting with a sat
+/// rarer than common are returned: the first sat of a range is the only one that can be rare.
+pub fn find_sat_ranges_held_by_address(
+    address: &str,
+    rare_only: bool,
+    after_start: Option<u64>,
+    offset: u64,
+    limit: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<SatRangeRow> {
+    let args: &[&dyn ToSql] = &[
+        &address.to_sql().unwrap(),
+        &after_start.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = match rare_only {
+        true => "SELECT COALESCE(outpoint, output_key), output_offset, start, end, rarity, block_height FROM sat_ranges WHERE address = ?1 AND spent_block_height IS NULL AND rarity IS NOT NULL AND (?2 IS NULL OR start > ?2) ORDER BY start LIMIT ?3 OFFSET ?4",
+        false => "SELECT COALESCE(outpoint, output_key), output_offset, start, end, rarity, block_height FROM sat_ranges WHERE address = ?1 AND spent_block_height IS NULL AND (?2 IS NULL OR start > ?2) ORDER BY start LIMIT ?3 OFFSET ?4",
+    };
+    perform_query_set(query, args, db_conn, ctx, |row| SatRangeRow {
+        outpoint: row.get(0).unwrap(),
+        offset: row.get(1).unwrap(),
+        start: row.get(2).unwrap(),
+        end: row.get(3).unwrap(),
+        rarity: row.get(4).unwrap(),
+        block_height: row.get(5).unwrap(),
+    })
+}
+
+/// Unspent rare sats held by an output, looked up by txid and output index.
+pub fn find_rare_sats_in_output(
+    txid: &str,
+    vout: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<SatRangeRow> {
+    let transaction_identifier = TransactionIdentifier {
+        hash: format!("0x{}", txid.trim_start_matches("0x")),
+    };
+    let output_key = format_output_key(&transaction_identifier.get_8_hash_bytes(), vout as usize);
+    let args: &[&dyn ToSql] = &[&output_key.to_sql().unwrap()];
+    let query = "SELECT COALESCE(outpoint, output_key), output_offset, start, end, rarity, block_height FROM sat_ranges WHERE output_key = ? AND spent_block_height IS NULL AND rarity IS NOT NULL ORDER BY output_offset";
+    perform_query_set(query, args, db_conn, ctx, |row| SatRangeRow {
+        outpoint: row.get(0).unwrap(),
+        offset: row.get(1).unwrap(),
+        start: row.get(2).unwrap(),
+        end: row.get(3).unwrap(),
+        rarity: row.get(4).unwrap(),
+        block_height: row.get(5).unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{distribute_sat_segments, SatSegment};
+
+    #[test]
+    fn distributes_sat_segments_across_outputs() {
+        let (outputs, fees) = distribute_sat_segments(
+            vec![SatSegment::Range(0, 10), SatSegment::Range(100, 105)],
+            &[4, 8, 0],
+        );
+        assert_eq!(
+            outputs,
+            vec![vec![(0, 0, 4)], vec![(0, 4, 10), (6, 100, 102)], vec![]]
+        );
+        assert_eq!(fees, vec![SatSegment::Range(102, 105)]);
+
+        let (outputs, fees) = distribute_sat_segments(vec![SatSegment::Range(0, 10)], &[4, 8]);
+        assert_eq!(outputs, vec![vec![(0, 0, 4)], vec![(0, 4, 10)]]);
+        assert!(fees.is_empty());
+    }
+
+    #[test]
+    fn distributes_untracked_sats() {
+        let (outputs, fees) = distribute_sat_segments(
+            vec![
+                SatSegment::Untracked(5),
+                SatSegment::Range(50, 51),
+                SatSegment::Untracked(10),
+            ],
+            &[3, 4],
+        );
+        assert_eq!(outputs, vec![vec![], vec![(2, 50, 51)]]);
+        assert_eq!(fees, vec![SatSegment::Untracked(9)]);
+    }
+}
@@ -1,93 +1,338 @@
-use crate::config::{Config, SnapshotConfig};
+pub mod progress;
+mod torrent;
+
+use crate::config::{Config, ResourcesConfig, SnapshotConfig};
+use crate::db::recovery::{
+    get_snapshot_restore_marker_path, recover_partial_snapshots, remove_partial_snapshot,
+};
+use crate::utils::http::outbound_http_client_builder;
 use crate::utils::read_file_content_at_path;
 use crate::{try_error, try_info, try_warn};
 use chainhook_sdk::utils::Context;
 use flate2::read::GzDecoder;
+use futures::future::join_all;
 use futures_util::StreamExt;
+use progress::{
+    set_snapshot_restore_phase, sha256_file, DownloadProgressReporter, SnapshotRestorePhase,
+};
 use progressing::mapping::Bar as MappingBar;
 use progressing::Baring;
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use std::cmp::Ordering;
 use std::fs::{self, File};
 use std::io::{self, Cursor};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tar::Archive;
+use torrent::{download_torrent_from_webseeds, read_torrent_metainfo};
+
+/// Limits the throughput of a download to `max_download_rate` bytes per second, when configured.
+struct DownloadThrottle {
+    max_rate: Option<u64>,
+    started_at: Instant,
+    consumed: u64,
+}
+
+impl DownloadThrottle {
+    fn new(max_rate: Option<u64>) -> DownloadThrottle {
+        DownloadThrottle {
+            max_rate,
+            started_at: Instant::now(),
+            consumed: 0,
+        }
+    }
+
+    /// Time to wait after having consumed `consumed` bytes in `elapsed`, to stay under `max_rate`.
+    fn delay(consumed: u64, max_rate: u64, elapsed: Duration) -> Duration {
+        let expected = Duration::from_secs_f64(consumed as f64 / max_rate.max(1) as f64);
+        expected.saturating_sub(elapsed)
+    }
+
+    async fn consume(&mut self, bytes: usize) {
+        let Some(max_rate) = self.max_rate else {
+            return;
+        };
+        self.consumed += bytes as u64;
+        let delay = Self::delay(self.consumed, max_rate, self.started_at.elapsed());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Waits until the window configured for heavy network operations opens.
+async fn wait_for_heavy_network_window(config: &Config, ctx: &Context) {
+    let Some(window) = config.resources.heavy_network_window else {
+        return;
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let minute_of_day = ((now / 60) % (24 * 60)) as u16;
+    let minutes = window.minutes_until_open(minute_of_day);
+    if minutes == 0 {
+        return;
+    }
+    try_info!(
+        ctx,
+        "Waiting {minutes} minutes for the heavy network window ({window} UTC) to open"
+    );
+    tokio::time::sleep(Duration::from_secs(minutes as u64 * 60)).await;
+}
+
+/// Mirrors are ranked by the throughput observed while downloading the first bytes of their archive.
+const MIRROR_PROBE_BYTES: u64 = 1_048_576;
+const MIRROR_PROBE_TIMEOUT_SECS: u64 = 10;
+
+/// Returns the throughput of `archive_url` in bytes per second, or `None` if the mirror is unreachable.
+async fn probe_mirror_throughput(client: &reqwest::Client, archive_url: String) -> Option<f64> {
+    let started_at = Instant::now();
+    let res = client
+        .get(&archive_url)
+        .header(RANGE, format!("bytes=0-{}", MIRROR_PROBE_BYTES - 1))
+        .timeout(Duration::from_secs(MIRROR_PROBE_TIMEOUT_SECS))
+        .send()
+        .await
+        .ok()?;
+    if !res.status().is_success() {
+        return None;
+    }
+    let mut stream = res.bytes_stream();
+    let mut received = 0;
+    while let Some(item) = stream.next().await {
+        received += item.ok()?.len() as u64;
+        if received >= MIRROR_PROBE_BYTES {
+            break;
+        }
+    }
+    let elapsed = started_at.elapsed().as_secs_f64().max(f64::EPSILON);
+    Some(received as f64 / elapsed)
+}
+
+/// Orders mirrors from the fastest to the slowest. Unreachable mirrors come last, in their configured order, so that
+/// they are still tried if the others fail.
+fn order_mirrors_by_throughput(probes: Vec<(String, Option<f64>)>) -> Vec<String> {
+    let (mut reachable, unreachable): (Vec<_>, Vec<_>) = probes
+        .into_iter()
+        .partition(|(_, throughput)| throughput.is_some());
+    reachable.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    reachable
+        .into_iter()
+        .chain(unreachable)
+        .map(|(url, _)| url)
+        .collect()
+}
+
+/// Client used for the snapshot downloads. Only connecting is bounded in time: archives take hours to download, and
+/// stalled transfers are detected through `network_read_timeout_secs` instead.
+fn build_snapshot_http_client(resources: &ResourcesConfig) -> Result<reqwest::Client, String> {
+    outbound_http_client_builder(resources)?
+        .build()
+        .map_err(|e| format!("unable to build http client: {e}"))
+}
+
+/// Probes the archives of `snapshot_urls` in parallel, and returns the urls from the fastest mirror to the slowest.
+async fn rank_snapshot_mirrors(
+    client: &reqwest::Client,
+    snapshot_urls: Vec<String>,
+    ctx: &Context,
+) -> Vec<String> {
+    if snapshot_urls.len() < 2 {
+        return snapshot_urls;
+    }
+    let throughputs = join_all(
+        snapshot_urls
+            .iter()
+            .map(|url| probe_mirror_throughput(client, format!("{url}.tar.gz"))),
+    )
+    .await;
+    for (url, throughput) in snapshot_urls.iter().zip(throughputs.iter()) {
+        match throughput {
+            Some(throughput) => {
+                try_info!(
+                    ctx,
+                    "Snapshot mirror {url}: {:.2} MB/s",
+                    throughput / 1_000_000.0
+                );
+            }
+            None => {
+                try_warn!(ctx, "Snapshot mirror {url} is not reachable");
+            }
+        }
+    }
+    order_mirrors_by_throughput(snapshot_urls.into_iter().zip(throughputs).collect())
+}
+
+/// Requests the archive from the next mirror responding, starting at `offset`. Each mirror is retried
+/// `network_max_retries` times before moving on to the next one.
+async fn open_archive_stream(
+    client: &reqwest::Client,
+    mirrors: &mut impl Iterator<Item = String>,
+    offset: i64,
+    resources: &ResourcesConfig,
+    ctx: &Context,
+) -> Option<(String, reqwest::Response)> {
+    for url in mirrors {
+        try_info!(ctx, "=> {url}");
+        for attempt in 0..=resources.network_max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(resources.network_retry_backoff(attempt)).await;
+            }
+            let mut req = client.get(&url);
+            if offset > 0 {
+                req = req.header(RANGE, format!("bytes={offset}-"));
+            }
+            match req.send().await {
+                Ok(res)
+                    if res.status() == StatusCode::OK
+                        || res.status() == StatusCode::PARTIAL_CONTENT =>
+                {
+                    return Some((url, res))
+                }
+                Ok(res) => {
+                    try_warn!(ctx, "Snapshot mirror {url} responded with {}", res.status());
+                }
+                Err(e) => {
+                    try_warn!(ctx, "Snapshot mirror {url} is not reachable: {e}");
+                }
+            }
+        }
+    }
+    None
+}
 
-/// Downloads and decompresses a remote `tar.gz` file.
+/// Downloads and decompresses a remote `tar.gz` file, served identically by each of `archive_urls`. Mirrors are tried
+/// in order, and an interrupted download resumes from the next mirror where it stopped.
 pub async fn download_and_decompress_archive_file(
-    file_url: String,
+    archive_urls: Vec<String>,
     file_name: &str,
     config: &Config,
     ctx: &Context,
 ) -> Result<(), String> {
-    let destination_dir_path = config.expected_cache_path();
+    let destination_dir_path = config.expected_sqlite_path();
     std::fs::create_dir_all(&destination_dir_path).unwrap_or_else(|e| {
         try_error!(ctx, "{e}");
     });
 
-    try_info!(ctx, "=> {file_url}");
-    let res = reqwest::get(&file_url)
-        .await
-        .or(Err(format!("Failed to GET from '{}'", &file_url)))?;
+    let client = build_snapshot_http_client(&config.resources)?;
+    let read_timeout = Duration::from_secs(config.resources.network_read_timeout_secs);
+    let mut mirrors = archive_urls.into_iter();
+    let Some((mut url, mut res)) =
+        open_archive_stream(&client, &mut mirrors, 0, &config.resources, ctx).await
+    else {
+        return Err(format!(
+            "unable to download {file_name} archive from any snapshot mirror"
+        ));
+    };
 
     // Download chunks
     let (tx, rx) = flume::bounded(0);
-    if res.status() == reqwest::StatusCode::OK {
-        let limit = res.content_length().unwrap_or(10_000_000_000) as i64;
-        let archive_tmp_file = PathBuf::from(format!("{file_name}.tar.gz"));
-        let decoder_thread = std::thread::spawn(move || {
-            {
-                let input = ChannelRead::new(rx);
-                let mut decoder = GzDecoder::new(input);
-                let mut tmp = File::create(&archive_tmp_file).unwrap();
-                let mut buffer = [0; 512_000];
-                loop {
-                    match decoder.read(&mut buffer) {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            if let Err(e) = tmp.write_all(&buffer[..n]) {
-                                let err = format!(
-                                    "unable to update compressed archive: {}",
-                                    e.to_string()
-                                );
-                                return Err(err);
-                            }
-                        }
-                        Err(e) => {
+    let limit = res.content_length().unwrap_or(10_000_000_000) as i64;
+    let mut reporter = DownloadProgressReporter::new(file_name, res.content_length());
+    let archive_tmp_file = PathBuf::from(format!("{file_name}.tar.gz"));
+    let decoder_thread = std::thread::spawn(move || {
+        {
+            let input = ChannelRead::new(rx);
+            let mut decoder = GzDecoder::new(input);
+            let mut tmp = File::create(&archive_tmp_file).unwrap();
+            let mut buffer = [0; 512_000];
+            loop {
+                match decoder.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if let Err(e) = tmp.write_all(&buffer[..n]) {
                             let err =
-                                format!("unable to write compressed archive: {}", e.to_string());
+                                format!("unable to update compressed archive: {}", e.to_string());
                             return Err(err);
                         }
                     }
+                    Err(e) => {
+                        let err = format!("unable to write compressed archive: {}", e.to_string());
+                        return Err(err);
+                    }
                 }
-                let _ = tmp.flush();
             }
-            let archive_file = File::open(&archive_tmp_file).unwrap();
-            let mut archive = Archive::new(archive_file);
-            if let Err(e) = archive.unpack(&destination_dir_path) {
-                let err = format!("unable to decompress file: {}", e.to_string());
-                return Err(err);
-            }
-            let _ = fs::remove_file(archive_tmp_file);
-            Ok(())
-        });
-
-        let mut progress_bar = MappingBar::with_range(0i64, limit);
-        progress_bar.set_len(60);
-        let mut stdout = std::io::stdout();
-        if ctx.logger.is_some() {
-            print!("{}", progress_bar);
-            let _ = stdout.flush();
+            let _ = tmp.flush();
         }
+        let archive_file = File::open(&archive_tmp_file).unwrap();
+        let mut archive = Archive::new(archive_file);
+        if let Err(e) = archive.unpack(&destination_dir_path) {
+            let err = format!("unable to decompress file: {}", e.to_string());
+            return Err(err);
+        }
+        let _ = fs::remove_file(archive_tmp_file);
+        Ok(())
+    });
+
+    let mut progress_bar = MappingBar::with_range(0i64, limit);
+    progress_bar.set_len(60);
+    let mut stdout = std::io::stdout();
+    if ctx.logger.is_some() {
+        print!("{}", progress_bar);
+        let _ = stdout.flush();
+    }
+    let mut throttle = DownloadThrottle::new(config.resources.max_download_rate);
+    let mut progress = 0;
+    let mut steps = 0;
+    let mut download_err = None;
+    'download: loop {
+        // A mirror ignoring the range requested sends the archive from its start again.
+        let mut skip = match res.status() {
+            StatusCode::PARTIAL_CONTENT => 0,
+            _ => progress as usize,
+        };
         let mut stream = res.bytes_stream();
-        let mut progress = 0;
-        let mut steps = 0;
-        let mut tx_err = None;
-        while let Some(item) = stream.next().await {
-            let chunk = item.or(Err(format!("Error while downloading file")))?;
+        loop {
+            // Stalled transfers are treated like interrupted ones.
+            let item = match tokio::time::timeout(read_timeout, stream.next()).await {
+                Ok(Some(item)) => item.map_err(|e| e.to_string()),
+                Ok(None) => break,
+                Err(_) => Err(format!("no data received for {:?}", read_timeout)),
+            };
+            let mut chunk = match item {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    try_warn!(
+                        ctx,
+                        "Download from {url} interrupted after {progress} bytes: {e}"
+                    );
+                    match open_archive_stream(
+                        &client,
+                        &mut mirrors,
+                        progress,
+                        &config.resources,
+                        ctx,
+                    )
+                    .await
+                    {
+                        Some((next_url, next_res)) => {
+                            url = next_url;
+                            res = next_res;
+                            continue 'download;
+                        }
+                        None => {
+                            download_err = Some(format!(
+                                "unable to download {file_name} archive: no snapshot mirror left to resume from"
+                            ));
+                            break 'download;
+                        }
+                    }
+                }
+            };
+            if skip > 0 {
+                let skipped = skip.min(chunk.len());
+                skip -= skipped;
+                chunk = chunk.slice(skipped..);
+            }
             if chunk.is_empty() {
                 continue;
             }
+            throttle.consume(chunk.len()).await;
             progress += chunk.len() as i64;
+            reporter.record(progress as u64, ctx);
             steps += chunk.len() as i64;
             if steps > 5_000_000 {
                 steps = 0;
@@ -101,22 +346,94 @@ pub async fn download_and_decompress_archive_file(
             }
             if let Err(e) = tx.send_async(chunk.to_vec()).await {
                 let err = format!("unable to download archive: {}", e.to_string());
-                tx_err = Some(err);
-                break;
+                download_err = Some(err);
+                break 'download;
             }
         }
-        progress_bar.set(limit);
-        if ctx.logger.is_some() {
-            print!("\r{}", progress_bar);
-            let _ = stdout.flush();
-            println!();
-        }
-        drop(tx);
+        break;
+    }
+    progress_bar.set(limit);
+    if ctx.logger.is_some() {
+        print!("\r{}", progress_bar);
+        let _ = stdout.flush();
+        println!();
+    }
+    drop(tx);
+
+    if download_err.is_none() {
+        set_snapshot_restore_phase(file_name, SnapshotRestorePhase::Extracting, ctx);
+    }
+    let decoded = decoder_thread.join().unwrap();
+    if let Some(e) = download_err.take() {
+        return Err(e);
+    }
+    decoded
+}
+
+/// Downloads a remote `tar.gz` file through the webseeds of its torrent, verifying each piece, and decompresses it.
+async fn download_and_decompress_torrent_file(
+    torrent_source: &str,
+    file_name: &str,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let destination_dir_path = config.expected_sqlite_path();
+    std::fs::create_dir_all(&destination_dir_path).unwrap_or_else(|e| {
+        try_error!(ctx, "{e}");
+    });
 
-        decoder_thread.join().unwrap()?;
-        if let Some(_e) = tx_err.take() {}
+    try_info!(ctx, "=> {torrent_source}");
+    let client = build_snapshot_http_client(&config.resources)?;
+    let metainfo = read_torrent_metainfo(&client, torrent_source).await?;
+    let archive_tmp_file = PathBuf::from(format!("{file_name}.tar.gz"));
+    let mut throttle = DownloadThrottle::new(config.resources.max_download_rate);
+    let mut reporter = DownloadProgressReporter::new(file_name, Some(metainfo.length));
+    download_torrent_from_webseeds(
+        &client,
+        &metainfo,
+        &archive_tmp_file,
+        &mut throttle,
+        &mut reporter,
+        ctx,
+    )
+    .await?;
+
+    set_snapshot_restore_phase(file_name, SnapshotRestorePhase::Extracting, ctx);
+    let archive_file = File::open(&archive_tmp_file)
+        .map_err(|e| format!("unable to open downloaded archive: {e}"))?;
+    let mut archive = Archive::new(GzDecoder::new(archive_file));
+    if let Err(e) = archive.unpack(&destination_dir_path) {
+        let err = format!("unable to decompress file: {}", e.to_string());
+        return Err(err);
     }
+    let _ = fs::remove_file(archive_tmp_file);
+    Ok(())
+}
 
+/// Checks the SHA256 of a restored database against the one published next to its archive.
+fn verify_restored_database(
+    sqlite_file_path: &Path,
+    expected_sha: Option<&str>,
+    file_name: &str,
+    ctx: &Context,
+) -> Result<(), String> {
+    set_snapshot_restore_phase(file_name, SnapshotRestorePhase::Verifying, ctx);
+    let Some(expected_sha) = expected_sha else {
+        try_warn!(
+            ctx,
+            "Snapshot {file_name}: no checksum published, {} not verified",
+            sqlite_file_path.display()
+        );
+        return Ok(());
+    };
+    let sha = sha256_file(sqlite_file_path)?;
+    if sha != expected_sha {
+        return Err(format!(
+            "{} sha256 is {sha}, expected {expected_sha}",
+            sqlite_file_path.display()
+        ));
+    }
+    try_info!(ctx, "Snapshot {file_name}: checksum verified");
     Ok(())
 }
 
@@ -150,27 +467,52 @@ impl Read for ChannelRead {
     }
 }
 
-/// Compares the SHA256 of a previous local archive to the latest remote archive and downloads if required.
+/// Removes the files of a failed restore, for the next run to download the snapshot again.
+fn discard_partial_snapshot(file_name: &str, config: &Config, ctx: &Context) {
+    if let Err(e) = remove_partial_snapshot(&config.expected_sqlite_path(), file_name) {
+        try_warn!(
+            ctx,
+            "Unable to remove the {file_name} snapshot restored partially: {e}"
+        );
+    }
+}
+
+/// Compares the SHA256 of a previous local archive to the latest remote archive and downloads if required, through
+/// `torrent` when configured, or else from the fastest of `snapshot_urls`.
 async fn validate_or_download_archive_file(
-    snapshot_url: &String,
+    snapshot_urls: Vec<String>,
+    torrent: Option<&String>,
     file_name: &str,
     config: &Config,
     ctx: &Context,
 ) {
-    let remote_archive_url = format!("{snapshot_url}.tar.gz");
-    let remote_sha_url = format!("{snapshot_url}.sha256");
+    let client = match build_snapshot_http_client(&config.resources) {
+        Ok(client) => client,
+        Err(e) => {
+            try_error!(ctx, "{e}");
+            std::process::exit(1);
+        }
+    };
+    let snapshot_urls = rank_snapshot_mirrors(&client, snapshot_urls, ctx).await;
+    let remote_sha_url = format!("{}.sha256", snapshot_urls[0]);
 
-    let mut local_sqlite_file_path = config.expected_cache_path();
+    let mut local_sqlite_file_path = config.expected_sqlite_path();
     local_sqlite_file_path.push(format!("{file_name}.sqlite"));
-    let mut local_sha_file_path = config.expected_cache_path();
+    let mut local_sha_file_path = config.expected_sqlite_path();
     local_sha_file_path.push(format!("{file_name}.sqlite.sha256"));
 
     // Compare local SHA256 to remote to see if there's a new one available.
     let local_sha_file = read_file_content_at_path(&local_sha_file_path);
-    let remote_sha_file = match reqwest::get(&remote_sha_url).await {
+    let remote_sha_file = match client.get(&remote_sha_url).send().await {
         Ok(response) => response.bytes().await,
         Err(e) => Err(e),
     };
+    let remote_sha = remote_sha_file
+        .as_ref()
+        .ok()
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .and_then(|content| content.split_whitespace().next())
+        .map(|sha| sha.to_lowercase());
     let should_download = match (local_sha_file, remote_sha_file) {
         (Ok(local), Ok(remote_response)) => {
             let cache_not_expired = remote_response.starts_with(&local[0..32]) == false;
@@ -189,16 +531,52 @@ async fn validate_or_download_archive_file(
     };
 
     if should_download {
-        try_info!(ctx, "Downloading {remote_archive_url}");
-        match download_and_decompress_archive_file(remote_archive_url, file_name, &config, &ctx)
-            .await
-        {
-            Ok(_) => {}
-            Err(e) => {
+        // Left behind if the restore is interrupted, so that the partially extracted files get cleaned up on startup.
+        let marker_path =
+            get_snapshot_restore_marker_path(&config.expected_sqlite_path(), file_name);
+        let _ = std::fs::create_dir_all(config.expected_sqlite_path());
+        if let Err(e) = std::fs::write(&marker_path, []) {
+            try_warn!(ctx, "Unable to create {}: {e}", marker_path.display());
+        }
+        wait_for_heavy_network_window(config, ctx).await;
+        let mut restored = false;
+        if let Some(torrent) = torrent {
+            try_info!(ctx, "Downloading {file_name} archive via torrent");
+            match download_and_decompress_torrent_file(torrent, file_name, config, ctx).await {
+                Ok(_) => restored = true,
+                Err(e) => {
+                    try_warn!(
+                        ctx,
+                        "Unable to download {file_name} archive via torrent ({e}), falling back to snapshot urls"
+                    );
+                }
+            }
+        }
+        if !restored {
+            let archive_urls = snapshot_urls
+                .iter()
+                .map(|url| format!("{url}.tar.gz"))
+                .collect();
+            try_info!(ctx, "Downloading {file_name} archive");
+            if let Err(e) =
+                download_and_decompress_archive_file(archive_urls, file_name, &config, &ctx).await
+            {
+                discard_partial_snapshot(file_name, config, ctx);
                 try_error!(ctx, "{e}");
                 std::process::exit(1);
             }
         }
+        if let Err(e) = verify_restored_database(
+            &local_sqlite_file_path,
+            remote_sha.as_deref(),
+            file_name,
+            ctx,
+        ) {
+            discard_partial_snapshot(file_name, config, ctx);
+            try_error!(ctx, "{e}");
+            std::process::exit(1);
+        }
+        let _ = std::fs::remove_file(&marker_path);
     } else {
         try_info!(
             ctx,
@@ -213,17 +591,73 @@ pub async fn download_archive_datasets_if_required(config: &Config, ctx: &Contex
     if !config.should_bootstrap_through_download() {
         return;
     }
+    // A restore interrupted midway left databases which would otherwise be taken for complete ones.
+    match recover_partial_snapshots(&config.expected_sqlite_path()) {
+        Ok(actions) => {
+            for action in actions.iter() {
+                try_info!(ctx, "Recovery: {action}");
+            }
+        }
+        Err(e) => {
+            try_error!(ctx, "{e}");
+            std::process::exit(1);
+        }
+    }
     let snapshot_urls = match &config.snapshot {
         SnapshotConfig::Build => unreachable!(),
         SnapshotConfig::Download(url) => url,
     };
-    validate_or_download_archive_file(&snapshot_urls.ordinals, "hord", config, ctx).await;
+    validate_or_download_archive_file(
+        snapshot_urls.ordinals_urls(),
+        snapshot_urls.ordinals_torrent.as_ref(),
+        "hord",
+        config,
+        ctx,
+    )
+    .await;
     if config.meta_protocols.brc20 {
-        match &snapshot_urls.brc20 {
-            Some(url) => validate_or_download_archive_file(url, "brc20", config, ctx).await,
-            None => {
+        match snapshot_urls.brc20_urls() {
+            urls if urls.is_empty() => {
                 try_warn!(ctx, "No brc20 snapshot url configured");
             }
+            urls => {
+                validate_or_download_archive_file(
+                    urls,
+                    snapshot_urls.brc20_torrent.as_ref(),
+                    "brc20",
+                    config,
+                    ctx,
+                )
+                .await
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{order_mirrors_by_throughput, DownloadThrottle};
+
+    #[test]
+    fn orders_mirrors_fastest_first_and_unreachable_last() {
+        let probes = vec![
+            ("https://a".to_string(), None),
+            ("https://b".to_string(), Some(1_000.0)),
+            ("https://c".to_string(), Some(5_000.0)),
+            ("https://d".to_string(), None),
+        ];
+        assert_eq!(
+            order_mirrors_by_throughput(probes),
+            vec!["https://c", "https://b", "https://a", "https://d"]
+        );
+    }
+
+    #[test]
+    fn throttles_downloads_above_max_rate() {
+        let delay = DownloadThrottle::delay(2_000, 1_000, Duration::from_millis(500));
+        assert_eq!(delay, Duration::from_millis(1_500));
+        assert!(DownloadThrottle::delay(1_000, 1_000, Duration::from_secs(2)).is_zero());
+    }
+}
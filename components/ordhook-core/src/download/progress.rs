@@ -0,0 +1,112 @@
+use std::{
+    fs::File,
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine},
+    utils::Context,
+};
+
+use crate::try_info;
+
+/// Interval between two progress log lines of a download.
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Phases of the restore of a snapshot archive (`hord`, `brc20`), logged as they start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotRestorePhase {
+    Extracting,
+    Verifying,
+}
+
+pub fn set_snapshot_restore_phase(archive: &str, phase: SnapshotRestorePhase, ctx: &Context) {
+    try_info!(ctx, "Snapshot {archive}: {phase:?}");
+}
+
+/// Seconds left to download `total` bytes, at the rate observed so far.
+fn estimate_eta_secs(downloaded: u64, total: Option<u64>, elapsed: Duration) -> Option<u64> {
+    let total = total?;
+    if downloaded == 0 || elapsed.is_zero() {
+        return None;
+    }
+    let rate = downloaded as f64 / elapsed.as_secs_f64();
+    Some((total.saturating_sub(downloaded) as f64 / rate).ceil() as u64)
+}
+
+/// Logs the progress of the download of an archive periodically.
+pub struct DownloadProgressReporter {
+    archive: String,
+    bytes_total: Option<u64>,
+    started_at: Instant,
+    logged_at: Instant,
+}
+
+impl DownloadProgressReporter {
+    pub fn new(archive: &str, bytes_total: Option<u64>) -> DownloadProgressReporter {
+        DownloadProgressReporter {
+            archive: archive.to_string(),
+            bytes_total,
+            started_at: Instant::now(),
+            logged_at: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, bytes_downloaded: u64, ctx: &Context) {
+        if self.logged_at.elapsed() >= PROGRESS_LOG_INTERVAL {
+            self.logged_at = Instant::now();
+            let eta_secs = estimate_eta_secs(
+                bytes_downloaded,
+                self.bytes_total,
+                self.started_at.elapsed(),
+            );
+            try_info!(
+                ctx,
+                "Snapshot {}: {bytes_downloaded}/{} bytes downloaded, eta {}s",
+                self.archive,
+                self.bytes_total
+                    .map(|total| total.to_string())
+                    .unwrap_or("?".into()),
+                eta_secs.map(|eta| eta.to_string()).unwrap_or("?".into())
+            );
+        }
+    }
+}
+
+/// Computes the SHA256 of the file at `path`, hex encoded.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    let mut engine = sha256::Hash::engine();
+    let mut buffer = vec![0; 1_048_576];
+    loop {
+        match io::Read::read(&mut file, &mut buffer) {
+            Ok(0) => break,
+            Ok(n) => engine.input(&buffer[..n]),
+            Err(e) => return Err(format!("unable to read {}: {e}", path.display())),
+        }
+    }
+    Ok(sha256::Hash::from_engine(engine).to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::estimate_eta_secs;
+
+    #[test]
+    fn estimates_download_eta() {
+        assert_eq!(
+            estimate_eta_secs(250, Some(1_000), Duration::from_secs(10)),
+            Some(30)
+        );
+        assert_eq!(
+            estimate_eta_secs(0, Some(1_000), Duration::from_secs(10)),
+            None
+        );
+        assert_eq!(estimate_eta_secs(250, None, Duration::from_secs(10)), None);
+    }
+}
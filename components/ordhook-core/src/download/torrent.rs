@@ -0,0 +1,410 @@
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha1, Hash},
+    utils::Context,
+};
+use futures_util::StreamExt;
+use reqwest::{header::RANGE, StatusCode};
+
+use super::{progress::DownloadProgressReporter, DownloadThrottle};
+use crate::try_warn;
+
+/// Pieces fetched concurrently from the webseeds.
+const WEBSEED_CONCURRENT_PIECES: usize = 8;
+
+/// Nesting of lists and dictionaries accepted in a metainfo file. Metainfo files of single file torrents nest 3 levels
+/// deep, the bound keeps a crafted file from overflowing the stack.
+const MAX_BENCODE_DEPTH: usize = 32;
+
+/// Largest piece length accepted, pieces being held in memory until verified. Clients use pieces of 16 MiB at most.
+const MAX_PIECE_LENGTH: u64 = 32 * 1024 * 1024;
+
+/// Largest metainfo file accepted.
+const MAX_METAINFO_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Bencode {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dict(BTreeMap<Vec<u8>, Bencode>),
+}
+
+impl Bencode {
+    fn get(&self, key: &str) -> Option<&Bencode> {
+        match self {
+            Bencode::Dict(entries) => entries.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Bencode::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Bencode::Bytes(bytes) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+}
+
+fn find_byte(input: &[u8], from: usize, byte: u8) -> Result<usize, String> {
+    input[from..]
+        .iter()
+        .position(|b| *b == byte)
+        .map(|i| from + i)
+        .ok_or("truncated bencoded value".to_string())
+}
+
+fn parse_number<T: std::str::FromStr>(input: &[u8]) -> Result<T, String> {
+    std::str::from_utf8(input)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or("invalid bencoded number".to_string())
+}
+
+fn decode_bencode(input: &[u8], pos: &mut usize, depth: usize) -> Result<Bencode, String> {
+    if depth > MAX_BENCODE_DEPTH {
+        return Err("bencoded value nested too deeply".into());
+    }
+    match input.get(*pos) {
+        Some(b'i') => {
+            let end = find_byte(input, *pos, b'e')?;
+            let n = parse_number(&input[*pos + 1..end])?;
+            *pos = end + 1;
+            Ok(Bencode::Int(n))
+        }
+        Some(b'l') => {
+            *pos += 1;
+            let mut items = vec![];
+            loop {
+                match input.get(*pos) {
+                    Some(b'e') => break,
+                    None => return Err("truncated bencoded list".into()),
+                    _ => items.push(decode_bencode(input, pos, depth + 1)?),
+                }
+            }
+            *pos += 1;
+            Ok(Bencode::List(items))
+        }
+        Some(b'd') => {
+            *pos += 1;
+            let mut entries = BTreeMap::new();
+            loop {
+                match input.get(*pos) {
+                    Some(b'e') => break,
+                    None => return Err("truncated bencoded dictionary".into()),
+                    _ => {
+                        let Bencode::Bytes(key) = decode_bencode(input, pos, depth + 1)? else {
+                            return Err("bencoded dictionary keys must be strings".into());
+                        };
+                        entries.insert(key, decode_bencode(input, pos, depth + 1)?);
+                    }
+                }
+            }
+            *pos += 1;
+            Ok(Bencode::Dict(entries))
+        }
+        Some(b'0'..=b'9') => {
+            let colon = find_byte(input, *pos, b':')?;
+            let len: usize = parse_number(&input[*pos..colon])?;
+            let start = colon + 1;
+            let end = start
+                .checked_add(len)
+                .filter(|end| *end <= input.len())
+                .ok_or("truncated bencoded string".to_string())?;
+            *pos = end;
+            Ok(Bencode::Bytes(input[start..end].to_vec()))
+        }
+        _ => Err("invalid bencoded value".into()),
+    }
+}
+
+/// Single file torrent, as described by its `.torrent` metainfo file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TorrentMetainfo {
+    pub name: String,
+    pub length: u64,
+    pub piece_length: u64,
+    /// SHA1 of each piece.
+    pub pieces: Vec<[u8; 20]>,
+    /// HTTP(S) servers seeding the file (BEP 19).
+    pub webseeds: Vec<String>,
+}
+
+impl TorrentMetainfo {
+    fn piece_range(&self, index: usize) -> (u64, u64) {
+        let start = index as u64 * self.piece_length;
+        let end = (start + self.piece_length).min(self.length) - 1;
+        (start, end)
+    }
+}
+
+pub fn parse_torrent_metainfo(input: &[u8]) -> Result<TorrentMetainfo, String> {
+    if input.len() > MAX_METAINFO_BYTES {
+        return Err(format!(
+            "torrent metainfo is larger than {MAX_METAINFO_BYTES} bytes"
+        ));
+    }
+    let mut pos = 0;
+    let metainfo = decode_bencode(input, &mut pos, 0)?;
+    let info = metainfo
+        .get("info")
+        .ok_or("torrent is missing its info dictionary")?;
+    if info.get("files").is_some() {
+        return Err("multi-file torrents are not supported".into());
+    }
+    let name = info
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("torrent is missing its name")?
+        .to_string();
+    let length = info
+        .get("length")
+        .and_then(|v| v.as_int())
+        .filter(|n| *n > 0)
+        .ok_or("torrent is missing its length")? as u64;
+    let piece_length = info
+        .get("piece length")
+        .and_then(|v| v.as_int())
+        .filter(|n| *n > 0)
+        .ok_or("torrent is missing its piece length")? as u64;
+    if piece_length > MAX_PIECE_LENGTH {
+        return Err(format!(
+            "torrent piece length {piece_length} is larger than {MAX_PIECE_LENGTH} bytes"
+        ));
+    }
+    let Some(Bencode::Bytes(pieces)) = info.get("pieces") else {
+        return Err("torrent is missing its pieces".into());
+    };
+    if pieces.len() % 20 != 0 {
+        return Err("torrent pieces are not a list of SHA1 hashes".into());
+    }
+    let pieces: Vec<[u8; 20]> = pieces
+        .chunks(20)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect();
+    if pieces.len() as u64 != length.div_ceil(piece_length) {
+        return Err("torrent pieces don't cover its length".into());
+    }
+    let webseeds = match metainfo.get("url-list") {
+        Some(Bencode::List(urls)) => urls
+            .iter()
+            .filter_map(|url| url.as_str().map(|s| s.to_string()))
+            .collect(),
+        Some(url) => url.as_str().map(|s| s.to_string()).into_iter().collect(),
+        None => vec![],
+    };
+    Ok(TorrentMetainfo {
+        name,
+        length,
+        piece_length,
+        pieces,
+        webseeds,
+    })
+}
+
+/// A webseed ending with `/` is a directory containing the file, otherwise it is the file itself.
+fn webseed_file_url(webseed: &str, name: &str) -> String {
+    match webseed.ends_with('/') {
+        true => format!("{webseed}{name}"),
+        false => webseed.to_string(),
+    }
+}
+
+fn verify_piece(piece: &[u8], expected: &[u8; 20]) -> bool {
+    sha1::Hash::hash(piece).to_byte_array() == *expected
+}
+
+/// Reads the body of a piece response, refusing bodies longer than the piece.
+async fn read_piece(res: reqwest::Response, piece_length: u64) -> Result<Vec<u8>, String> {
+    let mut piece = Vec::with_capacity(piece_length as usize);
+    let mut stream = res.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if (piece.len() + chunk.len()) as u64 > piece_length {
+            return Err(format!(
+                "response is longer than the piece ({piece_length} bytes)"
+            ));
+        }
+        piece.extend_from_slice(&chunk);
+    }
+    Ok(piece)
+}
+
+/// Fetches a piece from the webseeds, starting with a different webseed for each piece so that they share the load,
+/// and moving on to the next one when a piece can't be fetched or fails its hash verification.
+async fn fetch_piece(
+    client: &reqwest::Client,
+    metainfo: &TorrentMetainfo,
+    index: usize,
+    ctx: &Context,
+) -> Result<Vec<u8>, String> {
+    let (start, end) = metainfo.piece_range(index);
+    let webseeds = &metainfo.webseeds;
+    for attempt in 0..webseeds.len() {
+        let url = webseed_file_url(
+            &webseeds[(index + attempt) % webseeds.len()],
+            &metainfo.name,
+        );
+        let res = match client
+            .get(&url)
+            .header(RANGE, format!("bytes={start}-{end}"))
+            .send()
+            .await
+        {
+            Ok(res) if res.status() == StatusCode::PARTIAL_CONTENT => res,
+            Ok(res) => {
+                try_warn!(ctx, "Webseed {url} responded with {}", res.status());
+                continue;
+            }
+            Err(e) => {
+                try_warn!(ctx, "Webseed {url} is not reachable: {e}");
+                continue;
+            }
+        };
+        match read_piece(res, end - start + 1).await {
+            Ok(piece) if verify_piece(&piece, &metainfo.pieces[index]) => return Ok(piece),
+            Ok(_) => {
+                try_warn!(ctx, "Piece {index} from {url} failed hash verification");
+            }
+            Err(e) => {
+                try_warn!(ctx, "Unable to fetch piece {index} from {url}: {e}");
+            }
+        }
+    }
+    Err(format!(
+        "unable to fetch piece {index} of {} from any webseed",
+        metainfo.name
+    ))
+}
+
+/// Downloads the file of a torrent from its webseeds into `destination`, verifying the hash of each piece.
+pub async fn download_torrent_from_webseeds(
+    client: &reqwest::Client,
+    metainfo: &TorrentMetainfo,
+    destination: &Path,
+    throttle: &mut DownloadThrottle,
+    reporter: &mut DownloadProgressReporter,
+    ctx: &Context,
+) -> Result<(), String> {
+    if metainfo.webseeds.is_empty() {
+        return Err(format!("torrent {} has no webseeds", metainfo.name));
+    }
+    let mut file = File::create(destination)
+        .map_err(|e| format!("unable to create {}: {e}", destination.display()))?;
+    let mut pieces = futures::stream::iter(0..metainfo.pieces.len())
+        .map(|index| fetch_piece(client, metainfo, index, ctx))
+        .buffered(WEBSEED_CONCURRENT_PIECES);
+    let mut downloaded = 0;
+    while let Some(piece) = pieces.next().await {
+        let piece = piece?;
+        throttle.consume(piece.len()).await;
+        file.write_all(&piece)
+            .map_err(|e| format!("unable to write {}: {e}", destination.display()))?;
+        downloaded += piece.len() as u64;
+        reporter.record(downloaded, ctx);
+    }
+    file.flush()
+        .map_err(|e| format!("unable to write {}: {e}", destination.display()))
+}
+
+/// Reads a `.torrent` metainfo file, from an HTTP(S) url or a local path.
+pub async fn read_torrent_metainfo(
+    client: &reqwest::Client,
+    source: &str,
+) -> Result<TorrentMetainfo, String> {
+    let bytes = match source.starts_with("http://") || source.starts_with("https://") {
+        true => client
+            .get(source)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("unable to fetch torrent {source}: {e}"))?
+            .bytes()
+            .await
+            .map_err(|e| format!("unable to fetch torrent {source}: {e}"))?
+            .to_vec(),
+        false => {
+            std::fs::read(source).map_err(|e| format!("unable to read torrent {source}: {e}"))?
+        }
+    };
+    parse_torrent_metainfo(&bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::bitcoincore_rpc::bitcoin::hashes::{sha1, Hash};
+
+    use super::{
+        parse_torrent_metainfo, verify_piece, webseed_file_url, MAX_BENCODE_DEPTH, MAX_PIECE_LENGTH,
+    };
+
+    fn torrent(length: u64, piece_length: u64, pieces: &[&[u8]], url_list: &str) -> Vec<u8> {
+        let hashes: Vec<u8> = pieces
+            .iter()
+            .flat_map(|piece| sha1::Hash::hash(piece).to_byte_array())
+            .collect();
+        let mut bytes = format!(
+            "d8:url-list{url_list}4:infod6:lengthi{length}e4:name11:hord.tar.gz12:piece lengthi{piece_length}e6:pieces{}:",
+            hashes.len()
+        )
+        .into_bytes();
+        bytes.extend(hashes);
+        bytes.extend(b"ee");
+        bytes
+    }
+
+    #[test]
+    fn parses_single_file_torrents() {
+        let metainfo = parse_torrent_metainfo(&torrent(
+            5,
+            4,
+            &[b"abcd", b"e"],
+            "l18:https://a.example/18:https://b.example/e",
+        ))
+        .unwrap();
+        assert_eq!(metainfo.name, "hord.tar.gz");
+        assert_eq!(metainfo.length, 5);
+        assert_eq!(metainfo.pieces.len(), 2);
+        assert_eq!(metainfo.piece_range(1), (4, 4));
+        assert_eq!(metainfo.webseeds.len(), 2);
+        assert!(verify_piece(b"abcd", &metainfo.pieces[0]));
+        assert!(!verify_piece(b"abce", &metainfo.pieces[0]));
+    }
+
+    #[test]
+    fn rejects_pieces_not_covering_the_file() {
+        assert!(parse_torrent_metainfo(&torrent(9, 4, &[b"abcd", b"efgh"], "0:")).is_err());
+        assert!(parse_torrent_metainfo(b"d4:infod").is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_torrents() {
+        let piece_length = MAX_PIECE_LENGTH + 1;
+        assert!(
+            parse_torrent_metainfo(&torrent(piece_length, piece_length, &[b"a"], "0:")).is_err()
+        );
+        let mut nested = "l".repeat(MAX_BENCODE_DEPTH * 1_000).into_bytes();
+        nested.extend("e".repeat(MAX_BENCODE_DEPTH * 1_000).into_bytes());
+        assert!(parse_torrent_metainfo(&nested).is_err());
+    }
+
+    #[test]
+    fn resolves_webseed_urls() {
+        assert_eq!(
+            webseed_file_url("https://a.example/", "hord.tar.gz"),
+            "https://a.example/hord.tar.gz"
+        );
+        assert_eq!(
+            webseed_file_url("https://a.example/hord.tar.gz", "hord.tar.gz"),
+            "https://a.example/hord.tar.gz"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+/// Errors returned by ordhook's core APIs, by kind. Converts into `String` so that callers still returning string
+/// errors can use `?`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum OrdhookError {
+    /// bitcoind's RPC interface could not be reached, or rejected a request.
+    #[error("{0}")]
+    Rpc(String),
+    /// One of the local databases could not be opened, read or written.
+    #[error("{0}")]
+    Db(String),
+    /// Data received from bitcoind, a user or a database could not be decoded.
+    #[error("{0}")]
+    Parse(String),
+    /// A re-org could not be handled.
+    #[error("{0}")]
+    Reorg(String),
+    /// The configuration is missing settings required by the operation.
+    #[error("{0}")]
+    Config(String),
+}
+
+impl From<OrdhookError> for String {
+    fn from(error: OrdhookError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OrdhookError;
+
+    fn open_db() -> Result<(), OrdhookError> {
+        Err(OrdhookError::Db("unable to read hord.sqlite".into()))
+    }
+
+    fn run() -> Result<(), String> {
+        open_db()?;
+        Ok(())
+    }
+
+    #[test]
+    fn converts_into_string_errors() {
+        assert_eq!(run(), Err("unable to read hord.sqlite".to_string()));
+        assert!(matches!(open_db(), Err(OrdhookError::Db(_))));
+    }
+}
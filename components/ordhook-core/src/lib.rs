@@ -1,3 +1,4 @@
+#[cfg(feature = "http-api")]
 #[macro_use]
 extern crate rocket;
 
@@ -19,6 +20,7 @@ pub mod config;
 pub mod core;
 pub mod db;
 pub mod download;
+pub mod error;
 pub mod ord;
 pub mod scan;
 pub mod service;
@@ -211,8 +211,8 @@ impl RawEnvelope {
                     return Ok((
                         false,
                         Some(Envelope {
-                            input: input.try_into().unwrap(),
-                            offset: offset.try_into().unwrap(),
+                            input: input.try_into().unwrap_or(u32::MAX),
+                            offset: offset.try_into().unwrap_or(u32::MAX),
                             payload,
                             pushnum,
                             stutter,
@@ -13,6 +13,7 @@ pub mod height;
 pub mod inscription;
 pub mod inscription_id;
 pub mod media;
+pub mod rarity;
 pub mod sat;
 pub mod sat_point;
 
@@ -0,0 +1,69 @@
+use super::{sat::Sat, *};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+    Mythic,
+}
+
+impl Rarity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Common => "common",
+            Self::Uncommon => "uncommon",
+            Self::Rare => "rare",
+            Self::Epic => "epic",
+            Self::Legendary => "legendary",
+            Self::Mythic => "mythic",
+        }
+    }
+}
+
+impl From<Sat> for Rarity {
+    fn from(sat: Sat) -> Self {
+        if sat.is_common() {
+            return Self::Common;
+        }
+        let height = sat.height().n();
+        let hour = height / (CYCLE_EPOCHS * SUBSIDY_HALVING_INTERVAL);
+        let minute = height % SUBSIDY_HALVING_INTERVAL;
+        let second = height % DIFFCHANGE_INTERVAL;
+        if hour == 0 && minute == 0 && second == 0 {
+            Self::Mythic
+        } else if minute == 0 && second == 0 {
+            Self::Legendary
+        } else if minute == 0 {
+            Self::Epic
+        } else if second == 0 {
+            Self::Rare
+        } else {
+            Self::Uncommon
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{epoch::Epoch, height::Height, *};
+
+    #[test]
+    fn rarity() {
+        assert_eq!(Rarity::from(Sat(0)), Rarity::Mythic);
+        assert_eq!(Rarity::from(Sat(1)), Rarity::Common);
+        assert_eq!(Rarity::from(Sat(50 * COIN_VALUE)), Rarity::Uncommon);
+        assert_eq!(Rarity::from(Sat(50 * COIN_VALUE - 1)), Rarity::Common);
+        assert_eq!(
+            Rarity::from(Height(DIFFCHANGE_INTERVAL).starting_sat()),
+            Rarity::Rare
+        );
+        assert_eq!(Rarity::from(Epoch(1).starting_sat()), Rarity::Epic);
+        assert_eq!(
+            Rarity::from(Height(CYCLE_EPOCHS * SUBSIDY_HALVING_INTERVAL).starting_sat()),
+            Rarity::Legendary
+        );
+    }
+}
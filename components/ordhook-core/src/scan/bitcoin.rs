@@ -1,16 +1,32 @@
 use crate::config::Config;
+use crate::core::first_inscription_height;
+use crate::core::meta_protocols::brc20::predicate::apply_brc20_predicate_filter;
 use crate::core::protocol::inscription_parsing::{
     get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
-    parse_inscriptions_and_standardize_block,
+    parse_inscriptions_and_standardize_block, strip_inscription_contents_in_block,
 };
 use crate::core::protocol::inscription_sequencing::consolidate_block_with_pre_computed_ordinals_data;
+use crate::core::protocol::satributes::load_satribute_ranges;
 use crate::db::initialize_sqlite_dbs;
 use crate::db::ordinals::get_any_entry_in_ordinal_activities;
 use crate::download::download_archive_datasets_if_required;
+use crate::scan::predicate_scripts::apply_predicate_script;
+use crate::service::confirmations::{get_confirmed_block_height, get_predicate_min_confirmations};
+use crate::service::dead_letters::{record_dead_letter, DeadLetter};
+use crate::service::enrichment::{enrich_predicate_occurrence, EnrichmentDbConnections};
+use crate::service::liveness::is_predicate_paused;
 use crate::service::observers::{
     open_readwrite_observers_db_conn_or_panic, update_observer_progress,
 };
-use crate::utils::bitcoind::bitcoind_get_block_height;
+use crate::service::usage::{has_events_quota, record_events_delivered, wait_for_events_quota};
+use crate::service::wallets::{apply_wallet_predicate_filter, get_wallet_scripts};
+use crate::service::webhooks::send_webhook_request;
+use crate::try_warn;
+use crate::utils::bitcoind::{
+    bitcoind_block_filter_matches_any, bitcoind_check_blocks_available, bitcoind_get_block_height,
+    build_bitcoind_http_client,
+};
+use crate::utils::event_transforms::{apply_event_transforms, load_event_transforms};
 use chainhook_sdk::chainhooks::bitcoin::{
     evaluate_bitcoin_chainhooks_on_chain_event, handle_bitcoin_hook_action,
     BitcoinChainhookOccurrence, BitcoinTriggerChainhook,
@@ -19,23 +35,78 @@ use chainhook_sdk::chainhooks::types::{
     BitcoinChainhookSpecification, BitcoinPredicateType, OrdinalOperations,
 };
 use chainhook_sdk::indexer::bitcoin::{
-    build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
+    download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
 };
 use chainhook_sdk::observer::{gather_proofs, DataHandlerEvent, EventObserverConfig};
 use chainhook_sdk::types::{
     BitcoinBlockData, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
+    BitcoinChainUpdatedWithReorgData,
 };
-use chainhook_sdk::utils::{file_append, send_request, BlockHeights, Context};
+use chainhook_sdk::utils::{file_append, BlockHeights, Context};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Lets the caller of a scan follow its progress and interrupt it between two blocks.
+#[derive(Debug, Clone, Default)]
+pub struct ScanControl {
+    pub cancelled: Arc<AtomicBool>,
+    pub blocks_scanned: Arc<AtomicU64>,
+    /// Set for the scans of predicates being registered, which move the progress recorded for the predicate and stop
+    /// quietly once it gets paused, like uncontrolled scans. Targeted rescans leave both alone.
+    pub record_progress: bool,
+}
+
+impl ScanControl {
+    pub fn new() -> ScanControl {
+        ScanControl::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn get_blocks_scanned(&self) -> u64 {
+        self.blocks_scanned.load(Ordering::SeqCst)
+    }
+}
 
 pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
     predicate_spec: &BitcoinChainhookSpecification,
     config: &Config,
     event_observer_config_override: Option<&EventObserverConfig>,
     ctx: &Context,
+) -> Result<(), String> {
+    scan_bitcoin_chainstate_via_rpc_using_predicate_with_control(
+        predicate_spec,
+        config,
+        event_observer_config_override,
+        None,
+        ctx,
+    )
+    .await
+}
+
+/// Scans the chainstate like `scan_bitcoin_chainstate_via_rpc_using_predicate`. When a `control` is given, the scan can
+/// be cancelled, and it is treated as a targeted rescan unless `ScanControl::record_progress` is set: it doesn't move
+/// the progress recorded for the predicate.
+pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate_with_control(
+    predicate_spec: &BitcoinChainhookSpecification,
+    config: &Config,
+    event_observer_config_override: Option<&EventObserverConfig>,
+    control: Option<&ScanControl>,
+    ctx: &Context,
 ) -> Result<(), String> {
     download_archive_datasets_if_required(config, ctx).await;
+    load_event_transforms(config, ctx)?;
+    load_satribute_ranges(config, ctx)?;
     let mut floating_end_block = false;
+    // Blocks not confirmed enough for the predicate are left to its confirmed stream.
+    let min_confirmations = get_predicate_min_confirmations(&predicate_spec.uuid).unwrap_or(1);
 
     let block_heights_to_scan_res = if let Some(ref blocks) = predicate_spec.blocks {
         BlockHeights::Blocks(blocks.clone()).get_sorted_entries()
@@ -51,7 +122,13 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         };
         let (end_block, update_end_block) = match predicate_spec.end_block {
             Some(end_block) => (end_block, false),
-            None => (bitcoind_get_block_height(config, ctx), true),
+            None => (
+                get_confirmed_block_height(
+                    bitcoind_get_block_height(config, ctx),
+                    min_confirmations,
+                ),
+                true,
+            ),
         };
         floating_end_block = update_end_block;
         BlockHeights::BlockRange(start_block, end_block).get_sorted_entries()
@@ -59,6 +136,13 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
 
     let mut block_heights_to_scan =
         block_heights_to_scan_res.map_err(|_e| format!("Block start / end block spec invalid"))?;
+    // Blocks without inscriptions are never downloaded: only the ones from the first inscription need to be available.
+    if let Some(lowest_block_height) = block_heights_to_scan.front() {
+        bitcoind_check_blocks_available(
+            config,
+            (*lowest_block_height).max(first_inscription_height(config)),
+        )?;
+    }
 
     info!(
         ctx.expect_logger(),
@@ -73,9 +157,48 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
     };
     let bitcoin_config = event_observer_config.get_bitcoin_config();
     let mut number_of_blocks_scanned = 0;
-    let http_client = build_http_client();
+    let mut number_of_blocks_skipped = 0;
+    let http_client = build_bitcoind_http_client(config)?;
+    // Blocks of a wallet predicate that can't involve the wallet are skipped without being downloaded.
+    let mut prescreen_with_block_filters = config.network.bitcoind_block_filters;
+    let mut enrichment_db_conns = EnrichmentDbConnections::new();
 
+    let is_targeted_rescan = control.map_or(false, |c| !c.record_progress);
     while let Some(current_block_height) = block_heights_to_scan.pop_front() {
+        if control.map(|c| c.is_cancelled()).unwrap_or(false) {
+            return Err(format!("Scan cancelled at block #{current_block_height}"));
+        }
+        if is_predicate_paused(&predicate_spec.uuid, config, ctx) {
+            if is_targeted_rescan {
+                return Err(format!(
+                    "Scan stopped at block #{current_block_height}: predicate paused"
+                ));
+            }
+            // The scan starts over from the last block delivered once the predicate gets resumed.
+            info!(
+                ctx.expect_logger(),
+                "Scan of predicate {} stopped at block #{current_block_height}: predicate paused",
+                predicate_spec.uuid
+            );
+            return Ok(());
+        }
+        let owner = predicate_spec.owner_uuid.as_deref();
+        if !has_events_quota(owner) {
+            info!(
+                ctx.expect_logger(),
+                "Scan of predicate {} held back at block #{current_block_height}: daily events quota used up",
+                predicate_spec.uuid
+            );
+            let is_stopped = || {
+                control.map(|c| c.is_cancelled()).unwrap_or(false)
+                    || is_predicate_paused(&predicate_spec.uuid, config, ctx)
+            };
+            // Evaluated again once the wait ends, for a cancellation or a pause to be handled first.
+            if !wait_for_events_quota(owner, is_stopped).await {
+                block_heights_to_scan.push_front(current_block_height);
+                continue;
+            }
+        }
         // Open DB connections
         let db_connections = initialize_sqlite_dbs(&config, ctx);
         let mut inscriptions_db_conn = db_connections.ordinals;
@@ -88,6 +211,9 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         };
 
         number_of_blocks_scanned += 1;
+        if let Some(control) = control {
+            control.blocks_scanned.fetch_add(1, Ordering::SeqCst);
+        }
 
         if !get_any_entry_in_ordinal_activities(&current_block_height, &inscriptions_db_conn, &ctx)
         {
@@ -101,6 +227,25 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             ctx,
         )
         .await?;
+        if prescreen_with_block_filters {
+            // Addresses are read for every block, since more get derived as the wallet gets used.
+            if let Some(scripts) = get_wallet_scripts(&predicate_spec.uuid) {
+                match bitcoind_block_filter_matches_any(&block_hash, &scripts, config) {
+                    Ok(false) => {
+                        number_of_blocks_skipped += 1;
+                        continue;
+                    }
+                    Ok(true) => {}
+                    Err(e) => {
+                        try_warn!(
+                            ctx,
+                            "Unable to pre-screen block #{current_block_height} with its compact filter, downloading blocks instead: {e}"
+                        );
+                        prescreen_with_block_filters = false;
+                    }
+                }
+            }
+        }
         let block_breakdown =
             download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
                 .await?;
@@ -119,6 +264,8 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             }
         };
 
+        strip_inscription_contents_in_block(&mut block, &config.storage);
+
         {
             let inscriptions_db_tx = inscriptions_db_conn.transaction().unwrap();
             consolidate_block_with_pre_computed_ordinals_data(
@@ -149,6 +296,8 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             block,
             &vec![&predicate_spec],
             &event_observer_config,
+            &mut enrichment_db_conns,
+            config,
             ctx,
         )
         .await
@@ -156,7 +305,7 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             Ok(actions) => actions_triggered += actions,
             Err(e) => return Err(format!("Scan aborted: {e}")),
         }
-        {
+        if !is_targeted_rescan {
             let observers_db_conn = open_readwrite_observers_db_conn_or_panic(&config, &ctx);
             update_observer_progress(
                 &predicate_spec.uuid,
@@ -166,7 +315,10 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             )
         }
         if block_heights_to_scan.is_empty() && floating_end_block {
-            let bitcoind_chain_tip = bitcoind_get_block_height(config, ctx);
+            let bitcoind_chain_tip = get_confirmed_block_height(
+                bitcoind_get_block_height(config, ctx),
+                min_confirmations,
+            );
             let new_tip = match predicate_spec.end_block {
                 Some(end_block) => {
                     if end_block > bitcoind_chain_tip {
@@ -185,18 +337,21 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
     }
     info!(
         ctx.expect_logger(),
-        "{number_of_blocks_scanned} blocks scanned, {actions_triggered} actions triggered"
+        "{number_of_blocks_scanned} blocks scanned ({number_of_blocks_skipped} skipped by compact block filters), {actions_triggered} actions triggered"
     );
 
     Ok(())
 }
 
 pub async fn process_block_with_predicates(
-    block: BitcoinBlockData,
+    mut block: BitcoinBlockData,
     predicates: &Vec<&BitcoinChainhookSpecification>,
     event_observer_config: &EventObserverConfig,
+    enrichment_db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
     ctx: &Context,
 ) -> Result<u32, String> {
+    apply_event_transforms(&mut block, ctx);
     let chain_event =
         BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
             new_blocks: vec![block],
@@ -206,19 +361,74 @@ pub async fn process_block_with_predicates(
     let (predicates_triggered, _predicates_evaluated, _) =
         evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, predicates, ctx);
 
-    execute_predicates_action(predicates_triggered, &event_observer_config, &ctx).await
+    execute_predicates_action(
+        predicates_triggered,
+        &event_observer_config,
+        enrichment_db_conns,
+        config,
+        &ctx,
+    )
+    .await
+}
+
+/// Sends the predicates the rollback of blocks orphaned by a re-org, given from the highest one down.
+pub async fn process_rollback_with_predicates(
+    mut blocks: Vec<BitcoinBlockData>,
+    predicates: &Vec<&BitcoinChainhookSpecification>,
+    event_observer_config: &EventObserverConfig,
+    enrichment_db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) -> Result<u32, String> {
+    for block in blocks.iter_mut() {
+        apply_event_transforms(block, ctx);
+    }
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: blocks,
+        blocks_to_apply: vec![],
+        confirmed_blocks: vec![],
+    });
+
+    let (predicates_triggered, _predicates_evaluated, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, predicates, ctx);
+
+    execute_predicates_action(
+        predicates_triggered,
+        &event_observer_config,
+        enrichment_db_conns,
+        config,
+        &ctx,
+    )
+    .await
 }
 
 pub async fn execute_predicates_action<'a>(
     hits: Vec<BitcoinTriggerChainhook<'a>>,
-    config: &EventObserverConfig,
+    event_observer_config: &EventObserverConfig,
+    enrichment_db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
     ctx: &Context,
 ) -> Result<u32, String> {
     let mut actions_triggered = 0;
     let mut proofs = HashMap::new();
-    for trigger in hits.into_iter() {
+    for mut trigger in hits.into_iter() {
+        if !apply_brc20_predicate_filter(&mut trigger, enrichment_db_conns, config, ctx)
+            || !apply_wallet_predicate_filter(&mut trigger, enrichment_db_conns, config, ctx)
+            || !apply_predicate_script(&mut trigger, ctx)
+        {
+            continue;
+        }
+        let owner = trigger.chainhook.owner_uuid.clone();
+        let uuid = trigger.chainhook.uuid.clone();
+        // Deliveries past the quota are held back, in order, until the next day.
+        wait_for_events_quota(owner.as_deref(), || false).await;
+        let events_delivered: usize = trigger
+            .apply
+            .iter()
+            .map(|(transactions, _)| transactions.len())
+            .sum();
         if trigger.chainhook.include_proof {
-            gather_proofs(&trigger, &mut proofs, &config, &ctx);
+            gather_proofs(&trigger, &mut proofs, &event_observer_config, &ctx);
         }
         match handle_bitcoin_hook_action(trigger, &proofs) {
             Err(e) => {
@@ -226,13 +436,32 @@ pub async fn execute_predicates_action<'a>(
             }
             Ok(action) => {
                 actions_triggered += 1;
-                let result = match action {
+                let result = match enrich_predicate_occurrence(
+                    &uuid,
+                    action,
+                    enrichment_db_conns,
+                    config,
+                    ctx,
+                ) {
                     BitcoinChainhookOccurrence::Http(request, _data) => {
-                        send_request(request, 60, 3, &ctx).await
+                        // Only a handle on the body is kept, the payload gets captured if the delivery fails.
+                        let retained_request = request.try_clone();
+                        match send_webhook_request(request, &config.resources, &ctx).await {
+                            Err(error) => match retained_request
+                                .and_then(|request| DeadLetter::from_request(&uuid, &request))
+                            {
+                                Some(dead_letter) => {
+                                    record_dead_letter(dead_letter, &error, config, ctx);
+                                    continue;
+                                }
+                                None => Err(error),
+                            },
+                            result => result,
+                        }
                     }
                     BitcoinChainhookOccurrence::File(path, bytes) => file_append(path, bytes, &ctx),
                     BitcoinChainhookOccurrence::Data(payload) => {
-                        if let Some(ref tx) = config.data_handler_tx {
+                        if let Some(ref tx) = event_observer_config.data_handler_tx {
                             match tx.send(DataHandlerEvent::Process(payload)) {
                                 Ok(_) => Ok(()),
                                 Err(error) => Err(error.to_string()),
@@ -243,7 +472,7 @@ pub async fn execute_predicates_action<'a>(
                     }
                 };
                 match result {
-                    Ok(_) => {}
+                    Ok(_) => record_events_delivered(owner.as_deref(), events_delivered as u64),
                     Err(error) => return Err(error),
                 }
             }
@@ -1 +1,2 @@
 pub mod bitcoin;
+pub mod predicate_scripts;
@@ -0,0 +1,355 @@
+#[cfg(feature = "predicate-scripts")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "predicate-scripts")]
+use chainhook_sdk::types::{BitcoinBlockData, BitcoinTransactionData, OrdinalOperation};
+use chainhook_sdk::{
+    chainhooks::bitcoin::BitcoinTriggerChainhook, types::BitcoinNetwork, utils::Context,
+};
+#[cfg(feature = "predicate-scripts")]
+use rhai::{Dynamic, Engine, Scope, AST};
+#[cfg(feature = "predicate-scripts")]
+use serde_json::json;
+use serde_json::Value as JsonValue;
+
+#[cfg(feature = "predicate-scripts")]
+use crate::{
+    core::protocol::{inscription_parsing::get_stripped_content_hash, satributes::get_satributes},
+    try_warn,
+};
+
+/// Maximum number of operations a predicate script can perform on a single transaction.
+#[cfg(feature = "predicate-scripts")]
+const PREDICATE_SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+/// Maximum duration of a predicate script evaluation on a single transaction.
+#[cfg(feature = "predicate-scripts")]
+const PREDICATE_SCRIPT_TIMEOUT: Duration = Duration::from_millis(50);
+#[cfg(feature = "predicate-scripts")]
+const PREDICATE_SCRIPT_MAX_SIZE: usize = 16 * 1024;
+
+#[cfg(feature = "predicate-scripts")]
+lazy_static! {
+    /// Compiled scripts of the registered predicates, by predicate uuid.
+    static ref PREDICATE_SCRIPTS: RwLock<HashMap<String, Arc<AST>>> = RwLock::new(HashMap::new());
+}
+
+/// Builds a sandboxed Rhai engine: no module imports, bounded operations, data sizes and evaluation time. The
+/// deadline is shared with the caller, who resets it before each evaluation.
+#[cfg(feature = "predicate-scripts")]
+fn new_script_engine(deadline: Arc<Mutex<Instant>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(PREDICATE_SCRIPT_MAX_OPERATIONS);
+    engine.set_max_modules(0);
+    engine.set_max_expr_depths(32, 32);
+    engine.set_max_call_levels(16);
+    engine.set_max_string_size(1024 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.on_progress(move |_| match deadline.lock() {
+        Ok(deadline) if Instant::now() < *deadline => None,
+        _ => Some(Dynamic::UNIT),
+    });
+    engine.register_fn("parse_json", |text: &str| -> Dynamic {
+        serde_json::from_str::<JsonValue>(text)
+            .ok()
+            .and_then(|value| rhai::serde::to_dynamic(value).ok())
+            .unwrap_or(Dynamic::UNIT)
+    });
+    engine.register_fn("satributes", |ordinal_number: i64| -> rhai::Array {
+        get_satributes(ordinal_number.max(0) as u64)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    });
+    engine
+}
+
+/// Predicates can include a Rhai `script` next to `if_this` in their network specifications. The script is evaluated
+/// against every transaction matched by `if_this`, and the transaction is only delivered if it returns `true`.
+/// `satributes(ordinal_number)` lists the satributes of a sat, e.g. `inscriptions.some(|i| "rare" in i.satributes)`.
+/// The scripts of every network get validated and removed, and the one of the network ordhook runs on is returned.
+pub fn extract_predicate_script(
+    predicate: &mut JsonValue,
+    bitcoin_network: &BitcoinNetwork,
+) -> Result<Option<String>, String> {
+    let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    else {
+        return Ok(None);
+    };
+    let network_key = serde_json::to_value(bitcoin_network)
+        .map_err(|e| format!("unable to serialize network: {e}"))?;
+    let mut script = None;
+    for (key, network) in networks.iter_mut() {
+        let Some(network) = network.as_object_mut() else {
+            continue;
+        };
+        let Some(network_script) = network.remove("script") else {
+            continue;
+        };
+        let network_script = network_script
+            .as_str()
+            .ok_or("predicate script must be a string".to_string())?
+            .to_string();
+        validate_predicate_script(&network_script)?;
+        if network_key.as_str() == Some(key.as_str()) {
+            script = Some(network_script);
+        }
+    }
+    Ok(script)
+}
+
+fn validate_predicate_script(script: &str) -> Result<(), String> {
+    #[cfg(feature = "predicate-scripts")]
+    {
+        compile_predicate_script(script).map(|_| ())
+    }
+    #[cfg(not(feature = "predicate-scripts"))]
+    {
+        let _ = script;
+        Err(
+            "predicate scripts require ordhook to be built with the predicate-scripts feature"
+                .into(),
+        )
+    }
+}
+
+#[cfg(feature = "predicate-scripts")]
+pub fn compile_predicate_script(script: &str) -> Result<AST, String> {
+    if script.len() > PREDICATE_SCRIPT_MAX_SIZE {
+        return Err(format!(
+            "predicate script exceeds {PREDICATE_SCRIPT_MAX_SIZE} bytes"
+        ));
+    }
+    new_script_engine(Arc::new(Mutex::new(Instant::now())))
+        .compile(script)
+        .map_err(|e| format!("invalid predicate script: {e}"))
+}
+
+pub fn set_predicate_script(uuid: &str, script: Option<&str>) -> Result<(), String> {
+    #[cfg(feature = "predicate-scripts")]
+    {
+        let ast = match script {
+            Some(script) => Some(Arc::new(compile_predicate_script(script)?)),
+            None => None,
+        };
+        let mut scripts = PREDICATE_SCRIPTS
+            .write()
+            .map_err(|e| format!("unable to update predicate scripts: {e}"))?;
+        match ast {
+            Some(ast) => scripts.insert(uuid.to_string(), ast),
+            None => scripts.remove(uuid),
+        };
+        Ok(())
+    }
+    #[cfg(not(feature = "predicate-scripts"))]
+    {
+        let _ = uuid;
+        match script {
+            Some(_) => Err(
+                "predicate scripts require ordhook to be built with the predicate-scripts feature"
+                    .into(),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "predicate-scripts")]
+fn get_predicate_script(uuid: &str) -> Option<Arc<AST>> {
+    PREDICATE_SCRIPTS
+        .read()
+        .ok()
+        .and_then(|scripts| scripts.get(uuid).cloned())
+}
+
+pub fn has_predicate_script(uuid: &str) -> bool {
+    #[cfg(feature = "predicate-scripts")]
+    {
+        get_predicate_script(uuid).is_some()
+    }
+    #[cfg(not(feature = "predicate-scripts"))]
+    {
+        let _ = uuid;
+        false
+    }
+}
+
+/// Variables exposed to scripts: `block` (the block identifier), `tx` (the transaction, as delivered) and
+/// `inscriptions` (the inscriptions revealed by the transaction, with their `content` decoded as text when possible
+/// and the `satributes` of their sat). Inscriptions which content was not stored get a `null` `content` and the hex
+/// sha256 digest of their content as `content_sha256`.
+#[cfg(feature = "predicate-scripts")]
+fn build_script_scope(tx: &BitcoinTransactionData, block: &BitcoinBlockData) -> Scope<'static> {
+    let mut inscriptions = vec![];
+    for operation in tx.metadata.ordinal_operations.iter() {
+        let OrdinalOperation::InscriptionRevealed(reveal) = operation else {
+            continue;
+        };
+        let content = reveal
+            .content_bytes
+            .strip_prefix("0x")
+            .and_then(|hex_content| hex::decode(hex_content).ok())
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        inscriptions.push(json!({
+            "inscription_id": reveal.inscription_id,
+            "inscription_number": reveal.inscription_number.jubilee,
+            "content_type": reveal.content_type,
+            "content": content,
+            "content_sha256": get_stripped_content_hash(&reveal.content_bytes),
+            "inscriber_address": reveal.inscriber_address,
+            "ordinal_number": reveal.ordinal_number,
+            "satributes": get_satributes(reveal.ordinal_number),
+        }));
+    }
+    let mut scope = Scope::new();
+    scope.push_constant(
+        "block",
+        rhai::serde::to_dynamic(&block.block_identifier).unwrap_or(Dynamic::UNIT),
+    );
+    scope.push_constant("tx", rhai::serde::to_dynamic(tx).unwrap_or(Dynamic::UNIT));
+    scope.push_constant(
+        "inscriptions",
+        rhai::serde::to_dynamic(inscriptions).unwrap_or(Dynamic::UNIT),
+    );
+    scope
+}
+
+/// Drops the transactions rejected by the script of the triggered predicate, if any. Returns false when nothing is
+/// left to deliver. Scripts failing or timing out reject the transaction.
+#[cfg(feature = "predicate-scripts")]
+pub fn apply_predicate_script(trigger: &mut BitcoinTriggerChainhook, ctx: &Context) -> bool {
+    let Some(ast) = get_predicate_script(&trigger.chainhook.uuid) else {
+        return true;
+    };
+    let deadline = Arc::new(Mutex::new(Instant::now()));
+    let engine = new_script_engine(deadline.clone());
+    let uuid = trigger.chainhook.uuid.clone();
+    let evaluate = |tx: &BitcoinTransactionData, block: &BitcoinBlockData| -> bool {
+        if let Ok(mut deadline) = deadline.lock() {
+            *deadline = Instant::now() + PREDICATE_SCRIPT_TIMEOUT;
+        }
+        let mut scope = build_script_scope(tx, block);
+        match engine.eval_ast_with_scope::<bool>(&mut scope, &ast) {
+            Ok(matched) => matched,
+            Err(e) => {
+                try_warn!(
+                    ctx,
+                    "Script of predicate {uuid} failed on transaction {}: {e}",
+                    tx.transaction_identifier.hash
+                );
+                false
+            }
+        }
+    };
+    for (transactions, block) in trigger.apply.iter_mut() {
+        let block = *block;
+        transactions.retain(|tx| evaluate(tx, block));
+    }
+    trigger
+        .apply
+        .retain(|(transactions, _)| !transactions.is_empty());
+    for (transactions, block) in trigger.rollback.iter_mut() {
+        let block = *block;
+        transactions.retain(|tx| evaluate(tx, block));
+    }
+    trigger
+        .rollback
+        .retain(|(transactions, _)| !transactions.is_empty());
+    !trigger.apply.is_empty() || !trigger.rollback.is_empty()
+}
+
+/// Without the predicate-scripts feature no script can be registered, every transaction is delivered.
+#[cfg(not(feature = "predicate-scripts"))]
+pub fn apply_predicate_script(trigger: &mut BitcoinTriggerChainhook, ctx: &Context) -> bool {
+    let _ = ctx;
+    !trigger.apply.is_empty() || !trigger.rollback.is_empty()
+}
+
+#[cfg(all(test, feature = "predicate-scripts"))]
+mod test {
+    use chainhook_sdk::types::{BitcoinNetwork, OrdinalOperation};
+    use serde_json::json;
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::{get_test_ctx, Brc20RevealBuilder},
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        build_script_scope, compile_predicate_script, extract_predicate_script, new_script_engine,
+    };
+
+    #[test]
+    fn extracts_and_validates_scripts() {
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol" }, "script": "true" } }
+        });
+        assert_eq!(
+            extract_predicate_script(&mut predicate, &BitcoinNetwork::Mainnet),
+            Ok(Some("true".to_string()))
+        );
+        assert_eq!(predicate["networks"]["mainnet"].get("script"), None);
+
+        let mut predicate = json!({
+            "networks": {
+                "mainnet": { "script": "true" },
+                "testnet": { "script": "false" }
+            }
+        });
+        assert_eq!(
+            extract_predicate_script(&mut predicate, &BitcoinNetwork::Mainnet),
+            Ok(Some("true".to_string()))
+        );
+        assert_eq!(predicate["networks"]["testnet"].get("script"), None);
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "script": "let = ;" } }
+        });
+        assert!(extract_predicate_script(&mut predicate, &BitcoinNetwork::Testnet).is_err());
+    }
+
+    #[test]
+    fn evaluates_scripts_against_inscription_contents() {
+        let _ = get_test_ctx();
+        let mut reveal = Brc20RevealBuilder::new().build();
+        reveal.content_bytes = format!(
+            "0x{}",
+            hex::encode(r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#)
+        );
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(reveal))
+                    .build(),
+            )
+            .build();
+        let engine = new_script_engine(std::sync::Arc::new(std::sync::Mutex::new(
+            std::time::Instant::now() + std::time::Duration::from_secs(10),
+        )));
+        let evaluate = |script: &str| {
+            let ast = compile_predicate_script(script).unwrap();
+            let mut scope = build_script_scope(&block.transactions[0], &block);
+            engine.eval_ast_with_scope::<bool>(&mut scope, &ast)
+        };
+
+        let matching = r#"
+            inscriptions.some(|i| {
+                let j = parse_json(i.content);
+                type_of(j) == "map" && j.p == "brc-20" && ["ordi", "sats"].contains(j.tick)
+            })
+        "#;
+        assert!(evaluate(matching).unwrap());
+        assert!(
+            !evaluate(r#"inscriptions.some(|i| parse_json(i.content).tick == "pepe")"#).unwrap()
+        );
+        assert!(evaluate("loop {}").is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    os::unix::process::CommandExt,
+    process::Command,
+    sync::Mutex,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::utils::Context;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::{AlertsConfig, Config, ResourcesConfig},
+    try_error, try_info, try_warn,
+    utils::{bitcoind::bitcoind_try_get_block_height, monitoring::PrometheusMonitoring},
+};
+
+/// Interval between two chain tip comparisons performed by the alerts monitor.
+const ALERTS_MONITOR_INTERVAL_SECS: u64 = 60;
+/// Alerts waiting for the alerts worker. Alerts raised while it is full are only logged.
+const ALERTS_QUEUE_SIZE: usize = 64;
+
+/// Alert payload waiting to be delivered, with the hooks it goes to.
+struct AlertJob {
+    payload: JsonValue,
+    config: AlertsConfig,
+    resources: ResourcesConfig,
+}
+
+lazy_static! {
+    static ref ALERTS_JOB_TX: Mutex<Option<crossbeam_channel::Sender<AlertJob>>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    TipLag {
+        bitcoind_height: u64,
+        indexed_height: u64,
+    },
+    RpcFailures {
+        consecutive_failures: u32,
+        last_error: String,
+    },
+    DeepReorg {
+        depth: u64,
+        tip_height: u64,
+    },
+    BlockQuarantined {
+        block_height: u64,
+        attempts: u32,
+        error: String,
+    },
+}
+
+impl Alert {
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Alert::TipLag {
+                bitcoind_height,
+                indexed_height,
+            } => json!({
+                "type": "tip_lag",
+                "bitcoind_height": bitcoind_height,
+                "indexed_height": indexed_height,
+                "lag": bitcoind_height.saturating_sub(*indexed_height),
+            }),
+            Alert::RpcFailures {
+                consecutive_failures,
+                last_error,
+            } => json!({
+                "type": "rpc_failures",
+                "consecutive_failures": consecutive_failures,
+                "last_error": last_error,
+            }),
+            Alert::DeepReorg { depth, tip_height } => json!({
+                "type": "deep_reorg",
+                "depth": depth,
+                "tip_height": tip_height,
+            }),
+            Alert::BlockQuarantined {
+                block_height,
+                attempts,
+                error,
+            } => json!({
+                "type": "block_quarantined",
+                "block_height": block_height,
+                "attempts": attempts,
+                "error": error,
+            }),
+        }
+    }
+}
+
+/// Tracks the conditions monitored by the alerts hook. Tip lag and RPC failure alerts only fire once when their
+/// threshold is crossed, and are re-armed once the condition clears, so that a stuck indexer does not flood receivers.
+pub struct AlertsState {
+    config: AlertsConfig,
+    consecutive_rpc_failures: u32,
+    tip_lag_alerted: bool,
+    rpc_failures_alerted: bool,
+}
+
+impl AlertsState {
+    pub fn new(config: AlertsConfig) -> Self {
+        AlertsState {
+            config,
+            consecutive_rpc_failures: 0,
+            tip_lag_alerted: false,
+            rpc_failures_alerted: false,
+        }
+    }
+
+    pub fn observe_heights(&mut self, bitcoind_height: u64, indexed_height: u64) -> Option<Alert> {
+        self.consecutive_rpc_failures = 0;
+        self.rpc_failures_alerted = false;
+        if bitcoind_height.saturating_sub(indexed_height) <= self.config.max_tip_lag {
+            self.tip_lag_alerted = false;
+            return None;
+        }
+        if self.tip_lag_alerted {
+            return None;
+        }
+        self.tip_lag_alerted = true;
+        Some(Alert::TipLag {
+            bitcoind_height,
+            indexed_height,
+        })
+    }
+
+    pub fn observe_rpc_failure(&mut self, error: &str) -> Option<Alert> {
+        self.consecutive_rpc_failures += 1;
+        if self.consecutive_rpc_failures < self.config.max_consecutive_rpc_failures
+            || self.rpc_failures_alerted
+        {
+            return None;
+        }
+        self.rpc_failures_alerted = true;
+        Some(Alert::RpcFailures {
+            consecutive_failures: self.consecutive_rpc_failures,
+            last_error: error.to_string(),
+        })
+    }
+}
+
+/// Returns a `DeepReorg` alert if the number of blocks rolled back exceeds the configured threshold.
+pub fn check_reorg_depth(
+    config: &Config,
+    blocks_rolled_back: usize,
+    tip_height: u64,
+) -> Option<Alert> {
+    let alerts = config.alerts.as_ref()?;
+    let depth = blocks_rolled_back as u64;
+    if depth <= alerts.max_reorg_depth {
+        return None;
+    }
+    Some(Alert::DeepReorg { depth, tip_height })
+}
+
+/// Returns the sender of the alerts worker, starting the worker on first use.
+fn get_alerts_job_tx(ctx: &Context) -> Result<crossbeam_channel::Sender<AlertJob>, String> {
+    let mut job_tx = ALERTS_JOB_TX
+        .lock()
+        .map_err(|_| "alerts worker lock poisoned".to_string())?;
+    if let Some(ref job_tx) = *job_tx {
+        return Ok(job_tx.clone());
+    }
+    let (tx, rx) = crossbeam_channel::bounded::<AlertJob>(ALERTS_QUEUE_SIZE);
+    let moved_ctx = ctx.clone();
+    hiro_system_kit::thread_named("Alerts worker")
+        .spawn(move || {
+            while let Ok(job) = rx.recv() {
+                deliver_json_payload(
+                    &job.payload,
+                    &job.config.webhook_url,
+                    &job.config.command,
+                    "ORDHOOK_ALERT",
+                    &job.resources,
+                    &moved_ctx,
+                );
+            }
+        })
+        .map_err(|e| format!("unable to start alerts worker: {e}"))?;
+    *job_tx = Some(tx.clone());
+    Ok(tx)
+}
+
+/// Hands an alert over to the alerts worker, delivering it to the configured webhook and / or command with a timeout
+/// of `resources.webhook_timeout_secs`. Failures are logged and never propagated: alerting must not interfere with
+/// indexing, alerts are dropped when the worker falls behind.
+pub fn send_alert(
+    alert: &Alert,
+    config: &AlertsConfig,
+    resources: &ResourcesConfig,
+    ctx: &Context,
+) {
+    let payload = alert.to_json();
+    try_warn!(ctx, "Alert triggered: {}", payload);
+    if config.webhook_url.is_none() && config.command.is_none() {
+        return;
+    }
+    let job = AlertJob {
+        payload,
+        config: config.clone(),
+        resources: resources.clone(),
+    };
+    if let Err(e) = get_alerts_job_tx(ctx).and_then(|job_tx| {
+        job_tx
+            .try_send(job)
+            .map_err(|_| "alerts worker is behind".to_string())
+    }) {
+        try_error!(ctx, "Unable to deliver alert: {e}");
+    }
+}
+
+async fn post_json_payload(
+    client: &reqwest::Client,
+    url: &str,
+    body: &JsonValue,
+) -> Result<(), String> {
+    let res = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("unable to reach {}: {}", url, e))?;
+    if !res.status().is_success() {
+        return Err(format!("{} responded with status {}", url, res.status()));
+    }
+    Ok(())
+}
+
+/// POSTs a JSON payload to a webhook and / or runs a command with the payload available in `env_var`. Webhook
+/// deliveries are retried `resources.network_max_retries` times. Failures are logged and never propagated.
+pub fn deliver_json_payload(
+    payload: &JsonValue,
+    webhook_url: &Option<String>,
+    command: &Option<String>,
+    env_var: &str,
+    resources: &ResourcesConfig,
+    ctx: &Context,
+) {
+    if let Some(ref webhook_url) = webhook_url {
+        if let Err(e) = post_json_payload_with_retries(payload, webhook_url, resources) {
+            try_error!(ctx, "Unable to deliver {env_var} payload: {}", e);
+        }
+    }
+    if let Some(ref command) = command {
+        if let Err(e) = run_json_payload_command(payload, command, env_var, resources) {
+            try_error!(ctx, "{e}");
+        }
+    }
+}
+
+/// POSTs a JSON payload to a webhook, retrying `resources.network_max_retries` times.
+pub fn post_json_payload_with_retries(
+    payload: &JsonValue,
+    webhook_url: &str,
+    resources: &ResourcesConfig,
+) -> Result<(), String> {
+    let url = webhook_url.to_string();
+    let body = payload.clone();
+    let resources = resources.clone();
+    // Payloads can be delivered from within the async runtime, perform the request on a dedicated thread.
+    std::thread::spawn(move || {
+        hiro_system_kit::nestable_block_on(async move {
+            let client = outbound_http_client_builder(&resources)?
+                .timeout(Duration::from_secs(resources.webhook_timeout_secs))
+                .build()
+                .map_err(|e| format!("unable to build http client: {}", e))?;
+            let mut attempt = 0;
+            loop {
+                match post_json_payload(&client, &url, &body).await {
+                    Err(_) if attempt < resources.network_max_retries => {
+                        attempt += 1;
+                        tokio::time::sleep(resources.network_retry_backoff(attempt)).await;
+                    }
+                    res => return res,
+                }
+            }
+        })
+    })
+    .join()
+    .unwrap_or(Err("webhook thread panicked".to_string()))
+}
+
+/// Interval between two checks of a running command.
+const COMMAND_POLL_INTERVAL_MS: u64 = 50;
+
+/// Runs a command with a JSON payload available in `env_var`. Commands running longer than
+/// `resources.webhook_timeout_secs` are killed, along with the processes they started.
+pub fn run_json_payload_command(
+    payload: &JsonValue,
+    command: &str,
+    env_var: &str,
+    resources: &ResourcesConfig,
+) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(env_var, payload.to_string())
+        // Own process group, for the processes started by `sh` to be killed with it.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Unable to run {env_var} command: {}", e))?;
+    let timeout = Duration::from_secs(resources.webhook_timeout_secs);
+    let started_at = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("{env_var} command exited with {}", status)),
+            Ok(None) if started_at.elapsed() < timeout => {
+                sleep(Duration::from_millis(COMMAND_POLL_INTERVAL_MS))
+            }
+            Ok(None) => {
+                unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                let _ = child.wait();
+                return Err(format!(
+                    "{env_var} command timed out after {}s",
+                    resources.webhook_timeout_secs
+                ));
+            }
+            Err(e) => return Err(format!("Unable to wait for {env_var} command: {}", e)),
+        }
+    }
+}
+
+/// Periodically compares bitcoind's chain tip with the last block indexed, and raises alerts when ordhook falls
+/// behind or bitcoind becomes unreachable.
+pub fn start_alerts_monitor(config: &Config, prometheus: &PrometheusMonitoring, ctx: &Context) {
+    let Some(alerts_config) = config.alerts.clone() else {
+        return;
+    };
+    let moved_config = config.clone();
+    let moved_prometheus = prometheus.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Alerts monitor")
+        .spawn(move || {
+            try_info!(moved_ctx, "Alerts monitor started");
+            let mut state = AlertsState::new(alerts_config.clone());
+            loop {
+                sleep(Duration::from_secs(ALERTS_MONITOR_INTERVAL_SECS));
+                let alert = match bitcoind_try_get_block_height(&moved_config) {
+                    Ok(bitcoind_height) => state.observe_heights(
+                        bitcoind_height,
+                        moved_prometheus.last_indexed_block_height.get(),
+                    ),
+                    Err(e) => {
+                        try_warn!(moved_ctx, "Alerts monitor: bitcoind {}", e);
+                        state.observe_rpc_failure(&e.to_string())
+                    }
+                };
+                if let Some(alert) = alert {
+                    send_alert(&alert, &alerts_config, &moved_config.resources, &moved_ctx);
+                }
+            }
+        })
+        .expect("unable to spawn thread");
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{AlertsConfig, Config};
+
+    use super::{check_reorg_depth, Alert, AlertsState};
+
+    fn alerts_config() -> AlertsConfig {
+        AlertsConfig {
+            webhook_url: None,
+            command: None,
+            max_tip_lag: 6,
+            max_consecutive_rpc_failures: 3,
+            max_reorg_depth: 3,
+        }
+    }
+
+    #[test]
+    fn tip_lag_alert_fires_once_until_cleared() {
+        let mut state = AlertsState::new(alerts_config());
+        assert_eq!(state.observe_heights(800_006, 800_000), None);
+        assert_eq!(
+            state.observe_heights(800_010, 800_000),
+            Some(Alert::TipLag {
+                bitcoind_height: 800_010,
+                indexed_height: 800_000
+            })
+        );
+        assert_eq!(state.observe_heights(800_011, 800_000), None);
+        assert_eq!(state.observe_heights(800_011, 800_011), None);
+        assert!(state.observe_heights(800_020, 800_011).is_some());
+    }
+
+    #[test]
+    fn rpc_failures_alert_fires_after_threshold() {
+        let mut state = AlertsState::new(alerts_config());
+        assert_eq!(state.observe_rpc_failure("timeout"), None);
+        assert_eq!(state.observe_rpc_failure("timeout"), None);
+        assert_eq!(
+            state.observe_rpc_failure("timeout"),
+            Some(Alert::RpcFailures {
+                consecutive_failures: 3,
+                last_error: "timeout".to_string()
+            })
+        );
+        assert_eq!(state.observe_rpc_failure("timeout"), None);
+        let _ = state.observe_heights(800_000, 800_000);
+        assert_eq!(state.observe_rpc_failure("timeout"), None);
+    }
+
+    #[test]
+    fn deep_reorg_alert_respects_threshold() {
+        let mut config = Config::test_default();
+        assert_eq!(check_reorg_depth(&config, 10, 800_000), None);
+        config.alerts = Some(alerts_config());
+        assert_eq!(check_reorg_depth(&config, 3, 800_000), None);
+        assert_eq!(
+            check_reorg_depth(&config, 4, 800_000),
+            Some(Alert::DeepReorg {
+                depth: 4,
+                tip_height: 800_000
+            })
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use chainhook_sdk::{types::BlockIdentifier, utils::Context};
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    db::renumbering::{find_inscription_numbers_in_block, InscriptionNumberCorrection},
+    service::{
+        event_queue::{deliver_event_with_retries, enqueue_events, QueuedEvent},
+        query_cache::clear_query_cache,
+    },
+    try_info,
+};
+
+/// Why data already delivered to consumers was altered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmendmentReason {
+    /// Blocks indexed again by `ordhook db repair`.
+    Repair,
+    /// Blocks indexed again by `ordhook db renumber`, after a fix of the numbering rules.
+    Renumbering,
+    /// Blocks rolled back by a re-org deeper than `min_reorg_depth`.
+    DeepReorg,
+}
+
+impl AmendmentReason {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AmendmentReason::Repair => "repair",
+            AmendmentReason::Renumbering => "renumbering",
+            AmendmentReason::DeepReorg => "deep_reorg",
+        }
+    }
+}
+
+/// Change of data already delivered. Values are `null` on the side the data does not exist on: inscriptions no
+/// longer revealed, or newly revealed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Amendment {
+    pub reason: AmendmentReason,
+    pub block_height: u64,
+    pub block_hash: Option<String>,
+    /// Missing for amendments of the block itself.
+    pub inscription_id: Option<String>,
+    pub old_value: JsonValue,
+    pub new_value: JsonValue,
+}
+
+impl Amendment {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "type": "amendment",
+            "reason": self.reason.code(),
+            "block_height": self.block_height,
+            "block_hash": self.block_hash,
+            "inscription_id": self.inscription_id,
+            "old_value": self.old_value,
+            "new_value": self.new_value,
+        })
+    }
+}
+
+fn inscription_numbers_value(jubilee: Option<i64>, classic: Option<i64>) -> JsonValue {
+    match (jubilee, classic) {
+        (Some(jubilee), Some(classic)) => json!({
+            "inscription_number": {
+                "jubilee": jubilee,
+                "classic": classic,
+            },
+            "cursed": classic < 0,
+        }),
+        _ => JsonValue::Null,
+    }
+}
+
+pub fn amendments_from_number_corrections(
+    reason: AmendmentReason,
+    corrections: &Vec<InscriptionNumberCorrection>,
+) -> Vec<Amendment> {
+    corrections
+        .iter()
+        .map(|correction| Amendment {
+            reason,
+            block_height: correction.block_height,
+            block_hash: None,
+            inscription_id: Some(correction.inscription_id.clone()),
+            old_value: inscription_numbers_value(
+                correction.old_jubilee_inscription_number,
+                correction.old_classic_inscription_number,
+            ),
+            new_value: inscription_numbers_value(
+                correction.new_jubilee_inscription_number,
+                correction.new_classic_inscription_number,
+            ),
+        })
+        .collect()
+}
+
+/// Amendments of a block about to be rolled back, and of the inscriptions it revealed. Must be called before the
+/// data of the block is dropped. Re-orgs shallower than `min_reorg_depth` get none.
+pub fn get_rolled_back_block_amendments(
+    block_identifier: &BlockIdentifier,
+    reorg_depth: u64,
+    inscriptions_db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Vec<Amendment> {
+    let Some(ref amendments_config) = config.amendments else {
+        return vec![];
+    };
+    if reorg_depth < amendments_config.min_reorg_depth {
+        return vec![];
+    }
+    let block_hash = Some(block_identifier.hash.clone());
+    let mut amendments = vec![Amendment {
+        reason: AmendmentReason::DeepReorg,
+        block_height: block_identifier.index,
+        block_hash: block_hash.clone(),
+        inscription_id: None,
+        old_value: json!({ "block_hash": block_identifier.hash }),
+        new_value: JsonValue::Null,
+    }];
+    for numbers in
+        find_inscription_numbers_in_block(block_identifier.index, inscriptions_db_conn, ctx)
+    {
+        amendments.push(Amendment {
+            reason: AmendmentReason::DeepReorg,
+            block_height: block_identifier.index,
+            block_hash: block_hash.clone(),
+            inscription_id: Some(numbers.inscription_id),
+            old_value: inscription_numbers_value(
+                Some(numbers.jubilee_inscription_number),
+                Some(numbers.classic_inscription_number),
+            ),
+            new_value: JsonValue::Null,
+        });
+    }
+    amendments
+}
+
+fn get_amendment_events(
+    amendments: &Vec<Amendment>,
+    config: &Config,
+    ctx: &Context,
+) -> Vec<QueuedEvent> {
+    let Some(ref amendments_config) = config.amendments else {
+        return vec![];
+    };
+    if amendments_config.webhook_url.is_none() && amendments_config.command.is_none() {
+        return vec![];
+    }
+    amendments
+        .iter()
+        .map(|amendment| {
+            let payload = amendment.to_json();
+            try_info!(ctx, "Amendment: {}", payload);
+            QueuedEvent {
+                payload,
+                webhook_url: amendments_config.webhook_url.clone(),
+                command: amendments_config.command.clone(),
+                env_var: "ORDHOOK_EVENT".into(),
+                resources: config.resources.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Delivers an `amendment` event for every amendment, in order, each one retried until delivered. Returns once all of
+/// them were delivered: meant for the CLI commands altering the index.
+pub fn deliver_amendment_events(amendments: &Vec<Amendment>, config: &Config, ctx: &Context) {
+    for event in get_amendment_events(amendments, config, ctx).iter() {
+        deliver_event_with_retries(event, ctx);
+    }
+}
+
+/// Queues an `amendment` event for every amendment, delivered in order by the event delivery worker along with the
+/// `rollback` events, so that slow receivers do not hold back indexing. Must only be called once the data amended was
+/// dropped.
+pub fn send_amendment_events(amendments: Vec<Amendment>, config: &Config, ctx: &Context) {
+    if !amendments.is_empty() {
+        clear_query_cache();
+    }
+    enqueue_events(get_amendment_events(&amendments, config, ctx), ctx);
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::{types::BlockIdentifier, utils::Context};
+    use serde_json::json;
+
+    use crate::{
+        config::{AmendmentsConfig, Config},
+        db::{ordinals::initialize_ordinals_db, renumbering::InscriptionNumberCorrection},
+    };
+
+    use super::{
+        amendments_from_number_corrections, get_rolled_back_block_amendments, AmendmentReason,
+    };
+
+    #[test]
+    fn builds_amendment_events() {
+        let corrections = vec![
+            InscriptionNumberCorrection {
+                inscription_id: "a".to_string(),
+                block_height: 800_000,
+                old_jubilee_inscription_number: Some(12),
+                old_classic_inscription_number: Some(-3),
+                new_jubilee_inscription_number: Some(12),
+                new_classic_inscription_number: Some(11),
+            },
+            InscriptionNumberCorrection {
+                inscription_id: "b".to_string(),
+                block_height: 800_001,
+                old_jubilee_inscription_number: None,
+                old_classic_inscription_number: None,
+                new_jubilee_inscription_number: Some(13),
+                new_classic_inscription_number: Some(12),
+            },
+        ];
+        let amendments =
+            amendments_from_number_corrections(AmendmentReason::Renumbering, &corrections);
+        assert_eq!(
+            amendments[0].to_json(),
+            json!({
+                "type": "amendment",
+                "reason": "renumbering",
+                "block_height": 800_000,
+                "block_hash": null,
+                "inscription_id": "a",
+                "old_value": { "inscription_number": { "jubilee": 12, "classic": -3 }, "cursed": true },
+                "new_value": { "inscription_number": { "jubilee": 12, "classic": 11 }, "cursed": false },
+            })
+        );
+        assert_eq!(amendments[1].old_value, json!(null));
+
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        let working_dir = std::env::temp_dir().join("ordhook_test_amendments");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&working_dir, &ctx);
+        let block_identifier = BlockIdentifier {
+            index: 800_000,
+            hash: "0x00".to_string(),
+        };
+        assert!(
+            get_rolled_back_block_amendments(&block_identifier, 10, &db_conn, &config, &ctx)
+                .is_empty()
+        );
+        config.amendments = Some(AmendmentsConfig {
+            webhook_url: None,
+            command: None,
+            min_reorg_depth: 6,
+        });
+        assert!(
+            get_rolled_back_block_amendments(&block_identifier, 5, &db_conn, &config, &ctx)
+                .is_empty()
+        );
+        let amendments =
+            get_rolled_back_block_amendments(&block_identifier, 6, &db_conn, &config, &ctx);
+        assert_eq!(amendments.len(), 1);
+        assert_eq!(amendments[0].reason, AmendmentReason::DeepReorg);
+        assert_eq!(amendments[0].new_value, json!(null));
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{str::FromStr, sync::Mutex, time::SystemTime};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{sha256, Hash},
+    utils::Context,
+};
+use rusqlite::Connection;
+
+use crate::{
+    config::Config,
+    core::protocol::inscription_content::fetch_inscription_content,
+    ord::inscription_id::InscriptionId,
+    service::{
+        observers::{find_all_blocklist_entries, open_readonly_observers_db_conn},
+        query_cache::clear_query_cache,
+    },
+    try_warn,
+};
+
+pub const BLOCKLIST_SOURCE_API: &str = "api";
+pub const BLOCKLIST_SOURCE_FILE: &str = "file";
+const CONTENT_HASH_PREFIX: &str = "sha256:";
+
+/// Inscription id or content hash (`sha256:<hex>`) which content is not served. Inscriptions stay indexed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlocklistEntry {
+    pub entry: String,
+    pub reason: Option<String>,
+    pub added_at: u64,
+    /// `file` for the entries of `http_api.blocklist_path`, `api` for the entries managed through the API.
+    pub source: String,
+}
+
+lazy_static! {
+    /// Entries of the blocklist file, with the modification time of the file they were read at.
+    static ref BLOCKLIST_FILE_CACHE: Mutex<Option<(SystemTime, Vec<BlocklistEntry>)>> =
+        Mutex::new(None);
+}
+
+/// Validates a blocklist entry, returning its canonical form.
+pub fn parse_blocklist_entry(entry: &str) -> Result<String, String> {
+    let entry = entry.trim();
+    if let Some(hash) = entry.strip_prefix(CONTENT_HASH_PREFIX) {
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid content hash {entry}"));
+        }
+        return Ok(format!("{CONTENT_HASH_PREFIX}{}", hash.to_lowercase()));
+    }
+    InscriptionId::from_str(entry)
+        .map(|inscription_id| inscription_id.to_string())
+        .map_err(|e| format!("invalid inscription id {entry}: {e}"))
+}
+
+/// Parses a blocklist file: one entry per line, `#` starting a comment.
+pub fn parse_blocklist_file(content: &str) -> Result<Vec<String>, String> {
+    let mut entries = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        entries.push(parse_blocklist_entry(line).map_err(|e| format!("line {}: {e}", i + 1))?);
+    }
+    Ok(entries)
+}
+
+/// Entries of the blocklist file, read again whenever the file gets modified. An invalid file is reported and the
+/// entries previously read are kept.
+fn get_blocklist_file_entries(config: &Config, ctx: &Context) -> Vec<BlocklistEntry> {
+    let Some(path) = config.blocklist_path() else {
+        return vec![];
+    };
+    let Ok(mut cache) = BLOCKLIST_FILE_CACHE.lock() else {
+        return vec![];
+    };
+    let modified_at = match std::fs::metadata(path).and_then(|m| m.modified()) {
+        Ok(modified_at) => modified_at,
+        Err(e) => {
+            try_warn!(ctx, "Blocklist: unable to read {path}: {e}");
+            return cache.as_ref().map(|(_, e)| e.clone()).unwrap_or_default();
+        }
+    };
+    if let Some((cached_at, ref entries)) = *cache {
+        if cached_at == modified_at {
+            return entries.clone();
+        }
+    }
+    let added_at = modified_at
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_blocklist_file(&content))
+    {
+        Ok(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| BlocklistEntry {
+                    entry,
+                    reason: None,
+                    added_at,
+                    source: BLOCKLIST_SOURCE_FILE.into(),
+                })
+                .collect::<Vec<_>>();
+            *cache = Some((modified_at, entries.clone()));
+            clear_query_cache();
+            entries
+        }
+        Err(e) => {
+            try_warn!(ctx, "Blocklist: unable to load {path}: {e}");
+            cache.as_ref().map(|(_, e)| e.clone()).unwrap_or_default()
+        }
+    }
+}
+
+/// Entries of the blocklist file followed by the entries managed through the API.
+pub fn get_blocklist_entries(config: &Config, ctx: &Context) -> Vec<BlocklistEntry> {
+    let mut entries = get_blocklist_file_entries(config, ctx);
+    if let Ok(observers_db_conn) = open_readonly_observers_db_conn(config, ctx) {
+        entries.extend(find_all_blocklist_entries(&observers_db_conn, ctx));
+    }
+    entries
+}
+
+/// Checks that the content of an inscription is not blocklisted, by inscription id or by content hash. When the
+/// content is not provided, it is only read from bitcoind if content hashes are blocklisted. Refusals are logged.
+pub fn check_blocklist(
+    inscription_id: &str,
+    content: Option<&[u8]>,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let entries = get_blocklist_entries(config, ctx);
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut matched = entries.iter().find(|e| e.entry == inscription_id);
+    let content_hash_listed = entries
+        .iter()
+        .any(|e| e.entry.starts_with(CONTENT_HASH_PREFIX));
+    if matched.is_none() && content_hash_listed {
+        let content_hash = match content {
+            Some(content) => format!("{CONTENT_HASH_PREFIX}{}", sha256::Hash::hash(content)),
+            None => {
+                // Fail closed: content that can't be checked is not served.
+                let content = fetch_inscription_content(inscription_id, db_conn, config, ctx)
+                    .map_err(|e| {
+                        format!("unable to check {inscription_id} against the blocklist: {e}")
+                    })?;
+                format!("{CONTENT_HASH_PREFIX}{}", sha256::Hash::hash(&content.body))
+            }
+        };
+        matched = entries.iter().find(|e| e.entry == content_hash);
+    }
+    match matched {
+        Some(entry) => {
+            try_warn!(
+                ctx,
+                "Blocklist: refused to serve content of {inscription_id} (entry {}, source {}, reason {})",
+                entry.entry,
+                entry.source,
+                entry.reason.as_deref().unwrap_or("none")
+            );
+            Err(format!("content of {inscription_id} is not available"))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_blocklist_entry, parse_blocklist_file};
+
+    #[test]
+    fn parses_blocklist_files() {
+        let content = "# DMCA 2024-01\n\
+            6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0\n\
+            \n\
+            SHA256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 # empty\n";
+        assert!(parse_blocklist_file(content).is_err());
+        let content = content.replace("SHA256", "sha256");
+        assert_eq!(
+            parse_blocklist_file(&content),
+            Ok(vec![
+                "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0".to_string(),
+                "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                    .to_string(),
+            ])
+        );
+        assert!(parse_blocklist_entry("sha256:e3b0").is_err());
+        assert!(parse_blocklist_entry("6fb976ab49dcec017f1e").is_err());
+    }
+}
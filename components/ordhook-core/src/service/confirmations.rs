@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use chainhook_sdk::{chainhooks::types::BitcoinChainhookSpecification, utils::Context};
+use serde_json::Value as JsonValue;
+
+use crate::{try_info, try_warn};
+
+/// Scope of the finalized inscription feed, evaluated like `ordinals_protocol` but on confirmed blocks only.
+const FINALIZED_FEED_SCOPE: &str = "ordinals_protocol_finalized";
+/// Confirmations required by the finalized feed, unless the predicate specifies `min_confirmations`.
+pub const FINALIZED_FEED_MIN_CONFIRMATIONS: u64 = 6;
+
+lazy_static! {
+    /// Confirmations required by the registered predicates before blocks get delivered, by predicate uuid.
+    static ref PREDICATE_MIN_CONFIRMATIONS: RwLock<HashMap<String, u64>> = RwLock::new(HashMap::new());
+    /// Predicates streaming confirmed blocks, with the height of the last block scheduled for delivery.
+    static ref CONFIRMED_STREAMS: Mutex<HashMap<String, (BitcoinChainhookSpecification, u64)>> =
+        Mutex::new(HashMap::new());
+    /// Scan runloop the confirmed blocks are handed to, since they are evaluated like replays.
+    static ref CONFIRMED_STREAMS_SCAN_OP_TX: Mutex<Option<crossbeam_channel::Sender<BitcoinChainhookSpecification>>> =
+        Mutex::new(None);
+}
+
+/// Predicates can include `min_confirmations` next to `if_this` in their network specifications. Blocks then only get
+/// delivered once buried under `min_confirmations - 1` blocks, and no rollback is ever sent for them.
+///
+/// The `ordinals_protocol_finalized` scope offers the same feed as `ordinals_protocol` (tip of the chain, with
+/// rollbacks) with `FINALIZED_FEED_MIN_CONFIRMATIONS` confirmations. It gets rewritten to `ordinals_protocol`, so both
+/// feeds share the same sequencing.
+pub fn extract_predicate_min_confirmations(
+    predicate: &mut JsonValue,
+) -> Result<Option<u64>, String> {
+    let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    else {
+        return Ok(None);
+    };
+    let mut min_confirmations = None;
+    for (_, network) in networks.iter_mut() {
+        let Some(network) = network.as_object_mut() else {
+            continue;
+        };
+        if let Some(scope) = network
+            .get_mut("if_this")
+            .and_then(|if_this| if_this.get_mut("scope"))
+        {
+            if scope.as_str() == Some(FINALIZED_FEED_SCOPE) {
+                *scope = JsonValue::String("ordinals_protocol".to_string());
+                min_confirmations = Some(FINALIZED_FEED_MIN_CONFIRMATIONS);
+            }
+        }
+        let Some(value) = network.remove("min_confirmations") else {
+            continue;
+        };
+        match value.as_u64() {
+            Some(value) if value >= 1 => min_confirmations = Some(value),
+            _ => return Err("min_confirmations must be a positive integer".to_string()),
+        }
+    }
+    Ok(min_confirmations)
+}
+
+pub fn set_predicate_min_confirmations(uuid: &str, min_confirmations: Option<u64>) {
+    let Ok(mut predicates) = PREDICATE_MIN_CONFIRMATIONS.write() else {
+        return;
+    };
+    match min_confirmations {
+        Some(min_confirmations) => predicates.insert(uuid.to_string(), min_confirmations),
+        None => predicates.remove(uuid),
+    };
+}
+
+pub fn get_predicate_min_confirmations(uuid: &str) -> Option<u64> {
+    PREDICATE_MIN_CONFIRMATIONS
+        .read()
+        .ok()
+        .and_then(|predicates| predicates.get(uuid).cloned())
+}
+
+/// Highest block having at least `min_confirmations` confirmations, the chain tip counting as one.
+pub fn get_confirmed_block_height(chain_tip_height: u64, min_confirmations: u64) -> u64 {
+    (chain_tip_height + 1).saturating_sub(min_confirmations.max(1))
+}
+
+pub fn set_confirmed_streams_scan_op_tx(
+    bitcoin_scan_op_tx: crossbeam_channel::Sender<BitcoinChainhookSpecification>,
+) {
+    if let Ok(mut tx) = CONFIRMED_STREAMS_SCAN_OP_TX.lock() {
+        *tx = Some(bitcoin_scan_op_tx);
+    }
+}
+
+/// Used in place of enabling the predicate on the Chainhook observer, which would deliver blocks as soon as they are
+/// mined. Confirmed blocks after `last_block_height` get scheduled as the chain tip progresses.
+pub fn start_confirmed_stream(
+    predicate_spec: &BitcoinChainhookSpecification,
+    last_block_height: u64,
+) {
+    let Ok(mut streams) = CONFIRMED_STREAMS.lock() else {
+        return;
+    };
+    let entry = streams
+        .entry(predicate_spec.uuid.clone())
+        .or_insert_with(|| {
+            let mut spec = predicate_spec.clone();
+            spec.blocks = None;
+            spec.start_block = None;
+            (spec, last_block_height)
+        });
+    entry.1 = entry.1.max(last_block_height);
+}
+
+pub fn stop_confirmed_stream(uuid: &str) {
+    if let Ok(mut streams) = CONFIRMED_STREAMS.lock() {
+        streams.remove(uuid);
+    }
+}
+
+/// Schedules the scan of the blocks that reached the confirmation depth of each confirmed stream.
+pub fn on_chain_tip_updated(chain_tip_height: u64, ctx: &Context) {
+    let Ok(tx) = CONFIRMED_STREAMS_SCAN_OP_TX.lock() else {
+        return;
+    };
+    let Some(ref tx) = *tx else {
+        return;
+    };
+    let Ok(mut streams) = CONFIRMED_STREAMS.lock() else {
+        return;
+    };
+    let mut completed_streams = vec![];
+    for (uuid, (spec, last_block_height)) in streams.iter_mut() {
+        let Some(min_confirmations) = get_predicate_min_confirmations(uuid) else {
+            continue;
+        };
+        let mut confirmed_block_height =
+            get_confirmed_block_height(chain_tip_height, min_confirmations);
+        if let Some(end_block) = spec.end_block {
+            confirmed_block_height = confirmed_block_height.min(end_block);
+        }
+        if confirmed_block_height <= *last_block_height {
+            continue;
+        }
+        let mut scan_spec = spec.clone();
+        scan_spec.blocks = Some(((*last_block_height + 1)..=confirmed_block_height).collect());
+        try_info!(
+            ctx,
+            "Predicate {uuid}: blocks #{} to #{confirmed_block_height} reached {min_confirmations} confirmations",
+            *last_block_height + 1
+        );
+        if let Err(e) = tx.send(scan_spec) {
+            try_warn!(
+                ctx,
+                "Unable to schedule confirmed blocks of predicate {uuid}: {e}"
+            );
+            continue;
+        }
+        *last_block_height = confirmed_block_height;
+        if Some(confirmed_block_height) == spec.end_block {
+            completed_streams.push(uuid.clone());
+        }
+    }
+    for uuid in completed_streams.iter() {
+        streams.remove(uuid);
+    }
+}
+
+/// Confirmed streams are not rolled back: a re-org deeper than the confirmation depth of a predicate means it was
+/// delivered blocks that are no longer canonical. The stream resumes from the new branch.
+pub fn on_block_rolled_back(block_height: u64, ctx: &Context) {
+    let Ok(mut streams) = CONFIRMED_STREAMS.lock() else {
+        return;
+    };
+    for (uuid, (_, last_block_height)) in streams.iter_mut() {
+        if *last_block_height < block_height {
+            continue;
+        }
+        try_warn!(
+            ctx,
+            "Re-org deeper than the confirmation depth of predicate {uuid}: block #{block_height} was already delivered"
+        );
+        *last_block_height = block_height.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{
+        extract_predicate_min_confirmations, get_confirmed_block_height,
+        FINALIZED_FEED_MIN_CONFIRMATIONS,
+    };
+
+    #[test]
+    fn extracts_min_confirmations() {
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol" }, "min_confirmations": 6 } }
+        });
+        assert_eq!(
+            extract_predicate_min_confirmations(&mut predicate),
+            Ok(Some(6))
+        );
+        assert_eq!(
+            predicate["networks"]["mainnet"].get("min_confirmations"),
+            None
+        );
+
+        let mut predicate = json!({ "networks": { "mainnet": { "min_confirmations": 0 } } });
+        assert!(extract_predicate_min_confirmations(&mut predicate).is_err());
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol_finalized", "operation": "inscription_feed" } } }
+        });
+        assert_eq!(
+            extract_predicate_min_confirmations(&mut predicate),
+            Ok(Some(FINALIZED_FEED_MIN_CONFIRMATIONS))
+        );
+        assert_eq!(
+            predicate["networks"]["mainnet"]["if_this"]["scope"],
+            "ordinals_protocol"
+        );
+    }
+
+    #[test]
+    fn computes_confirmed_block_height() {
+        assert_eq!(get_confirmed_block_height(800000, 1), 800000);
+        assert_eq!(get_confirmed_block_height(800000, 6), 799995);
+        assert_eq!(get_confirmed_block_height(3, 6), 0);
+    }
+}
@@ -0,0 +1,225 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chainhook_sdk::chainhooks::types::{ChainhookSpecification, HookAction};
+use chainhook_sdk::utils::Context;
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    service::{
+        observers::{
+            find_dead_letters, find_observer_with_uuid, initialize_observers_db,
+            insert_dead_letter_in_observers, open_readwrite_observers_db_conn,
+            remove_dead_letter_from_observers, update_dead_letter_in_observers,
+        },
+        webhooks::{build_webhook_client, send_webhook_request},
+    },
+    try_info, try_warn,
+};
+
+/// Predicate occurrence still rejected by its webhook after the delivery retries. Dead letters are kept in the
+/// observers db, so that they can be redelivered once the consumer is fixed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// Assigned by the observers db.
+    pub id: u64,
+    pub predicate_uuid: String,
+    pub url: String,
+    /// Headers of the request, without its authorization header: redeliveries use the one of the registered
+    /// predicate instead of keeping a copy of the credentials in the observers db.
+    pub headers: Vec<(String, String)>,
+    pub payload: JsonValue,
+    pub error: String,
+    pub failed_at: u64,
+    pub redelivery_attempts: u32,
+}
+
+impl DeadLetter {
+    /// Captures a webhook request whose delivery failed. Requests whose body is not a JSON document can't be captured.
+    pub fn from_request(predicate_uuid: &str, request: &RequestBuilder) -> Option<DeadLetter> {
+        let (_, request) = request.try_clone()?.build_split();
+        let request = request.ok()?;
+        let payload = serde_json::from_slice(request.body()?.as_bytes()?).ok()?;
+        let headers = request
+            .headers()
+            .iter()
+            .filter(|(name, _)| *name != AUTHORIZATION)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        Some(DeadLetter {
+            id: 0,
+            predicate_uuid: predicate_uuid.to_string(),
+            url: request.url().to_string(),
+            headers,
+            payload,
+            error: String::new(),
+            failed_at: 0,
+            redelivery_attempts: 0,
+        })
+    }
+
+    pub fn to_request(
+        &self,
+        client: &Client,
+        authorization_header: Option<&str>,
+    ) -> RequestBuilder {
+        let mut request = client.post(&self.url);
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+        if let Some(authorization_header) = authorization_header {
+            request = request.header(AUTHORIZATION, authorization_header);
+        }
+        request.body(self.payload.to_string())
+    }
+
+    /// Summary of the dead letter, without its payload.
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "id": self.id,
+            "predicate_uuid": self.predicate_uuid,
+            "url": self.url,
+            "error": self.error,
+            "failed_at": self.failed_at,
+            "redelivery_attempts": self.redelivery_attempts,
+        })
+    }
+}
+
+/// Stores an occurrence whose delivery failed with `error`.
+pub fn record_dead_letter(
+    mut dead_letter: DeadLetter,
+    error: &str,
+    config: &Config,
+    ctx: &Context,
+) {
+    dead_letter.error = error.to_string();
+    dead_letter.failed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    try_warn!(
+        ctx,
+        "Occurrence of predicate {} moved to the dead-letter queue: {error}",
+        dead_letter.predicate_uuid
+    );
+    match open_readwrite_observers_db_conn(config, ctx) {
+        Ok(observers_db_conn) => {
+            insert_dead_letter_in_observers(&dead_letter, &observers_db_conn, ctx)
+        }
+        Err(e) => try_warn!(ctx, "Unable to store dead letter: {e}"),
+    }
+}
+
+/// Redelivers dead letters, oldest first. Delivered dead letters are removed from the queue, the other ones stay with
+/// their last error. Returns the number of dead letters delivered and still failing.
+pub async fn redeliver_dead_letters(
+    dead_letters: Vec<DeadLetter>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(usize, usize), String> {
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    let client = build_webhook_client(&config.resources)?;
+    let mut delivered = 0;
+    let mut failed = 0;
+    for dead_letter in dead_letters.into_iter() {
+        let authorization_header =
+            match find_observer_with_uuid(&dead_letter.predicate_uuid, &observers_db_conn, ctx) {
+                Some((ChainhookSpecification::Bitcoin(spec), _)) => match spec.action {
+                    HookAction::HttpPost(http) => Some(http.authorization_header),
+                    _ => None,
+                },
+                _ => None,
+            };
+        if authorization_header.is_none() {
+            try_warn!(
+                ctx,
+                "Predicate {} is not registered anymore, dead letter #{} is redelivered without authorization header",
+                dead_letter.predicate_uuid,
+                dead_letter.id
+            );
+        }
+        let request = dead_letter.to_request(&client, authorization_header.as_deref());
+        match send_webhook_request(request, &config.resources, ctx).await {
+            Ok(_) => {
+                try_info!(
+                    ctx,
+                    "Dead letter #{} redelivered to {}",
+                    dead_letter.id,
+                    dead_letter.url
+                );
+                remove_dead_letter_from_observers(dead_letter.id, &observers_db_conn, ctx);
+                delivered += 1;
+            }
+            Err(e) => {
+                try_warn!(
+                    ctx,
+                    "Unable to redeliver dead letter #{}: {e}",
+                    dead_letter.id
+                );
+                update_dead_letter_in_observers(dead_letter.id, &e, &observers_db_conn, ctx);
+                failed += 1;
+            }
+        }
+    }
+    Ok((delivered, failed))
+}
+
+/// Dead letters selected by id, or all the dead letters of a predicate, or all of them.
+pub fn get_dead_letters(
+    predicate_uuid: Option<&str>,
+    ids: &[u64],
+    config: &Config,
+    ctx: &Context,
+) -> Vec<DeadLetter> {
+    let observers_db_conn = initialize_observers_db(config, ctx);
+    find_dead_letters(predicate_uuid, &observers_db_conn, ctx)
+        .into_iter()
+        .filter(|dead_letter| ids.is_empty() || ids.contains(&dead_letter.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use reqwest::Client;
+    use serde_json::json;
+
+    use super::DeadLetter;
+
+    #[test]
+    fn captures_webhook_requests() {
+        let payload = json!({ "apply": [], "rollback": [], "chainhook": { "uuid": "1" } });
+        let request = Client::new()
+            .post("https://consumer.test/hook")
+            .header("authorization", "Bearer secret")
+            .header("x-ordhook-test", "1")
+            .body(payload.to_string());
+        let dead_letter = DeadLetter::from_request("1", &request).unwrap();
+        assert_eq!(dead_letter.url, "https://consumer.test/hook");
+        assert_eq!(
+            dead_letter.headers,
+            vec![("x-ordhook-test".to_string(), "1".to_string())]
+        );
+        assert_eq!(dead_letter.payload, payload);
+
+        let (_, redelivery) = dead_letter
+            .to_request(&Client::new(), Some("Bearer rotated"))
+            .build_split();
+        let redelivery = redelivery.unwrap();
+        assert_eq!(redelivery.headers()["authorization"], "Bearer rotated");
+        assert_eq!(redelivery.headers()["x-ordhook-test"], "1");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(
+                redelivery.body().unwrap().as_bytes().unwrap()
+            )
+            .unwrap(),
+            payload
+        );
+
+        let request = Client::new()
+            .post("https://consumer.test/hook")
+            .body("not json");
+        assert!(DeadLetter::from_request("1", &request).is_none());
+    }
+}
@@ -0,0 +1,979 @@
+use std::collections::HashMap;
+
+use chainhook_sdk::{
+    bitcoincore_rpc_json::bitcoin::Network, chainhooks::bitcoin::BitcoinChainhookOccurrence,
+    utils::Context,
+};
+use reqwest::RequestBuilder;
+use rusqlite::Connection;
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::brc20::{
+            db::{get_rejected_operations_in_block, open_brc20_db},
+            predicate::get_brc20_predicate_filter,
+        },
+        meta_protocols::runes::{
+            db::{
+                find_cenotaph_flaw_of_transaction, find_rune_activity_of_transaction,
+                find_rune_outpoints_of_transaction, get_rune_entry, open_runes_db,
+            },
+            RuneId,
+        },
+        protocol::{
+            addresses::script_hex_address, inscription_parsing::get_stripped_content_hash,
+            inscription_sequencing::get_bitcoin_network,
+        },
+    },
+    db::ordinals::{
+        find_inscription_content_types, find_inscriptions_genesis_with_ordinal_number,
+        find_latest_inscription_transfer_data, find_miner_payout_address, find_watched_outputs,
+        open_ordinals_db,
+    },
+    service::observers::{
+        find_predicate_enrichment, get_default_observers_db_file_path,
+        open_readonly_observers_db_conn,
+    },
+    try_warn,
+    utils::format_outpoint_to_watch,
+};
+
+const ENRICHMENT_FIELDS: [&str; 7] = [
+    "genesis",
+    "collection",
+    "current_owner",
+    "content_json",
+    "content_type",
+    "miner_address",
+    "runes",
+];
+/// Contents decoded by the `content_json` enrichment must be under this size.
+pub const CONTENT_JSON_MAX_BYTES: usize = 64 * 1024;
+const CONTENT_JSON_CONTENT_TYPES: [&str; 2] = ["application/json", "text/plain"];
+
+/// Data a predicate can have joined to the transfer events it gets delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrichmentField {
+    /// Number, genesis height, content type and metaprotocol of the inscriptions of the transferred sat.
+    Genesis,
+    /// Parent of the inscriptions of the transferred sat.
+    Collection,
+    /// Latest location of the transferred sat, with the address of its output.
+    CurrentOwner,
+    /// Content of revealed `application/json` and `text/plain` inscriptions, when it parses as JSON.
+    ContentJson,
+    /// Content type detected from the content of revealed inscriptions, and whether their declared type mismatches it.
+    ContentType,
+    /// Address of the coinbase output receiving the sats of transfers spent in fees.
+    MinerAddress,
+    /// Runes etched, minted, transferred and burned by the delivered transactions, and their cenotaphs.
+    Runes,
+}
+
+impl EnrichmentField {
+    pub fn parse(value: &str) -> Option<EnrichmentField> {
+        match value {
+            "genesis" => Some(EnrichmentField::Genesis),
+            "collection" => Some(EnrichmentField::Collection),
+            "current_owner" => Some(EnrichmentField::CurrentOwner),
+            "content_json" => Some(EnrichmentField::ContentJson),
+            "content_type" => Some(EnrichmentField::ContentType),
+            "miner_address" => Some(EnrichmentField::MinerAddress),
+            "runes" => Some(EnrichmentField::Runes),
+            _ => None,
+        }
+    }
+
+    fn is_transfer_enrichment(&self) -> bool {
+        matches!(
+            self,
+            EnrichmentField::Genesis | EnrichmentField::Collection | EnrichmentField::CurrentOwner
+        )
+    }
+}
+
+/// Predicates can include `enrich`, a list of `genesis`, `collection` and `current_owner`, next to `if_this` in their
+/// network specifications. Every `inscription_transferred` operation delivered then gets an `enrichment` object
+/// computed from the index, sparing consumers a lookup per event. With `content_json`, `inscription_revealed`
+/// operations of small JSON or text inscriptions get their content parsed in a `content_json` field, the ones which
+/// content was not stored get `content_stored: false` instead. With `content_type`,
+/// `inscription_revealed` operations get the `detected_content_type` of their content and a `content_type_mismatch`
+/// flag. With `miner_address`, transfers spent in fees get the address of the miner payout in their `destination`.
+/// With `runes`, transactions get the `rune_operations` they performed, burns being told apart from transfers.
+pub fn extract_predicate_enrichment(
+    predicate: &mut JsonValue,
+) -> Result<Option<Vec<EnrichmentField>>, String> {
+    let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    else {
+        return Ok(None);
+    };
+    let mut enrichment = None;
+    for (_, network) in networks.iter_mut() {
+        let Some(network) = network.as_object_mut() else {
+            continue;
+        };
+        let Some(value) = network.remove("enrich") else {
+            continue;
+        };
+        let values = value
+            .as_array()
+            .ok_or("enrich must be a list of strings".to_string())?;
+        let mut fields = vec![];
+        for value in values.iter() {
+            let field = value
+                .as_str()
+                .and_then(EnrichmentField::parse)
+                .ok_or(format!(
+                    "invalid enrichment {value}, expected one of {}",
+                    ENRICHMENT_FIELDS.join(", ")
+                ))?;
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        if fields.is_empty() {
+            return Err(format!(
+                "enrich must list at least one of {}",
+                ENRICHMENT_FIELDS.join(", ")
+            ));
+        }
+        enrichment = Some(fields);
+    }
+    Ok(enrichment)
+}
+
+/// Connections read by the enrichment of the occurrences, opened on the first enriched occurrence and kept by the
+/// delivery loop for the next blocks.
+#[derive(Default)]
+pub struct EnrichmentDbConnections {
+    observers: Option<Connection>,
+    ordinals: Option<Connection>,
+    runes: Option<Connection>,
+    brc20: Option<Connection>,
+}
+
+impl EnrichmentDbConnections {
+    pub fn new() -> EnrichmentDbConnections {
+        EnrichmentDbConnections::default()
+    }
+
+    /// Enrichments requested by a predicate, stored in the observers db when it got registered. Scans of predicate
+    /// files run by the CLI have no observers db, nor enrichments.
+    fn get_predicate_enrichment(
+        &mut self,
+        uuid: &str,
+        config: &Config,
+        ctx: &Context,
+    ) -> Option<Vec<EnrichmentField>> {
+        let db_conn = self.get_observers_db_conn(uuid, config, ctx)?;
+        find_predicate_enrichment(uuid, db_conn, ctx)
+    }
+
+    fn get_observers_db_conn(
+        &mut self,
+        uuid: &str,
+        config: &Config,
+        ctx: &Context,
+    ) -> Option<&Connection> {
+        if self.observers.is_none() {
+            if !get_default_observers_db_file_path(config).exists() {
+                return None;
+            }
+            match open_readonly_observers_db_conn(config, ctx) {
+                Ok(conn) => self.observers = Some(conn),
+                Err(e) => {
+                    try_warn!(ctx, "Unable to read enrichment of predicate {uuid}: {e}");
+                    return None;
+                }
+            }
+        }
+        self.observers.as_ref()
+    }
+
+    pub(crate) fn get_ordinals_db_conn(
+        &mut self,
+        config: &Config,
+        ctx: &Context,
+    ) -> Result<&Connection, String> {
+        if self.ordinals.is_none() {
+            let conn =
+                open_ordinals_db(&config.expected_sqlite_path(), ctx).map_err(|e| e.to_string())?;
+            self.ordinals = Some(conn);
+        }
+        self.ordinals
+            .as_ref()
+            .ok_or("ordinals db not opened".to_string())
+    }
+
+    pub(crate) fn get_brc20_db_conn(
+        &mut self,
+        config: &Config,
+        ctx: &Context,
+    ) -> Result<&Connection, String> {
+        if self.brc20.is_none() {
+            self.brc20 = Some(open_brc20_db(config, ctx)?);
+        }
+        self.brc20.as_ref().ok_or("brc20 db not opened".to_string())
+    }
+
+    fn get_runes_db_conn(&mut self, config: &Config, ctx: &Context) -> Result<&Connection, String> {
+        if self.runes.is_none() {
+            self.runes = Some(open_runes_db(config, ctx)?);
+        }
+        self.runes.as_ref().ok_or("runes db not opened".to_string())
+    }
+}
+
+/// Data joined to the transfers of `ordinal_number`. The current owner is read at delivery time, so replayed transfers
+/// get the owner of today.
+pub fn get_transfer_enrichment(
+    ordinal_number: u64,
+    fields: &[EnrichmentField],
+    db_conn: &Connection,
+    network: &Network,
+    ctx: &Context,
+) -> JsonValue {
+    let mut enrichment = Map::new();
+    let genesis = fields.contains(&EnrichmentField::Genesis);
+    let collection = fields.contains(&EnrichmentField::Collection);
+    if genesis || collection {
+        let inscriptions =
+            find_inscriptions_genesis_with_ordinal_number(ordinal_number, db_conn, ctx)
+                .into_iter()
+                .map(|inscription| {
+                    let mut entry = Map::new();
+                    entry.insert("inscription_id".into(), json!(inscription.inscription_id));
+                    if genesis {
+                        entry.insert(
+                            "inscription_number".into(),
+                            json!({
+                                "jubilee": inscription.jubilee_inscription_number,
+                                "classic": inscription.classic_inscription_number,
+                            }),
+                        );
+                        entry.insert(
+                            "genesis_block_height".into(),
+                            json!(inscription.block_height),
+                        );
+                        entry.insert("content_type".into(), json!(inscription.content_type));
+                        entry.insert("metaprotocol".into(), json!(inscription.metaprotocol));
+                    }
+                    if collection {
+                        entry.insert("collection".into(), json!(inscription.parent));
+                    }
+                    JsonValue::Object(entry)
+                })
+                .collect::<Vec<_>>();
+        enrichment.insert("inscriptions".into(), json!(inscriptions));
+    }
+    if fields.contains(&EnrichmentField::CurrentOwner) {
+        let current_owner = match find_latest_inscription_transfer_data(
+            &ordinal_number,
+            db_conn,
+            ctx,
+        ) {
+            Ok(Some(transfer)) => {
+                let outpoint = format_outpoint_to_watch(
+                    &transfer.transaction_identifier_location,
+                    transfer.output_index,
+                );
+                let address = find_watched_outputs(&[outpoint.clone()], db_conn, ctx)
+                    .remove(&outpoint)
+                    .and_then(|output| script_hex_address(&output.script_pubkey, network));
+                json!({
+                    "satpoint": format!("{outpoint}:{}", transfer.inscription_offset_intra_output),
+                    "address": address,
+                })
+            }
+            _ => JsonValue::Null,
+        };
+        enrichment.insert("current_owner".into(), current_owner);
+    }
+    JsonValue::Object(enrichment)
+}
+
+/// Address of the coinbase output a sat spent in fees landed in, stored when its block was indexed. Blocks indexed by a
+/// previous release fall back on the coinbase output stored with the other watched outputs. Returns `None` for sats
+/// lost past the coinbase outputs, and for payouts to scripts without address.
+pub fn get_miner_payout_address(
+    satpoint_post_transfer: &str,
+    db_conn: &Connection,
+    network: &Network,
+    ctx: &Context,
+) -> Option<String> {
+    if let Some(address) = find_miner_payout_address(satpoint_post_transfer, db_conn, ctx) {
+        return Some(address);
+    }
+    let (outpoint, offset) = satpoint_post_transfer.rsplit_once(':')?;
+    let offset = offset.parse::<u64>().ok()?;
+    let output = find_watched_outputs(&[outpoint.to_string()], db_conn, ctx).remove(outpoint)?;
+    if offset >= output.value {
+        return None;
+    }
+    script_hex_address(&output.script_pubkey, network)
+}
+
+/// Content of a revealed inscription, parsed as JSON. Only `application/json` and `text/plain` contents smaller than
+/// `CONTENT_JSON_MAX_BYTES` get parsed.
+pub fn decode_content_json(reveal: &Map<String, JsonValue>) -> Option<JsonValue> {
+    let content_type = reveal.get("content_type")?.as_str()?;
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if !CONTENT_JSON_CONTENT_TYPES.contains(&media_type.as_str()) {
+        return None;
+    }
+    let hex_content = reveal.get("content_bytes")?.as_str()?;
+    let hex_content = hex_content.strip_prefix("0x").unwrap_or(hex_content);
+    if hex_content.len() / 2 > CONTENT_JSON_MAX_BYTES {
+        return None;
+    }
+    let content = hex::decode(hex_content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Whether a revealed inscription carries the digest of its content instead of its content, which was not stored.
+fn is_content_stripped(reveal: &Map<String, JsonValue>) -> bool {
+    reveal
+        .get("content_bytes")
+        .and_then(|c| c.as_str())
+        .and_then(get_stripped_content_hash)
+        .is_some()
+}
+
+/// Adds an `enrichment` object to every `inscription_transferred` operation of a predicate occurrence payload,
+/// `content_json` and detected content type fields to its `inscription_revealed` operations, and the miner address to
+/// the destination of its transfers spent in fees, as requested by `fields`.
+pub fn enrich_predicate_payload(
+    payload: &mut JsonValue,
+    fields: &[EnrichmentField],
+    db_conn: &Connection,
+    network: &Network,
+    ctx: &Context,
+) {
+    let enrich_transfers = fields.iter().any(|field| field.is_transfer_enrichment());
+    let decode_contents = fields.contains(&EnrichmentField::ContentJson);
+    let detect_content_types = fields.contains(&EnrichmentField::ContentType);
+    let resolve_miners = fields.contains(&EnrichmentField::MinerAddress);
+    let mut enrichments: HashMap<u64, JsonValue> = HashMap::new();
+    for key in ["apply", "rollback"] {
+        let Some(blocks) = payload.get_mut(key).and_then(|b| b.as_array_mut()) else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let Some(transactions) = block.get_mut("transactions").and_then(|t| t.as_array_mut())
+            else {
+                continue;
+            };
+            for tx in transactions.iter_mut() {
+                let Some(operations) = tx
+                    .pointer_mut("/metadata/ordinal_operations")
+                    .and_then(|o| o.as_array_mut())
+                else {
+                    continue;
+                };
+                for operation in operations.iter_mut() {
+                    if let Some(reveal) = operation
+                        .get_mut("inscription_revealed")
+                        .and_then(|r| r.as_object_mut())
+                    {
+                        if decode_contents && is_content_stripped(reveal) {
+                            reveal.insert("content_stored".into(), json!(false));
+                        } else if let Some(content_json) = decode_contents
+                            .then(|| decode_content_json(reveal))
+                            .flatten()
+                        {
+                            reveal.insert("content_json".into(), content_json);
+                        }
+                        if let Some(content_types) = detect_content_types
+                            .then(|| {
+                                reveal
+                                    .get("inscription_id")
+                                    .and_then(|id| id.as_str())
+                                    .and_then(|id| find_inscription_content_types(id, db_conn, ctx))
+                            })
+                            .flatten()
+                        {
+                            reveal.insert(
+                                "detected_content_type".into(),
+                                json!(content_types.detected_content_type),
+                            );
+                            reveal.insert(
+                                "content_type_mismatch".into(),
+                                json!(content_types.content_type_mismatch),
+                            );
+                        }
+                        continue;
+                    }
+                    let Some(transfer) = operation
+                        .get_mut("inscription_transferred")
+                        .and_then(|t| t.as_object_mut())
+                    else {
+                        continue;
+                    };
+                    if resolve_miners {
+                        enrich_spent_in_fees_destination(transfer, db_conn, network, ctx);
+                    }
+                    if !enrich_transfers {
+                        continue;
+                    }
+                    let Some(ordinal_number) =
+                        transfer.get("ordinal_number").and_then(|n| n.as_u64())
+                    else {
+                        continue;
+                    };
+                    let enrichment = enrichments
+                        .entry(ordinal_number)
+                        .or_insert_with(|| {
+                            get_transfer_enrichment(ordinal_number, fields, db_conn, network, ctx)
+                        })
+                        .clone();
+                    transfer.insert("enrichment".into(), enrichment);
+                }
+            }
+        }
+    }
+}
+
+/// Adds the `rune_operations` of every transaction of a predicate occurrence payload to its metadata: the cenotaph of
+/// the transaction with its flaw, then its etchings, mints and burns, then the runes transferred to its outputs. Burns
+/// carry their `reason`, `op_return`, `no_output` or `cenotaph`, so that consumers keep supplies right. Operations are
+/// read from the index at delivery time, rolled back blocks having none left.
+pub fn enrich_payload_with_rune_operations(
+    payload: &mut JsonValue,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    let mut spaced_runes: HashMap<RuneId, Option<String>> = HashMap::new();
+    let mut get_spaced_rune = |rune_id: &RuneId| -> JsonValue {
+        json!(spaced_runes
+            .entry(*rune_id)
+            .or_insert_with(|| {
+                get_rune_entry(rune_id, db_conn, ctx).map(|entry| entry.spaced_rune.to_string())
+            })
+            .clone())
+    };
+    for key in ["apply", "rollback"] {
+        let Some(blocks) = payload.get_mut(key).and_then(|b| b.as_array_mut()) else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let Some(block_height) = block
+                .pointer("/block_identifier/index")
+                .and_then(|i| i.as_u64())
+            else {
+                continue;
+            };
+            let Some(transactions) = block.get_mut("transactions").and_then(|t| t.as_array_mut())
+            else {
+                continue;
+            };
+            for tx in transactions.iter_mut() {
+                let Some(tx_id) = tx
+                    .pointer("/transaction_identifier/hash")
+                    .and_then(|h| h.as_str())
+                    .map(|h| h.trim_start_matches("0x").to_string())
+                else {
+                    continue;
+                };
+                let mut operations = vec![];
+                if let Some(flaw) =
+                    find_cenotaph_flaw_of_transaction(&tx_id, block_height, db_conn, ctx)
+                {
+                    operations.push(json!({ "cenotaph": { "flaw": flaw } }));
+                }
+                for activity in
+                    find_rune_activity_of_transaction(&tx_id, block_height, db_conn, ctx)
+                {
+                    let mut operation = Map::new();
+                    operation.insert("rune_id".into(), json!(activity.rune_id.to_string()));
+                    operation.insert("spaced_rune".into(), get_spaced_rune(&activity.rune_id));
+                    operation.insert("amount".into(), json!(activity.amount.to_string()));
+                    let key = match activity.operation.as_str() {
+                        "etching" => "rune_etched",
+                        "mint" => "rune_minted",
+                        "burn" => {
+                            operation.insert("reason".into(), json!(activity.reason));
+                            "rune_burned"
+                        }
+                        _ => continue,
+                    };
+                    operations.push(json!({ key: operation }));
+                }
+                for outpoint in
+                    find_rune_outpoints_of_transaction(&tx_id, block_height, db_conn, ctx)
+                {
+                    let output = outpoint
+                        .outpoint
+                        .rsplit_once(':')
+                        .and_then(|(_, vout)| vout.parse::<u32>().ok());
+                    operations.push(json!({
+                        "rune_transferred": {
+                            "rune_id": outpoint.rune_id.to_string(),
+                            "spaced_rune": get_spaced_rune(&outpoint.rune_id),
+                            "amount": outpoint.amount.to_string(),
+                            "output": output,
+                            "address": outpoint.address,
+                        }
+                    }));
+                }
+                if operations.is_empty() {
+                    continue;
+                }
+                if let Some(metadata) = tx.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+                    metadata.insert("rune_operations".into(), json!(operations));
+                }
+            }
+        }
+    }
+}
+
+/// Adds a `brc20_validation` object to the metadata of every transaction of a predicate occurrence payload, delivered
+/// to a predicate with a BRC-20 filter: `valid` when the transaction carries a verified `brc20_operation`, and its
+/// `rejected_operations` with the reason each of them was rejected for.
+pub fn attach_brc20_validation(payload: &mut JsonValue, db_conn: &Connection, ctx: &Context) {
+    for key in ["apply", "rollback"] {
+        let Some(blocks) = payload.get_mut(key).and_then(|b| b.as_array_mut()) else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let Some(block_height) = block
+                .pointer("/block_identifier/index")
+                .and_then(|i| i.as_u64())
+            else {
+                continue;
+            };
+            let mut rejected_operations =
+                get_rejected_operations_in_block(block_height, db_conn, ctx);
+            let Some(transactions) = block.get_mut("transactions").and_then(|t| t.as_array_mut())
+            else {
+                continue;
+            };
+            for tx in transactions.iter_mut() {
+                let tx_id = tx
+                    .pointer("/transaction_identifier/hash")
+                    .and_then(|h| h.as_str())
+                    .map(|h| h.trim_start_matches("0x").to_string())
+                    .unwrap_or_default();
+                let Some(metadata) = tx.get_mut("metadata").and_then(|m| m.as_object_mut()) else {
+                    continue;
+                };
+                let valid = metadata
+                    .get("brc20_operation")
+                    .is_some_and(|operation| !operation.is_null());
+                let rejected = rejected_operations.remove(&tx_id).unwrap_or_default();
+                metadata.insert(
+                    "brc20_validation".into(),
+                    json!({ "valid": valid, "rejected_operations": rejected }),
+                );
+            }
+        }
+    }
+}
+
+/// Sets the miner address as the `value` of the `spent_in_fees` destination of a transfer, when resolvable.
+fn enrich_spent_in_fees_destination(
+    transfer: &mut Map<String, JsonValue>,
+    db_conn: &Connection,
+    network: &Network,
+    ctx: &Context,
+) {
+    let Some(satpoint) = transfer
+        .get("satpoint_post_transfer")
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string())
+    else {
+        return;
+    };
+    let Some(destination) = transfer
+        .get_mut("destination")
+        .and_then(|d| d.as_object_mut())
+        .filter(|d| d.get("type").and_then(|t| t.as_str()) == Some("spent_in_fees"))
+    else {
+        return;
+    };
+    if let Some(address) = get_miner_payout_address(&satpoint, db_conn, network, ctx) {
+        destination.insert("value".into(), json!(address));
+    }
+}
+
+/// Enriches the occurrence of a predicate before its delivery, if the predicate requested it, and attaches the BRC-20
+/// validation results to the deliveries of predicates with a BRC-20 filter. Payloads handed to data handlers are typed,
+/// and get delivered as is.
+pub fn enrich_predicate_occurrence(
+    uuid: &str,
+    occurrence: BitcoinChainhookOccurrence,
+    db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) -> BitcoinChainhookOccurrence {
+    let fields = db_conns
+        .get_predicate_enrichment(uuid, config, ctx)
+        .unwrap_or_default();
+    let validate_brc20 = get_brc20_predicate_filter(uuid).is_some();
+    if fields.is_empty() && !validate_brc20 {
+        return occurrence;
+    }
+    if validate_brc20 {
+        if let Err(e) = db_conns.get_brc20_db_conn(config, ctx) {
+            try_warn!(
+                ctx,
+                "Unable to validate occurrence of predicate {uuid}: {e}"
+            );
+        }
+    }
+    let enrich_runes = fields.contains(&EnrichmentField::Runes);
+    if enrich_runes {
+        if let Err(e) = db_conns.get_runes_db_conn(config, ctx) {
+            try_warn!(
+                ctx,
+                "Unable to enrich occurrence of predicate {uuid} with runes: {e}"
+            );
+        }
+    }
+    if let Err(e) = db_conns.get_ordinals_db_conn(config, ctx) {
+        try_warn!(ctx, "Unable to enrich occurrence of predicate {uuid}: {e}");
+        return occurrence;
+    }
+    let Some(db_conn) = db_conns.ordinals.as_ref() else {
+        return occurrence;
+    };
+    let runes_db_conn = db_conns.runes.as_ref().filter(|_| enrich_runes);
+    let brc20_db_conn = db_conns.brc20.as_ref().filter(|_| validate_brc20);
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    let enrich = |bytes: &[u8]| -> Option<Vec<u8>> {
+        let mut payload = match serde_json::from_slice::<JsonValue>(bytes) {
+            Ok(payload) => payload,
+            Err(e) => {
+                try_warn!(ctx, "Unable to enrich occurrence of predicate {uuid}: {e}");
+                return None;
+            }
+        };
+        enrich_predicate_payload(&mut payload, &fields, db_conn, &network, ctx);
+        if let Some(runes_db_conn) = runes_db_conn {
+            enrich_payload_with_rune_operations(&mut payload, runes_db_conn, ctx);
+        }
+        if let Some(brc20_db_conn) = brc20_db_conn {
+            attach_brc20_validation(&mut payload, brc20_db_conn, ctx);
+        }
+        match serde_json::to_vec(&payload) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                try_warn!(ctx, "Unable to enrich occurrence of predicate {uuid}: {e}");
+                None
+            }
+        }
+    };
+    match occurrence {
+        BitcoinChainhookOccurrence::Http(request, data) => {
+            let Some((client, Ok(mut built))) =
+                request.try_clone().map(|request| request.build_split())
+            else {
+                return BitcoinChainhookOccurrence::Http(request, data);
+            };
+            let Some(body) = built
+                .body()
+                .and_then(|body| body.as_bytes())
+                .and_then(|bytes| enrich(bytes))
+            else {
+                return BitcoinChainhookOccurrence::Http(request, data);
+            };
+            *built.body_mut() = Some(body.into());
+            BitcoinChainhookOccurrence::Http(RequestBuilder::from_parts(client, built), data)
+        }
+        BitcoinChainhookOccurrence::File(path, bytes) => {
+            let bytes = enrich(&bytes).unwrap_or(bytes);
+            BitcoinChainhookOccurrence::File(path, bytes)
+        }
+        occurrence => occurrence,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::{bitcoincore_rpc_json::bitcoin::Network, utils::Context};
+    use serde_json::json;
+
+    use chainhook_sdk::types::TransactionIdentifier;
+
+    use crate::{
+        config::Config,
+        core::{
+            meta_protocols::{
+                brc20::test_utils::get_test_ctx,
+                runes::{
+                    db::{index_runes_in_block, initialize_runes_db},
+                    test_utils::runestone_tx,
+                },
+            },
+            protocol::inscription_parsing::get_inscription_content_hash,
+            test_builders::TestBlockBuilder,
+        },
+        db::ordinals::initialize_ordinals_db,
+    };
+
+    use super::{
+        decode_content_json, enrich_payload_with_rune_operations, enrich_predicate_payload,
+        extract_predicate_enrichment, is_content_stripped, EnrichmentField, CONTENT_JSON_MAX_BYTES,
+    };
+
+    #[test]
+    fn extracts_enrichment_fields() {
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol" }, "enrich": ["genesis", "current_owner", "genesis"] } }
+        });
+        assert_eq!(
+            extract_predicate_enrichment(&mut predicate),
+            Ok(Some(vec![
+                EnrichmentField::Genesis,
+                EnrichmentField::CurrentOwner
+            ]))
+        );
+        assert_eq!(predicate["networks"]["mainnet"].get("enrich"), None);
+
+        let mut predicate = json!({ "networks": { "mainnet": { "enrich": ["owner"] } } });
+        assert!(extract_predicate_enrichment(&mut predicate).is_err());
+        let mut predicate = json!({ "networks": { "mainnet": { "enrich": [] } } });
+        assert!(extract_predicate_enrichment(&mut predicate).is_err());
+    }
+
+    #[test]
+    fn enriches_transfer_events() {
+        let ctx = Context::empty();
+        let working_dir = std::env::temp_dir().join("ordhook_test_enrichment");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&working_dir, &ctx);
+        let tx_a = "a".repeat(64);
+        let tx_b = "b".repeat(64);
+        let tx_c = "c".repeat(64);
+        let tx_d = "d".repeat(64);
+        let tx_e = "e".repeat(64);
+        let tx_p = "f".repeat(64);
+        db_conn
+            .execute_batch(&format!(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES ('{tx_a}i0', 10, 7, 7, 800000, 0);
+                INSERT INTO inscription_genesis (inscription_id, block_height, content_type, parent, metaprotocol) VALUES ('{tx_a}i0', 800000, 'image/png', '{tx_p}i0', NULL);
+                INSERT INTO locations (ordinal_number, outpoint_to_watch, offset, block_height, tx_index) VALUES (10, '{tx_a}:0', 0, 800000, 1), (10, '{tx_b}:1', 0, 800001, 2);
+                INSERT INTO watched_outputs (outpoint, block_height, value, script_pubkey) VALUES ('{tx_b}:1', 800001, 546, '0x0014751e76e8199196d454941c45d1b3a323f1433bd6');
+                INSERT INTO watched_outputs (outpoint, block_height, value, script_pubkey) VALUES ('{tx_c}:0', 800002, 625000000, '0x0014751e76e8199196d454941c45d1b3a323f1433bd6');
+                INSERT INTO watched_outputs (outpoint, block_height, value, script_pubkey) VALUES ('{tx_d}:0', 800003, 1000, '0x0014751e76e8199196d454941c45d1b3a323f1433bd6');
+                INSERT INTO miner_payouts (satpoint, block_height, address) VALUES ('{tx_e}:1:0', 800004, 'bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3');"
+            ))
+            .unwrap();
+
+        let mut payload = json!({
+            "apply": [{
+                "transactions": [{
+                    "metadata": {
+                        "ordinal_operations": [
+                            { "inscription_transferred": { "ordinal_number": 10, "satpoint_post_transfer": format!("{tx_b}:1:0") } },
+                        ],
+                    },
+                }],
+            }],
+            "rollback": [],
+        });
+        enrich_predicate_payload(
+            &mut payload,
+            &[
+                EnrichmentField::Genesis,
+                EnrichmentField::Collection,
+                EnrichmentField::CurrentOwner,
+            ],
+            &db_conn,
+            &Network::Bitcoin,
+            &ctx,
+        );
+        assert_eq!(
+            payload["apply"][0]["transactions"][0]["metadata"]["ordinal_operations"][0]
+                ["inscription_transferred"]["enrichment"],
+            json!({
+                "inscriptions": [{
+                    "inscription_id": format!("{tx_a}i0"),
+                    "inscription_number": { "jubilee": 7, "classic": 7 },
+                    "genesis_block_height": 800000,
+                    "content_type": "image/png",
+                    "metaprotocol": null,
+                    "collection": format!("{tx_p}i0"),
+                }],
+                "current_owner": {
+                    "satpoint": format!("{tx_b}:1:0"),
+                    "address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                },
+            })
+        );
+
+        let mut payload = json!({
+            "apply": [{
+                "transactions": [{
+                    "metadata": {
+                        "ordinal_operations": [
+                            { "inscription_transferred": { "ordinal_number": 10, "destination": { "type": "spent_in_fees" }, "satpoint_post_transfer": format!("{tx_c}:0:312500000") } },
+                            { "inscription_transferred": { "ordinal_number": 11, "destination": { "type": "spent_in_fees" }, "satpoint_post_transfer": format!("{tx_d}:0:1000") } },
+                            { "inscription_transferred": { "ordinal_number": 12, "destination": { "type": "spent_in_fees" }, "satpoint_post_transfer": format!("{tx_e}:1:0") } },
+                        ],
+                    },
+                }],
+            }],
+            "rollback": [],
+        });
+        enrich_predicate_payload(
+            &mut payload,
+            &[EnrichmentField::MinerAddress],
+            &db_conn,
+            &Network::Bitcoin,
+            &ctx,
+        );
+        let transfer = &payload["apply"][0]["transactions"][0]["metadata"]["ordinal_operations"][0]
+            ["inscription_transferred"];
+        assert_eq!(
+            transfer["destination"],
+            json!({ "type": "spent_in_fees", "value": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4" })
+        );
+        assert_eq!(transfer.get("enrichment"), None);
+        let operations = &payload["apply"][0]["transactions"][0]["metadata"]["ordinal_operations"];
+        // Lost past the coinbase outputs.
+        assert_eq!(
+            operations[1]["inscription_transferred"]["destination"],
+            json!({ "type": "spent_in_fees" })
+        );
+        assert_eq!(
+            operations[2]["inscription_transferred"]["destination"],
+            json!({ "type": "spent_in_fees", "value": "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3" })
+        );
+
+        db_conn
+            .execute_batch(&format!(
+                "INSERT INTO inscription_content_types (inscription_id, block_height, declared_content_type, detected_content_type) VALUES ('{tx_a}i0', 800000, 'image/png', 'text/html');"
+            ))
+            .unwrap();
+        let mut payload = json!({
+            "apply": [{
+                "transactions": [{
+                    "metadata": {
+                        "ordinal_operations": [
+                            { "inscription_revealed": { "inscription_id": format!("{tx_a}i0"), "content_type": "image/png" } },
+                            { "inscription_revealed": { "inscription_id": format!("{tx_b}i0"), "content_type": "image/png" } },
+                        ],
+                    },
+                }],
+            }],
+            "rollback": [],
+        });
+        enrich_predicate_payload(
+            &mut payload,
+            &[EnrichmentField::ContentType],
+            &db_conn,
+            &Network::Bitcoin,
+            &ctx,
+        );
+        let operations = &payload["apply"][0]["transactions"][0]["metadata"]["ordinal_operations"];
+        assert_eq!(
+            operations[0]["inscription_revealed"]["detected_content_type"],
+            json!("text/html")
+        );
+        assert_eq!(
+            operations[0]["inscription_revealed"]["content_type_mismatch"],
+            json!(true)
+        );
+        // Contents that could not be sniffed have no detected type.
+        assert_eq!(
+            operations[1]["inscription_revealed"].get("content_type_mismatch"),
+            None
+        );
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+
+    #[test]
+    fn decodes_json_contents() {
+        let reveal = |content_type: &str, content: &str| {
+            json!({
+                "content_type": content_type,
+                "content_bytes": format!("0x{}", hex::encode(content)),
+            })
+            .as_object()
+            .unwrap()
+            .clone()
+        };
+        let mint = r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#;
+        assert_eq!(
+            decode_content_json(&reveal("text/plain;charset=utf-8", mint)),
+            Some(json!({ "p": "brc-20", "op": "mint", "tick": "ordi", "amt": "1000" }))
+        );
+        assert_eq!(
+            decode_content_json(&reveal("application/json", "[1, 2]")),
+            Some(json!([1, 2]))
+        );
+        assert_eq!(decode_content_json(&reveal("text/plain", "gm")), None);
+        assert_eq!(decode_content_json(&reveal("text/html", mint)), None);
+        let large = format!("\"{}\"", "a".repeat(CONTENT_JSON_MAX_BYTES));
+        assert_eq!(
+            decode_content_json(&reveal("application/json", &large)),
+            None
+        );
+
+        let stripped = json!({
+            "content_type": "application/json",
+            "content_bytes": get_inscription_content_hash(mint.as_bytes()),
+        });
+        let stripped = stripped.as_object().unwrap();
+        assert!(is_content_stripped(stripped));
+        assert!(!is_content_stripped(&reveal("application/json", mint)));
+    }
+
+    #[test]
+    fn enriches_rune_operations() {
+        let ctx = get_test_ctx();
+        let mut config = Config::mainnet_default();
+        config.meta_protocols.runes = true;
+        let db_conn = initialize_runes_db(None, &ctx);
+        // Etching with a premine of 1000, 400 of them burned by an edict to the OP_RETURN output.
+        let mut etching = runestone_tx(&[2, 0b1, 6, 1000, 0, 0, 0, 400, 0], 1);
+        etching.transaction_identifier = TransactionIdentifier {
+            hash: format!("0x{}", "aa".repeat(32)),
+        };
+        let block = TestBlockBuilder::new()
+            .height(850000)
+            .add_transaction(runestone_tx(&[], 1))
+            .add_transaction(etching)
+            .build();
+        index_runes_in_block(&block, &config, &db_conn, &ctx).unwrap();
+
+        let mut payload = json!({
+            "apply": [{
+                "block_identifier": { "index": 850000, "hash": "0x00" },
+                "transactions": [
+                    { "transaction_identifier": { "hash": format!("0x{}", "00".repeat(32)) }, "metadata": {} },
+                    { "transaction_identifier": { "hash": format!("0x{}", "aa".repeat(32)) }, "metadata": {} },
+                ],
+            }],
+            "rollback": [],
+        });
+        enrich_payload_with_rune_operations(&mut payload, &db_conn, &ctx);
+        let transactions = &payload["apply"][0]["transactions"];
+        assert!(transactions[0]["metadata"].get("rune_operations").is_none());
+        let operations = transactions[1]["metadata"]["rune_operations"]
+            .as_array()
+            .unwrap();
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations[0]["rune_etched"]["rune_id"], json!("850000:1"));
+        assert_eq!(operations[0]["rune_etched"]["amount"], json!("1000"));
+        assert_eq!(operations[1]["rune_burned"]["amount"], json!("400"));
+        assert_eq!(operations[1]["rune_burned"]["reason"], json!("op_return"));
+        assert_eq!(operations[2]["rune_transferred"]["amount"], json!("600"));
+        assert_eq!(operations[2]["rune_transferred"]["output"], json!(1));
+        assert_eq!(
+            operations[2]["rune_transferred"]["address"],
+            json!("bc1qd2j97e4h8k4jh7lq9usx9feyjgzy08u9me5yda")
+        );
+    }
+}
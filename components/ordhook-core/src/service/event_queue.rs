@@ -0,0 +1,126 @@
+use std::{sync::Mutex, thread::sleep};
+
+use chainhook_sdk::utils::Context;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    config::ResourcesConfig,
+    service::alerts::{post_json_payload_with_retries, run_json_payload_command},
+    try_warn,
+};
+
+/// Event waiting for its delivery to the webhook and / or command of its hook.
+#[derive(Debug, Clone)]
+pub struct QueuedEvent {
+    pub payload: JsonValue,
+    pub webhook_url: Option<String>,
+    pub command: Option<String>,
+    pub env_var: String,
+    pub resources: ResourcesConfig,
+}
+
+lazy_static! {
+    /// Events of the `[rollbacks]` and `[amendments]` hooks, delivered one at a time by the event delivery worker.
+    static ref EVENT_QUEUE_TX: Mutex<Option<crossbeam_channel::Sender<QueuedEvent>>> =
+        Mutex::new(None);
+}
+
+/// Delivers an event to its webhook and its command, until both accepted it. A destination accepting the event is not
+/// sent it again when the other one is retried.
+pub fn deliver_event_with_retries(event: &QueuedEvent, ctx: &Context) {
+    let mut webhook_url = event.webhook_url.clone();
+    let mut command = event.command.clone();
+    let mut attempt = 0;
+    loop {
+        if let Some(ref url) = webhook_url {
+            match post_json_payload_with_retries(&event.payload, url, &event.resources) {
+                Ok(()) => webhook_url = None,
+                Err(e) => try_warn!(ctx, "Unable to deliver {} payload: {e}", event.env_var),
+            }
+        }
+        if let Some(ref cmd) = command {
+            match run_json_payload_command(&event.payload, cmd, &event.env_var, &event.resources) {
+                Ok(()) => command = None,
+                Err(e) => try_warn!(ctx, "{e}"),
+            }
+        }
+        if webhook_url.is_none() && command.is_none() {
+            return;
+        }
+        attempt += 1;
+        sleep(event.resources.network_retry_backoff(attempt));
+    }
+}
+
+/// Queues events for their delivery, in order, by a worker started on the first call. An event is retried until
+/// delivered before the next one is sent, so that consumers never see a rollback or an amendment out of order.
+pub fn enqueue_events(events: Vec<QueuedEvent>, ctx: &Context) {
+    if events.is_empty() {
+        return;
+    }
+    let Ok(mut queue_tx) = EVENT_QUEUE_TX.lock() else {
+        return;
+    };
+    if queue_tx.is_none() {
+        let (tx, rx) = crossbeam_channel::unbounded::<QueuedEvent>();
+        let moved_ctx = ctx.clone();
+        if let Err(e) = hiro_system_kit::thread_named("Event delivery worker").spawn(move || {
+            while let Ok(event) = rx.recv() {
+                deliver_event_with_retries(&event, &moved_ctx);
+            }
+        }) {
+            try_warn!(ctx, "Unable to start event delivery worker: {e}");
+            return;
+        }
+        *queue_tx = Some(tx);
+    }
+    let Some(tx) = queue_tx.as_ref() else {
+        return;
+    };
+    for event in events.into_iter() {
+        if let Err(e) = tx.send(event) {
+            try_warn!(ctx, "Unable to queue event: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+    use serde_json::json;
+
+    use crate::config::Config;
+
+    use super::{deliver_event_with_retries, QueuedEvent};
+
+    #[test]
+    fn retries_events_until_delivered() {
+        let working_dir = std::env::temp_dir().join("ordhook_test_event_queue");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let attempted = working_dir.join("attempted");
+        let delivered = working_dir.join("delivered");
+        let mut resources = Config::test_default().resources;
+        resources.network_retry_backoff_ms = 1;
+        // Fails on the first run, and writes the payload on the second one.
+        let command = format!(
+            "if [ -f {0} ]; then echo \"$ORDHOOK_EVENT\" > {1}; else touch {0}; exit 1; fi",
+            attempted.display(),
+            delivered.display()
+        );
+        let event = QueuedEvent {
+            payload: json!({ "type": "rollback", "block_height": 1 }),
+            webhook_url: None,
+            command: Some(command),
+            env_var: "ORDHOOK_EVENT".into(),
+            resources,
+        };
+        deliver_event_with_retries(&event, &Context::empty());
+        let written = std::fs::read_to_string(&delivered).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&written).unwrap(),
+            event.payload
+        );
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+}
@@ -1,6 +1,15 @@
 use std::{
-    net::{IpAddr, Ipv4Addr},
-    sync::{mpsc::Sender, Arc, Mutex},
+    collections::BTreeMap,
+    io::Cursor,
+    io::Write,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use chainhook_sdk::{
@@ -12,23 +21,137 @@ use chainhook_sdk::{
 };
 use rocket::{
     config::{self, Config as RocketConfig, LogLevel},
-    Ignite, Rocket, Shutdown,
+    fairing::{Fairing, Info, Kind},
+    Data, Ignite, Rocket, Shutdown,
 };
 use rocket::{
     http::Status,
     response::status,
     serde::json::{json, Json, Value},
 };
-use rocket::{response::status::Custom, State};
+use rocket::{
+    http::{uri::Origin, ContentType, Method},
+    request::{self, FromRequest, Request},
+    response::{self as rocket_response, status::Custom, stream::ByteStream, Responder, Response},
+    State,
+};
+use rusqlite::Connection;
 
 use crate::{
-    config::{Config, PredicatesApi},
+    config::{Config, IndexScope, PredicatesApi, DEFAULT_QUERY_TIMEOUT_MS},
+    core::meta_protocols::brc20::db::{
+        get_address_balances_at_block_height, get_latest_ledger_block_height, get_token,
+        get_token_holders, get_token_holders_count, get_token_minted_supply,
+        open_readonly_brc20_db_conn, Brc20DbBalanceRow,
+    },
+    core::meta_protocols::brc20::predicate::{
+        enable_brc20_meta_protocol, extract_brc20_predicate_filter, set_brc20_predicate_filter,
+    },
+    core::meta_protocols::runes::{
+        db::{
+            count_rune_mints, find_unspent_rune_outpoints_of_address, get_rune_burned_amount,
+            get_rune_entry, get_rune_entry_by_name, open_readonly_runes_db_conn, RunesDbEntryRow,
+        },
+        RuneId, SpacedRune,
+    },
+    core::meta_protocols::sns::{
+        db::{get_sns_name, open_readonly_sns_db_conn},
+        normalize_sns_name,
+    },
+    core::protocol::addresses::normalize_address,
+    core::protocol::index_commitment::get_index_commitment_tree,
+    core::protocol::inscription_content::{
+        fetch_inscription_content, write_inscription_contents_archive, ContentBody,
+        INSCRIPTION_CONTENTS_ARCHIVE_MAX_IDS,
+    },
+    core::protocol::inscription_sequencing::get_bitcoin_network,
+    core::protocol::satributes::get_satributes,
+    db::{
+        backup::{backup_all_dbs, get_default_backup_path, is_backup_in_progress},
+        ordinals::{
+            count_inscriptions_with_satribute, find_block_events_hash,
+            find_block_height_range_for_timestamps, find_index_commitment,
+            find_index_commitment_at_height, find_inscription_changes_in_block_range,
+            find_inscription_content_scan, find_inscription_content_types,
+            find_inscription_genesis_content_type, find_inscription_location,
+            find_inscriptions_with_satribute, find_latest_inscription_block_height,
+            open_ordinals_db_snapshot, parse_unbound_satpoint, InscriptionLocationRow,
+        },
+        query_timeout::QueryDeadline,
+        sales::{
+            find_latest_sales, find_sales_of_sat, get_sale_sort_keys, open_readonly_sales_db_conn,
+            SaleRow,
+        },
+        sat_ranges::{find_sat_ranges_held_by_address, open_readonly_sat_ranges_db_conn},
+    },
+    ord::{rarity::Rarity, sat::Sat},
+    scan::predicate_scripts::{extract_predicate_script, set_predicate_script},
+    service::blocklist::{
+        check_blocklist, get_blocklist_entries, parse_blocklist_entry, BlocklistEntry,
+        BLOCKLIST_SOURCE_API,
+    },
+    service::confirmations::{
+        extract_predicate_min_confirmations, set_predicate_min_confirmations, stop_confirmed_stream,
+    },
+    service::enrichment::extract_predicate_enrichment,
+    service::jobs::{cancel_job, get_job, get_jobs, submit_job, JobControl, JobKind, JobStatus},
+    service::live_deliveries::{
+        forget_live_delivery, get_observer_hook_action, is_delivered_by_ordhook,
+        queue_live_delivery, restore_observer_hook_action,
+    },
+    service::liveness::{
+        acknowledge_predicate_pause, pause_predicate, resume_predicate, PredicateHealthRegistry,
+    },
     service::observers::{
-        insert_entry_in_observers, open_readwrite_observers_db_conn, remove_entry_from_observers,
-        update_observer_progress, update_observer_streaming_enabled,
+        find_blocklist_entry, find_predicate_paused_at, find_predicate_payload_version,
+        insert_blocklist_entry_in_observers, insert_brc20_filter_in_observers,
+        insert_entry_in_observers, insert_predicate_enrichment_in_observers,
+        insert_predicate_min_confirmations_in_observers,
+        insert_predicate_payload_version_in_observers, insert_predicate_script_in_observers,
+        insert_wallet_filter_in_observers, open_readwrite_observers_db_conn,
+        remove_blocklist_entry_from_observers, remove_brc20_filter_from_observers,
+        remove_entry_from_observers, remove_predicate_enrichment_from_observers,
+        remove_predicate_min_confirmations_from_observers, remove_predicate_pause_from_observers,
+        remove_predicate_payload_version_from_observers, remove_predicate_script_from_observers,
+        remove_wallet_filter_from_observers, update_observer_progress,
+        update_observer_streaming_enabled,
+    },
+    service::predicate_versions::{
+        get_supported_versions, upgrade_predicate_specification, PAYLOAD_SCHEMA_VERSION,
+        PREDICATE_SPEC_VERSION,
+    },
+    service::psbt::annotate_psbt,
+    service::query_cache::{
+        cache_response, clear_query_cache, get_cached_response, get_query_cache_generation,
+        CacheTag,
+    },
+    service::read_through::{read_through_upstream, validate_read_through_result},
+    service::rescans::queue_rescan,
+    service::schemas::{get_payload_schema, get_payload_schemas_index},
+    service::status::get_service_status,
+    service::tenants::TenantScope,
+    service::usage::{
+        check_api_calls_quota, get_usage_report, record_api_call, record_events_delivered,
+        usage_report_to_csv, validate_usage_day, DEFAULT_TENANT,
+    },
+    service::utxos::{
+        address_scan_requests, annotate_output, parse_utxo_addresses, UtxoScanHandle,
+    },
+    service::wallets::{
+        extract_wallet_predicate_filter, get_wallet_addresses, get_wallet_predicate_filter,
+        unwatch_wallet, watch_wallet,
+    },
+    try_error, try_info, try_warn,
+    utils::{
+        bitcoind::bitcoind_get_block_header,
+        content_scanning::check_content_scan,
+        dates::parse_date_to_timestamp,
+        logger::{log_level_code, parse_log_level, LogLevels, LogSubsystem},
+        monitoring::PrometheusMonitoring,
+        pagination::{decode_page_cursor, paginate},
+        previews::get_or_render_preview,
+        unix_socket::forward_unix_socket_to_tcp,
     },
-    try_error, try_info,
-    utils::monitoring::PrometheusMonitoring,
 };
 
 use super::observers::{
@@ -41,10 +164,20 @@ pub async fn start_observers_http_server(
     observer_event_rx: crossbeam_channel::Receiver<ObserverEvent>,
     bitcoin_scan_op_tx: crossbeam_channel::Sender<BitcoinChainhookSpecification>,
     prometheus: &PrometheusMonitoring,
+    log_levels: Option<LogLevels>,
+    predicate_health: &PredicateHealthRegistry,
     ctx: &Context,
 ) -> Result<Shutdown, String> {
     // Build and start HTTP server.
-    let ignite = build_server(config, observer_commands_tx, ctx).await;
+    let ignite = build_server(
+        config,
+        observer_commands_tx,
+        prometheus,
+        log_levels,
+        predicate_health,
+        ctx,
+    )
+    .await;
     let shutdown = ignite.shutdown();
     let _ = hiro_system_kit::thread_named("observers_api-server").spawn(move || {
         let _ = hiro_system_kit::nestable_block_on(ignite.launch());
@@ -54,13 +187,15 @@ pub async fn start_observers_http_server(
     let moved_config = config.clone();
     let moved_ctx = ctx.clone();
     let moved_prometheus = prometheus.clone();
+    let moved_predicate_health = predicate_health.clone();
     let _ = hiro_system_kit::thread_named("observers_api-events").spawn(move || loop {
         let event = match observer_event_rx.recv() {
             Ok(cmd) => cmd,
             Err(_) => break,
         };
         match event {
-            ObserverEvent::PredicateRegistered(spec) => {
+            ObserverEvent::PredicateRegistered(mut spec) => {
+                restore_observer_hook_action(&mut spec);
                 let observers_db_conn =
                     match open_readwrite_observers_db_conn(&moved_config, &moved_ctx) {
                         Ok(con) => con,
@@ -113,11 +248,53 @@ pub async fn start_observers_http_server(
                             continue;
                         }
                     };
+                if acknowledge_predicate_pause(
+                    &uuid,
+                    &moved_predicate_health,
+                    &observers_db_conn,
+                    &moved_ctx,
+                ) {
+                    // Paused predicates are kept in the observers db, to be registered again once resumed.
+                    moved_prometheus.metrics_deregister_predicate();
+                    continue;
+                }
                 remove_entry_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                remove_brc20_filter_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                set_brc20_predicate_filter(&uuid, None);
+                unwatch_wallet(&uuid);
+                remove_wallet_filter_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                forget_live_delivery(&uuid);
+                remove_predicate_script_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                let _ = set_predicate_script(&uuid, None);
+                remove_predicate_min_confirmations_from_observers(
+                    &uuid,
+                    &observers_db_conn,
+                    &moved_ctx,
+                );
+                set_predicate_min_confirmations(&uuid, None);
+                remove_predicate_enrichment_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                remove_predicate_payload_version_from_observers(
+                    &uuid,
+                    &observers_db_conn,
+                    &moved_ctx,
+                );
+                remove_predicate_pause_from_observers(&uuid, &observers_db_conn, &moved_ctx);
+                stop_confirmed_stream(&uuid);
+                moved_predicate_health.forget(&uuid);
                 moved_prometheus.metrics_deregister_predicate();
             }
             ObserverEvent::BitcoinPredicateTriggered(data) => {
+                // Occurrences of predicates delivered by ordhook are accounted once delivered.
+                if is_delivered_by_ordhook(&data.chainhook.uuid) {
+                    queue_live_delivery(data);
+                    continue;
+                }
                 if let Some(ref tip) = data.apply.last() {
+                    let events_delivered: usize = data
+                        .apply
+                        .iter()
+                        .map(|apply| apply.block.transactions.len())
+                        .sum();
                     let observers_db_conn =
                         match open_readwrite_observers_db_conn(&moved_config, &moved_ctx) {
                             Ok(con) => con,
@@ -136,7 +313,20 @@ pub async fn start_observers_http_server(
                         last_block_height_update,
                         &observers_db_conn,
                         &moved_ctx,
-                    )
+                    );
+                    // Deliveries made by the observer at the tip are accounted to the owner of the predicate.
+                    if let Some((ChainhookSpecification::Bitcoin(spec), _)) =
+                        find_observer_with_uuid(
+                            &data.chainhook.uuid,
+                            &observers_db_conn,
+                            &moved_ctx,
+                        )
+                    {
+                        record_events_delivered(
+                            spec.owner_uuid.as_deref(),
+                            events_delivered as u64,
+                        );
+                    }
                 }
             }
             _ => {}
@@ -149,6 +339,9 @@ pub async fn start_observers_http_server(
 async fn build_server(
     config: &Config,
     observer_command_tx: &std::sync::mpsc::Sender<ObserverCommand>,
+    prometheus: &PrometheusMonitoring,
+    log_levels: Option<LogLevels>,
+    predicate_health: &PredicateHealthRegistry,
     ctx: &Context,
 ) -> Rocket<Ignite> {
     let PredicatesApi::On(ref api_config) = config.http_api else {
@@ -156,9 +349,29 @@ async fn build_server(
     };
     try_info!(
         ctx,
-        "Listening on port {} for chainhook predicate registrations",
-        api_config.http_port
+        "Listening on {} for chainhook predicate registrations",
+        SocketAddr::new(api_config.http_address, api_config.http_port)
     );
+    // Rocket is not able to listen on a unix socket: connections are forwarded to the TCP listener.
+    if let Some(ref socket) = api_config.unix_socket {
+        let target = SocketAddr::new(
+            match api_config.http_address {
+                IpAddr::V4(addr) if addr.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(addr) if addr.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                addr => addr,
+            },
+            api_config.http_port,
+        );
+        let moved_socket = socket.clone();
+        let moved_ctx = ctx.clone();
+        let _ = hiro_system_kit::thread_named("observers_api-unix_socket").spawn(move || {
+            hiro_system_kit::nestable_block_on(forward_unix_socket_to_tcp(
+                moved_socket,
+                target,
+                moved_ctx,
+            ))
+        });
+    }
     let moved_config = config.clone();
     let moved_ctx = ctx.clone();
     let moved_observer_commands_tx = observer_command_tx.clone();
@@ -169,7 +382,7 @@ async fn build_server(
     let control_config = RocketConfig {
         port: api_config.http_port,
         workers: 1,
-        address: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+        address: api_config.http_address,
         keep_alive: 5,
         temp_dir: std::env::temp_dir().into(),
         log_level: LogLevel::Off,
@@ -179,10 +392,58 @@ async fn build_server(
     };
     let routes = routes![
         handle_ping,
+        handle_get_versions,
+        handle_get_schemas,
+        handle_get_schema,
         handle_get_predicates,
         handle_get_predicate,
         handle_create_predicate,
         handle_delete_bitcoin_predicate,
+        handle_pause_predicate,
+        handle_resume_predicate,
+        handle_create_backup,
+        handle_get_status,
+        handle_set_log_level,
+        handle_get_blocklist,
+        handle_add_blocklist_entries,
+        handle_delete_blocklist_entry,
+        handle_create_rescan,
+        handle_get_jobs,
+        handle_get_job,
+        handle_cancel_job,
+        handle_get_usage,
+        handle_get_brc20_token,
+        handle_get_brc20_token_holders,
+        handle_get_brc20_balances,
+        handle_get_sns_name,
+        handle_get_sns_name_availability,
+        handle_get_rune,
+        handle_get_address_runes,
+        handle_get_address_rune_utxos,
+        handle_get_block_events_hash,
+        handle_get_latest_index_commitment,
+        handle_get_index_commitment_proof,
+        handle_get_inscription,
+        handle_get_inscriptions_batch,
+        handle_get_ordinals_diff,
+        handle_get_inscription_contents_archive,
+        handle_get_inscription_content,
+        handle_get_inscription_content_preview,
+        handle_get_sat,
+        handle_get_sats_batch,
+        handle_get_satribute_inscriptions,
+        handle_get_inscription_sales,
+        handle_get_sales,
+        handle_get_address_sat_ranges,
+        handle_get_address_rare_sats,
+        handle_get_address_utxos,
+        handle_annotate_utxos,
+        handle_get_outputs_batch,
+        handle_get_wallet,
+        handle_get_wallet_utxos,
+        handle_annotate_psbt,
+        handle_quota_exceeded,
+        handle_api_key_required,
     ];
     let background_job_tx_mutex = Arc::new(Mutex::new(moved_observer_commands_tx));
 
@@ -190,7 +451,16 @@ async fn build_server(
         .manage(background_job_tx_mutex)
         .manage(moved_config)
         .manage(moved_ctx.clone())
+        .manage(prometheus.clone())
+        .manage(log_levels)
+        .manage(predicate_health.clone())
         .mount("/", routes)
+        .register(
+            "/",
+            catchers![handle_unauthorized, handle_too_many_requests],
+        )
+        .attach(QueryTimeouts)
+        .attach(UsageAccounting)
         .ignite()
         .await
         .expect("Unable to build observers API");
@@ -206,202 +476,3237 @@ fn handle_ping(ctx: &State<Context>) -> Json<Value> {
     }))
 }
 
-#[get("/v1/observers", format = "application/json")]
-fn handle_get_predicates(
+/// Scope of the caller, resolved from the API key sent as `Authorization: Bearer <key>` or `X-API-Key`.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TenantScope {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Some(config) = request.rocket().state::<Config>() else {
+            return request::Outcome::Error((
+                Status::InternalServerError,
+                "config not available".into(),
+            ));
+        };
+        // Daily API quotas are enforced by `UsageAccounting`, before any route is matched.
+        match TenantScope::resolve(config, request_api_key(request)) {
+            Ok(scope) => request::Outcome::Success(scope),
+            Err(e) => request::Outcome::Error((Status::Unauthorized, e)),
+        }
+    }
+}
+
+fn request_api_key<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(request.headers().get_one("X-API-Key"))
+}
+
+#[catch(401)]
+fn handle_unauthorized() -> Json<Value> {
+    Json(json!({
+        "status": 401,
+        "error": "A valid API key is required",
+    }))
+}
+
+#[catch(429)]
+fn handle_too_many_requests() -> Json<Value> {
+    Json(json!({
+        "status": 429,
+        "error": "Daily API quota exceeded",
+    }))
+}
+
+/// Delay suggested to the callers of a request which timed out before retrying it.
+const QUERY_TIMEOUT_RETRY_AFTER_SECS: u64 = 5;
+
+/// Raised when the statements of a request are interrupted by its `QueryDeadline`.
+struct QueryTimedOut(Arc<AtomicBool>);
+
+fn request_query_timed_out<'r>(request: &'r Request<'_>) -> &'r QueryTimedOut {
+    request.local_cache(|| QueryTimedOut(Arc::new(AtomicBool::new(false))))
+}
+
+/// Deadline of the database statements of a read request, `query_timeout_ms` after it is received.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for QueryDeadline {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let timeout_ms = request
+            .rocket()
+            .state::<Config>()
+            .map(|config| config.query_timeout_ms())
+            .unwrap_or(DEFAULT_QUERY_TIMEOUT_MS);
+        request::Outcome::Success(QueryDeadline::with_flag(
+            Duration::from_millis(timeout_ms),
+            request_query_timed_out(request).0.clone(),
+        ))
+    }
+}
+
+/// Replaces the response of a request which statements were interrupted, built from partial results, by a 503.
+struct QueryTimeouts;
+
+#[rocket::async_trait]
+impl Fairing for QueryTimeouts {
+    fn info(&self) -> Info {
+        Info {
+            name: "Query timeouts",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !request_query_timed_out(request).0.load(Ordering::SeqCst) {
+            return;
+        }
+        let body = json!({
+            "status": 503,
+            "error": "Query timed out",
+            "retry_after_secs": QUERY_TIMEOUT_RETRY_AFTER_SECS,
+        })
+        .to_string();
+        response.set_status(Status::ServiceUnavailable);
+        response.set_header(ContentType::JSON);
+        response.set_raw_header("Retry-After", QUERY_TIMEOUT_RETRY_AFTER_SECS.to_string());
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
+
+/// Route the requests of the tenants past their daily API quota are sent to by `UsageAccounting`.
+#[get("/ordhook/v1/usage/quota_exceeded")]
+fn handle_quota_exceeded() -> Status {
+    Status::TooManyRequests
+}
+
+/// Route the requests without a valid API key are sent to by `UsageAccounting` when tenants are configured.
+#[get("/ordhook/v1/usage/api_key_required")]
+fn handle_api_key_required() -> Status {
+    Status::Unauthorized
+}
+
+/// Accounts every API call, and the size of its response, to the tenant of the caller. Requests of tenants past
+/// their daily API quota are answered with a 429, whatever the route, without their handler running. When tenants
+/// are configured, requests without a valid API key are answered with a 401, except `/ping`, so that quotas can't
+/// be bypassed by dropping the key.
+struct UsageAccounting;
+
+#[rocket::async_trait]
+impl Fairing for UsageAccounting {
+    fn info(&self) -> Info {
+        Info {
+            name: "Usage accounting",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let Some(config) = request.rocket().state::<Config>() else {
+            return;
+        };
+        if request.uri().path().as_str() == "/ping" {
+            return;
+        }
+        let Ok(scope) = TenantScope::resolve(config, request_api_key(request)) else {
+            request.set_method(Method::Get);
+            request.set_uri(uri!(handle_api_key_required));
+            return;
+        };
+        if check_api_calls_quota(scope.tenant.as_deref()).is_err() {
+            request.set_method(Method::Get);
+            request.set_uri(uri!(handle_quota_exceeded));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(config) = request.rocket().state::<Config>() else {
+            return;
+        };
+        // Rejected keyless requests are not billed to anyone.
+        let Ok(scope) = TenantScope::resolve(config, request_api_key(request)) else {
+            return;
+        };
+        // Streamed responses have no size known upfront, and only count as a call.
+        let bytes_served = response.body().preset_size().unwrap_or(0);
+        record_api_call(scope.tenant.as_deref(), bytes_served as u64);
+    }
+}
+
+/// Control endpoints affecting the whole instance are reserved to admin tenants.
+fn require_admin(scope: &TenantScope) -> Result<(), Custom<Json<Value>>> {
+    if scope.admin {
+        return Ok(());
+    }
+    Err(Custom(
+        Status::Forbidden,
+        Json(json!({
+            "status": 403,
+            "error": "An admin API key is required",
+        })),
+    ))
+}
+
+/// Decodes the `cursor` of a list request. Cursors are opaque and only valid for the list which returned them.
+fn parse_page_cursor<K: serde::de::DeserializeOwned>(
+    cursor: Option<String>,
+) -> Result<Option<K>, Custom<Json<Value>>> {
+    match cursor {
+        Some(cursor) => decode_page_cursor(&cursor).map(Some).map_err(|e| {
+            Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+#[get("/v1/observers", format = "application/json")]
+fn handle_get_predicates(
+    scope: TenantScope,
+    config: &State<Config>,
+    predicate_health: &State<PredicateHealthRegistry>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /v1/observers");
+    match open_readonly_observers_db_conn(config, ctx) {
+        Ok(mut db_conn) => {
+            let observers = find_all_observers(&mut db_conn, &ctx);
+            let serialized_predicates = observers
+                .iter()
+                .filter(|(p, _)| scope.can_access_predicate(p))
+                .map(|(p, s)| {
+                    serialized_predicate_with_status(p, s, predicate_health, &db_conn, ctx)
+                })
+                .collect::<Vec<_>>();
+            Ok(Json(json!({
+                "status": 200,
+                "result": serialized_predicates
+            })))
+        }
+        Err(e) => Err(Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "message": e,
+            })),
+        )),
+    }
+}
+
+/// Predicate specification and payload schema versions supported by this release.
+#[get("/v1/versions", format = "application/json")]
+fn handle_get_versions(ctx: &State<Context>) -> Json<Value> {
+    try_info!(ctx, "Handling HTTP GET /v1/versions");
+    Json(json!({
+        "status": 200,
+        "result": get_supported_versions(),
+    }))
+}
+
+/// Payload schemas served for each supported payload version.
+#[get("/ordhook/v1/schemas", format = "application/json")]
+fn handle_get_schemas(ctx: &State<Context>) -> Json<Value> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/schemas");
+    Json(json!({
+        "status": 200,
+        "result": get_payload_schemas_index(),
+    }))
+}
+
+/// JSON Schema of a payload. The schema is returned as is, so that it can be fed to validators and code generators.
+#[get("/ordhook/v1/schemas/<version>/<name>", format = "application/json")]
+fn handle_get_schema(
+    version: u64,
+    name: String,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/schemas/{}/{}",
+        version,
+        name
+    );
+    get_payload_schema(version, &name).map(Json).ok_or(Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": format!("no schema {name} for payload version {version}"),
+        })),
+    ))
+}
+
+#[post("/v1/observers", format = "application/json", data = "<predicate>")]
+fn handle_create_predicate(
+    predicate: Json<Value>,
+    scope: TenantScope,
+    config: &State<Config>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /v1/observers");
+    let mut predicate = predicate.into_inner();
+    let payload_version = match upgrade_predicate_specification(&mut predicate) {
+        Ok(versions) => {
+            if versions.version != PREDICATE_SPEC_VERSION {
+                try_info!(
+                    ctx,
+                    "Predicate upgraded from version {} to {PREDICATE_SPEC_VERSION}",
+                    versions.version
+                );
+            }
+            versions.payload_version
+        }
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    scope.claim_predicate(&mut predicate);
+    let script = match extract_predicate_script(&mut predicate, &config.network.bitcoin_network) {
+        Ok(script) => script,
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    let min_confirmations = match extract_predicate_min_confirmations(&mut predicate) {
+        Ok(min_confirmations) => min_confirmations,
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    let enrichment = match extract_predicate_enrichment(&mut predicate) {
+        Ok(enrichment) => enrichment,
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    // Enrichments are computed by ordhook, which only delivers the blocks of confirmed streams itself.
+    if enrichment.is_some() && min_confirmations.is_none() {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": "enrich requires min_confirmations to be set",
+            })),
+        ));
+    }
+    let brc20_filter = match extract_brc20_predicate_filter(&mut predicate) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    let wallet_filter = match extract_wallet_predicate_filter(&mut predicate) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            ));
+        }
+    };
+    let mut predicate = match serde_json::from_value::<ChainhookFullSpecification>(predicate) {
+        Ok(predicate) => predicate,
+        Err(_) => {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": "Invalid predicate JSON",
+                })),
+            ));
+        }
+    };
+    if brc20_filter.is_some() {
+        enable_brc20_meta_protocol(&mut predicate);
+    }
+    if let Err(e) = predicate.validate() {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        ));
+    }
+    let mut predicates_db_conn = match open_readonly_observers_db_conn(config, ctx) {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(json!({
+                    "status": 500,
+                    "error": err.to_string(),
+                })),
+            ));
+        }
+    };
+    let predicate_uuid = predicate.get_uuid().to_string();
+    if find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx).is_some() {
+        return Err(status::Custom(
+            Status::Conflict,
+            Json(json!({
+                "status": 409,
+                "error": "Predicate uuid already in use",
+            })),
+        ));
+    }
+    let wallet_filter = match wallet_filter {
+        Some(filter) => match watch_wallet(&predicate_uuid, filter, config) {
+            Ok(filter) => Some(filter),
+            Err(e) => {
+                return Err(Custom(
+                    Status::UnprocessableEntity,
+                    Json(json!({
+                        "status": 422,
+                        "error": e,
+                    })),
+                ));
+            }
+        },
+        None => None,
+    };
+    let observers_db_conn = match open_readwrite_observers_db_conn(config, ctx) {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(json!({
+                    "status": 500,
+                    "error": err.to_string(),
+                })),
+            ));
+        }
+    };
+    insert_predicate_payload_version_in_observers(
+        &predicate_uuid,
+        payload_version,
+        &observers_db_conn,
+        ctx,
+    );
+    if let Some(filter) = brc20_filter {
+        insert_brc20_filter_in_observers(&predicate_uuid, &filter, &observers_db_conn, ctx);
+        set_brc20_predicate_filter(&predicate_uuid, Some(filter));
+    }
+    if let Some(filter) = wallet_filter {
+        insert_wallet_filter_in_observers(&predicate_uuid, &filter, &observers_db_conn, ctx);
+    }
+    if let Some(script) = script {
+        insert_predicate_script_in_observers(&predicate_uuid, &script, &observers_db_conn, ctx);
+        let _ = set_predicate_script(&predicate_uuid, Some(&script));
+    }
+    if let Some(min_confirmations) = min_confirmations {
+        insert_predicate_min_confirmations_in_observers(
+            &predicate_uuid,
+            min_confirmations,
+            &observers_db_conn,
+            ctx,
+        );
+        set_predicate_min_confirmations(&predicate_uuid, Some(min_confirmations));
+    }
+    if let Some(fields) = enrichment {
+        insert_predicate_enrichment_in_observers(&predicate_uuid, &fields, &observers_db_conn, ctx);
+    }
+    if let ChainhookFullSpecification::Bitcoin(ref mut spec) = predicate {
+        if let Some(network) = spec.networks.get_mut(&config.network.bitcoin_network) {
+            network.action = get_observer_hook_action(&predicate_uuid, network.action.clone());
+        }
+    }
+    match background_job_tx.inner().lock() {
+        Ok(tx) => {
+            let _ = tx.send(ObserverCommand::RegisterPredicate(predicate));
+        }
+        Err(err) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(json!({
+                    "status": 500,
+                    "error": err.to_string(),
+                })),
+            ));
+        }
+    };
+    Ok(Json(json!({
+        "status": 200,
+        "result": predicate_uuid,
+    })))
+}
+
+#[get("/v1/observers/<predicate_uuid>", format = "application/json")]
+fn handle_get_predicate(
+    predicate_uuid: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /v1/observers/{}", predicate_uuid);
+    match open_readonly_observers_db_conn(config, ctx) {
+        Ok(mut predicates_db_conn) => {
+            let entry =
+                match find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx)
+                    .filter(|(spec, _)| scope.can_access_predicate(spec))
+                {
+                    Some((ChainhookSpecification::Bitcoin(spec), report)) => json!({
+                        "chain": "bitcoin",
+                        "uuid": spec.uuid,
+                        "network": spec.network,
+                        "predicate": spec.predicate,
+                        "status": report,
+                        "enabled": spec.enabled,
+                        "payload_version": find_predicate_payload_version(
+                            &spec.uuid,
+                            &predicates_db_conn,
+                            ctx,
+                        )
+                        .unwrap_or(PAYLOAD_SCHEMA_VERSION),
+                    }),
+                    _ => {
+                        return Err(Custom(
+                            Status::NotFound,
+                            Json(json!({
+                                "status": 404,
+                            })),
+                        ))
+                    }
+                };
+            Ok(Json(json!({
+                "status": 200,
+                "result": entry
+            })))
+        }
+        Err(e) => Err(Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "message": e,
+            })),
+        )),
+    }
+}
+
+#[delete("/v1/observers/<predicate_uuid>", format = "application/json")]
+fn handle_delete_bitcoin_predicate(
+    predicate_uuid: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP DELETE /v1/observers/{}", predicate_uuid);
+    let mut predicates_db_conn = match open_readwrite_observers_db_conn(config, ctx) {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(json!({
+                    "status": 500,
+                    "error": err.to_string(),
+                })),
+            ));
+        }
+    };
+    if find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx)
+        .filter(|(spec, _)| scope.can_access_predicate(spec))
+        .is_none()
+    {
+        return Err(status::Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": "Predicate not found",
+            })),
+        ));
+    }
+    // Paused predicates are kept in the observers db when deregistered.
+    remove_predicate_pause_from_observers(&predicate_uuid, &predicates_db_conn, ctx);
+    match background_job_tx.inner().lock() {
+        Ok(tx) => {
+            let _ = tx.send(ObserverCommand::DeregisterBitcoinPredicate(predicate_uuid));
+        }
+        Err(err) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                Json(json!({
+                    "status": 500,
+                    "error": err.to_string(),
+                })),
+            ));
+        }
+    };
+
+    Ok(Json(json!({
+        "status": 200,
+        "result": "Predicate deleted",
+    })))
+}
+
+#[post("/v1/observers/<predicate_uuid>/pause")]
+fn handle_pause_predicate(
+    predicate_uuid: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    predicate_health: &State<PredicateHealthRegistry>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP POST /v1/observers/{}/pause",
+        predicate_uuid
+    );
+    toggle_predicate_pause(
+        &predicate_uuid,
+        true,
+        &scope,
+        config,
+        predicate_health,
+        background_job_tx,
+        ctx,
+    )?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": "Predicate paused",
+    })))
+}
+
+#[post("/v1/observers/<predicate_uuid>/resume")]
+fn handle_resume_predicate(
+    predicate_uuid: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    predicate_health: &State<PredicateHealthRegistry>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP POST /v1/observers/{}/resume",
+        predicate_uuid
+    );
+    toggle_predicate_pause(
+        &predicate_uuid,
+        false,
+        &scope,
+        config,
+        predicate_health,
+        background_job_tx,
+        ctx,
+    )?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": "Predicate resumed",
+    })))
+}
+
+fn toggle_predicate_pause(
+    predicate_uuid: &str,
+    pause: bool,
+    scope: &TenantScope,
+    config: &Config,
+    predicate_health: &PredicateHealthRegistry,
+    background_job_tx: &Arc<Mutex<Sender<ObserverCommand>>>,
+    ctx: &Context,
+) -> Result<(), Custom<Json<Value>>> {
+    let internal_error = |err: String| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": err,
+            })),
+        )
+    };
+    let mut predicates_db_conn =
+        open_readonly_observers_db_conn(config, ctx).map_err(|e| internal_error(e.to_string()))?;
+    if find_observer_with_uuid(predicate_uuid, &mut predicates_db_conn, ctx)
+        .filter(|(spec, _)| scope.can_access_predicate(spec))
+        .is_none()
+    {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": "Predicate not found",
+            })),
+        ));
+    }
+    if find_predicate_paused_at(predicate_uuid, &predicates_db_conn, ctx).is_some() == pause {
+        return Err(Custom(
+            Status::Conflict,
+            Json(json!({
+                "status": 409,
+                "error": if pause { "Predicate already paused" } else { "Predicate not paused" },
+            })),
+        ));
+    }
+    let tx = background_job_tx
+        .lock()
+        .map_err(|e| internal_error(e.to_string()))?;
+    if pause {
+        pause_predicate(predicate_uuid, predicate_health, &tx, config, ctx).map_err(internal_error)
+    } else {
+        resume_predicate(predicate_uuid, predicate_health, &tx, config, ctx).map_err(internal_error)
+    }
+}
+
+#[post(
+    "/ordhook/v1/control/backup",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_create_backup(
+    payload: Option<Json<Value>>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/control/backup");
+    require_admin(&scope)?;
+    if is_backup_in_progress() {
+        return Err(Custom(
+            Status::Conflict,
+            Json(json!({
+                "status": 409,
+                "error": "Backup already in progress",
+            })),
+        ));
+    }
+    let destination = match payload
+        .as_ref()
+        .and_then(|p| p.get("destination"))
+        .and_then(|d| d.as_str())
+    {
+        Some(destination) => PathBuf::from(destination),
+        None => get_default_backup_path(config),
+    };
+    // Backups can take a while on large dbs, run them as a job and let clients poll its status.
+    let moved_config = config.inner().clone();
+    let moved_ctx = ctx.inner().clone();
+    let moved_destination = destination.clone();
+    let mut params = json!({
+        "destination": destination.display().to_string(),
+    });
+    scope.claim_job_params(&mut params);
+    let job = submit_job(
+        JobKind::Backup,
+        params,
+        1,
+        Box::new(move |control: &JobControl| {
+            backup_all_dbs(&moved_config, &moved_destination, &moved_ctx)?;
+            control.add_completed_units(1);
+            Ok(())
+        }),
+        config,
+        ctx,
+    )
+    .map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )
+    })?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "destination": destination.display().to_string(),
+            "job": job,
+        },
+    })))
+}
+
+/// Sync height and lag, predicates, jobs and resource usage of the node, as printed by `ordhook service status`.
+#[get("/ordhook/v1/control/status", format = "application/json")]
+fn handle_get_status(
+    scope: TenantScope,
+    config: &State<Config>,
+    prometheus: &State<PrometheusMonitoring>,
+    predicate_health: &State<PredicateHealthRegistry>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/control/status");
+    require_admin(&scope)?;
+    match get_service_status(config, prometheus, predicate_health, ctx) {
+        Ok(status) => Ok(Json(json!({
+            "status": 200,
+            "result": status,
+        }))),
+        Err(e) => Err(Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )),
+    }
+}
+
+/// Adjusts the verbosity of a subsystem (`{"subsystem": "pipeline", "level": "debug"}`), or of every subsystem when
+/// `subsystem` is omitted, without restarting the node.
+#[put(
+    "/ordhook/v1/control/log_level",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_set_log_level(
+    payload: Json<Value>,
+    scope: TenantScope,
+    log_levels: &State<Option<LogLevels>>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP PUT /ordhook/v1/control/log_level");
+    require_admin(&scope)?;
+    let Some(log_levels) = log_levels.inner() else {
+        return Err(Custom(
+            Status::Conflict,
+            Json(json!({
+                "status": 409,
+                "error": "Log levels of this node can't be adjusted at runtime",
+            })),
+        ));
+    };
+    let subsystem = payload
+        .get("subsystem")
+        .and_then(|s| s.as_str())
+        .map(LogSubsystem::parse)
+        .transpose();
+    let level = payload
+        .get("level")
+        .and_then(|l| l.as_str())
+        .ok_or("level must be one of error, warn, info, debug or trace".to_string())
+        .and_then(parse_log_level);
+    let (subsystem, level) = subsystem.and_then(|s| Ok((s, level?))).map_err(|e| {
+        Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        )
+    })?;
+    log_levels.set(subsystem, level).map_err(|e| {
+        Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        )
+    })?;
+    try_warn!(
+        ctx,
+        "Log level of {} set to {}",
+        subsystem.map(|s| s.code()).unwrap_or("every subsystem"),
+        log_level_code(level)
+    );
+    Ok(Json(json!({
+        "status": 200,
+        "result": log_levels.to_json(),
+    })))
+}
+
+#[get("/ordhook/v1/control/blocklist", format = "application/json")]
+fn handle_get_blocklist(
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/control/blocklist");
+    require_admin(&scope)?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": get_blocklist_entries(config, ctx),
+    })))
+}
+
+/// Adds inscription ids and content hashes (`{"entries": [..], "reason": ".."}`) to the blocklist.
+#[post(
+    "/ordhook/v1/control/blocklist",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_add_blocklist_entries(
+    payload: Json<Value>,
+    remote: Option<SocketAddr>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/control/blocklist");
+    require_admin(&scope)?;
+    let entries = payload
+        .get("entries")
+        .and_then(|entries| entries.as_array())
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|entry| {
+                    entry
+                        .as_str()
+                        .ok_or(format!("invalid entry {entry}"))
+                        .and_then(parse_blocklist_entry)
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or(Err(
+            "entries must list inscription ids or content hashes".into()
+        ))
+        .map_err(|e| {
+            Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": e,
+                })),
+            )
+        })?;
+    let reason = payload
+        .get("reason")
+        .and_then(|reason| reason.as_str())
+        .map(|reason| reason.to_string());
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )
+    })?;
+    let added_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut added = vec![];
+    for entry in entries.into_iter() {
+        let entry = BlocklistEntry {
+            entry,
+            reason: reason.clone(),
+            added_at,
+            source: BLOCKLIST_SOURCE_API.into(),
+        };
+        insert_blocklist_entry_in_observers(&entry, &observers_db_conn, ctx);
+        try_warn!(
+            ctx,
+            "Blocklist: {} added by {} (reason {})",
+            entry.entry,
+            remote
+                .map(|r| r.to_string())
+                .unwrap_or("unknown client".into()),
+            entry.reason.as_deref().unwrap_or("none")
+        );
+        added.push(entry);
+    }
+    clear_query_cache();
+    Ok(Json(json!({
+        "status": 200,
+        "result": added,
+    })))
+}
+
+#[delete("/ordhook/v1/control/blocklist/<entry>", format = "application/json")]
+fn handle_delete_blocklist_entry(
+    entry: String,
+    remote: Option<SocketAddr>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP DELETE /ordhook/v1/control/blocklist/{}",
+        entry
+    );
+    require_admin(&scope)?;
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )
+    })?;
+    let entry = parse_blocklist_entry(&entry).unwrap_or(entry);
+    if find_blocklist_entry(&entry, &observers_db_conn, ctx).is_none() {
+        let listed_in_file = get_blocklist_entries(config, ctx)
+            .iter()
+            .any(|e| e.entry == entry);
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": match listed_in_file {
+                    true => format!("{entry} is listed in the blocklist file, edit it to remove the entry"),
+                    false => format!("{entry} is not blocklisted"),
+                },
+            })),
+        ));
+    }
+    remove_blocklist_entry_from_observers(&entry, &observers_db_conn, ctx);
+    clear_query_cache();
+    try_warn!(
+        ctx,
+        "Blocklist: {entry} removed by {}",
+        remote
+            .map(|r| r.to_string())
+            .unwrap_or("unknown client".into())
+    );
+    Ok(Json(json!({
+        "status": 200,
+        "result": "Blocklist entry deleted",
+    })))
+}
+
+/// Queues a historical rescan of a block range (`{"start": .., "end": .., "predicates": [..]}`) for registered
+/// predicates, without affecting the other observers.
+#[post(
+    "/ordhook/v1/control/rescan",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_create_rescan(
+    payload: Json<Value>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/control/rescan");
+    let unprocessable = |e: String| {
+        Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        )
+    };
+    let start = payload
+        .get("start")
+        .and_then(|start| start.as_u64())
+        .ok_or(unprocessable("start must be a block height".into()))?;
+    let end = payload
+        .get("end")
+        .and_then(|end| end.as_u64())
+        .ok_or(unprocessable("end must be a block height".into()))?;
+    let predicates = payload
+        .get("predicates")
+        .and_then(|predicates| predicates.as_array())
+        .and_then(|predicates| {
+            predicates
+                .iter()
+                .map(|uuid| uuid.as_str().map(|uuid| uuid.to_string()))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or(unprocessable(
+            "predicates must list the uuids of the predicates to rescan".into(),
+        ))?;
+    let job = queue_rescan(start, end, predicates, &scope, config, ctx).map_err(unprocessable)?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": job,
+    })))
+}
+
+/// Background jobs, most recent first, optionally filtered by kind (`rescan`, `backup`, `predicate_scan`) and status.
+#[get("/ordhook/v1/jobs?<kind>&<status>", format = "application/json")]
+fn handle_get_jobs(
+    kind: Option<String>,
+    status: Option<String>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/jobs");
+    let unprocessable = |e: String| {
+        Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        )
+    };
+    let kind = match kind {
+        Some(kind) => Some(
+            JobKind::from_str(&kind).ok_or(unprocessable(format!("unknown job kind {kind}")))?,
+        ),
+        None => None,
+    };
+    let status = match status {
+        Some(status) => Some(
+            JobStatus::from_str(&status)
+                .ok_or(unprocessable(format!("unknown job status {status}")))?,
+        ),
+        None => None,
+    };
+    let jobs = get_jobs(kind, status, config, ctx).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )
+    })?;
+    let jobs: Vec<_> = jobs
+        .into_iter()
+        .filter(|job| scope.can_access_job(job))
+        .collect();
+    Ok(Json(json!({
+        "status": 200,
+        "result": jobs,
+    })))
+}
+
+#[get("/ordhook/v1/jobs/<job_id>", format = "application/json")]
+fn handle_get_job(
+    job_id: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/jobs/{}", job_id);
+    match get_job(&job_id, config, ctx).map(|job| job.filter(|job| scope.can_access_job(job))) {
+        Ok(Some(job)) => Ok(Json(json!({
+            "status": 200,
+            "result": job,
+        }))),
+        Ok(None) => Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": "Job not found",
+            })),
+        )),
+        Err(e) => Err(Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )),
+    }
+}
+
+#[delete("/ordhook/v1/jobs/<job_id>", format = "application/json")]
+fn handle_cancel_job(
+    job_id: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP DELETE /ordhook/v1/jobs/{}", job_id);
+    if let Ok(None) =
+        get_job(&job_id, config, ctx).map(|job| job.filter(|job| scope.can_access_job(job)))
+    {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": "Job not found",
+            })),
+        ));
+    }
+    match cancel_job(&job_id, config, ctx) {
+        Ok(job) => Ok(Json(json!({
+            "status": 200,
+            "result": job,
+        }))),
+        Err(e) => Err(Custom(
+            Status::Conflict,
+            Json(json!({
+                "status": 409,
+                "error": e,
+            })),
+        )),
+    }
+}
+
+/// Usage of the tenants between the days `from` and `to` (`YYYY-MM-DD`, UTC, inclusive), as JSON or CSV
+/// (`format=csv`). Tenants only see their own usage.
+#[get("/ordhook/v1/usage?<from>&<to>&<format>")]
+fn handle_get_usage(
+    from: String,
+    to: String,
+    format: Option<String>,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<(ContentType, String), Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/usage");
+    let unprocessable = |e: String| {
+        Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": e,
+            })),
+        )
+    };
+    validate_usage_day(&from).map_err(unprocessable)?;
+    validate_usage_day(&to).map_err(unprocessable)?;
+    let tenant = match scope.admin {
+        true => None,
+        false => Some(scope.tenant.clone().unwrap_or(DEFAULT_TENANT.to_string())),
+    };
+    let report = get_usage_report(&from, &to, tenant.as_deref(), config, ctx).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            Json(json!({
+                "status": 500,
+                "error": e,
+            })),
+        )
+    })?;
+    match format.as_deref() {
+        Some("csv") => Ok((ContentType::CSV, usage_report_to_csv(&report))),
+        None | Some("json") => Ok((
+            ContentType::JSON,
+            json!({
+                "status": 200,
+                "result": report,
+            })
+            .to_string(),
+        )),
+        Some(format) => Err(unprocessable(format!("unknown format {format}"))),
+    }
+}
+
+/// Default and maximum page sizes for paginated BRC-20 endpoints.
+const BRC20_DEFAULT_PAGE_LIMIT: u64 = 20;
+const BRC20_MAX_PAGE_LIMIT: u64 = 60;
+
+#[get("/ordhook/v1/brc-20/tokens/<ticker>", format = "application/json")]
+fn handle_get_brc20_token(
+    ticker: String,
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/brc-20/tokens/{}",
+        ticker
+    );
+    let db_conn = open_readonly_brc20_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let tick = ticker.to_lowercase();
+    let Some(token) = get_token(&tick, &db_conn, ctx) else {
+        return read_through_miss(origin, config, ctx).ok_or(brc20_token_not_found(&ticker));
+    };
+    let minted_supply = get_token_minted_supply(&tick, &db_conn, ctx).unwrap_or(0.0);
+    let holders = get_token_holders_count(&tick, None, &db_conn, ctx);
+    let result = json!({
+            "ticker": token.display_tick,
+            "inscription_id": token.inscription_id,
+            "inscription_number": token.inscription_number,
+            "block_height": token.block_height,
+            "max_supply": token.max,
+            "mint_limit": token.lim,
+            "decimals": token.dec,
+            "deployer": token.address,
+            "self_mint": token.self_mint,
+        "minted_supply": minted_supply,
+        "holders": holders,
+    });
+    validate_read_through_result(
+        &origin.to_string(),
+        &result,
+        &[
+            "inscription_id",
+            "block_height",
+            "max_supply",
+            "mint_limit",
+            "decimals",
+            "deployer",
+        ],
+        ctx,
+    );
+    Ok(Json(json!({
+        "status": 200,
+        "result": result,
+    })))
+}
+
+#[get(
+    "/ordhook/v1/brc-20/tokens/<ticker>/holders?<cursor>&<offset>&<limit>&<at_height>",
+    format = "application/json"
+)]
+fn handle_get_brc20_token_holders(
+    ticker: String,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    at_height: Option<u64>,
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/brc-20/tokens/{}/holders",
+        ticker
+    );
+    let db_conn = open_readonly_brc20_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let tick = ticker.to_lowercase();
+    if get_token(&tick, &db_conn, ctx).is_none() {
+        return read_through_miss(origin, config, ctx).ok_or(brc20_token_not_found(&ticker));
+    }
+    let cursor = parse_page_cursor::<(u64, f64, String)>(cursor)?;
+    // Balances move with every block: pages are all served as of the height of the first one.
+    let at_height = match cursor {
+        Some((at_height, _, _)) => Some(at_height),
+        None => at_height.or(get_latest_ledger_block_height(&db_conn, ctx)),
+    };
+    let after = cursor.map(|(_, balance, address)| (balance, address));
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(BRC20_DEFAULT_PAGE_LIMIT)
+        .min(BRC20_MAX_PAGE_LIMIT);
+    let total = get_token_holders_count(&tick, at_height, &db_conn, ctx);
+    let holders = get_token_holders(
+        &tick,
+        at_height,
+        after.as_ref(),
+        offset,
+        limit + 1,
+        &db_conn,
+        ctx,
+    );
+    let (holders, next_cursor) = paginate(holders, limit, |holder| {
+        (
+            at_height.unwrap_or(0),
+            holder.avail_balance + holder.trans_balance,
+            holder.address.clone(),
+        )
+    });
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "at_height": at_height,
+            "total": total,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": holders.iter().map(serialized_brc20_balance).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+#[get(
+    "/ordhook/v1/brc-20/balances/<address>?<ticker>&<block_height>&<at_height>",
+    format = "application/json"
+)]
+fn handle_get_brc20_balances(
+    address: String,
+    ticker: Option<String>,
+    block_height: Option<u64>,
+    at_height: Option<u64>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/brc-20/balances/{}",
+        address
+    );
+    // `at_height` is the name used across point-in-time queries, `block_height` is kept for existing clients.
+    let block_height = at_height.or(block_height);
+    let db_conn = open_readonly_brc20_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let balances = get_address_balances_at_block_height(
+        &address,
+        ticker.as_deref(),
+        block_height,
+        &db_conn,
+        ctx,
+    );
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "address": address,
+            "block_height": block_height,
+            "results": balances.iter().map(serialized_brc20_balance).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+fn serialized_brc20_balance(balance: &Brc20DbBalanceRow) -> Value {
+    json!({
+        "ticker": balance.tick,
+        "address": balance.address,
+        "available_balance": balance.avail_balance,
+        "transferrable_balance": balance.trans_balance,
+        "overall_balance": balance.avail_balance + balance.trans_balance,
+    })
+}
+
+#[get("/ordhook/v1/sns/names/<name>?<proof>", format = "application/json")]
+fn handle_get_sns_name(
+    name: String,
+    proof: Option<bool>,
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/sns/names/{}", name);
+    let db_conn = open_readonly_sns_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(row) = normalize_sns_name(&name).and_then(|n| get_sns_name(&n, &db_conn, ctx)) else {
+        return read_through_miss(origin, config, ctx).ok_or(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Name {} not found", name),
+            })),
+        ));
+    };
+    let mut result = json!({
+        "name": row.name,
+        "inscription_id": row.inscription_id,
+        "inscription_number": row.inscription_number,
+        "block_height": row.block_height,
+        "address": row.address,
+    });
+    if proof.unwrap_or(false) {
+        result["proof"] = build_inscription_proof(&row.inscription_id, None, config, ctx)?;
+    }
+    validate_read_through_result(
+        &origin.to_string(),
+        &result,
+        &["inscription_id", "block_height"],
+        ctx,
+    );
+    Ok(Json(json!({
+        "status": 200,
+        "result": result,
+    })))
+}
+
+#[get(
+    "/ordhook/v1/sns/names/<name>/availability",
+    format = "application/json"
+)]
+fn handle_get_sns_name_availability(
+    name: String,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/sns/names/{}/availability",
+        name
+    );
+    let db_conn = open_readonly_sns_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let (name, valid, available) = match normalize_sns_name(&name) {
+        Some(normalized_name) => {
+            let available = get_sns_name(&normalized_name, &db_conn, ctx).is_none();
+            (normalized_name, true, available)
+        }
+        None => (name, false, false),
+    };
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "name": name,
+            "valid": valid,
+            "available": available,
+        },
+    })))
+}
+
+/// Looks a rune up by id (`840000:1`) or by name, with or without its spacers.
+fn find_rune(rune: &str, db_conn: &Connection, ctx: &Context) -> Option<RunesDbEntryRow> {
+    if let Ok(rune_id) = rune.parse::<RuneId>() {
+        return get_rune_entry(&rune_id, db_conn, ctx);
+    }
+    let spaced_rune = rune.to_uppercase().parse::<SpacedRune>().ok()?;
+    get_rune_entry_by_name(&spaced_rune.rune, db_conn, ctx)
+}
+
+fn rune_not_found(rune: &str) -> Custom<Json<Value>> {
+    Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": format!("Rune {} not found", rune),
+        })),
+    )
+}
+
+/// Rune amounts are `u128` and rendered as strings, JSON numbers not being able to represent them.
+#[get("/ordhook/v1/runes/<rune>", format = "application/json")]
+fn handle_get_rune(
+    rune: String,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/runes/{}", rune);
+    let db_conn = open_readonly_runes_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(entry) = find_rune(&rune, &db_conn, ctx) else {
+        return Err(rune_not_found(&rune));
+    };
+    let mints = count_rune_mints(&entry.id, &db_conn, ctx);
+    let minted = u128::from(mints).saturating_mul(
+        entry
+            .terms
+            .and_then(|terms| terms.amount)
+            .unwrap_or_default(),
+    );
+    let burned = get_rune_burned_amount(&entry.id, &db_conn, ctx);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "id": entry.id.to_string(),
+            "number": entry.number,
+            "name": entry.spaced_rune.rune.to_string(),
+            "spaced_name": entry.spaced_rune.to_string(),
+            "block_height": entry.block_height,
+            "tx_index": entry.tx_index,
+            "tx_id": entry.tx_id,
+            "divisibility": entry.divisibility,
+            "symbol": entry.symbol.map(|symbol| symbol.to_string()),
+            "premine": entry.premine.to_string(),
+            "terms": entry.terms.map(|terms| json!({
+                "amount": terms.amount.map(|amount| amount.to_string()),
+                "cap": terms.cap.map(|cap| cap.to_string()),
+                "height_start": terms.height.0,
+                "height_end": terms.height.1,
+                "offset_start": terms.offset.0,
+                "offset_end": terms.offset.1,
+            })),
+            "mint_start": entry.mint_start(),
+            "mint_end": entry.mint_end(),
+            "mints": mints,
+            "supply": entry.premine.saturating_add(minted).to_string(),
+            "burned": burned.to_string(),
+            "turbo": entry.turbo,
+            "cenotaph": entry.cenotaph,
+            "timestamp": entry.timestamp,
+        },
+    })))
+}
+
+/// Rune balances held by the unspent outputs of an address.
+#[get("/ordhook/v1/addresses/<address>/runes", format = "application/json")]
+fn handle_get_address_runes(
+    address: String,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/addresses/{}/runes",
+        address
+    );
+    let address = parse_address(&address, config)?;
+    let db_conn = open_readonly_runes_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let mut balances: BTreeMap<RuneId, (u128, u64)> = BTreeMap::new();
+    for row in find_unspent_rune_outpoints_of_address(&address, &db_conn, ctx) {
+        let balance = balances.entry(row.rune_id).or_default();
+        balance.0 = balance.0.saturating_add(row.amount);
+        balance.1 += 1;
+    }
+    let results = balances
+        .iter()
+        .map(|(rune_id, (amount, outputs))| {
+            let entry = get_rune_entry(rune_id, &db_conn, ctx);
+            json!({
+                "id": rune_id.to_string(),
+                "spaced_name": entry.as_ref().map(|entry| entry.spaced_rune.to_string()),
+                "divisibility": entry.as_ref().map(|entry| entry.divisibility),
+                "symbol": entry.as_ref().and_then(|entry| entry.symbol).map(|symbol| symbol.to_string()),
+                "amount": amount.to_string(),
+                "outputs": outputs,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "address": address,
+            "results": results,
+        },
+    })))
+}
+
+/// Unspent outputs of an address holding runes, with every rune they hold: spending one of them moves all its runes.
+/// `rune` narrows them down to the outputs holding a rune, given by id or name.
+#[get(
+    "/ordhook/v1/addresses/<address>/runes/utxos?<rune>",
+    format = "application/json"
+)]
+fn handle_get_address_rune_utxos(
+    address: String,
+    rune: Option<String>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/addresses/{}/runes/utxos",
+        address
+    );
+    let address = parse_address(&address, config)?;
+    let db_conn = open_readonly_runes_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let rune_id = match rune {
+        Some(rune) => match find_rune(&rune, &db_conn, ctx) {
+            Some(entry) => Some(entry.id),
+            None => return Err(rune_not_found(&rune)),
+        },
+        None => None,
+    };
+    let mut entries: BTreeMap<RuneId, Option<RunesDbEntryRow>> = BTreeMap::new();
+    let mut utxos: Vec<(String, u64, u64, Vec<Value>, bool)> = vec![];
+    for row in find_unspent_rune_outpoints_of_address(&address, &db_conn, ctx) {
+        let entry = entries
+            .entry(row.rune_id)
+            .or_insert_with(|| get_rune_entry(&row.rune_id, &db_conn, ctx));
+        let balance = json!({
+            "id": row.rune_id.to_string(),
+            "spaced_name": entry.as_ref().map(|entry| entry.spaced_rune.to_string()),
+            "divisibility": entry.as_ref().map(|entry| entry.divisibility),
+            "amount": row.amount.to_string(),
+        });
+        let holds_rune = rune_id.map_or(true, |rune_id| rune_id == row.rune_id);
+        match utxos.last_mut() {
+            Some(utxo) if utxo.0 == row.outpoint => {
+                utxo.3.push(balance);
+                utxo.4 |= holds_rune;
+            }
+            _ => utxos.push((
+                row.outpoint,
+                row.value,
+                row.block_height,
+                vec![balance],
+                holds_rune,
+            )),
+        }
+    }
+    let results = utxos
+        .into_iter()
+        .filter(|(_, _, _, _, holds_rune)| *holds_rune)
+        .map(|(outpoint, value, block_height, runes, _)| {
+            json!({
+                "outpoint": outpoint,
+                "value": value,
+                "block_height": block_height,
+                "runes": runes,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "address": address,
+            "results": results,
+        },
+    })))
+}
+
+#[get(
+    "/ordhook/v1/blocks/<block_height>/events-hash",
+    format = "application/json"
+)]
+fn handle_get_block_events_hash(
+    block_height: u64,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/blocks/{}/events-hash",
+        block_height
+    );
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(row) = find_block_events_hash(block_height, &db_conn, ctx) else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Events hash of block #{} not found", block_height),
+            })),
+        ));
+    };
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "block_height": row.block_height,
+            "block_hash": row.block_hash,
+            "events_hash": row.events_hash,
+            "events_count": row.events_count,
+        },
+    })))
+}
+
+#[get("/ordhook/v1/commitments/latest", format = "application/json")]
+fn handle_get_latest_index_commitment(
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    prometheus: &State<PrometheusMonitoring>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/commitments/latest");
+    let cache_key = origin.to_string();
+    let cache_generation = get_query_cache_generation();
+    if let Some(response) = get_cached_response(&cache_key, prometheus) {
+        return Ok(Json(response));
+    }
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(commitment) = find_index_commitment(None, &db_conn, ctx) else {
+        return Err(index_commitment_not_found());
+    };
+    let response = json!({
+        "status": 200,
+        "result": {
+            "block_height": commitment.block_height,
+            "block_hash": commitment.block_hash,
+            "root": commitment.root,
+            "leaves_count": commitment.leaves_count,
+        },
+    });
+    if !deadline.timed_out() {
+        cache_response(
+            cache_key,
+            &response,
+            vec![CacheTag::ChainTip],
+            cache_generation,
+        );
+    }
+    Ok(Json(response))
+}
+
+/// Inclusion proof of an inscription, by id or number, in the commitment stored at `block_height`.
+#[get(
+    "/ordhook/v1/commitments/<block_height>/proofs/<inscription>",
+    format = "application/json"
+)]
+fn handle_get_index_commitment_proof(
+    block_height: u64,
+    inscription: String,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/commitments/{}/proofs/{}",
+        block_height,
+        inscription
+    );
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some((commitment, tree)) = get_index_commitment_tree(block_height, &db_conn, ctx) else {
+        return Err(index_commitment_not_found());
+    };
+    let leaf_index = match inscription.parse::<i64>() {
+        Ok(inscription_number) => tree.find_leaf_index_by_number(inscription_number),
+        Err(_) => tree.find_leaf_index(&inscription),
+    };
+    let Some(leaf_index) = leaf_index else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Inscription {} not found in commitment", inscription),
+            })),
+        ));
+    };
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "block_height": commitment.block_height,
+            "block_hash": commitment.block_hash,
+            "root": commitment.root,
+            "leaf_index": leaf_index,
+            "leaf": tree.leaves[leaf_index],
+            "proof": tree.proof(leaf_index),
+        },
+    })))
+}
+
+/// Location of an inscription, by id or number, optionally as of block `at_height`. With `proof=true`, the response
+/// includes an inclusion proof against the latest index commitment at or below `at_height`.
+#[get(
+    "/ordhook/v1/inscriptions/<inscription>?<at_height>&<proof>",
+    format = "application/json"
+)]
+fn handle_get_inscription(
+    inscription: String,
+    at_height: Option<u64>,
+    proof: Option<bool>,
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    prometheus: &State<PrometheusMonitoring>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/inscriptions/{}",
+        inscription
+    );
+    let cache_key = origin.to_string();
+    let cache_generation = get_query_cache_generation();
+    if let Some(response) = get_cached_response(&cache_key, prometheus) {
+        return Ok(Json(response));
+    }
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(row) = find_inscription_location(&inscription, at_height, &db_conn, ctx) else {
+        return read_through_miss(origin, config, ctx).ok_or(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Inscription {} not found", inscription),
+            })),
+        ));
+    };
+    let mut result = serialize_inscription(&row, at_height, &db_conn, ctx);
+    let mut cache_tags = vec![CacheTag::Inscription(row.inscription_id.clone())];
+    // Unbound inscriptions never move.
+    if let Some(ordinal_number) = row.ordinal_number {
+        cache_tags.push(CacheTag::Sat(ordinal_number));
+    }
+    if proof.unwrap_or(false) {
+        result["proof"] = build_inscription_proof(&row.inscription_id, at_height, config, ctx)?;
+        cache_tags.push(CacheTag::ChainTip);
+    }
+    validate_read_through_result(
+        &cache_key,
+        &result,
+        &["inscription_id", "inscription_number", "block_height"],
+        ctx,
+    );
+    let response = json!({
+        "status": 200,
+        "result": result,
+    });
+    if !deadline.timed_out() {
+        cache_response(cache_key, &response, cache_tags, cache_generation);
+    }
+    Ok(Json(response))
+}
+
+fn serialize_inscription(
+    row: &InscriptionLocationRow,
+    at_height: Option<u64>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Value {
+    let unbound = parse_unbound_satpoint(&row.location).is_some();
+    let mut result = json!({
+        "inscription_id": row.inscription_id,
+        "inscription_number": row.inscription_number,
+        "ordinal_number": row.ordinal_number,
+        "block_height": row.block_height,
+        "location": row.location,
+        "unbound": unbound,
+        "at_height": at_height,
+    });
+    if let Some(content_types) = find_inscription_content_types(&row.inscription_id, db_conn, ctx) {
+        result["content_type"] = json!(content_types.declared_content_type);
+        result["detected_content_type"] = json!(content_types.detected_content_type);
+        result["content_type_mismatch"] = json!(content_types.content_type_mismatch);
+    }
+    if let Some(content_scan) = find_inscription_content_scan(&row.inscription_id, db_conn, ctx) {
+        result["content_scan"] = json!(content_scan);
+    }
+    // Unbound inscriptions are not inscribed on any sat.
+    if let Some(ordinal_number) = row.ordinal_number {
+        result["satributes"] = json!(get_satributes(ordinal_number));
+    }
+    result
+}
+
+/// Maximum number of ids looked up by a single batch request.
+const BATCH_LOOKUP_MAX_IDS: usize = 1_000;
+
+/// Ids of a `{"ids": [..]}` batch lookup request.
+fn parse_batch_ids(payload: &Value) -> Result<&Vec<Value>, Custom<Json<Value>>> {
+    let bad_request = |e: String| {
+        Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": e,
+            })),
+        )
+    };
+    let ids = payload
+        .get("ids")
+        .and_then(|ids| ids.as_array())
+        .ok_or(bad_request("ids must be an array".to_string()))?;
+    if ids.is_empty() {
+        return Err(bad_request("no id provided".to_string()));
+    }
+    if ids.len() > BATCH_LOOKUP_MAX_IDS {
+        return Err(bad_request(format!(
+            "at most {BATCH_LOOKUP_MAX_IDS} ids can be looked up at once"
+        )));
+    }
+    Ok(ids)
+}
+
+/// Same as `handle_get_inscription` for a `{"ids": [..]}` batch of inscription ids or numbers, without proofs. Results
+/// are in the order of the ids, `null` for the inscriptions not found.
+#[post(
+    "/ordhook/v1/inscriptions/batch",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_get_inscriptions_batch(
+    payload: Json<Value>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/inscriptions/batch");
+    let ids = parse_batch_ids(&payload)?;
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let results = ids
+        .iter()
+        .map(|id| {
+            let id = match id {
+                Value::String(id) => id.clone(),
+                id => id.to_string(),
+            };
+            find_inscription_location(&id, None, &db_conn, ctx)
+                .map(|row| serialize_inscription(&row, None, &db_conn, ctx))
+                .unwrap_or(Value::Null)
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "status": 200,
+        "result": results,
+    })))
+}
+
+/// Content of an inscription id never changes: responses can be cached forever. Inscription numbers can still be
+/// reassigned by a re-org, responses addressed by number are only cached briefly.
+const CONTENT_CACHE_CONTROL_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const CONTENT_CACHE_CONTROL_BY_NUMBER: &str = "public, max-age=60";
+/// Inscription contents are served on the origin of the API: HTML and SVG contents get rendered in an opaque origin,
+/// without scripts nor network access, so that they can't act on the API with the credentials of an operator.
+const CONTENT_SECURITY_POLICY: &str = "sandbox; default-src 'none'";
+
+/// Value of the `If-None-Match` request header, if any.
+struct IfNoneMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(IfNoneMatch(
+            request
+                .headers()
+                .get_one("If-None-Match")
+                .map(|value| value.to_string()),
+        ))
+    }
+}
+
+/// Returns true if an `If-None-Match` header value matches the given strong ETag, using the weak comparison
+/// required for this header.
+fn if_none_match_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        candidate == "*" || candidate.trim_start_matches("W/") == etag
+    })
+}
+
+struct InscriptionContentResponse {
+    etag: String,
+    cache_control: &'static str,
+    /// Content type and bytes of the inscription, `None` when answering with `304 Not Modified`.
+    content: Option<(ContentType, ContentBody)>,
+}
+
+impl<'r> Responder<'r, 'static> for InscriptionContentResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket_response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", self.etag)
+            .raw_header("Cache-Control", self.cache_control);
+        match self.content {
+            Some((content_type, body)) => {
+                response
+                    .status(Status::Ok)
+                    .header(content_type)
+                    .raw_header("X-Content-Type-Options", "nosniff")
+                    .raw_header("Content-Security-Policy", CONTENT_SECURITY_POLICY)
+                    .sized_body(body.len(), std::io::Cursor::new(body));
+            }
+            None => {
+                response.status(Status::NotModified);
+            }
+        }
+        response.ok()
+    }
+}
+
+/// Raw content of an inscription, by id or number, read from its reveal transaction. Responses carry a strong ETag
+/// derived from the inscription id, so that revalidations are answered without reaching bitcoind. Revalidations go
+/// through the blocklist and the content scanner first, for caches to drop contents blocked since.
+#[get("/ordhook/v1/inscriptions/<inscription>/content")]
+fn handle_get_inscription_content(
+    inscription: String,
+    if_none_match: IfNoneMatch,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<InscriptionContentResponse, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/inscriptions/{}/content",
+        inscription
+    );
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    let Some(row) = find_inscription_location(&inscription, None, &db_conn, ctx) else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Inscription {} not found", inscription),
+            })),
+        ));
+    };
+    let etag = format!("\"{}\"", row.inscription_id);
+    let cache_control = match inscription.parse::<i64>() {
+        Ok(_) => CONTENT_CACHE_CONTROL_BY_NUMBER,
+        Err(_) => CONTENT_CACHE_CONTROL_IMMUTABLE,
+    };
+    let revalidated = if_none_match.0.as_deref().map_or(false, |if_none_match| {
+        if_none_match_matches(if_none_match, &etag)
+    });
+    // Without the declared content type, the content scan can only be checked once the content is read.
+    if revalidated {
+        if let Some(content_type) =
+            find_inscription_genesis_content_type(&row.inscription_id, &db_conn, ctx)
+        {
+            check_content_scan(
+                &row.inscription_id,
+                Some(&content_type),
+                &db_conn,
+                config,
+                ctx,
+            )
+            .map_err(content_unavailable)?;
+            check_blocklist(&row.inscription_id, None, &db_conn, config, ctx)
+                .map_err(content_unavailable)?;
+            return Ok(InscriptionContentResponse {
+                etag,
+                cache_control,
+                content: None,
+            });
+        }
+    }
+    let content =
+        fetch_inscription_content(&row.inscription_id, &db_conn, config, ctx).map_err(|e| {
+            Custom(
+                Status::BadGateway,
+                Json(json!({
+                    "status": 502,
+                    "error": format!("Unable to read inscription content: {}", e),
+                })),
+            )
+        })?;
+    check_blocklist(
+        &row.inscription_id,
+        Some(&content.body[..]),
+        &db_conn,
+        config,
+        ctx,
+    )
+    .map_err(content_unavailable)?;
+    check_content_scan(
+        &row.inscription_id,
+        Some(&content.content_type),
+        &db_conn,
+        config,
+        ctx,
+    )
+    .map_err(content_unavailable)?;
+    if revalidated {
+        return Ok(InscriptionContentResponse {
+            etag,
+            cache_control,
+            content: None,
+        });
+    }
+    Ok(InscriptionContentResponse {
+        etag,
+        cache_control,
+        content: Some((
+            ContentType::parse_flexible(&content.content_type).unwrap_or(ContentType::Binary),
+            content.body,
+        )),
+    })
+}
+
+/// PNG preview of an image inscription, fitting in a `size` x `size` box (defaults to the first size configured).
+#[get("/ordhook/v1/inscriptions/<inscription>/content/preview?<size>")]
+fn handle_get_inscription_content_preview(
+    inscription: String,
+    size: Option<u32>,
+    if_none_match: IfNoneMatch,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<InscriptionContentResponse, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/inscriptions/{}/content/preview",
+        inscription
+    );
+    let Some(ref previews_config) = config.previews else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": "Previews are not enabled",
+            })),
+        ));
+    };
+    let size = size.unwrap_or(previews_config.sizes[0]);
+    if !previews_config.sizes.contains(&size) {
+        return Err(Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": format!("size must be one of {:?}", previews_config.sizes),
+            })),
+        ));
+    }
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    let Some(row) = find_inscription_location(&inscription, None, &db_conn, ctx) else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Inscription {} not found", inscription),
+            })),
+        ));
+    };
+    let etag = format!("\"{}-preview-{}\"", row.inscription_id, size);
+    let cache_control = match inscription.parse::<i64>() {
+        Ok(_) => CONTENT_CACHE_CONTROL_BY_NUMBER,
+        Err(_) => CONTENT_CACHE_CONTROL_IMMUTABLE,
+    };
+    check_blocklist(&row.inscription_id, None, &db_conn, config, ctx)
+        .map_err(content_unavailable)?;
+    let content_type = find_inscription_genesis_content_type(&row.inscription_id, &db_conn, ctx);
+    check_content_scan(
+        &row.inscription_id,
+        content_type.as_deref(),
+        &db_conn,
+        config,
+        ctx,
+    )
+    .map_err(content_unavailable)?;
+    if let Some(ref if_none_match) = if_none_match.0 {
+        if if_none_match_matches(if_none_match, &etag) {
+            return Ok(InscriptionContentResponse {
+                etag,
+                cache_control,
+                content: None,
+            });
+        }
+    }
+    let preview =
+        get_or_render_preview(&row.inscription_id, size, &db_conn, config, ctx).map_err(|e| {
+            Custom(
+                Status::UnprocessableEntity,
+                Json(json!({
+                    "status": 422,
+                    "error": format!("Unable to render preview: {}", e),
+                })),
+            )
+        })?;
+    Ok(InscriptionContentResponse {
+        etag,
+        cache_control,
+        content: Some((ContentType::PNG, ContentBody::Heap(preview))),
+    })
+}
+
+/// Contents the operator refuses to serve are answered with a 451.
+fn content_unavailable(e: String) -> Custom<Json<Value>> {
+    Custom(
+        Status::UnavailableForLegalReasons,
+        Json(json!({
+            "status": 451,
+            "error": e,
+        })),
+    )
+}
+
+/// Forwards the bytes of an archive being built to the response stream.
+struct ArchiveChunkWriter(tokio::sync::mpsc::Sender<Vec<u8>>);
+
+impl Write for ArchiveChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.blocking_send(buf.to_vec()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tar archive of the contents of a list of inscriptions (`{"inscription_ids": [..]}`, ids or numbers), with a
+/// `manifest.json`. The archive is streamed while contents get read from bitcoind.
+#[post(
+    "/ordhook/v1/inscriptions/contents/archive",
+    format = "application/json",
+    data = "<request>"
+)]
+fn handle_get_inscription_contents_archive(
+    request: Json<Value>,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<(ContentType, ByteStream![Vec<u8>]), Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP POST /ordhook/v1/inscriptions/contents/archive"
+    );
+    let inscriptions = request
+        .get("inscription_ids")
+        .and_then(|ids| ids.as_array())
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| match id {
+                    Value::String(id) => Some(id.clone()),
+                    Value::Number(number) => Some(number.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if inscriptions.is_empty() || inscriptions.len() > INSCRIPTION_CONTENTS_ARCHIVE_MAX_IDS {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            Json(json!({
+                "status": 422,
+                "error": format!("inscription_ids must list between 1 and {} inscription ids or numbers", INSCRIPTION_CONTENTS_ARCHIVE_MAX_IDS),
+            })),
+        ));
+    }
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    let (chunks_tx, mut chunks_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(16);
+    let moved_config = config.inner().clone();
+    let moved_ctx = ctx.inner().clone();
+    let _ = hiro_system_kit::thread_named("Inscription contents archive").spawn(move || {
+        let writer = std::io::BufWriter::with_capacity(64 * 1024, ArchiveChunkWriter(chunks_tx));
+        if let Err(e) = write_inscription_contents_archive(
+            &inscriptions,
+            writer,
+            &db_conn,
+            &moved_config,
+            &moved_ctx,
+        ) {
+            try_warn!(moved_ctx, "Unable to build contents archive: {e}");
+        }
+    });
+    Ok((
+        ContentType::new("application", "x-tar"),
+        ByteStream! {
+            while let Some(chunk) = chunks_rx.recv().await {
+                yield chunk;
+            }
+        },
+    ))
+}
+
+/// Rarity and satributes of a sat.
+#[get("/ordhook/v1/sats/<ordinal_number>", format = "application/json")]
+fn handle_get_sat(
+    ordinal_number: u64,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/sats/{}", ordinal_number);
+    let Some(result) = serialize_sat(ordinal_number) else {
+        return Err(Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": format!("Sat {} does not exist", ordinal_number),
+            })),
+        ));
+    };
+    Ok(Json(json!({
+        "status": 200,
+        "result": result,
+    })))
+}
+
+/// Rarity and satributes of a sat, `None` if it doesn't exist.
+fn serialize_sat(ordinal_number: u64) -> Option<Value> {
+    if ordinal_number >= Sat::SUPPLY {
+        return None;
+    }
+    let sat = Sat(ordinal_number);
+    Some(json!({
+        "ordinal_number": ordinal_number,
+        "name": sat.name(),
+        "block_height": sat.height().n(),
+        "rarity": Rarity::from(sat).name(),
+        "satributes": get_satributes(ordinal_number),
+    }))
+}
+
+/// Same as `handle_get_sat` for a `{"ids": [..]}` batch of ordinal numbers. Results are in the order of the ids, `null`
+/// for the sats which don't exist.
+#[post(
+    "/ordhook/v1/sats/batch",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_get_sats_batch(
+    payload: Json<Value>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/sats/batch");
+    let results = parse_batch_ids(&payload)?
+        .iter()
+        .map(|id| {
+            id.as_u64()
+                .or(id.as_str().and_then(|id| id.parse().ok()))
+                .and_then(serialize_sat)
+                .unwrap_or(Value::Null)
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "status": 200,
+        "result": results,
+    })))
+}
+
+const SATRIBUTE_DEFAULT_PAGE_LIMIT: u64 = 20;
+const SATRIBUTE_MAX_PAGE_LIMIT: u64 = 60;
+
+/// Inscriptions revealed on sats with a given satribute, ordered by block height.
+#[get(
+    "/ordhook/v1/satributes/<satribute>/inscriptions?<cursor>&<offset>&<limit>",
+    format = "application/json"
+)]
+fn handle_get_satribute_inscriptions(
+    satribute: String,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/satributes/{}/inscriptions",
+        satribute
+    );
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let after = parse_page_cursor::<(u64, String)>(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(SATRIBUTE_DEFAULT_PAGE_LIMIT)
+        .min(SATRIBUTE_MAX_PAGE_LIMIT);
+    let inscriptions = find_inscriptions_with_satribute(
+        &satribute,
+        after.as_ref(),
+        offset,
+        limit + 1,
+        &db_conn,
+        ctx,
+    );
+    let (inscriptions, next_cursor) = paginate(inscriptions, limit, |inscription| {
+        (inscription.block_height, inscription.inscription_id.clone())
+    });
+    let total = count_inscriptions_with_satribute(&satribute, &db_conn, ctx);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "satribute": satribute,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "total": total,
+            "results": inscriptions,
+        },
+    })))
+}
+
+const SALES_DEFAULT_PAGE_LIMIT: u64 = 20;
+const SALES_MAX_PAGE_LIMIT: u64 = 100;
+
+fn serialize_sale(sale: &SaleRow) -> Value {
+    json!({
+        "ordinal_number": sale.ordinal_number,
+        "block_height": sale.block_height,
+        "tx_id": sale.tx_id,
+        "tx_index": sale.tx_index,
+        "price_sats": sale.price_sats,
+        "seller_address": sale.seller_address,
+        "buyer_address": sale.buyer_address,
+        "confidence": sale.confidence,
+    })
+}
+
+/// Likely sales of the sat of an inscription, by id or number, most recent first. Requires sales analytics.
+#[get(
+    "/ordhook/v1/inscriptions/<inscription>/sales?<cursor>&<offset>&<limit>",
+    format = "application/json"
+)]
+fn handle_get_inscription_sales(
+    inscription: String,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/inscriptions/{}/sales",
+        inscription
+    );
+    let sales_db_conn =
+        open_readonly_sales_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&sales_db_conn);
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let Some(row) = find_inscription_location(&inscription, None, &db_conn, ctx) else {
+        return Err(Custom(
+            Status::NotFound,
+            Json(json!({
+                "status": 404,
+                "error": format!("Inscription {} not found", inscription),
+            })),
+        ));
+    };
+    let after = parse_page_cursor(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(SALES_DEFAULT_PAGE_LIMIT)
+        .min(SALES_MAX_PAGE_LIMIT);
+    // Unbound inscriptions are not inscribed on any sat, and can't be sold.
+    let sales = match row.ordinal_number {
+        Some(ordinal_number) => find_sales_of_sat(
+            ordinal_number,
+            after.as_ref(),
+            offset,
+            limit + 1,
+            &sales_db_conn,
+            ctx,
+        ),
+        None => vec![],
+    };
+    let (sales, next_cursor) = paginate(sales, limit, get_sale_sort_keys);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "inscription_id": row.inscription_id,
+            "ordinal_number": row.ordinal_number,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": sales.iter().map(serialize_sale).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+/// Latest likely sales of inscriptions, most recent first. With `high_confidence=true`, only the sales of inputs signed
+/// like marketplace listings are listed, and with `from_date`/`to_date`, only the sales of blocks mined in these days.
+/// Requires sales analytics.
+#[get(
+    "/ordhook/v1/sales?<cursor>&<offset>&<limit>&<high_confidence>&<from_date>&<to_date>",
+    format = "application/json"
+)]
+fn handle_get_sales(
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    high_confidence: Option<bool>,
+    from_date: Option<String>,
+    to_date: Option<String>,
+    origin: &Origin<'_>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    prometheus: &State<PrometheusMonitoring>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/sales");
+    let cache_key = origin.to_string();
+    let cache_generation = get_query_cache_generation();
+    if let Some(response) = get_cached_response(&cache_key, prometheus) {
+        return Ok(Json(response));
+    }
+    let db_conn = open_readonly_sales_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let after = parse_page_cursor(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(SALES_DEFAULT_PAGE_LIMIT)
+        .min(SALES_MAX_PAGE_LIMIT);
+    let date_range = match (&from_date, &to_date) {
+        (None, None) => None,
+        _ => {
+            let ordinals_db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+                .map_err(meta_protocol_unavailable)?;
+            resolve_date_range(
+                from_date.as_deref(),
+                to_date.as_deref(),
+                &ordinals_db_conn,
+                ctx,
+            )?
+        }
+    };
+    let sales = match date_range {
+        Some(None) => vec![],
+        date_range => find_latest_sales(
+            high_confidence.unwrap_or(false),
+            date_range.flatten(),
+            after.as_ref(),
+            offset,
+            limit + 1,
+            &db_conn,
+            ctx,
+        ),
+    };
+    let (sales, next_cursor) = paginate(sales, limit, get_sale_sort_keys);
+    let response = json!({
+        "status": 200,
+        "result": {
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": sales.iter().map(serialize_sale).collect::<Vec<_>>(),
+        },
+    });
+    if !deadline.timed_out() {
+        cache_response(
+            cache_key,
+            &response,
+            vec![CacheTag::ChainTip],
+            cache_generation,
+        );
+    }
+    Ok(Json(response))
+}
+
+const SAT_RANGES_DEFAULT_PAGE_LIMIT: u64 = 20;
+const SAT_RANGES_MAX_PAGE_LIMIT: u64 = 100;
+
+/// Unspent sat ranges held by an address. Requires the `full` index scope.
+#[get(
+    "/ordhook/v1/addresses/<address>/sat_ranges?<cursor>&<offset>&<limit>",
+    format = "application/json"
+)]
+fn handle_get_address_sat_ranges(
+    address: String,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/addresses/{}/sat_ranges",
+        address
+    );
+    let address = parse_address(&address, config)?;
+    if config.storage.index_scope != IndexScope::Full {
+        return Err(meta_protocol_unavailable(
+            "Sat ranges are only tracked with the full index scope".to_string(),
+        ));
+    }
+    let db_conn =
+        open_readonly_sat_ranges_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let after_start = parse_page_cursor::<u64>(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(SAT_RANGES_DEFAULT_PAGE_LIMIT)
+        .min(SAT_RANGES_MAX_PAGE_LIMIT);
+    let sat_ranges = find_sat_ranges_held_by_address(
+        &address,
+        false,
+        after_start,
+        offset,
+        limit + 1,
+        &db_conn,
+        ctx,
+    );
+    let (sat_ranges, next_cursor) = paginate(sat_ranges, limit, |range| range.start);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "address": address,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": sat_ranges.iter().map(|range| json!({
+                "outpoint": range.outpoint,
+                "offset": range.offset,
+                "start": range.start,
+                "end": range.end,
+                "size": range.end - range.start,
+                "block_height": range.block_height,
+            })).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+/// Sats rarer than common held by an address, with their satributes. Requires the `inscribed_plus_rare` or `full` index
+/// scope.
+#[get(
+    "/ordhook/v1/addresses/<address>/rare_sats?<cursor>&<offset>&<limit>",
+    format = "application/json"
+)]
+fn handle_get_address_rare_sats(
+    address: String,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    deadline: QueryDeadline,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/addresses/{}/rare_sats",
+        address
+    );
+    let address = parse_address(&address, config)?;
+    let db_conn =
+        open_readonly_sat_ranges_db_conn(config, ctx).map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let after_start = parse_page_cursor::<u64>(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(SAT_RANGES_DEFAULT_PAGE_LIMIT)
+        .min(SAT_RANGES_MAX_PAGE_LIMIT);
+    let sat_ranges = find_sat_ranges_held_by_address(
+        &address,
+        true,
+        after_start,
+        offset,
+        limit + 1,
+        &db_conn,
+        ctx,
+    );
+    let (sat_ranges, next_cursor) = paginate(sat_ranges, limit, |range| range.start);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "address": address,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": sat_ranges.iter().map(|range| json!({
+                "ordinal_number": range.start,
+                "name": Sat(range.start).name(),
+                "rarity": range.rarity,
+                "satributes": get_satributes(range.start),
+                "satpoint": format!("{}:{}", range.outpoint, range.offset),
+            })).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+/// Unspent outputs of an address, annotated with the inscriptions, rare sats and runes they carry for coin selection.
+#[get("/ordhook/v1/addresses/<address>/utxos", format = "application/json")]
+async fn handle_get_address_utxos(
+    address: String,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/addresses/{}/utxos",
+        address
+    );
+    annotate_utxos(&[address], config, ctx).await
+}
+
+/// Same as `handle_get_address_utxos` for a `{"addresses": [..]}` batch of addresses, scanned at once.
+#[post("/ordhook/v1/utxos", format = "application/json", data = "<payload>")]
+async fn handle_annotate_utxos(
+    payload: Json<Value>,
     config: &State<Config>,
     ctx: &State<Context>,
 ) -> Result<Json<Value>, Custom<Json<Value>>> {
-    try_info!(ctx, "Handling HTTP GET /v1/observers");
-    match open_readonly_observers_db_conn(config, ctx) {
-        Ok(mut db_conn) => {
-            let observers = find_all_observers(&mut db_conn, &ctx);
-            let serialized_predicates = observers
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/utxos");
+    let addresses = payload
+        .get("addresses")
+        .and_then(|addresses| addresses.as_array())
+        .map(|addresses| {
+            addresses
                 .iter()
-                .map(|(p, s)| serialized_predicate_with_status(p, s))
-                .collect::<Vec<_>>();
-            Ok(Json(json!({
-                "status": 200,
-                "result": serialized_predicates
-            })))
-        }
-        Err(e) => Err(Custom(
-            Status::InternalServerError,
-            Json(json!({
-                "status": 500,
-                "message": e,
-            })),
-        )),
-    }
+                .filter_map(|address| address.as_str().map(|a| a.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    annotate_utxos(&addresses, config, ctx).await
 }
 
-#[post("/v1/observers", format = "application/json", data = "<predicate>")]
-fn handle_create_predicate(
-    predicate: Json<Value>,
+/// Inscriptions, rare sats and runes held by a `{"ids": ["<txid>:<vout>", ..]}` batch of unspent outputs. Results are
+/// in the order of the ids, `null` for the ids which are not outpoints.
+#[post(
+    "/ordhook/v1/outputs/batch",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_get_outputs_batch(
+    payload: Json<Value>,
+    deadline: QueryDeadline,
     config: &State<Config>,
-    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
     ctx: &State<Context>,
 ) -> Result<Json<Value>, Custom<Json<Value>>> {
-    try_info!(ctx, "Handling HTTP POST /v1/observers");
-    let predicate =
-        match serde_json::from_value::<ChainhookFullSpecification>(predicate.into_inner()) {
-            Ok(predicate) => predicate,
-            Err(_) => {
-                return Err(Custom(
-                    Status::UnprocessableEntity,
-                    Json(json!({
-                        "status": 422,
-                        "error": "Invalid predicate JSON",
-                    })),
-                ));
-            }
-        };
-    if let Err(e) = predicate.validate() {
-        return Err(Custom(
-            Status::UnprocessableEntity,
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/outputs/batch");
+    let ids = parse_batch_ids(&payload)?;
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let sat_ranges_db_conn = open_readonly_sat_ranges_db_conn(config, ctx).ok();
+    if let Some(ref sat_ranges_db_conn) = sat_ranges_db_conn {
+        deadline.watch(sat_ranges_db_conn);
+    }
+    let runes_db_conn = open_readonly_runes_db_conn(config, ctx).ok();
+    if let Some(ref runes_db_conn) = runes_db_conn {
+        deadline.watch(runes_db_conn);
+    }
+    let results = ids
+        .iter()
+        .map(|id| {
+            id.as_str()
+                .and_then(|outpoint| {
+                    annotate_output(
+                        outpoint,
+                        &db_conn,
+                        sat_ranges_db_conn.as_ref(),
+                        runes_db_conn.as_ref(),
+                        ctx,
+                    )
+                })
+                .map(|output| json!(output))
+                .unwrap_or(Value::Null)
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "status": 200,
+        "result": results,
+    })))
+}
+
+async fn annotate_utxos(
+    addresses: &[String],
+    config: &Config,
+    ctx: &Context,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    let addresses = parse_utxo_addresses(addresses, config).map_err(|e| {
+        Custom(
+            Status::BadRequest,
             Json(json!({
-                "status": 422,
+                "status": 400,
                 "error": e,
             })),
-        ));
-    }
-    let mut predicates_db_conn = match open_readonly_observers_db_conn(config, ctx) {
-        Ok(conn) => conn,
-        Err(err) => {
-            return Err(Custom(
-                Status::InternalServerError,
+        )
+    })?;
+    let utxo_set = UtxoScanHandle::spawn(address_scan_requests(&addresses), config, ctx)
+        .join()
+        .await
+        .map_err(|e| {
+            Custom(
+                Status::ServiceUnavailable,
                 Json(json!({
-                    "status": 500,
-                    "error": err.to_string(),
+                    "status": 503,
+                    "error": e,
                 })),
-            ));
-        }
-    };
-    let predicate_uuid = predicate.get_uuid().to_string();
-    if find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx).is_some() {
-        return Err(status::Custom(
-            Status::Conflict,
+            )
+        })?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": utxo_set,
+    })))
+}
+
+fn wallet_not_found(uuid: &str) -> Custom<Json<Value>> {
+    Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": format!("No wallet watched by predicate {uuid}"),
+        })),
+    )
+}
+
+/// Wallets are only shown to the tenants which can access the predicate watching them. Others get a 404, to not
+/// disclose which predicates exist.
+fn check_wallet_access(
+    predicate_uuid: &str,
+    scope: &TenantScope,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), Custom<Json<Value>>> {
+    let mut observers_db_conn = open_readonly_observers_db_conn(config, ctx).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
             Json(json!({
-                "status": 409,
-                "error": "Predicate uuid already in use",
+                "status": 500,
+                "error": e,
             })),
-        ));
+        )
+    })?;
+    match find_observer_with_uuid(predicate_uuid, &mut observers_db_conn, ctx) {
+        Some((spec, _)) if scope.can_access_predicate(&spec) => Ok(()),
+        _ => Err(wallet_not_found(predicate_uuid)),
     }
-    match background_job_tx.inner().lock() {
-        Ok(tx) => {
-            let _ = tx.send(ObserverCommand::RegisterPredicate(predicate));
-        }
-        Err(err) => {
-            return Err(Custom(
-                Status::InternalServerError,
-                Json(json!({
-                    "status": 500,
-                    "error": err.to_string(),
-                })),
-            ));
-        }
+}
+
+/// Wallet watched by a predicate, with the addresses derived so far.
+#[get("/ordhook/v1/wallets/<predicate_uuid>", format = "application/json")]
+fn handle_get_wallet(
+    predicate_uuid: String,
+    scope: TenantScope,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/wallets/{}",
+        predicate_uuid
+    );
+    check_wallet_access(&predicate_uuid, &scope, config, ctx)?;
+    let (Some(filter), Some(addresses)) = (
+        get_wallet_predicate_filter(&predicate_uuid),
+        get_wallet_addresses(&predicate_uuid),
+    ) else {
+        return Err(wallet_not_found(&predicate_uuid));
     };
     Ok(Json(json!({
         "status": 200,
-        "result": predicate_uuid,
+        "result": {
+            "gap_limit": filter.gap_limit,
+            "descriptors": filter.descriptors.iter().zip(addresses.iter()).enumerate().map(|(i, (descriptor, addresses))| json!({
+                "descriptor": descriptor,
+                "last_used_index": filter.last_used_indexes.get(i).copied().flatten(),
+                "addresses": addresses,
+            })).collect::<Vec<_>>(),
+        },
     })))
 }
 
-#[get("/v1/observers/<predicate_uuid>", format = "application/json")]
-fn handle_get_predicate(
+/// Unspent outputs held by the addresses derived for the wallet of a predicate, annotated like
+/// `handle_get_address_utxos`.
+#[get(
+    "/ordhook/v1/wallets/<predicate_uuid>/utxos",
+    format = "application/json"
+)]
+async fn handle_get_wallet_utxos(
     predicate_uuid: String,
+    scope: TenantScope,
     config: &State<Config>,
     ctx: &State<Context>,
 ) -> Result<Json<Value>, Custom<Json<Value>>> {
-    try_info!(ctx, "Handling HTTP GET /v1/observers/{}", predicate_uuid);
-    match open_readonly_observers_db_conn(config, ctx) {
-        Ok(mut predicates_db_conn) => {
-            let entry =
-                match find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx) {
-                    Some((ChainhookSpecification::Bitcoin(spec), report)) => json!({
-                        "chain": "bitcoin",
-                        "uuid": spec.uuid,
-                        "network": spec.network,
-                        "predicate": spec.predicate,
-                        "status": report,
-                        "enabled": spec.enabled,
-                    }),
-                    _ => {
-                        return Err(Custom(
-                            Status::NotFound,
-                            Json(json!({
-                                "status": 404,
-                            })),
-                        ))
-                    }
-                };
-            Ok(Json(json!({
-                "status": 200,
-                "result": entry
-            })))
-        }
-        Err(e) => Err(Custom(
-            Status::InternalServerError,
+    try_info!(
+        ctx,
+        "Handling HTTP GET /ordhook/v1/wallets/{}/utxos",
+        predicate_uuid
+    );
+    check_wallet_access(&predicate_uuid, &scope, config, ctx)?;
+    let Some(filter) = get_wallet_predicate_filter(&predicate_uuid) else {
+        return Err(wallet_not_found(&predicate_uuid));
+    };
+    let utxo_set = UtxoScanHandle::spawn(filter.scan_requests(), config, ctx)
+        .join()
+        .await
+        .map_err(|e| {
+            Custom(
+                Status::ServiceUnavailable,
+                Json(json!({
+                    "status": 503,
+                    "error": e,
+                })),
+            )
+        })?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": utxo_set,
+    })))
+}
+
+/// Annotates a `{"psbt": "<base64 or hex>"}` PSBT with the inscriptions and rare sats carried by its inputs, and where
+/// they would land once broadcast.
+#[post(
+    "/ordhook/v1/psbt/annotate",
+    format = "application/json",
+    data = "<payload>"
+)]
+fn handle_annotate_psbt(
+    payload: Json<Value>,
+    config: &State<Config>,
+    ctx: &State<Context>,
+) -> Result<Json<Value>, Custom<Json<Value>>> {
+    try_info!(ctx, "Handling HTTP POST /ordhook/v1/psbt/annotate");
+    let Some(psbt) = payload.get("psbt").and_then(|psbt| psbt.as_str()) else {
+        return Err(Custom(
+            Status::BadRequest,
             Json(json!({
-                "status": 500,
-                "message": e,
+                "status": 400,
+                "error": "psbt must be a base64 or hex encoded PSBT",
             })),
-        )),
-    }
+        ));
+    };
+    let annotation = annotate_psbt(psbt, config, ctx).map_err(|e| {
+        Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": e,
+            })),
+        )
+    })?;
+    Ok(Json(json!({
+        "status": 200,
+        "result": annotation,
+    })))
 }
 
-#[delete("/v1/observers/<predicate_uuid>", format = "application/json")]
-fn handle_delete_bitcoin_predicate(
-    predicate_uuid: String,
+const DIFF_DEFAULT_PAGE_LIMIT: u64 = 1_000;
+const DIFF_MAX_PAGE_LIMIT: u64 = 10_000;
+
+/// Net inscription changes between two heights: inscriptions revealed or moved after block `from`, up to block `to`,
+/// with their location at `to`. Lets caching layers catch up without replaying every event. The range can also be
+/// given as `from_date`/`to_date`, covering the blocks mined in these days.
+#[get(
+    "/ordhook/v1/diff?<from>&<to>&<from_date>&<to_date>&<cursor>&<offset>&<limit>",
+    format = "application/json"
+)]
+fn handle_get_ordinals_diff(
+    from: Option<u64>,
+    to: Option<u64>,
+    from_date: Option<String>,
+    to_date: Option<String>,
+    cursor: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    deadline: QueryDeadline,
     config: &State<Config>,
-    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
     ctx: &State<Context>,
 ) -> Result<Json<Value>, Custom<Json<Value>>> {
-    try_info!(ctx, "Handling HTTP DELETE /v1/observers/{}", predicate_uuid);
-    let mut predicates_db_conn = match open_readonly_observers_db_conn(config, ctx) {
-        Ok(conn) => conn,
-        Err(err) => {
+    try_info!(ctx, "Handling HTTP GET /ordhook/v1/diff");
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    deadline.watch(&db_conn);
+    let date_range = resolve_date_range(from_date.as_deref(), to_date.as_deref(), &db_conn, ctx)?;
+    let (from, to) = match (from, to, date_range) {
+        (Some(from), Some(to), None) => (from, to),
+        (None, None, Some(Some((first_block_height, last_block_height)))) => {
+            (first_block_height.saturating_sub(1), last_block_height)
+        }
+        (None, None, Some(None)) => {
+            return Ok(Json(json!({
+                "status": 200,
+                "result": {
+                    "from": null,
+                    "to": null,
+                    "offset": offset.unwrap_or(0),
+                    "limit": limit.unwrap_or(DIFF_DEFAULT_PAGE_LIMIT).min(DIFF_MAX_PAGE_LIMIT),
+                    "next_cursor": null,
+                    "results": [],
+                },
+            })));
+        }
+        _ => {
             return Err(Custom(
-                Status::InternalServerError,
+                Status::BadRequest,
                 Json(json!({
-                    "status": 500,
-                    "error": err.to_string(),
+                    "status": 400,
+                    "error": "either from and to, or from_date and/or to_date are required",
                 })),
             ));
         }
     };
-    if find_observer_with_uuid(&predicate_uuid, &mut predicates_db_conn, &ctx).is_none() {
-        return Err(status::Custom(
+    if from > to {
+        return Err(Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": "from must not be greater than to",
+            })),
+        ));
+    }
+    let after_inscription_number = parse_page_cursor::<i64>(cursor)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(DIFF_DEFAULT_PAGE_LIMIT)
+        .min(DIFF_MAX_PAGE_LIMIT);
+    let changes = find_inscription_changes_in_block_range(
+        from,
+        to,
+        after_inscription_number,
+        offset,
+        limit + 1,
+        &db_conn,
+        ctx,
+    );
+    let (changes, next_cursor) = paginate(changes, limit, |change| change.inscription_number);
+    Ok(Json(json!({
+        "status": 200,
+        "result": {
+            "from": from,
+            "to": to,
+            "offset": offset,
+            "limit": limit,
+            "next_cursor": next_cursor,
+            "results": changes.iter().map(|change| json!({
+                "change": if change.block_height > from { "revealed" } else { "moved" },
+                "inscription_id": change.inscription_id,
+                "inscription_number": change.inscription_number,
+                "location": change.location,
+            })).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+/// Proof material for an inscription: its leaf and Merkle path in the latest index commitment at or below `at_height`,
+/// and the header of the committed block, to be checked against the client's own header chain. The root itself is
+/// computed and served by this instance and not signed: the proof only shows the inscription is part of the index the
+/// operator committed to, and is only as trustworthy as the operator.
+fn build_inscription_proof(
+    inscription_id: &str,
+    at_height: Option<u64>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Value, Custom<Json<Value>>> {
+    let db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .map_err(meta_protocol_unavailable)?;
+    let commitment = find_index_commitment_at_height(at_height, &db_conn, ctx)
+        .ok_or_else(index_commitment_not_found)?;
+    let (commitment, tree) = get_index_commitment_tree(commitment.block_height, &db_conn, ctx)
+        .ok_or_else(index_commitment_not_found)?;
+    let Some(leaf_index) = tree.find_leaf_index(inscription_id) else {
+        return Err(Custom(
             Status::NotFound,
             Json(json!({
                 "status": 404,
-                "error": "Predicate not found",
+                "error": format!("Inscription {} not found in commitment", inscription_id),
             })),
         ));
-    }
-    match background_job_tx.inner().lock() {
-        Ok(tx) => {
-            let _ = tx.send(ObserverCommand::DeregisterBitcoinPredicate(predicate_uuid));
-        }
-        Err(err) => {
-            return Err(Custom(
-                Status::InternalServerError,
-                Json(json!({
-                    "status": 500,
-                    "error": err.to_string(),
-                })),
-            ));
-        }
     };
+    let (block_header, confirmations) = bitcoind_get_block_header(&commitment.block_hash, config)
+        .map_err(|e| {
+        Custom(
+            Status::ServiceUnavailable,
+            Json(json!({
+                "status": 503,
+                "error": e.to_string(),
+            })),
+        )
+    })?;
+    Ok(json!({
+        "commitment": {
+            "block_height": commitment.block_height,
+            "block_hash": commitment.block_hash,
+            "root": commitment.root,
+        },
+        "leaf_index": leaf_index,
+        "leaf": tree.leaves[leaf_index],
+        "path": tree.proof(leaf_index),
+        "block_header": block_header,
+        "confirmations": confirmations,
+    }))
+}
 
-    Ok(Json(json!({
+/// Serves a request that missed locally from the upstream API, when one is configured. Responses are flagged with
+/// `"source": "upstream"`.
+fn read_through_miss(origin: &Origin<'_>, config: &Config, ctx: &Context) -> Option<Json<Value>> {
+    config.upstream_api_url()?;
+    let local_tip = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)
+        .ok()
+        .and_then(|db_conn| {
+            find_latest_inscription_block_height(&db_conn, ctx)
+                .ok()
+                .flatten()
+        });
+    let result = read_through_upstream(&origin.to_string(), local_tip, config, ctx)?;
+    Some(Json(json!({
         "status": 200,
-        "result": "Predicate deleted",
+        "result": result,
+        "source": "upstream",
     })))
 }
 
+fn index_commitment_not_found() -> Custom<Json<Value>> {
+    Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": "Index commitment not found",
+        })),
+    )
+}
+
+/// Validates an address given in a path, rendered like the addresses of the index.
+fn parse_address(address: &str, config: &Config) -> Result<String, Custom<Json<Value>>> {
+    normalize_address(
+        address,
+        &get_bitcoin_network(&config.network.bitcoin_network),
+    )
+    .map_err(|e| {
+        Custom(
+            Status::BadRequest,
+            Json(json!({
+                "status": 400,
+                "error": e,
+            })),
+        )
+    })
+}
+
+/// Resolves a `from_date`/`to_date` range to the heights of the first and last blocks mined in it. Returns `None` when
+/// no date was given, and `Some(None)` when no block was indexed in the range. Ranges reaching blocks indexed without
+/// timestamps are rejected.
+fn resolve_date_range(
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<Option<(u64, u64)>>, Custom<Json<Value>>> {
+    if from_date.is_none() && to_date.is_none() {
+        return Ok(None);
+    }
+    let parse = |date: Option<&str>, end_of_day: bool| {
+        date.map(|date| parse_date_to_timestamp(date, end_of_day))
+            .transpose()
+            .map_err(|e| {
+                Custom(
+                    Status::BadRequest,
+                    Json(json!({
+                        "status": 400,
+                        "error": e,
+                    })),
+                )
+            })
+    };
+    let from_timestamp = parse(from_date, false)?;
+    let to_timestamp = parse(to_date, true)?;
+    find_block_height_range_for_timestamps(from_timestamp, to_timestamp, db_conn, ctx)
+        .map(Some)
+        .map_err(|e| {
+            Custom(
+                Status::BadRequest,
+                Json(json!({
+                    "status": 400,
+                    "error": e,
+                })),
+            )
+        })
+}
+
+fn meta_protocol_unavailable<E: ToString>(e: E) -> Custom<Json<Value>> {
+    Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": e.to_string(),
+        })),
+    )
+}
+
+fn brc20_token_not_found(ticker: &str) -> Custom<Json<Value>> {
+    Custom(
+        Status::NotFound,
+        Json(json!({
+            "status": 404,
+            "error": format!("Token {} not found", ticker),
+        })),
+    )
+}
+
 fn serialized_predicate_with_status(
     predicate: &ChainhookSpecification,
     report: &ObserverReport,
+    predicate_health: &PredicateHealthRegistry,
+    observers_db_conn: &Connection,
+    ctx: &Context,
 ) -> Value {
     match (predicate, report) {
         (ChainhookSpecification::Stacks(_), _) => json!({}),
@@ -409,8 +3714,11 @@ fn serialized_predicate_with_status(
             "chain": "bitcoin",
             "uuid": spec.uuid,
             "network": spec.network,
+            "version": spec.version,
             "predicate": spec.predicate,
             "status": report,
+            "health": predicate_health.get(&spec.uuid),
+            "paused_at": find_predicate_paused_at(&spec.uuid, observers_db_conn, ctx),
             "enabled": spec.enabled,
         }),
     }
@@ -435,18 +3743,30 @@ mod test {
     use serde_json::{json, Value};
 
     use crate::{
-        config::{Config, PredicatesApi, PredicatesApiConfig},
+        config::{
+            Config, PredicatesApi, PredicatesApiConfig, DEFAULT_LISTENER_ADDRESS,
+            DEFAULT_QUERY_TIMEOUT_MS,
+        },
         service::observers::{delete_observers_db, initialize_observers_db},
         utils::monitoring::PrometheusMonitoring,
     };
 
-    use super::start_observers_http_server;
+    use super::{
+        if_none_match_matches, parse_batch_ids, serialize_sat, start_observers_http_server,
+        BATCH_LOOKUP_MAX_IDS,
+    };
 
     async fn launch_server(observer_event_rx: Receiver<ObserverEvent>) -> Shutdown {
         let mut config = Config::devnet_default();
         config.http_api = PredicatesApi::On(PredicatesApiConfig {
+            http_address: DEFAULT_LISTENER_ADDRESS,
             http_port: 20456,
+            unix_socket: None,
             display_logs: true,
+            upstream_api_url: None,
+            blocklist_path: None,
+            query_timeout_ms: DEFAULT_QUERY_TIMEOUT_MS,
+            tenants: vec![],
         });
         config.storage.observers_working_dir = "tmp".to_string();
         let ctx = Context::empty();
@@ -790,4 +4110,24 @@ mod test {
     async fn accepts_ping() {
         //
     }
+
+    #[test]
+    fn matches_if_none_match_etags() {
+        let etag = "\"abcdi0\"";
+        assert!(if_none_match_matches("\"abcdi0\"", etag));
+        assert!(if_none_match_matches("\"other\", W/\"abcdi0\"", etag));
+        assert!(if_none_match_matches("*", etag));
+        assert!(!if_none_match_matches("\"abcdi1\"", etag));
+    }
+
+    #[test]
+    fn validates_batch_lookups() {
+        assert_eq!(parse_batch_ids(&json!({ "ids": [1, 2] })).unwrap().len(), 2);
+        assert!(parse_batch_ids(&json!({ "ids": [] })).is_err());
+        assert!(parse_batch_ids(&json!({ "ids": "1" })).is_err());
+        let ids = vec![0; BATCH_LOOKUP_MAX_IDS + 1];
+        assert!(parse_batch_ids(&json!({ "ids": ids })).is_err());
+        assert_eq!(serialize_sat(0).unwrap()["rarity"], "mythic");
+        assert_eq!(serialize_sat(u64::MAX), None);
+    }
 }
@@ -0,0 +1,564 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha256, Hash, HashEngine,
+    },
+    utils::Context,
+};
+use hyper::{
+    body::HttpBody, header::CONTENT_TYPE, server::conn::Http, service::service_fn, Body, HeaderMap,
+    Request, Response, StatusCode,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{
+    rustls::{
+        server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+use crate::{
+    config::{Config, IngestionGuardConfig, IngestionTlsConfig},
+    service::tenants::secrets_match,
+    try_error, try_info, try_warn,
+    utils::monitoring::PrometheusMonitoring,
+};
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("unable to read certificates from {}: {e}", path.display()))?;
+    if certificates.is_empty() {
+        return Err(format!("no certificate found in {}", path.display()));
+    }
+    Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .map_err(|e| format!("unable to read private key from {}: {e}", path.display()))?
+        {
+            Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(format!("no private key found in {}", path.display())),
+        }
+    }
+}
+
+/// Builds the TLS acceptor of the ingestion port, requiring client certificates signed by `client_ca_path` when set.
+pub fn build_ingestion_tls_acceptor(config: &IngestionTlsConfig) -> Result<TlsAcceptor, String> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match config.client_ca_path {
+        Some(ref client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in load_certificates(client_ca_path)? {
+                roots.add(&certificate).map_err(|e| {
+                    format!("invalid certificate in {}: {e}", client_ca_path.display())
+                })?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let server_config = builder
+        .with_single_cert(
+            load_certificates(&config.cert_path)?,
+            load_private_key(&config.key_path)?,
+        )
+        .map_err(|e| format!("invalid ingestion tls certificate: {e}"))?;
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Payloads larger than this are rejected before being authenticated.
+pub const MAX_INGESTION_PAYLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+/// Signed payloads which timestamp is further than this from the clock are rejected. Nonces are remembered as long.
+pub const INGESTION_SIGNATURE_TOLERANCE_SECS: u64 = 300;
+
+const MAX_INGESTION_NONCE_LEN: usize = 128;
+
+/// Checks the `Authorization: Bearer <secret>` header of a payload, before its body gets read.
+pub fn authenticate_bearer_token(secret: &str, authorization: &str) -> Result<(), String> {
+    match authorization.strip_prefix("Bearer ") {
+        Some(token) if secrets_match(token.trim(), secret) => Ok(()),
+        _ => Err("invalid bearer token".into()),
+    }
+}
+
+/// Nonces of the signed payloads accepted within the last `INGESTION_SIGNATURE_TOLERANCE_SECS`, with their timestamp.
+#[derive(Debug, Default)]
+pub struct SeenNonces(HashMap<String, u64>);
+
+impl SeenNonces {
+    /// Records a nonce, unless already seen. Nonces older than the tolerance are forgotten, their payloads being
+    /// rejected on their timestamp anyway.
+    fn insert(&mut self, nonce: &str, timestamp: u64, now: u64) -> bool {
+        self.0
+            .retain(|_, seen_at| *seen_at + INGESTION_SIGNATURE_TOLERANCE_SECS >= now);
+        self.0.insert(nonce.to_string(), timestamp).is_none()
+    }
+}
+
+/// Checks that a payload was signed by the Stacks node: `X-Signature: sha256=<hex>` carries the HMAC-SHA256 keyed with
+/// the secret of `<X-Signature-Timestamp>.<X-Signature-Nonce>.<body>`. The timestamp, in seconds since the epoch, must
+/// be within `INGESTION_SIGNATURE_TOLERANCE_SECS` of `now`, and the nonce must not have been used in that window, so
+/// that captured payloads can't be replayed.
+pub fn authenticate_signed_payload(
+    secret: &str,
+    signature: Option<&str>,
+    timestamp: Option<&str>,
+    nonce: Option<&str>,
+    body: &[u8],
+    now: u64,
+    seen_nonces: &mut SeenNonces,
+) -> Result<(), String> {
+    let Some(signature) = signature else {
+        return Err("missing bearer token or signature".into());
+    };
+    let Some(signature) = signature.strip_prefix("sha256=") else {
+        return Err("unsupported signature scheme".into());
+    };
+    let (Some(timestamp), Some(nonce)) = (timestamp, nonce) else {
+        return Err("missing signature timestamp or nonce".into());
+    };
+    if nonce.is_empty() || nonce.len() > MAX_INGESTION_NONCE_LEN {
+        return Err("invalid signature nonce".into());
+    }
+    let Ok(signed_at) = timestamp.trim().parse::<u64>() else {
+        return Err("invalid signature timestamp".into());
+    };
+    if signed_at.abs_diff(now) > INGESTION_SIGNATURE_TOLERANCE_SECS {
+        return Err(format!(
+            "signature timestamp {signed_at} is too far from {now}"
+        ));
+    }
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(timestamp.trim().as_bytes());
+    engine.input(b".");
+    engine.input(nonce.as_bytes());
+    engine.input(b".");
+    engine.input(body);
+    let expected = hex::encode(Hmac::<sha256::Hash>::from_engine(engine).to_byte_array());
+    if !secrets_match(&signature.trim().to_ascii_lowercase(), &expected) {
+        return Err("invalid signature".into());
+    }
+    // Only recorded once the signature checked, for forged payloads not to fill the nonces up.
+    match seen_nonces.insert(nonce, signed_at, now) {
+        true => Ok(()),
+        false => Err(format!("nonce {nonce} was already used")),
+    }
+}
+
+/// Reads a body, unless larger than `max_bytes`.
+async fn read_capped_body(
+    mut body: Body,
+    max_bytes: usize,
+) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > max_bytes as u64 {
+        return Ok(None);
+    }
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > max_bytes {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Some(bytes))
+}
+
+/// Where and how the connections accepted by the guard are forwarded.
+#[derive(Clone)]
+struct IngestionRelay {
+    target: SocketAddr,
+    payload_secret: Option<Arc<String>>,
+    seen_nonces: Arc<Mutex<SeenNonces>>,
+    http_client: reqwest::Client,
+    prometheus: PrometheusMonitoring,
+    ctx: Context,
+}
+
+fn get_header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+async fn forward_ingestion_payload(
+    req: Request<Body>,
+    peer: SocketAddr,
+    secret: Arc<String>,
+    relay: IngestionRelay,
+) -> Result<Response<Body>, hyper::Error> {
+    let (parts, body) = req.into_parts();
+    let reject = |status: StatusCode, e: String| -> Result<Response<Body>, hyper::Error> {
+        relay.prometheus.metrics_ingestion_payload_rejected();
+        let ctx = &relay.ctx;
+        try_warn!(
+            ctx,
+            "Ingestion guard: rejected payload {} from {peer}: {e}",
+            parts.uri.path()
+        );
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = status;
+        Ok(response)
+    };
+    // Bearer tokens are checked before the body gets read, and signatures once it was read up to the cap.
+    let authorization = get_header_value(&parts.headers, "authorization");
+    if let Some(authorization) = authorization {
+        if let Err(e) = authenticate_bearer_token(&secret, authorization) {
+            return reject(StatusCode::UNAUTHORIZED, e);
+        }
+    }
+    let Some(body) = read_capped_body(body, MAX_INGESTION_PAYLOAD_BYTES).await? else {
+        return reject(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("body larger than {MAX_INGESTION_PAYLOAD_BYTES} bytes"),
+        );
+    };
+    if authorization.is_none() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let result = match relay.seen_nonces.lock() {
+            Ok(mut seen_nonces) => authenticate_signed_payload(
+                &secret,
+                get_header_value(&parts.headers, "x-signature"),
+                get_header_value(&parts.headers, "x-signature-timestamp"),
+                get_header_value(&parts.headers, "x-signature-nonce"),
+                &body,
+                now,
+                &mut seen_nonces,
+            ),
+            Err(e) => Err(format!("unable to lock nonces: {e}")),
+        };
+        if let Err(e) = result {
+            return reject(StatusCode::UNAUTHORIZED, e);
+        }
+    }
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let mut request = relay
+        .http_client
+        .request(
+            parts.method.clone(),
+            format!("http://{}{path}", relay.target),
+        )
+        .body(body);
+    if let Some(content_type) = parts.headers.get(CONTENT_TYPE) {
+        request = request.header(CONTENT_TYPE, content_type);
+    }
+    let response = match request.send().await {
+        Ok(res) => {
+            let status = res.status();
+            let content_type = res.headers().get(CONTENT_TYPE).cloned();
+            let body = res.bytes().await.unwrap_or_default();
+            let mut response = Response::new(Body::from(body));
+            *response.status_mut() = status;
+            if let Some(content_type) = content_type {
+                response.headers_mut().insert(CONTENT_TYPE, content_type);
+            }
+            response
+        }
+        Err(e) => {
+            let ctx = &relay.ctx;
+            try_warn!(ctx, "Ingestion guard: unable to reach event observer: {e}");
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::BAD_GATEWAY;
+            response
+        }
+    };
+    Ok(response)
+}
+
+/// Forwards the bytes of the connection as is, unless payloads have to be authenticated: requests are then read one
+/// by one, and only the authenticated ones are forwarded.
+async fn relay_ingestion_connection<S>(mut inbound: S, peer: SocketAddr, relay: IngestionRelay)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let ctx = relay.ctx.clone();
+    let result = match relay.payload_secret.clone() {
+        Some(secret) => Http::new()
+            .serve_connection(
+                inbound,
+                service_fn(move |req| {
+                    forward_ingestion_payload(req, peer, secret.clone(), relay.clone())
+                }),
+            )
+            .await
+            .map_err(|e| e.to_string()),
+        None => match TcpStream::connect(relay.target).await {
+            Ok(mut outbound) => tokio::io::copy_bidirectional(&mut inbound, &mut outbound)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => {
+                try_warn!(ctx, "Ingestion guard: unable to reach event observer: {e}");
+                return;
+            }
+        },
+    };
+    if let Err(e) = result {
+        try_warn!(
+            ctx,
+            "Ingestion guard: connection from {peer} interrupted: {e}"
+        );
+    }
+}
+
+async fn forward_ingestion_connection(
+    inbound: TcpStream,
+    peer: SocketAddr,
+    tls_acceptor: Option<TlsAcceptor>,
+    relay: IngestionRelay,
+) {
+    match tls_acceptor {
+        Some(tls_acceptor) => match tls_acceptor.accept(inbound).await {
+            Ok(inbound) => relay_ingestion_connection(inbound, peer, relay).await,
+            Err(e) => {
+                let ctx = &relay.ctx;
+                try_warn!(
+                    ctx,
+                    "Ingestion guard: tls handshake with {peer} failed: {e}"
+                );
+            }
+        },
+        None => relay_ingestion_connection(inbound, peer, relay).await,
+    }
+}
+
+/// Listens on `port` on every interface, over IPv6 and IPv4 (as IPv4-mapped addresses), falling back to IPv4 only on
+/// hosts where IPv6 is disabled.
+async fn bind_ingestion_port(port: u16) -> Result<TcpListener, String> {
+    match TcpListener::bind((IpAddr::V6(Ipv6Addr::UNSPECIFIED), port)).await {
+        Ok(listener) => Ok(listener),
+        Err(_) => TcpListener::bind((IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))
+            .await
+            .map_err(|e| format!("unable to listen on port {port}: {e}")),
+    }
+}
+
+/// Listens on the ingestion port, and forwards the connections of the allowed addresses, once their TLS handshake
+/// succeeded when configured, to chainhook-sdk's event observer. Other connections are dropped, as well as the
+/// unauthenticated payloads when `payload_secret` is set.
+async fn start_ingestion_guard(
+    guard: IngestionGuardConfig,
+    tls_acceptor: Option<TlsAcceptor>,
+    ingestion_port: u16,
+    prometheus: PrometheusMonitoring,
+    ctx: Context,
+) -> Result<(), String> {
+    let listener = bind_ingestion_port(ingestion_port).await?;
+    let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), guard.internal_port);
+    try_info!(
+        ctx,
+        "Ingestion guard: listening on port {ingestion_port}, forwarding to {target}"
+    );
+    let relay = IngestionRelay {
+        target,
+        payload_secret: guard.payload_secret.clone().map(Arc::new),
+        seen_nonces: Arc::new(Mutex::new(SeenNonces::default())),
+        http_client: reqwest::Client::builder()
+            .no_proxy()
+            .build()
+            .map_err(|e| format!("unable to build http client: {e}"))?,
+        prometheus,
+        ctx: ctx.clone(),
+    };
+    loop {
+        let (inbound, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                try_warn!(ctx, "Ingestion guard: unable to accept connection: {e}");
+                continue;
+            }
+        };
+        if !guard.is_allowed(&peer.ip()) {
+            try_warn!(ctx, "Ingestion guard: rejected connection from {peer}");
+            continue;
+        }
+        tokio::spawn(forward_ingestion_connection(
+            inbound,
+            peer,
+            tls_acceptor.clone(),
+            relay.clone(),
+        ));
+    }
+}
+
+/// Starts the ingestion guard on a dedicated thread, when configured. Fails if the TLS certificates can't be loaded.
+pub fn start_ingestion_guard_thread(
+    config: &Config,
+    prometheus: &PrometheusMonitoring,
+    ctx: &Context,
+) -> Result<(), String> {
+    let Some(guard) = config.network.ingestion_guard.clone() else {
+        return Ok(());
+    };
+    let tls_acceptor = match guard.tls {
+        Some(ref tls) => Some(build_ingestion_tls_acceptor(tls)?),
+        None => None,
+    };
+    let ingestion_port = config.network.ingestion_port;
+    let moved_prometheus = prometheus.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Ingestion guard").spawn(move || {
+        if let Err(e) = hiro_system_kit::nestable_block_on(start_ingestion_guard(
+            guard,
+            tls_acceptor,
+            ingestion_port,
+            moved_prometheus,
+            moved_ctx.clone(),
+        )) {
+            try_error!(moved_ctx, "Ingestion guard: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::bitcoincore_rpc::bitcoin::hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha256, Hash, HashEngine,
+    };
+
+    use super::{
+        authenticate_bearer_token, authenticate_signed_payload, SeenNonces,
+        INGESTION_SIGNATURE_TOLERANCE_SECS,
+    };
+
+    fn sign(secret: &str, timestamp: &str, nonce: &str, body: &[u8]) -> String {
+        let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+        engine.input(format!("{timestamp}.{nonce}.").as_bytes());
+        engine.input(body);
+        format!(
+            "sha256={}",
+            hex::encode(Hmac::<sha256::Hash>::from_engine(engine).to_byte_array())
+        )
+    }
+
+    #[test]
+    fn authenticates_bearer_tokens() {
+        assert!(authenticate_bearer_token("secret", "Bearer secret").is_ok());
+        assert!(authenticate_bearer_token("secret", "Bearer other").is_err());
+        assert!(authenticate_bearer_token("secret", "secret").is_err());
+    }
+
+    #[test]
+    fn authenticates_signed_payloads() {
+        let body = br#"{"block_height":1}"#;
+        let now = 1_700_000_000;
+        let timestamp = now.to_string();
+        let signature = sign("secret", &timestamp, "n1", body);
+        let check = |secret: &str,
+                     signature: Option<&str>,
+                     timestamp: &str,
+                     nonce: &str,
+                     body: &[u8],
+                     seen_nonces: &mut SeenNonces| {
+            authenticate_signed_payload(
+                secret,
+                signature,
+                Some(timestamp),
+                Some(nonce),
+                body,
+                now,
+                seen_nonces,
+            )
+        };
+
+        let mut seen_nonces = SeenNonces::default();
+        assert!(check(
+            "other",
+            Some(&signature),
+            &timestamp,
+            "n1",
+            body,
+            &mut seen_nonces
+        )
+        .is_err());
+        assert!(check(
+            "secret",
+            Some(&signature),
+            &timestamp,
+            "n1",
+            b"{}",
+            &mut seen_nonces
+        )
+        .is_err());
+        assert!(check(
+            "secret",
+            Some(&signature),
+            &timestamp,
+            "n2",
+            body,
+            &mut seen_nonces
+        )
+        .is_err());
+        assert!(check("secret", None, &timestamp, "n1", body, &mut seen_nonces).is_err());
+        assert!(check(
+            "secret",
+            Some(&signature),
+            &timestamp,
+            "n1",
+            body,
+            &mut seen_nonces
+        )
+        .is_ok());
+        // Replays are rejected.
+        assert!(check(
+            "secret",
+            Some(&signature),
+            &timestamp,
+            "n1",
+            body,
+            &mut seen_nonces
+        )
+        .is_err());
+
+        let stale_timestamp = (now - INGESTION_SIGNATURE_TOLERANCE_SECS - 1).to_string();
+        let stale_signature = sign("secret", &stale_timestamp, "n3", body);
+        assert!(check(
+            "secret",
+            Some(&stale_signature),
+            &stale_timestamp,
+            "n3",
+            body,
+            &mut seen_nonces
+        )
+        .is_err());
+        assert!(authenticate_signed_payload(
+            "secret",
+            Some(&signature),
+            None,
+            Some("n4"),
+            body,
+            now,
+            &mut seen_nonces
+        )
+        .is_err());
+    }
+}
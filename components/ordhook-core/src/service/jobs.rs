@@ -0,0 +1,495 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chainhook_sdk::utils::Context;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    config::Config,
+    scan::bitcoin::ScanControl,
+    service::observers::{
+        fail_interrupted_jobs_in_observers, find_all_jobs, find_job, initialize_observers_db,
+        insert_job_in_observers, open_readonly_observers_db_conn, open_readwrite_observers_db_conn,
+        prune_finished_jobs_in_observers,
+    },
+    try_error, try_info,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Rescan,
+    Backup,
+    /// Scan of the chainstate a predicate goes through when registered, before being streamed new blocks.
+    PredicateScan,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Rescan => "rescan",
+            JobKind::Backup => "backup",
+            JobKind::PredicateScan => "predicate_scan",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<JobKind> {
+        match value {
+            "rescan" => Some(JobKind::Rescan),
+            "backup" => Some(JobKind::Backup),
+            "predicate_scan" => Some(JobKind::PredicateScan),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<JobStatus> {
+        match value {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match self {
+            JobStatus::Queued | JobStatus::Running => false,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => true,
+        }
+    }
+}
+
+/// Long-running operation performed in the background, persisted in observers.sqlite.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    /// Parameters the job was submitted with.
+    pub params: JsonValue,
+    pub progress_percent: f64,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Handed to the work of a job to report its progress and check for its cancellation.
+#[derive(Debug, Clone)]
+pub struct JobControl {
+    cancelled: Arc<AtomicBool>,
+    completed_units: Arc<AtomicU64>,
+    total_units: u64,
+}
+
+impl JobControl {
+    pub fn new(total_units: u64) -> JobControl {
+        JobControl {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            completed_units: Arc::new(AtomicU64::new(0)),
+            total_units,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn add_completed_units(&self, units: u64) {
+        self.completed_units.fetch_add(units, Ordering::SeqCst);
+    }
+
+    pub fn get_progress_percent(&self) -> f64 {
+        if self.total_units == 0 {
+            return 0.0;
+        }
+        let completed_units = self.completed_units.load(Ordering::SeqCst);
+        (completed_units as f64 * 100.0 / self.total_units as f64).min(100.0)
+    }
+
+    /// Scan control sharing the cancellation of the job, which counts the blocks scanned as completed units.
+    pub fn scan_control(&self) -> ScanControl {
+        ScanControl {
+            cancelled: self.cancelled.clone(),
+            blocks_scanned: self.completed_units.clone(),
+            record_progress: false,
+        }
+    }
+}
+
+pub type JobWork = Box<dyn FnOnce(&JobControl) -> Result<(), String> + Send>;
+
+struct JobQueue {
+    /// Controls of the jobs queued or running.
+    controls: HashMap<String, JobControl>,
+    pending: VecDeque<(String, JobWork)>,
+    running: usize,
+}
+
+lazy_static! {
+    static ref JOB_QUEUE: Mutex<JobQueue> = Mutex::new(JobQueue {
+        controls: HashMap::new(),
+        pending: VecDeque::new(),
+        running: 0,
+    });
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn update_job<F>(job_id: &str, f: F, config: &Config, ctx: &Context) -> Option<Job>
+where
+    F: FnOnce(&mut Job),
+{
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx).ok()?;
+    let mut job = find_job(job_id, &observers_db_conn, ctx)?;
+    f(&mut job);
+    insert_job_in_observers(&job, &observers_db_conn, ctx);
+    Some(job)
+}
+
+fn with_live_progress(mut job: Job) -> Job {
+    if job.status == JobStatus::Running {
+        if let Ok(queue) = JOB_QUEUE.lock() {
+            if let Some(control) = queue.controls.get(&job.id) {
+                job.progress_percent = control.get_progress_percent();
+            }
+        }
+    }
+    job
+}
+
+/// Marks the jobs left queued or running by the previous run of the service as failed. Jobs are not resumed.
+pub fn fail_interrupted_jobs(config: &Config, ctx: &Context) {
+    let observers_db_conn = initialize_observers_db(config, ctx);
+    fail_interrupted_jobs_in_observers(&observers_db_conn, ctx);
+    prune_finished_jobs_in_observers(
+        get_retention_cutoff(now(), config.resources.job_retention_days),
+        &observers_db_conn,
+        ctx,
+    );
+}
+
+/// Timestamp before which finished jobs are deleted.
+fn get_retention_cutoff(now: u64, retention_days: u64) -> u64 {
+    now.saturating_sub(retention_days.saturating_mul(24 * 3600))
+}
+
+fn new_job(kind: JobKind, status: JobStatus, params: JsonValue) -> Job {
+    Job {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind,
+        status,
+        params,
+        progress_percent: 0.0,
+        created_at: now(),
+        started_at: None,
+        finished_at: None,
+        error: None,
+    }
+}
+
+/// Queues a job. `work` reports its progress in units out of `total_units`, and should return early once the job gets
+/// cancelled.
+pub fn submit_job(
+    kind: JobKind,
+    params: JsonValue,
+    total_units: u64,
+    work: JobWork,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Job, String> {
+    let job = new_job(kind, JobStatus::Queued, params);
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    insert_job_in_observers(&job, &observers_db_conn, ctx);
+    {
+        let mut queue = JOB_QUEUE.lock().map_err(|e| e.to_string())?;
+        queue
+            .controls
+            .insert(job.id.clone(), JobControl::new(total_units));
+        queue.pending.push_back((job.id.clone(), work));
+    }
+    try_info!(ctx, "Job {}: {} queued", job.id, kind.as_str());
+    dispatch_jobs(config, ctx);
+    Ok(job)
+}
+
+/// Starts queued jobs, up to `resources.max_concurrent_jobs` jobs running at the same time.
+fn dispatch_jobs(config: &Config, ctx: &Context) {
+    loop {
+        let (job_id, work, control) = {
+            let Ok(mut queue) = JOB_QUEUE.lock() else {
+                return;
+            };
+            if queue.running >= config.resources.max_concurrent_jobs {
+                return;
+            }
+            let Some((job_id, work)) = queue.pending.pop_front() else {
+                return;
+            };
+            let Some(control) = queue.controls.get(&job_id).cloned() else {
+                continue;
+            };
+            queue.running += 1;
+            (job_id, work, control)
+        };
+        let moved_config = config.clone();
+        let moved_ctx = ctx.clone();
+        let moved_job_id = job_id.clone();
+        let res = hiro_system_kit::thread_named("Job").spawn(move || {
+            let _ = run_job(&moved_job_id, work, &control, &moved_config, &moved_ctx);
+            if let Ok(mut queue) = JOB_QUEUE.lock() {
+                queue.running -= 1;
+                queue.controls.remove(&moved_job_id);
+            }
+            dispatch_jobs(&moved_config, &moved_ctx);
+        });
+        if let Err(e) = res {
+            try_error!(ctx, "Job {job_id}: unable to start: {e}");
+            if let Ok(mut queue) = JOB_QUEUE.lock() {
+                queue.running -= 1;
+                queue.controls.remove(&job_id);
+            }
+            let _ = update_job(
+                &job_id,
+                |job| {
+                    job.status = JobStatus::Failed;
+                    job.finished_at = Some(now());
+                    job.error = Some(e.to_string());
+                },
+                config,
+                ctx,
+            );
+        }
+    }
+}
+
+/// Runs a job on the current thread and records its outcome, which is returned.
+fn run_job(
+    job_id: &str,
+    work: JobWork,
+    control: &JobControl,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let _ = update_job(
+        job_id,
+        |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(now());
+        },
+        config,
+        ctx,
+    );
+    try_info!(ctx, "Job {job_id}: started");
+    let result = work(control);
+    let status = match result {
+        Ok(_) => JobStatus::Completed,
+        Err(_) if control.is_cancelled() => JobStatus::Cancelled,
+        Err(ref e) => {
+            try_error!(ctx, "Job {job_id}: failed: {e}");
+            JobStatus::Failed
+        }
+    };
+    let progress_percent = match status {
+        JobStatus::Completed => 100.0,
+        _ => control.get_progress_percent(),
+    };
+    let _ = update_job(
+        job_id,
+        |job| {
+            job.status = status;
+            job.progress_percent = progress_percent;
+            job.finished_at = Some(now());
+            if status == JobStatus::Failed {
+                job.error = result.clone().err();
+            }
+        },
+        config,
+        ctx,
+    );
+    try_info!(ctx, "Job {job_id}: {}", status.as_str());
+    if let Ok(observers_db_conn) = open_readwrite_observers_db_conn(config, ctx) {
+        prune_finished_jobs_in_observers(
+            get_retention_cutoff(now(), config.resources.job_retention_days),
+            &observers_db_conn,
+            ctx,
+        );
+    }
+    result
+}
+
+/// Runs work on the current thread as a job, for the work with its own executor and concurrency limit rather than
+/// the job queue's. The job gets listed and can be cancelled like the queued ones.
+pub fn run_tracked_job(
+    kind: JobKind,
+    params: JsonValue,
+    total_units: u64,
+    work: JobWork,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let job = new_job(kind, JobStatus::Running, params);
+    let control = JobControl::new(total_units);
+    match open_readwrite_observers_db_conn(config, ctx) {
+        Ok(observers_db_conn) => insert_job_in_observers(&job, &observers_db_conn, ctx),
+        // The work still runs, untracked.
+        Err(e) => try_error!(ctx, "Job {}: unable to record: {e}", job.id),
+    }
+    if let Ok(mut queue) = JOB_QUEUE.lock() {
+        queue.controls.insert(job.id.clone(), control.clone());
+    }
+    try_info!(ctx, "Job {}: {} submitted", job.id, kind.as_str());
+    let result = run_job(&job.id, work, &control, config, ctx);
+    if let Ok(mut queue) = JOB_QUEUE.lock() {
+        queue.controls.remove(&job.id);
+    }
+    result
+}
+
+pub fn get_job(job_id: &str, config: &Config, ctx: &Context) -> Result<Option<Job>, String> {
+    let observers_db_conn = open_readonly_observers_db_conn(config, ctx)?;
+    Ok(find_job(job_id, &observers_db_conn, ctx).map(with_live_progress))
+}
+
+/// Jobs submitted, most recent first, optionally filtered by kind and status.
+pub fn get_jobs(
+    kind: Option<JobKind>,
+    status: Option<JobStatus>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Vec<Job>, String> {
+    let observers_db_conn = open_readonly_observers_db_conn(config, ctx)?;
+    Ok(find_all_jobs(&observers_db_conn, ctx)
+        .into_iter()
+        .filter(|job| kind.map(|kind| job.kind == kind).unwrap_or(true))
+        .filter(|job| status.map(|status| job.status == status).unwrap_or(true))
+        .map(with_live_progress)
+        .collect())
+}
+
+/// Cancels a job. Queued jobs are cancelled right away, running jobs stop at their next cancellation check.
+pub fn cancel_job(job_id: &str, config: &Config, ctx: &Context) -> Result<Job, String> {
+    let dequeued = {
+        let mut queue = JOB_QUEUE.lock().map_err(|e| e.to_string())?;
+        match queue.controls.get(job_id) {
+            Some(control) => {
+                control.cancel();
+                let pending = queue.pending.len();
+                queue.pending.retain(|(id, _)| id != job_id);
+                let dequeued = queue.pending.len() < pending;
+                if dequeued {
+                    queue.controls.remove(job_id);
+                }
+                Some(dequeued)
+            }
+            None => None,
+        }
+    };
+    match dequeued {
+        Some(true) => update_job(
+            job_id,
+            |job| {
+                job.status = JobStatus::Cancelled;
+                job.finished_at = Some(now());
+            },
+            config,
+            ctx,
+        )
+        .ok_or(format!("job {job_id} not found")),
+        Some(false) => get_job(job_id, config, ctx)?.ok_or(format!("job {job_id} not found")),
+        None => match get_job(job_id, config, ctx)? {
+            Some(job) => Err(format!("job {job_id} is already {}", job.status.as_str())),
+            None => Err(format!("job {job_id} not found")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_retention_cutoff, JobControl, JobKind, JobStatus};
+
+    #[test]
+    fn reports_progress_in_percent() {
+        let control = JobControl::new(200);
+        assert_eq!(control.get_progress_percent(), 0.0);
+        control.add_completed_units(50);
+        assert_eq!(control.get_progress_percent(), 25.0);
+        control
+            .scan_control()
+            .blocks_scanned
+            .fetch_add(250, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(control.get_progress_percent(), 100.0);
+        assert_eq!(JobControl::new(0).get_progress_percent(), 0.0);
+    }
+
+    #[test]
+    fn shares_cancellation_with_scans() {
+        let control = JobControl::new(10);
+        let scan_control = control.scan_control();
+        control.cancel();
+        assert!(scan_control.is_cancelled());
+    }
+
+    #[test]
+    fn parses_job_statuses() {
+        for status in [
+            JobStatus::Queued,
+            JobStatus::Running,
+            JobStatus::Completed,
+            JobStatus::Failed,
+            JobStatus::Cancelled,
+        ] {
+            assert_eq!(JobStatus::from_str(status.as_str()), Some(status));
+        }
+        assert!(!JobStatus::Running.is_finished());
+        assert!(JobStatus::Cancelled.is_finished());
+        for kind in [JobKind::Rescan, JobKind::Backup, JobKind::PredicateScan] {
+            assert_eq!(JobKind::from_str(kind.as_str()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn computes_retention_cutoff() {
+        assert_eq!(get_retention_cutoff(10 * 86_400, 7), 3 * 86_400);
+        assert_eq!(get_retention_cutoff(86_400, 7), 0);
+        assert_eq!(get_retention_cutoff(86_400, u64::MAX), 0);
+    }
+}
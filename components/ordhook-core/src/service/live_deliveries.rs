@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use chainhook_sdk::{
+    chainhooks::{
+        bitcoin::{
+            evaluate_bitcoin_chainhooks_on_chain_event, BitcoinChainhookOccurrencePayload,
+            BitcoinTriggerChainhook,
+        },
+        types::{BitcoinChainhookSpecification, ChainhookSpecification, HookAction},
+    },
+    types::{
+        BitcoinBlockData, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
+        BitcoinChainUpdatedWithReorgData,
+    },
+    utils::Context,
+};
+
+use crate::{
+    config::Config,
+    core::meta_protocols::brc20::predicate::get_brc20_predicate_filter,
+    scan::{bitcoin::execute_predicates_action, predicate_scripts::has_predicate_script},
+    service::{
+        enrichment::EnrichmentDbConnections,
+        observers::{
+            find_observer_with_uuid, open_readwrite_observers_db_conn, update_observer_progress,
+        },
+        wallets::get_wallet_predicate_filter,
+    },
+    try_warn,
+    utils::event_transforms::{apply_event_transforms, has_event_transforms},
+};
+
+lazy_static! {
+    /// Actions of the predicates delivered by ordhook at the tip of the chain, by predicate uuid. The Chainhook observer
+    /// evaluates them with a `noop` action, and hands their occurrences over to the live deliveries worker.
+    static ref LIVE_DELIVERY_ACTIONS: RwLock<HashMap<String, HookAction>> = RwLock::new(HashMap::new());
+    static ref LIVE_DELIVERIES_TX: Mutex<Option<crossbeam_channel::Sender<BitcoinChainhookOccurrencePayload>>> =
+        Mutex::new(None);
+}
+
+/// Whether the deliveries of a predicate get narrowed down or transformed by ordhook, which the Chainhook observer is
+/// not aware of.
+fn is_delivery_filtered(uuid: &str) -> bool {
+    has_event_transforms()
+        || get_brc20_predicate_filter(uuid).is_some()
+        || get_wallet_predicate_filter(uuid).is_some()
+        || has_predicate_script(uuid)
+}
+
+/// Action a predicate gets registered with on the Chainhook observer. Predicates with deliveries narrowed down by
+/// ordhook get `noop`, their action being kept for the live deliveries worker. Filters must be set before.
+pub fn get_observer_hook_action(uuid: &str, action: HookAction) -> HookAction {
+    if !matches!(action, HookAction::HttpPost(_) | HookAction::FileAppend(_))
+        || !is_delivery_filtered(uuid)
+    {
+        return action;
+    }
+    let Ok(mut actions) = LIVE_DELIVERY_ACTIONS.write() else {
+        return action;
+    };
+    actions.insert(uuid.to_string(), action);
+    HookAction::Noop
+}
+
+/// Puts back the action of a predicate registered on the Chainhook observer with `noop`, before it gets stored.
+pub fn restore_observer_hook_action(spec: &mut ChainhookSpecification) {
+    let ChainhookSpecification::Bitcoin(spec) = spec else {
+        return;
+    };
+    if let Some(action) = get_live_delivery_action(&spec.uuid) {
+        spec.action = action;
+    }
+}
+
+fn get_live_delivery_action(uuid: &str) -> Option<HookAction> {
+    LIVE_DELIVERY_ACTIONS
+        .read()
+        .ok()
+        .and_then(|actions| actions.get(uuid).cloned())
+}
+
+pub fn forget_live_delivery(uuid: &str) {
+    if let Ok(mut actions) = LIVE_DELIVERY_ACTIONS.write() {
+        actions.remove(uuid);
+    }
+}
+
+/// Whether the occurrences of a predicate are delivered by ordhook rather than by the Chainhook observer.
+pub fn is_delivered_by_ordhook(uuid: &str) -> bool {
+    get_live_delivery_action(uuid).is_some()
+}
+
+/// Hands an occurrence of a predicate delivered by ordhook over to the live deliveries worker.
+pub fn queue_live_delivery(payload: BitcoinChainhookOccurrencePayload) {
+    if let Ok(tx) = LIVE_DELIVERIES_TX.lock() {
+        if let Some(ref tx) = *tx {
+            let _ = tx.send(payload);
+        }
+    }
+}
+
+/// Delivers an occurrence to the action of its predicate, through the same filters, enrichments, retries and dead
+/// letters as scans, then moves the progress of the predicate forward.
+fn deliver_live_occurrence(
+    payload: BitcoinChainhookOccurrencePayload,
+    enrichment_db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let uuid = payload.chainhook.uuid.clone();
+    // The predicate may have been deregistered in the meantime.
+    let Some(action) = get_live_delivery_action(&uuid) else {
+        return Ok(());
+    };
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    let Some((ChainhookSpecification::Bitcoin(spec), _)) =
+        find_observer_with_uuid(&uuid, &observers_db_conn, ctx)
+    else {
+        return Ok(());
+    };
+    let spec = BitcoinChainhookSpecification { action, ..spec };
+    let event_observer_config = config.get_event_observer_config();
+    let tip_height = payload
+        .apply
+        .last()
+        .map(|tip| tip.block.block_identifier.index);
+    if has_event_transforms() {
+        // The observer matched the transactions before they got transformed: they are evaluated again once
+        // transformed, as they are during scans.
+        let mut apply_blocks: Vec<BitcoinBlockData> =
+            payload.apply.into_iter().map(|apply| apply.block).collect();
+        let mut rollback_blocks: Vec<BitcoinBlockData> = payload
+            .rollback
+            .into_iter()
+            .map(|rollback| rollback.block)
+            .collect();
+        for block in apply_blocks.iter_mut().chain(rollback_blocks.iter_mut()) {
+            apply_event_transforms(block, ctx);
+        }
+        let chain_event = if rollback_blocks.is_empty() {
+            BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+                new_blocks: apply_blocks,
+                confirmed_blocks: vec![],
+            })
+        } else {
+            BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+                blocks_to_rollback: rollback_blocks,
+                blocks_to_apply: apply_blocks,
+                confirmed_blocks: vec![],
+            })
+        };
+        let predicates = vec![&spec];
+        let (predicates_triggered, _, _) =
+            evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &predicates, ctx);
+        hiro_system_kit::nestable_block_on(execute_predicates_action(
+            predicates_triggered,
+            &event_observer_config,
+            enrichment_db_conns,
+            config,
+            ctx,
+        ))?;
+    } else {
+        let trigger = BitcoinTriggerChainhook {
+            chainhook: &spec,
+            apply: payload
+                .apply
+                .iter()
+                .map(|apply| (apply.block.transactions.iter().collect(), &apply.block))
+                .collect(),
+            rollback: payload
+                .rollback
+                .iter()
+                .map(|rollback| {
+                    (
+                        rollback.block.transactions.iter().collect(),
+                        &rollback.block,
+                    )
+                })
+                .collect(),
+        };
+        hiro_system_kit::nestable_block_on(execute_predicates_action(
+            vec![trigger],
+            &event_observer_config,
+            enrichment_db_conns,
+            config,
+            ctx,
+        ))?;
+    }
+    if let Some(tip_height) = tip_height {
+        update_observer_progress(&uuid, tip_height, &observers_db_conn, ctx);
+    }
+    Ok(())
+}
+
+/// Starts the background worker delivering the occurrences of the predicates delivered by ordhook, one at a time and in
+/// the order the Chainhook observer emitted them.
+pub fn start_live_deliveries_worker(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Ok(mut deliveries_tx) = LIVE_DELIVERIES_TX.lock() else {
+        return Ok(());
+    };
+    if deliveries_tx.is_some() {
+        return Ok(());
+    }
+    let (tx, rx) = crossbeam_channel::unbounded::<BitcoinChainhookOccurrencePayload>();
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    hiro_system_kit::thread_named("Live deliveries worker")
+        .spawn(move || {
+            let mut enrichment_db_conns = EnrichmentDbConnections::new();
+            while let Ok(payload) = rx.recv() {
+                let uuid = payload.chainhook.uuid.clone();
+                if let Err(e) = deliver_live_occurrence(
+                    payload,
+                    &mut enrichment_db_conns,
+                    &moved_config,
+                    &moved_ctx,
+                ) {
+                    try_warn!(
+                        moved_ctx,
+                        "Unable to deliver occurrence of predicate {uuid}: {e}"
+                    );
+                }
+            }
+        })
+        .map_err(|e| format!("unable to start live deliveries worker: {e}"))?;
+    *deliveries_tx = Some(tx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::chainhooks::types::{HookAction, HttpHook};
+
+    use super::{forget_live_delivery, get_observer_hook_action, is_delivered_by_ordhook};
+    use crate::core::meta_protocols::brc20::predicate::{
+        set_brc20_predicate_filter, Brc20PredicateFilter,
+    };
+
+    #[test]
+    fn registers_filtered_predicates_with_noop() {
+        let action = HookAction::HttpPost(HttpHook {
+            url: "http://localhost:3700/payload".to_string(),
+            authorization_header: "Bearer test".to_string(),
+        });
+        let uuid = "live-deliveries-unfiltered";
+        assert!(matches!(
+            get_observer_hook_action(uuid, action.clone()),
+            HookAction::HttpPost(_)
+        ));
+        assert!(!is_delivered_by_ordhook(uuid));
+
+        let uuid = "live-deliveries-filtered";
+        set_brc20_predicate_filter(uuid, Some(Brc20PredicateFilter::default()));
+        assert!(matches!(
+            get_observer_hook_action(uuid, HookAction::Noop),
+            HookAction::Noop
+        ));
+        assert!(!is_delivered_by_ordhook(uuid));
+        assert!(matches!(
+            get_observer_hook_action(uuid, action),
+            HookAction::Noop
+        ));
+        assert!(is_delivered_by_ordhook(uuid));
+
+        forget_live_delivery(uuid);
+        set_brc20_predicate_filter(uuid, None);
+        assert!(!is_delivered_by_ordhook(uuid));
+    }
+}
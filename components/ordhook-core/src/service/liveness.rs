@@ -0,0 +1,545 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc::Sender, Arc, RwLock},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_sdk::{
+    chainhooks::types::{ChainhookFullSpecification, ChainhookSpecification, HookAction},
+    observer::ObserverCommand,
+    utils::Context,
+};
+use futures_util::StreamExt;
+use rusqlite::Connection;
+
+use crate::{
+    config::{Config, ObserverLivenessConfig},
+    service::{
+        confirmations::stop_confirmed_stream,
+        observers::{
+            build_catchup_specification, find_all_observers, find_dead_letter_errors_after,
+            find_last_dead_letter_id, find_predicate_paused_at, get_default_observers_db_file_path,
+            insert_predicate_pause_in_observers, open_readonly_observers_db_conn,
+            open_readwrite_observers_db_conn, remove_predicate_pause_from_observers,
+            update_observer_streaming_enabled,
+        },
+    },
+    try_info, try_warn,
+    utils::http::outbound_http_client_builder,
+};
+
+/// Endpoints probed at the same time by the liveness monitor.
+const LIVENESS_PROBE_CONCURRENCY: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PredicateHealthStatus {
+    Healthy,
+    /// The endpoint failed `max_consecutive_failures` probes in a row.
+    Degraded,
+    /// Degraded, with delivery paused until the endpoint recovers.
+    Paused,
+}
+
+/// Health of the endpoint of a predicate, served next to its status by the API.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PredicateHealth {
+    pub status: PredicateHealthStatus,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// The predicate is being deregistered from the observer to pause its delivery.
+    #[serde(skip)]
+    pause_pending: bool,
+}
+
+impl Default for PredicateHealth {
+    fn default() -> Self {
+        PredicateHealth {
+            status: PredicateHealthStatus::Healthy,
+            consecutive_failures: 0,
+            last_error: None,
+            pause_pending: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LivenessTransition {
+    Degraded,
+    Paused,
+    /// The endpoint answered again. `was_paused` predicates must have their delivery resumed.
+    Recovered {
+        was_paused: bool,
+    },
+}
+
+impl PredicateHealth {
+    pub fn observe_probe(
+        &mut self,
+        result: Result<(), String>,
+        config: &ObserverLivenessConfig,
+    ) -> Option<LivenessTransition> {
+        match result {
+            Ok(()) => {
+                let was_paused = self.status == PredicateHealthStatus::Paused;
+                let recovered = self.status != PredicateHealthStatus::Healthy;
+                *self = PredicateHealth::default();
+                recovered.then_some(LivenessTransition::Recovered { was_paused })
+            }
+            Err(e) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(e);
+                if self.status != PredicateHealthStatus::Healthy
+                    || self.consecutive_failures < config.max_consecutive_failures
+                {
+                    return None;
+                }
+                match config.pause_delivery {
+                    true => {
+                        self.status = PredicateHealthStatus::Paused;
+                        self.pause_pending = true;
+                        Some(LivenessTransition::Paused)
+                    }
+                    false => {
+                        self.status = PredicateHealthStatus::Degraded;
+                        Some(LivenessTransition::Degraded)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Health of the endpoints of the predicates of a service, by predicate uuid. Shared by the liveness monitor, the API
+/// and the handler of the observer events.
+#[derive(Clone, Debug, Default)]
+pub struct PredicateHealthRegistry(Arc<RwLock<HashMap<String, PredicateHealth>>>);
+
+impl PredicateHealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Health of the endpoint of a predicate. Predicates not probed are reported healthy.
+    pub fn get(&self, uuid: &str) -> PredicateHealth {
+        self.0
+            .read()
+            .ok()
+            .and_then(|health| health.get(uuid).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn forget(&self, uuid: &str) {
+        if let Ok(mut health) = self.0.write() {
+            health.remove(uuid);
+        }
+    }
+
+    fn retain(&self, endpoints: &[(String, String)]) {
+        if let Ok(mut health) = self.0.write() {
+            health.retain(|uuid, _| endpoints.iter().any(|(u, _)| u == uuid));
+        }
+    }
+
+    fn observe_probe(
+        &self,
+        uuid: &str,
+        result: Result<(), String>,
+        config: &ObserverLivenessConfig,
+    ) -> Option<LivenessTransition> {
+        let mut health = self.0.write().ok()?;
+        health
+            .entry(uuid.to_string())
+            .or_default()
+            .observe_probe(result, config)
+    }
+
+    fn take_pause_pending(&self, uuid: &str) -> bool {
+        let Ok(mut health) = self.0.write() else {
+            return false;
+        };
+        match health.get_mut(uuid) {
+            Some(health) if health.pause_pending => {
+                health.pause_pending = false;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if the deregistration of `uuid` was requested to pause its delivery, in which case the predicate must
+/// be kept in the observers db. Predicates paused through the API are recorded in the observers db before being
+/// deregistered, and unpaused before being deleted.
+pub fn acknowledge_predicate_pause(
+    uuid: &str,
+    predicate_health: &PredicateHealthRegistry,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) -> bool {
+    if find_predicate_paused_at(uuid, observers_db_conn, ctx).is_some() {
+        return true;
+    }
+    predicate_health.take_pause_pending(uuid)
+}
+
+/// Returns true if a predicate is paused through the API. Scans of a paused predicate stop before delivering their next
+/// block, and resume from the last block delivered once the predicate gets resumed.
+pub fn is_predicate_paused(uuid: &str, config: &Config, ctx: &Context) -> bool {
+    // Scans of predicate files run by the CLI have no observers db.
+    if !get_default_observers_db_file_path(config).exists() {
+        return false;
+    }
+    open_readonly_observers_db_conn(config, ctx)
+        .map(|observers_db_conn| find_predicate_paused_at(uuid, &observers_db_conn, ctx).is_some())
+        .unwrap_or(false)
+}
+
+/// Stops the delivery of a predicate until it gets resumed, across restarts. The predicate stays in the observers db
+/// with the last block delivered, which the delivery resumes from.
+pub fn pause_predicate(
+    uuid: &str,
+    predicate_health: &PredicateHealthRegistry,
+    observer_command_tx: &Sender<ObserverCommand>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    if find_predicate_paused_at(uuid, &observers_db_conn, ctx).is_some() {
+        return Err("Predicate already paused".into());
+    }
+    let paused_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    // Recorded before the deregistration, for the predicate to be kept in the observers db, and for the scans in
+    // progress to stop.
+    insert_predicate_pause_in_observers(uuid, paused_at, &observers_db_conn, ctx);
+    // Predicates already paused by the liveness monitor are not registered on the observer anymore.
+    if predicate_health.get(uuid).status != PredicateHealthStatus::Paused {
+        pause_predicate_delivery(uuid, observer_command_tx, config, ctx);
+    }
+    try_info!(ctx, "Predicate {uuid} paused");
+    Ok(())
+}
+
+/// Resumes the delivery of a predicate paused through the API, from the last block delivered before the pause. Blocks
+/// of predicates also paused by the liveness monitor are only replayed once their endpoint recovers.
+pub fn resume_predicate(
+    uuid: &str,
+    predicate_health: &PredicateHealthRegistry,
+    observer_command_tx: &Sender<ObserverCommand>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    if find_predicate_paused_at(uuid, &observers_db_conn, ctx).is_none() {
+        return Err("Predicate not paused".into());
+    }
+    remove_predicate_pause_from_observers(uuid, &observers_db_conn, ctx);
+    if predicate_health.get(uuid).status != PredicateHealthStatus::Paused {
+        resume_predicate_delivery(uuid, observer_command_tx, config, ctx);
+    }
+    try_info!(ctx, "Predicate {uuid} resumed");
+    Ok(())
+}
+
+/// Any response but a server error means the endpoint is alive: webhooks are not expected to handle `HEAD` requests.
+async fn probe_endpoint(client: &reqwest::Client, url: &str) -> Result<(), String> {
+    match client.head(url).send().await {
+        Ok(res) if res.status().is_server_error() => {
+            Err(format!("responded with {}", res.status()))
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("not reachable: {e}")),
+    }
+}
+
+fn pause_predicate_delivery(
+    uuid: &str,
+    observer_command_tx: &Sender<ObserverCommand>,
+    config: &Config,
+    ctx: &Context,
+) {
+    stop_confirmed_stream(uuid);
+    if let Ok(observers_db_conn) = open_readwrite_observers_db_conn(config, ctx) {
+        update_observer_streaming_enabled(uuid, false, &observers_db_conn, ctx);
+    }
+    let _ = observer_command_tx.send(ObserverCommand::DeregisterBitcoinPredicate(
+        uuid.to_string(),
+    ));
+}
+
+/// Registers a paused predicate again. Its blocks get replayed from the last one delivered before the pause.
+fn resume_predicate_delivery(
+    uuid: &str,
+    observer_command_tx: &Sender<ObserverCommand>,
+    config: &Config,
+    ctx: &Context,
+) {
+    let Ok(observers_db_conn) = open_readonly_observers_db_conn(config, ctx) else {
+        return;
+    };
+    let observer = find_all_observers(&observers_db_conn, ctx)
+        .into_iter()
+        .find(|(spec, _)| spec.uuid() == uuid);
+    let Some((ChainhookSpecification::Bitcoin(spec), report)) = observer else {
+        return;
+    };
+    let full_spec = build_catchup_specification(spec, &report, config);
+    let _ = observer_command_tx.send(ObserverCommand::RegisterPredicate(
+        ChainhookFullSpecification::Bitcoin(full_spec),
+    ));
+}
+
+/// Periodically probes the webhooks of the registered predicates, all at once. A round fails for a predicate if its
+/// endpoint fails the probe, or if a delivery to it failed since the previous round. Predicates failing consecutive
+/// rounds are marked `degraded`, and paused if `pause_delivery` is enabled: they are deregistered from the observer but
+/// kept in the observers db, and registered again once their endpoint recovers. Predicates paused through the API are
+/// not probed.
+pub fn start_observer_liveness_monitor(
+    config: &Config,
+    predicate_health: &PredicateHealthRegistry,
+    observer_command_tx: &Sender<ObserverCommand>,
+    ctx: &Context,
+) {
+    let Some(liveness_config) = config.observer_liveness.clone() else {
+        return;
+    };
+    let moved_config = config.clone();
+    let moved_predicate_health = predicate_health.clone();
+    let moved_observer_command_tx = observer_command_tx.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Observer liveness monitor")
+        .spawn(move || {
+            try_info!(moved_ctx, "Observer liveness monitor started");
+            let client =
+                match outbound_http_client_builder(&moved_config.resources).and_then(|builder| {
+                    builder
+                        .timeout(Duration::from_secs(
+                            moved_config.resources.webhook_timeout_secs,
+                        ))
+                        .build()
+                        .map_err(|e| format!("unable to build http client: {e}"))
+                }) {
+                    Ok(client) => client,
+                    Err(e) => {
+                        try_warn!(moved_ctx, "Observer liveness monitor: {e}");
+                        return;
+                    }
+                };
+            // Deliveries failed before the start were accounted by the previous run.
+            let mut last_dead_letter_id =
+                open_readonly_observers_db_conn(&moved_config, &moved_ctx)
+                    .map(|observers_db_conn| {
+                        find_last_dead_letter_id(&observers_db_conn, &moved_ctx)
+                    })
+                    .unwrap_or_default();
+            loop {
+                sleep(Duration::from_secs(liveness_config.probe_interval_secs));
+                let Ok(observers_db_conn) =
+                    open_readonly_observers_db_conn(&moved_config, &moved_ctx)
+                else {
+                    continue;
+                };
+                let mut endpoints = vec![];
+                for (spec, _) in find_all_observers(&observers_db_conn, &moved_ctx) {
+                    if find_predicate_paused_at(spec.uuid(), &observers_db_conn, &moved_ctx)
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    if let ChainhookSpecification::Bitcoin(spec) = spec {
+                        if let HookAction::HttpPost(http) = spec.action {
+                            endpoints.push((spec.uuid, http.url));
+                        }
+                    }
+                }
+                moved_predicate_health.retain(&endpoints);
+                let delivery_errors = find_dead_letter_errors_after(
+                    last_dead_letter_id,
+                    &observers_db_conn,
+                    &moved_ctx,
+                );
+                if let Some((id, _, _)) = delivery_errors.last() {
+                    last_dead_letter_id = *id;
+                }
+                let probes: Vec<Result<(), String>> = hiro_system_kit::nestable_block_on(
+                    futures_util::stream::iter(
+                        endpoints
+                            .iter()
+                            .map(|(_, url)| probe_endpoint(&client, url)),
+                    )
+                    .buffered(LIVENESS_PROBE_CONCURRENCY)
+                    .collect(),
+                );
+                for ((uuid, url), probe) in endpoints.into_iter().zip(probes.into_iter()) {
+                    let result = match delivery_errors.iter().rev().find(|(_, u, _)| *u == uuid) {
+                        Some((_, _, error)) => Err(format!("failed a delivery: {error}")),
+                        None => probe,
+                    };
+                    let transition = moved_predicate_health.observe_probe(
+                        &uuid,
+                        result.clone(),
+                        &liveness_config,
+                    );
+                    match transition {
+                        Some(LivenessTransition::Degraded) => {
+                            try_warn!(
+                                moved_ctx,
+                                "Predicate {uuid} degraded: {url} {}",
+                                result.unwrap_err()
+                            );
+                        }
+                        Some(LivenessTransition::Paused) => {
+                            try_warn!(
+                                moved_ctx,
+                                "Predicate {uuid} degraded, delivery paused: {url} {}",
+                                result.unwrap_err()
+                            );
+                            pause_predicate_delivery(
+                                &uuid,
+                                &moved_observer_command_tx,
+                                &moved_config,
+                                &moved_ctx,
+                            );
+                        }
+                        Some(LivenessTransition::Recovered { was_paused }) => {
+                            try_info!(moved_ctx, "Predicate {uuid} recovered: {url} is reachable");
+                            if was_paused {
+                                resume_predicate_delivery(
+                                    &uuid,
+                                    &moved_observer_command_tx,
+                                    &moved_config,
+                                    &moved_ctx,
+                                );
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+        })
+        .expect("unable to spawn thread");
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+
+    use chainhook_sdk::{observer::ObserverCommand, utils::Context};
+
+    use crate::{
+        config::{Config, ObserverLivenessConfig},
+        service::observers::{
+            delete_observers_db, find_predicate_paused_at, initialize_observers_db,
+        },
+    };
+
+    use super::{
+        acknowledge_predicate_pause, pause_predicate, resume_predicate, LivenessTransition,
+        PredicateHealth, PredicateHealthRegistry, PredicateHealthStatus,
+    };
+
+    fn liveness_config(pause_delivery: bool) -> ObserverLivenessConfig {
+        ObserverLivenessConfig {
+            probe_interval_secs: 60,
+            max_consecutive_failures: 2,
+            pause_delivery,
+        }
+    }
+
+    #[test]
+    fn degrades_after_consecutive_failures() {
+        let config = liveness_config(false);
+        let mut health = PredicateHealth::default();
+        assert_eq!(health.observe_probe(Err("timeout".into()), &config), None);
+        assert_eq!(health.observe_probe(Ok(()), &config), None);
+        assert_eq!(health.observe_probe(Err("timeout".into()), &config), None);
+        assert_eq!(
+            health.observe_probe(Err("timeout".into()), &config),
+            Some(LivenessTransition::Degraded)
+        );
+        assert_eq!(health.observe_probe(Err("timeout".into()), &config), None);
+        assert_eq!(health.consecutive_failures, 3);
+        assert_eq!(
+            health.observe_probe(Ok(()), &config),
+            Some(LivenessTransition::Recovered { was_paused: false })
+        );
+        assert_eq!(health.status, PredicateHealthStatus::Healthy);
+    }
+
+    #[test]
+    fn pauses_and_resumes_delivery() {
+        let config = liveness_config(true);
+        let mut health = PredicateHealth::default();
+        assert_eq!(health.observe_probe(Err("timeout".into()), &config), None);
+        assert_eq!(
+            health.observe_probe(Err("timeout".into()), &config),
+            Some(LivenessTransition::Paused)
+        );
+        assert_eq!(health.status, PredicateHealthStatus::Paused);
+        assert_eq!(
+            health.observe_probe(Ok(()), &config),
+            Some(LivenessTransition::Recovered { was_paused: true })
+        );
+    }
+
+    #[test]
+    fn tracks_paused_predicates() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        let observers_dir = std::env::temp_dir().join("ordhook_test_paused_predicates");
+        std::fs::create_dir_all(&observers_dir).unwrap();
+        config.storage.observers_working_dir = observers_dir.display().to_string();
+        delete_observers_db(&config);
+        let observers_db_conn = initialize_observers_db(&config, &ctx);
+        let (observer_command_tx, observer_command_rx) = channel();
+        let predicate_health = PredicateHealthRegistry::new();
+        let uuid = "paused-predicate";
+
+        assert_eq!(
+            find_predicate_paused_at(uuid, &observers_db_conn, &ctx),
+            None
+        );
+        assert!(!acknowledge_predicate_pause(
+            uuid,
+            &predicate_health,
+            &observers_db_conn,
+            &ctx
+        ));
+        pause_predicate(uuid, &predicate_health, &observer_command_tx, &config, &ctx).unwrap();
+        assert!(find_predicate_paused_at(uuid, &observers_db_conn, &ctx).is_some());
+        assert!(matches!(
+            observer_command_rx.try_recv(),
+            Ok(ObserverCommand::DeregisterBitcoinPredicate(_))
+        ));
+        // The deregistration requested by the pause keeps the predicate in the observers db.
+        assert!(acknowledge_predicate_pause(
+            uuid,
+            &predicate_health,
+            &observers_db_conn,
+            &ctx
+        ));
+        assert!(
+            pause_predicate(uuid, &predicate_health, &observer_command_tx, &config, &ctx).is_err()
+        );
+
+        resume_predicate(uuid, &predicate_health, &observer_command_tx, &config, &ctx).unwrap();
+        assert_eq!(
+            find_predicate_paused_at(uuid, &observers_db_conn, &ctx),
+            None
+        );
+        assert!(!acknowledge_predicate_pause(
+            uuid,
+            &predicate_health,
+            &observers_db_conn,
+            &ctx
+        ));
+        assert!(
+            resume_predicate(uuid, &predicate_health, &observer_command_tx, &config, &ctx).is_err()
+        );
+        delete_observers_db(&config);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    path::PathBuf,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::{Config, MaintenanceConfig},
+    db::{
+        blocks::open_readwrite_blocks_db,
+        cold_blocks::{tier_cold_blocks, COLD_BLOCKS_MAINTENANCE_BATCH_SIZE},
+        ordinals::create_or_open_readwrite_db,
+        recovery::WORKING_DIR_SQLITE_DBS,
+    },
+    service::usage::get_api_calls_since_startup,
+    try_info, try_warn,
+    utils::{bitcoind::bitcoind_try_get_block_height, monitoring::PrometheusMonitoring},
+};
+
+/// Interval between two checks of the conditions of a maintenance run. API traffic is measured over this interval.
+const MAINTENANCE_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Pages sampled by ANALYZE per index, bounding its duration on large databases.
+const ANALYSIS_LIMIT: u32 = 1_000;
+
+fn current_minute_of_day() -> u16 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    ((now % 86_400) / 60) as u16
+}
+
+/// Maintenance runs when the API sees little traffic, and either the indexer is at the chain tip or the time is within
+/// one of the configured windows. `tip_lag` is unknown when bitcoind can't be reached.
+pub fn is_maintenance_due(
+    config: &MaintenanceConfig,
+    api_calls_per_minute: u64,
+    tip_lag: Option<u64>,
+    minute_of_day: u16,
+) -> bool {
+    if api_calls_per_minute > config.max_api_calls_per_minute {
+        return false;
+    }
+    tip_lag == Some(0)
+        || config
+            .windows
+            .iter()
+            .any(|window| window.contains(minute_of_day))
+}
+
+/// Reclaims the free pages of a SQLite database and refreshes the statistics of its query planner.
+fn optimize_sqlite_db(db_path: &PathBuf, ctx: &Context) -> Result<(), String> {
+    let conn = create_or_open_readwrite_db(Some(db_path), ctx);
+    let auto_vacuum: i64 = conn
+        .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+        .map_err(|e| format!("unable to read auto_vacuum of {}: {e}", db_path.display()))?;
+    // Databases created before incremental vacuuming was enabled need a one-off VACUUM, which requires downtime.
+    if auto_vacuum == 2 {
+        let mut stmt = conn
+            .prepare("PRAGMA incremental_vacuum")
+            .map_err(|e| format!("unable to vacuum {}: {e}", db_path.display()))?;
+        let mut rows = stmt
+            .query([])
+            .map_err(|e| format!("unable to vacuum {}: {e}", db_path.display()))?;
+        // Pages are freed as the statement is stepped through.
+        while rows
+            .next()
+            .map_err(|e| format!("unable to vacuum {}: {e}", db_path.display()))?
+            .is_some()
+        {}
+    }
+    conn.pragma_update(None, "analysis_limit", ANALYSIS_LIMIT)
+        .map_err(|e| format!("unable to analyze {}: {e}", db_path.display()))?;
+    conn.execute_batch("ANALYZE")
+        .map_err(|e| format!("unable to analyze {}: {e}", db_path.display()))?;
+    Ok(())
+}
+
+/// Vacuums and analyzes the SQLite databases, moves the blocks due to cold storage, and compacts the blocks db. Runs
+/// alongside the indexer: writes wait for each other.
+pub fn run_maintenance(config: &Config, ctx: &Context) {
+    let sqlite_dir = config.expected_sqlite_path();
+    for db_name in WORKING_DIR_SQLITE_DBS {
+        let db_path = sqlite_dir.join(db_name);
+        if !db_path.exists() {
+            continue;
+        }
+        let started_at = Instant::now();
+        match optimize_sqlite_db(&db_path, ctx) {
+            Ok(()) => try_info!(
+                ctx,
+                "Maintenance: optimized {} in {:?}",
+                db_path.display(),
+                started_at.elapsed()
+            ),
+            Err(e) => try_warn!(ctx, "Maintenance: {e}"),
+        }
+    }
+    let started_at = Instant::now();
+    match open_readwrite_blocks_db(config, ctx) {
+        Ok(blocks_db) => {
+            // Moves the whole backlog, which the next compaction then reclaims. Batches let the indexer move blocks
+            // in between.
+            while tier_cold_blocks(COLD_BLOCKS_MAINTENANCE_BATCH_SIZE, &blocks_db, config, ctx)
+                == COLD_BLOCKS_MAINTENANCE_BATCH_SIZE
+            {}
+            blocks_db.compact_range(None::<&[u8]>, None::<&[u8]>);
+            try_info!(
+                ctx,
+                "Maintenance: compacted hord.rocksdb in {:?}",
+                started_at.elapsed()
+            );
+        }
+        // The indexer holds the blocks db while it writes blocks: compaction is retried on the next run.
+        Err(e) => try_warn!(ctx, "Maintenance: skipping compaction, {}", e),
+    }
+}
+
+/// Periodically checks the traffic of the API and the lag of the indexer, and runs the maintenance of the databases
+/// once due.
+pub fn start_maintenance_scheduler(
+    config: &Config,
+    prometheus: &PrometheusMonitoring,
+    ctx: &Context,
+) {
+    let Some(maintenance_config) = config.maintenance.clone() else {
+        return;
+    };
+    let moved_config = config.clone();
+    let moved_prometheus = prometheus.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Maintenance scheduler")
+        .spawn(move || {
+            try_info!(moved_ctx, "Maintenance scheduler started");
+            let mut last_run: Option<Instant> = None;
+            let mut api_calls = get_api_calls_since_startup();
+            loop {
+                sleep(Duration::from_secs(MAINTENANCE_CHECK_INTERVAL_SECS));
+                let total_api_calls = get_api_calls_since_startup();
+                let api_calls_per_minute = total_api_calls.saturating_sub(api_calls);
+                api_calls = total_api_calls;
+                if last_run.map_or(false, |last_run| {
+                    last_run.elapsed() < Duration::from_secs(maintenance_config.interval_secs)
+                }) {
+                    continue;
+                }
+                let tip_lag = bitcoind_try_get_block_height(&moved_config)
+                    .ok()
+                    .map(|height| {
+                        height.saturating_sub(moved_prometheus.last_indexed_block_height.get())
+                    });
+                if !is_maintenance_due(
+                    &maintenance_config,
+                    api_calls_per_minute,
+                    tip_lag,
+                    current_minute_of_day(),
+                ) {
+                    continue;
+                }
+                try_info!(
+                    moved_ctx,
+                    "Maintenance: starting ({api_calls_per_minute} API calls in the last minute)"
+                );
+                run_maintenance(&moved_config, &moved_ctx);
+                last_run = Some(Instant::now());
+            }
+        })
+        .expect("unable to spawn thread");
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{DailyWindow, MaintenanceConfig};
+
+    use super::is_maintenance_due;
+
+    #[test]
+    fn schedules_maintenance_in_low_traffic() {
+        let config = MaintenanceConfig {
+            windows: vec![DailyWindow::parse("23:30-02:00").unwrap()],
+            max_api_calls_per_minute: 60,
+            interval_secs: 86_400,
+        };
+        assert_eq!(
+            config.windows[0],
+            DailyWindow {
+                start_minute: 23 * 60 + 30,
+                end_minute: 120,
+            }
+        );
+        assert!(DailyWindow::parse("02:00").is_err());
+        assert!(DailyWindow::parse("24:00-02:00").is_err());
+
+        // At the tip, whatever the time.
+        assert!(is_maintenance_due(&config, 10, Some(0), 12 * 60));
+        // Behind the tip, or with bitcoind unreachable, within the windows only.
+        assert!(!is_maintenance_due(&config, 10, Some(3), 12 * 60));
+        assert!(is_maintenance_due(&config, 10, Some(3), 0));
+        assert!(is_maintenance_due(&config, 10, None, 23 * 60 + 45));
+        assert!(!is_maintenance_due(&config, 10, None, 120));
+        // Never under high traffic.
+        assert!(!is_maintenance_due(&config, 100, Some(0), 0));
+    }
+}
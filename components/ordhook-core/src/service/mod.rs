@@ -1,24 +1,62 @@
+pub mod alerts;
+pub mod amendments;
+pub mod blocklist;
+pub mod confirmations;
+pub mod dead_letters;
+pub mod enrichment;
+pub mod event_queue;
+#[cfg(feature = "http-api")]
 mod http_api;
+#[cfg(feature = "ingestion-guard")]
+pub mod ingestion_guard;
+pub mod jobs;
+pub mod live_deliveries;
+pub mod liveness;
+pub mod maintenance;
+pub mod native_ingestion;
 pub mod observers;
+pub mod predicate_versions;
+pub mod psbt;
+pub mod query_cache;
+pub mod read_through;
+pub mod replay_log;
+pub mod rescans;
+pub mod rollbacks;
 mod runloops;
+pub mod sales;
+pub mod schemas;
+pub mod status;
+pub mod tenants;
+pub mod usage;
+pub mod utxos;
+pub mod wallets;
+pub mod webhooks;
 
-use crate::config::{Config, PredicatesApi};
+use crate::config::validation::{validate_config, DiagnosticSeverity, ValidationStage};
+use crate::config::{BlockIngestion, Config, PredicatesApi};
 use crate::core::meta_protocols::brc20::brc20_activation_height;
 use crate::core::meta_protocols::brc20::cache::{brc20_new_cache, Brc20MemoryCache};
-use crate::core::meta_protocols::brc20::db::write_augmented_block_to_brc20_db;
+use crate::core::meta_protocols::brc20::db::{
+    insert_rejected_operation, write_augmented_block_to_brc20_db, Brc20DbRejectedOperationRow,
+};
 use crate::core::meta_protocols::brc20::parser::ParsedBrc20Operation;
 use crate::core::meta_protocols::brc20::verifier::{
     verify_brc20_operation, verify_brc20_transfer, VerifiedBrc20Operation,
 };
+use crate::core::meta_protocols::indexer::index_block_with_metaprotocol_indexers;
+use crate::core::meta_protocols::runes::db::index_runes_in_block;
+use crate::core::meta_protocols::sns::db::index_sns_names_in_block;
 use crate::core::pipeline::bitcoind_download_blocks;
 use crate::core::pipeline::processors::block_archiving::start_block_archiving_processor;
 use crate::core::pipeline::processors::inscription_indexing::process_block;
 use crate::core::pipeline::processors::start_inscription_indexing_processor;
 use crate::core::pipeline::processors::transfers_recomputing::start_transfers_recomputing_processor;
+use crate::core::protocol::index_commitment::update_index_commitment_with_block;
 use crate::core::protocol::inscription_parsing::{
     get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
 };
-use crate::core::protocol::inscription_sequencing::SequenceCursor;
+use crate::core::protocol::inscription_sequencing::{get_jubilee_block_height, SequenceCursor};
+use crate::core::protocol::satributes::load_satribute_ranges;
 use crate::core::{
     first_inscription_height, new_traversals_lazy_cache, should_sync_ordhook_db,
     should_sync_rocks_db,
@@ -26,18 +64,57 @@ use crate::core::{
 use crate::db::blocks::{
     find_missing_blocks, insert_entry_in_blocks, open_blocks_db_with_retry, run_compaction,
 };
+use crate::db::cold_blocks::{tier_cold_blocks, COLD_BLOCKS_BATCH_SIZE};
 use crate::db::cursor::{BlockBytesCursor, TransactionBytesCursor};
 use crate::db::ordinals::{
-    find_latest_inscription_block_height, get_latest_indexed_inscription_number, open_ordinals_db,
-    update_ordinals_db_with_block, update_sequence_metadata_with_block,
+    find_latest_inscription_block_height, get_latest_indexed_inscription_number,
+    insert_block_events_hash, insert_reorg_event, open_ordinals_db, update_ordinals_db_with_block,
+    update_sequence_metadata_with_block,
+};
+use crate::db::sales::index_sales_in_block;
+use crate::db::sat_ranges::{
+    catch_up_sat_ranges_db, index_sat_ranges_in_block, resolve_sat_ranges_addresses,
+};
+use crate::db::{check_dbs_network, drop_block_data_from_all_dbs, open_all_dbs_rw};
+use crate::error::OrdhookError;
+use crate::scan::bitcoin::{process_block_with_predicates, process_rollback_with_predicates};
+use crate::service::alerts::{check_reorg_depth, send_alert, start_alerts_monitor};
+use crate::service::amendments::{get_rolled_back_block_amendments, send_amendment_events};
+use crate::service::confirmations::{
+    on_block_rolled_back, on_chain_tip_updated, set_confirmed_streams_scan_op_tx,
+};
+use crate::service::enrichment::EnrichmentDbConnections;
+#[cfg(feature = "ingestion-guard")]
+use crate::service::ingestion_guard::start_ingestion_guard_thread;
+use crate::service::jobs::fail_interrupted_jobs;
+use crate::service::live_deliveries::start_live_deliveries_worker;
+#[cfg(feature = "http-api")]
+use crate::service::liveness::{start_observer_liveness_monitor, PredicateHealthRegistry};
+use crate::service::maintenance::start_maintenance_scheduler;
+use crate::service::native_ingestion::{
+    start_native_block_ingestion, NATIVE_INGESTION_MAX_REORG_DEPTH,
 };
-use crate::db::{drop_block_data_from_all_dbs, open_all_dbs_rw};
-use crate::scan::bitcoin::process_block_with_predicates;
 use crate::service::observers::create_and_consolidate_chainhook_config_with_predicates;
+use crate::service::query_cache::{clear_query_cache, invalidate_cached_responses_of_block};
+use crate::service::replay_log::{
+    list_replay_log_entries, read_replay_log_entry, record_replay_log_entry, ReplayLogEntry,
+};
+use crate::service::rollbacks::{get_block_rollback, send_rollback_events};
 use crate::service::runloops::start_bitcoin_scan_runloop;
+use crate::service::sales::send_sale_detected_events;
+use crate::service::usage::start_usage_accounting;
+use crate::service::wallets::start_wallet_watching_worker;
 use crate::utils::bitcoind::bitcoind_wait_for_chain_tip;
-use crate::utils::monitoring::{start_serving_prometheus_metrics, PrometheusMonitoring};
-use crate::{try_debug, try_error, try_info};
+use crate::utils::content_scanning::{enqueue_block_content_scans, start_content_scanning_worker};
+use crate::utils::event_transforms::load_event_transforms;
+use crate::utils::logger::LogLevels;
+use crate::utils::monitoring::{
+    start_serving_prometheus_metrics, start_serving_prometheus_metrics_over_unix_socket,
+    PrometheusMonitoring, PIPELINE_METRICS,
+};
+use crate::utils::previews::{enqueue_block_previews, start_previews_worker};
+use crate::utils::profiler::BlockProfiler;
+use crate::{try_debug, try_error, try_info, try_warn};
 use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookOccurrencePayload;
 use chainhook_sdk::chainhooks::types::{
     BitcoinChainhookSpecification, ChainhookConfig, ChainhookFullSpecification,
@@ -53,14 +130,16 @@ use chainhook_sdk::types::{
 };
 use chainhook_sdk::utils::{BlockHeights, Context};
 use crossbeam_channel::unbounded;
-use crossbeam_channel::{select, Sender};
+use crossbeam_channel::{never, select, Sender};
 use dashmap::DashMap;
 use fxhash::FxHasher;
+#[cfg(feature = "http-api")]
 use http_api::start_observers_http_server;
 use rusqlite::Transaction;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::hash::BuildHasherDefault;
+use std::net::SocketAddr;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 
@@ -68,6 +147,11 @@ pub struct Service {
     pub prometheus: PrometheusMonitoring,
     pub config: Config,
     pub ctx: Context,
+    /// Levels of the logger of `ctx`, adjusted by the API. Not set when the logger is not wrapped by
+    /// `with_runtime_log_levels`.
+    pub log_levels: Option<LogLevels>,
+    /// Health of the endpoints of the predicates, tracked by the liveness monitor.
+    pub predicate_health: PredicateHealthRegistry,
 }
 
 impl Service {
@@ -76,9 +160,16 @@ impl Service {
             prometheus: PrometheusMonitoring::new(),
             config,
             ctx,
+            log_levels: None,
+            predicate_health: PredicateHealthRegistry::new(),
         }
     }
 
+    pub fn with_log_levels(mut self, log_levels: LogLevels) -> Self {
+        self.log_levels = Some(log_levels);
+        self
+    }
+
     pub async fn run(
         &mut self,
         observer_specs: Vec<BitcoinChainhookSpecification>,
@@ -87,20 +178,77 @@ impl Service {
         >,
         check_blocks_integrity: bool,
         stream_indexing_to_observers: bool,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
+        if self.config.network.block_ingestion == BlockIngestion::Observer {
+            self.config.expected_bitcoin_block_signaling()?;
+        }
+        let mut config_errors = vec![];
+        for diagnostic in
+            validate_config(&self.config, ValidationStage::ServiceStart, &self.ctx).await
+        {
+            match diagnostic.severity {
+                DiagnosticSeverity::Error => config_errors.push(diagnostic.to_string()),
+                DiagnosticSeverity::Warning => try_warn!(self.ctx, "Config: {diagnostic}"),
+            }
+        }
+        if !config_errors.is_empty() {
+            return Err(OrdhookError::Config(format!(
+                "invalid configuration, run `ordhook config validate` for details:\n{}",
+                config_errors.join("\n")
+            )));
+        }
+        check_dbs_network(&self.config, &self.ctx)?;
         // Start Prometheus monitoring server.
         if let Some(port) = self.config.network.prometheus_monitoring_port {
+            let addr = SocketAddr::new(self.config.network.prometheus_monitoring_address, port);
             let registry_moved = self.prometheus.registry.clone();
             let ctx_cloned = self.ctx.clone();
             let _ = std::thread::spawn(move || {
                 let _ = hiro_system_kit::nestable_block_on(start_serving_prometheus_metrics(
-                    port,
+                    addr,
                     registry_moved,
                     ctx_cloned,
                 ));
             });
         }
-        let ordhook_db = open_ordinals_db(&self.config.expected_cache_path(), &self.ctx)
+        if let Some(ref socket) = self.config.network.prometheus_monitoring_unix_socket {
+            let socket_moved = socket.clone();
+            let registry_moved = self.prometheus.registry.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(
+                    start_serving_prometheus_metrics_over_unix_socket(
+                        socket_moved,
+                        registry_moved,
+                        ctx_cloned,
+                    ),
+                );
+            });
+        }
+        start_alerts_monitor(&self.config, &self.prometheus, &self.ctx);
+        start_usage_accounting(&self.config, &self.ctx);
+        start_maintenance_scheduler(&self.config, &self.prometheus, &self.ctx);
+        fail_interrupted_jobs(&self.config, &self.ctx);
+        load_event_transforms(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        load_satribute_ranges(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        start_previews_worker(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        start_content_scanning_worker(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        start_wallet_watching_worker(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        start_live_deliveries_worker(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
+        if self.config.network.block_ingestion == BlockIngestion::Observer {
+            #[cfg(feature = "ingestion-guard")]
+            start_ingestion_guard_thread(&self.config, &self.prometheus, &self.ctx)
+                .map_err(OrdhookError::Config)?;
+            #[cfg(not(feature = "ingestion-guard"))]
+            if self.config.network.ingestion_guard.is_some() {
+                return Err(OrdhookError::Config(
+                    "network.ingestion_guard: ordhook was built without the ingestion-guard feature"
+                        .to_string(),
+                ));
+            }
+        }
+
+        let ordhook_db = open_ordinals_db(&self.config.expected_sqlite_path(), &self.ctx)
             .expect("unable to retrieve ordhook db");
         self.prometheus.initialize(
             0,
@@ -116,10 +264,13 @@ impl Service {
             for mut observer_spec in specs.into_iter() {
                 observer_spec.enabled = true;
                 let spec = ChainhookSpecification::Bitcoin(observer_spec);
-                chainhook_config.register_specification(spec)?;
+                chainhook_config
+                    .register_specification(spec)
+                    .map_err(OrdhookError::Config)?;
             }
             event_observer_config.chainhook_config = Some(chainhook_config);
-            let block_tx = start_observer_forwarding(&event_observer_config, &self.ctx);
+            let block_tx =
+                start_observer_forwarding(&event_observer_config, &self.config, &self.ctx);
             Some(block_tx)
         } else {
             None
@@ -129,6 +280,15 @@ impl Service {
         }
         self.catch_up_to_bitcoin_chain_tip(block_post_processor)
             .await?;
+        if let BlockIngestion::Native { poll_interval_ms } = self.config.network.block_ingestion {
+            return self
+                .run_with_native_block_ingestion(
+                    observer_specs,
+                    event_observer_config,
+                    poll_interval_ms,
+                )
+                .await;
+        }
         try_info!(self.ctx, "Service: Streaming blocks start");
 
         // Sidecar channels setup
@@ -184,6 +344,61 @@ impl Service {
         Ok(())
     }
 
+    /// Keeps indexing blocks polled from bitcoind, without chainhook-sdk's event observer. The predicates provided and
+    /// the ones previously registered are evaluated against new blocks, but predicates can't be registered at runtime:
+    /// the HTTP predicates API is not started, and predicates lagging behind the chain tip are not replayed.
+    async fn run_with_native_block_ingestion(
+        &self,
+        observer_specs: Vec<BitcoinChainhookSpecification>,
+        mut event_observer_config: EventObserverConfig,
+        poll_interval_ms: u64,
+    ) -> Result<(), OrdhookError> {
+        try_info!(self.ctx, "Service: Native block ingestion start");
+        if let PredicatesApi::On(_) = self.config.http_api {
+            try_warn!(
+                self.ctx,
+                "Service: HTTP predicates API is not available with native block ingestion"
+            );
+        }
+        let ordhook_db = open_ordinals_db(&self.config.expected_sqlite_path(), &self.ctx)?;
+        let (chainhook_config, outdated_observers) =
+            create_and_consolidate_chainhook_config_with_predicates(
+                observer_specs,
+                find_latest_inscription_block_height(&ordhook_db, &self.ctx)?.unwrap_or(0),
+                false,
+                &self.prometheus,
+                &self.config,
+                &self.ctx,
+            )?;
+        for outdated_observer_spec in outdated_observers.iter() {
+            try_warn!(
+                self.ctx,
+                "Service: Predicate {} is behind the chain tip and won't be replayed with native block ingestion",
+                outdated_observer_spec.uuid
+            );
+        }
+        let (block_post_processor, predicate_rollbacks) =
+            match chainhook_config.bitcoin_chainhooks.is_empty() {
+                true => (None, None),
+                false => {
+                    event_observer_config.chainhook_config = Some(chainhook_config);
+                    let (blocks_tx, rollbacks_tx) = start_native_observer_forwarding(
+                        &event_observer_config,
+                        &self.config,
+                        &self.ctx,
+                    );
+                    (Some(blocks_tx), Some(rollbacks_tx))
+                }
+            };
+        start_native_block_ingestion(
+            self,
+            block_post_processor,
+            predicate_rollbacks,
+            poll_interval_ms,
+        )
+        .await
+    }
+
     // TODO: Deprecated? Only used by ordhook-sdk-js.
     pub async fn start_event_observer(
         &mut self,
@@ -193,8 +408,9 @@ impl Service {
             std::sync::mpsc::Sender<ObserverCommand>,
             crossbeam_channel::Receiver<ObserverEvent>,
         ),
-        String,
+        OrdhookError,
     > {
+        self.config.expected_bitcoin_block_signaling()?;
         let mut event_observer_config = self.config.get_event_observer_config();
         let (chainhook_config, _) = create_and_consolidate_chainhook_config_with_predicates(
             vec![],
@@ -238,7 +454,7 @@ impl Service {
         predicate_activity_relayer: Option<
             crossbeam_channel::Sender<BitcoinChainhookOccurrencePayload>,
         >,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         loop {
             let event = match observer_event_rx.recv() {
                 Ok(cmd) => cmd,
@@ -276,8 +492,9 @@ impl Service {
         _predicate_activity_relayer: Option<
             crossbeam_channel::Sender<BitcoinChainhookOccurrencePayload>,
         >,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         let (bitcoin_scan_op_tx, bitcoin_scan_op_rx) = crossbeam_channel::unbounded();
+        set_confirmed_streams_scan_op_tx(bitcoin_scan_op_tx.clone());
         let ctx = self.ctx.clone();
         let config = self.config.clone();
         let observer_command_tx_moved = observer_command_tx.clone();
@@ -293,21 +510,42 @@ impl Service {
             .expect("unable to spawn thread");
 
         if let PredicatesApi::On(_) = self.config.http_api {
-            let moved_config = self.config.clone();
-            let moved_ctx = self.ctx.clone();
-            let moved_observer_commands_tx = observer_command_tx.clone();
-            let moved_observer_event_rx = observer_event_rx.clone();
-            let moved_prometheus = self.prometheus.clone();
-            let _ = hiro_system_kit::thread_named("HTTP Observers API").spawn(move || {
-                let _ = hiro_system_kit::nestable_block_on(start_observers_http_server(
-                    &moved_config,
-                    &moved_observer_commands_tx,
-                    moved_observer_event_rx,
-                    bitcoin_scan_op_tx,
-                    &moved_prometheus,
-                    &moved_ctx,
-                ));
-            });
+            #[cfg(feature = "http-api")]
+            {
+                let moved_config = self.config.clone();
+                let moved_ctx = self.ctx.clone();
+                let moved_observer_commands_tx = observer_command_tx.clone();
+                let moved_observer_event_rx = observer_event_rx.clone();
+                let moved_prometheus = self.prometheus.clone();
+                let moved_log_levels = self.log_levels.clone();
+                let moved_predicate_health = self.predicate_health.clone();
+                start_observer_liveness_monitor(
+                    &self.config,
+                    &self.predicate_health,
+                    observer_command_tx,
+                    &self.ctx,
+                );
+                let _ = hiro_system_kit::thread_named("HTTP Observers API").spawn(move || {
+                    let _ = hiro_system_kit::nestable_block_on(start_observers_http_server(
+                        &moved_config,
+                        &moved_observer_commands_tx,
+                        moved_observer_event_rx,
+                        bitcoin_scan_op_tx,
+                        &moved_prometheus,
+                        moved_log_levels,
+                        &moved_predicate_health,
+                        &moved_ctx,
+                    ));
+                });
+            }
+            #[cfg(not(feature = "http-api"))]
+            {
+                let _ = bitcoin_scan_op_tx;
+                try_error!(
+                    self.ctx,
+                    "HTTP API not started: ordhook was built without the http-api feature"
+                );
+            }
         }
 
         // Block the main thread indefinitely until the chainhook-sdk channel is closed.
@@ -341,7 +579,7 @@ impl Service {
             EventObserverConfig,
             Option<crossbeam_channel::Receiver<DataHandlerEvent>>,
         ),
-        String,
+        OrdhookError,
     > {
         let mut event_observer_config = self.config.get_event_observer_config();
         let (chainhook_config, _) = create_and_consolidate_chainhook_config_with_predicates(
@@ -363,7 +601,7 @@ impl Service {
         Ok((event_observer_config, data_rx))
     }
 
-    pub fn set_up_observer_sidecar_runloop(&self) -> Result<ObserverSidecar, String> {
+    pub fn set_up_observer_sidecar_runloop(&self) -> Result<ObserverSidecar, OrdhookError> {
         let (block_mutator_in_tx, block_mutator_in_rx) = crossbeam_channel::unbounded();
         let (block_mutator_out_tx, block_mutator_out_rx) = crossbeam_channel::unbounded();
         let (chain_event_notifier_tx, chain_event_notifier_rx) = crossbeam_channel::unbounded();
@@ -381,6 +619,16 @@ impl Service {
             select! {
                 recv(block_mutator_in_rx) -> msg => {
                     if let Ok((mut blocks_to_mutate, blocks_ids_to_rollback)) = msg {
+                        record_replay_log_entry(
+                            || {
+                                ReplayLogEntry::from_blocks_to_mutate(
+                                    &blocks_to_mutate,
+                                    &blocks_ids_to_rollback,
+                                )
+                            },
+                            &config,
+                            &ctx,
+                        );
                         chainhook_sidecar_mutate_blocks(
                             &mut blocks_to_mutate,
                             &blocks_ids_to_rollback,
@@ -395,6 +643,11 @@ impl Service {
                 }
                 recv(chain_event_notifier_rx) -> msg => {
                     if let Ok(command) = msg {
+                        record_replay_log_entry(
+                            || ReplayLogEntry::from_handle_block(&command),
+                            &config,
+                            &ctx,
+                        );
                         chainhook_sidecar_mutate_ordhook_db(command, &config, &ctx)
                     }
                 }
@@ -404,19 +657,19 @@ impl Service {
         Ok(observer_sidecar)
     }
 
-    pub async fn check_blocks_db_integrity(&mut self) -> Result<(), String> {
+    pub async fn check_blocks_db_integrity(&mut self) -> Result<(), OrdhookError> {
         bitcoind_wait_for_chain_tip(&self.config, &self.ctx);
         let (tip, missing_blocks) = {
             let blocks_db = open_blocks_db_with_retry(false, &self.config, &self.ctx);
 
-            let ordhook_db = open_ordinals_db(&self.config.expected_cache_path(), &self.ctx)
+            let ordhook_db = open_ordinals_db(&self.config.expected_sqlite_path(), &self.ctx)
                 .expect("unable to retrieve ordhook db");
             let tip = find_latest_inscription_block_height(&ordhook_db, &self.ctx)?.unwrap() as u32;
             info!(
                 self.ctx.expect_logger(),
                 "Checking database integrity up to block #{tip}",
             );
-            let missing_blocks = find_missing_blocks(&blocks_db, 0, tip, &self.ctx);
+            let missing_blocks = find_missing_blocks(&blocks_db, 0, tip, &self.config, &self.ctx);
             (tip, missing_blocks)
         };
         if !missing_blocks.is_empty() {
@@ -447,10 +700,20 @@ impl Service {
     pub async fn catch_up_to_bitcoin_chain_tip(
         &self,
         block_post_processor: Option<crossbeam_channel::Sender<BitcoinBlockData>>,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
+        load_satribute_ranges(&self.config, &self.ctx).map_err(OrdhookError::Config)?;
         // 0: Make sure bitcoind is synchronized.
         bitcoind_wait_for_chain_tip(&self.config, &self.ctx);
+        self.index_blocks_up_to_bitcoin_chain_tip(block_post_processor)
+            .await
+    }
 
+    /// Downloads and indexes the blocks bitcoind has and ordhook hasn't indexed yet, without checking first if bitcoind
+    /// is itself synchronized.
+    pub async fn index_blocks_up_to_bitcoin_chain_tip(
+        &self,
+        block_post_processor: Option<crossbeam_channel::Sender<BitcoinBlockData>>,
+    ) -> Result<(), OrdhookError> {
         // 1: Catch up blocks DB so it is at least at the same height as the ordinals DB.
         if let Some((start_block, end_block)) = should_sync_rocks_db(&self.config, &self.ctx)? {
             let blocks_post_processor = start_block_archiving_processor(
@@ -465,7 +728,7 @@ impl Service {
             );
             let blocks = BlockHeights::BlockRange(start_block, end_block)
                 .get_sorted_entries()
-                .map_err(|_e| format!("Block start / end block spec invalid"))?;
+                .map_err(|_e| OrdhookError::Parse("Block start / end block spec invalid".into()))?;
             bitcoind_download_blocks(
                 &self.config,
                 blocks.into(),
@@ -486,6 +749,11 @@ impl Service {
             if last_block_processed == end_block {
                 break;
             }
+            // Sat ranges are indexed from genesis: blocks already in the blocks DB are indexed from there.
+            if start_block > 0 {
+                catch_up_sat_ranges_db(start_block - 1, &self.config, &self.ctx)
+                    .map_err(OrdhookError::Db)?;
+            }
             let blocks_post_processor = start_inscription_indexing_processor(
                 &self.config,
                 &self.ctx,
@@ -498,7 +766,7 @@ impl Service {
             );
             let blocks = BlockHeights::BlockRange(start_block, end_block)
                 .get_sorted_entries()
-                .map_err(|_e| format!("Block start / end block spec invalid"))?;
+                .map_err(|_e| OrdhookError::Parse("Block start / end block spec invalid".into()))?;
             bitcoind_download_blocks(
                 &self.config,
                 blocks.into(),
@@ -511,15 +779,80 @@ impl Service {
             last_block_processed = end_block;
         }
 
+        if self.config.storage.index_scope.tracks_sat_ranges() {
+            let inscriptions_db_conn =
+                open_ordinals_db(&self.config.expected_sqlite_path(), &self.ctx)?;
+            if let Some(block_height) =
+                find_latest_inscription_block_height(&inscriptions_db_conn, &self.ctx)?
+            {
+                catch_up_sat_ranges_db(block_height, &self.config, &self.ctx)
+                    .map_err(OrdhookError::Db)?;
+            }
+            resolve_sat_ranges_addresses(&self.config, &self.ctx)
+                .await
+                .map_err(OrdhookError::Db)?;
+        }
+
         try_info!(self.ctx, "Service: Index has reached bitcoin chain tip");
         Ok(())
     }
 
+    /// Feeds the payloads recorded in the replay log, from sequence `from` to `to`, to the sidecar handlers, in the order
+    /// they were received. Meant to reproduce issues on a copy of the index.
+    pub fn replay_received_payloads(&self, from: u64, to: u64) -> Result<u64, OrdhookError> {
+        let Some(replay_log_path) = self.config.expected_replay_log_path() else {
+            return Err(OrdhookError::Config("replay_log is not configured".into()));
+        };
+        let cache_l2 = Arc::new(new_traversals_lazy_cache(100_000));
+        let mut brc20_cache = brc20_new_cache(&self.config);
+        let mut replayed = 0;
+        for (sequence, path) in
+            list_replay_log_entries(&replay_log_path).map_err(OrdhookError::Config)?
+        {
+            if sequence < from || sequence > to {
+                continue;
+            }
+            try_info!(self.ctx, "Replay log: replaying entry #{sequence}");
+            match read_replay_log_entry(&path).map_err(OrdhookError::Parse)? {
+                ReplayLogEntry::MutateBlocks { blocks, rollbacks } => {
+                    let mut blocks_to_mutate = blocks
+                        .into_iter()
+                        .map(|entry| BitcoinBlockDataCached {
+                            block: entry.block,
+                            processed_by_sidecar: entry.processed_by_sidecar,
+                        })
+                        .collect();
+                    chainhook_sidecar_mutate_blocks(
+                        &mut blocks_to_mutate,
+                        &rollbacks,
+                        &cache_l2,
+                        &mut brc20_cache,
+                        &self.prometheus,
+                        &self.config,
+                        &self.ctx,
+                    );
+                }
+                ReplayLogEntry::ApplyBlock { block } => chainhook_sidecar_mutate_ordhook_db(
+                    HandleBlock::ApplyBlock(block),
+                    &self.config,
+                    &self.ctx,
+                ),
+                ReplayLogEntry::UndoBlock { block } => chainhook_sidecar_mutate_ordhook_db(
+                    HandleBlock::UndoBlock(block),
+                    &self.config,
+                    &self.ctx,
+                ),
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
     pub async fn replay_transfers(
         &self,
         blocks: Vec<u64>,
         block_post_processor: Option<crossbeam_channel::Sender<BitcoinBlockData>>,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         // Start predicate processor
         let blocks_post_processor =
             start_transfers_recomputing_processor(&self.config, &self.ctx, block_post_processor);
@@ -559,6 +892,7 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                 block.block_identifier.index,
                 &blocks_db_rw,
                 &sqlite_dbs_rw,
+                config,
                 ctx,
             );
             if let Err(e) = res {
@@ -568,6 +902,9 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                     block.block_identifier
                 );
             }
+            insert_reorg_event(&block.block_identifier, &sqlite_dbs_rw.ordinals, ctx);
+            clear_query_cache();
+            on_block_rolled_back(block.block_identifier.index, ctx);
         }
         HandleBlock::ApplyBlock(block) => {
             let block_bytes = match BlockBytesCursor::from_standardized_block(&block) {
@@ -592,50 +929,214 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
             if let Err(e) = blocks_db_rw.flush() {
                 try_error!(ctx, "{}", e.to_string());
             }
+            tier_cold_blocks(COLD_BLOCKS_BATCH_SIZE, &blocks_db_rw, config, ctx);
+            if let Some(sat_ranges_conn_rw) = &sqlite_dbs_rw.sat_ranges {
+                if let Err(e) = index_sat_ranges_in_block(
+                    block.block_identifier.index,
+                    &block_bytes,
+                    Some(&block),
+                    config.storage.index_scope,
+                    sat_ranges_conn_rw,
+                    ctx,
+                ) {
+                    try_error!(
+                        ctx,
+                        "Unable to index sat ranges of block #{}: {e}",
+                        block.block_identifier.index
+                    );
+                }
+            }
 
             update_ordinals_db_with_block(&block, &sqlite_dbs_rw.ordinals, ctx);
             update_sequence_metadata_with_block(&block, &sqlite_dbs_rw.ordinals, &ctx);
+            insert_block_events_hash(&block, &sqlite_dbs_rw.ordinals, ctx);
+            enqueue_block_previews(&block);
+            enqueue_block_content_scans(&block);
+            update_index_commitment_with_block(&block, &sqlite_dbs_rw.ordinals, config, ctx);
 
             if let Some(brc20_conn_rw) = &sqlite_dbs_rw.brc20 {
                 write_augmented_block_to_brc20_db(&block, brc20_conn_rw, ctx);
             }
+            if let Some(sns_conn_rw) = &sqlite_dbs_rw.sns {
+                if let Err(e) = index_sns_names_in_block(&block, sns_conn_rw, ctx) {
+                    try_error!(ctx, "{e}");
+                }
+            }
+            if let Some(runes_conn_rw) = &sqlite_dbs_rw.runes {
+                if let Err(e) = index_runes_in_block(&block, config, runes_conn_rw, ctx) {
+                    try_error!(ctx, "{e}");
+                }
+            }
+            if let Some(metaprotocols_conn_rw) = &sqlite_dbs_rw.metaprotocols {
+                index_block_with_metaprotocol_indexers(&block, metaprotocols_conn_rw, ctx);
+            }
+            if let Some(sales_conn_rw) = &sqlite_dbs_rw.sales {
+                let sales = index_sales_in_block(&block, config, sales_conn_rw, ctx);
+                send_sale_detected_events(&block, &sales, &sqlite_dbs_rw.ordinals, config, ctx);
+            }
+            invalidate_cached_responses_of_block(&block);
+            on_chain_tip_updated(block.block_identifier.index, ctx);
         }
     }
 }
 
 pub fn start_observer_forwarding(
     event_observer_config: &EventObserverConfig,
+    config: &Config,
     ctx: &Context,
 ) -> Sender<BitcoinBlockData> {
     let (tx_replayer, rx_replayer) = unbounded();
+    spawn_observer_forwarding(event_observer_config, rx_replayer, never(), 0, config, ctx);
+    tx_replayer
+}
+
+/// Forwards the blocks indexed by the native ingestion to the predicates like `start_observer_forwarding`, and returns
+/// a second sender taking the fork points of the re-orgs: the predicates then get the rollback of the blocks forwarded
+/// above the fork point.
+pub fn start_native_observer_forwarding(
+    event_observer_config: &EventObserverConfig,
+    config: &Config,
+    ctx: &Context,
+) -> (Sender<BitcoinBlockData>, Sender<u64>) {
+    let (tx_replayer, rx_replayer) = unbounded();
+    let (tx_rollbacks, rx_rollbacks) = unbounded();
+    spawn_observer_forwarding(
+        event_observer_config,
+        rx_replayer,
+        rx_rollbacks,
+        NATIVE_INGESTION_MAX_REORG_DEPTH as usize,
+        config,
+        ctx,
+    );
+    (tx_replayer, tx_rollbacks)
+}
+
+/// Block kept to be rolled back, with the transactions predicates can match only: ordhook predicates are evaluated on
+/// ordinal and BRC-20 operations.
+fn retain_block_for_rollback(block: &BitcoinBlockData) -> BitcoinBlockData {
+    let mut block = block.clone();
+    block.transactions.retain(|tx| {
+        !tx.metadata.ordinal_operations.is_empty() || tx.metadata.brc20_operation.is_some()
+    });
+    block
+}
+
+fn forward_block_to_predicates(
+    block: BitcoinBlockData,
+    predicates: &Vec<&BitcoinChainhookSpecification>,
+    event_observer_config: &EventObserverConfig,
+    enrichment_db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) {
+    let future = process_block_with_predicates(
+        block,
+        predicates,
+        event_observer_config,
+        enrichment_db_conns,
+        config,
+        ctx,
+    );
+    let res = hiro_system_kit::nestable_block_on(future);
+    if let Err(_) = res {
+        error!(ctx.expect_logger(), "Initial ingestion failing");
+    }
+    PIPELINE_METRICS.metrics_block_delivered();
+}
+
+/// Forwards the blocks received to the predicates. Blocks are also kept, up to `rollback_depth`, for the predicates to
+/// be sent their rollback when a fork point below them is received.
+fn spawn_observer_forwarding(
+    event_observer_config: &EventObserverConfig,
+    rx_replayer: crossbeam_channel::Receiver<BitcoinBlockData>,
+    mut rx_rollbacks: crossbeam_channel::Receiver<u64>,
+    rollback_depth: usize,
+    config: &Config,
+    ctx: &Context,
+) {
     let mut moved_event_observer_config = event_observer_config.clone();
+    let moved_config = config.clone();
     let moved_ctx = ctx.clone();
 
     let _ = hiro_system_kit::thread_named("Initial predicate processing")
         .spawn(move || {
-            if let Some(mut chainhook_config) = moved_event_observer_config.chainhook_config.take()
-            {
-                let mut bitcoin_predicates_ref: Vec<&BitcoinChainhookSpecification> = vec![];
-                for bitcoin_predicate in chainhook_config.bitcoin_chainhooks.iter_mut() {
-                    bitcoin_predicates_ref.push(bitcoin_predicate);
+            let Some(mut chainhook_config) = moved_event_observer_config.chainhook_config.take()
+            else {
+                return;
+            };
+            let mut bitcoin_predicates_ref: Vec<&BitcoinChainhookSpecification> = vec![];
+            for bitcoin_predicate in chainhook_config.bitcoin_chainhooks.iter_mut() {
+                bitcoin_predicates_ref.push(bitcoin_predicate);
+            }
+            let mut enrichment_db_conns = EnrichmentDbConnections::new();
+            // Highest block at the back.
+            let mut forwarded_blocks: VecDeque<BitcoinBlockData> = VecDeque::new();
+            loop {
+                let mut blocks = vec![];
+                let mut fork_point = None;
+                select! {
+                    recv(rx_replayer) -> block => match block {
+                        Ok(block) => blocks.push(block),
+                        Err(_) => break,
+                    },
+                    recv(rx_rollbacks) -> received => match received {
+                        Ok(received) => {
+                            // The blocks indexed before the re-org got detected are forwarded first.
+                            blocks.extend(rx_replayer.try_iter());
+                            fork_point = Some(received);
+                        }
+                        Err(_) => rx_rollbacks = never(),
+                    },
                 }
-                while let Ok(block) = rx_replayer.recv() {
-                    let future = process_block_with_predicates(
+                for block in blocks.into_iter() {
+                    if rollback_depth > 0 {
+                        forwarded_blocks.push_back(retain_block_for_rollback(&block));
+                        if forwarded_blocks.len() > rollback_depth {
+                            forwarded_blocks.pop_front();
+                        }
+                    }
+                    forward_block_to_predicates(
                         block,
                         &bitcoin_predicates_ref,
                         &moved_event_observer_config,
+                        &mut enrichment_db_conns,
+                        &moved_config,
                         &moved_ctx,
                     );
-                    let res = hiro_system_kit::nestable_block_on(future);
-                    if let Err(_) = res {
-                        error!(moved_ctx.expect_logger(), "Initial ingestion failing");
-                    }
+                }
+                let Some(fork_point) = fork_point else {
+                    continue;
+                };
+                let mut orphaned_blocks = vec![];
+                while forwarded_blocks
+                    .back()
+                    .map(|b| b.block_identifier.index > fork_point)
+                    .unwrap_or(false)
+                {
+                    orphaned_blocks.extend(forwarded_blocks.pop_back());
+                }
+                if orphaned_blocks.is_empty() {
+                    continue;
+                }
+                try_info!(
+                    moved_ctx,
+                    "Re-org handling: sending the rollback of {} blocks to predicates",
+                    orphaned_blocks.len()
+                );
+                let future = process_rollback_with_predicates(
+                    orphaned_blocks,
+                    &bitcoin_predicates_ref,
+                    &moved_event_observer_config,
+                    &mut enrichment_db_conns,
+                    &moved_config,
+                    &moved_ctx,
+                );
+                if let Err(e) = hiro_system_kit::nestable_block_on(future) {
+                    try_error!(moved_ctx, "Unable to send rollbacks to predicates: {e}");
                 }
             }
         })
         .expect("unable to spawn thread");
-
-    tx_replayer
 }
 
 pub fn chainhook_sidecar_mutate_blocks(
@@ -649,6 +1150,13 @@ pub fn chainhook_sidecar_mutate_blocks(
 ) {
     let mut updated_blocks_ids = vec![];
 
+    let rollback_tip = blocks_ids_to_rollback.iter().map(|b| b.index).max();
+    if let (Some(alerts_config), Some(tip)) = (&config.alerts, rollback_tip) {
+        if let Some(alert) = check_reorg_depth(config, blocks_ids_to_rollback.len(), tip) {
+            send_alert(&alert, alerts_config, &config.resources, ctx);
+        }
+    }
+
     let (blocks_db_rw, mut sqlite_dbs_rw) = match open_all_dbs_rw(&config, &ctx) {
         Ok(dbs) => dbs,
         Err(e) => {
@@ -657,12 +1165,25 @@ pub fn chainhook_sidecar_mutate_blocks(
         }
     };
 
+    let mut amendments = vec![];
+    let mut rollbacks = vec![];
     for block_id_to_rollback in blocks_ids_to_rollback.iter() {
+        let mut block_amendments = get_rolled_back_block_amendments(
+            block_id_to_rollback,
+            blocks_ids_to_rollback.len() as u64,
+            &sqlite_dbs_rw.ordinals,
+            config,
+            ctx,
+        );
+        let block_rollback =
+            get_block_rollback(block_id_to_rollback, &sqlite_dbs_rw.ordinals, config, ctx);
+        // Events are only sent for the blocks actually dropped.
         if let Err(e) = drop_block_data_from_all_dbs(
             block_id_to_rollback.index,
             block_id_to_rollback.index,
             &blocks_db_rw,
             &sqlite_dbs_rw,
+            config,
             &ctx,
         ) {
             try_error!(
@@ -670,8 +1191,17 @@ pub fn chainhook_sidecar_mutate_blocks(
                 "Unable to rollback bitcoin block {}: {e}",
                 block_id_to_rollback.index
             );
+            break;
         }
+        amendments.append(&mut block_amendments);
+        rollbacks.extend(block_rollback);
+        insert_reorg_event(block_id_to_rollback, &sqlite_dbs_rw.ordinals, ctx);
+    }
+    if !blocks_ids_to_rollback.is_empty() {
+        clear_query_cache();
     }
+    send_amendment_events(amendments, config, ctx);
+    send_rollback_events(rollbacks, config, ctx);
 
     let brc20_db_tx = sqlite_dbs_rw
         .brc20
@@ -707,11 +1237,15 @@ pub fn chainhook_sidecar_mutate_blocks(
         if cache.processed_by_sidecar {
             update_ordinals_db_with_block(&cache.block, &inscriptions_db_tx, &ctx);
             update_sequence_metadata_with_block(&cache.block, &inscriptions_db_tx, &ctx);
+            insert_block_events_hash(&cache.block, &inscriptions_db_tx, &ctx);
+            update_index_commitment_with_block(&cache.block, &inscriptions_db_tx, config, &ctx);
         } else {
             updated_blocks_ids.push(format!("{}", cache.block.block_identifier.index));
 
             let mut cache_l1 = BTreeMap::new();
-            let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_tx);
+            let mut sequence_cursor =
+                SequenceCursor::new(&inscriptions_db_tx, get_jubilee_block_height(config));
+            let mut profiler = BlockProfiler::new(cache.block.block_identifier.index, config);
 
             let _ = process_block(
                 &mut cache.block,
@@ -722,10 +1256,12 @@ pub fn chainhook_sidecar_mutate_blocks(
                 &inscriptions_db_tx,
                 brc20_db_tx.as_ref(),
                 brc20_cache.as_mut(),
+                &mut profiler,
                 prometheus,
                 &config,
                 &ctx,
             );
+            profiler.report(config, ctx);
 
             let inscription_numbers = get_inscriptions_revealed_in_block(&cache.block)
                 .iter()
@@ -744,6 +1280,8 @@ pub fn chainhook_sidecar_mutate_blocks(
             );
             cache.processed_by_sidecar = true;
         }
+        enqueue_block_previews(&cache.block);
+        enqueue_block_content_scans(&cache.block);
     }
     let _ = inscriptions_db_tx.rollback();
 
@@ -891,6 +1429,27 @@ pub fn write_brc20_block_operations(
                             },
                             Err(e) => {
                                 try_debug!(ctx, "Error validating BRC-20 operation {}", e);
+                                insert_rejected_operation(
+                                    &Brc20DbRejectedOperationRow {
+                                        inscription_id: reveal.inscription_id.clone(),
+                                        block_height: block.block_identifier.index,
+                                        tx_id: tx
+                                            .transaction_identifier
+                                            .get_hash_bytes_str()
+                                            .to_string(),
+                                        operation: parsed_brc20_operation
+                                            .operation_name()
+                                            .to_string(),
+                                        tick: parsed_brc20_operation.tick().to_string(),
+                                        address: reveal
+                                            .inscriber_address
+                                            .clone()
+                                            .unwrap_or_default(),
+                                        reason: e,
+                                    },
+                                    db_tx,
+                                    ctx,
+                                );
                             }
                         }
                     } else {
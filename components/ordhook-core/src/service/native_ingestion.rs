@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use chainhook_sdk::{
+    types::{BitcoinBlockData, BlockIdentifier},
+    utils::Context,
+};
+
+use crate::{
+    config::Config,
+    db::{
+        drop_block_data_from_all_dbs, open_all_dbs_rw,
+        ordinals::{
+            find_block_events_hash, find_last_block_events_hash, insert_reorg_event,
+            open_ordinals_db,
+        },
+    },
+    error::OrdhookError,
+    service::{
+        alerts::{check_reorg_depth, send_alert},
+        amendments::{get_rolled_back_block_amendments, send_amendment_events},
+        confirmations::{on_block_rolled_back, on_chain_tip_updated},
+        query_cache::clear_query_cache,
+        rollbacks::{get_block_rollback, send_rollback_events},
+        Service,
+    },
+    try_error, try_info, try_warn,
+    utils::bitcoind::{bitcoind_get_block_hash, bitcoind_try_get_block_height},
+};
+
+/// Re-orgs deeper than this are not looked for: the index is then left untouched and the error is logged.
+pub const NATIVE_INGESTION_MAX_REORG_DEPTH: u64 = 100;
+
+/// Walks back from `tip` until the hash indexed at a height matches bitcoind's, and returns that height. Heights which
+/// hash wasn't recorded are considered canonical. Returns an error if bitcoind can't be reached, or if no common block is
+/// found within `max_depth` blocks.
+pub fn find_fork_point<L, R>(
+    tip: u64,
+    max_depth: u64,
+    indexed_block_hash: L,
+    canonical_block_hash: R,
+) -> Result<u64, OrdhookError>
+where
+    L: Fn(u64) -> Option<String>,
+    R: Fn(u64) -> Result<String, OrdhookError>,
+{
+    let mut height = tip;
+    loop {
+        let Some(indexed_hash) = indexed_block_hash(height) else {
+            return Ok(height);
+        };
+        let canonical_hash = canonical_block_hash(height)?;
+        if indexed_hash.trim_start_matches("0x") == canonical_hash.trim_start_matches("0x") {
+            return Ok(height);
+        }
+        if tip - height >= max_depth || height == 0 {
+            return Err(OrdhookError::Reorg(format!(
+                "no common block found with bitcoind between #{height} and #{tip}"
+            )));
+        }
+        height -= 1;
+    }
+}
+
+/// Reverts the changes of the blocks indexed above `fork_point`, from the highest one down.
+fn rollback_orphaned_blocks(
+    fork_point: u64,
+    tip: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let (blocks_db_rw, sqlite_dbs_rw) = open_all_dbs_rw(config, ctx)?;
+    let mut amendments = vec![];
+    let mut rollbacks = vec![];
+    for block_height in (fork_point + 1..=tip).rev() {
+        try_info!(
+            ctx,
+            "Re-org handling: reverting changes in block #{block_height}"
+        );
+        let block_hash = find_block_events_hash(block_height, &sqlite_dbs_rw.ordinals, ctx)
+            .map(|hash| hash.block_hash)
+            .unwrap_or_default();
+        let block_identifier = BlockIdentifier {
+            index: block_height,
+            hash: block_hash,
+        };
+        let mut block_amendments = get_rolled_back_block_amendments(
+            &block_identifier,
+            tip - fork_point,
+            &sqlite_dbs_rw.ordinals,
+            config,
+            ctx,
+        );
+        let block_rollback =
+            get_block_rollback(&block_identifier, &sqlite_dbs_rw.ordinals, config, ctx);
+        // Events are only sent for the blocks actually dropped.
+        if let Err(e) = drop_block_data_from_all_dbs(
+            block_height,
+            block_height,
+            &blocks_db_rw,
+            &sqlite_dbs_rw,
+            config,
+            ctx,
+        ) {
+            send_amendment_events(amendments, config, ctx);
+            send_rollback_events(rollbacks, config, ctx);
+            return Err(e);
+        }
+        amendments.append(&mut block_amendments);
+        rollbacks.extend(block_rollback);
+        insert_reorg_event(&block_identifier, &sqlite_dbs_rw.ordinals, ctx);
+        clear_query_cache();
+        on_block_rolled_back(block_height, ctx);
+    }
+    send_amendment_events(amendments, config, ctx);
+    send_rollback_events(rollbacks, config, ctx);
+    Ok(())
+}
+
+/// Polls bitcoind's chain tip every `poll_interval_ms` and indexes the new blocks with ordhook's own pipeline. Before
+/// indexing, the hashes of the last blocks indexed are compared with bitcoind's best chain, and the blocks orphaned by a
+/// re-org are rolled back. Indexed blocks are forwarded to `block_post_processor` for predicate evaluation, and the fork
+/// points of the re-orgs to `predicate_rollbacks`, for the predicates to be sent the rollback of the orphaned blocks.
+pub async fn start_native_block_ingestion(
+    service: &Service,
+    block_post_processor: Option<crossbeam_channel::Sender<BitcoinBlockData>>,
+    predicate_rollbacks: Option<crossbeam_channel::Sender<u64>>,
+    poll_interval_ms: u64,
+) -> Result<(), OrdhookError> {
+    let config = &service.config;
+    let ctx = &service.ctx;
+    loop {
+        tokio::time::sleep(Duration::from_millis(poll_interval_ms)).await;
+        let bitcoind_tip = match bitcoind_try_get_block_height(config) {
+            Ok(height) => height,
+            Err(e) => {
+                try_warn!(ctx, "Native ingestion: {e}");
+                continue;
+            }
+        };
+        let inscriptions_db_conn = open_ordinals_db(&config.expected_sqlite_path(), ctx)?;
+        if let Some(last_block) = find_last_block_events_hash(&inscriptions_db_conn, ctx) {
+            let indexed_tip = last_block.block_height;
+            if bitcoind_tip < indexed_tip {
+                try_warn!(
+                    ctx,
+                    "Native ingestion: bitcoind chain tip (#{bitcoind_tip}) is behind the index (#{indexed_tip}), waiting"
+                );
+                continue;
+            }
+            let fork_point = match find_fork_point(
+                indexed_tip,
+                NATIVE_INGESTION_MAX_REORG_DEPTH,
+                |height| {
+                    find_block_events_hash(height, &inscriptions_db_conn, ctx)
+                        .map(|hash| hash.block_hash)
+                },
+                |height| bitcoind_get_block_hash(height, config),
+            ) {
+                Ok(fork_point) => fork_point,
+                Err(e) => {
+                    try_error!(ctx, "Native ingestion: unable to handle re-org: {e}");
+                    continue;
+                }
+            };
+            if fork_point < indexed_tip {
+                let blocks_rolled_back = (indexed_tip - fork_point) as usize;
+                try_warn!(
+                    ctx,
+                    "Native ingestion: re-org detected, rolling back {blocks_rolled_back} blocks"
+                );
+                rollback_orphaned_blocks(fork_point, indexed_tip, config, ctx)?;
+                if let Some(ref predicate_rollbacks) = predicate_rollbacks {
+                    let _ = predicate_rollbacks.send(fork_point);
+                }
+                if let Some(ref alerts_config) = config.alerts {
+                    if let Some(alert) = check_reorg_depth(config, blocks_rolled_back, bitcoind_tip)
+                    {
+                        send_alert(&alert, alerts_config, &config.resources, ctx);
+                    }
+                }
+            } else if indexed_tip == bitcoind_tip {
+                continue;
+            }
+        }
+        service
+            .index_blocks_up_to_bitcoin_chain_tip(block_post_processor.clone())
+            .await?;
+        if let Some(last_block) = find_last_block_events_hash(&inscriptions_db_conn, ctx) {
+            service
+                .prometheus
+                .metrics_block_indexed(last_block.block_height);
+            on_chain_tip_updated(last_block.block_height, ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_fork_point;
+    use crate::error::OrdhookError;
+
+    fn indexed(height: u64) -> Option<String> {
+        match height {
+            0..=99 => None,
+            _ => Some(format!("0x{:064x}", height)),
+        }
+    }
+
+    #[test]
+    fn finds_no_fork_on_the_canonical_chain() {
+        let fork_point = find_fork_point(110, 100, indexed, |h| Ok(format!("{:064x}", h)));
+        assert_eq!(fork_point, Ok(110));
+    }
+
+    #[test]
+    fn finds_the_last_common_block() {
+        let canonical = |h: u64| -> Result<String, OrdhookError> {
+            match h {
+                0..=107 => Ok(format!("{:064x}", h)),
+                _ => Ok(format!("{:064x}", h + 1_000)),
+            }
+        };
+        assert_eq!(find_fork_point(110, 100, indexed, canonical), Ok(107));
+    }
+
+    #[test]
+    fn fails_on_deep_forks_and_rpc_errors() {
+        let canonical =
+            |h: u64| -> Result<String, OrdhookError> { Ok(format!("{:064x}", h + 1_000)) };
+        assert!(find_fork_point(110, 5, indexed, canonical).is_err());
+        assert!(find_fork_point(110, 100, indexed, |_| {
+            Err(OrdhookError::Rpc("unreachable".to_string()))
+        })
+        .is_err());
+    }
+}
@@ -15,16 +15,30 @@ use chainhook_sdk::{
     utils::Context,
 };
 use rusqlite::{Connection, ToSql};
-use serde_json::json;
+use serde_json::{json, Value as JsonValue};
 
 use crate::{
     config::Config,
+    core::meta_protocols::brc20::predicate::{set_brc20_predicate_filter, Brc20PredicateFilter},
     db::ordinals::{
         create_or_open_readwrite_db, open_existing_readonly_db, perform_query_one,
         perform_query_set,
     },
-    scan::bitcoin::process_block_with_predicates,
-    try_warn,
+    error::OrdhookError,
+    scan::{bitcoin::process_block_with_predicates, predicate_scripts::set_predicate_script},
+    service::blocklist::{BlocklistEntry, BLOCKLIST_SOURCE_API},
+    service::confirmations::{
+        get_predicate_min_confirmations, set_predicate_min_confirmations, start_confirmed_stream,
+        stop_confirmed_stream,
+    },
+    service::dead_letters::DeadLetter,
+    service::enrichment::{EnrichmentDbConnections, EnrichmentField},
+    service::jobs::{Job, JobKind, JobStatus},
+    service::live_deliveries::get_observer_hook_action,
+    service::predicate_versions::{check_payload_version, upgrade_stored_specification},
+    service::usage::{UsageCounters, UsageReportEntry},
+    service::wallets::{unwatch_wallet, watch_wallet, WalletPredicateFilter},
+    try_info, try_warn,
     utils::monitoring::PrometheusMonitoring,
 };
 
@@ -74,7 +88,7 @@ pub fn insert_entry_in_observers(
     }
 }
 
-fn get_default_observers_db_file_path(config: &Config) -> PathBuf {
+pub fn get_default_observers_db_file_path(config: &Config) -> PathBuf {
     let mut destination_path = config.expected_observers_cache_path().clone();
     destination_path.push("observers.sqlite");
     destination_path
@@ -85,8 +99,7 @@ pub fn open_readonly_observers_db_conn(
     ctx: &Context,
 ) -> Result<Connection, String> {
     let db_path = get_default_observers_db_file_path(config);
-    let conn = open_existing_readonly_db(&db_path, ctx);
-    Ok(conn)
+    Ok(open_existing_readonly_db(&db_path, ctx)?)
 }
 
 pub fn open_readwrite_observers_db_conn(
@@ -124,9 +137,201 @@ pub fn initialize_observers_db(config: &Config, ctx: &Context) -> Connection {
     ) {
         try_warn!(ctx, "Unable to create table observers: {}", e.to_string());
     }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS brc20_filters (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            filter TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table brc20_filters: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS wallet_filters (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            filter TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table wallet_filters: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS predicate_scripts (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            script TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table predicate_scripts: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS predicate_min_confirmations (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            min_confirmations INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table predicate_min_confirmations: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS predicate_enrichments (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            fields TEXT NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table predicate_enrichments: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS predicate_payload_versions (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            payload_version INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table predicate_payload_versions: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS paused_predicates (
+            uuid TEXT NOT NULL PRIMARY KEY,
+            paused_at INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table paused_predicates: {}",
+            e.to_string()
+        );
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS dead_letters (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            uuid TEXT NOT NULL,
+            url TEXT NOT NULL,
+            headers TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            error TEXT NOT NULL,
+            failed_at INTEGER NOT NULL,
+            redelivery_attempts INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(
+            ctx,
+            "Unable to create table dead_letters: {}",
+            e.to_string()
+        );
+    }
+    redact_dead_letter_headers_in_observers(&conn, ctx);
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS blocklist (
+            entry TEXT NOT NULL PRIMARY KEY,
+            reason TEXT,
+            added_at INTEGER NOT NULL
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table blocklist: {}", e.to_string());
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS jobs (
+            id TEXT NOT NULL PRIMARY KEY,
+            kind TEXT NOT NULL,
+            status TEXT NOT NULL,
+            params TEXT NOT NULL,
+            progress REAL NOT NULL,
+            created_at INTEGER NOT NULL,
+            started_at INTEGER,
+            finished_at INTEGER,
+            error TEXT
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table jobs: {}", e.to_string());
+    }
+    if let Err(e) = conn.execute(
+        "CREATE TABLE IF NOT EXISTS usage (
+            tenant TEXT NOT NULL,
+            day INTEGER NOT NULL,
+            api_calls INTEGER NOT NULL,
+            bytes_served INTEGER NOT NULL,
+            events_delivered INTEGER NOT NULL,
+            PRIMARY KEY (tenant, day)
+        )",
+        [],
+    ) {
+        try_warn!(ctx, "Unable to create table usage: {}", e.to_string());
+    }
+    upgrade_stored_observers(&conn, ctx);
     conn
 }
 
+/// Upgrades the predicates registered by previous releases to the current predicate specification version.
+fn upgrade_stored_observers(db_conn: &Connection, ctx: &Context) {
+    let args: &[&dyn ToSql] = &[];
+    let stored_specs = perform_query_set(
+        "SELECT uuid, spec FROM observers",
+        args,
+        db_conn,
+        ctx,
+        |row| {
+            let uuid: String = row.get(0).unwrap();
+            let spec: String = row.get(1).unwrap();
+            (uuid, spec)
+        },
+    );
+    for (uuid, encoded_spec) in stored_specs.into_iter() {
+        let Ok(mut spec) = serde_json::from_str::<JsonValue>(&encoded_spec) else {
+            try_warn!(ctx, "Unable to parse stored predicate {uuid}");
+            continue;
+        };
+        match upgrade_stored_specification(&mut spec) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                try_warn!(ctx, "Unable to upgrade stored predicate {uuid}: {e}");
+                continue;
+            }
+        }
+        while let Err(e) = db_conn.execute(
+            "UPDATE observers SET spec = ? WHERE uuid = ?",
+            rusqlite::params![spec.to_string(), uuid],
+        ) {
+            try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+        try_info!(
+            ctx,
+            "Predicate {uuid} upgraded to the current specification version"
+        );
+    }
+}
+
 #[cfg(test)]
 pub fn delete_observers_db(config: &Config) {
     let path = get_default_observers_db_file_path(config);
@@ -185,6 +390,624 @@ pub fn remove_entry_from_observers(uuid: &str, db_conn: &Connection, ctx: &Conte
     }
 }
 
+pub fn insert_brc20_filter_in_observers(
+    uuid: &str,
+    filter: &Brc20PredicateFilter,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO brc20_filters (uuid, filter) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, json!(filter).to_string()],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_brc20_filter_from_observers(uuid: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM brc20_filters WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_all_brc20_filters(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(String, Brc20PredicateFilter)> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT uuid, filter FROM brc20_filters";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        let uuid: String = row.get(0).ok()?;
+        let encoded_filter: String = row.get(1).ok()?;
+        match serde_json::from_str(&encoded_filter) {
+            Ok(filter) => Some((uuid, filter)),
+            Err(e) => {
+                try_warn!(
+                    ctx,
+                    "Unable to decode BRC-20 filter of predicate {uuid}: {e}"
+                );
+                None
+            }
+        }
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn insert_wallet_filter_in_observers(
+    uuid: &str,
+    filter: &WalletPredicateFilter,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO wallet_filters (uuid, filter) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, json!(filter).to_string()],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_wallet_filter_from_observers(uuid: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM wallet_filters WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_all_wallet_filters(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(String, WalletPredicateFilter)> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT uuid, filter FROM wallet_filters";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        let uuid: String = row.get(0).ok()?;
+        let encoded_filter: String = row.get(1).ok()?;
+        match serde_json::from_str(&encoded_filter) {
+            Ok(filter) => Some((uuid, filter)),
+            Err(e) => {
+                try_warn!(ctx, "Unable to decode wallet of predicate {uuid}: {e}");
+                None
+            }
+        }
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn insert_predicate_script_in_observers(
+    uuid: &str,
+    script: &str,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO predicate_scripts (uuid, script) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, &script],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_predicate_script_from_observers(uuid: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM predicate_scripts WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_all_predicate_scripts(db_conn: &Connection, ctx: &Context) -> Vec<(String, String)> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT uuid, script FROM predicate_scripts";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        (row.get(0).unwrap(), row.get(1).unwrap())
+    })
+}
+
+pub fn insert_predicate_min_confirmations_in_observers(
+    uuid: &str,
+    min_confirmations: u64,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO predicate_min_confirmations (uuid, min_confirmations) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, &min_confirmations],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_predicate_min_confirmations_from_observers(
+    uuid: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM predicate_min_confirmations WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_all_predicate_min_confirmations(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(String, u64)> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT uuid, min_confirmations FROM predicate_min_confirmations";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        (row.get(0).unwrap(), row.get(1).unwrap())
+    })
+}
+
+pub fn insert_predicate_enrichment_in_observers(
+    uuid: &str,
+    fields: &Vec<EnrichmentField>,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO predicate_enrichments (uuid, fields) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, json!(fields).to_string()],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_predicate_enrichment_from_observers(uuid: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM predicate_enrichments WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_predicate_enrichment(
+    uuid: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<Vec<EnrichmentField>> {
+    let args: &[&dyn ToSql] = &[&uuid.to_sql().unwrap()];
+    let query = "SELECT fields FROM predicate_enrichments WHERE uuid = ?";
+    let encoded_fields: String =
+        perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())?;
+    match serde_json::from_str(&encoded_fields) {
+        Ok(fields) => Some(fields),
+        Err(e) => {
+            try_warn!(ctx, "Unable to parse enrichment of predicate {uuid}: {e}");
+            None
+        }
+    }
+}
+
+pub fn insert_predicate_payload_version_in_observers(
+    uuid: &str,
+    payload_version: u64,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO predicate_payload_versions (uuid, payload_version) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, &payload_version],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_predicate_payload_version_from_observers(
+    uuid: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM predicate_payload_versions WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Payload version a predicate was registered with. None for the predicates registered before it was stored.
+pub fn find_predicate_payload_version(
+    uuid: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[&uuid.to_sql().unwrap()];
+    let query = "SELECT payload_version FROM predicate_payload_versions WHERE uuid = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+pub fn insert_predicate_pause_in_observers(
+    uuid: &str,
+    paused_at: u64,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO paused_predicates (uuid, paused_at) VALUES (?1, ?2)",
+        rusqlite::params![&uuid, &paused_at],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_predicate_pause_from_observers(uuid: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM paused_predicates WHERE uuid = ?1",
+        rusqlite::params![&uuid],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Time a predicate was paused at through the API, if it is paused.
+pub fn find_predicate_paused_at(uuid: &str, db_conn: &Connection, ctx: &Context) -> Option<u64> {
+    let args: &[&dyn ToSql] = &[&uuid.to_sql().unwrap()];
+    let query = "SELECT paused_at FROM paused_predicates WHERE uuid = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| row.get(0).unwrap())
+}
+
+pub fn insert_dead_letter_in_observers(
+    dead_letter: &DeadLetter,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT INTO dead_letters (uuid, url, headers, payload, error, failed_at, redelivery_attempts) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            &dead_letter.predicate_uuid,
+            &dead_letter.url,
+            json!(dead_letter.headers).to_string(),
+            dead_letter.payload.to_string(),
+            &dead_letter.error,
+            &dead_letter.failed_at,
+            &dead_letter.redelivery_attempts
+        ],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Records a failed redelivery of a dead letter.
+pub fn update_dead_letter_in_observers(
+    id: u64,
+    error: &str,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "UPDATE dead_letters SET error = ?1, redelivery_attempts = redelivery_attempts + 1 WHERE id = ?2",
+        rusqlite::params![&error, &id],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_dead_letter_from_observers(id: u64, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM dead_letters WHERE id = ?1",
+        rusqlite::params![&id],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Removes the authorization headers stored with dead letters by previous versions.
+fn redact_dead_letter_headers_in_observers(db_conn: &Connection, ctx: &Context) {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT id, headers FROM dead_letters WHERE headers LIKE '%authorization%'";
+    let rows: Vec<(u64, String)> = perform_query_set(query, args, db_conn, ctx, |row| {
+        (row.get(0).unwrap(), row.get(1).unwrap())
+    });
+    for (id, encoded_headers) in rows.into_iter() {
+        let headers: Vec<(String, String)> =
+            serde_json::from_str(&encoded_headers).unwrap_or_default();
+        let headers: Vec<(String, String)> = headers
+            .into_iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("authorization"))
+            .collect();
+        if let Err(e) = db_conn.execute(
+            "UPDATE dead_letters SET headers = ?1 WHERE id = ?2",
+            rusqlite::params![json!(headers).to_string(), &id],
+        ) {
+            try_warn!(ctx, "Unable to redact dead letter #{id}: {}", e.to_string());
+        }
+    }
+}
+
+fn parse_dead_letter(row: &rusqlite::Row<'_>) -> DeadLetter {
+    let encoded_headers: String = row.get(3).unwrap();
+    let encoded_payload: String = row.get(4).unwrap();
+    DeadLetter {
+        id: row.get(0).unwrap(),
+        predicate_uuid: row.get(1).unwrap(),
+        url: row.get(2).unwrap(),
+        headers: serde_json::from_str(&encoded_headers).unwrap_or_default(),
+        payload: serde_json::from_str(&encoded_payload).unwrap_or_default(),
+        error: row.get(5).unwrap(),
+        failed_at: row.get(6).unwrap(),
+        redelivery_attempts: row.get(7).unwrap(),
+    }
+}
+
+/// Dead letters, oldest first, optionally of a single predicate.
+pub fn find_dead_letters(
+    predicate_uuid: Option<&str>,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<DeadLetter> {
+    let columns = "id, uuid, url, headers, payload, error, failed_at, redelivery_attempts";
+    match predicate_uuid {
+        Some(predicate_uuid) => {
+            let args: &[&dyn ToSql] = &[&predicate_uuid.to_sql().unwrap()];
+            let query = format!("SELECT {columns} FROM dead_letters WHERE uuid = ? ORDER BY id");
+            perform_query_set(&query, args, db_conn, ctx, parse_dead_letter)
+        }
+        None => {
+            let args: &[&dyn ToSql] = &[];
+            let query = format!("SELECT {columns} FROM dead_letters ORDER BY id");
+            perform_query_set(&query, args, db_conn, ctx, parse_dead_letter)
+        }
+    }
+}
+
+/// Id of the last dead letter recorded, or 0.
+pub fn find_last_dead_letter_id(db_conn: &Connection, ctx: &Context) -> u64 {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT MAX(id) FROM dead_letters";
+    perform_query_one(query, args, db_conn, ctx, |row| {
+        row.get::<_, Option<u64>>(0).unwrap()
+    })
+    .flatten()
+    .unwrap_or_default()
+}
+
+/// Id, predicate uuid and error of the dead letters recorded after `id`, oldest first.
+pub fn find_dead_letter_errors_after(
+    id: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(u64, String, String)> {
+    let args: &[&dyn ToSql] = &[&id.to_sql().unwrap()];
+    let query = "SELECT id, uuid, error FROM dead_letters WHERE id > ? ORDER BY id";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        (
+            row.get(0).unwrap(),
+            row.get(1).unwrap(),
+            row.get(2).unwrap(),
+        )
+    })
+}
+
+pub fn insert_blocklist_entry_in_observers(
+    entry: &BlocklistEntry,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO blocklist (entry, reason, added_at) VALUES (?1, ?2, ?3)",
+        rusqlite::params![&entry.entry, &entry.reason, &entry.added_at],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn remove_blocklist_entry_from_observers(entry: &str, db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = db_conn.execute(
+        "DELETE FROM blocklist WHERE entry = ?1",
+        rusqlite::params![&entry],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+pub fn find_blocklist_entry(
+    entry: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Option<BlocklistEntry> {
+    let args: &[&dyn ToSql] = &[&entry.to_sql().unwrap()];
+    let query = "SELECT entry, reason, added_at FROM blocklist WHERE entry = ?";
+    perform_query_one(query, args, db_conn, ctx, |row| BlocklistEntry {
+        entry: row.get(0).unwrap(),
+        reason: row.get(1).unwrap(),
+        added_at: row.get(2).unwrap(),
+        source: BLOCKLIST_SOURCE_API.into(),
+    })
+}
+
+pub fn find_all_blocklist_entries(db_conn: &Connection, ctx: &Context) -> Vec<BlocklistEntry> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT entry, reason, added_at FROM blocklist ORDER BY added_at";
+    perform_query_set(query, args, db_conn, ctx, |row| BlocklistEntry {
+        entry: row.get(0).unwrap(),
+        reason: row.get(1).unwrap(),
+        added_at: row.get(2).unwrap(),
+        source: BLOCKLIST_SOURCE_API.into(),
+    })
+}
+
+pub fn insert_job_in_observers(job: &Job, observers_db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT OR REPLACE INTO jobs (id, kind, status, params, progress, created_at, started_at, finished_at, error)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            &job.id,
+            job.kind.as_str(),
+            job.status.as_str(),
+            job.params.to_string(),
+            &job.progress_percent,
+            &job.created_at,
+            &job.started_at,
+            &job.finished_at,
+            &job.error
+        ],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Jobs still queued or running were interrupted by the previous shutdown of the service.
+pub fn fail_interrupted_jobs_in_observers(observers_db_conn: &Connection, ctx: &Context) {
+    while let Err(e) = observers_db_conn.execute(
+        "UPDATE jobs SET status = 'failed', error = 'interrupted by a restart of the service',
+            finished_at = CAST(strftime('%s', 'now') AS INTEGER)
+        WHERE status = 'queued' OR status = 'running'",
+        [],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Deletes the jobs finished before `finished_before` (unix timestamp, in seconds).
+pub fn prune_finished_jobs_in_observers(
+    finished_before: u64,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "DELETE FROM jobs WHERE status NOT IN ('queued', 'running') AND finished_at < ?1",
+        rusqlite::params![&finished_before],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+fn job_from(row: &rusqlite::Row<'_>) -> Job {
+    let kind: String = row.get(1).unwrap();
+    let status: String = row.get(2).unwrap();
+    let params: String = row.get(3).unwrap();
+    Job {
+        id: row.get(0).unwrap(),
+        kind: JobKind::from_str(&kind).unwrap_or(JobKind::Rescan),
+        status: JobStatus::from_str(&status).unwrap_or(JobStatus::Failed),
+        params: serde_json::from_str(&params).unwrap_or_default(),
+        progress_percent: row.get(4).unwrap(),
+        created_at: row.get(5).unwrap(),
+        started_at: row.get(6).unwrap(),
+        finished_at: row.get(7).unwrap(),
+        error: row.get(8).unwrap(),
+    }
+}
+
+pub fn find_job(id: &str, db_conn: &Connection, ctx: &Context) -> Option<Job> {
+    let args: &[&dyn ToSql] = &[&id.to_sql().unwrap()];
+    let query = "SELECT id, kind, status, params, progress, created_at, started_at, finished_at, error FROM jobs WHERE id = ?";
+    perform_query_one(query, args, db_conn, ctx, job_from)
+}
+
+/// Most recent jobs first.
+pub fn find_all_jobs(db_conn: &Connection, ctx: &Context) -> Vec<Job> {
+    let args: &[&dyn ToSql] = &[];
+    let query = "SELECT id, kind, status, params, progress, created_at, started_at, finished_at, error FROM jobs ORDER BY created_at DESC, id";
+    perform_query_set(query, args, db_conn, ctx, job_from)
+}
+
+/// Adds `usage` to the usage of `tenant` recorded for `day` (UNIX timestamp of its start).
+pub fn add_usage_in_observers(
+    tenant: &str,
+    day: u64,
+    usage: &UsageCounters,
+    observers_db_conn: &Connection,
+    ctx: &Context,
+) {
+    while let Err(e) = observers_db_conn.execute(
+        "INSERT INTO usage (tenant, day, api_calls, bytes_served, events_delivered) VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT (tenant, day) DO UPDATE SET
+            api_calls = api_calls + excluded.api_calls,
+            bytes_served = bytes_served + excluded.bytes_served,
+            events_delivered = events_delivered + excluded.events_delivered",
+        rusqlite::params![
+            tenant,
+            day,
+            usage.api_calls,
+            usage.bytes_served,
+            usage.events_delivered
+        ],
+    ) {
+        try_warn!(ctx, "unable to query observers.sqlite: {}", e.to_string());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Usage recorded between the days `from` and `to` (`YYYY-MM-DD`, inclusive), by tenant and day.
+pub fn find_usage_in_observers(
+    from: &str,
+    to: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<UsageReportEntry> {
+    let args: &[&dyn ToSql] = &[&from.to_sql().unwrap(), &to.to_sql().unwrap()];
+    let query = "SELECT tenant, strftime('%Y-%m-%d', day, 'unixepoch'), api_calls, bytes_served, events_delivered
+        FROM usage WHERE day BETWEEN CAST(strftime('%s', ?1) AS INTEGER) AND CAST(strftime('%s', ?2) AS INTEGER)
+        ORDER BY day, tenant";
+    perform_query_set(query, args, db_conn, ctx, |row| UsageReportEntry {
+        tenant: row.get(0).unwrap(),
+        day: row.get(1).unwrap(),
+        usage: UsageCounters {
+            api_calls: row.get(2).unwrap(),
+            bytes_served: row.get(3).unwrap(),
+            events_delivered: row.get(4).unwrap(),
+        },
+    })
+}
+
+/// Usage of each tenant recorded for `day` (UNIX timestamp of its start).
+pub fn find_usage_of_day_in_observers(
+    day: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(String, UsageCounters)> {
+    let args: &[&dyn ToSql] = &[&day.to_sql().unwrap()];
+    let query = "SELECT tenant, api_calls, bytes_served, events_delivered FROM usage WHERE day = ?";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        (
+            row.get(0).unwrap(),
+            UsageCounters {
+                api_calls: row.get(1).unwrap(),
+                bytes_served: row.get(2).unwrap(),
+                events_delivered: row.get(3).unwrap(),
+            },
+        )
+    })
+}
+
 // Cases to cover:
 // - Empty state
 // - State present, but not up to date
@@ -194,11 +1017,13 @@ pub fn remove_entry_from_observers(uuid: &str, db_conn: &Connection, ctx: &Conte
 
 pub fn start_predicate_processor(
     event_observer_config: &EventObserverConfig,
+    config: &Config,
     ctx: &Context,
 ) -> Sender<BitcoinBlockData> {
     let (tx, rx) = channel();
 
     let mut moved_event_observer_config = event_observer_config.clone();
+    let moved_config = config.clone();
     let moved_ctx = ctx.clone();
 
     let _ = hiro_system_kit::thread_named("Initial predicate processing")
@@ -210,11 +1035,14 @@ pub fn start_predicate_processor(
                     bitcoin_predicate.enabled = false;
                     bitcoin_predicates_ref.push(bitcoin_predicate);
                 }
+                let mut enrichment_db_conns = EnrichmentDbConnections::new();
                 while let Ok(block) = rx.recv() {
                     let future = process_block_with_predicates(
                         block,
                         &bitcoin_predicates_ref,
                         &moved_event_observer_config,
+                        &mut enrichment_db_conns,
+                        &moved_config,
                         &moved_ctx,
                     );
                     let res = hiro_system_kit::nestable_block_on(future);
@@ -228,6 +1056,37 @@ pub fn start_predicate_processor(
     tx
 }
 
+/// Specification registering `observer` again, resuming after the last block delivered according to `report`.
+pub fn build_catchup_specification(
+    observer: BitcoinChainhookSpecification,
+    report: &ObserverReport,
+    config: &Config,
+) -> BitcoinChainhookFullSpecification {
+    let mut networks = BTreeMap::new();
+    networks.insert(
+        config.network.bitcoin_network.clone(),
+        BitcoinChainhookNetworkSpecification {
+            start_block: Some(report.last_block_height_update + 1),
+            end_block: observer.end_block,
+            blocks: observer.blocks,
+            expire_after_occurrence: observer.expire_after_occurrence,
+            include_proof: Some(observer.include_proof),
+            include_inputs: Some(observer.include_inputs),
+            include_outputs: Some(observer.include_outputs),
+            include_witness: Some(observer.include_witness),
+            predicate: observer.predicate,
+            action: get_observer_hook_action(&observer.uuid, observer.action),
+        },
+    );
+    BitcoinChainhookFullSpecification {
+        uuid: observer.uuid,
+        owner_uuid: observer.owner_uuid,
+        name: observer.name,
+        version: observer.version,
+        networks,
+    }
+}
+
 pub fn create_and_consolidate_chainhook_config_with_predicates(
     provided_observers: Vec<BitcoinChainhookSpecification>,
     chain_tip_height: u64,
@@ -235,7 +1094,7 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
     prometheus: &PrometheusMonitoring,
     config: &Config,
     ctx: &Context,
-) -> Result<(ChainhookConfig, Vec<BitcoinChainhookFullSpecification>), String> {
+) -> Result<(ChainhookConfig, Vec<BitcoinChainhookFullSpecification>), OrdhookError> {
     let mut chainhook_config: ChainhookConfig = ChainhookConfig::new();
     let mut meta_protocols: Option<HashSet<OrdinalsMetaProtocol>> = None;
     if config.meta_protocols.brc20 {
@@ -272,6 +1131,24 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
     }
 
     let observers_db_conn = initialize_observers_db(config, ctx);
+    for (uuid, filter) in find_all_brc20_filters(&observers_db_conn, ctx).into_iter() {
+        set_brc20_predicate_filter(&uuid, Some(filter));
+    }
+    for (uuid, filter) in find_all_wallet_filters(&observers_db_conn, ctx).into_iter() {
+        if let Err(e) = watch_wallet(&uuid, filter, config) {
+            try_warn!(ctx, "Unable to restore wallet of predicate {uuid}: {e}");
+        }
+    }
+    for (uuid, script) in find_all_predicate_scripts(&observers_db_conn, ctx).into_iter() {
+        if let Err(e) = set_predicate_script(&uuid, Some(&script)) {
+            try_warn!(ctx, "Unable to restore script of predicate {uuid}: {e}");
+        }
+    }
+    for (uuid, min_confirmations) in
+        find_all_predicate_min_confirmations(&observers_db_conn, ctx).into_iter()
+    {
+        set_predicate_min_confirmations(&uuid, Some(min_confirmations));
+    }
 
     let mut observers_to_catchup = vec![];
     let mut observers_to_clean_up = vec![];
@@ -304,6 +1181,26 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
             }
         }
 
+        // Paused observers are registered again once resumed.
+        if find_predicate_paused_at(&spec.uuid, &observers_db_conn, ctx).is_some() {
+            continue;
+        }
+
+        // Predicates pinned to a payload version no longer delivered are kept, but not registered, rather than
+        // receiving payloads they can't read.
+        if let Some(payload_version) =
+            find_predicate_payload_version(&spec.uuid, &observers_db_conn, ctx)
+        {
+            if let Err(e) = check_payload_version(payload_version) {
+                try_warn!(
+                    ctx,
+                    "Predicate {} is not registered: {e}, register it again with a supported payload version",
+                    spec.uuid
+                );
+                continue;
+            }
+        }
+
         if report.last_block_height_update == chain_tip_height {
             observers_ready.push(spec);
         } else {
@@ -314,13 +1211,36 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
     // Clean-up
     for outdated_observer in observers_to_clean_up.iter() {
         remove_entry_from_observers(outdated_observer, &observers_db_conn, ctx);
+        remove_brc20_filter_from_observers(outdated_observer, &observers_db_conn, ctx);
+        set_brc20_predicate_filter(outdated_observer, None);
+        unwatch_wallet(outdated_observer);
+        remove_wallet_filter_from_observers(outdated_observer, &observers_db_conn, ctx);
+        remove_predicate_script_from_observers(outdated_observer, &observers_db_conn, ctx);
+        let _ = set_predicate_script(outdated_observer, None);
+        remove_predicate_min_confirmations_from_observers(
+            outdated_observer,
+            &observers_db_conn,
+            ctx,
+        );
+        set_predicate_min_confirmations(outdated_observer, None);
+        remove_predicate_enrichment_from_observers(outdated_observer, &observers_db_conn, ctx);
+        remove_predicate_payload_version_from_observers(outdated_observer, &observers_db_conn, ctx);
+        remove_predicate_pause_from_observers(outdated_observer, &observers_db_conn, ctx);
+        stop_confirmed_stream(outdated_observer);
     }
 
     // Registrations
     for mut bitcoin_spec in observers_ready.into_iter() {
+        if get_predicate_min_confirmations(&bitcoin_spec.uuid).is_some() {
+            start_confirmed_stream(&bitcoin_spec, chain_tip_height);
+            continue;
+        }
         bitcoin_spec.enabled = true;
+        bitcoin_spec.action = get_observer_hook_action(&bitcoin_spec.uuid, bitcoin_spec.action);
         let spec = ChainhookSpecification::Bitcoin(bitcoin_spec);
-        chainhook_config.register_specification(spec)?;
+        chainhook_config
+            .register_specification(spec)
+            .map_err(OrdhookError::Config)?;
     }
 
     // Among observers provided, only consider the ones that are not known
@@ -337,29 +1257,7 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
 
     prometheus.metrics_set_registered_predicates(observers_to_catchup.len() as u64);
     for (observer, report) in observers_to_catchup.into_iter() {
-        let mut networks = BTreeMap::new();
-        networks.insert(
-            config.network.bitcoin_network.clone(),
-            BitcoinChainhookNetworkSpecification {
-                start_block: Some(report.last_block_height_update + 1),
-                end_block: observer.end_block,
-                blocks: observer.blocks,
-                expire_after_occurrence: observer.expire_after_occurrence,
-                include_proof: Some(observer.include_proof),
-                include_inputs: Some(observer.include_inputs),
-                include_outputs: Some(observer.include_outputs),
-                include_witness: Some(observer.include_witness),
-                predicate: observer.predicate,
-                action: observer.action,
-            },
-        );
-        let full_spec = BitcoinChainhookFullSpecification {
-            uuid: observer.uuid,
-            owner_uuid: observer.owner_uuid,
-            name: observer.name,
-            version: observer.version,
-            networks,
-        };
+        let full_spec = build_catchup_specification(observer, &report, config);
         info!(
             ctx.expect_logger(),
             "Observer '{}' to be caught-up (last block sent: {}, tip: {})",
@@ -0,0 +1,194 @@
+use serde_json::{json, Value as JsonValue};
+
+/// Version of the predicate specifications registered by this release. Older versions get upgraded on registration,
+/// and in the observers db on startup.
+pub const PREDICATE_SPEC_VERSION: u64 = 1;
+/// Oldest predicate specification version still accepted.
+pub const PREDICATE_SPEC_MIN_VERSION: u64 = 1;
+/// Version of the payloads delivered to predicates. Predicates can pin the version they expect with `payload_version`,
+/// stored with the predicate, and get rejected by the releases which no longer deliver it instead of receiving payloads
+/// they can't read. The version must be bumped whenever the shape of the payloads changes.
+pub const PAYLOAD_SCHEMA_VERSION: u64 = 2;
+/// Payloads are only delivered in their current shape: version 1 payloads, which destinations of outputs without
+/// address and locations of unbound inscriptions changed with version 2, are no longer delivered.
+pub const SUPPORTED_PAYLOAD_SCHEMA_VERSIONS: [u64; 1] = [2];
+
+/// Checks that a payload version pinned by a predicate is still delivered.
+pub fn check_payload_version(payload_version: u64) -> Result<(), String> {
+    if !SUPPORTED_PAYLOAD_SCHEMA_VERSIONS.contains(&payload_version) {
+        return Err(format!(
+            "payload version {payload_version} is not supported, expected one of {:?}",
+            SUPPORTED_PAYLOAD_SCHEMA_VERSIONS
+        ));
+    }
+    Ok(())
+}
+
+/// Upgrades of `if_this`, from each version starting at `PREDICATE_SPEC_MIN_VERSION` to the next one. Empty as long as
+/// the specification is at its first version.
+const PREDICATE_MIGRATIONS: [fn(&mut JsonValue); 0] = [];
+
+fn check_predicate_version(version: u64) -> Result<(), String> {
+    if version < PREDICATE_SPEC_MIN_VERSION || version > PREDICATE_SPEC_VERSION {
+        return Err(format!(
+            "predicate version {version} is not supported, expected {PREDICATE_SPEC_MIN_VERSION} to {PREDICATE_SPEC_VERSION}"
+        ));
+    }
+    Ok(())
+}
+
+fn upgrade_if_this(if_this: &mut JsonValue, from_version: u64) {
+    for migration in PREDICATE_MIGRATIONS
+        .iter()
+        .skip((from_version - PREDICATE_SPEC_MIN_VERSION) as usize)
+    {
+        migration(if_this);
+    }
+}
+
+/// Versions of a predicate specification submitted to the API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredicateVersions {
+    /// Version the specification was submitted with.
+    pub version: u64,
+    /// Payload version pinned by the predicate, or the current one.
+    pub payload_version: u64,
+}
+
+/// Upgrades a predicate specification submitted to the API to `PREDICATE_SPEC_VERSION`, and returns the version it was
+/// submitted with and the payload version it expects. Predicates without `version` are considered version 1.
+pub fn upgrade_predicate_specification(
+    predicate: &mut JsonValue,
+) -> Result<PredicateVersions, String> {
+    let version = match predicate.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or("predicate version must be a positive integer".to_string())?,
+        None => 1,
+    };
+    check_predicate_version(version)?;
+    let Some(predicate) = predicate.as_object_mut() else {
+        return Err("predicate must be an object".to_string());
+    };
+    let payload_version = match predicate.remove("payload_version") {
+        Some(payload_version) => {
+            let version = payload_version
+                .as_u64()
+                .ok_or("payload version must be a positive integer".to_string())?;
+            check_payload_version(version)?;
+            version
+        }
+        None => PAYLOAD_SCHEMA_VERSION,
+    };
+    if let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    {
+        for (_, network) in networks.iter_mut() {
+            if let Some(if_this) = network.get_mut("if_this") {
+                upgrade_if_this(if_this, version);
+            }
+        }
+    }
+    predicate.insert("version".to_string(), json!(PREDICATE_SPEC_VERSION));
+    Ok(PredicateVersions {
+        version,
+        payload_version,
+    })
+}
+
+/// Upgrades a predicate stored in the observers db to `PREDICATE_SPEC_VERSION`. Returns false if it was up to date.
+pub fn upgrade_stored_specification(spec: &mut JsonValue) -> Result<bool, String> {
+    let version = spec
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or("stored predicate has no version".to_string())?;
+    if version == PREDICATE_SPEC_VERSION {
+        return Ok(false);
+    }
+    check_predicate_version(version)?;
+    if let Some(predicate) = spec.get_mut("predicate") {
+        upgrade_if_this(predicate, version);
+    }
+    spec["version"] = json!(PREDICATE_SPEC_VERSION);
+    Ok(true)
+}
+
+/// Versions advertised by the API.
+pub fn get_supported_versions() -> JsonValue {
+    json!({
+        "ordhook": env!("CARGO_PKG_VERSION"),
+        "predicate": {
+            "current": PREDICATE_SPEC_VERSION,
+            "supported": (PREDICATE_SPEC_MIN_VERSION..=PREDICATE_SPEC_VERSION).collect::<Vec<u64>>(),
+        },
+        "payload": {
+            "current": PAYLOAD_SCHEMA_VERSION,
+            "supported": SUPPORTED_PAYLOAD_SCHEMA_VERSIONS,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{
+        upgrade_predicate_specification, upgrade_stored_specification, PredicateVersions,
+        PAYLOAD_SCHEMA_VERSION, PREDICATE_SPEC_VERSION,
+    };
+
+    #[test]
+    fn extracts_payload_version() {
+        let mut predicate = json!({
+            "payload_version": 2,
+            "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol", "operation": "inscription_feed" } } }
+        });
+        assert_eq!(
+            upgrade_predicate_specification(&mut predicate),
+            Ok(PredicateVersions {
+                version: 1,
+                payload_version: 2
+            })
+        );
+        assert_eq!(
+            predicate,
+            json!({
+                "version": PREDICATE_SPEC_VERSION,
+                "networks": { "mainnet": { "if_this": { "scope": "ordinals_protocol", "operation": "inscription_feed" } } }
+            })
+        );
+        assert_eq!(
+            upgrade_predicate_specification(&mut json!({ "version": 1 })),
+            Ok(PredicateVersions {
+                version: 1,
+                payload_version: PAYLOAD_SCHEMA_VERSION
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        assert!(upgrade_predicate_specification(&mut json!({ "version": 99 })).is_err());
+        assert!(upgrade_predicate_specification(&mut json!({ "version": 0 })).is_err());
+        assert!(upgrade_predicate_specification(
+            &mut json!({ "version": 1, "payload_version": 3 })
+        )
+        .is_err());
+        // Version 1 payloads are no longer delivered.
+        assert!(upgrade_predicate_specification(
+            &mut json!({ "version": 1, "payload_version": 1 })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn keeps_stored_predicates() {
+        let mut spec = json!({
+            "version": 1,
+            "predicate": { "scope": "ordinals_protocol", "operation": "inscription_feed" }
+        });
+        assert_eq!(upgrade_stored_specification(&mut spec), Ok(false));
+        assert!(upgrade_stored_specification(&mut json!({ "version": 2 })).is_err());
+    }
+}
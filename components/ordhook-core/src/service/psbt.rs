@@ -0,0 +1,319 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chainhook_sdk::{bitcoincore_rpc_json::bitcoin::psbt::Psbt, utils::Context};
+
+use crate::{
+    config::Config,
+    core::{
+        compute_next_satpoint_data,
+        protocol::{
+            addresses::{classify_script, script_address, ScriptType},
+            inscription_sequencing::get_bitcoin_network,
+        },
+        SatPosition,
+    },
+    db::{
+        ordinals::{
+            find_inscriptions_at_outpoint, find_latest_inscription_block_height,
+            find_watched_outputs, open_ordinals_db_snapshot,
+        },
+        sat_ranges::{find_rare_sats_in_output, open_readonly_sat_ranges_db_conn},
+    },
+    ord::sat::Sat,
+    utils::bitcoind::bitcoind_get_utxo_values,
+};
+
+/// `psbt` magic followed by the 0xff separator, hex encoded.
+const PSBT_MAGIC_HEX: &str = "70736274ff";
+
+/// Where a sat of an input would land once the transaction is mined.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtSatDestination {
+    /// None when the sat would be spent in fees.
+    pub vout: Option<u32>,
+    /// Offset in the output, or in the fees paid by the transaction.
+    pub offset: u64,
+    pub address: Option<String>,
+    /// None when the sat would be spent in fees.
+    pub script_type: Option<ScriptType>,
+    pub spent_in_fees: bool,
+    /// True if the output is an OP_RETURN output.
+    pub burnt: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtInscription {
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    /// Offset of the inscribed sat in the input.
+    pub offset: u64,
+    /// None when the value of an input before it is unknown.
+    pub destination: Option<PsbtSatDestination>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtRareSat {
+    pub ordinal_number: u64,
+    pub name: String,
+    pub rarity: Option<String>,
+    pub offset: u64,
+    pub destination: Option<PsbtSatDestination>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtInputAnnotation {
+    pub index: usize,
+    pub outpoint: String,
+    pub value: Option<u64>,
+    pub inscriptions: Vec<PsbtInscription>,
+    /// None when rare sats are not tracked by the index scope.
+    pub rare_sats: Option<Vec<PsbtRareSat>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtAnnotation {
+    /// Last block indexed by ordhook.
+    pub indexed_block_height: u64,
+    pub inputs: Vec<PsbtInputAnnotation>,
+    /// Inscriptions and rare sats spent in fees or burnt, and inputs which sats can't be followed.
+    pub warnings: Vec<String>,
+}
+
+/// Decodes a PSBT, base64 or hex encoded.
+pub fn decode_psbt(encoded: &str) -> Result<Psbt, String> {
+    let encoded = encoded.trim();
+    let bytes = match encoded.starts_with(PSBT_MAGIC_HEX) {
+        true => hex::decode(encoded).map_err(|e| format!("invalid psbt hex encoding: {e}"))?,
+        false => STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("invalid psbt base64 encoding: {e}"))?,
+    };
+    Psbt::deserialize(&bytes).map_err(|e| format!("invalid psbt: {e}"))
+}
+
+/// Where a sat of an input would land per ordinal theory: sats are assigned to the outputs first in first out, and sats
+/// past the last output are spent in fees.
+pub fn locate_sat_in_outputs(
+    input_index: usize,
+    offset: u64,
+    input_values: &Vec<u64>,
+    output_values: &Vec<u64>,
+) -> SatPosition {
+    if output_values.is_empty() {
+        let absolute_offset: u64 = input_values.iter().take(input_index).sum();
+        return SatPosition::Fee(absolute_offset + offset);
+    }
+    compute_next_satpoint_data(input_index, input_values, output_values, offset, None)
+}
+
+/// Annotates the inputs of a PSBT with the inscriptions and rare sats they carry, and where these would land if the
+/// transaction was broadcast, so that wallets can warn before signing. Input values are read from the PSBT, or from
+/// the outputs watched by the index and bitcoind's UTXO set when missing. Runes are not indexed by ordhook and are not reported.
+pub fn annotate_psbt(
+    encoded: &str,
+    config: &Config,
+    ctx: &Context,
+) -> Result<PsbtAnnotation, String> {
+    let psbt = decode_psbt(encoded)?;
+    let inscriptions_db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)?;
+    let sat_ranges_db_conn = open_readonly_sat_ranges_db_conn(config, ctx).ok();
+    let indexed_block_height =
+        find_latest_inscription_block_height(&inscriptions_db_conn, ctx)?.unwrap_or(0);
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    let tx = &psbt.unsigned_tx;
+    let mut warnings = vec![];
+
+    let mut input_values = tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(i, txin)| {
+            psbt.inputs
+                .get(i)
+                .and_then(|input| match input.witness_utxo {
+                    Some(ref output) => Some(output.value.to_sat()),
+                    None => input
+                        .non_witness_utxo
+                        .as_ref()
+                        .and_then(|prev_tx| prev_tx.output.get(txin.previous_output.vout as usize))
+                        .map(|output| output.value.to_sat()),
+                })
+        })
+        .collect::<Vec<_>>();
+    // Missing values are read from the outputs holding inscriptions first, then looked up in bitcoind in a single batch.
+    let missing_outpoints = tx
+        .input
+        .iter()
+        .zip(input_values.iter())
+        .filter(|(_, value)| value.is_none())
+        .map(|(txin, _)| txin.previous_output.to_string())
+        .collect::<Vec<_>>();
+    let watched_outputs = find_watched_outputs(&missing_outpoints, &inscriptions_db_conn, ctx);
+    for (txin, value) in tx.input.iter().zip(input_values.iter_mut()) {
+        if value.is_none() {
+            *value = watched_outputs
+                .get(&txin.previous_output.to_string())
+                .map(|output| output.value);
+        }
+    }
+    let missing_inputs = input_values
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| value.is_none())
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if !missing_inputs.is_empty() {
+        let outpoints = missing_inputs
+            .iter()
+            .map(|i| tx.input[*i].previous_output)
+            .collect::<Vec<_>>();
+        if let Ok(values) = bitcoind_get_utxo_values(&outpoints, config) {
+            for (i, value) in missing_inputs.into_iter().zip(values) {
+                input_values[i] = value;
+            }
+        }
+    }
+    for (i, (txin, value)) in tx.input.iter().zip(input_values.iter()).enumerate() {
+        if value.is_none() {
+            warnings.push(format!(
+                "value of input {i} ({}) is unknown, the sats of the inputs after it can't be located",
+                txin.previous_output
+            ));
+        }
+    }
+    let known_input_values = input_values
+        .iter()
+        .map(|value| value.unwrap_or(0))
+        .collect::<Vec<_>>();
+    let output_values = tx
+        .output
+        .iter()
+        .map(|output| output.value.to_sat())
+        .collect::<Vec<_>>();
+    let locate = |input_index: usize, offset: u64| -> Option<PsbtSatDestination> {
+        if input_values.iter().take(input_index).any(|v| v.is_none()) {
+            return None;
+        }
+        let destination =
+            match locate_sat_in_outputs(input_index, offset, &known_input_values, &output_values) {
+                SatPosition::Output((vout, offset)) => {
+                    let script_pubkey = &tx.output[vout].script_pubkey;
+                    PsbtSatDestination {
+                        vout: Some(vout as u32),
+                        offset,
+                        address: script_address(script_pubkey, &network),
+                        script_type: Some(classify_script(script_pubkey)),
+                        spent_in_fees: false,
+                        burnt: script_pubkey.is_op_return(),
+                    }
+                }
+                SatPosition::Fee(offset) => PsbtSatDestination {
+                    vout: None,
+                    offset,
+                    address: None,
+                    script_type: None,
+                    spent_in_fees: true,
+                    burnt: false,
+                },
+            };
+        Some(destination)
+    };
+
+    let mut inputs = vec![];
+    for (i, txin) in tx.input.iter().enumerate() {
+        let txid = txin.previous_output.txid.to_string();
+        let vout = txin.previous_output.vout;
+        let outpoint = format!("{txid}:{vout}");
+        let mut inscriptions = vec![];
+        for row in find_inscriptions_at_outpoint(&outpoint, &inscriptions_db_conn, ctx) {
+            let offset = row
+                .location
+                .rsplit(':')
+                .next()
+                .and_then(|offset| offset.parse().ok())
+                .unwrap_or(0);
+            let destination = locate(i, offset);
+            match destination {
+                Some(ref d) if d.spent_in_fees => warnings.push(format!(
+                    "inscription {} of input {i} would be spent in fees",
+                    row.inscription_id
+                )),
+                Some(ref d) if d.burnt => warnings.push(format!(
+                    "inscription {} of input {i} would be burnt",
+                    row.inscription_id
+                )),
+                _ => {}
+            }
+            inscriptions.push(PsbtInscription {
+                inscription_id: row.inscription_id,
+                inscription_number: row.inscription_number,
+                offset,
+                destination,
+            });
+        }
+        let rare_sats = sat_ranges_db_conn.as_ref().map(|db_conn| {
+            let mut rare_sats = vec![];
+            for range in find_rare_sats_in_output(&txid, vout, db_conn, ctx) {
+                let destination = locate(i, range.offset);
+                match destination {
+                    Some(ref d) if d.spent_in_fees => warnings.push(format!(
+                        "rare sat {} of input {i} would be spent in fees",
+                        range.start
+                    )),
+                    Some(ref d) if d.burnt => warnings.push(format!(
+                        "rare sat {} of input {i} would be burnt",
+                        range.start
+                    )),
+                    _ => {}
+                }
+                rare_sats.push(PsbtRareSat {
+                    ordinal_number: range.start,
+                    name: Sat(range.start).name(),
+                    rarity: range.rarity,
+                    offset: range.offset,
+                    destination,
+                });
+            }
+            rare_sats
+        });
+        inputs.push(PsbtInputAnnotation {
+            index: i,
+            outpoint,
+            value: input_values[i],
+            inscriptions,
+            rare_sats,
+        });
+    }
+    Ok(PsbtAnnotation {
+        indexed_block_height,
+        inputs,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{decode_psbt, locate_sat_in_outputs};
+    use crate::core::SatPosition;
+
+    #[test_case(0, 0, vec![1_000, 500], vec![600, 700] => SatPosition::Output((0, 0)); "first sat")]
+    #[test_case(1, 0, vec![1_000, 500], vec![600, 700] => SatPosition::Output((1, 400)); "first sat of the second input")]
+    #[test_case(1, 350, vec![1_000, 500], vec![600, 700] => SatPosition::Fee(50); "sat spent in fees")]
+    #[test_case(0, 10, vec![1_000], vec![] => SatPosition::Fee(10); "no outputs")]
+    fn locates_sats_in_outputs(
+        input_index: usize,
+        offset: u64,
+        input_values: Vec<u64>,
+        output_values: Vec<u64>,
+    ) -> SatPosition {
+        locate_sat_in_outputs(input_index, offset, &input_values, &output_values)
+    }
+
+    #[test]
+    fn rejects_invalid_psbts() {
+        assert!(decode_psbt("cHNidP8=").is_err());
+        assert!(decode_psbt("70736274ffzz").is_err());
+        assert!(decode_psbt("not a psbt").is_err());
+    }
+}
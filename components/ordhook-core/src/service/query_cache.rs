@@ -0,0 +1,165 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use chainhook_sdk::types::BitcoinBlockData;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    core::protocol::inscription_parsing::{
+        get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
+    },
+    utils::monitoring::PrometheusMonitoring,
+};
+
+/// Responses kept before the cache is emptied, bounding its memory usage.
+const QUERY_CACHE_MAX_ENTRIES: usize = 10_000;
+
+/// Rows a cached response was built from. Responses are invalidated when a block mutates one of them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheTag {
+    /// Inscriptions held by a sat, moved by reveals and transfers.
+    Sat(u64),
+    /// Data of an inscription updated after its reveal, like its content scan.
+    Inscription(String),
+    /// Data depending on the latest block indexed, like the latest index commitment or sales.
+    ChainTip,
+}
+
+struct CachedResponse {
+    response: JsonValue,
+    tags: Vec<CacheTag>,
+}
+
+#[derive(Default)]
+struct QueryCache {
+    /// Bumped by every invalidation, for the responses built from rows read before it to be discarded.
+    generation: u64,
+    responses: HashMap<String, CachedResponse>,
+}
+
+lazy_static! {
+    /// Responses of the read API, by request URI.
+    static ref QUERY_CACHE: RwLock<QueryCache> = RwLock::new(QueryCache::default());
+}
+
+/// Generation of the cache, to be read before querying the rows of a response and passed to `cache_response`.
+pub fn get_query_cache_generation() -> u64 {
+    QUERY_CACHE
+        .read()
+        .map(|cache| cache.generation)
+        .unwrap_or(0)
+}
+
+/// Cached response of the request `uri`, if any. Lookups are recorded in the hit rate metrics.
+pub fn get_cached_response(uri: &str, prometheus: &PrometheusMonitoring) -> Option<JsonValue> {
+    let response = QUERY_CACHE
+        .read()
+        .ok()
+        .and_then(|cache| cache.responses.get(uri).map(|entry| entry.response.clone()));
+    prometheus.metrics_query_cache_lookup(response.is_some());
+    response
+}
+
+/// Caches a response built from rows read at `generation`. Responses are discarded when the cache was invalidated
+/// since then: their rows may have been mutated after being read.
+pub fn cache_response(uri: String, response: &JsonValue, tags: Vec<CacheTag>, generation: u64) {
+    let Ok(mut cache) = QUERY_CACHE.write() else {
+        return;
+    };
+    if cache.generation != generation {
+        return;
+    }
+    if cache.responses.len() >= QUERY_CACHE_MAX_ENTRIES {
+        cache.responses.clear();
+    }
+    cache.responses.insert(
+        uri,
+        CachedResponse {
+            response: response.clone(),
+            tags,
+        },
+    );
+}
+
+fn invalidate_cached_responses(tags: &[CacheTag]) {
+    if let Ok(mut cache) = QUERY_CACHE.write() {
+        cache.generation += 1;
+        cache
+            .responses
+            .retain(|_, entry| !entry.tags.iter().any(|tag| tags.contains(tag)));
+    }
+}
+
+/// Invalidates the responses built from rows mutated by `block`. Must be called once its changes are committed.
+pub fn invalidate_cached_responses_of_block(block: &BitcoinBlockData) {
+    let mut tags = vec![CacheTag::ChainTip];
+    for reveal in get_inscriptions_revealed_in_block(block) {
+        tags.push(CacheTag::Sat(reveal.ordinal_number));
+        tags.push(CacheTag::Inscription(reveal.inscription_id.clone()));
+    }
+    for transfer in get_inscriptions_transferred_in_block(block) {
+        tags.push(CacheTag::Sat(transfer.ordinal_number));
+    }
+    invalidate_cached_responses(&tags);
+}
+
+pub fn invalidate_cached_responses_of_inscription(inscription_id: &str) {
+    invalidate_cached_responses(&[CacheTag::Inscription(inscription_id.to_string())]);
+}
+
+/// Empties the cache. Rollbacks and amendments can renumber inscriptions, and blocklist entries can match any
+/// content, so nothing cached can be trusted after one.
+pub fn clear_query_cache() {
+    if let Ok(mut cache) = QUERY_CACHE.write() {
+        cache.generation += 1;
+        cache.responses.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::utils::monitoring::PrometheusMonitoring;
+
+    use super::{
+        cache_response, get_cached_response, get_query_cache_generation,
+        invalidate_cached_responses, CacheTag,
+    };
+
+    #[test]
+    fn invalidates_responses_by_tag() {
+        let prometheus = PrometheusMonitoring::new();
+        let generation = get_query_cache_generation();
+        cache_response(
+            "/ordhook/v1/inscriptions/a".into(),
+            &json!({ "result": "a" }),
+            vec![CacheTag::Sat(1), CacheTag::Inscription("a".into())],
+            generation,
+        );
+        cache_response(
+            "/ordhook/v1/commitments/latest".into(),
+            &json!({ "result": "tip" }),
+            vec![CacheTag::ChainTip],
+            generation,
+        );
+        assert_eq!(
+            get_cached_response("/ordhook/v1/inscriptions/a", &prometheus),
+            Some(json!({ "result": "a" }))
+        );
+        invalidate_cached_responses(&[CacheTag::ChainTip, CacheTag::Sat(2)]);
+        assert!(get_cached_response("/ordhook/v1/inscriptions/a", &prometheus).is_some());
+        assert!(get_cached_response("/ordhook/v1/commitments/latest", &prometheus).is_none());
+        invalidate_cached_responses(&[CacheTag::Sat(1)]);
+        assert!(get_cached_response("/ordhook/v1/inscriptions/a", &prometheus).is_none());
+        // Responses read before an invalidation are not cached.
+        cache_response(
+            "/ordhook/v1/inscriptions/a".into(),
+            &json!({ "result": "a" }),
+            vec![CacheTag::Sat(1)],
+            generation,
+        );
+        assert!(get_cached_response("/ordhook/v1/inscriptions/a", &prometheus).is_none());
+        assert_eq!(prometheus.query_cache_hits.get(), 2);
+        assert_eq!(prometheus.query_cache_misses.get(), 3);
+    }
+}
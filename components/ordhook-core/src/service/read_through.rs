@@ -0,0 +1,131 @@
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::utils::Context;
+use lru::LruCache;
+use serde_json::Value as JsonValue;
+
+use crate::{config::Config, try_info, try_warn, utils::http::outbound_http_client_builder};
+
+/// Upstream responses are cached for a short time only: inscriptions keep moving until the local index catches up.
+const READ_THROUGH_CACHE_TTL: Duration = Duration::from_secs(60);
+const READ_THROUGH_CACHE_SIZE: usize = 10_000;
+const READ_THROUGH_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// Results served from the upstream API, by request path.
+    static ref READ_THROUGH_CACHE: Mutex<LruCache<String, (JsonValue, Instant)>> = Mutex::new(
+        LruCache::new(NonZeroUsize::new(READ_THROUGH_CACHE_SIZE).unwrap())
+    );
+}
+
+/// Fetches the result of a request that missed locally from the upstream ordhook API configured in
+/// `http_api.upstream_api_url`, so that a deployment still syncing can serve traffic.
+///
+/// Results about blocks the local index already covers are discarded: local data is authoritative for those, and a
+/// local miss then means the upstream disagrees with us.
+pub fn read_through_upstream(
+    path: &str,
+    local_tip: Option<u64>,
+    config: &Config,
+    ctx: &Context,
+) -> Option<JsonValue> {
+    let upstream_api_url = config.upstream_api_url()?;
+    if let Ok(mut cache) = READ_THROUGH_CACHE.lock() {
+        if let Some((result, fetched_at)) = cache.get(path) {
+            if fetched_at.elapsed() < READ_THROUGH_CACHE_TTL {
+                return Some(result.clone());
+            }
+        }
+        cache.pop(path);
+    }
+    let url = format!("{}{}", upstream_api_url.trim_end_matches('/'), path);
+    let result = match fetch_upstream_result(&url, config) {
+        Ok(Some(result)) => result,
+        Ok(None) => return None,
+        Err(e) => {
+            try_warn!(ctx, "Read-through: {e}");
+            return None;
+        }
+    };
+    let upstream_block_height = result.get("block_height").and_then(|h| h.as_u64());
+    if let (Some(upstream_block_height), Some(local_tip)) = (upstream_block_height, local_tip) {
+        if upstream_block_height <= local_tip {
+            try_warn!(
+                ctx,
+                "Read-through: {url} returned data from block #{upstream_block_height}, already indexed locally (tip #{local_tip}), discarding"
+            );
+            return None;
+        }
+    }
+    try_info!(ctx, "Read-through: {path} served from upstream");
+    if let Ok(mut cache) = READ_THROUGH_CACHE.lock() {
+        cache.put(path.to_string(), (result.clone(), Instant::now()));
+    }
+    Some(result)
+}
+
+/// Compares the immutable `keys` of a result found locally with the upstream result previously served for the same
+/// request, if any. Entries are evicted once local data is available.
+pub fn validate_read_through_result(
+    path: &str,
+    local_result: &JsonValue,
+    keys: &[&str],
+    ctx: &Context,
+) {
+    let Ok(mut cache) = READ_THROUGH_CACHE.lock() else {
+        return;
+    };
+    let Some((upstream_result, _)) = cache.pop(path) else {
+        return;
+    };
+    let mismatches = keys
+        .iter()
+        .filter(|key| upstream_result.get(**key) != local_result.get(**key))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !mismatches.is_empty() {
+        try_warn!(
+            ctx,
+            "Read-through: upstream result previously served for {path} differs from local data ({})",
+            mismatches.join(", ")
+        );
+    }
+}
+
+/// Returns the `result` of a successful upstream response, or `None` if the upstream does not know the resource
+/// either.
+fn fetch_upstream_result(url: &str, config: &Config) -> Result<Option<JsonValue>, String> {
+    let url = url.to_string();
+    let client_builder = outbound_http_client_builder(&config.resources)?;
+    // Handlers run within the async runtime, perform the request on a dedicated thread.
+    std::thread::spawn(move || {
+        hiro_system_kit::nestable_block_on(async move {
+            let client = client_builder
+                .timeout(READ_THROUGH_TIMEOUT)
+                .build()
+                .map_err(|e| format!("unable to build http client: {}", e))?;
+            let res = client
+                .get(&url)
+                .send()
+                .await
+                .map_err(|e| format!("unable to reach {}: {}", url, e))?;
+            if res.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !res.status().is_success() {
+                return Err(format!("{} responded with status {}", url, res.status()));
+            }
+            let body = res
+                .json::<JsonValue>()
+                .await
+                .map_err(|e| format!("invalid response from {}: {}", url, e))?;
+            Ok(body.get("result").cloned())
+        })
+    })
+    .join()
+    .unwrap_or(Err("read-through thread panicked".to_string()))
+}
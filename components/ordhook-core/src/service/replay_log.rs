@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chainhook_sdk::{
+    observer::{BitcoinBlockDataCached, HandleBlock},
+    types::{BitcoinBlockData, BlockIdentifier},
+    utils::Context,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{config::Config, try_warn};
+
+const REPLAY_LOG_ENTRY_EXTENSION: &str = "json.gz";
+
+lazy_static! {
+    /// Sequence number of the next entry, by replay log, read from the entries on disk on the first record.
+    static ref NEXT_REPLAY_LOG_SEQUENCES: Mutex<HashMap<PathBuf, u64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayLogBlock {
+    pub block: BitcoinBlockData,
+    pub processed_by_sidecar: bool,
+}
+
+/// Payload received from the event observer, in the order the sidecar handled them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplayLogEntry {
+    /// Blocks to augment, after rolling back the blocks orphaned by a re-org.
+    MutateBlocks {
+        blocks: Vec<ReplayLogBlock>,
+        rollbacks: Vec<BlockIdentifier>,
+    },
+    /// Augmented block to write to the index.
+    ApplyBlock { block: BitcoinBlockData },
+    /// Block to remove from the index.
+    UndoBlock { block: BitcoinBlockData },
+}
+
+impl ReplayLogEntry {
+    pub fn from_blocks_to_mutate(
+        blocks: &Vec<BitcoinBlockDataCached>,
+        rollbacks: &Vec<BlockIdentifier>,
+    ) -> ReplayLogEntry {
+        ReplayLogEntry::MutateBlocks {
+            blocks: blocks
+                .iter()
+                .map(|cache| ReplayLogBlock {
+                    block: cache.block.clone(),
+                    processed_by_sidecar: cache.processed_by_sidecar,
+                })
+                .collect(),
+            rollbacks: rollbacks.clone(),
+        }
+    }
+
+    pub fn from_handle_block(command: &HandleBlock) -> ReplayLogEntry {
+        match command {
+            HandleBlock::ApplyBlock(block) => ReplayLogEntry::ApplyBlock {
+                block: block.clone(),
+            },
+            HandleBlock::UndoBlock(block) => ReplayLogEntry::UndoBlock {
+                block: block.clone(),
+            },
+        }
+    }
+}
+
+fn parse_replay_log_sequence(path: &Path) -> Option<u64> {
+    let file_name = path.file_name()?.to_str()?;
+    let sequence = file_name.strip_suffix(&format!(".{REPLAY_LOG_ENTRY_EXTENSION}"))?;
+    sequence.parse::<u64>().ok()
+}
+
+/// Entries of the replay log, oldest first.
+pub fn list_replay_log_entries(replay_log_path: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let dir = match fs::read_dir(replay_log_path) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("unable to read {}: {e}", replay_log_path.display())),
+    };
+    let mut entries = vec![];
+    for entry in dir.flatten() {
+        let path = entry.path();
+        if let Some(sequence) = parse_replay_log_sequence(&path) {
+            entries.push((sequence, path));
+        }
+    }
+    entries.sort_by_key(|(sequence, _)| *sequence);
+    Ok(entries)
+}
+
+pub fn read_replay_log_entry(path: &Path) -> Result<ReplayLogEntry, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    serde_json::from_reader(BufReader::new(GzDecoder::new(file)))
+        .map_err(|e| format!("unable to read {}: {e}", path.display()))
+}
+
+/// Drops the oldest entries until the log fits in `max_size_bytes`. The latest entry is always kept.
+fn prune_replay_log(replay_log_path: &Path, max_size_bytes: u64) -> Result<(), String> {
+    let entries = list_replay_log_entries(replay_log_path)?;
+    let sizes = entries
+        .iter()
+        .map(|(_, path)| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+        .collect::<Vec<_>>();
+    let mut total_size: u64 = sizes.iter().sum();
+    for ((_, path), size) in entries
+        .iter()
+        .zip(sizes)
+        .take(entries.len().saturating_sub(1))
+    {
+        if total_size <= max_size_bytes {
+            break;
+        }
+        fs::remove_file(path).map_err(|e| format!("unable to remove {}: {e}", path.display()))?;
+        total_size -= size;
+    }
+    Ok(())
+}
+
+fn write_replay_log_entry(
+    entry: &ReplayLogEntry,
+    replay_log_path: &Path,
+    max_size_bytes: u64,
+) -> Result<u64, String> {
+    let mut next_sequences = NEXT_REPLAY_LOG_SEQUENCES
+        .lock()
+        .map_err(|e| format!("unable to lock replay log: {e}"))?;
+    let sequence = match next_sequences.get(replay_log_path) {
+        Some(sequence) => *sequence,
+        None => {
+            fs::create_dir_all(replay_log_path)
+                .map_err(|e| format!("unable to create {}: {e}", replay_log_path.display()))?;
+            list_replay_log_entries(replay_log_path)?
+                .last()
+                .map(|(sequence, _)| sequence + 1)
+                .unwrap_or(0)
+        }
+    };
+    let path = replay_log_path.join(format!("{sequence:020}.{REPLAY_LOG_ENTRY_EXTENSION}"));
+    let file =
+        File::create(&path).map_err(|e| format!("unable to create {}: {e}", path.display()))?;
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    serde_json::to_writer(&mut encoder, entry)
+        .map_err(|e| format!("unable to write {}: {e}", path.display()))?;
+    encoder
+        .finish()
+        .map_err(|e| format!("unable to write {}: {e}", path.display()))?;
+    next_sequences.insert(replay_log_path.to_path_buf(), sequence + 1);
+    prune_replay_log(replay_log_path, max_size_bytes)?;
+    Ok(sequence)
+}
+
+/// Appends a received payload to the replay log, when enabled. Failures are only logged: recording must never hold
+/// back indexing.
+pub fn record_replay_log_entry<F>(build_entry: F, config: &Config, ctx: &Context)
+where
+    F: FnOnce() -> ReplayLogEntry,
+{
+    let (Some(replay_log_config), Some(replay_log_path)) =
+        (&config.replay_log, config.expected_replay_log_path())
+    else {
+        return;
+    };
+    if let Err(e) = write_replay_log_entry(
+        &build_entry(),
+        &replay_log_path,
+        replay_log_config.max_size_mb * 1024 * 1024,
+    ) {
+        try_warn!(ctx, "Replay log: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::BlockIdentifier;
+
+    use crate::core::test_builders::TestBlockBuilder;
+
+    use super::{
+        list_replay_log_entries, read_replay_log_entry, write_replay_log_entry, ReplayLogBlock,
+        ReplayLogEntry,
+    };
+
+    #[test]
+    fn records_replay_log_as_a_ring_buffer() {
+        let replay_log_path = std::env::temp_dir().join("ordhook_test_replay_log");
+        let _ = std::fs::remove_dir_all(&replay_log_path);
+
+        let block = TestBlockBuilder::new().height(800_000).build();
+        let entries = vec![
+            ReplayLogEntry::MutateBlocks {
+                blocks: vec![ReplayLogBlock {
+                    block: block.clone(),
+                    processed_by_sidecar: false,
+                }],
+                rollbacks: vec![BlockIdentifier {
+                    index: 800_000,
+                    hash: "0x01".to_string(),
+                }],
+            },
+            ReplayLogEntry::ApplyBlock {
+                block: block.clone(),
+            },
+            ReplayLogEntry::UndoBlock { block },
+        ];
+        for entry in entries.iter() {
+            write_replay_log_entry(entry, &replay_log_path, u64::MAX).unwrap();
+        }
+        let recorded = list_replay_log_entries(&replay_log_path).unwrap();
+        assert_eq!(
+            recorded.iter().map(|(s, _)| *s).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        for ((_, path), entry) in recorded.iter().zip(entries.iter()) {
+            assert_eq!(&read_replay_log_entry(path).unwrap(), entry);
+        }
+
+        // With a cap smaller than a single entry, only the latest entry is kept.
+        let sequence = write_replay_log_entry(&entries[1], &replay_log_path, 1).unwrap();
+        assert_eq!(sequence, 3);
+        let recorded = list_replay_log_entries(&replay_log_path).unwrap();
+        assert_eq!(
+            recorded.iter().map(|(s, _)| *s).collect::<Vec<_>>(),
+            vec![3]
+        );
+        let _ = std::fs::remove_dir_all(&replay_log_path);
+    }
+}
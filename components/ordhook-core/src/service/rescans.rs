@@ -0,0 +1,98 @@
+use chainhook_sdk::{
+    chainhooks::types::{BitcoinChainhookSpecification, ChainhookSpecification},
+    utils::Context,
+};
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate_with_control,
+    service::{
+        jobs::{submit_job, Job, JobControl, JobKind},
+        observers::{find_observer_with_uuid, open_readonly_observers_db_conn},
+        tenants::TenantScope,
+    },
+};
+
+fn validate_rescan_request(
+    start_block: u64,
+    end_block: u64,
+    predicate_uuids: &Vec<String>,
+) -> Result<(), String> {
+    if start_block > end_block {
+        return Err("start must be lower than or equal to end".into());
+    }
+    if predicate_uuids.is_empty() {
+        return Err("predicates must list the uuids of the predicates to rescan".into());
+    }
+    Ok(())
+}
+
+/// Queues the rescan of `start_block..=end_block` for registered predicates. Occurrences are delivered through the
+/// actions of the predicates, which progress and streaming are left untouched.
+pub fn queue_rescan(
+    start_block: u64,
+    end_block: u64,
+    predicate_uuids: Vec<String>,
+    scope: &TenantScope,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Job, String> {
+    validate_rescan_request(start_block, end_block, &predicate_uuids)?;
+    let observers_db_conn = open_readonly_observers_db_conn(config, ctx)?;
+    let mut predicate_specs: Vec<BitcoinChainhookSpecification> = vec![];
+    for uuid in predicate_uuids.iter() {
+        let Some((ChainhookSpecification::Bitcoin(mut spec), _)) =
+            find_observer_with_uuid(uuid, &observers_db_conn, ctx)
+                .filter(|(spec, _)| scope.can_access_predicate(spec))
+        else {
+            return Err(format!("predicate {uuid} not found"));
+        };
+        spec.blocks = None;
+        spec.start_block = Some(start_block);
+        spec.end_block = Some(end_block);
+        predicate_specs.push(spec);
+    }
+
+    let mut params = json!({
+        "start": start_block,
+        "end": end_block,
+        "predicates": predicate_uuids,
+    });
+    scope.claim_job_params(&mut params);
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    submit_job(
+        JobKind::Rescan,
+        params,
+        (end_block - start_block + 1) * predicate_specs.len() as u64,
+        Box::new(move |control: &JobControl| {
+            let scan_control = control.scan_control();
+            for spec in predicate_specs.iter() {
+                let op = scan_bitcoin_chainstate_via_rpc_using_predicate_with_control(
+                    spec,
+                    &moved_config,
+                    None,
+                    Some(&scan_control),
+                    &moved_ctx,
+                );
+                hiro_system_kit::nestable_block_on(op)?;
+            }
+            Ok(())
+        }),
+        config,
+        ctx,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::validate_rescan_request;
+
+    #[test]
+    fn rejects_invalid_rescan_requests() {
+        assert!(validate_rescan_request(10, 5, &vec!["uuid".into()]).is_err());
+        assert!(validate_rescan_request(5, 10, &vec![]).is_err());
+        assert!(validate_rescan_request(5, 5, &vec!["uuid".into()]).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+use chainhook_sdk::{types::BlockIdentifier, utils::Context};
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    db::ordinals::{
+        find_all_transfers_in_block, find_inscription_ids_with_ordinal_number,
+        find_inscription_transfer_data_before_block_height, find_inscriptions_revealed_in_block,
+        RevealedInscription, TransferData,
+    },
+    service::event_queue::{enqueue_events, QueuedEvent},
+    try_info,
+    utils::format_outpoint_to_watch,
+};
+
+/// Move of an inscribed sat undone by a rollback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevertedTransfer {
+    pub ordinal_number: u64,
+    pub inscription_ids: Vec<String>,
+    pub tx_index: u64,
+    pub satpoint: String,
+    /// Location the sat is back to once the transfer is reverted. Missing if it had no known location.
+    pub prior_satpoint: Option<String>,
+}
+
+/// Data of a block undone by a rollback: the inscriptions it revealed, and the transfers of previously revealed
+/// inscriptions it performed. Inscriptions revealed by the block are not listed among the transfers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockRollback {
+    pub block_identifier: BlockIdentifier,
+    pub unrevealed_inscriptions: Vec<RevealedInscription>,
+    pub reverted_transfers: Vec<RevertedTransfer>,
+}
+
+fn format_satpoint(transfer: &TransferData) -> String {
+    format!(
+        "{}:{}",
+        format_outpoint_to_watch(
+            &transfer.transaction_identifier_location,
+            transfer.output_index
+        ),
+        transfer.inscription_offset_intra_output
+    )
+}
+
+impl BlockRollback {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "type": "rollback",
+            "block_height": self.block_identifier.index,
+            "block_hash": self.block_identifier.hash,
+            "unrevealed_inscriptions": self.unrevealed_inscriptions.iter().map(|inscription| json!({
+                "inscription_id": inscription.inscription_id,
+                "ordinal_number": inscription.ordinal_number,
+                "inscription_number": {
+                    "jubilee": inscription.jubilee_inscription_number,
+                    "classic": inscription.classic_inscription_number,
+                },
+            })).collect::<Vec<_>>(),
+            "reverted_transfers": self.reverted_transfers.iter().map(|transfer| json!({
+                "ordinal_number": transfer.ordinal_number,
+                "inscription_ids": transfer.inscription_ids,
+                "tx_index": transfer.tx_index,
+                "satpoint": transfer.satpoint,
+                "prior_satpoint": transfer.prior_satpoint,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Data of a block about to be rolled back. Must be called before the data of the block is dropped. Returns `None`
+/// when no `[rollbacks]` hook is configured.
+pub fn get_block_rollback(
+    block_identifier: &BlockIdentifier,
+    inscriptions_db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Option<BlockRollback> {
+    config.rollbacks.as_ref()?;
+    let unrevealed_inscriptions =
+        find_inscriptions_revealed_in_block(block_identifier.index, inscriptions_db_conn, ctx);
+    let mut reverted_transfers = vec![];
+    for (ordinal_number, transfers) in
+        find_all_transfers_in_block(&block_identifier.index, inscriptions_db_conn, ctx)
+    {
+        if unrevealed_inscriptions
+            .iter()
+            .any(|inscription| inscription.ordinal_number == Some(ordinal_number))
+        {
+            continue;
+        }
+        let inscription_ids =
+            find_inscription_ids_with_ordinal_number(ordinal_number, inscriptions_db_conn, ctx);
+        // A sat moved several times within the block goes back through each of its locations.
+        let mut prior_satpoint = find_inscription_transfer_data_before_block_height(
+            ordinal_number,
+            block_identifier.index,
+            inscriptions_db_conn,
+            ctx,
+        )
+        .map(|transfer| format_satpoint(&transfer));
+        for transfer in transfers.iter() {
+            let satpoint = format_satpoint(transfer);
+            reverted_transfers.push(RevertedTransfer {
+                ordinal_number,
+                inscription_ids: inscription_ids.clone(),
+                tx_index: transfer.tx_index,
+                satpoint: satpoint.clone(),
+                prior_satpoint: prior_satpoint.replace(satpoint),
+            });
+        }
+    }
+    reverted_transfers.sort_by_key(|transfer| transfer.tx_index);
+    Some(BlockRollback {
+        block_identifier: block_identifier.clone(),
+        unrevealed_inscriptions,
+        reverted_transfers,
+    })
+}
+
+/// Queues a `rollback` event for every block rolled back, from the highest one down. Events are delivered in order by
+/// the event delivery worker, so that slow receivers do not hold back indexing. Must only be called once the data of
+/// the blocks was dropped.
+pub fn send_rollback_events(rollbacks: Vec<BlockRollback>, config: &Config, ctx: &Context) {
+    let Some(ref rollbacks_config) = config.rollbacks else {
+        return;
+    };
+    if rollbacks_config.webhook_url.is_none() && rollbacks_config.command.is_none() {
+        return;
+    }
+    let events = rollbacks
+        .iter()
+        .map(|rollback| {
+            try_info!(
+                ctx,
+                "Rollback: block #{} ({} inscriptions unrevealed, {} transfers reverted)",
+                rollback.block_identifier.index,
+                rollback.unrevealed_inscriptions.len(),
+                rollback.reverted_transfers.len()
+            );
+            QueuedEvent {
+                payload: rollback.to_json(),
+                webhook_url: rollbacks_config.webhook_url.clone(),
+                command: rollbacks_config.command.clone(),
+                env_var: "ORDHOOK_EVENT".into(),
+                resources: config.resources.clone(),
+            }
+        })
+        .collect();
+    enqueue_events(events, ctx);
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::{types::BlockIdentifier, utils::Context};
+    use serde_json::json;
+
+    use crate::{
+        config::{Config, RollbacksConfig},
+        db::ordinals::initialize_ordinals_db,
+    };
+
+    use super::get_block_rollback;
+
+    #[test]
+    fn lists_data_reverted_by_rollbacks() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        let working_dir = std::env::temp_dir().join("ordhook_test_rollbacks");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let db_conn = initialize_ordinals_db(&working_dir, &ctx);
+        let block_identifier = BlockIdentifier {
+            index: 800_001,
+            hash: "0x01".to_string(),
+        };
+        assert!(get_block_rollback(&block_identifier, &db_conn, &config, &ctx).is_none());
+        config.rollbacks = Some(RollbacksConfig {
+            webhook_url: None,
+            command: None,
+        });
+
+        let tx_a = "a".repeat(64);
+        let tx_b = "b".repeat(64);
+        let tx_c = "c".repeat(64);
+        db_conn
+            .execute_batch(&format!(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES ('{tx_a}i0', 10, 1, 1, 800000, 0), ('{tx_c}i0', 20, 2, 2, 800001, 0);
+                INSERT INTO locations (ordinal_number, outpoint_to_watch, offset, block_height, tx_index) VALUES (10, '{tx_a}:0', 0, 800000, 1), (10, '{tx_b}:1', 0, 800001, 2), (10, '{tx_c}:1', 0, 800001, 3), (20, '{tx_c}:0', 0, 800001, 3);"
+            ))
+            .unwrap();
+
+        let rollback = get_block_rollback(&block_identifier, &db_conn, &config, &ctx).unwrap();
+        let event = rollback.to_json();
+        assert_eq!(
+            event["unrevealed_inscriptions"],
+            json!([{
+                "inscription_id": format!("{tx_c}i0"),
+                "ordinal_number": 20,
+                "inscription_number": { "jubilee": 2, "classic": 2 },
+            }])
+        );
+        assert_eq!(
+            event["reverted_transfers"],
+            json!([
+                {
+                    "ordinal_number": 10,
+                    "inscription_ids": [format!("{tx_a}i0")],
+                    "tx_index": 2,
+                    "satpoint": format!("{tx_b}:1:0"),
+                    "prior_satpoint": format!("{tx_a}:0:0"),
+                },
+                {
+                    "ordinal_number": 10,
+                    "inscription_ids": [format!("{tx_a}i0")],
+                    "tx_index": 3,
+                    "satpoint": format!("{tx_c}:1:0"),
+                    "prior_satpoint": format!("{tx_b}:1:0"),
+                },
+            ])
+        );
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+}
@@ -5,17 +5,75 @@ use chainhook_sdk::{
     observer::ObserverCommand,
     utils::Context,
 };
+use serde_json::json;
 use threadpool::ThreadPool;
 
 use crate::{
     config::Config,
-    scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate,
-    service::observers::{
-        open_readwrite_observers_db_conn_or_panic, update_observer_streaming_enabled,
+    scan::bitcoin::{scan_bitcoin_chainstate_via_rpc_using_predicate_with_control, ScanControl},
+    service::{
+        confirmations::{get_predicate_min_confirmations, start_confirmed_stream},
+        jobs::{run_tracked_job, JobControl, JobKind},
+        liveness::is_predicate_paused,
+        observers::{
+            find_observer_with_uuid, open_readwrite_observers_db_conn_or_panic,
+            update_observer_streaming_enabled,
+        },
+        tenants::TenantScope,
     },
     try_error, try_info,
+    utils::bitcoind::bitcoind_get_block_height,
 };
 
+/// Blocks a predicate scan goes through, given the current chain tip.
+fn get_predicate_scan_units(predicate_spec: &BitcoinChainhookSpecification, chain_tip: u64) -> u64 {
+    if let Some(ref blocks) = predicate_spec.blocks {
+        return blocks.len() as u64;
+    }
+    let start_block = predicate_spec.start_block.unwrap_or(0);
+    let end_block = predicate_spec.end_block.unwrap_or(chain_tip);
+    (end_block + 1).saturating_sub(start_block)
+}
+
+/// Scans the chainstate for a predicate being registered, as a `predicate_scan` job owned by the predicate's tenant.
+fn run_predicate_scan_job(
+    predicate_spec: &BitcoinChainhookSpecification,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let chain_tip = bitcoind_get_block_height(config, ctx);
+    let mut params = json!({ "predicate": predicate_spec.uuid });
+    TenantScope {
+        tenant: predicate_spec.owner_uuid.clone(),
+        admin: false,
+    }
+    .claim_job_params(&mut params);
+    let moved_predicate_spec = predicate_spec.clone();
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    run_tracked_job(
+        JobKind::PredicateScan,
+        params,
+        get_predicate_scan_units(predicate_spec, chain_tip),
+        Box::new(move |control: &JobControl| {
+            let scan_control = ScanControl {
+                record_progress: true,
+                ..control.scan_control()
+            };
+            let op = scan_bitcoin_chainstate_via_rpc_using_predicate_with_control(
+                &moved_predicate_spec,
+                &moved_config,
+                None,
+                Some(&scan_control),
+                &moved_ctx,
+            );
+            hiro_system_kit::nestable_block_on(op)
+        }),
+        config,
+        ctx,
+    )
+}
+
 pub fn start_bitcoin_scan_runloop(
     config: &Config,
     bitcoin_scan_op_rx: crossbeam_channel::Receiver<BitcoinChainhookSpecification>,
@@ -29,16 +87,14 @@ pub fn start_bitcoin_scan_runloop(
         let moved_config = config.clone();
         let observer_command_tx = observer_command_tx.clone();
         bitcoin_scan_pool.execute(move || {
-            let op = scan_bitcoin_chainstate_via_rpc_using_predicate(
-                &predicate_spec,
-                &moved_config,
-                None,
-                &moved_ctx,
-            );
-
-            match hiro_system_kit::nestable_block_on(op) {
+            match run_predicate_scan_job(&predicate_spec, &moved_config, &moved_ctx) {
+                // Paused predicates get scanned again and enabled once resumed.
+                Ok(_) if is_predicate_paused(&predicate_spec.uuid, &moved_config, &moved_ctx) => {
+                    return;
+                }
                 Ok(_) => {}
                 Err(e) => {
+                    // Also reached when the job gets cancelled: the predicate stays disabled.
                     try_error!(
                         moved_ctx,
                         "Unable to evaluate predicate on Bitcoin chainstate: {e}",
@@ -56,6 +112,26 @@ pub fn start_bitcoin_scan_runloop(
                     return;
                 }
             };
+            // Predicates requiring confirmations are not streamed by the Chainhook observer, which delivers blocks as
+            // soon as they are mined. Their stream picks up after the last block the scan went through, the blocks
+            // confirmed since the scan ended included.
+            if get_predicate_min_confirmations(&predicate_spec.uuid).is_some() {
+                let observers_db_conn =
+                    open_readwrite_observers_db_conn_or_panic(&moved_config, &moved_ctx);
+                let last_block_height_update =
+                    find_observer_with_uuid(&predicate_spec.uuid, &observers_db_conn, &moved_ctx)
+                        .map(|(_, report)| report.last_block_height_update)
+                        .unwrap_or(0)
+                        .max(predicate_spec.start_block.unwrap_or(0).saturating_sub(1));
+                start_confirmed_stream(&predicate_spec, last_block_height_update);
+                update_observer_streaming_enabled(
+                    &predicate_spec.uuid,
+                    true,
+                    &observers_db_conn,
+                    &moved_ctx,
+                );
+                return;
+            }
             let _ = observer_command_tx.send(ObserverCommand::EnablePredicate(
                 ChainhookSpecification::Bitcoin(predicate_spec),
             ));
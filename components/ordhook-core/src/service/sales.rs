@@ -0,0 +1,70 @@
+use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config, core::protocol::sale_detection::DetectedSale,
+    db::ordinals::find_inscription_ids_with_ordinal_number, service::alerts::deliver_json_payload,
+};
+
+pub fn sale_detected_event(
+    sale: &DetectedSale,
+    block: &BitcoinBlockData,
+    inscription_ids: Vec<String>,
+) -> JsonValue {
+    json!({
+        "type": "sale_detected",
+        "block_height": block.block_identifier.index,
+        "block_hash": block.block_identifier.hash,
+        "tx_id": sale.tx_id,
+        "ordinal_number": sale.ordinal_number,
+        "inscription_ids": inscription_ids,
+        "price_sats": sale.price_sats,
+        "seller_address": sale.seller_address,
+        "buyer_address": sale.buyer_address,
+        "confidence": sale.confidence,
+    })
+}
+
+/// Delivers a `sale_detected` event for every sale detected in a block reaching the chain tip. Events are delivered
+/// from a dedicated thread so that slow receivers do not hold back indexing.
+pub fn send_sale_detected_events(
+    block: &BitcoinBlockData,
+    sales: &Vec<DetectedSale>,
+    inscriptions_db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) {
+    let Some(ref sales_config) = config.sales_analytics else {
+        return;
+    };
+    if sales.is_empty() || (sales_config.webhook_url.is_none() && sales_config.command.is_none()) {
+        return;
+    }
+    let events = sales
+        .iter()
+        .map(|sale| {
+            let inscription_ids = find_inscription_ids_with_ordinal_number(
+                sale.ordinal_number,
+                inscriptions_db_conn,
+                ctx,
+            );
+            sale_detected_event(sale, block, inscription_ids)
+        })
+        .collect::<Vec<_>>();
+    let moved_sales_config = sales_config.clone();
+    let moved_resources = config.resources.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Sale events").spawn(move || {
+        for event in events.iter() {
+            deliver_json_payload(
+                event,
+                &moved_sales_config.webhook_url,
+                &moved_sales_config.command,
+                "ORDHOOK_EVENT",
+                &moved_resources,
+                &moved_ctx,
+            );
+        }
+    });
+}
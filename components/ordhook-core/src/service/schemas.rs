@@ -0,0 +1,584 @@
+use serde_json::{json, Value as JsonValue};
+
+use crate::service::predicate_versions::SUPPORTED_PAYLOAD_SCHEMA_VERSIONS;
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Payloads delivered by ordhook with payload version 2, named after the schemas describing them. Schemas of a version
+/// are frozen once released: changes of the payloads get described by a new version. Version 2 added `rollback`
+/// events, the enrichments of occurrences, and changed the destinations of outputs without address and the locations
+/// of unbound inscriptions: version 1 payloads are no longer delivered.
+pub const PAYLOAD_SCHEMA_NAMES_V2: [&str; 5] = [
+    "predicate_occurrence",
+    "alert",
+    "amendment",
+    "rollback",
+    "sale_detected",
+];
+
+fn get_payload_schema_names(version: u64) -> &'static [&'static str] {
+    match version {
+        2 => &PAYLOAD_SCHEMA_NAMES_V2,
+        _ => &[],
+    }
+}
+
+fn schema_id(version: u64, name: &str) -> String {
+    format!("/ordhook/v1/schemas/{version}/{name}")
+}
+
+fn block_identifier_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["index", "hash"],
+        "properties": {
+            "index": { "type": "integer", "minimum": 0 },
+            "hash": { "type": "string" },
+        },
+    })
+}
+
+fn inscription_revealed_schema() -> JsonValue {
+    let mut schema = json!({
+        "type": "object",
+        "required": [
+            "content_type", "content_bytes", "content_length", "inscription_id", "inscription_number",
+            "inscription_input_index", "inscription_output_value", "inscription_fee", "ordinal_number",
+            "ordinal_block_height", "ordinal_offset", "satpoint_post_inscription", "tx_index",
+        ],
+        "properties": {
+            "content_type": { "type": "string" },
+            "content_bytes": { "type": "string", "description": "Hex encoded content, prefixed with 0x, or sha256:<hex digest> when the content is not stored" },
+            "content_length": { "type": "integer", "minimum": 0 },
+            "inscription_id": { "type": "string" },
+            "inscription_number": {
+                "type": "object",
+                "required": ["jubilee", "classic"],
+                "properties": {
+                    "jubilee": { "type": "integer" },
+                    "classic": { "type": "integer" },
+                },
+            },
+            "inscriber_address": { "type": ["string", "null"] },
+            "inscription_input_index": { "type": "integer", "minimum": 0 },
+            "inscription_output_value": { "type": "integer", "minimum": 0 },
+            "inscription_pointer": { "type": ["integer", "null"], "minimum": 0 },
+            "inscription_fee": { "type": "integer", "minimum": 0 },
+            "parent": { "type": ["string", "null"] },
+            "delegate": { "type": ["string", "null"] },
+            "metaprotocol": { "type": ["string", "null"] },
+            "metadata": {},
+            "ordinal_number": { "type": "integer", "minimum": 0 },
+            "ordinal_block_height": { "type": "integer", "minimum": 0 },
+            "ordinal_offset": { "type": "integer", "minimum": 0 },
+            "transfers_pre_inscription": { "type": "integer", "minimum": 0 },
+            "satpoint_post_inscription": { "type": "string" },
+            "curse_type": {},
+            "tx_index": { "type": "integer", "minimum": 0 },
+        },
+    });
+    let properties = &mut schema["properties"];
+    properties["content_json"] = json!({
+        "description": "Parsed content of small JSON and text inscriptions, for predicates listing `content_json` in `enrich`",
+    });
+    properties["content_stored"] = json!({
+        "type": "boolean",
+        "description": "Set to false on inscriptions which content is not stored and can not be parsed, for predicates listing `content_json` in `enrich`",
+    });
+    properties["detected_content_type"] = json!({
+        "type": "string",
+        "description": "Content type detected from the content, for predicates listing `content_type` in `enrich`",
+    });
+    properties["content_type_mismatch"] = json!({
+        "type": "boolean",
+        "description": "Whether `content_type` does not describe the content, for predicates listing `content_type` in `enrich`",
+    });
+    properties["satpoint_post_inscription"] = json!({
+        "type": "string",
+        "description": "Unbound inscriptions, revealed on a zero-value input or with an unrecognized even field, are located at 0000000000000000000000000000000000000000000000000000000000000000:0:<unbound sequence>",
+    });
+    schema
+}
+
+fn inscription_transferred_schema() -> JsonValue {
+    let mut schema = json!({
+        "type": "object",
+        "required": [
+            "ordinal_number", "destination", "satpoint_pre_transfer", "satpoint_post_transfer", "tx_index",
+        ],
+        "properties": {
+            "ordinal_number": { "type": "integer", "minimum": 0 },
+            "destination": {
+                "type": "object",
+                "required": ["type"],
+                "properties": {
+                    "type": { "enum": ["transferred", "spent_in_fees", "burnt"] },
+                    "value": { "type": "string" },
+                },
+            },
+            "satpoint_pre_transfer": { "type": "string" },
+            "satpoint_post_transfer": { "type": "string" },
+            "post_transfer_output_value": { "type": ["integer", "null"], "minimum": 0 },
+            "tx_index": { "type": "integer", "minimum": 0 },
+        },
+    });
+    let properties = &mut schema["properties"];
+    properties["destination"]["properties"]["value"] = json!({
+        "type": "string",
+        "description": "Address of the recipient, or `<script type>:<script hex>` for outputs without address, like the OP_RETURN outputs of burnt sats. Also set to the miner address of transfers spent in fees, for predicates listing `miner_address` in `enrich`",
+    });
+    properties["enrichment"] = json!({
+        "type": "object",
+        "description": "Only delivered to predicates listing enrichments in `enrich`",
+        "properties": {
+            "inscriptions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["inscription_id"],
+                    "properties": {
+                        "inscription_id": { "type": "string" },
+                        "inscription_number": {
+                            "type": "object",
+                            "properties": {
+                                "jubilee": { "type": "integer" },
+                                "classic": { "type": "integer" },
+                            },
+                        },
+                        "genesis_block_height": { "type": "integer", "minimum": 0 },
+                        "content_type": { "type": ["string", "null"] },
+                        "metaprotocol": { "type": ["string", "null"] },
+                        "collection": { "type": ["string", "null"] },
+                    },
+                },
+            },
+            "current_owner": {
+                "type": ["object", "null"],
+                "properties": {
+                    "satpoint": { "type": "string" },
+                    "address": { "type": ["string", "null"] },
+                },
+            },
+        },
+    });
+    schema
+}
+
+/// Occurrences are built by chainhook-sdk: blocks are listed with the ordinals operations of their transactions.
+/// Unknown properties are allowed, so that consumers keep validating payloads when fields get added.
+fn rune_operation_schema() -> JsonValue {
+    let rune = json!({
+        "type": "object",
+        "required": ["rune_id", "spaced_rune", "amount"],
+        "properties": {
+            "rune_id": { "type": "string" },
+            "spaced_rune": { "type": ["string", "null"] },
+            "amount": { "type": "string", "description": "Amount in the smallest unit of the rune, as a decimal string" },
+        },
+    });
+    let mut transferred = rune.clone();
+    transferred["properties"]["output"] = json!({ "type": "integer", "minimum": 0 });
+    transferred["properties"]["address"] = json!({ "type": ["string", "null"] });
+    let mut burned = rune.clone();
+    burned["required"] = json!(["rune_id", "spaced_rune", "amount", "reason"]);
+    burned["properties"]["reason"] = json!({
+        "enum": ["op_return", "no_output", "cenotaph"],
+        "description": "Runes allocated to an OP_RETURN output, left without an output able to receive them, or spent by a cenotaph",
+    });
+    json!({
+        "oneOf": [
+            {
+                "type": "object",
+                "required": ["cenotaph"],
+                "properties": {
+                    "cenotaph": {
+                        "type": "object",
+                        "required": ["flaw"],
+                        "properties": { "flaw": { "type": "string" } },
+                    },
+                },
+            },
+            { "type": "object", "required": ["rune_etched"], "properties": { "rune_etched": rune } },
+            { "type": "object", "required": ["rune_minted"], "properties": { "rune_minted": rune } },
+            { "type": "object", "required": ["rune_transferred"], "properties": { "rune_transferred": transferred } },
+            { "type": "object", "required": ["rune_burned"], "properties": { "rune_burned": burned } },
+        ],
+    })
+}
+
+fn predicate_occurrence_schema() -> JsonValue {
+    let mut schema = json!({
+        "type": "object",
+        "required": ["apply", "rollback", "chainhook"],
+        "properties": {
+            "apply": { "type": "array", "items": { "$ref": "#/$defs/block" } },
+            "rollback": { "type": "array", "items": { "$ref": "#/$defs/block" } },
+            "chainhook": {
+                "type": "object",
+                "required": ["uuid", "predicate", "is_streaming_blocks"],
+                "properties": {
+                    "uuid": { "type": "string" },
+                    "predicate": { "type": "object" },
+                    "is_streaming_blocks": { "type": "boolean" },
+                },
+            },
+        },
+        "$defs": {
+            "block_identifier": block_identifier_schema(),
+            "block": {
+                "type": "object",
+                "required": ["block_identifier", "parent_block_identifier", "timestamp", "transactions"],
+                "properties": {
+                    "block_identifier": { "$ref": "#/$defs/block_identifier" },
+                    "parent_block_identifier": { "$ref": "#/$defs/block_identifier" },
+                    "timestamp": { "type": "integer", "minimum": 0 },
+                    "transactions": { "type": "array", "items": { "$ref": "#/$defs/transaction" } },
+                    "metadata": { "type": "object" },
+                },
+            },
+            "transaction": {
+                "type": "object",
+                "required": ["transaction_identifier", "metadata"],
+                "properties": {
+                    "transaction_identifier": {
+                        "type": "object",
+                        "required": ["hash"],
+                        "properties": { "hash": { "type": "string" } },
+                    },
+                    "operations": { "type": "array" },
+                    "metadata": {
+                        "type": "object",
+                        "properties": {
+                            "ordinal_operations": {
+                                "type": "array",
+                                "items": { "$ref": "#/$defs/ordinal_operation" },
+                            },
+                            "brc20_operation": { "type": ["object", "null"] },
+                            "fee": { "type": "integer", "minimum": 0 },
+                            "index": { "type": "integer", "minimum": 0 },
+                            "inputs": { "type": "array" },
+                            "outputs": { "type": "array" },
+                        },
+                    },
+                },
+            },
+            "ordinal_operation": {
+                "oneOf": [
+                    {
+                        "type": "object",
+                        "required": ["inscription_revealed"],
+                        "properties": { "inscription_revealed": { "$ref": "#/$defs/inscription_revealed" } },
+                    },
+                    {
+                        "type": "object",
+                        "required": ["inscription_transferred"],
+                        "properties": { "inscription_transferred": { "$ref": "#/$defs/inscription_transferred" } },
+                    },
+                ],
+            },
+            "inscription_revealed": inscription_revealed_schema(),
+            "inscription_transferred": inscription_transferred_schema(),
+        },
+    });
+    schema["$defs"]["transaction"]["properties"]["metadata"]["properties"]["rune_operations"] = json!({
+        "type": "array",
+        "items": { "$ref": "#/$defs/rune_operation" },
+        "description": "Runes operations of the transaction, for predicates listing `runes` in `enrich`",
+    });
+    schema["$defs"]["rune_operation"] = rune_operation_schema();
+    schema["$defs"]["transaction"]["properties"]["metadata"]["properties"]["brc20_validation"] = json!({
+        "type": "object",
+        "required": ["valid", "rejected_operations"],
+        "description": "Validation of the BRC-20 operations of the transaction, for predicates with the `brc20` scope",
+        "properties": {
+            "valid": { "type": "boolean", "description": "Whether `brc20_operation` holds a verified operation" },
+            "rejected_operations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["inscription_id", "operation", "tick", "address", "reason"],
+                    "properties": {
+                        "inscription_id": { "type": "string" },
+                        "operation": { "enum": ["deploy", "mint", "transfer"] },
+                        "tick": { "type": "string" },
+                        "address": { "type": "string" },
+                        "reason": { "type": "string" },
+                    },
+                },
+            },
+        },
+    });
+    schema
+}
+
+fn alert_schema() -> JsonValue {
+    json!({
+        "oneOf": [
+            {
+                "type": "object",
+                "required": ["type", "bitcoind_height", "indexed_height", "lag"],
+                "properties": {
+                    "type": { "const": "tip_lag" },
+                    "bitcoind_height": { "type": "integer", "minimum": 0 },
+                    "indexed_height": { "type": "integer", "minimum": 0 },
+                    "lag": { "type": "integer", "minimum": 0 },
+                },
+            },
+            {
+                "type": "object",
+                "required": ["type", "consecutive_failures", "last_error"],
+                "properties": {
+                    "type": { "const": "rpc_failures" },
+                    "consecutive_failures": { "type": "integer", "minimum": 0 },
+                    "last_error": { "type": "string" },
+                },
+            },
+            {
+                "type": "object",
+                "required": ["type", "depth", "tip_height"],
+                "properties": {
+                    "type": { "const": "deep_reorg" },
+                    "depth": { "type": "integer", "minimum": 0 },
+                    "tip_height": { "type": "integer", "minimum": 0 },
+                },
+            },
+            {
+                "type": "object",
+                "required": ["type", "block_height", "attempts", "error"],
+                "properties": {
+                    "type": { "const": "block_quarantined" },
+                    "block_height": { "type": "integer", "minimum": 0 },
+                    "attempts": { "type": "integer", "minimum": 0 },
+                    "error": { "type": "string" },
+                },
+            },
+        ],
+    })
+}
+
+fn amendment_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": [
+            "type", "reason", "block_height", "block_hash", "inscription_id", "old_value", "new_value",
+        ],
+        "properties": {
+            "type": { "const": "amendment" },
+            "reason": { "enum": ["repair", "renumbering", "deep_reorg"] },
+            "block_height": { "type": "integer", "minimum": 0 },
+            "block_hash": { "type": ["string", "null"] },
+            "inscription_id": { "type": ["string", "null"] },
+            "old_value": {},
+            "new_value": {},
+        },
+    })
+}
+
+fn rollback_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["type", "block_height", "block_hash", "unrevealed_inscriptions", "reverted_transfers"],
+        "properties": {
+            "type": { "const": "rollback" },
+            "block_height": { "type": "integer", "minimum": 0 },
+            "block_hash": { "type": "string" },
+            "unrevealed_inscriptions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["inscription_id", "ordinal_number", "inscription_number"],
+                    "properties": {
+                        "inscription_id": { "type": "string" },
+                        "ordinal_number": {
+                            "type": ["integer", "null"],
+                            "minimum": 0,
+                            "description": "Null for unbound inscriptions, which are not inscribed on any sat",
+                        },
+                        "inscription_number": {
+                            "type": "object",
+                            "required": ["jubilee", "classic"],
+                            "properties": {
+                                "jubilee": { "type": "integer" },
+                                "classic": { "type": "integer" },
+                            },
+                        },
+                    },
+                },
+            },
+            "reverted_transfers": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["ordinal_number", "inscription_ids", "tx_index", "satpoint", "prior_satpoint"],
+                    "properties": {
+                        "ordinal_number": { "type": "integer", "minimum": 0 },
+                        "inscription_ids": { "type": "array", "items": { "type": "string" } },
+                        "tx_index": { "type": "integer", "minimum": 0 },
+                        "satpoint": { "type": "string" },
+                        "prior_satpoint": { "type": ["string", "null"] },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn sale_detected_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": [
+            "type", "block_height", "block_hash", "tx_id", "ordinal_number", "inscription_ids", "price_sats",
+            "seller_address", "buyer_address", "confidence",
+        ],
+        "properties": {
+            "type": { "const": "sale_detected" },
+            "block_height": { "type": "integer", "minimum": 0 },
+            "block_hash": { "type": "string" },
+            "tx_id": { "type": "string" },
+            "ordinal_number": { "type": "integer", "minimum": 0 },
+            "inscription_ids": { "type": "array", "items": { "type": "string" } },
+            "price_sats": { "type": "integer", "minimum": 0 },
+            "seller_address": { "type": ["string", "null"] },
+            "buyer_address": { "type": "string" },
+            "confidence": { "enum": ["high", "low"] },
+        },
+    })
+}
+
+/// JSON Schema of the payload `name`, as delivered with payload version `version`.
+pub fn get_payload_schema(version: u64, name: &str) -> Option<JsonValue> {
+    if !SUPPORTED_PAYLOAD_SCHEMA_VERSIONS.contains(&version)
+        || !get_payload_schema_names(version).contains(&name)
+    {
+        return None;
+    }
+    let mut schema = match name {
+        "predicate_occurrence" => predicate_occurrence_schema(),
+        "alert" => alert_schema(),
+        "amendment" => amendment_schema(),
+        "rollback" => rollback_schema(),
+        "sale_detected" => sale_detected_schema(),
+        _ => return None,
+    };
+    let schema_object = schema.as_object_mut()?;
+    schema_object.insert("$schema".into(), json!(JSON_SCHEMA_DIALECT));
+    schema_object.insert("$id".into(), json!(schema_id(version, name)));
+    schema_object.insert("title".into(), json!(name));
+    Some(schema)
+}
+
+/// Schemas served for every supported payload version.
+pub fn get_payload_schemas_index() -> JsonValue {
+    let versions = SUPPORTED_PAYLOAD_SCHEMA_VERSIONS
+        .iter()
+        .map(|version| {
+            json!({
+                "version": version,
+                "schemas": get_payload_schema_names(*version)
+                    .iter()
+                    .map(|name| json!({ "name": name, "$id": schema_id(*version, name) }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "versions": versions })
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::types::BlockIdentifier;
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::service::{
+        alerts::Alert,
+        amendments::{Amendment, AmendmentReason},
+        predicate_versions::SUPPORTED_PAYLOAD_SCHEMA_VERSIONS,
+        rollbacks::BlockRollback,
+    };
+
+    use super::{get_payload_schema, get_payload_schema_names};
+
+    /// Properties of `payload` must all be declared by `schema`, and every required property must be present.
+    fn assert_matches_properties(payload: &JsonValue, schema: &JsonValue) {
+        let properties = schema["properties"].as_object().unwrap();
+        let payload = payload.as_object().unwrap();
+        for key in payload.keys() {
+            assert!(properties.contains_key(key), "{key} is not declared");
+        }
+        for required in schema["required"].as_array().unwrap() {
+            assert!(payload.contains_key(required.as_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn describes_delivered_payloads() {
+        for version in SUPPORTED_PAYLOAD_SCHEMA_VERSIONS {
+            for name in get_payload_schema_names(version) {
+                let schema = get_payload_schema(version, name).unwrap();
+                assert_eq!(
+                    schema["$id"],
+                    json!(format!("/ordhook/v1/schemas/{version}/{name}"))
+                );
+            }
+        }
+        assert!(get_payload_schema(0, "alert").is_none());
+        assert!(get_payload_schema(1, "predicate_occurrence").is_none());
+        assert!(get_payload_schema(2, "inscription_feed").is_none());
+
+        let transferred_v2 = &get_payload_schema(2, "predicate_occurrence").unwrap()["$defs"]
+            ["inscription_transferred"];
+        assert!(transferred_v2["properties"].get("enrichment").is_some());
+
+        let amendment = Amendment {
+            reason: AmendmentReason::DeepReorg,
+            block_height: 800_000,
+            block_hash: Some("00".into()),
+            inscription_id: None,
+            old_value: json!(null),
+            new_value: json!(null),
+        };
+        assert_matches_properties(
+            &amendment.to_json(),
+            &get_payload_schema(2, "amendment").unwrap(),
+        );
+
+        let rollback = BlockRollback {
+            block_identifier: BlockIdentifier {
+                index: 800_000,
+                hash: "0x00".into(),
+            },
+            unrevealed_inscriptions: vec![],
+            reverted_transfers: vec![],
+        };
+        assert_matches_properties(
+            &rollback.to_json(),
+            &get_payload_schema(2, "rollback").unwrap(),
+        );
+
+        let alert_schema = get_payload_schema(2, "alert").unwrap();
+        let alerts = [
+            Alert::TipLag {
+                bitcoind_height: 10,
+                indexed_height: 8,
+            },
+            Alert::RpcFailures {
+                consecutive_failures: 3,
+                last_error: "timeout".into(),
+            },
+            Alert::DeepReorg {
+                depth: 7,
+                tip_height: 800_000,
+            },
+            Alert::BlockQuarantined {
+                block_height: 800_000,
+                attempts: 3,
+                error: "parse".into(),
+            },
+        ];
+        for (alert, schema) in alerts.iter().zip(alert_schema["oneOf"].as_array().unwrap()) {
+            let payload = alert.to_json();
+            assert_eq!(payload["type"], schema["properties"]["type"]["const"]);
+            assert_matches_properties(&payload, schema);
+        }
+    }
+}
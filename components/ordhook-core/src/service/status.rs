@@ -0,0 +1,326 @@
+use std::time::Duration;
+
+use chainhook_sdk::{
+    bitcoincore_rpc::RpcApi, chainhooks::types::ChainhookSpecification, utils::Context,
+};
+
+use crate::{
+    config::Config,
+    service::{
+        jobs::{get_jobs, JobKind, JobStatus},
+        liveness::{PredicateHealthRegistry, PredicateHealthStatus},
+        observers::{
+            find_all_observers, find_predicate_paused_at, open_readonly_observers_db_conn,
+        },
+        usage::get_api_calls_since_startup,
+    },
+    utils::{
+        bitcoind::bitcoind_rpc_client_with_timeout,
+        monitoring::{PrometheusMonitoring, PIPELINE_METRICS},
+    },
+};
+
+/// Finished jobs listed next to the queued and running ones, most recent first.
+pub const STATUS_RECENT_JOBS: usize = 10;
+
+/// The status is requested interactively: the chain tip is reported unavailable rather than waiting on a busy bitcoind.
+const STATUS_BITCOIND_RPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Overview of a running node, served by `GET /ordhook/v1/control/status` and printed by `ordhook service status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub network: String,
+    pub sync: SyncStatus,
+    pub predicates: Vec<PredicateSummary>,
+    pub jobs: Vec<JobSummary>,
+    pub resources: ResourceStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub block_height: u64,
+    pub inscription_number: u64,
+    /// Not set when bitcoind can't be reached.
+    pub chain_tip: Option<u64>,
+    pub lag: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PredicateSummary {
+    pub uuid: String,
+    pub enabled: bool,
+    pub streaming: bool,
+    pub last_block_height_update: u64,
+    pub health: PredicateHealthStatus,
+    pub paused_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub progress_percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceStats {
+    pub downloaded_blocks_queue: u64,
+    pub parsed_blocks_queue: u64,
+    pub pending_db_writes: u64,
+    pub pending_deliveries: u64,
+    pub quarantined_blocks: u64,
+    pub query_cache_hits: u64,
+    pub query_cache_misses: u64,
+    pub api_calls_since_startup: u64,
+    /// Only available on Linux.
+    pub resident_memory_bytes: Option<u64>,
+    pub cpu_core_available: usize,
+    /// In GB, as configured.
+    pub memory_available: usize,
+}
+
+pub fn get_service_status(
+    config: &Config,
+    prometheus: &PrometheusMonitoring,
+    predicate_health: &PredicateHealthRegistry,
+    ctx: &Context,
+) -> Result<ServiceStatus, String> {
+    let block_height = prometheus.last_indexed_block_height.get();
+    let chain_tip = bitcoind_rpc_client_with_timeout(config, STATUS_BITCOIND_RPC_TIMEOUT)
+        .ok()
+        .and_then(|client| client.get_blockchain_info().ok())
+        .map(|info| info.blocks);
+    let sync = SyncStatus {
+        block_height,
+        inscription_number: prometheus.last_indexed_inscription_number.get(),
+        chain_tip,
+        lag: chain_tip.map(|chain_tip| chain_tip.saturating_sub(block_height)),
+    };
+
+    let observers_db_conn = open_readonly_observers_db_conn(config, ctx)?;
+    let predicates = find_all_observers(&observers_db_conn, ctx)
+        .into_iter()
+        .filter_map(|(predicate, report)| match predicate {
+            ChainhookSpecification::Bitcoin(spec) => Some(PredicateSummary {
+                health: predicate_health.get(&spec.uuid).status,
+                paused_at: find_predicate_paused_at(&spec.uuid, &observers_db_conn, ctx),
+                uuid: spec.uuid,
+                enabled: spec.enabled,
+                streaming: report.streaming_enabled,
+                last_block_height_update: report.last_block_height_update,
+            }),
+            ChainhookSpecification::Stacks(_) => None,
+        })
+        .collect();
+
+    let mut finished_jobs = 0;
+    let jobs = get_jobs(None, None, config, ctx)?
+        .into_iter()
+        .filter(|job| {
+            if !job.status.is_finished() {
+                return true;
+            }
+            finished_jobs += 1;
+            finished_jobs <= STATUS_RECENT_JOBS
+        })
+        .map(|job| JobSummary {
+            id: job.id,
+            kind: job.kind,
+            status: job.status,
+            progress_percent: job.progress_percent,
+        })
+        .collect();
+
+    let resources = ResourceStats {
+        downloaded_blocks_queue: PIPELINE_METRICS.downloaded_blocks_queue.get(),
+        parsed_blocks_queue: PIPELINE_METRICS.parsed_blocks_queue.get(),
+        pending_db_writes: PIPELINE_METRICS.pending_db_writes.get(),
+        pending_deliveries: PIPELINE_METRICS.pending_deliveries.get(),
+        quarantined_blocks: prometheus.quarantined_blocks.get(),
+        query_cache_hits: prometheus.query_cache_hits.get(),
+        query_cache_misses: prometheus.query_cache_misses.get(),
+        api_calls_since_startup: get_api_calls_since_startup(),
+        resident_memory_bytes: get_resident_memory_bytes(),
+        cpu_core_available: config.resources.cpu_core_available,
+        memory_available: config.resources.memory_available,
+    };
+
+    Ok(ServiceStatus {
+        network: config.network.network_name().to_string(),
+        sync,
+        predicates,
+        jobs,
+        resources,
+    })
+}
+
+fn get_resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Renders a status for operators, one section per line group.
+pub fn format_service_status(status: &ServiceStatus) -> String {
+    let mut lines = vec![];
+    let sync = &status.sync;
+    lines.push(format!("Network:      {}", status.network));
+    lines.push(format!(
+        "Indexed:      block #{} (inscription #{})",
+        sync.block_height, sync.inscription_number
+    ));
+    lines.push(match (sync.chain_tip, sync.lag) {
+        (Some(chain_tip), Some(lag)) => {
+            format!("Chain tip:    block #{chain_tip} ({lag} blocks behind)")
+        }
+        _ => "Chain tip:    unavailable (bitcoind unreachable)".to_string(),
+    });
+
+    lines.push(String::new());
+    lines.push(format!("Predicates:   {}", status.predicates.len()));
+    for predicate in status.predicates.iter() {
+        let state = match (predicate.enabled, predicate.paused_at) {
+            (false, _) => "disabled",
+            (true, Some(_)) => "paused",
+            (true, None) if predicate.streaming => "streaming",
+            (true, None) => "scanning",
+        };
+        lines.push(format!(
+            "  {}  {:<9}  health: {:<8}  last block: #{}",
+            predicate.uuid,
+            state,
+            match predicate.health {
+                PredicateHealthStatus::Healthy => "healthy",
+                PredicateHealthStatus::Degraded => "degraded",
+                PredicateHealthStatus::Paused => "paused",
+            },
+            predicate.last_block_height_update
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push(format!("Jobs:         {}", status.jobs.len()));
+    for job in status.jobs.iter() {
+        lines.push(format!(
+            "  {}  {:<6}  {:<9}  {:.1}%",
+            job.id,
+            job.kind.as_str(),
+            job.status.as_str(),
+            job.progress_percent
+        ));
+    }
+
+    let resources = &status.resources;
+    lines.push(String::new());
+    lines.push("Resources:".to_string());
+    lines.push(format!(
+        "  Pipeline queues:  {} downloaded, {} parsed, {} pending writes, {} pending deliveries",
+        resources.downloaded_blocks_queue,
+        resources.parsed_blocks_queue,
+        resources.pending_db_writes,
+        resources.pending_deliveries
+    ));
+    lines.push(format!(
+        "  Quarantined:      {} blocks",
+        resources.quarantined_blocks
+    ));
+    lines.push(format!(
+        "  Query cache:      {} hits, {} misses",
+        resources.query_cache_hits, resources.query_cache_misses
+    ));
+    lines.push(format!(
+        "  API calls:        {} since startup",
+        resources.api_calls_since_startup
+    ));
+    if let Some(resident_memory_bytes) = resources.resident_memory_bytes {
+        lines.push(format!(
+            "  Memory:           {} MiB resident",
+            resident_memory_bytes / (1024 * 1024)
+        ));
+    }
+    lines.push(format!(
+        "  Configured:       {} cores, {} GB",
+        resources.cpu_core_available, resources.memory_available
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::{
+        jobs::{JobKind, JobStatus},
+        liveness::PredicateHealthStatus,
+    };
+
+    use super::{
+        format_service_status, JobSummary, PredicateSummary, ResourceStats, ServiceStatus,
+        SyncStatus,
+    };
+
+    #[test]
+    fn formats_service_status() {
+        let mut status = ServiceStatus {
+            network: "mainnet".to_string(),
+            sync: SyncStatus {
+                block_height: 840_000,
+                inscription_number: 70_000_000,
+                chain_tip: Some(840_003),
+                lag: Some(3),
+            },
+            predicates: vec![PredicateSummary {
+                uuid: "1".to_string(),
+                enabled: true,
+                streaming: true,
+                last_block_height_update: 840_000,
+                health: PredicateHealthStatus::Degraded,
+                paused_at: None,
+            }],
+            jobs: vec![JobSummary {
+                id: "job-1".to_string(),
+                kind: JobKind::Rescan,
+                status: JobStatus::Running,
+                progress_percent: 42.0,
+            }],
+            resources: ResourceStats {
+                downloaded_blocks_queue: 1,
+                parsed_blocks_queue: 2,
+                pending_db_writes: 3,
+                pending_deliveries: 4,
+                quarantined_blocks: 0,
+                query_cache_hits: 10,
+                query_cache_misses: 5,
+                api_calls_since_startup: 100,
+                resident_memory_bytes: Some(512 * 1024 * 1024),
+                cpu_core_available: 8,
+                memory_available: 16,
+            },
+        };
+        let output = format_service_status(&status);
+        assert!(output.contains("Indexed:      block #840000 (inscription #70000000)"));
+        assert!(output.contains("Chain tip:    block #840003 (3 blocks behind)"));
+        assert!(output.contains("  1  streaming  health: degraded  last block: #840000"));
+        assert!(output.contains("  job-1  rescan  running    42.0%"));
+        assert!(output.contains("  Memory:           512 MiB resident"));
+
+        status.sync.chain_tip = None;
+        status.sync.lag = None;
+        status.predicates[0].paused_at = Some(1_700_000_000);
+        let output = format_service_status(&status);
+        assert!(output.contains("Chain tip:    unavailable (bitcoind unreachable)"));
+        assert!(output.contains("  1  paused "));
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(
+            serde_json::from_value::<ServiceStatus>(json).unwrap(),
+            status
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use chainhook_sdk::chainhooks::types::ChainhookSpecification;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::{Config, PredicatesApi, TenantConfig},
+    service::jobs::Job,
+};
+
+/// Job parameter recording the tenant which submitted a job.
+const JOB_TENANT_PARAM: &str = "tenant";
+
+/// Predicates and jobs a caller of the API has access to, resolved from its API key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TenantScope {
+    /// Tenant owning the predicates and jobs created by the caller, `None` when no tenant is configured.
+    pub tenant: Option<String>,
+    /// Access to the predicates and jobs of every tenant.
+    pub admin: bool,
+}
+
+/// Compares secrets in constant time, so that response times don't leak how much of a secret was guessed.
+pub(crate) fn secrets_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+impl TenantScope {
+    pub fn unrestricted() -> TenantScope {
+        TenantScope {
+            tenant: None,
+            admin: true,
+        }
+    }
+
+    /// Resolves the scope of a request. Without tenants configured, the API stays open to every caller.
+    pub fn resolve(config: &Config, api_key: Option<&str>) -> Result<TenantScope, String> {
+        let tenants: &[TenantConfig] = match config.http_api {
+            PredicatesApi::On(ref api_config) => &api_config.tenants,
+            PredicatesApi::Off => &[],
+        };
+        if tenants.is_empty() {
+            return Ok(TenantScope::unrestricted());
+        }
+        let api_key = api_key.ok_or("API key required".to_string())?;
+        tenants
+            .iter()
+            .find(|tenant| {
+                tenant
+                    .api_keys
+                    .iter()
+                    .any(|key| secrets_match(key, api_key))
+            })
+            .map(|tenant| TenantScope {
+                tenant: Some(tenant.name.clone()),
+                admin: tenant.admin,
+            })
+            .ok_or("invalid API key".to_string())
+    }
+
+    fn can_access(&self, owner: Option<&str>) -> bool {
+        self.admin || (self.tenant.is_some() && owner == self.tenant.as_deref())
+    }
+
+    pub fn can_access_predicate(&self, spec: &ChainhookSpecification) -> bool {
+        match spec {
+            ChainhookSpecification::Bitcoin(spec) => self.can_access(spec.owner_uuid.as_deref()),
+            ChainhookSpecification::Stacks(_) => self.admin,
+        }
+    }
+
+    pub fn can_access_job(&self, job: &Job) -> bool {
+        self.can_access(job.params.get(JOB_TENANT_PARAM).and_then(|t| t.as_str()))
+    }
+
+    /// Records the caller's tenant as the owner of a predicate submitted to the API. Admins can register predicates on
+    /// behalf of another tenant with `owner_uuid`.
+    pub fn claim_predicate(&self, predicate: &mut JsonValue) {
+        let (Some(tenant), Some(predicate)) = (&self.tenant, predicate.as_object_mut()) else {
+            return;
+        };
+        if self.admin && predicate.get("owner_uuid").is_some() {
+            return;
+        }
+        predicate.insert("owner_uuid".to_string(), json!(tenant));
+    }
+
+    /// Records the caller's tenant in the parameters of a job it submits.
+    pub fn claim_job_params(&self, params: &mut JsonValue) {
+        if let (Some(tenant), Some(params)) = (&self.tenant, params.as_object_mut()) {
+            params.insert(JOB_TENANT_PARAM.to_string(), json!(tenant));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::config::{
+        Config, PredicatesApi, PredicatesApiConfig, TenantConfig, DEFAULT_LISTENER_ADDRESS,
+        DEFAULT_QUERY_TIMEOUT_MS,
+    };
+
+    use super::TenantScope;
+
+    fn config_with_tenants() -> Config {
+        let mut config = Config::devnet_default();
+        config.http_api = PredicatesApi::On(PredicatesApiConfig {
+            http_address: DEFAULT_LISTENER_ADDRESS,
+            http_port: 20456,
+            unix_socket: None,
+            display_logs: true,
+            upstream_api_url: None,
+            blocklist_path: None,
+            query_timeout_ms: DEFAULT_QUERY_TIMEOUT_MS,
+            tenants: vec![
+                TenantConfig {
+                    name: "indexers".to_string(),
+                    api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+                    admin: false,
+                    max_api_calls_per_day: None,
+                    max_events_per_day: None,
+                },
+                TenantConfig {
+                    name: "ops".to_string(),
+                    api_keys: vec!["key-ops".to_string()],
+                    admin: true,
+                    max_api_calls_per_day: None,
+                    max_events_per_day: None,
+                },
+            ],
+        });
+        config
+    }
+
+    #[test]
+    fn resolves_tenants_from_api_keys() {
+        let config = config_with_tenants();
+        let scope = TenantScope::resolve(&config, Some("key-2")).unwrap();
+        assert_eq!(scope.tenant.as_deref(), Some("indexers"));
+        assert!(!scope.admin);
+        assert!(TenantScope::resolve(&config, Some("key-3")).is_err());
+        assert!(TenantScope::resolve(&config, None).is_err());
+        assert_eq!(
+            TenantScope::resolve(&Config::devnet_default(), None),
+            Ok(TenantScope::unrestricted())
+        );
+    }
+
+    #[test]
+    fn scopes_access_to_owned_resources() {
+        let config = config_with_tenants();
+        let indexers = TenantScope::resolve(&config, Some("key-1")).unwrap();
+        let ops = TenantScope::resolve(&config, Some("key-ops")).unwrap();
+        let mut predicate = json!({ "uuid": "1" });
+        indexers.claim_predicate(&mut predicate);
+        assert_eq!(predicate["owner_uuid"], "indexers");
+        assert!(indexers.can_access(Some("indexers")));
+        assert!(!indexers.can_access(Some("ops")));
+        assert!(!indexers.can_access(None));
+        assert!(ops.can_access(Some("indexers")));
+        assert!(ops.can_access(None));
+    }
+}
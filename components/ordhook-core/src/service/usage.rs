@@ -0,0 +1,352 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::{Config, PredicatesApi},
+    service::observers::{
+        add_usage_in_observers, find_usage_in_observers, find_usage_of_day_in_observers,
+        open_readonly_observers_db_conn, open_readwrite_observers_db_conn,
+    },
+    try_info, try_warn,
+};
+
+/// Interval between two flushes of the usage recorded to observers.sqlite.
+const USAGE_FLUSH_INTERVAL_SECS: u64 = 60;
+/// Tenant the usage is accounted to when no tenant is configured, or the caller sent no valid API key.
+pub const DEFAULT_TENANT: &str = "default";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct UsageCounters {
+    pub api_calls: u64,
+    /// Bytes of the API responses.
+    pub bytes_served: u64,
+    /// Transactions delivered to the predicates of the tenant.
+    pub events_delivered: u64,
+}
+
+impl UsageCounters {
+    fn add(&mut self, other: &UsageCounters) {
+        self.api_calls += other.api_calls;
+        self.bytes_served += other.bytes_served;
+        self.events_delivered += other.events_delivered;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageReportEntry {
+    pub tenant: String,
+    /// `YYYY-MM-DD`, in UTC.
+    pub day: String,
+    #[serde(flatten)]
+    pub usage: UsageCounters,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageQuota {
+    pub max_api_calls_per_day: Option<u64>,
+    pub max_events_per_day: Option<u64>,
+}
+
+#[derive(Default)]
+struct DailyUsage {
+    /// Start of the day the usage is recorded for.
+    day: u64,
+    /// Usage of the day, including the usage flushed by previous runs.
+    total: UsageCounters,
+    /// Usage not yet flushed to observers.sqlite.
+    pending: UsageCounters,
+}
+
+lazy_static! {
+    /// Usage of the current day, by tenant.
+    static ref DAILY_USAGE: Mutex<HashMap<String, DailyUsage>> = Mutex::new(HashMap::new());
+    /// Quotas of the tenants which have any.
+    static ref USAGE_QUOTAS: RwLock<HashMap<String, UsageQuota>> = RwLock::new(HashMap::new());
+}
+
+/// API calls of all tenants since startup, sampled to detect low-traffic periods.
+static API_CALLS_SINCE_STARTUP: AtomicU64 = AtomicU64::new(0);
+
+pub fn get_api_calls_since_startup() -> u64 {
+    API_CALLS_SINCE_STARTUP.load(Ordering::Relaxed)
+}
+
+fn current_day() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    now - now % 86_400
+}
+
+fn record_usage(tenant: Option<&str>, usage: UsageCounters) {
+    let Ok(mut daily_usage) = DAILY_USAGE.lock() else {
+        return;
+    };
+    let day = current_day();
+    let entry = daily_usage
+        .entry(tenant.unwrap_or(DEFAULT_TENANT).to_string())
+        .or_default();
+    if entry.day != day {
+        // Usage of the previous day still pending stays accounted to it: only the total is reset.
+        entry.day = day;
+        entry.total = UsageCounters::default();
+    }
+    entry.total.add(&usage);
+    entry.pending.add(&usage);
+}
+
+pub fn record_api_call(tenant: Option<&str>, bytes_served: u64) {
+    API_CALLS_SINCE_STARTUP.fetch_add(1, Ordering::Relaxed);
+    record_usage(
+        tenant,
+        UsageCounters {
+            api_calls: 1,
+            bytes_served,
+            events_delivered: 0,
+        },
+    );
+}
+
+pub fn record_events_delivered(tenant: Option<&str>, events_delivered: u64) {
+    record_usage(
+        tenant,
+        UsageCounters {
+            api_calls: 0,
+            bytes_served: 0,
+            events_delivered,
+        },
+    );
+}
+
+fn get_usage_today(tenant: &str) -> UsageCounters {
+    let day = current_day();
+    DAILY_USAGE
+        .lock()
+        .ok()
+        .and_then(|daily_usage| {
+            daily_usage
+                .get(tenant)
+                .filter(|usage| usage.day == day)
+                .map(|usage| usage.total)
+        })
+        .unwrap_or_default()
+}
+
+fn get_usage_quota(tenant: Option<&str>) -> Option<UsageQuota> {
+    USAGE_QUOTAS
+        .read()
+        .ok()
+        .and_then(|quotas| quotas.get(tenant?).cloned())
+}
+
+/// Returns an error if `tenant` used up its API calls of the day.
+pub fn check_api_calls_quota(tenant: Option<&str>) -> Result<(), String> {
+    let Some(max) = get_usage_quota(tenant).and_then(|q| q.max_api_calls_per_day) else {
+        return Ok(());
+    };
+    if get_usage_today(tenant.unwrap_or(DEFAULT_TENANT)).api_calls >= max {
+        return Err(format!("daily quota of {max} API calls exceeded"));
+    }
+    Ok(())
+}
+
+/// Returns false if `tenant` used up its events of the day.
+pub fn has_events_quota(tenant: Option<&str>) -> bool {
+    let Some(max) = get_usage_quota(tenant).and_then(|q| q.max_events_per_day) else {
+        return true;
+    };
+    get_usage_today(tenant.unwrap_or(DEFAULT_TENANT)).events_delivered < max
+}
+
+/// Interval between two checks of the events quota of a tenant whose deliveries are held back.
+const EVENTS_QUOTA_POLL_INTERVAL_SECS: u64 = 60;
+
+/// Holds the deliveries of `tenant` back until it gets events again, on the next day (UTC): events past the quota are
+/// delivered late rather than dropped. `is_stopped` is checked between two polls. Returns false when it ended the
+/// wait before the quota got available.
+pub async fn wait_for_events_quota(tenant: Option<&str>, is_stopped: impl Fn() -> bool) -> bool {
+    while !has_events_quota(tenant) {
+        if is_stopped() {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_secs(EVENTS_QUOTA_POLL_INTERVAL_SECS)).await;
+    }
+    true
+}
+
+/// Writes the usage recorded since the last flush to observers.sqlite.
+pub fn flush_usage(config: &Config, ctx: &Context) -> Result<(), String> {
+    let pending: Vec<(String, u64, UsageCounters)> = {
+        let mut daily_usage = DAILY_USAGE.lock().map_err(|e| e.to_string())?;
+        daily_usage
+            .iter_mut()
+            .filter(|(_, usage)| usage.pending != UsageCounters::default())
+            .map(|(tenant, usage)| {
+                let pending = std::mem::take(&mut usage.pending);
+                (tenant.clone(), usage.day, pending)
+            })
+            .collect()
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    for (tenant, day, usage) in pending.iter() {
+        add_usage_in_observers(tenant, *day, usage, &observers_db_conn, ctx);
+    }
+    Ok(())
+}
+
+/// Usage recorded between the days `from` and `to` (`YYYY-MM-DD`, inclusive), optionally restricted to a tenant.
+pub fn get_usage_report(
+    from: &str,
+    to: &str,
+    tenant: Option<&str>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Vec<UsageReportEntry>, String> {
+    flush_usage(config, ctx)?;
+    let observers_db_conn = open_readonly_observers_db_conn(config, ctx)?;
+    Ok(find_usage_in_observers(from, to, &observers_db_conn, ctx)
+        .into_iter()
+        .filter(|entry| tenant.map(|t| t == entry.tenant).unwrap_or(true))
+        .collect())
+}
+
+/// Checks that `day` is a `YYYY-MM-DD` date.
+pub fn validate_usage_day(day: &str) -> Result<(), String> {
+    let parts: Vec<&str> = day.split('-').collect();
+    let valid = match parts.as_slice() {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 => {
+            match (
+                year.parse::<u32>(),
+                month.parse::<u32>(),
+                day.parse::<u32>(),
+            ) {
+                (Ok(_), Ok(month), Ok(day)) => (1..=12).contains(&month) && (1..=31).contains(&day),
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(format!("{day} is not a YYYY-MM-DD date")),
+    }
+}
+
+pub fn usage_report_to_csv(entries: &[UsageReportEntry]) -> String {
+    let mut csv = "tenant,day,api_calls,bytes_served,events_delivered\n".to_string();
+    for entry in entries.iter() {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            entry.tenant,
+            entry.day,
+            entry.usage.api_calls,
+            entry.usage.bytes_served,
+            entry.usage.events_delivered
+        ));
+    }
+    csv
+}
+
+/// Loads the quotas of the tenants and the usage already recorded today, and periodically flushes the usage recorded
+/// to observers.sqlite.
+pub fn start_usage_accounting(config: &Config, ctx: &Context) {
+    let PredicatesApi::On(ref api_config) = config.http_api else {
+        return;
+    };
+    if let Ok(mut quotas) = USAGE_QUOTAS.write() {
+        for tenant in api_config.tenants.iter() {
+            quotas.insert(
+                tenant.name.clone(),
+                UsageQuota {
+                    max_api_calls_per_day: tenant.max_api_calls_per_day,
+                    max_events_per_day: tenant.max_events_per_day,
+                },
+            );
+        }
+    }
+    let day = current_day();
+    if let Ok(observers_db_conn) = open_readonly_observers_db_conn(config, ctx) {
+        if let Ok(mut daily_usage) = DAILY_USAGE.lock() {
+            for (tenant, usage) in find_usage_of_day_in_observers(day, &observers_db_conn, ctx) {
+                let entry = daily_usage.entry(tenant).or_default();
+                entry.day = day;
+                entry.total.add(&usage);
+            }
+        }
+    }
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Usage accounting")
+        .spawn(move || {
+            try_info!(moved_ctx, "Usage accounting started");
+            loop {
+                sleep(Duration::from_secs(USAGE_FLUSH_INTERVAL_SECS));
+                if let Err(e) = flush_usage(&moved_config, &moved_ctx) {
+                    try_warn!(moved_ctx, "Unable to flush usage: {e}");
+                }
+            }
+        })
+        .expect("unable to spawn thread");
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_api_calls_quota, has_events_quota, record_api_call, record_events_delivered,
+        usage_report_to_csv, validate_usage_day, UsageCounters, UsageQuota, UsageReportEntry,
+        USAGE_QUOTAS,
+    };
+
+    #[test]
+    fn enforces_daily_quotas() {
+        USAGE_QUOTAS.write().unwrap().insert(
+            "quota-test".to_string(),
+            UsageQuota {
+                max_api_calls_per_day: Some(2),
+                max_events_per_day: Some(10),
+            },
+        );
+        record_api_call(Some("quota-test"), 100);
+        assert!(check_api_calls_quota(Some("quota-test")).is_ok());
+        record_api_call(Some("quota-test"), 100);
+        assert!(check_api_calls_quota(Some("quota-test")).is_err());
+        record_events_delivered(Some("quota-test"), 9);
+        assert!(has_events_quota(Some("quota-test")));
+        record_events_delivered(Some("quota-test"), 1);
+        assert!(!has_events_quota(Some("quota-test")));
+        assert!(has_events_quota(None));
+    }
+
+    #[test]
+    fn exports_usage_reports_as_csv() {
+        let csv = usage_report_to_csv(&[UsageReportEntry {
+            tenant: "indexers".to_string(),
+            day: "2024-03-01".to_string(),
+            usage: UsageCounters {
+                api_calls: 12,
+                bytes_served: 3_400,
+                events_delivered: 56,
+            },
+        }]);
+        assert_eq!(
+            csv,
+            "tenant,day,api_calls,bytes_served,events_delivered\nindexers,2024-03-01,12,3400,56\n"
+        );
+        assert!(validate_usage_day("2024-03-01").is_ok());
+        assert!(validate_usage_day("2024-13-01").is_err());
+        assert!(validate_usage_day("yesterday").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use chainhook_sdk::{bitcoincore_rpc_json::ScanTxOutRequest, utils::Context};
+use rusqlite::Connection;
+use tokio::task::JoinHandle;
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::runes::db::{
+            find_unspent_rune_balances_at_outpoint, get_rune_entry, open_readonly_runes_db_conn,
+        },
+        protocol::{
+            addresses::{describe_script, normalize_address, OutputScript},
+            inscription_sequencing::get_bitcoin_network,
+        },
+    },
+    db::{
+        ordinals::{
+            find_inscriptions_at_outpoint, find_latest_inscription_block_height,
+            open_ordinals_db_snapshot,
+        },
+        sat_ranges::{find_rare_sats_in_output, open_readonly_sat_ranges_db_conn},
+    },
+    ord::sat::Sat,
+    try_warn,
+    utils::bitcoind::{bitcoind_abort_utxo_scan, bitcoind_scan_utxos},
+};
+
+/// Maximum number of addresses annotated in a single request.
+pub const ANNOTATED_UTXOS_MAX_ADDRESSES: usize = 100;
+
+lazy_static! {
+    /// Held while bitcoind scans its UTXO set, which it only does one scan at a time.
+    static ref UTXO_SET_SCANS: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UtxoInscription {
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    /// Offset of the inscribed sat in the output.
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UtxoRareSat {
+    pub ordinal_number: u64,
+    pub name: String,
+    pub rarity: Option<String>,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UtxoRune {
+    pub rune_id: String,
+    pub spaced_rune: Option<String>,
+    /// Amount in the smallest unit of the rune, as a decimal string.
+    pub amount: String,
+    pub divisibility: Option<u8>,
+}
+
+/// Unspent output annotated for coin selection.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnnotatedUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    pub block_height: u64,
+    /// Address, type and hex of the output script.
+    #[serde(flatten)]
+    pub script: OutputScript,
+    pub inscriptions: Vec<UtxoInscription>,
+    /// None when rare sats are not tracked by the index scope.
+    pub rare_sats: Option<Vec<UtxoRareSat>>,
+    /// None when runes are not indexed.
+    pub runes: Option<Vec<UtxoRune>>,
+    /// False when the output was created after the last block indexed: its annotations can't be trusted yet.
+    pub indexed: bool,
+    /// True if the output can be spent as plain sats: indexed, without inscriptions, and without rare sats nor runes
+    /// when those are tracked.
+    pub safe_to_spend: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnnotatedUtxoSet {
+    /// Last block indexed by ordhook.
+    pub indexed_block_height: u64,
+    /// Block bitcoind's UTXO set was scanned at.
+    pub scan_block_height: u64,
+    pub utxos: Vec<AnnotatedUtxo>,
+}
+
+/// Inscriptions, rare sats and runes held by an output looked up by outpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnnotatedOutput {
+    pub outpoint: String,
+    pub inscriptions: Vec<UtxoInscription>,
+    /// None when rare sats are not tracked by the index scope.
+    pub rare_sats: Option<Vec<UtxoRareSat>>,
+    /// None when runes are not indexed.
+    pub runes: Option<Vec<UtxoRune>>,
+}
+
+fn find_utxo_inscriptions(
+    outpoint: &str,
+    inscriptions_db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<UtxoInscription> {
+    find_inscriptions_at_outpoint(outpoint, inscriptions_db_conn, ctx)
+        .into_iter()
+        .map(|row| UtxoInscription {
+            offset: row
+                .location
+                .rsplit(':')
+                .next()
+                .and_then(|offset| offset.parse().ok())
+                .unwrap_or(0),
+            inscription_id: row.inscription_id,
+            inscription_number: row.inscription_number,
+        })
+        .collect()
+}
+
+fn find_utxo_rare_sats(
+    txid: &str,
+    vout: u32,
+    sat_ranges_db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<UtxoRareSat> {
+    find_rare_sats_in_output(txid, vout, sat_ranges_db_conn, ctx)
+        .into_iter()
+        .map(|range| UtxoRareSat {
+            ordinal_number: range.start,
+            name: Sat(range.start).name(),
+            rarity: range.rarity,
+            offset: range.offset,
+        })
+        .collect()
+}
+
+fn find_utxo_runes(outpoint: &str, runes_db_conn: &Connection, ctx: &Context) -> Vec<UtxoRune> {
+    find_unspent_rune_balances_at_outpoint(outpoint, runes_db_conn, ctx)
+        .into_iter()
+        .map(|balance| {
+            let entry = get_rune_entry(&balance.rune_id, runes_db_conn, ctx);
+            UtxoRune {
+                rune_id: balance.rune_id.to_string(),
+                spaced_rune: entry.as_ref().map(|entry| entry.spaced_rune.to_string()),
+                amount: balance.amount.to_string(),
+                divisibility: entry.map(|entry| entry.divisibility),
+            }
+        })
+        .collect()
+}
+
+/// Annotates an unspent output given as `<txid>:<vout>`. Returns None if `outpoint` is malformed.
+pub fn annotate_output(
+    outpoint: &str,
+    inscriptions_db_conn: &Connection,
+    sat_ranges_db_conn: Option<&Connection>,
+    runes_db_conn: Option<&Connection>,
+    ctx: &Context,
+) -> Option<AnnotatedOutput> {
+    let (txid, vout) = outpoint.split_once(':')?;
+    let vout = vout.parse::<u32>().ok()?;
+    let txid = txid.trim_start_matches("0x").to_lowercase();
+    if txid.len() != 64 || !txid.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let outpoint = format!("{txid}:{vout}");
+    Some(AnnotatedOutput {
+        inscriptions: find_utxo_inscriptions(&outpoint, inscriptions_db_conn, ctx),
+        rare_sats: sat_ranges_db_conn.map(|db_conn| find_utxo_rare_sats(&txid, vout, db_conn, ctx)),
+        runes: runes_db_conn.map(|db_conn| find_utxo_runes(&outpoint, db_conn, ctx)),
+        outpoint,
+    })
+}
+
+/// Validates the addresses of an annotation request against the network indexed.
+pub fn parse_utxo_addresses(addresses: &[String], config: &Config) -> Result<Vec<String>, String> {
+    if addresses.is_empty() {
+        return Err("no address provided".to_string());
+    }
+    if addresses.len() > ANNOTATED_UTXOS_MAX_ADDRESSES {
+        return Err(format!(
+            "at most {ANNOTATED_UTXOS_MAX_ADDRESSES} addresses can be annotated at once"
+        ));
+    }
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    addresses
+        .iter()
+        .map(|address| normalize_address(address, &network))
+        .collect()
+}
+
+/// UTXO set scan requests for addresses validated by `parse_utxo_addresses`.
+pub fn address_scan_requests(addresses: &[String]) -> Vec<ScanTxOutRequest> {
+    addresses
+        .iter()
+        .map(|address| ScanTxOutRequest::Single(format!("addr({address})")))
+        .collect()
+}
+
+/// Lists the unspent outputs matching UTXO set scan requests from bitcoind, annotated with the inscriptions, rare sats
+/// and runes they carry, so that wallets can keep them out of coin selection.
+pub fn get_annotated_utxos(
+    scan_requests: &[ScanTxOutRequest],
+    config: &Config,
+    ctx: &Context,
+) -> Result<AnnotatedUtxoSet, String> {
+    let inscriptions_db_conn = open_ordinals_db_snapshot(&config.expected_sqlite_path(), ctx)?;
+    let sat_ranges_db_conn = open_readonly_sat_ranges_db_conn(config, ctx).ok();
+    let runes_db_conn = open_readonly_runes_db_conn(config, ctx).ok();
+    let indexed_block_height =
+        find_latest_inscription_block_height(&inscriptions_db_conn, ctx)?.unwrap_or(0);
+    let (scan_block_height, scanned_utxos) = bitcoind_scan_utxos(scan_requests, config)?;
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    let mut utxos = vec![];
+    for utxo in scanned_utxos.into_iter() {
+        let outpoint = format!("{}:{}", utxo.txid, utxo.vout);
+        let inscriptions = find_utxo_inscriptions(&outpoint, &inscriptions_db_conn, ctx);
+        let rare_sats = sat_ranges_db_conn
+            .as_ref()
+            .map(|db_conn| find_utxo_rare_sats(&utxo.txid, utxo.vout, db_conn, ctx));
+        let runes = runes_db_conn
+            .as_ref()
+            .map(|db_conn| find_utxo_runes(&outpoint, db_conn, ctx));
+        let indexed = utxo.block_height <= indexed_block_height;
+        let safe_to_spend = indexed
+            && inscriptions.is_empty()
+            && rare_sats.as_ref().map_or(true, |sats| sats.is_empty())
+            && runes.as_ref().map_or(true, |runes| runes.is_empty());
+        utxos.push(AnnotatedUtxo {
+            script: describe_script(&utxo.script_pubkey, &network),
+            txid: utxo.txid,
+            vout: utxo.vout,
+            value: utxo.value,
+            block_height: utxo.block_height,
+            inscriptions,
+            rare_sats,
+            runes,
+            indexed,
+            safe_to_spend,
+        });
+    }
+    utxos.sort_by(|a, b| (a.block_height, &a.txid, a.vout).cmp(&(b.block_height, &b.txid, b.vout)));
+    Ok(AnnotatedUtxoSet {
+        indexed_block_height,
+        scan_block_height,
+        utxos,
+    })
+}
+
+/// Annotated UTXO set scan running on the blocking thread pool, `scantxoutset` walking the whole UTXO set of bitcoind.
+/// Dropping the handle before the scan completes, as when the client of the request goes away, aborts it.
+pub struct UtxoScanHandle {
+    task: Option<JoinHandle<Result<AnnotatedUtxoSet, String>>>,
+    cancelled: Arc<AtomicBool>,
+    scanning: Arc<AtomicBool>,
+    config: Config,
+    ctx: Context,
+}
+
+impl UtxoScanHandle {
+    pub fn spawn(
+        scan_requests: Vec<ScanTxOutRequest>,
+        config: &Config,
+        ctx: &Context,
+    ) -> UtxoScanHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let scanning = Arc::new(AtomicBool::new(false));
+        let moved_cancelled = cancelled.clone();
+        let moved_scanning = scanning.clone();
+        let moved_config = config.clone();
+        let moved_ctx = ctx.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let _scan = UTXO_SET_SCANS
+                .lock()
+                .map_err(|e| format!("unable to scan utxo set: {e}"))?;
+            if moved_cancelled.load(Ordering::SeqCst) {
+                return Err("utxo set scan cancelled".to_string());
+            }
+            moved_scanning.store(true, Ordering::SeqCst);
+            let result = get_annotated_utxos(&scan_requests, &moved_config, &moved_ctx);
+            moved_scanning.store(false, Ordering::SeqCst);
+            result
+        });
+        UtxoScanHandle {
+            task: Some(task),
+            cancelled,
+            scanning,
+            config: config.clone(),
+            ctx: ctx.clone(),
+        }
+    }
+
+    pub async fn join(mut self) -> Result<AnnotatedUtxoSet, String> {
+        let Some(task) = self.task.take() else {
+            return Err("utxo set scan already joined".to_string());
+        };
+        task.await
+            .map_err(|e| format!("utxo set scan failed: {e}"))?
+    }
+}
+
+impl Drop for UtxoScanHandle {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if !self.scanning.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = bitcoind_abort_utxo_scan(&self.config) {
+            try_warn!(self.ctx, "Unable to abort utxo set scan: {e}");
+        }
+    }
+}
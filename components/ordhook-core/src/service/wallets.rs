@@ -0,0 +1,545 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Mutex, RwLock},
+};
+
+use chainhook_sdk::{
+    bitcoincore_rpc::bitcoin::{bip32::Xpub, Address, ScriptBuf},
+    bitcoincore_rpc_json::ScanTxOutRequest,
+    chainhooks::bitcoin::BitcoinTriggerChainhook,
+    types::{BitcoinTransactionData, OrdinalInscriptionTransferDestination, OrdinalOperation},
+    utils::Context,
+};
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    core::protocol::{addresses::script_hex_address, inscription_sequencing::get_bitcoin_network},
+    db::ordinals::find_watched_outputs,
+    service::{
+        enrichment::EnrichmentDbConnections,
+        observers::{insert_wallet_filter_in_observers, open_readwrite_observers_db_conn},
+    },
+    try_info, try_warn,
+    utils::{
+        bitcoind::{bitcoind_derive_addresses, bitcoind_get_descriptor_info},
+        format_outpoint_to_watch, parse_satpoint_to_watch,
+    },
+};
+
+const WALLET_PREDICATE_SCOPE: &str = "wallet";
+pub const WALLET_DEFAULT_GAP_LIMIT: u32 = 20;
+const WALLET_MAX_GAP_LIMIT: u32 = 1_000;
+const WALLET_MAX_DESCRIPTORS: usize = 8;
+const XPUB_ADDRESS_TYPES: [&str; 4] = ["p2tr", "p2wpkh", "p2sh-p2wpkh", "p2pkh"];
+
+/// HD wallet watched by a predicate. Addresses are derived from ranged descriptors, up to `gap_limit` addresses past the
+/// last one used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletPredicateFilter {
+    pub descriptors: Vec<String>,
+    pub gap_limit: u32,
+    /// Derivation index of the last address used, by descriptor.
+    #[serde(default)]
+    pub last_used_indexes: Vec<Option<u32>>,
+}
+
+impl WalletPredicateFilter {
+    /// Number of addresses of a descriptor to derive.
+    pub fn derivation_count(&self, descriptor_index: usize) -> u32 {
+        match self
+            .last_used_indexes
+            .get(descriptor_index)
+            .copied()
+            .flatten()
+        {
+            Some(last_used_index) => last_used_index + 1 + self.gap_limit,
+            None => self.gap_limit,
+        }
+    }
+
+    /// UTXO set scan requests covering the addresses derived.
+    pub fn scan_requests(&self) -> Vec<ScanTxOutRequest> {
+        self.descriptors
+            .iter()
+            .enumerate()
+            .map(|(i, descriptor)| ScanTxOutRequest::Extended {
+                desc: descriptor.clone(),
+                range: (0, self.derivation_count(i) as u64 - 1),
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct WalletPredicateScope {
+    #[serde(default)]
+    descriptors: Vec<String>,
+    xpub: Option<String>,
+    address_type: Option<String>,
+    gap_limit: Option<u32>,
+}
+
+/// Addresses derived for a watched wallet, with the descriptor and the derivation index they were derived at.
+struct WalletWatch {
+    filter: WalletPredicateFilter,
+    addresses: HashMap<String, (usize, u32)>,
+}
+
+/// Address of a watched wallet seen in a transaction delivered.
+struct WalletUsage {
+    uuid: String,
+    descriptor_index: usize,
+    derivation_index: u32,
+}
+
+lazy_static! {
+    /// Wallets watched by the registered predicates, by predicate uuid.
+    static ref WALLET_WATCHES: RwLock<HashMap<String, WalletWatch>> = RwLock::new(HashMap::new());
+    /// Held while a wallet gets watched, unwatched or extended, so that addresses derived from a filter are never
+    /// stored next to another version of it.
+    static ref WALLET_UPDATES: Mutex<()> = Mutex::new(());
+    static ref WALLET_USAGE_TX: Mutex<Option<crossbeam_channel::Sender<WalletUsage>>> =
+        Mutex::new(None);
+}
+
+/// Receive (`/0/*`) and change (`/1/*`) descriptors of an account xpub.
+pub fn xpub_descriptors(xpub: &str, address_type: &str) -> Result<Vec<String>, String> {
+    Xpub::from_str(xpub).map_err(|e| format!("invalid xpub {xpub}: {e}"))?;
+    let (prefix, suffix) = match address_type {
+        "p2tr" => ("tr(", ")"),
+        "p2wpkh" => ("wpkh(", ")"),
+        "p2sh-p2wpkh" => ("sh(wpkh(", "))"),
+        "p2pkh" => ("pkh(", ")"),
+        _ => {
+            return Err(format!(
+                "invalid address type {address_type}, expected one of {}",
+                XPUB_ADDRESS_TYPES.join(", ")
+            ))
+        }
+    };
+    Ok((0..2)
+        .map(|chain| format!("{prefix}{xpub}/{chain}/*{suffix}"))
+        .collect())
+}
+
+/// Predicates can use `{"scope": "wallet", "descriptors": [..]}` or `{"scope": "wallet", "xpub": "..", "address_type":
+/// ".."}` as `if_this`, with an optional `gap_limit`, to follow the inscriptions revealed to or received by the
+/// addresses of an HD wallet. Like the `brc20` scope, it gets replaced by an inscription feed, and the filter is
+/// returned so that deliveries can be narrowed down to the wallet's transactions.
+pub fn extract_wallet_predicate_filter(
+    predicate: &mut JsonValue,
+) -> Result<Option<WalletPredicateFilter>, String> {
+    let Some(networks) = predicate
+        .get_mut("networks")
+        .and_then(|n| n.as_object_mut())
+    else {
+        return Ok(None);
+    };
+    let mut filter = None;
+    for (_, network) in networks.iter_mut() {
+        let Some(if_this) = network.get_mut("if_this") else {
+            continue;
+        };
+        if if_this.get("scope").and_then(|s| s.as_str()) != Some(WALLET_PREDICATE_SCOPE) {
+            continue;
+        }
+        let scope: WalletPredicateScope = serde_json::from_value(if_this.clone())
+            .map_err(|e| format!("invalid wallet predicate: {e}"))?;
+        let descriptors = match (scope.xpub, scope.descriptors.is_empty()) {
+            (Some(xpub), true) => {
+                xpub_descriptors(&xpub, scope.address_type.as_deref().unwrap_or("p2tr"))?
+            }
+            (None, false) => scope.descriptors,
+            _ => {
+                return Err(
+                    "wallet predicates expect either an xpub or a list of descriptors".to_string(),
+                )
+            }
+        };
+        if descriptors.len() > WALLET_MAX_DESCRIPTORS {
+            return Err(format!(
+                "wallet predicates are limited to {WALLET_MAX_DESCRIPTORS} descriptors"
+            ));
+        }
+        if let Some(descriptor) = descriptors.iter().find(|d| !d.contains('*')) {
+            return Err(format!("descriptor {descriptor} is not ranged"));
+        }
+        let gap_limit = scope.gap_limit.unwrap_or(WALLET_DEFAULT_GAP_LIMIT);
+        if gap_limit == 0 || gap_limit > WALLET_MAX_GAP_LIMIT {
+            return Err(format!(
+                "gap_limit must be between 1 and {WALLET_MAX_GAP_LIMIT}"
+            ));
+        }
+        filter = Some(WalletPredicateFilter {
+            last_used_indexes: vec![None; descriptors.len()],
+            descriptors,
+            gap_limit,
+        });
+        *if_this = json!({
+            "scope": "ordinals_protocol",
+            "operation": "inscription_feed",
+        });
+    }
+    Ok(filter)
+}
+
+/// Derives the addresses of a wallet past the `derived_counts` already derived, by descriptor.
+fn derive_wallet_addresses(
+    filter: &WalletPredicateFilter,
+    derived_counts: &[u32],
+    config: &Config,
+) -> Result<HashMap<String, (usize, u32)>, String> {
+    let mut addresses = HashMap::new();
+    for (i, descriptor) in filter.descriptors.iter().enumerate() {
+        let start = derived_counts.get(i).copied().unwrap_or(0);
+        let end = filter.derivation_count(i);
+        if start >= end {
+            continue;
+        }
+        let derived = bitcoind_derive_addresses(descriptor, start, end - 1, config)?;
+        for (address, derivation_index) in derived.into_iter().zip(start..end) {
+            addresses.insert(address, (i, derivation_index));
+        }
+    }
+    Ok(addresses)
+}
+
+/// Starts watching the wallet of a predicate: descriptors are checked and normalized by bitcoind, and addresses derived
+/// up to the gap limit. Returns the filter to persist.
+pub fn watch_wallet(
+    uuid: &str,
+    mut filter: WalletPredicateFilter,
+    config: &Config,
+) -> Result<WalletPredicateFilter, String> {
+    for descriptor in filter.descriptors.iter_mut() {
+        let (normalized_descriptor, is_range) = bitcoind_get_descriptor_info(descriptor, config)?;
+        if !is_range {
+            return Err(format!("descriptor {descriptor} is not ranged"));
+        }
+        *descriptor = normalized_descriptor;
+    }
+    filter
+        .last_used_indexes
+        .resize(filter.descriptors.len(), None);
+    let _update = WALLET_UPDATES
+        .lock()
+        .map_err(|e| format!("unable to watch wallet: {e}"))?;
+    let addresses = derive_wallet_addresses(&filter, &[], config)?;
+    let mut watches = WALLET_WATCHES
+        .write()
+        .map_err(|e| format!("unable to watch wallet: {e}"))?;
+    watches.insert(
+        uuid.to_string(),
+        WalletWatch {
+            filter: filter.clone(),
+            addresses,
+        },
+    );
+    Ok(filter)
+}
+
+pub fn unwatch_wallet(uuid: &str) {
+    let Ok(_update) = WALLET_UPDATES.lock() else {
+        return;
+    };
+    if let Ok(mut watches) = WALLET_WATCHES.write() {
+        watches.remove(uuid);
+    }
+}
+
+pub fn get_wallet_predicate_filter(uuid: &str) -> Option<WalletPredicateFilter> {
+    WALLET_WATCHES
+        .read()
+        .ok()
+        .and_then(|watches| watches.get(uuid).map(|watch| watch.filter.clone()))
+}
+
+/// Addresses derived for the wallet of a predicate, by descriptor, in derivation order.
+pub fn get_wallet_addresses(uuid: &str) -> Option<Vec<Vec<String>>> {
+    let watches = WALLET_WATCHES.read().ok()?;
+    let watch = watches.get(uuid)?;
+    let mut addresses = vec![vec![]; watch.filter.descriptors.len()];
+    let mut derived = watch.addresses.iter().collect::<Vec<_>>();
+    derived.sort_by_key(|(_, (i, derivation_index))| (*i, *derivation_index));
+    for (address, (i, _)) in derived.into_iter() {
+        addresses[*i].push(address.clone());
+    }
+    Some(addresses)
+}
+
+/// Output scripts of the addresses derived for the wallet of a predicate.
+pub fn get_wallet_scripts(uuid: &str) -> Option<Vec<ScriptBuf>> {
+    let watches = WALLET_WATCHES.read().ok()?;
+    let watch = watches.get(uuid)?;
+    Some(
+        watch
+            .addresses
+            .keys()
+            .filter_map(|address| Address::from_str(address).ok())
+            .map(|address| address.assume_checked().script_pubkey())
+            .collect(),
+    )
+}
+
+/// Addresses receiving inscriptions in a transaction, as inscriber or as transfer destination.
+fn get_transaction_destination_addresses(tx: &BitcoinTransactionData) -> Vec<&String> {
+    tx.metadata
+        .ordinal_operations
+        .iter()
+        .filter_map(|operation| match operation {
+            OrdinalOperation::InscriptionRevealed(reveal) => reveal.inscriber_address.as_ref(),
+            OrdinalOperation::InscriptionTransferred(transfer) => match transfer.destination {
+                OrdinalInscriptionTransferDestination::Transferred(ref address) => Some(address),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Addresses inscriptions are moved out of in a transaction. They are resolved from the watched outputs spent, which
+/// are only kept `SPENT_WATCHED_OUTPUTS_RETENTION_BLOCKS` blocks past their spending.
+fn get_transaction_source_addresses(
+    tx: &BitcoinTransactionData,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Vec<String> {
+    let outpoints = tx
+        .metadata
+        .ordinal_operations
+        .iter()
+        .filter_map(|operation| match operation {
+            OrdinalOperation::InscriptionTransferred(transfer) => {
+                let (tx_identifier, output_index, _) =
+                    parse_satpoint_to_watch(&transfer.satpoint_pre_transfer);
+                Some(format_outpoint_to_watch(&tx_identifier, output_index))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if outpoints.is_empty() {
+        return vec![];
+    }
+    let network = get_bitcoin_network(&config.network.bitcoin_network);
+    find_watched_outputs(&outpoints, db_conn, ctx)
+        .into_values()
+        .filter_map(|output| script_hex_address(&output.script_pubkey, &network))
+        .collect()
+}
+
+/// Drops the transactions not involving an address of the wallet watched by the triggered predicate, if any, as
+/// inscriber, transfer destination or transfer source. Addresses used past the last one used get more addresses
+/// derived by the wallet watching worker. Returns false when nothing is left to deliver.
+pub fn apply_wallet_predicate_filter(
+    trigger: &mut BitcoinTriggerChainhook,
+    db_conns: &mut EnrichmentDbConnections,
+    config: &Config,
+    ctx: &Context,
+) -> bool {
+    let Ok(watches) = WALLET_WATCHES.read() else {
+        return true;
+    };
+    let Some(watch) = watches.get(&trigger.chainhook.uuid) else {
+        return true;
+    };
+    let db_conn = match db_conns.get_ordinals_db_conn(config, ctx) {
+        Ok(db_conn) => Some(db_conn),
+        Err(e) => {
+            try_warn!(
+                ctx,
+                "Unable to resolve transfer sources for wallet of predicate {}: {e}",
+                trigger.chainhook.uuid
+            );
+            None
+        }
+    };
+    let is_sent_from_wallet = |tx: &BitcoinTransactionData| {
+        db_conn.map_or(false, |db_conn| {
+            get_transaction_source_addresses(tx, db_conn, config, ctx)
+                .iter()
+                .any(|address| watch.addresses.contains_key(address))
+        })
+    };
+    let mut usages = vec![];
+    for (transactions, _) in trigger.apply.iter_mut() {
+        transactions.retain(|tx| {
+            let mut matched = false;
+            for address in get_transaction_destination_addresses(tx) {
+                let Some((descriptor_index, derivation_index)) = watch.addresses.get(address)
+                else {
+                    continue;
+                };
+                matched = true;
+                let last_used_index = watch
+                    .filter
+                    .last_used_indexes
+                    .get(*descriptor_index)
+                    .copied()
+                    .flatten();
+                if last_used_index.map_or(true, |i| i < *derivation_index) {
+                    usages.push(WalletUsage {
+                        uuid: trigger.chainhook.uuid.clone(),
+                        descriptor_index: *descriptor_index,
+                        derivation_index: *derivation_index,
+                    });
+                }
+            }
+            matched || is_sent_from_wallet(*tx)
+        });
+    }
+    trigger
+        .apply
+        .retain(|(transactions, _)| !transactions.is_empty());
+    for (transactions, _) in trigger.rollback.iter_mut() {
+        transactions.retain(|tx| {
+            get_transaction_destination_addresses(tx)
+                .iter()
+                .any(|address| watch.addresses.contains_key(*address))
+                || is_sent_from_wallet(*tx)
+        });
+    }
+    trigger
+        .rollback
+        .retain(|(transactions, _)| !transactions.is_empty());
+    drop(watches);
+    if let Ok(usage_tx) = WALLET_USAGE_TX.lock() {
+        if let Some(ref usage_tx) = *usage_tx {
+            for usage in usages.into_iter() {
+                let _ = usage_tx.send(usage);
+            }
+        }
+    }
+    !trigger.apply.is_empty() || !trigger.rollback.is_empty()
+}
+
+/// Moves the last address used of a wallet forward, deriving the addresses now within the gap limit.
+fn record_wallet_usage(usage: WalletUsage, config: &Config, ctx: &Context) -> Result<(), String> {
+    let _update = WALLET_UPDATES
+        .lock()
+        .map_err(|e| format!("unable to update wallet: {e}"))?;
+    let Some(mut filter) = get_wallet_predicate_filter(&usage.uuid) else {
+        return Ok(());
+    };
+    let derived_counts = (0..filter.descriptors.len())
+        .map(|i| filter.derivation_count(i))
+        .collect::<Vec<_>>();
+    let Some(last_used_index) = filter.last_used_indexes.get_mut(usage.descriptor_index) else {
+        return Ok(());
+    };
+    if last_used_index.map_or(false, |i| i >= usage.derivation_index) {
+        return Ok(());
+    }
+    *last_used_index = Some(usage.derivation_index);
+    let addresses = derive_wallet_addresses(&filter, &derived_counts, config)?;
+    let observers_db_conn = open_readwrite_observers_db_conn(config, ctx)?;
+    let mut watches = WALLET_WATCHES
+        .write()
+        .map_err(|e| format!("unable to update wallet: {e}"))?;
+    // The predicate may have been deregistered in the meantime.
+    let Some(watch) = watches.get_mut(&usage.uuid) else {
+        return Ok(());
+    };
+    insert_wallet_filter_in_observers(&usage.uuid, &filter, &observers_db_conn, ctx);
+    try_info!(
+        ctx,
+        "Wallet of predicate {}: address #{} of descriptor #{} used, {} addresses derived",
+        usage.uuid,
+        usage.derivation_index,
+        usage.descriptor_index,
+        addresses.len()
+    );
+    watch.filter = filter;
+    watch.addresses.extend(addresses);
+    Ok(())
+}
+
+/// Starts the background worker extending the addresses derived for watched wallets as they get used.
+pub fn start_wallet_watching_worker(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Ok(mut usage_tx) = WALLET_USAGE_TX.lock() else {
+        return Ok(());
+    };
+    if usage_tx.is_some() {
+        return Ok(());
+    }
+    let (tx, rx) = crossbeam_channel::unbounded::<WalletUsage>();
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    hiro_system_kit::thread_named("Wallet watching worker")
+        .spawn(move || {
+            while let Ok(usage) = rx.recv() {
+                let uuid = usage.uuid.clone();
+                if let Err(e) = record_wallet_usage(usage, &moved_config, &moved_ctx) {
+                    try_warn!(
+                        moved_ctx,
+                        "Unable to update wallet of predicate {uuid}: {e}"
+                    );
+                }
+            }
+        })
+        .map_err(|e| format!("unable to start wallet watching worker: {e}"))?;
+    *usage_tx = Some(tx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{extract_wallet_predicate_filter, xpub_descriptors, WalletPredicateFilter};
+
+    const XPUB: &str = "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+
+    #[test]
+    fn extracts_wallet_filter_from_predicate() {
+        let mut predicate = json!({
+            "networks": {
+                "mainnet": {
+                    "if_this": { "scope": "wallet", "xpub": XPUB, "address_type": "p2wpkh", "gap_limit": 5 },
+                    "then_that": "noop"
+                }
+            }
+        });
+        let filter = extract_wallet_predicate_filter(&mut predicate)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            filter,
+            WalletPredicateFilter {
+                descriptors: vec![format!("wpkh({XPUB}/0/*)"), format!("wpkh({XPUB}/1/*)")],
+                gap_limit: 5,
+                last_used_indexes: vec![None, None],
+            }
+        );
+        assert_eq!(
+            predicate["networks"]["mainnet"]["if_this"],
+            json!({ "scope": "ordinals_protocol", "operation": "inscription_feed" })
+        );
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "wallet", "descriptors": ["tr(xpub/0/1)"] } } }
+        });
+        assert!(extract_wallet_predicate_filter(&mut predicate).is_err());
+
+        let mut predicate = json!({
+            "networks": { "mainnet": { "if_this": { "scope": "wallet" } } }
+        });
+        assert!(extract_wallet_predicate_filter(&mut predicate).is_err());
+
+        assert!(xpub_descriptors("xpub", "p2tr").is_err());
+        assert!(xpub_descriptors(XPUB, "p2wsh").is_err());
+    }
+
+    #[test]
+    fn computes_derivation_counts() {
+        let filter = WalletPredicateFilter {
+            descriptors: vec!["a".to_string(), "b".to_string()],
+            gap_limit: 20,
+            last_used_indexes: vec![Some(4), None],
+        };
+        assert_eq!(filter.derivation_count(0), 25);
+        assert_eq!(filter.derivation_count(1), 20);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use chainhook_sdk::utils::Context;
+use reqwest::{Client, RequestBuilder, Url};
+
+use crate::{config::ResourcesConfig, try_warn, utils::http::outbound_http_client_builder};
+
+/// Idle connections to a destination are kept open this long, so that frequent deliveries skip the TCP and TLS
+/// handshakes.
+const WEBHOOK_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// Interval of the pings keeping HTTP/2 connections alive between deliveries.
+const WEBHOOK_HTTP2_KEEP_ALIVE_INTERVAL_SECS: u64 = 30;
+
+lazy_static! {
+    /// Clients delivering predicate occurrences, by destination (scheme, host and port).
+    static ref WEBHOOK_CLIENTS: Mutex<HashMap<String, Client>> = Mutex::new(HashMap::new());
+}
+
+fn get_webhook_destination(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+/// HTTPS destinations negotiate HTTP/2 when they support it, and share a single multiplexed connection. Others keep
+/// HTTP/1.1 connections alive between deliveries. Deliveries time out after `webhook_timeout_secs`.
+pub(crate) fn build_webhook_client(resources: &ResourcesConfig) -> Result<Client, String> {
+    outbound_http_client_builder(resources)?
+        .timeout(Duration::from_secs(resources.webhook_timeout_secs))
+        .pool_idle_timeout(Duration::from_secs(WEBHOOK_POOL_IDLE_TIMEOUT_SECS))
+        .tcp_keepalive(Duration::from_secs(WEBHOOK_POOL_IDLE_TIMEOUT_SECS))
+        .http2_keep_alive_interval(Duration::from_secs(WEBHOOK_HTTP2_KEEP_ALIVE_INTERVAL_SECS))
+        .http2_keep_alive_while_idle(true)
+        .http2_adaptive_window(true)
+        .build()
+        .map_err(|e| format!("unable to build http client: {e}"))
+}
+
+/// Returns the client of the destination of `url`, building it on the first delivery.
+fn get_webhook_client(url: &Url, resources: &ResourcesConfig) -> Result<Client, String> {
+    let mut clients = WEBHOOK_CLIENTS
+        .lock()
+        .map_err(|e| format!("unable to get webhook client: {e}"))?;
+    let destination = get_webhook_destination(url);
+    if let Some(client) = clients.get(&destination) {
+        return Ok(client.clone());
+    }
+    let client = build_webhook_client(resources)?;
+    clients.insert(destination, client.clone());
+    Ok(client)
+}
+
+/// Delivers a predicate occurrence through the pooled client of its destination, instead of the client chainhook-sdk
+/// builds for every occurrence. Failed deliveries are retried `network_max_retries` times, with the backoff of
+/// `network_retry_backoff_ms`.
+pub async fn send_webhook_request(
+    request: RequestBuilder,
+    resources: &ResourcesConfig,
+    ctx: &Context,
+) -> Result<(), String> {
+    let (_, request) = request.build_split();
+    let request = request.map_err(|e| format!("unable to build webhook request: {e}"))?;
+    let client = get_webhook_client(request.url(), resources)?;
+    let url = request.url().clone();
+    let mut attempts = 0;
+    loop {
+        let Some(attempt) = request.try_clone() else {
+            return Err(format!("unable to replay webhook request to {url}"));
+        };
+        attempts += 1;
+        match client.execute(attempt).await {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => {
+                try_warn!(ctx, "Webhook {url} responded with {}", res.status());
+            }
+            Err(e) => {
+                try_warn!(ctx, "Webhook {url} is not reachable: {e}");
+            }
+        }
+        if attempts > resources.network_max_retries {
+            return Err(format!(
+                "unable to deliver to {url} after {attempts} attempts"
+            ));
+        }
+        tokio::time::sleep(resources.network_retry_backoff(attempts)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use reqwest::Url;
+
+    use crate::config::Config;
+
+    use super::{get_webhook_client, get_webhook_destination, WEBHOOK_CLIENTS};
+
+    #[test]
+    fn pools_webhook_clients_per_destination() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert_eq!(
+            get_webhook_destination(&url("https://example.com/a?b=c")),
+            get_webhook_destination(&url("https://example.com:443/d"))
+        );
+        assert_ne!(
+            get_webhook_destination(&url("https://example.com/a")),
+            get_webhook_destination(&url("http://example.com/a"))
+        );
+
+        let resources = Config::test_default().resources;
+        get_webhook_client(&url("https://pool.test/a"), &resources).unwrap();
+        get_webhook_client(&url("https://pool.test/b"), &resources).unwrap();
+        get_webhook_client(&url("https://other.pool.test/a"), &resources).unwrap();
+        let clients = WEBHOOK_CLIENTS.lock().unwrap();
+        assert_eq!(
+            clients.keys().filter(|k| k.ends_with("pool.test")).count(),
+            2
+        );
+    }
+}
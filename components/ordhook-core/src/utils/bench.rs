@@ -0,0 +1,359 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::{
+    indexer::bitcoin::parse_downloaded_block,
+    types::{BitcoinNetwork, OrdinalInscriptionNumber, OrdinalOperation},
+    utils::Context,
+};
+use rocksdb::DB;
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    config::Config,
+    core::protocol::{
+        inscription_parsing::{
+            get_inscriptions_revealed_in_block, parse_inscriptions_and_standardize_block,
+        },
+        inscription_sequencing::{
+            augment_block_with_ordinals_inscriptions_data, get_jubilee_block_height, SequenceCursor,
+        },
+        satoshi_numbering::TraversalResult,
+    },
+    db::{
+        blocks::{
+            find_last_block_inserted, find_pinned_block_bytes_at_block_height,
+            insert_entry_in_blocks, open_readonly_blocks_db, rocks_db_default_options,
+        },
+        cursor::BlockBytesCursor,
+        ordinals::initialize_ordinals_db,
+    },
+};
+
+/// Block #279671 as returned by bitcoind, bundled so that the `sequence` and `write` workloads run the same on every
+/// machine.
+const BENCH_FIXTURE_BLOCK: &str = include_str!("../db/fixtures/blocks_json/279671.json");
+
+/// Witness revealing a text inscription, set on the first input of every transaction of the fixture block for the
+/// `sequence` workload to number inscriptions.
+const BENCH_INSCRIPTION_WITNESS: [&str; 3] = [
+    "6c00eb3c4d35fedd257051333b4ca81d1a25a37a9af4891f1fec2869edd56b14180eafbda8851d63138a724c9b15384bc5f0536de658bd294d426a36212e6f08",
+    "209e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746ac0063036f7264010118746578742f706c61696e3b636861727365743d7574662d38004c5e7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d68",
+    "c19e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746",
+];
+
+/// The fixture block, with an inscription revealed by every transaction but the coinbase.
+fn build_inscriptions_fixture_block() -> Result<Vec<u8>, String> {
+    let mut fixture: JsonValue = serde_json::from_str(BENCH_FIXTURE_BLOCK)
+        .map_err(|e| format!("unable to decode fixture block: {e}"))?;
+    let Some(transactions) = fixture["result"]["tx"].as_array_mut() else {
+        return Err("fixture block has no transactions".into());
+    };
+    for tx in transactions.iter_mut().skip(1) {
+        tx["vin"][0]["txinwitness"] = json!(BENCH_INSCRIPTION_WITNESS);
+    }
+    serde_json::to_vec(&fixture).map_err(|e| format!("unable to encode fixture block: {e}"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BenchWorkload {
+    /// Reads the most recent blocks of hord.rocksdb, and walks through their transactions.
+    Parse,
+    /// Decodes, standardizes and parses the inscriptions of the fixture block with an inscription per transaction, then
+    /// compacts it and numbers its inscriptions, as the pipeline does for every downloaded block.
+    Sequence,
+    /// Writes copies of the compacted fixture block in a scratch rocksdb.
+    Write,
+}
+
+impl BenchWorkload {
+    pub fn parse(workload: &str) -> Result<BenchWorkload, String> {
+        match workload {
+            "parse" => Ok(BenchWorkload::Parse),
+            "sequence" => Ok(BenchWorkload::Sequence),
+            "write" => Ok(BenchWorkload::Write),
+            _ => Err(format!(
+                "unknown workload {workload}, expected parse, sequence or write"
+            )),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            BenchWorkload::Parse => "parse",
+            BenchWorkload::Sequence => "sequence",
+            BenchWorkload::Write => "write",
+        }
+    }
+}
+
+/// Outcome of a workload. Scores are throughputs: higher is better.
+#[derive(Debug, Clone)]
+pub struct BenchScore {
+    pub workload: BenchWorkload,
+    pub blocks: u64,
+    pub transactions: u64,
+    pub inscriptions: u64,
+    pub bytes: u64,
+    pub duration: Duration,
+}
+
+impl BenchScore {
+    fn per_sec(&self, count: u64) -> f64 {
+        count as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn blocks_per_sec(&self) -> f64 {
+        self.per_sec(self.blocks)
+    }
+
+    pub fn transactions_per_sec(&self) -> f64 {
+        self.per_sec(self.transactions)
+    }
+
+    pub fn megabytes_per_sec(&self) -> f64 {
+        self.per_sec(self.bytes) / (1024.0 * 1024.0)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "workload": self.workload.code(),
+            "blocks": self.blocks,
+            "transactions": self.transactions,
+            "inscriptions": self.inscriptions,
+            "bytes": self.bytes,
+            "duration_ms": self.duration.as_millis() as u64,
+            "blocks_per_sec": self.blocks_per_sec(),
+            "transactions_per_sec": self.transactions_per_sec(),
+            "megabytes_per_sec": self.megabytes_per_sec(),
+        })
+    }
+}
+
+fn bench_parse_cached_blocks(
+    count: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BenchScore, String> {
+    let blocks_db = open_readonly_blocks_db(config, ctx)?;
+    let tip = find_last_block_inserted(&blocks_db) as u64;
+    if tip == 0 {
+        return Err("hord.rocksdb is empty: the parse workload needs indexed blocks".into());
+    }
+    let start = tip.saturating_sub(count.saturating_sub(1)).max(1);
+    let mut score = BenchScore {
+        workload: BenchWorkload::Parse,
+        blocks: 0,
+        transactions: 0,
+        inscriptions: 0,
+        bytes: 0,
+        duration: Duration::ZERO,
+    };
+    let started_at = Instant::now();
+    for block_height in start..=tip {
+        let Some(block_bytes) = find_pinned_block_bytes_at_block_height(
+            block_height as u32,
+            3,
+            &blocks_db,
+            config,
+            ctx,
+        ) else {
+            return Err(format!(
+                "block #{block_height} is missing from hord.rocksdb"
+            ));
+        };
+        let cursor = BlockBytesCursor::new(&block_bytes);
+        for tx in cursor.iter_tx() {
+            let sats_in: u64 = tx.inputs.iter().map(|input| input.txin_value).sum();
+            let sats_out: u64 = tx.outputs.iter().sum();
+            std::hint::black_box((sats_in, sats_out));
+            score.transactions += 1;
+        }
+        score.blocks += 1;
+        score.bytes += block_bytes.len() as u64;
+    }
+    score.duration = started_at.elapsed();
+    Ok(score)
+}
+
+fn bench_sequence_fixture(
+    iterations: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BenchScore, String> {
+    let fixture_block = build_inscriptions_fixture_block()?;
+    // Numbers are picked from an empty scratch db, the same for every iteration since nothing gets written.
+    let scratch_path = config.expected_cache_path().join("bench_sqlite");
+    let _ = std::fs::remove_dir_all(&scratch_path);
+    std::fs::create_dir_all(&scratch_path)
+        .map_err(|e| format!("unable to create {}: {e}", scratch_path.display()))?;
+    let score = {
+        let inscriptions_db_conn = initialize_ordinals_db(&scratch_path, ctx);
+        sequence_fixture_block(
+            iterations,
+            &fixture_block,
+            &inscriptions_db_conn,
+            config,
+            ctx,
+        )
+    };
+    let _ = std::fs::remove_dir_all(&scratch_path);
+    score
+}
+
+fn sequence_fixture_block(
+    iterations: u64,
+    fixture_block: &[u8],
+    inscriptions_db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BenchScore, String> {
+    let jubilee_height = get_jubilee_block_height(config);
+    let mut score = BenchScore {
+        workload: BenchWorkload::Sequence,
+        blocks: 0,
+        transactions: 0,
+        inscriptions: 0,
+        bytes: 0,
+        duration: Duration::ZERO,
+    };
+    let started_at = Instant::now();
+    for _ in 0..iterations {
+        let raw_block = parse_downloaded_block(fixture_block.to_vec())
+            .map_err(|e| format!("unable to decode fixture block: {e}"))?;
+        let block_bytes = BlockBytesCursor::from_full_block(&raw_block)
+            .map_err(|e| format!("unable to compact fixture block: {e}"))?;
+        let mut block =
+            parse_inscriptions_and_standardize_block(raw_block, &BitcoinNetwork::Mainnet, ctx)
+                .map_err(|(e, _)| format!("unable to standardize fixture block: {e}"))?;
+        // Each inscription gets its own sat, as the satoshi numbering of the pipeline would do.
+        let mut inscriptions_data = BTreeMap::new();
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            let reveals_inscription = tx
+                .metadata
+                .ordinal_operations
+                .iter()
+                .any(|op| matches!(op, OrdinalOperation::InscriptionRevealed(_)));
+            if reveals_inscription {
+                inscriptions_data.insert(
+                    (tx.transaction_identifier.clone(), 0, 0),
+                    TraversalResult {
+                        inscription_number: OrdinalInscriptionNumber::zero(),
+                        inscription_input_index: 0,
+                        transaction_identifier_inscription: tx.transaction_identifier.clone(),
+                        ordinal_number: tx_index as u64 * 100_000_000,
+                        transfers: 0,
+                    },
+                );
+            }
+        }
+        let mut sequence_cursor = SequenceCursor::new(inscriptions_db_conn, jubilee_height);
+        augment_block_with_ordinals_inscriptions_data(
+            &mut block,
+            &mut sequence_cursor,
+            &mut inscriptions_data,
+            &mut HashMap::new(),
+            ctx,
+        )?;
+        score.blocks += 1;
+        score.transactions += block.transactions.len() as u64;
+        score.inscriptions += get_inscriptions_revealed_in_block(&block).len() as u64;
+        score.bytes += fixture_block.len() as u64;
+        std::hint::black_box((block, block_bytes));
+    }
+    score.duration = started_at.elapsed();
+    Ok(score)
+}
+
+fn bench_write_throughput(
+    count: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BenchScore, String> {
+    let raw_block = parse_downloaded_block(BENCH_FIXTURE_BLOCK.as_bytes().to_vec())
+        .map_err(|e| format!("unable to decode fixture block: {e}"))?;
+    let transactions = raw_block.tx.len() as u64;
+    let block_bytes = BlockBytesCursor::from_full_block(&raw_block)
+        .map_err(|e| format!("unable to compact fixture block: {e}"))?;
+    // Written next to the index, so that the disk it lives on is measured.
+    let scratch_path = config.expected_cache_path().join("bench.rocksdb");
+    let _ = std::fs::remove_dir_all(&scratch_path);
+    let opts = rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
+    let mut score = BenchScore {
+        workload: BenchWorkload::Write,
+        blocks: 0,
+        transactions: 0,
+        inscriptions: 0,
+        bytes: 0,
+        duration: Duration::ZERO,
+    };
+    {
+        let scratch_db = DB::open(&opts, &scratch_path)
+            .map_err(|e| format!("unable to open {}: {e}", scratch_path.display()))?;
+        let started_at = Instant::now();
+        for block_height in 0..count {
+            insert_entry_in_blocks(block_height as u32, &block_bytes, true, &scratch_db, ctx);
+            score.blocks += 1;
+            score.transactions += transactions;
+            score.bytes += block_bytes.len() as u64;
+        }
+        scratch_db
+            .flush()
+            .map_err(|e| format!("unable to flush {}: {e}", scratch_path.display()))?;
+        score.duration = started_at.elapsed();
+    }
+    let _ = std::fs::remove_dir_all(&scratch_path);
+    Ok(score)
+}
+
+/// Runs a workload on `count` blocks: the most recent blocks of the index for `parse`, copies of the fixture block
+/// otherwise.
+pub fn run_bench_workload(
+    workload: BenchWorkload,
+    count: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BenchScore, String> {
+    match workload {
+        BenchWorkload::Parse => bench_parse_cached_blocks(count, config, ctx),
+        BenchWorkload::Sequence => bench_sequence_fixture(count, config, ctx),
+        BenchWorkload::Write => bench_write_throughput(count, config, ctx),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::config::Config;
+
+    use super::{run_bench_workload, BenchWorkload};
+
+    #[test]
+    fn runs_fixture_workloads() {
+        assert_eq!(BenchWorkload::parse("write").unwrap(), BenchWorkload::Write);
+        assert!(BenchWorkload::parse("compact").is_err());
+
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        let working_dir = std::env::temp_dir().join("ordhook_test_bench");
+        let _ = std::fs::remove_dir_all(&working_dir);
+        std::fs::create_dir_all(&working_dir).unwrap();
+        config.storage.working_dir = working_dir.to_string_lossy().to_string();
+
+        let score = run_bench_workload(BenchWorkload::Sequence, 2, &config, &ctx).unwrap();
+        assert_eq!(score.blocks, 2);
+        assert_eq!(score.transactions, 2 * 217);
+        assert_eq!(score.inscriptions, 2 * 216);
+        assert!(!working_dir.join("bench_sqlite").exists());
+
+        let score = run_bench_workload(BenchWorkload::Write, 3, &config, &ctx).unwrap();
+        assert_eq!(score.blocks, 3);
+        assert!(score.bytes > 0);
+        assert!(!working_dir.join("bench.rocksdb").exists());
+        let _ = std::fs::remove_dir_all(&working_dir);
+    }
+}
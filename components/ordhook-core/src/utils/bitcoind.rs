@@ -1,24 +1,185 @@
-use std::{thread::sleep, time::Duration};
+use std::{fmt, str::FromStr, sync::OnceLock, thread::sleep, time::Duration};
 
 use chainhook_sdk::{
-    bitcoincore_rpc::{Auth, Client, RpcApi},
+    bitcoincore_rpc::{
+        bitcoin::{
+            consensus::encode::serialize_hex, BlockHash, OutPoint, ScriptBuf, Transaction, Txid,
+        },
+        json::{GetTxOutResult, ScanTxOutRequest},
+        jsonrpc::{
+            self, simple_http::SimpleHttpTransport, Client as JsonRpcClient, Request, Response,
+            Transport,
+        },
+        Client, RpcApi,
+    },
     utils::Context,
 };
+use reqwest::header::CONTENT_TYPE;
+use serde::de::DeserializeOwned;
+use tokio::runtime::Runtime;
 
-use crate::{config::Config, try_error, try_info};
+use crate::{
+    config::Config, error::OrdhookError, try_error, try_info,
+    utils::http::outbound_http_client_builder,
+};
+
+/// Number of calls sent in a single JSON-RPC batch, so that a batch doesn't hold bitcoind's work queue for too long.
+const BITCOIND_RPC_BATCH_SIZE: usize = 100;
+
+/// Threads of the runtime sending the calls of the proxied transports, which mostly wait on the network.
+const PROXIED_RPC_WORKER_THREADS: usize = 2;
+
+/// JSON-RPC transport going through `resources.network_proxy`, which the transport of bitcoincore-rpc can't do.
+struct ProxiedRpcTransport {
+    client: reqwest::Client,
+    timeout: Duration,
+    url: String,
+    username: String,
+    password: String,
+}
+
+/// Runtime the calls of the proxied transports are sent on. Connections are kept by the runtime that opened them: a
+/// single runtime is shared by the transports, for their clients to reuse their connections between calls.
+fn get_proxied_rpc_runtime() -> Result<&'static Runtime, String> {
+    static PROXIED_RPC_RUNTIME: OnceLock<Result<Runtime, String>> = OnceLock::new();
+    PROXIED_RPC_RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(PROXIED_RPC_WORKER_THREADS)
+                .thread_name("Proxied RPC")
+                .enable_all()
+                .build()
+                .map_err(|e| format!("unable to start proxied rpc runtime: {e}"))
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+impl ProxiedRpcTransport {
+    fn post<T: DeserializeOwned + Send + 'static>(
+        &self,
+        body: Vec<u8>,
+    ) -> Result<T, jsonrpc::Error> {
+        let runtime = get_proxied_rpc_runtime().map_err(|e| jsonrpc::Error::Transport(e.into()))?;
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        let url = self.url.clone();
+        let username = self.username.clone();
+        let password = self.password.clone();
+        // Calls can be made from within another async runtime: the caller only waits for the response.
+        let (response_tx, response_rx) = std::sync::mpsc::channel();
+        runtime.spawn(async move {
+            let result = async {
+                let res = client
+                    .post(&url)
+                    .timeout(timeout)
+                    .basic_auth(username, Some(password))
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body)
+                    .send()
+                    .await
+                    .map_err(|e| format!("unable to reach {url}: {e}"))?;
+                let status = res.status();
+                let bytes = res
+                    .bytes()
+                    .await
+                    .map_err(|e| format!("unable to read response of {url}: {e}"))?;
+                // RPC errors come with an error status, and a response describing them.
+                serde_json::from_slice::<T>(&bytes).map_err(|e| match status.is_success() {
+                    true => format!("invalid response from {url}: {e}"),
+                    false => format!("{url} responded with status {status}"),
+                })
+            }
+            .await;
+            let _ = response_tx.send(result);
+        });
+        let result = response_rx
+            .recv()
+            .unwrap_or_else(|_| Err("rpc task panicked".to_string()));
+        result.map_err(|e| jsonrpc::Error::Transport(e.into()))
+    }
+}
+
+impl Transport for ProxiedRpcTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.post(serde_json::to_vec(&request)?)
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.post(serde_json::to_vec(requests)?)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+/// RPC client of bitcoind, which calls time out after `resources.bitcoind_rpc_timeout` seconds. Calls go through
+/// `resources.network_proxy` when configured.
+pub fn bitcoind_rpc_client(config: &Config) -> Result<Client, OrdhookError> {
+    bitcoind_rpc_client_with_timeout(
+        config,
+        Duration::from_secs(config.resources.bitcoind_rpc_timeout as u64),
+    )
+}
+
+/// RPC client of bitcoind, which calls time out after `timeout`.
+pub fn bitcoind_rpc_client_with_timeout(
+    config: &Config,
+    timeout: Duration,
+) -> Result<Client, OrdhookError> {
+    if config.resources.network_proxy.is_some() {
+        let transport = ProxiedRpcTransport {
+            client: build_bitcoind_http_client(config)?,
+            timeout,
+            url: config.network.bitcoind_rpc_url.clone(),
+            username: config.network.bitcoind_rpc_username.clone(),
+            password: config.network.bitcoind_rpc_password.clone(),
+        };
+        return Ok(Client::from_jsonrpc(JsonRpcClient::with_transport(
+            transport,
+        )));
+    }
+    let transport = SimpleHttpTransport::builder()
+        .url(&config.network.bitcoind_rpc_url)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get client: {}", e)))?
+        .timeout(timeout)
+        .auth(
+            config.network.bitcoind_rpc_username.clone(),
+            Some(config.network.bitcoind_rpc_password.clone()),
+        )
+        .build();
+    Ok(Client::from_jsonrpc(JsonRpcClient::with_transport(
+        transport,
+    )))
+}
+
+/// HTTP client downloading blocks from bitcoind's RPC interface, with the timeouts of `resources`. The proxies of the
+/// environment are ignored, only `resources.network_proxy` is used.
+pub fn build_bitcoind_http_client(config: &Config) -> Result<reqwest::Client, OrdhookError> {
+    let mut builder = outbound_http_client_builder(&config.resources)
+        .map_err(OrdhookError::Config)?
+        .timeout(Duration::from_secs(
+            config.resources.bitcoind_rpc_timeout as u64,
+        ))
+        .tcp_keepalive(Some(config.resources.network_connect_timeout()))
+        .http1_only();
+    if config.resources.network_proxy.is_none() {
+        builder = builder.no_proxy();
+    }
+    builder
+        .build()
+        .map_err(|e| OrdhookError::Rpc(format!("unable to build http client: {}", e)))
+}
 
 fn bitcoind_get_client(config: &Config, ctx: &Context) -> Client {
     loop {
-        let auth = Auth::UserPass(
-            config.network.bitcoind_rpc_username.clone(),
-            config.network.bitcoind_rpc_password.clone(),
-        );
-        match Client::new(&config.network.bitcoind_rpc_url, auth) {
+        match bitcoind_rpc_client(config) {
             Ok(con) => {
                 return con;
             }
             Err(e) => {
-                try_error!(ctx, "bitcoind: Unable to get client: {}", e.to_string());
+                try_error!(ctx, "bitcoind: {}", e.to_string());
                 sleep(Duration::from_secs(1));
             }
         }
@@ -45,6 +206,15 @@ pub fn bitcoind_get_block_height(config: &Config, ctx: &Context) -> u64 {
     }
 }
 
+/// Retrieves the block height from bitcoind, without retrying on failure.
+pub fn bitcoind_try_get_block_height(config: &Config) -> Result<u64, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    bitcoin_rpc
+        .get_blockchain_info()
+        .map(|result| result.blocks)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block height: {}", e)))
+}
+
 /// Checks if bitcoind is still synchronizing blocks and waits until it's finished if that is the case.
 pub fn bitcoind_wait_for_chain_tip(config: &Config, ctx: &Context) {
     let bitcoin_rpc = bitcoind_get_client(config, ctx);
@@ -63,7 +233,10 @@ pub fn bitcoind_wait_for_chain_tip(config: &Config, ctx: &Context) {
                     try_info!(ctx, "bitcoind: Verifying chain tip");
                 } else {
                     confirmations = 0;
-                    try_info!(ctx, "bitcoind: Node has not reached chain tip, trying again");
+                    try_info!(
+                        ctx,
+                        "bitcoind: Node has not reached chain tip, trying again"
+                    );
                 }
             }
             Err(e) => {
@@ -77,3 +250,237 @@ pub fn bitcoind_wait_for_chain_tip(config: &Config, ctx: &Context) {
         sleep(Duration::from_secs(1));
     }
 }
+
+/// Height of the first block bitcoind still has, None if bitcoind is not pruned.
+pub fn bitcoind_get_prune_height(config: &Config) -> Result<Option<u64>, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let info = bitcoin_rpc
+        .get_blockchain_info()
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get blockchain info: {}", e)))?;
+    Ok(match info.pruned {
+        true => Some(info.prune_height.unwrap_or(0)),
+        false => None,
+    })
+}
+
+/// Checks that bitcoind still has the blocks from `lowest_block_height`, when `network.bitcoind_pruned` is set.
+pub fn bitcoind_check_blocks_available(
+    config: &Config,
+    lowest_block_height: u64,
+) -> Result<(), OrdhookError> {
+    if !config.network.bitcoind_pruned {
+        return Ok(());
+    }
+    match bitcoind_get_prune_height(config)? {
+        Some(prune_height) if lowest_block_height < prune_height => Err(OrdhookError::Rpc(format!(
+            "block #{lowest_block_height} was pruned by bitcoind, its first block available is #{prune_height}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the compact block filter (BIP158) of a block matches any of `scripts`. Filters have false positives, but no
+/// false negatives: a block which filter doesn't match doesn't create nor spend any output with these scripts. Requires
+/// bitcoind to run with `blockfilterindex=1`.
+pub fn bitcoind_block_filter_matches_any(
+    block_hash: &str,
+    scripts: &[ScriptBuf],
+    config: &Config,
+) -> Result<bool, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let block_hash = BlockHash::from_str(block_hash.trim_start_matches("0x"))
+        .map_err(|e| OrdhookError::Parse(format!("invalid block hash: {}", e)))?;
+    let filter = bitcoin_rpc
+        .get_block_filter(&block_hash)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block filter: {}", e)))?
+        .to_filter();
+    filter
+        .match_any(&block_hash, scripts.iter().map(|script| script.as_bytes()))
+        .map_err(|e| OrdhookError::Parse(format!("invalid block filter: {}", e)))
+}
+
+/// Retrieves the serialized header of a block and its number of confirmations, for SPV verification by API clients.
+pub fn bitcoind_get_block_header(
+    block_hash: &str,
+    config: &Config,
+) -> Result<(String, i32), OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let block_hash = BlockHash::from_str(block_hash.trim_start_matches("0x"))
+        .map_err(|e| OrdhookError::Parse(format!("invalid block hash: {}", e)))?;
+    let header = bitcoin_rpc
+        .get_block_header(&block_hash)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block header: {}", e)))?;
+    let header_info = bitcoin_rpc
+        .get_block_header_info(&block_hash)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block header: {}", e)))?;
+    Ok((serialize_hex(&header), header_info.confirmations))
+}
+
+/// Retrieves the hash of the block at the given height on bitcoind's main chain.
+pub fn bitcoind_get_block_hash(block_height: u64, config: &Config) -> Result<String, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    bitcoin_rpc
+        .get_block_hash(block_height)
+        .map(|block_hash| block_hash.to_string())
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block hash: {}", e)))
+}
+
+/// Retrieves the hash of the block at the given height on bitcoind's main chain, along with its coinbase txid.
+pub fn bitcoind_get_block_summary(
+    block_height: u64,
+    config: &Config,
+) -> Result<(String, String), OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let block_hash = bitcoin_rpc
+        .get_block_hash(block_height)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block hash: {}", e)))?;
+    let block_info = bitcoin_rpc
+        .get_block_info(&block_hash)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block: {}", e)))?;
+    let coinbase_txid =
+        block_info
+            .tx
+            .first()
+            .map(|txid| txid.to_string())
+            .ok_or(OrdhookError::Rpc(format!(
+                "block #{block_height} has no transactions"
+            )))?;
+    Ok((block_hash.to_string(), coinbase_txid))
+}
+
+/// Retrieves a transaction mined in the block at the given height. Passing the block hash lets bitcoind serve the
+/// transaction without `txindex`.
+pub fn bitcoind_get_transaction_in_block(
+    txid: &str,
+    block_height: u64,
+    config: &Config,
+) -> Result<Transaction, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let txid =
+        Txid::from_str(txid).map_err(|e| OrdhookError::Parse(format!("invalid txid: {}", e)))?;
+    let block_hash = bitcoin_rpc
+        .get_block_hash(block_height)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get block hash: {}", e)))?;
+    bitcoin_rpc
+        .get_raw_transaction(&txid, Some(&block_hash))
+        .map_err(|e| OrdhookError::Rpc(format!("unable to get transaction {}: {}", txid, e)))
+}
+
+/// Unspent output found by `bitcoind_scan_utxos`.
+pub struct ScannedUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    pub block_height: u64,
+    pub script_pubkey: ScriptBuf,
+}
+
+/// Lists the unspent outputs matching output descriptors (e.g. `addr(<address>)`, or ranged descriptors with their
+/// derivation range) in bitcoind's UTXO set. Scans take tens of seconds on mainnet, and bitcoind only runs one at a
+/// time. Returns the block height the set was scanned at.
+pub fn bitcoind_scan_utxos(
+    requests: &[ScanTxOutRequest],
+    config: &Config,
+) -> Result<(u64, Vec<ScannedUtxo>), OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let result = bitcoin_rpc
+        .scan_tx_out_set_blocking(requests)
+        .map_err(|e| OrdhookError::Rpc(format!("unable to scan utxo set: {}", e)))?;
+    let utxos = result
+        .unspents
+        .into_iter()
+        .map(|utxo| ScannedUtxo {
+            txid: utxo.txid.to_string(),
+            vout: utxo.vout,
+            value: utxo.amount.to_sat(),
+            block_height: utxo.height,
+            script_pubkey: utxo.script_pub_key,
+        })
+        .collect();
+    Ok((result.height.unwrap_or(0), utxos))
+}
+
+/// Aborts the UTXO set scan bitcoind is running. Returns false if there was none.
+pub fn bitcoind_abort_utxo_scan(config: &Config) -> Result<bool, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    bitcoin_rpc
+        .call::<bool>("scantxoutset", &["abort".into()])
+        .map_err(|e| OrdhookError::Rpc(format!("unable to abort utxo set scan: {}", e)))
+}
+
+/// Checks an output descriptor, returning its canonical form with its checksum and whether it is ranged.
+pub fn bitcoind_get_descriptor_info(
+    descriptor: &str,
+    config: &Config,
+) -> Result<(String, bool), OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let info = bitcoin_rpc
+        .get_descriptor_info(descriptor)
+        .map_err(|e| OrdhookError::Rpc(format!("invalid descriptor {}: {}", descriptor, e)))?;
+    Ok((info.descriptor, info.is_range))
+}
+
+/// Derives the addresses of a ranged output descriptor, from index `start` to index `end` (inclusive).
+pub fn bitcoind_derive_addresses(
+    descriptor: &str,
+    start: u32,
+    end: u32,
+    config: &Config,
+) -> Result<Vec<String>, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let addresses = bitcoin_rpc
+        .derive_addresses(descriptor, Some([start, end]))
+        .map_err(|e| {
+            OrdhookError::Rpc(format!(
+                "unable to derive addresses of {}: {}",
+                descriptor, e
+            ))
+        })?;
+    Ok(addresses
+        .into_iter()
+        .map(|address| address.assume_checked().to_string())
+        .collect())
+}
+
+/// Values of unspent outputs, None for the outputs spent or unknown. Outputs are looked up with batched `gettxout`
+/// calls: one round-trip per `BITCOIND_RPC_BATCH_SIZE` outputs instead of one per output.
+pub fn bitcoind_get_utxo_values(
+    outpoints: &[OutPoint],
+    config: &Config,
+) -> Result<Vec<Option<u64>>, OrdhookError> {
+    let bitcoin_rpc = bitcoind_rpc_client(config)?;
+    let jsonrpc_client = bitcoin_rpc.get_jsonrpc_client();
+    let mut values = Vec::with_capacity(outpoints.len());
+    for batch in outpoints.chunks(BITCOIND_RPC_BATCH_SIZE) {
+        let params = batch
+            .iter()
+            .map(|outpoint| {
+                vec![
+                    jsonrpc::arg(outpoint.txid),
+                    jsonrpc::arg(outpoint.vout),
+                    jsonrpc::arg(true),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let requests = params
+            .iter()
+            .map(|params| jsonrpc_client.build_request("gettxout", params))
+            .collect::<Vec<_>>();
+        let responses = jsonrpc_client
+            .send_batch(&requests)
+            .map_err(|e| OrdhookError::Rpc(format!("unable to get outputs: {}", e)))?;
+        for (outpoint, response) in batch.iter().zip(responses.into_iter()) {
+            let utxo = response
+                .ok_or(OrdhookError::Rpc(format!(
+                    "no response for output {}",
+                    outpoint
+                )))?
+                .result::<Option<GetTxOutResult>>()
+                .map_err(|e| {
+                    OrdhookError::Rpc(format!("unable to get output {}: {}", outpoint, e))
+                })?;
+            values.push(utxo.map(|utxo| utxo.value.to_sat()));
+        }
+    }
+    Ok(values)
+}
@@ -0,0 +1,394 @@
+use std::{
+    io::{Read, Write},
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::Mutex,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::{
+    types::{BitcoinBlockData, OrdinalOperation},
+    utils::Context,
+};
+use rusqlite::Connection;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    config::{content_type_matches, Config, ContentScanningConfig},
+    core::protocol::inscription_content::fetch_inscription_content,
+    db::ordinals::{
+        find_inscription_content_scan, insert_inscription_content_scan, open_ordinals_db_rw,
+        InscriptionContentScan,
+    },
+    service::query_cache::invalidate_cached_responses_of_inscription,
+    try_info, try_warn,
+};
+
+pub const CONTENT_SCAN_VERDICT_CLEAN: &str = "clean";
+pub const CONTENT_SCAN_VERDICT_FLAGGED: &str = "flagged";
+pub const CONTENT_SCAN_VERDICT_ERROR: &str = "error";
+
+/// Scan jobs waiting for the worker. Once full, indexing waits for the scanner to catch up, rather than piling up
+/// contents in memory.
+const CONTENT_SCAN_QUEUE_SIZE: usize = 256;
+
+/// Interval between two checks of a running scan command.
+const SCAN_COMMAND_POLL_INTERVAL_MS: u64 = 50;
+
+/// Inscription revealed in a block, with its content when it was kept.
+pub struct ContentScanJob {
+    pub inscription_id: String,
+    pub block_height: u64,
+    pub content_type: String,
+    pub content: Option<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref CONTENT_SCAN_JOB_TX: Mutex<Option<crossbeam_channel::Sender<ContentScanJob>>> =
+        Mutex::new(None);
+}
+
+pub fn should_scan_content_type(
+    scanning_config: &ContentScanningConfig,
+    content_type: &str,
+) -> bool {
+    scanning_config.content_types.is_empty()
+        || scanning_config
+            .content_types
+            .iter()
+            .any(|pattern| content_type_matches(pattern, content_type))
+}
+
+/// Reads the `{"flagged": bool, "labels": [..]}` answer of a classifier.
+pub fn parse_classifier_response(response: &JsonValue) -> Result<(bool, Vec<String>), String> {
+    let flagged = response
+        .get("flagged")
+        .and_then(|f| f.as_bool())
+        .ok_or("classifier response is missing a boolean flagged field".to_string())?;
+    let labels = response
+        .get("labels")
+        .and_then(|l| l.as_array())
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label.as_str().map(|l| l.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((flagged, labels))
+}
+
+fn scan_with_classifier(
+    url: &str,
+    job: &ContentScanJob,
+    content: &[u8],
+    timeout: Duration,
+) -> Result<(bool, Vec<String>), String> {
+    let url = url.to_string();
+    let inscription_id = job.inscription_id.clone();
+    let content_type = job.content_type.clone();
+    let body = content.to_vec();
+    let response = hiro_system_kit::nestable_block_on(async move {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("unable to build http client: {}", e))?;
+        let res = client
+            .post(&url)
+            .header("Content-Type", content_type)
+            .header("X-Ordhook-Inscription-Id", inscription_id)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("unable to reach {}: {}", url, e))?;
+        if !res.status().is_success() {
+            return Err(format!("{} responded with status {}", url, res.status()));
+        }
+        res.json::<JsonValue>()
+            .await
+            .map_err(|e| format!("invalid response from {}: {}", url, e))
+    })?;
+    parse_classifier_response(&response)
+}
+
+/// Runs the scan command on a content. Commands running longer than `timeout` are killed, along with the processes
+/// they started.
+fn scan_with_command(
+    command: &str,
+    job: &ContentScanJob,
+    content: &[u8],
+    timeout: Duration,
+) -> Result<(bool, Vec<String>), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ORDHOOK_INSCRIPTION_ID", &job.inscription_id)
+        .env("ORDHOOK_CONTENT_TYPE", &job.content_type)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        // Own process group, for the processes started by `sh` to be killed with it.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("unable to run scan command: {e}"))?;
+    // Feed the content and read the output from other threads, the command can start writing its output before
+    // reading it all.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or("unable to open scan command stdin")?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or("unable to open scan command stdout")?;
+    let moved_content = content.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&moved_content));
+    let reader = std::thread::spawn(move || {
+        let mut output = vec![];
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let started_at = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started_at.elapsed() < timeout => {
+                sleep(Duration::from_millis(SCAN_COMMAND_POLL_INTERVAL_MS))
+            }
+            Ok(None) => {
+                unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                let _ = child.wait();
+                let _ = writer.join();
+                let _ = reader.join();
+                return Err(format!(
+                    "scan command timed out after {}s",
+                    timeout.as_secs()
+                ));
+            }
+            Err(e) => return Err(format!("unable to wait for scan command: {e}")),
+        }
+    };
+    let _ = writer.join();
+    let output = reader
+        .join()
+        .map_err(|_| "scan command output reader panicked".to_string())?
+        .map_err(|e| format!("unable to read scan command output: {e}"))?;
+    let labels = String::from_utf8_lossy(&output)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    match status.code() {
+        Some(0) => Ok((false, labels)),
+        Some(1) => Ok((true, labels)),
+        _ => Err(format!("scan command exited with {}", status)),
+    }
+}
+
+/// Runs the configured classifier and / or command on a content. The content is flagged if any of them flags it.
+fn scan_content(
+    job: &ContentScanJob,
+    content: &[u8],
+    scanning_config: &ContentScanningConfig,
+) -> Result<(bool, Vec<String>), String> {
+    let mut flagged = false;
+    let mut labels = vec![];
+    if let Some(ref url) = scanning_config.classifier_url {
+        let (classifier_flagged, classifier_labels) = scan_with_classifier(
+            url,
+            job,
+            content,
+            Duration::from_secs(scanning_config.timeout_secs),
+        )?;
+        flagged |= classifier_flagged;
+        labels.extend(classifier_labels);
+    }
+    if let Some(ref command) = scanning_config.command {
+        let (command_flagged, command_labels) = scan_with_command(
+            command,
+            job,
+            content,
+            Duration::from_secs(scanning_config.timeout_secs),
+        )?;
+        flagged |= command_flagged;
+        labels.extend(command_labels);
+    }
+    Ok((flagged, labels))
+}
+
+fn process_content_scan_job(
+    job: ContentScanJob,
+    scanning_config: &ContentScanningConfig,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) {
+    if !should_scan_content_type(scanning_config, &job.content_type) {
+        return;
+    }
+    let result = match job.content {
+        Some(ref content) => Ok(content.clone()),
+        None => fetch_inscription_content(&job.inscription_id, db_conn, config, ctx)
+            .map(|content| content.body.to_vec()),
+    }
+    .and_then(|content| {
+        if content.len() as u64 > scanning_config.max_content_bytes {
+            return Err(format!(
+                "content exceeds {} bytes",
+                scanning_config.max_content_bytes
+            ));
+        }
+        scan_content(&job, &content, scanning_config)
+    });
+    let scanned_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let scan = match result {
+        Ok((flagged, labels)) => {
+            if flagged {
+                try_info!(
+                    ctx,
+                    "Content of {} flagged by the content scanner ({})",
+                    job.inscription_id,
+                    labels.join(", ")
+                );
+            }
+            InscriptionContentScan {
+                verdict: match flagged {
+                    true => CONTENT_SCAN_VERDICT_FLAGGED,
+                    false => CONTENT_SCAN_VERDICT_CLEAN,
+                }
+                .into(),
+                labels,
+                scanned_at,
+            }
+        }
+        Err(e) => {
+            try_warn!(ctx, "Unable to scan content of {}: {e}", job.inscription_id);
+            InscriptionContentScan {
+                verdict: CONTENT_SCAN_VERDICT_ERROR.into(),
+                labels: vec![],
+                scanned_at,
+            }
+        }
+    };
+    insert_inscription_content_scan(&job.inscription_id, job.block_height, &scan, db_conn, ctx);
+    invalidate_cached_responses_of_inscription(&job.inscription_id);
+}
+
+/// Starts the background worker scanning the contents of the inscriptions indexed, if enabled.
+pub fn start_content_scanning_worker(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Some(ref scanning_config) = config.content_scanning else {
+        return Ok(());
+    };
+    let Ok(mut job_tx) = CONTENT_SCAN_JOB_TX.lock() else {
+        return Ok(());
+    };
+    if job_tx.is_some() {
+        return Ok(());
+    }
+    let db_conn = open_ordinals_db_rw(&config.expected_sqlite_path(), ctx)?;
+    let (tx, rx) = crossbeam_channel::bounded::<ContentScanJob>(CONTENT_SCAN_QUEUE_SIZE);
+    let moved_scanning_config = scanning_config.clone();
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    hiro_system_kit::thread_named("Content scanning worker")
+        .spawn(move || {
+            while let Ok(job) = rx.recv() {
+                process_content_scan_job(
+                    job,
+                    &moved_scanning_config,
+                    &db_conn,
+                    &moved_config,
+                    &moved_ctx,
+                );
+            }
+        })
+        .map_err(|e| format!("unable to start content scanning worker: {e}"))?;
+    *job_tx = Some(tx);
+    try_info!(ctx, "Content scanning worker started");
+    Ok(())
+}
+
+/// Queues the inscriptions revealed in a block for content scanning. Inscriptions which content was not kept get
+/// their content read from bitcoind by the worker. Waits for room in the queue when the worker is behind.
+pub fn enqueue_block_content_scans(block: &BitcoinBlockData) {
+    let Some(job_tx) = CONTENT_SCAN_JOB_TX
+        .lock()
+        .ok()
+        .and_then(|job_tx| job_tx.clone())
+    else {
+        return;
+    };
+    for tx in block.transactions.iter() {
+        for operation in tx.metadata.ordinal_operations.iter() {
+            let OrdinalOperation::InscriptionRevealed(reveal) = operation else {
+                continue;
+            };
+            let _ = job_tx.send(ContentScanJob {
+                inscription_id: reveal.inscription_id.clone(),
+                block_height: block.block_identifier.index,
+                content_type: reveal.content_type.clone(),
+                content: reveal
+                    .content_bytes
+                    .strip_prefix("0x")
+                    .and_then(|hex_content| hex::decode(hex_content).ok()),
+            });
+        }
+    }
+}
+
+/// Checks that the content of an inscription can be served according to its content scan verdict. Contents which type
+/// is not known are assumed to be scanned.
+pub fn check_content_scan(
+    inscription_id: &str,
+    content_type: Option<&str>,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let Some(ref scanning_config) = config.content_scanning else {
+        return Ok(());
+    };
+    if let Some(content_type) = content_type {
+        if !should_scan_content_type(scanning_config, content_type) {
+            return Ok(());
+        }
+    }
+    match find_inscription_content_scan(inscription_id, db_conn, ctx) {
+        Some(scan) if scan.verdict == CONTENT_SCAN_VERDICT_FLAGGED => {
+            if scanning_config.block_flagged {
+                return Err(format!(
+                    "content of {inscription_id} was flagged by the content scanner"
+                ));
+            }
+        }
+        Some(scan) if scan.verdict == CONTENT_SCAN_VERDICT_CLEAN => {}
+        _ => {
+            if scanning_config.block_unscanned {
+                return Err(format!("content of {inscription_id} was not scanned yet"));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::parse_classifier_response;
+
+    #[test]
+    fn parses_classifier_responses() {
+        assert_eq!(
+            parse_classifier_response(&json!({"flagged": true, "labels": ["nsfw", 3]})),
+            Ok((true, vec!["nsfw".to_string()]))
+        );
+        assert_eq!(
+            parse_classifier_response(&json!({"flagged": false})),
+            Ok((false, vec![]))
+        );
+        assert!(parse_classifier_response(&json!({"labels": []})).is_err());
+    }
+}
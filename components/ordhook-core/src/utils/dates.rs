@@ -0,0 +1,53 @@
+/// Parses a `YYYY-MM-DD` date or a unix timestamp into a unix timestamp. Dates are UTC days, resolved to their first
+/// second, or to their last one with `end_of_day` so that ranges include the whole day.
+pub fn parse_date_to_timestamp(date: &str, end_of_day: bool) -> Result<u64, String> {
+    if let Ok(timestamp) = date.parse::<u64>() {
+        return Ok(timestamp);
+    }
+    let invalid = || format!("{date} is not a YYYY-MM-DD date or a unix timestamp");
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (
+        year.parse::<i64>(),
+        month.parse::<i64>(),
+        day.parse::<i64>(),
+    ) else {
+        return Err(invalid());
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    // Days since the unix epoch in the proleptic Gregorian calendar, years starting in March.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let timestamp = days as u64 * 86400;
+    Ok(match end_of_day {
+        true => timestamp + 86399,
+        false => timestamp,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_date_to_timestamp;
+
+    #[test]
+    fn parses_dates_to_timestamps() {
+        assert_eq!(parse_date_to_timestamp("1970-01-01", false), Ok(0));
+        assert_eq!(parse_date_to_timestamp("2024-04-20", false), Ok(1713571200));
+        assert_eq!(parse_date_to_timestamp("2024-04-20", true), Ok(1713657599));
+        assert_eq!(parse_date_to_timestamp("2024-03-01", false), Ok(1709251200));
+        assert_eq!(parse_date_to_timestamp("1713571767", true), Ok(1713571767));
+        assert!(parse_date_to_timestamp("2024-13-01", false).is_err());
+        assert!(parse_date_to_timestamp("20/04/2024", false).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! User-supplied WebAssembly modules transforming the transactions delivered to predicates.
+//!
+//! Host ABI (version 1), modules must not import anything and must export:
+//! - `memory`: the module's linear memory
+//! - `ordhook_abi_version() -> i32`: returning `1`
+//! - `ordhook_alloc(len: i32) -> i32`: allocating `len` bytes for the host to write its input into
+//! - `ordhook_transform(ptr: i32, len: i32) -> i64`: receiving a JSON `{"block_identifier": .., "transaction": ..}`
+//!   document and returning `(out_ptr << 32) | out_len`, the location of the transformed transaction JSON. Returning
+//!   `0` drops the transaction. The output must lie within the module's memory.
+//!
+//! Each call runs in a fresh instance, bounded by the fuel and memory limits configured for the module.
+//!
+//! Transforms apply to scans and live deliveries alike. Live, the Chainhook observer picks transactions before they
+//! get transformed: transforms can drop or rewrite them, but not make other transactions match.
+
+use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
+
+use crate::config::Config;
+#[cfg(feature = "wasm-plugins")]
+use crate::{try_info, try_warn};
+
+pub const EVENT_TRANSFORM_ABI_VERSION: i32 = 1;
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime {
+    use std::sync::RwLock;
+
+    use chainhook_sdk::types::{BitcoinTransactionData, BlockIdentifier};
+    use serde_json::json;
+    use wasmtime::{Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+    use crate::config::EventTransformConfig;
+
+    use super::EVENT_TRANSFORM_ABI_VERSION;
+
+    lazy_static! {
+        pub static ref EVENT_TRANSFORMS: RwLock<Vec<EventTransform>> = RwLock::new(vec![]);
+    }
+
+    pub struct EventTransform {
+        pub path: String,
+        engine: Engine,
+        module: Module,
+        max_fuel: u64,
+        max_memory_bytes: usize,
+    }
+
+    impl EventTransform {
+        pub fn load(config: &EventTransformConfig) -> Result<EventTransform, String> {
+            let mut engine_config = wasmtime::Config::new();
+            engine_config.consume_fuel(true);
+            let engine = Engine::new(&engine_config)
+                .map_err(|e| format!("unable to create wasm engine: {e}"))?;
+            let module = Module::from_file(&engine, &config.path)
+                .map_err(|e| format!("unable to load {}: {e}", config.path))?;
+            if module.imports().next().is_some() {
+                return Err(format!(
+                    "{}: event transform modules must not import anything",
+                    config.path
+                ));
+            }
+            let transform = EventTransform {
+                path: config.path.clone(),
+                engine,
+                module,
+                max_fuel: config.max_fuel,
+                max_memory_bytes: config.max_memory_bytes as usize,
+            };
+            let (mut store, instance) = transform.instantiate()?;
+            let abi_version = instance
+                .get_typed_func::<(), i32>(&mut store, "ordhook_abi_version")
+                .and_then(|f| f.call(&mut store, ()))
+                .map_err(|e| format!("{}: unable to read ABI version: {e}", config.path))?;
+            if abi_version != EVENT_TRANSFORM_ABI_VERSION {
+                return Err(format!(
+                    "{}: unsupported ABI version {abi_version}, expected {EVENT_TRANSFORM_ABI_VERSION}",
+                    config.path
+                ));
+            }
+            Ok(transform)
+        }
+
+        fn instantiate(&self) -> Result<(Store<StoreLimits>, Instance), String> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.max_memory_bytes)
+                .build();
+            let mut store = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store
+                .set_fuel(self.max_fuel)
+                .map_err(|e| format!("unable to set fuel: {e}"))?;
+            let instance = Linker::new(&self.engine)
+                .instantiate(&mut store, &self.module)
+                .map_err(|e| format!("unable to instantiate {}: {e}", self.path))?;
+            Ok((store, instance))
+        }
+
+        pub fn apply(
+            &self,
+            tx: &BitcoinTransactionData,
+            block_identifier: &BlockIdentifier,
+        ) -> Result<Option<BitcoinTransactionData>, String> {
+            let (mut store, instance) = self.instantiate()?;
+            let input = json!({
+                "block_identifier": block_identifier,
+                "transaction": tx,
+            })
+            .to_string();
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or(format!("{}: missing memory export", self.path))?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut store, "ordhook_alloc")
+                .map_err(|e| format!("{}: {e}", self.path))?;
+            let transform = instance
+                .get_typed_func::<(i32, i32), i64>(&mut store, "ordhook_transform")
+                .map_err(|e| format!("{}: {e}", self.path))?;
+
+            let input_ptr = alloc
+                .call(&mut store, input.len() as i32)
+                .map_err(|e| format!("{}: ordhook_alloc failed: {e}", self.path))?;
+            memory
+                .write(&mut store, input_ptr as usize, input.as_bytes())
+                .map_err(|e| format!("{}: {e}", self.path))?;
+            let output = transform
+                .call(&mut store, (input_ptr, input.len() as i32))
+                .map_err(|e| format!("{}: ordhook_transform failed: {e}", self.path))?;
+            if output == 0 {
+                return Ok(None);
+            }
+            let output_ptr = (output as u64 >> 32) as usize;
+            let output_len = (output as u64 & 0xffff_ffff) as usize;
+            // Checked before allocating, the length being chosen by the module.
+            if output_ptr.saturating_add(output_len) > memory.data_size(&store) {
+                return Err(format!(
+                    "{}: output of {output_len} bytes at {output_ptr} is out of memory bounds",
+                    self.path
+                ));
+            }
+            let mut output_bytes = vec![0u8; output_len];
+            memory
+                .read(&store, output_ptr, &mut output_bytes)
+                .map_err(|e| format!("{}: {e}", self.path))?;
+            serde_json::from_slice(&output_bytes)
+                .map(Some)
+                .map_err(|e| format!("{}: invalid transaction returned: {e}", self.path))
+        }
+    }
+}
+
+/// Compiles the event transform modules configured. Only performed once per process.
+pub fn load_event_transforms(config: &Config, ctx: &Context) -> Result<(), String> {
+    if config.event_transforms.is_empty() {
+        return Ok(());
+    }
+    #[cfg(feature = "wasm-plugins")]
+    {
+        let mut transforms = runtime::EVENT_TRANSFORMS
+            .write()
+            .map_err(|e| format!("unable to load event transforms: {e}"))?;
+        if !transforms.is_empty() {
+            return Ok(());
+        }
+        for transform_config in config.event_transforms.iter() {
+            transforms.push(runtime::EventTransform::load(transform_config)?);
+            try_info!(ctx, "Event transform {} loaded", transform_config.path);
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "wasm-plugins"))]
+    {
+        let _ = ctx;
+        Err("event transforms require ordhook to be built with the wasm-plugins feature".into())
+    }
+}
+
+/// Whether event transforms are loaded, the predicates then being evaluated against transformed blocks.
+pub fn has_event_transforms() -> bool {
+    #[cfg(feature = "wasm-plugins")]
+    {
+        runtime::EVENT_TRANSFORMS
+            .read()
+            .map_or(false, |transforms| !transforms.is_empty())
+    }
+    #[cfg(not(feature = "wasm-plugins"))]
+    {
+        false
+    }
+}
+
+/// Runs the transactions of a block through the loaded event transforms, in order, before they get evaluated
+/// against predicates. Transactions failing a transform are delivered untransformed.
+pub fn apply_event_transforms(block: &mut BitcoinBlockData, ctx: &Context) {
+    #[cfg(feature = "wasm-plugins")]
+    {
+        let Ok(transforms) = runtime::EVENT_TRANSFORMS.read() else {
+            return;
+        };
+        if transforms.is_empty() {
+            return;
+        }
+        let block_identifier = block.block_identifier.clone();
+        let mut transactions = vec![];
+        'transactions: for tx in block.transactions.drain(..) {
+            let mut tx = tx;
+            for transform in transforms.iter() {
+                match transform.apply(&tx, &block_identifier) {
+                    Ok(Some(transformed_tx)) => tx = transformed_tx,
+                    Ok(None) => continue 'transactions,
+                    Err(e) => {
+                        try_warn!(ctx, "Event transform failed: {e}");
+                    }
+                }
+            }
+            transactions.push(tx);
+        }
+        block.transactions = transactions;
+    }
+    #[cfg(not(feature = "wasm-plugins"))]
+    {
+        let _ = (block, ctx);
+    }
+}
+
+#[cfg(all(test, feature = "wasm-plugins"))]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::{
+        config::EventTransformConfig,
+        core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::runtime::EventTransform;
+
+    fn load_transform(name: &str, transform_body: &str) -> EventTransform {
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "ordhook-event-transform-{name}-{}.wat",
+            std::process::id()
+        ));
+        let module = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "ordhook_abi_version") (result i32) (i32.const 1))
+                (func (export "ordhook_alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "ordhook_transform") (param i32 i32) (result i64) {transform_body}))"#
+        );
+        std::fs::write(&path, module).unwrap();
+        let transform = EventTransform::load(&EventTransformConfig {
+            path: path.to_string_lossy().to_string(),
+            max_fuel: 1_000_000,
+            max_memory_bytes: 1024 * 1024,
+        });
+        let _ = std::fs::remove_file(&path);
+        transform.unwrap()
+    }
+
+    #[test]
+    fn applies_transforms() {
+        let block = TestBlockBuilder::new()
+            .add_transaction(TestTransactionBuilder::new().build())
+            .build();
+        let tx = &block.transactions[0];
+
+        let identity = load_transform(
+            "identity",
+            "(i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1)))",
+        );
+        let transformed_tx = identity.apply(tx, &block.block_identifier).unwrap();
+        assert_eq!(
+            transformed_tx.map(|tx| tx.transaction_identifier),
+            Some(tx.transaction_identifier.clone())
+        );
+
+        let drop = load_transform("drop", "(i64.const 0)");
+        assert!(drop.apply(tx, &block.block_identifier).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_outputs_out_of_memory_bounds() {
+        let block = TestBlockBuilder::new()
+            .add_transaction(TestTransactionBuilder::new().build())
+            .build();
+        let oversized = load_transform("oversized", "(i64.const 0xffffffff)");
+        assert!(oversized
+            .apply(&block.transactions[0], &block.block_identifier)
+            .is_err());
+    }
+}
@@ -1,3 +1,10 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use hiro_system_kit::slog::{self, Drain, Level, Logger, Never, OwnedKVList, Record};
+
 #[macro_export]
 macro_rules! try_info {
     ($a:expr, $tag:expr, $($args:tt)*) => {
@@ -37,3 +44,222 @@ macro_rules! try_error {
         $a.try_log(|l| error!(l, $tag));
     };
 }
+
+/// Parts of the node which verbosity can be adjusted separately, matched on the module path of log records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogSubsystem {
+    Pipeline,
+    Protocol,
+    Db,
+    Service,
+    Scan,
+    Chainhook,
+    /// Records of every other module.
+    Default,
+}
+
+const LOG_SUBSYSTEMS: [LogSubsystem; 7] = [
+    LogSubsystem::Pipeline,
+    LogSubsystem::Protocol,
+    LogSubsystem::Db,
+    LogSubsystem::Service,
+    LogSubsystem::Scan,
+    LogSubsystem::Chainhook,
+    LogSubsystem::Default,
+];
+
+impl LogSubsystem {
+    pub fn parse(subsystem: &str) -> Result<LogSubsystem, String> {
+        LOG_SUBSYSTEMS
+            .iter()
+            .find(|s| s.code() == subsystem)
+            .copied()
+            .ok_or(format!(
+                "unknown subsystem {subsystem}, expected pipeline, protocol, db, service, scan, chainhook or default"
+            ))
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            LogSubsystem::Pipeline => "pipeline",
+            LogSubsystem::Protocol => "protocol",
+            LogSubsystem::Db => "db",
+            LogSubsystem::Service => "service",
+            LogSubsystem::Scan => "scan",
+            LogSubsystem::Chainhook => "chainhook",
+            LogSubsystem::Default => "default",
+        }
+    }
+
+    /// `core::pipeline` is checked before the rest of `core`, which holds the protocol.
+    fn from_module(module: &str) -> LogSubsystem {
+        if module.starts_with("ordhook::core::pipeline") {
+            LogSubsystem::Pipeline
+        } else if module.starts_with("ordhook::core") {
+            LogSubsystem::Protocol
+        } else if module.starts_with("ordhook::db") {
+            LogSubsystem::Db
+        } else if module.starts_with("ordhook::service") {
+            LogSubsystem::Service
+        } else if module.starts_with("ordhook::scan") {
+            LogSubsystem::Scan
+        } else if module.starts_with("chainhook_sdk") {
+            LogSubsystem::Chainhook
+        } else {
+            LogSubsystem::Default
+        }
+    }
+
+    fn index(&self) -> usize {
+        LOG_SUBSYSTEMS.iter().position(|s| s == self).unwrap_or(0)
+    }
+}
+
+pub fn parse_log_level(level: &str) -> Result<Level, String> {
+    match level {
+        "error" => Ok(Level::Error),
+        "warn" => Ok(Level::Warning),
+        "info" => Ok(Level::Info),
+        "debug" => Ok(Level::Debug),
+        "trace" => Ok(Level::Trace),
+        _ => Err(format!(
+            "unknown level {level}, expected error, warn, info, debug or trace"
+        )),
+    }
+}
+
+pub fn log_level_code(level: Level) -> &'static str {
+    match level {
+        Level::Critical | Level::Error => "error",
+        Level::Warning => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+/// Most verbose level compiled in the build, set by slog's `max_level_*` and `release_max_level_*` features: records
+/// of more verbose levels are removed at compile time, and no log level can bring them back.
+pub fn get_compiled_max_log_level() -> Level {
+    Level::from_usize(slog::__slog_static_max_level().as_usize()).unwrap_or(Level::Critical)
+}
+
+/// Most verbose level logged by each subsystem, as `Level::as_usize`, shared by the logger of the node and the API
+/// adjusting it. Every subsystem starts at the most verbose level compiled in the build.
+#[derive(Debug, Clone)]
+pub struct LogLevels(Arc<[AtomicUsize; 7]>);
+
+impl LogLevels {
+    pub fn new() -> LogLevels {
+        let level = get_compiled_max_log_level().as_usize();
+        LogLevels(Arc::new(std::array::from_fn(|_| AtomicUsize::new(level))))
+    }
+
+    /// Changes the verbosity of `subsystem`, or of every subsystem, on a running node. Levels more verbose than the
+    /// one compiled in the build are rejected.
+    pub fn set(&self, subsystem: Option<LogSubsystem>, level: Level) -> Result<(), String> {
+        let max_level = get_compiled_max_log_level();
+        if level.as_usize() > max_level.as_usize() {
+            return Err(format!(
+                "level {} is compiled out of this build, the most verbose level available is {}",
+                log_level_code(level),
+                log_level_code(max_level)
+            ));
+        }
+        for s in LOG_SUBSYSTEMS.iter() {
+            if subsystem.is_none() || subsystem == Some(*s) {
+                self.0[s.index()].store(level.as_usize(), Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, subsystem: LogSubsystem) -> usize {
+        self.0[subsystem.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut levels = serde_json::Map::new();
+        for s in LOG_SUBSYSTEMS.iter() {
+            let level = Level::from_usize(self.get(*s)).unwrap_or(Level::Info);
+            levels.insert(s.code().into(), log_level_code(level).into());
+        }
+        serde_json::Value::Object(levels)
+    }
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        LogLevels::new()
+    }
+}
+
+struct RuntimeLevelFilter {
+    logger: Logger,
+    levels: LogLevels,
+}
+
+impl Drain for RuntimeLevelFilter {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Never> {
+        let subsystem = LogSubsystem::from_module(record.module());
+        if record.level().as_usize() <= self.levels.get(subsystem) {
+            self.logger.log(record, values)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Wraps `logger` so that the verbosity of each subsystem can be adjusted through `levels`. Records filtered out by
+/// `logger` itself, or compiled out by slog's `max_level_*` features, can't be brought back.
+pub fn with_runtime_log_levels(logger: Logger, levels: &LogLevels) -> Logger {
+    Logger::root(
+        RuntimeLevelFilter {
+            logger,
+            levels: levels.clone(),
+        },
+        slog::o!(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use hiro_system_kit::slog::Level;
+
+    use super::{get_compiled_max_log_level, log_level_code, LogLevels, LogSubsystem};
+
+    #[test]
+    fn adjusts_subsystem_log_levels() {
+        assert_eq!(
+            LogSubsystem::from_module("ordhook::core::pipeline::processors"),
+            LogSubsystem::Pipeline
+        );
+        assert_eq!(
+            LogSubsystem::from_module("ordhook::core::protocol::inscription_sequencing"),
+            LogSubsystem::Protocol
+        );
+        assert_eq!(
+            LogSubsystem::from_module("chainhook_sdk::observer"),
+            LogSubsystem::Chainhook
+        );
+        assert_eq!(
+            LogSubsystem::from_module("ordhook_cli::cli"),
+            LogSubsystem::Default
+        );
+        assert!(LogSubsystem::parse("indexer").is_err());
+
+        let levels = LogLevels::new();
+        let max_level = get_compiled_max_log_level();
+        levels.set(None, Level::Error).unwrap();
+        levels.set(Some(LogSubsystem::Pipeline), max_level).unwrap();
+        let json = levels.to_json();
+        assert_eq!(json["pipeline"], log_level_code(max_level));
+        assert_eq!(json["db"], "error");
+        if max_level != Level::Trace {
+            assert!(levels.set(None, Level::Trace).is_err());
+        }
+    }
+}
@@ -1,11 +1,21 @@
+pub mod bench;
 pub mod bitcoind;
+pub mod content_scanning;
+pub mod dates;
+pub mod event_transforms;
+pub mod http;
 pub mod logger;
 pub mod monitoring;
+pub mod pagination;
+pub mod previews;
+pub mod profiler;
+pub mod unix_socket;
 
 use std::{
     fs,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use chainhook_sdk::types::TransactionIdentifier;
@@ -42,6 +52,32 @@ pub fn write_file_content_at_path(file_path: &PathBuf, content: &[u8]) -> Result
     Ok(())
 }
 
+static TMP_PATH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Temporary sibling of `path` to write before renaming it to `path`. Unique per process and call, so that concurrent
+/// writers of the same path don't write to the same temporary file.
+pub fn get_unique_tmp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{file_name}.{}.{}.tmp",
+        std::process::id(),
+        TMP_PATH_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Syncs the directory of `path`, for a rename or creation of `path` to survive a crash.
+pub fn sync_parent_dir(path: &Path) -> Result<(), String> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    fs::File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|e| format!("unable to sync {}: {e}", parent.display()))
+}
+
 pub fn format_inscription_id(
     transaction_identifier: &TransactionIdentifier,
     inscription_subindex: usize,
@@ -1,23 +1,159 @@
-use chainhook_sdk::utils::Context;
+use std::{net::SocketAddr, task::Poll};
+
+use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
 use hyper::{
     header::CONTENT_TYPE,
+    server::accept,
     service::{make_service_fn, service_fn},
     Body, Method, Request, Response, Server,
 };
 use prometheus::{
-    core::{AtomicU64, GenericGauge},
+    core::{AtomicU64, GenericCounter, GenericGauge},
     Encoder, Registry, TextEncoder,
 };
 
-use crate::{try_debug, try_info, try_warn};
+use crate::{
+    config::UnixSocketConfig, try_debug, try_info, try_warn, utils::unix_socket::bind_unix_socket,
+};
 
 type UInt64Gauge = GenericGauge<AtomicU64>;
+type UInt64Counter = GenericCounter<AtomicU64>;
+
+lazy_static! {
+    /// The block pipeline is started without the service's `PrometheusMonitoring`: its metrics are shared by every
+    /// instance, and registered by each of them.
+    pub static ref PIPELINE_METRICS: PipelineMetrics = PipelineMetrics::new();
+}
+
+/// Depth of each queue of the block processing pipeline, and number of blocks through each of its stages, to locate
+/// where backpressure originates. Blocks move from one queue to the next: downloaded, parsed, written, delivered.
+#[derive(Debug, Clone)]
+pub struct PipelineMetrics {
+    pub downloaded_blocks_queue: UInt64Gauge,
+    pub parsed_blocks_queue: UInt64Gauge,
+    pub pending_db_writes: UInt64Gauge,
+    pub pending_deliveries: UInt64Gauge,
+    pub blocks_downloaded: UInt64Counter,
+    pub blocks_parsed: UInt64Counter,
+    pub blocks_written: UInt64Counter,
+    pub blocks_delivered: UInt64Counter,
+}
+
+impl PipelineMetrics {
+    fn new() -> PipelineMetrics {
+        let gauge = |name: &str, help: &str| UInt64Gauge::new(name, help).unwrap();
+        let counter = |name: &str, help: &str| UInt64Counter::new(name, help).unwrap();
+        PipelineMetrics {
+            downloaded_blocks_queue: gauge(
+                "pipeline_downloaded_blocks_queue",
+                "The number of blocks downloaded from bitcoind, waiting to be parsed.",
+            ),
+            parsed_blocks_queue: gauge(
+                "pipeline_parsed_blocks_queue",
+                "The number of blocks parsed, waiting for the blocks preceding them to be parsed.",
+            ),
+            pending_db_writes: gauge(
+                "pipeline_pending_db_writes",
+                "The number of blocks sequenced, waiting to be indexed and written to the databases.",
+            ),
+            pending_deliveries: gauge(
+                "pipeline_pending_deliveries",
+                "The number of blocks indexed, waiting to be evaluated against predicates.",
+            ),
+            blocks_downloaded: counter(
+                "pipeline_blocks_downloaded_total",
+                "The number of blocks downloaded from bitcoind since startup.",
+            ),
+            blocks_parsed: counter(
+                "pipeline_blocks_parsed_total",
+                "The number of blocks parsed since startup.",
+            ),
+            blocks_written: counter(
+                "pipeline_blocks_written_total",
+                "The number of blocks written to the databases since startup.",
+            ),
+            blocks_delivered: counter(
+                "pipeline_blocks_delivered_total",
+                "The number of blocks evaluated against predicates since startup.",
+            ),
+        }
+    }
+
+    fn register(&self, registry: &Registry) {
+        for gauge in [
+            &self.downloaded_blocks_queue,
+            &self.parsed_blocks_queue,
+            &self.pending_db_writes,
+            &self.pending_deliveries,
+        ] {
+            let _ = registry.register(Box::new(gauge.clone()));
+        }
+        for counter in [
+            &self.blocks_downloaded,
+            &self.blocks_parsed,
+            &self.blocks_written,
+            &self.blocks_delivered,
+        ] {
+            let _ = registry.register(Box::new(counter.clone()));
+        }
+    }
+
+    pub fn metrics_block_downloaded(&self) {
+        self.blocks_downloaded.inc();
+        self.downloaded_blocks_queue.inc();
+    }
+
+    pub fn metrics_block_parsed(&self) {
+        dequeue(&self.downloaded_blocks_queue, 1);
+        self.blocks_parsed.inc();
+        self.parsed_blocks_queue.inc();
+    }
+
+    pub fn metrics_blocks_sequenced(&self, count: u64) {
+        dequeue(&self.parsed_blocks_queue, count);
+        self.pending_db_writes.add(count);
+    }
+
+    pub fn metrics_blocks_written(&self, count: u64) {
+        dequeue(&self.pending_db_writes, count);
+        self.blocks_written.inc_by(count);
+    }
+
+    /// Sends a block to the predicate evaluation thread, which calls `metrics_block_delivered` once it evaluated it.
+    /// Blocks the thread no longer receives are not counted as pending.
+    pub fn queue_block_for_delivery(
+        &self,
+        block: BitcoinBlockData,
+        post_processor_tx: &crossbeam_channel::Sender<BitcoinBlockData>,
+    ) {
+        // Counted before being sent, for the evaluation not to be recorded before the block was queued.
+        self.pending_deliveries.inc();
+        if post_processor_tx.send(block).is_err() {
+            dequeue(&self.pending_deliveries, 1);
+        }
+    }
+
+    pub fn metrics_block_delivered(&self) {
+        dequeue(&self.pending_deliveries, 1);
+        self.blocks_delivered.inc();
+    }
+}
+
+/// Queues also see blocks which didn't go through the previous stages, like blocks replayed from storage: depths
+/// are kept from wrapping around.
+fn dequeue(queue: &UInt64Gauge, count: u64) {
+    queue.set(queue.get().saturating_sub(count));
+}
 
 #[derive(Debug, Clone)]
 pub struct PrometheusMonitoring {
     pub last_indexed_block_height: UInt64Gauge,
     pub last_indexed_inscription_number: UInt64Gauge,
     pub registered_predicates: UInt64Gauge,
+    pub quarantined_blocks: UInt64Gauge,
+    pub query_cache_hits: UInt64Counter,
+    pub query_cache_misses: UInt64Counter,
+    pub rejected_ingestion_payloads: UInt64Gauge,
     pub registry: Registry,
 }
 
@@ -40,10 +176,35 @@ impl PrometheusMonitoring {
             "registered_predicates",
             "The current number of predicates registered to receive ordinal events.",
         );
+        let quarantined_blocks = PrometheusMonitoring::create_and_register_uint64_gauge(
+            &registry,
+            "quarantined_blocks",
+            "The number of blocks skipped since startup after their processing kept panicking.",
+        );
+        let query_cache_hits = PrometheusMonitoring::create_and_register_uint64_counter(
+            &registry,
+            "query_cache_hits_total",
+            "The number of API responses served from the query cache since startup.",
+        );
+        let query_cache_misses = PrometheusMonitoring::create_and_register_uint64_counter(
+            &registry,
+            "query_cache_misses_total",
+            "The number of cacheable API responses not found in the query cache since startup.",
+        );
+        let rejected_ingestion_payloads = PrometheusMonitoring::create_and_register_uint64_gauge(
+            &registry,
+            "rejected_ingestion_payloads",
+            "The number of Stacks node payloads rejected by the ingestion guard since startup.",
+        );
+        PIPELINE_METRICS.register(&registry);
         PrometheusMonitoring {
             last_indexed_block_height,
             last_indexed_inscription_number,
             registered_predicates,
+            quarantined_blocks,
+            query_cache_hits,
+            query_cache_misses,
+            rejected_ingestion_payloads,
             registry,
         }
     }
@@ -58,6 +219,16 @@ impl PrometheusMonitoring {
         g
     }
 
+    pub fn create_and_register_uint64_counter(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+    ) -> UInt64Counter {
+        let c = UInt64Counter::new(name, help).unwrap();
+        registry.register(Box::new(c.clone())).unwrap();
+        c
+    }
+
     pub fn initialize(
         &self,
         total_predicates: u64,
@@ -88,6 +259,21 @@ impl PrometheusMonitoring {
         }
     }
 
+    pub fn metrics_block_quarantined(&self) {
+        self.quarantined_blocks.inc();
+    }
+
+    pub fn metrics_query_cache_lookup(&self, hit: bool) {
+        match hit {
+            true => self.query_cache_hits.inc(),
+            false => self.query_cache_misses.inc(),
+        }
+    }
+
+    pub fn metrics_ingestion_payload_rejected(&self) {
+        self.rejected_ingestion_payloads.inc();
+    }
+
     pub fn metrics_block_indexed(&self, block_height: u64) {
         let highest_appended = self.last_indexed_block_height.get();
         if block_height > highest_appended {
@@ -139,8 +325,7 @@ async fn serve_req(
     }
 }
 
-pub async fn start_serving_prometheus_metrics(port: u16, registry: Registry, ctx: Context) {
-    let addr = ([0, 0, 0, 0], port).into();
+pub async fn start_serving_prometheus_metrics(addr: SocketAddr, registry: Registry, ctx: Context) {
     let ctx_clone = ctx.clone();
     let make_svc = make_service_fn(|_| {
         let registry = registry.clone();
@@ -152,7 +337,45 @@ pub async fn start_serving_prometheus_metrics(port: u16, registry: Registry, ctx
         }
     });
     let serve_future = Server::bind(&addr).serve(make_svc);
-    try_info!(ctx, "Prometheus monitoring: listening on port {}", port);
+    try_info!(ctx, "Prometheus monitoring: listening on {}", addr);
+    if let Err(err) = serve_future.await {
+        try_warn!(ctx, "Prometheus monitoring: server error: {}", err);
+    }
+}
+
+pub async fn start_serving_prometheus_metrics_over_unix_socket(
+    socket: UnixSocketConfig,
+    registry: Registry,
+    ctx: Context,
+) {
+    let listener = match bind_unix_socket(&socket) {
+        Ok(listener) => listener,
+        Err(e) => {
+            try_warn!(ctx, "Prometheus monitoring: {}", e);
+            return;
+        }
+    };
+    let ctx_clone = ctx.clone();
+    let make_svc = make_service_fn(|_| {
+        let registry = registry.clone();
+        let ctx_clone = ctx_clone.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |r| {
+                serve_req(r, registry.clone(), ctx_clone.clone())
+            }))
+        }
+    });
+    let incoming = accept::poll_fn(move |cx| match listener.poll_accept(cx) {
+        Poll::Ready(Ok((stream, _))) => Poll::Ready(Some(Ok(stream))),
+        Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+        Poll::Pending => Poll::Pending,
+    });
+    let serve_future = Server::builder(incoming).serve(make_svc);
+    try_info!(
+        ctx,
+        "Prometheus monitoring: listening on {}",
+        socket.path.display()
+    );
     if let Err(err) = serve_future.await {
         try_warn!(ctx, "Prometheus monitoring: server error: {}", err);
     }
@@ -160,7 +383,10 @@ pub async fn start_serving_prometheus_metrics(port: u16, registry: Registry, ctx
 
 #[cfg(test)]
 mod test {
-    use crate::utils::monitoring::PrometheusMonitoring;
+    use crate::{
+        core::test_builders::TestBlockBuilder,
+        utils::monitoring::{PipelineMetrics, PrometheusMonitoring},
+    };
 
     #[test]
     fn it_tracks_predicate_registration_deregistration_with_defaults() {
@@ -189,4 +415,28 @@ mod test {
         prometheus.metrics_inscription_indexed(5000);
         assert_eq!(prometheus.last_indexed_inscription_number.get(), 5000);
     }
+
+    #[test]
+    fn it_tracks_pipeline_queue_depths() {
+        let pipeline = PipelineMetrics::new();
+        pipeline.metrics_block_downloaded();
+        pipeline.metrics_block_downloaded();
+        pipeline.metrics_block_parsed();
+        assert_eq!(pipeline.downloaded_blocks_queue.get(), 1);
+        assert_eq!(pipeline.parsed_blocks_queue.get(), 1);
+        pipeline.metrics_blocks_sequenced(1);
+        pipeline.metrics_blocks_written(1);
+        let (tx, rx) = crossbeam_channel::unbounded();
+        pipeline.queue_block_for_delivery(TestBlockBuilder::new().build(), &tx);
+        assert_eq!(pipeline.pending_db_writes.get(), 0);
+        assert_eq!(pipeline.pending_deliveries.get(), 1);
+        assert_eq!(pipeline.blocks_written.get(), 1);
+        pipeline.metrics_block_delivered();
+        assert_eq!(pipeline.pending_deliveries.get(), 0);
+        assert_eq!(pipeline.blocks_delivered.get(), 1);
+        // Blocks sent once the evaluation thread is gone are not pending.
+        drop(rx);
+        pipeline.queue_block_for_delivery(TestBlockBuilder::new().build(), &tx);
+        assert_eq!(pipeline.pending_deliveries.get(), 0);
+    }
 }
@@ -0,0 +1,53 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodes the sort keys of the last row of a page into the opaque cursor of the next page. Pages are then selected by
+/// comparing sort keys instead of skipping rows, so rows indexed between two requests don't shift the following pages.
+pub fn encode_page_cursor<K: Serialize>(keys: &K) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(keys).unwrap_or_default())
+}
+
+pub fn decode_page_cursor<K: DeserializeOwned>(cursor: &str) -> Result<K, String> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or(format!("invalid cursor {cursor}"))
+}
+
+/// Splits the `limit + 1` rows fetched for a page into the rows of the page and the cursor of the next page, if any.
+pub fn paginate<R, K: Serialize>(
+    mut rows: Vec<R>,
+    limit: u64,
+    sort_keys: impl Fn(&R) -> K,
+) -> (Vec<R>, Option<String>) {
+    if rows.len() as u64 <= limit {
+        return (rows, None);
+    }
+    rows.truncate(limit as usize);
+    let next_cursor = rows.last().map(|row| encode_page_cursor(&sort_keys(row)));
+    (rows, next_cursor)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_page_cursor, encode_page_cursor, paginate};
+
+    #[test]
+    fn paginates_with_opaque_cursors() {
+        let cursor = encode_page_cursor(&(840000u64, "abci0".to_string()));
+        assert_eq!(
+            decode_page_cursor::<(u64, String)>(&cursor),
+            Ok((840000, "abci0".to_string()))
+        );
+        assert!(decode_page_cursor::<(u64, String)>("not-a-cursor").is_err());
+        assert!(decode_page_cursor::<u64>(&cursor).is_err());
+
+        let (rows, next_cursor) = paginate(vec![1u64, 2, 3], 2, |row| *row);
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(decode_page_cursor::<u64>(&next_cursor.unwrap()), Ok(2));
+        let (rows, next_cursor) = paginate(vec![3u64], 2, |row| *row);
+        assert_eq!(rows, vec![3]);
+        assert_eq!(next_cursor, None);
+    }
+}
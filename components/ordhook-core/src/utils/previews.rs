@@ -0,0 +1,252 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use chainhook_sdk::{
+    types::{BitcoinBlockData, OrdinalOperation},
+    utils::Context,
+};
+use rusqlite::Connection;
+
+use crate::{
+    config::{content_type_matches, Config, PreviewsConfig},
+    core::protocol::{
+        inscription_content::fetch_inscription_content,
+        inscription_parsing::get_stripped_content_hash,
+    },
+    db::{
+        blocks::{decrypt_stored_file_bytes, encrypt_stored_file_bytes},
+        ordinals::open_ordinals_db_rw,
+    },
+    try_info, try_warn,
+    utils::get_unique_tmp_path,
+};
+
+/// Content types previews can be rendered for.
+const PREVIEWABLE_CONTENT_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+/// Preview jobs waiting for the worker. Images revealed while it is full are left to be rendered on demand.
+const PREVIEWS_QUEUE_SIZE: usize = 256;
+/// Bounds on the dimensions of the images decoded, protecting the worker from decompression bombs.
+#[cfg(feature = "previews")]
+const PREVIEW_MAX_SOURCE_DIMENSION: u32 = 8192;
+
+/// Image inscription revealed in a block, with its content if it was stored.
+pub struct PreviewJob {
+    pub inscription_id: String,
+    pub content: Option<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref PREVIEWS_JOB_TX: Mutex<Option<crossbeam_channel::Sender<PreviewJob>>> =
+        Mutex::new(None);
+}
+
+pub fn is_previewable_content_type(content_type: &str) -> bool {
+    PREVIEWABLE_CONTENT_TYPES
+        .iter()
+        .any(|pattern| content_type_matches(pattern, content_type))
+}
+
+/// Previews are stored as PNG files under `<content_dir>/previews/<size>/`, encrypted like blocks when a storage
+/// encryption key is set.
+pub fn get_preview_path(config: &Config, inscription_id: &str, size: u32) -> PathBuf {
+    let mut path = config.expected_content_path();
+    path.push("previews");
+    path.push(format!("{size}"));
+    path.push(format!("{inscription_id}.png"));
+    path
+}
+
+/// Renders a PNG preview fitting in a `size` x `size` box, preserving the aspect ratio.
+pub fn render_preview(content: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    #[cfg(feature = "previews")]
+    {
+        let mut reader = image::io::Reader::new(std::io::Cursor::new(content))
+            .with_guessed_format()
+            .map_err(|e| format!("unable to read image: {e}"))?;
+        let mut limits = image::io::Limits::default();
+        limits.max_image_width = Some(PREVIEW_MAX_SOURCE_DIMENSION);
+        limits.max_image_height = Some(PREVIEW_MAX_SOURCE_DIMENSION);
+        reader.limits(limits);
+        let image = reader
+            .decode()
+            .map_err(|e| format!("unable to decode image: {e}"))?;
+        let mut preview = std::io::Cursor::new(vec![]);
+        image
+            .thumbnail(size, size)
+            .write_to(&mut preview, image::ImageOutputFormat::Png)
+            .map_err(|e| format!("unable to encode preview: {e}"))?;
+        Ok(preview.into_inner())
+    }
+    #[cfg(not(feature = "previews"))]
+    {
+        let _ = (content, size);
+        Err("previews require ordhook to be built with the previews feature".into())
+    }
+}
+
+fn write_previews(
+    inscription_id: &str,
+    content: &[u8],
+    previews_config: &PreviewsConfig,
+    config: &Config,
+) -> Result<(), String> {
+    if content.len() as u64 > previews_config.max_content_bytes {
+        return Err(format!(
+            "content exceeds {} bytes",
+            previews_config.max_content_bytes
+        ));
+    }
+    for size in previews_config.sizes.iter() {
+        let path = get_preview_path(config, inscription_id, *size);
+        if path.exists() {
+            continue;
+        }
+        let preview = encrypt_stored_file_bytes(render_preview(content, *size)?)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("unable to create {dir:?}: {e}"))?;
+        }
+        // Readers of the previews directory never see a partially written preview.
+        let tmp_path = get_unique_tmp_path(&path);
+        if let Err(e) =
+            std::fs::write(&tmp_path, preview).and_then(|_| std::fs::rename(&tmp_path, &path))
+        {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(format!("unable to write {path:?}: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// Starts the background worker rendering the previews of the image inscriptions indexed, if enabled.
+pub fn start_previews_worker(config: &Config, ctx: &Context) -> Result<(), String> {
+    let Some(ref previews_config) = config.previews else {
+        return Ok(());
+    };
+    if cfg!(not(feature = "previews")) {
+        return Err("previews require ordhook to be built with the previews feature".into());
+    }
+    let Ok(mut job_tx) = PREVIEWS_JOB_TX.lock() else {
+        return Ok(());
+    };
+    if job_tx.is_some() {
+        return Ok(());
+    }
+    let db_conn = open_ordinals_db_rw(&config.expected_sqlite_path(), ctx)?;
+    let (tx, rx) = crossbeam_channel::bounded::<PreviewJob>(PREVIEWS_QUEUE_SIZE);
+    let moved_previews_config = previews_config.clone();
+    let moved_config = config.clone();
+    let moved_ctx = ctx.clone();
+    hiro_system_kit::thread_named("Previews worker")
+        .spawn(move || {
+            while let Ok(job) = rx.recv() {
+                let content = match job.content {
+                    Some(content) => Ok(content),
+                    None => fetch_inscription_content(
+                        &job.inscription_id,
+                        &db_conn,
+                        &moved_config,
+                        &moved_ctx,
+                    )
+                    .map(|content| content.body.to_vec()),
+                };
+                if let Err(e) = content.and_then(|content| {
+                    write_previews(
+                        &job.inscription_id,
+                        &content,
+                        &moved_previews_config,
+                        &moved_config,
+                    )
+                }) {
+                    try_warn!(
+                        moved_ctx,
+                        "Unable to render preview of {}: {e}",
+                        job.inscription_id
+                    );
+                }
+            }
+        })
+        .map_err(|e| format!("unable to start previews worker: {e}"))?;
+    *job_tx = Some(tx);
+    try_info!(ctx, "Previews worker started");
+    Ok(())
+}
+
+/// Queues the image inscriptions revealed in a block for preview rendering. Inscriptions which content was not kept
+/// get their content read from bitcoind by the worker. The ones revealed while the worker is behind get rendered on
+/// demand instead.
+pub fn enqueue_block_previews(block: &BitcoinBlockData) {
+    let Ok(job_tx) = PREVIEWS_JOB_TX.lock() else {
+        return;
+    };
+    let Some(ref job_tx) = *job_tx else {
+        return;
+    };
+    for tx in block.transactions.iter() {
+        for operation in tx.metadata.ordinal_operations.iter() {
+            let OrdinalOperation::InscriptionRevealed(reveal) = operation else {
+                continue;
+            };
+            if !is_previewable_content_type(&reveal.content_type) {
+                continue;
+            }
+            // Contents not stored are read by the worker.
+            let content = match get_stripped_content_hash(&reveal.content_bytes) {
+                Some(_) => None,
+                None => reveal
+                    .content_bytes
+                    .strip_prefix("0x")
+                    .and_then(|hex_content| hex::decode(hex_content).ok()),
+            };
+            let _ = job_tx.try_send(PreviewJob {
+                inscription_id: reveal.inscription_id.clone(),
+                content,
+            });
+        }
+    }
+}
+
+/// Returns the preview of an inscription, rendering it from its content read from bitcoind if it is not available yet.
+pub fn get_or_render_preview(
+    inscription_id: &str,
+    size: u32,
+    db_conn: &Connection,
+    config: &Config,
+    ctx: &Context,
+) -> Result<Vec<u8>, String> {
+    let Some(ref previews_config) = config.previews else {
+        return Err("previews are not enabled".into());
+    };
+    let path = get_preview_path(config, inscription_id, size);
+    if let Ok(preview) = std::fs::read(&path) {
+        match decrypt_stored_file_bytes(preview) {
+            Ok(preview) => return Ok(preview),
+            // Rendered again when the storage encryption key changed.
+            Err(_) => {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    let content = fetch_inscription_content(inscription_id, db_conn, config, ctx)?;
+    if !is_previewable_content_type(&content.content_type) {
+        return Err(format!(
+            "{} content can not be previewed",
+            content.content_type
+        ));
+    }
+    write_previews(inscription_id, &content.body, previews_config, config)?;
+    std::fs::read(&path)
+        .map_err(|e| format!("unable to read {path:?}: {e}"))
+        .and_then(decrypt_stored_file_bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_previewable_content_type;
+
+    #[test]
+    fn detects_previewable_content_types() {
+        assert!(is_previewable_content_type("image/png"));
+        assert!(is_previewable_content_type("image/jpeg;charset=binary"));
+        assert!(!is_previewable_content_type("image/svg+xml"));
+        assert!(!is_previewable_content_type("text/html"));
+    }
+}
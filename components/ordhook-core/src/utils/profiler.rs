@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chainhook_sdk::utils::Context;
+use serde_json::json;
+
+use crate::{config::Config, try_error, try_warn};
+
+/// File, inside the configured profiles directory, that slow block profiles get appended to.
+const SLOW_BLOCKS_PROFILE_FILE_NAME: &str = "slow_blocks.folded";
+
+lazy_static! {
+    /// Download durations of blocks fetched by the pipeline, waiting to be picked up by the processor indexing them.
+    static ref PENDING_FETCH_DURATIONS: Mutex<HashMap<u64, Duration>> = Mutex::new(HashMap::new());
+}
+
+pub fn is_block_profiling_enabled(config: &Config) -> bool {
+    config.logs.slow_block_threshold_ms.is_some()
+}
+
+/// Records the time it took to fetch a block from bitcoind, so it can be included in the block's profile.
+pub fn record_block_fetch_duration(block_height: u64, duration: Duration, config: &Config) {
+    if !is_block_profiling_enabled(config) {
+        return;
+    }
+    if let Ok(mut pending) = PENDING_FETCH_DURATIONS.lock() {
+        pending.insert(block_height, duration);
+    }
+}
+
+/// Forgets, when dropped, the fetch durations recorded for a range of blocks that were not picked up by a processor:
+/// blocks which download, parsing or indexing failed, or that were left behind by an interrupted pipeline.
+pub struct PendingFetchDurationsGuard {
+    start_block: u64,
+    end_block: u64,
+}
+
+impl PendingFetchDurationsGuard {
+    pub fn new(start_block: u64, end_block: u64) -> PendingFetchDurationsGuard {
+        PendingFetchDurationsGuard {
+            start_block,
+            end_block,
+        }
+    }
+}
+
+impl Drop for PendingFetchDurationsGuard {
+    fn drop(&mut self) {
+        let mut pending = match PENDING_FETCH_DURATIONS.lock() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        pending.retain(|block_height, _| {
+            *block_height < self.start_block || *block_height > self.end_block
+        });
+    }
+}
+
+/// Measures the time spent in each stage of a block's processing. Stages are delimited by calls to `mark`, each
+/// stage lasting from the previous mark (or the profiler creation) to the current one.
+pub struct BlockProfiler {
+    block_height: u64,
+    enabled: bool,
+    last_mark: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl BlockProfiler {
+    pub fn new(block_height: u64, config: &Config) -> BlockProfiler {
+        let enabled = is_block_profiling_enabled(config);
+        let mut stages = vec![];
+        if enabled {
+            if let Some(fetch) = PENDING_FETCH_DURATIONS
+                .lock()
+                .ok()
+                .and_then(|mut pending| pending.remove(&block_height))
+            {
+                stages.push(("fetch", fetch));
+            }
+        }
+        BlockProfiler {
+            block_height,
+            enabled,
+            last_mark: Instant::now(),
+            stages,
+        }
+    }
+
+    /// Closes the current stage.
+    pub fn mark(&mut self, stage: &'static str) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        self.stages
+            .push((stage, now.duration_since(self.last_mark)));
+        self.last_mark = now;
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, d)| *d).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut stages = serde_json::Map::new();
+        for (stage, duration) in self.stages.iter() {
+            stages.insert(stage.to_string(), json!(duration.as_millis()));
+        }
+        json!({
+            "block_height": self.block_height,
+            "total_ms": self.total().as_millis(),
+            "stages_ms": stages,
+        })
+    }
+
+    /// Stages formatted as collapsed stacks (`ordhook;block_<height>;<stage> <microseconds>`), the input format of
+    /// `flamegraph.pl` and `inferno-flamegraph`.
+    pub fn to_folded_stacks(&self) -> String {
+        let mut output = String::new();
+        for (stage, duration) in self.stages.iter() {
+            output.push_str(&format!(
+                "ordhook;block_{};{} {}\n",
+                self.block_height,
+                stage,
+                duration.as_micros()
+            ));
+        }
+        output
+    }
+
+    /// Emits the block's stage breakdown if its processing took longer than the configured threshold.
+    pub fn report(&self, config: &Config, ctx: &Context) {
+        let Some(threshold_ms) = config.logs.slow_block_threshold_ms else {
+            return;
+        };
+        if self.total() < Duration::from_millis(threshold_ms) {
+            return;
+        }
+        try_warn!(ctx, "Slow block #{}: {}", self.block_height, self.to_json());
+        if let Some(ref profiles_dir) = config.logs.slow_block_profiles_dir {
+            if let Err(e) = self.append_folded_stacks(profiles_dir) {
+                try_error!(ctx, "Unable to write slow block profile: {e}");
+            }
+        }
+    }
+
+    fn append_folded_stacks(&self, profiles_dir: &str) -> Result<(), String> {
+        let mut file_path = PathBuf::from(profiles_dir);
+        std::fs::create_dir_all(&file_path)
+            .map_err(|e| format!("unable to create {}: {}", file_path.display(), e))?;
+        file_path.push(SLOW_BLOCKS_PROFILE_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file_path)
+            .map_err(|e| format!("unable to open {}: {}", file_path.display(), e))?;
+        file.write_all(self.to_folded_stacks().as_bytes())
+            .map_err(|e| format!("unable to write {}: {}", file_path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::config::Config;
+
+    use super::{
+        record_block_fetch_duration, BlockProfiler, PendingFetchDurationsGuard,
+        PENDING_FETCH_DURATIONS,
+    };
+
+    #[test]
+    fn profiles_stages_when_enabled() {
+        let mut config = Config::test_default();
+        let mut profiler = BlockProfiler::new(800000, &config);
+        profiler.mark("parse");
+        assert_eq!(profiler.to_folded_stacks(), "");
+
+        config.logs.slow_block_threshold_ms = Some(0);
+        record_block_fetch_duration(800001, Duration::from_millis(12), &config);
+        let mut profiler = BlockProfiler::new(800001, &config);
+        profiler.mark("parse");
+        profiler.mark("number");
+        let folded = profiler.to_folded_stacks();
+        let lines: Vec<&str> = folded.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "ordhook;block_800001;fetch 12000");
+        assert!(lines[1].starts_with("ordhook;block_800001;parse "));
+        assert!(lines[2].starts_with("ordhook;block_800001;number "));
+        assert!(profiler.total() >= Duration::from_millis(12));
+        assert_eq!(profiler.to_json()["stages_ms"]["fetch"], 12);
+    }
+
+    #[test]
+    fn forgets_fetch_durations_of_blocks_left_behind() {
+        let mut config = Config::test_default();
+        config.logs.slow_block_threshold_ms = Some(0);
+        {
+            let _guard = PendingFetchDurationsGuard::new(900000, 900001);
+            record_block_fetch_duration(900000, Duration::from_millis(5), &config);
+            record_block_fetch_duration(900001, Duration::from_millis(5), &config);
+            record_block_fetch_duration(900002, Duration::from_millis(5), &config);
+            let _ = BlockProfiler::new(900000, &config);
+        }
+        let pending = PENDING_FETCH_DURATIONS.lock().unwrap();
+        assert!(!pending.contains_key(&900000));
+        assert!(!pending.contains_key(&900001));
+        assert!(pending.contains_key(&900002));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    net::SocketAddr,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+};
+
+use chainhook_sdk::utils::Context;
+use tokio::net::{TcpStream, UnixListener};
+
+use crate::{config::UnixSocketConfig, try_info, try_warn, utils::get_unique_tmp_path};
+
+/// Binds a unix domain socket, replacing any stale socket left behind by a previous run. Other files found at the path
+/// are left untouched and fail the bind. The socket is bound in a private 0700 directory, given the configured
+/// permissions and only then renamed into place, so that it is never reachable with broader ones. The umask of the
+/// process, shared with every other thread creating files, is left alone. Must be called from within a tokio runtime.
+pub fn bind_unix_socket(config: &UnixSocketConfig) -> Result<UnixListener, String> {
+    match std::fs::symlink_metadata(&config.path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            std::fs::remove_file(&config.path).map_err(|e| {
+                format!(
+                    "unable to remove stale socket {}: {}",
+                    config.path.display(),
+                    e
+                )
+            })?;
+        }
+        Ok(_) => {
+            return Err(format!(
+                "unable to bind {}: file exists and is not a socket",
+                config.path.display()
+            ))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("unable to read {}: {}", config.path.display(), e)),
+    }
+    if let Some(parent) = config.path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("unable to create {}: {}", parent.display(), e))?;
+    }
+    let private_dir = get_unique_tmp_path(&config.path);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|e| format!("unable to create {}: {}", private_dir.display(), e))?;
+    let result = bind_in_private_dir(config, &private_dir);
+    let _ = std::fs::remove_dir_all(&private_dir);
+    result
+}
+
+fn bind_in_private_dir(
+    config: &UnixSocketConfig,
+    private_dir: &std::path::Path,
+) -> Result<UnixListener, String> {
+    let tmp_path = private_dir.join("s");
+    let listener = UnixListener::bind(&tmp_path)
+        .map_err(|e| format!("unable to bind {}: {}", config.path.display(), e))?;
+    std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(config.mode)).map_err(
+        |e| {
+            format!(
+                "unable to set permissions of {}: {}",
+                config.path.display(),
+                e
+            )
+        },
+    )?;
+    std::fs::rename(&tmp_path, &config.path)
+        .map_err(|e| format!("unable to bind {}: {}", config.path.display(), e))?;
+    Ok(listener)
+}
+
+/// Forwards every connection accepted on a unix domain socket to a TCP listener. Used for exposing servers that are
+/// not able to listen on a unix socket natively.
+pub async fn forward_unix_socket_to_tcp(
+    config: UnixSocketConfig,
+    target: SocketAddr,
+    ctx: Context,
+) {
+    let listener = match bind_unix_socket(&config) {
+        Ok(listener) => listener,
+        Err(e) => {
+            try_warn!(ctx, "{e}");
+            return;
+        }
+    };
+    try_info!(
+        ctx,
+        "Listening on {} (forwarding to {})",
+        config.path.display(),
+        target
+    );
+    loop {
+        let (mut inbound, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                try_warn!(ctx, "unable to accept unix socket connection: {e}");
+                continue;
+            }
+        };
+        let moved_ctx = ctx.clone();
+        tokio::spawn(async move {
+            match TcpStream::connect(target).await {
+                Ok(mut outbound) => {
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                }
+                Err(e) => {
+                    try_warn!(moved_ctx, "unable to forward unix socket connection: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::{config::UnixSocketConfig, utils::get_unique_tmp_path};
+
+    use super::bind_unix_socket;
+
+    #[tokio::test]
+    async fn binds_with_configured_mode_and_keeps_other_files() {
+        let dir = get_unique_tmp_path(&std::env::temp_dir().join("ordhook_unix_socket_test"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = UnixSocketConfig {
+            path: dir.join("ordhook.sock"),
+            mode: 0o600,
+        };
+        let listener = bind_unix_socket(&config).unwrap();
+        let metadata = std::fs::metadata(&config.path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        // The private directory the socket was bound in is gone.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        drop(listener);
+        // Stale sockets get replaced.
+        assert!(bind_unix_socket(&config).is_ok());
+
+        let config = UnixSocketConfig {
+            path: dir.join("ordhook.sqlite"),
+            mode: 0o600,
+        };
+        std::fs::write(&config.path, b"data").unwrap();
+        assert!(bind_unix_socket(&config).is_err());
+        assert_eq!(std::fs::read(&config.path).unwrap(), b"data");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
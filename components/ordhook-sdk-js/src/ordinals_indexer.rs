@@ -10,8 +10,9 @@ use ordhook::chainhook_sdk::chainhooks::types::{
   HookAction, InscriptionFeedData, OrdinalOperations,
 };
 use ordhook::chainhook_sdk::observer::DataHandlerEvent;
+use ordhook::chainhook_sdk::types::{BitcoinBlockSignaling, StacksNodeConfig};
 use ordhook::chainhook_sdk::utils::{BlockHeights, Context as OrdhookContext};
-use ordhook::config::Config;
+use ordhook::config::{Config, DEFAULT_INGESTION_PORT};
 use ordhook::scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate;
 use ordhook::service::Service;
 use std::collections::BTreeMap;
@@ -271,6 +272,9 @@ impl OrdinalsIndexer {
   #[napi(constructor)]
   pub fn new(config_overrides: Option<OrdinalsIndexerConfig>) -> Self {
     let mut config = Config::mainnet_default();
+    config.network.bitcoin_block_signaling = Some(BitcoinBlockSignaling::Stacks(
+      StacksNodeConfig::default_localhost(DEFAULT_INGESTION_PORT),
+    ));
 
     if let Some(config_overrides) = config_overrides {
       if let Some(bitcoin_rpc_url) = config_overrides.bitcoin_rpc_url {